@@ -0,0 +1,86 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use datavalue_rs::{bench_corpus, from_str, to_string, Bump, DataValue};
+
+// Benchmark: parsing each document into a fresh arena
+fn bench_parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("CorpusParse");
+
+    for (name, text) in bench_corpus::documents() {
+        group.throughput(Throughput::Bytes(text.len() as u64));
+        group.bench_function(BenchmarkId::from_parameter(name), |b| {
+            let mut arena = Bump::new();
+            b.iter(|| {
+                black_box(from_str(&arena, black_box(&text)).unwrap());
+                arena.reset();
+            })
+        });
+    }
+
+    group.finish();
+}
+
+// Benchmark: reading the documented pointers from each parsed document
+fn bench_access(c: &mut Criterion) {
+    let mut group = c.benchmark_group("CorpusAccess");
+
+    let arena = Bump::new();
+    for (name, text) in bench_corpus::documents() {
+        let value = from_str(&arena, &text).unwrap();
+        let pointers = bench_corpus::pointers(name);
+        group.throughput(Throughput::Elements(pointers.len() as u64));
+        group.bench_function(BenchmarkId::from_parameter(name), |b| {
+            b.iter(|| {
+                for pointer in pointers {
+                    black_box(value.pointer(black_box(pointer)).unwrap());
+                }
+            })
+        });
+    }
+
+    group.finish();
+}
+
+// Benchmark: serializing each parsed document back to compact JSON
+fn bench_serialize(c: &mut Criterion) {
+    let mut group = c.benchmark_group("CorpusSerialize");
+
+    let arena = Bump::new();
+    for (name, text) in bench_corpus::documents() {
+        let value = from_str(&arena, &text).unwrap();
+        group.throughput(Throughput::Bytes(text.len() as u64));
+        group.bench_function(BenchmarkId::from_parameter(name), |b| {
+            b.iter(|| black_box(to_string(black_box(&value))))
+        });
+    }
+
+    group.finish();
+}
+
+// Benchmark: loading each document from a binary snapshot into a fresh arena
+fn bench_load_snapshot(c: &mut Criterion) {
+    let mut group = c.benchmark_group("CorpusLoadSnapshot");
+
+    let arena = Bump::new();
+    for (name, text) in bench_corpus::documents() {
+        let snapshot = from_str(&arena, &text).unwrap().to_binary();
+        group.throughput(Throughput::Bytes(text.len() as u64));
+        group.bench_function(BenchmarkId::from_parameter(name), |b| {
+            let mut cache = Bump::new();
+            b.iter(|| {
+                black_box(DataValue::from_binary(&cache, black_box(&snapshot)).unwrap());
+                cache.reset();
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_parse,
+    bench_access,
+    bench_serialize,
+    bench_load_snapshot
+);
+criterion_main!(benches);
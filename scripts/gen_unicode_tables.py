@@ -0,0 +1,63 @@
+#!/usr/bin/env python3
+"""Generates src/unicode/tables.rs from Python's unicodedata module.
+
+Usage: python3 scripts/gen_unicode_tables.py > src/unicode/tables.rs
+
+The tables hold full (recursive) decompositions, canonical combining classes
+and primary composites, which is everything NFC and NFKC need apart from the
+algorithmic Hangul syllables.
+"""
+
+import sys
+import unicodedata
+
+HANGUL = range(0xAC00, 0xAC00 + 11172)
+
+
+def chars():
+    for cp in range(0x110000):
+        if 0xD800 <= cp < 0xE000 or cp in HANGUL:
+            continue
+        yield cp, chr(cp)
+
+
+def fmt_seq(s):
+    return "&[" + ", ".join("0x%X" % ord(c) for c in s) + "]"
+
+
+def emit(name, ty, entries, per_line):
+    print()
+    print("#[rustfmt::skip]")
+    print("pub(super) static %s: &[%s] = &[" % (name, ty))
+    for i in range(0, len(entries), per_line):
+        print("    " + " ".join(e + "," for e in entries[i:i + per_line]))
+    print("];")
+
+
+canonical, compat, ccc, compose = [], [], [], []
+for cp, ch in chars():
+    nfd = unicodedata.normalize("NFD", ch)
+    nfkd = unicodedata.normalize("NFKD", ch)
+    if nfd != ch:
+        canonical.append("(0x%X, %s)" % (cp, fmt_seq(nfd)))
+    if nfkd != nfd:
+        compat.append("(0x%X, %s)" % (cp, fmt_seq(nfkd)))
+    cc = unicodedata.combining(ch)
+    if cc:
+        ccc.append("(0x%X, %d)" % (cp, cc))
+    decomposition = unicodedata.decomposition(ch)
+    if decomposition and not decomposition.startswith("<"):
+        parts = [int(p, 16) for p in decomposition.split()]
+        # Primary composites: pairs that recompose under NFC
+        if len(parts) == 2 and unicodedata.normalize("NFC", chr(parts[0]) + chr(parts[1])) == ch:
+            compose.append((parts[0], parts[1], cp))
+
+compose.sort()
+
+print("// Generated by scripts/gen_unicode_tables.py from Unicode %s; do not edit."
+      % unicodedata.unidata_version)
+emit("CANONICAL_DECOMPOSITION", "(u32, &[u32])", canonical, 3)
+emit("COMPATIBILITY_DECOMPOSITION", "(u32, &[u32])", compat, 3)
+emit("COMBINING_CLASS", "(u32, u8)", ccc, 6)
+emit("COMPOSITION", "(u32, u32, u32)", ["(0x%X, 0x%X, 0x%X)" % c for c in compose], 4)
+sys.stdout.flush()
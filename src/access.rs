@@ -3,8 +3,28 @@
 //! This module provides methods to check the type of a DataValue and to access
 //! values using JSON Pointer syntax, allowing targeted access to nested values.
 
+use std::borrow::Cow;
+
 use crate::datavalue::DataValue;
 
+/// Escapes a key for use as a JSON pointer reference token (`~` as `~0`, `/` as `~1`)
+pub(crate) fn escape_pointer_token(token: &str) -> Cow<'_, str> {
+    if token.contains(['~', '/']) {
+        Cow::Owned(token.replace('~', "~0").replace('/', "~1"))
+    } else {
+        Cow::Borrowed(token)
+    }
+}
+
+/// Unescapes a JSON pointer reference token (`~1` as `/`, `~0` as `~`)
+pub(crate) fn unescape_pointer_token(token: &str) -> Cow<'_, str> {
+    if token.contains('~') {
+        Cow::Owned(token.replace("~1", "/").replace("~0", "~"))
+    } else {
+        Cow::Borrowed(token)
+    }
+}
+
 impl DataValue<'_> {
     /// Returns true if the value is null.
     ///
@@ -213,7 +233,7 @@ impl DataValue<'_> {
 
         let mut current = self;
         for reference_token in pointer.split('/').skip(1) {
-            let token = reference_token.replace("~1", "/").replace("~0", "~");
+            let token = unescape_pointer_token(reference_token);
 
             current = match current {
                 DataValue::Object(obj) => obj.iter().find(|(k, _)| k == &token).map(|(_, v)| v)?,
@@ -0,0 +1,532 @@
+//! Zero-copy archives of DataValue trees, enabled by the `rkyv` feature
+//!
+//! [`to_bytes`] writes a value as an [rkyv](https://rkyv.org) archive, and
+//! [`access`] validates an archive and returns an [`ArchivedValue`] that reads
+//! it in place: nothing is parsed or copied, so a cache can map or load the
+//! bytes and start answering lookups straight away. [`ArchivedValue::to_value`]
+//! turns part or all of an archive into a regular DataValue when one is needed,
+//! with strings borrowed from the archive.
+//!
+//! The archive holds the tree as a flat table of nodes in breadth-first order,
+//! where the children of an array or object are a contiguous run of later
+//! nodes. Raw numbers, raw values and arbitrary-precision numbers are stored as
+//! their text, so every value comes back unchanged.
+//!
+//! rkyv archives must be read from memory aligned for the archived types. The
+//! buffer returned by [`to_bytes`] is; when loading an archive from a file or
+//! the network, copy it into an [`AlignedVec`] first.
+//!
+//! # Example
+//!
+//! ```
+//! use datavalue_rs::{archive, from_str, Bump};
+//!
+//! let arena = Bump::new();
+//! let config = from_str(&arena, r#"{"routes": [{"path": "/", "ttl": 60}]}"#).unwrap();
+//! let bytes = archive::to_bytes(&config).unwrap();
+//!
+//! let root = archive::access(&bytes).unwrap();
+//! assert_eq!(root.pointer("/routes/0/ttl").and_then(|t| t.as_i64()), Some(60));
+//! assert_eq!(root.to_value(&arena), config);
+//! ```
+
+use bumpalo::Bump;
+use chrono::{DateTime, Duration};
+use rkyv::rancor;
+pub use rkyv::util::AlignedVec;
+use rkyv::{Archive, Serialize};
+
+use crate::access::unescape_pointer_token;
+use crate::datavalue::{DataValue, Number};
+use crate::error::{Error, Result};
+
+/// A tree as a table of nodes, with the keys of object entries alongside
+#[derive(Archive, Serialize)]
+struct Tree {
+    nodes: Vec<Node>,
+    keys: Vec<String>,
+}
+
+/// A value whose children, if any, are the nodes from `start`
+#[derive(Archive, Serialize)]
+enum Node {
+    Null,
+    Bool(bool),
+    Integer(i64),
+    Float(f64),
+    RawNumber(String),
+    // Declared without the `bignum` feature too, so archives have the same
+    // layout and can still be read
+    #[cfg_attr(not(feature = "bignum"), allow(dead_code))]
+    BigInt(String),
+    #[cfg_attr(not(feature = "bignum"), allow(dead_code))]
+    BigDecimal(String),
+    String(String),
+    Array {
+        start: u32,
+        len: u32,
+    },
+    /// Entry values are the nodes from `start` and their keys the keys from `keys`
+    Object {
+        start: u32,
+        len: u32,
+        keys: u32,
+    },
+    DateTime {
+        secs: i64,
+        nanos: u32,
+    },
+    Duration {
+        secs: i64,
+        nanos: i32,
+    },
+    Raw(String),
+}
+
+/// Archives a value
+///
+/// # Errors
+///
+/// Returns an error if the value has more than `u32::MAX` nodes.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::{archive, from_str, Bump};
+///
+/// let arena = Bump::new();
+/// let value = from_str(&arena, r#"["a", 1.5]"#).unwrap();
+/// let bytes = archive::to_bytes(&value).unwrap();
+/// assert_eq!(archive::access(&bytes).unwrap().get_index(0).unwrap().as_str(), Some("a"));
+/// ```
+pub fn to_bytes(value: &DataValue<'_>) -> Result<AlignedVec> {
+    let too_large = || Error::custom("Value has too many nodes to archive");
+    let mut tree = Tree {
+        nodes: Vec::new(),
+        keys: Vec::new(),
+    };
+    // Values in the order their nodes are written; containers append their
+    // children, so every node's children end up contiguous
+    let mut values = vec![value];
+    let mut next = 0;
+    while let Some(&value) = values.get(next) {
+        next += 1;
+        let start = u32::try_from(values.len()).map_err(|_| too_large())?;
+        let node = match value {
+            DataValue::Null => Node::Null,
+            DataValue::Bool(b) => Node::Bool(*b),
+            DataValue::Number(Number::Integer(i)) => Node::Integer(*i),
+            DataValue::Number(Number::Float(f)) => Node::Float(*f),
+            DataValue::Number(Number::Raw(text)) => Node::RawNumber(text.to_string()),
+            #[cfg(feature = "bignum")]
+            DataValue::Number(Number::BigInt(n)) => Node::BigInt(n.to_string()),
+            #[cfg(feature = "bignum")]
+            DataValue::Number(Number::BigDecimal(n)) => Node::BigDecimal(n.to_string()),
+            DataValue::String(s) => Node::String(s.to_string()),
+            DataValue::Array(items) => {
+                values.extend(items.iter());
+                Node::Array {
+                    start,
+                    len: u32::try_from(items.len()).map_err(|_| too_large())?,
+                }
+            }
+            DataValue::Object(entries) => {
+                let keys = u32::try_from(tree.keys.len()).map_err(|_| too_large())?;
+                for (key, value) in entries.iter() {
+                    tree.keys.push(key.to_string());
+                    values.push(value);
+                }
+                Node::Object {
+                    start,
+                    len: u32::try_from(entries.len()).map_err(|_| too_large())?,
+                    keys,
+                }
+            }
+            DataValue::DateTime(dt) => Node::DateTime {
+                secs: dt.timestamp(),
+                nanos: dt.timestamp_subsec_nanos(),
+            },
+            DataValue::Duration(dur) => Node::Duration {
+                secs: dur.num_seconds(),
+                nanos: dur.subsec_nanos(),
+            },
+            DataValue::Raw(text) => Node::Raw(text.to_string()),
+        };
+        tree.nodes.push(node);
+    }
+    u32::try_from(values.len()).map_err(|_| too_large())?;
+    rkyv::to_bytes::<rancor::Error>(&tree).map_err(|e| Error::custom(e.to_string()))
+}
+
+/// Validates an archive written by [`to_bytes`] and returns its root value
+///
+/// Validation checks every node once, so its cost grows with the size of the
+/// archive, but nothing is allocated or copied.
+///
+/// # Errors
+///
+/// Returns an error if `bytes` is not such an archive, is not aligned, or
+/// holds a node table that is not a tree.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::archive::{self, AlignedVec};
+/// use datavalue_rs::{from_str, Bump};
+///
+/// let arena = Bump::new();
+/// let value = from_str(&arena, r#"{"enabled": true}"#).unwrap();
+/// let stored: Vec<u8> = archive::to_bytes(&value).unwrap().to_vec();
+///
+/// // Bytes loaded from elsewhere are copied to an aligned buffer
+/// let mut bytes = AlignedVec::<16>::new();
+/// bytes.extend_from_slice(&stored);
+/// let root = archive::access(&bytes).unwrap();
+/// assert_eq!(root.get("enabled").and_then(|e| e.as_bool()), Some(true));
+///
+/// assert!(archive::access(b"not an archive").is_err());
+/// ```
+pub fn access(bytes: &[u8]) -> Result<ArchivedValue<'_>> {
+    let tree = rkyv::access::<ArchivedTree, rancor::Error>(bytes)
+        .map_err(|e| Error::custom(format!("Invalid archive: {}", e)))?;
+
+    // Each container's children must directly follow those of the containers
+    // before it, which makes the table a tree in breadth-first order
+    let invalid = |index| Error::custom(format!("Invalid archive: malformed node {}", index));
+    let (mut next, mut next_key) = (1usize, 0usize);
+    for (index, node) in tree.nodes.iter().enumerate() {
+        match node {
+            ArchivedNode::Array { start, len } => {
+                if start.to_native() as usize != next || next <= index {
+                    return Err(invalid(index));
+                }
+                next += len.to_native() as usize;
+            }
+            ArchivedNode::Object { start, len, keys } => {
+                if start.to_native() as usize != next
+                    || next <= index
+                    || keys.to_native() as usize != next_key
+                {
+                    return Err(invalid(index));
+                }
+                next += len.to_native() as usize;
+                next_key += len.to_native() as usize;
+            }
+            ArchivedNode::DateTime { secs, nanos } => {
+                DateTime::from_timestamp(secs.to_native(), nanos.to_native())
+                    .ok_or_else(|| invalid(index))?;
+            }
+            ArchivedNode::Duration { secs, nanos } => {
+                duration(secs.to_native(), nanos.to_native()).ok_or_else(|| invalid(index))?;
+            }
+            _ => {}
+        }
+    }
+    if tree.nodes.is_empty() || next != tree.nodes.len() || next_key != tree.keys.len() {
+        return Err(Error::custom("Invalid archive: node table is not a tree"));
+    }
+    Ok(ArchivedValue { tree, index: 0 })
+}
+
+fn duration(secs: i64, nanos: i32) -> Option<Duration> {
+    Duration::try_seconds(secs)?.checked_add(&Duration::nanoseconds(nanos.into()))
+}
+
+/// A value read in place from an archive
+///
+/// Lookups mirror those of [`DataValue`]: objects are searched in order and the
+/// first entry with a matching key wins.
+#[derive(Clone, Copy)]
+pub struct ArchivedValue<'b> {
+    tree: &'b ArchivedTree,
+    index: usize,
+}
+
+impl<'b> ArchivedValue<'b> {
+    fn node(&self) -> &'b ArchivedNode {
+        &self.tree.nodes[self.index]
+    }
+
+    fn at(&self, index: usize) -> ArchivedValue<'b> {
+        ArchivedValue {
+            tree: self.tree,
+            index,
+        }
+    }
+
+    /// Returns true if the value is null
+    pub fn is_null(&self) -> bool {
+        matches!(self.node(), ArchivedNode::Null)
+    }
+
+    /// Returns the value if it is a boolean
+    pub fn as_bool(&self) -> Option<bool> {
+        match self.node() {
+            ArchivedNode::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    /// Returns the value if it is a number that is an integer in the `i64` range
+    pub fn as_i64(&self) -> Option<i64> {
+        match self.node() {
+            ArchivedNode::Integer(i) => Some(i.to_native()),
+            ArchivedNode::Float(f) if f.to_native().fract() == 0.0 => {
+                let f = f.to_native();
+                (f >= i64::MIN as f64 && f < i64::MAX as f64).then_some(f as i64)
+            }
+            ArchivedNode::RawNumber(text) | ArchivedNode::BigInt(text) => {
+                text.as_str().parse().ok()
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the value as an `f64` if it is a number
+    pub fn as_f64(&self) -> Option<f64> {
+        match self.node() {
+            ArchivedNode::Integer(i) => Some(i.to_native() as f64),
+            ArchivedNode::Float(f) => Some(f.to_native()),
+            ArchivedNode::RawNumber(text)
+            | ArchivedNode::BigInt(text)
+            | ArchivedNode::BigDecimal(text) => text.as_str().parse().ok(),
+            _ => None,
+        }
+    }
+
+    /// Returns the value if it is a string, borrowed from the archive
+    pub fn as_str(&self) -> Option<&'b str> {
+        match self.node() {
+            ArchivedNode::String(s) => Some(s.as_str()),
+            _ => None,
+        }
+    }
+
+    /// Returns the elements of an array in order
+    pub fn elements(&self) -> Option<impl ExactSizeIterator<Item = ArchivedValue<'b>>> {
+        let ArchivedNode::Array { start, len } = self.node() else {
+            return None;
+        };
+        let start = start.to_native() as usize;
+        let value = *self;
+        Some((start..start + len.to_native() as usize).map(move |index| value.at(index)))
+    }
+
+    /// Returns the value of the first entry with the given key, if the value is
+    /// an object
+    pub fn get(&self, key: &str) -> Option<ArchivedValue<'b>> {
+        self.entries()?
+            .find(|(name, _)| *name == key)
+            .map(|(_, value)| value)
+    }
+
+    /// Returns the element at `index`, if the value is an array
+    pub fn get_index(&self, index: usize) -> Option<ArchivedValue<'b>> {
+        match self.node() {
+            ArchivedNode::Array { start, len } if index < len.to_native() as usize => {
+                Some(self.at(start.to_native() as usize + index))
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the entries of an object in order
+    pub fn entries(&self) -> Option<impl ExactSizeIterator<Item = (&'b str, ArchivedValue<'b>)>> {
+        let ArchivedNode::Object { start, len, keys } = self.node() else {
+            return None;
+        };
+        let (start, keys) = (start.to_native() as usize, keys.to_native() as usize);
+        let value = *self;
+        Some(
+            (0..len.to_native() as usize)
+                .map(move |i| (value.tree.keys[keys + i].as_str(), value.at(start + i))),
+        )
+    }
+
+    /// Looks up a value by JSON pointer, as [`DataValue::pointer`] does
+    pub fn pointer(&self, pointer: &str) -> Option<ArchivedValue<'b>> {
+        if pointer.is_empty() {
+            return Some(*self);
+        }
+        let tokens = pointer.strip_prefix('/')?;
+        tokens.split('/').try_fold(*self, |current, token| {
+            let token = unescape_pointer_token(token);
+            match current.node() {
+                ArchivedNode::Object { .. } => current.get(&token),
+                ArchivedNode::Array { .. } => current.get_index(token.parse().ok()?),
+                _ => None,
+            }
+        })
+    }
+
+    /// Copies the value into `arena` as a DataValue
+    ///
+    /// Strings, keys and number text are borrowed from the archive, so only the
+    /// arrays and objects are allocated.
+    pub fn to_value<'a>(&self, arena: &'a Bump) -> DataValue<'a>
+    where
+        'b: 'a,
+    {
+        /// An array or object whose elements are still being copied
+        enum Frame<'a> {
+            Array(std::ops::Range<usize>, Vec<DataValue<'a>>),
+            /// The remaining entries, the position of their keys and the copied
+            /// entries
+            Object(std::ops::Range<usize>, usize, Vec<(&'a str, DataValue<'a>)>),
+        }
+
+        let tree = self.tree;
+        let mut stack: Vec<Frame<'a>> = Vec::new();
+        let mut next = self.index;
+        loop {
+            let number = |number| Some(DataValue::Number(number));
+            let mut value = match &tree.nodes[next] {
+                ArchivedNode::Null => Some(DataValue::Null),
+                ArchivedNode::Bool(b) => Some(DataValue::Bool(*b)),
+                ArchivedNode::Integer(i) => number(Number::Integer(i.to_native())),
+                ArchivedNode::Float(f) => number(Number::Float(f.to_native())),
+                ArchivedNode::RawNumber(text) => number(Number::Raw(text.as_str())),
+                ArchivedNode::BigInt(text) | ArchivedNode::BigDecimal(text) => {
+                    number(big_number(arena, text.as_str()))
+                }
+                ArchivedNode::String(s) => Some(DataValue::String(s.as_str())),
+                ArchivedNode::Array { start, len } => {
+                    let start = start.to_native() as usize;
+                    let len = len.to_native() as usize;
+                    stack.push(Frame::Array(start..start + len, Vec::with_capacity(len)));
+                    None
+                }
+                ArchivedNode::Object { start, len, keys } => {
+                    let start = start.to_native() as usize;
+                    let len = len.to_native() as usize;
+                    stack.push(Frame::Object(
+                        start..start + len,
+                        keys.to_native() as usize,
+                        Vec::with_capacity(len),
+                    ));
+                    None
+                }
+                ArchivedNode::DateTime { secs, nanos } => Some(DataValue::DateTime(
+                    DateTime::from_timestamp(secs.to_native(), nanos.to_native())
+                        .expect("checked by access"),
+                )),
+                ArchivedNode::Duration { secs, nanos } => Some(DataValue::Duration(
+                    duration(secs.to_native(), nanos.to_native()).expect("checked by access"),
+                )),
+                ArchivedNode::Raw(text) => Some(DataValue::Raw(text.as_str())),
+            };
+
+            // Hand the value to its container, closing finished containers
+            loop {
+                match stack.last_mut() {
+                    None => return value.expect("a value was copied"),
+                    Some(Frame::Array(children, items)) => {
+                        items.extend(value.take());
+                        if let Some(child) = children.next() {
+                            next = child;
+                            break;
+                        }
+                    }
+                    Some(Frame::Object(children, keys, entries)) => {
+                        if let Some(value) = value.take() {
+                            let key = tree.keys[*keys + entries.len()].as_str();
+                            entries.push((key, value));
+                        }
+                        if let Some(child) = children.next() {
+                            next = child;
+                            break;
+                        }
+                    }
+                }
+                value = Some(match stack.pop() {
+                    Some(Frame::Array(_, items)) => {
+                        DataValue::Array(arena.alloc_slice_fill_iter(items))
+                    }
+                    Some(Frame::Object(_, _, entries)) => {
+                        DataValue::Object(arena.alloc_slice_fill_iter(entries))
+                    }
+                    None => unreachable!("a frame was open"),
+                });
+            }
+        }
+    }
+}
+
+/// Converts the text of an arbitrary-precision number, which stays raw number
+/// text when the `bignum` feature is disabled
+fn big_number<'a>(arena: &'a Bump, text: &'a str) -> Number<'a> {
+    #[cfg(feature = "bignum")]
+    if let Some(number) = crate::bignum::parse_number(arena, text, text.contains(['.', 'e'])) {
+        return number;
+    }
+    let _ = arena;
+    Number::Raw(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_str;
+
+    #[test]
+    fn test_round_trip() {
+        let arena = Bump::new();
+        let value = from_str(
+            &arena,
+            r#"{"a": [null, true, -1, 0.5, "x", [], {}], "b": {"c/d": [[{"e~f": 2}]]}, "": 3}"#,
+        )
+        .unwrap();
+        let bytes = to_bytes(&value).unwrap();
+        let root = access(&bytes).unwrap();
+
+        assert_eq!(root.to_value(&arena), value);
+        assert_eq!(root.get("a").unwrap().to_value(&arena), value["a"]);
+        assert_eq!(root.pointer("/b/c~1d/0/0/e~0f").unwrap().as_i64(), Some(2));
+        assert_eq!(root.pointer("/a/3").unwrap().as_f64(), Some(0.5));
+        assert!(root.pointer("/a/0").unwrap().is_null());
+        assert_eq!(root.pointer("/a/6").unwrap().entries().unwrap().len(), 0);
+        let elements = root.get("a").unwrap().elements().unwrap();
+        assert_eq!(
+            elements.map(|e| e.as_str()).collect::<Vec<_>>()[4],
+            Some("x")
+        );
+        assert_eq!(root.pointer("/").unwrap().as_i64(), Some(3));
+        assert!(root.pointer("/a/7").is_none());
+        assert!(root.pointer("/a/x").is_none());
+        assert!(root.pointer("a").is_none());
+        let keys: Vec<&str> = root.entries().unwrap().map(|(k, _)| k).collect();
+        assert_eq!(keys, ["a", "b", ""]);
+
+        let special = [
+            DataValue::DateTime("2024-02-29T12:30:45.123456789Z".parse().unwrap()),
+            DataValue::Duration(crate::helpers::parse_duration("-P1DT0.25S").unwrap()),
+            DataValue::Raw(r#"{"x": 1}"#),
+            DataValue::Number(Number::Raw("1.50")),
+            DataValue::Number(Number::Float(f64::NAN)),
+        ];
+        let special = DataValue::Array(arena.alloc_slice_fill_iter(special));
+        let bytes = to_bytes(&special).unwrap();
+        let copied = access(&bytes).unwrap().to_value(&arena);
+        let (copied, special) = (copied.as_array().unwrap(), special.as_array().unwrap());
+        assert_eq!(copied[..4], special[..4]);
+        assert!(copied[4].as_f64().unwrap().is_nan());
+    }
+
+    #[test]
+    fn test_invalid() {
+        let arena = Bump::new();
+        assert!(access(&[]).is_err());
+
+        // A node table whose array contains itself is not a tree
+        let tree = Tree {
+            nodes: vec![Node::Array { start: 0, len: 1 }],
+            keys: Vec::new(),
+        };
+        let bytes = rkyv::to_bytes::<rancor::Error>(&tree).unwrap();
+        assert!(access(&bytes).is_err());
+
+        let value = from_str(&arena, "[[1], {}]").unwrap();
+        let bytes = to_bytes(&value).unwrap();
+        assert!(access(&bytes[..bytes.len() - 1]).is_err());
+    }
+}
@@ -0,0 +1,120 @@
+//! Base64 for binary data carried in JSON strings
+//!
+//! The padded alphabet serves the binary formats, the url alphabet envelope
+//! signatures.
+#![cfg_attr(
+    not(any(
+        feature = "avro",
+        feature = "flexbuffers",
+        feature = "ion",
+        feature = "cbor",
+        feature = "otel",
+        feature = "redis"
+    )),
+    allow(dead_code)
+)]
+
+use crate::error::{Error, Result};
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const URL_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// Padded base64 encoding (RFC 4648 section 4)
+pub(crate) fn encode(bytes: &[u8]) -> String {
+    encode_with(bytes, ALPHABET, true)
+}
+
+/// Unpadded base64url encoding (RFC 4648 section 5)
+pub(crate) fn encode_url(bytes: &[u8]) -> String {
+    encode_with(bytes, URL_ALPHABET, false)
+}
+
+/// Decodes padded or unpadded base64, ignoring whitespace
+pub(crate) fn decode(text: &str) -> Result<Vec<u8>> {
+    decode_with(text, ALPHABET)
+}
+
+/// Decodes padded or unpadded base64url, ignoring whitespace
+pub(crate) fn decode_url(text: &str) -> Result<Vec<u8>> {
+    decode_with(text, URL_ALPHABET)
+}
+
+fn encode_with(bytes: &[u8], alphabet: &[u8; 64], pad: bool) -> String {
+    let mut output = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | u32::from(b) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                output.push(alphabet[(n >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else if pad {
+                output.push('=');
+            }
+        }
+    }
+    output
+}
+
+fn decode_with(text: &str, alphabet: &[u8; 64]) -> Result<Vec<u8>> {
+    let mut output = Vec::with_capacity(text.len() / 4 * 3);
+    let (mut n, mut bits) = (0u32, 0);
+    for b in text.bytes().filter(|b| !b.is_ascii_whitespace()) {
+        if b == b'=' {
+            break;
+        }
+        let value = alphabet
+            .iter()
+            .position(|&c| c == b)
+            .ok_or_else(|| Error::syntax(format!("invalid base64 character {:?}", b as char)))?;
+        n = n << 6 | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            output.push((n >> bits) as u8);
+        }
+    }
+    // A single character left over cannot encode a whole byte
+    if bits == 6 {
+        return Err(Error::syntax("truncated base64"));
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base64() {
+        for (bytes, text) in [
+            (&b""[..], ""),
+            (b"f", "Zg=="),
+            (b"fo", "Zm8="),
+            (b"foo", "Zm9v"),
+            (b"\xff\x00\x80", "/wCA"),
+        ] {
+            assert_eq!(encode(bytes), text);
+            assert_eq!(decode(text).unwrap(), bytes);
+        }
+        assert_eq!(decode("Zm9v\n Zg").unwrap(), b"foof");
+        assert!(decode("Zm9v!").is_err());
+        assert!(decode("Zm9vZ").is_err());
+    }
+
+    #[test]
+    fn test_base64url() {
+        for len in 0..10 {
+            let bytes: Vec<u8> = (0..len).map(|i| (i * 37 + 250) as u8).collect();
+            let encoded = encode_url(&bytes);
+            assert!(!encoded.contains('='));
+            assert_eq!(decode_url(&encoded).unwrap(), bytes);
+        }
+        assert_eq!(encode_url(b"hi?>"), "aGk_Pg");
+        assert_eq!(decode_url("aGk_Pg==").unwrap(), b"hi?>");
+        assert!(decode_url("a").is_err());
+        assert!(decode_url("a*bc").is_err());
+        assert!(decode_url("aGk/Pg").is_err());
+    }
+}
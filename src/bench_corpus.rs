@@ -0,0 +1,313 @@
+//! Generated benchmark documents, enabled by the `bench-corpus` feature
+//!
+//! The `corpus` benchmark measures parse, access and serialize throughput on
+//! documents shaped like the files commonly used to benchmark JSON libraries.
+//! The originals cannot be shipped with the crate, so this module generates
+//! equivalents from a fixed seed: every call returns the same text, on every
+//! platform, and numbers from one run can be compared with the next.
+//!
+//! | Document | Shape |
+//! |---|---|
+//! | [`twitter`] | API search results: deep objects with many string fields, unicode text and nulls |
+//! | [`citm`] | An event catalog: wide objects keyed by id, integer arrays and repeated small objects |
+//! | [`canada`] | A GeoJSON polygon collection: long arrays of float coordinate pairs |
+//!
+//! Run `cargo bench --bench corpus --features bench-corpus` before and after a
+//! change to see its effect. [`POINTERS`] lists the paths the access benchmark
+//! reads from each document.
+//!
+//! # Example
+//!
+//! ```
+//! use datavalue_rs::{bench_corpus, from_str, Bump};
+//!
+//! let arena = Bump::new();
+//! for (name, text) in bench_corpus::documents() {
+//!     let value = from_str(&arena, &text).unwrap();
+//!     for pointer in bench_corpus::pointers(name) {
+//!         assert!(value.pointer(pointer).is_some(), "{} {}", name, pointer);
+//!     }
+//! }
+//! assert_eq!(bench_corpus::twitter(), bench_corpus::twitter());
+//! ```
+
+use std::fmt::Write;
+
+/// JSON pointers read by the access benchmark, by document name
+pub const POINTERS: &[(&str, &[&str])] = &[
+    (
+        "twitter",
+        &[
+            "/statuses/0/user/screen_name",
+            "/statuses/50/entities/hashtags/0/text",
+            "/statuses/99/retweet_count",
+            "/search_metadata/max_id",
+        ],
+    ),
+    (
+        "citm",
+        &[
+            "/events/138586341/name",
+            "/performances/0/seatCategories/0/areas/0/areaId",
+            "/performances/242/start",
+            "/topicNames/107888604",
+        ],
+    ),
+    (
+        "canada",
+        &[
+            "/features/0/geometry/coordinates/0/0/0",
+            "/features/0/geometry/coordinates/479/115/1",
+            "/features/0/properties/name",
+        ],
+    ),
+];
+
+/// Returns the name and text of every document
+pub fn documents() -> [(&'static str, String); 3] {
+    [
+        ("twitter", twitter()),
+        ("citm", citm()),
+        ("canada", canada()),
+    ]
+}
+
+/// Returns the pointers the access benchmark reads from a document
+pub fn pointers(name: &str) -> &'static [&'static str] {
+    POINTERS
+        .iter()
+        .find(|(document, _)| *document == name)
+        .map_or(&[], |(_, pointers)| pointers)
+}
+
+/// Generates about 140 KB of search results in the shape of `twitter.json`
+pub fn twitter() -> String {
+    const WORDS: &[&str] = &[
+        "rust",
+        "arena",
+        "parser",
+        "こんにちは",
+        "benchmark",
+        "café",
+        "json",
+        "zero-copy",
+        "🚀",
+        "values",
+        "schema",
+        "naïve",
+        "fast",
+        "release",
+        "世界",
+        "notes",
+    ];
+    let mut rng = Rng(0x7477_6974);
+    let mut out = String::from(r#"{"statuses":["#);
+    for i in 0..100u64 {
+        if i > 0 {
+            out.push(',');
+        }
+        let id = 505_874_924_095_815_681 - i * 7919;
+        let user_id = rng.below(2_000_000_000);
+        let text: Vec<&str> = (0..8 + rng.below(12)).map(|_| rng.pick(WORDS)).collect();
+        let hashtag = rng.pick(WORDS);
+        let _ = write!(
+            out,
+            r#"{{"metadata":{{"result_type":"recent","iso_language_code":"ja"}},"created_at":"Sun Aug 31 00:{:02}:{:02} +0000 2014","id":{},"id_str":"{}","text":"{} \"{}\"\n","source":"<a href=\"https://example.com/app\" rel=\"nofollow\">app</a>","truncated":false,"in_reply_to_status_id":null,"in_reply_to_user_id":{},"user":{{"id":{},"id_str":"{}","name":"user {}","screen_name":"user_{}","location":"{}","description":"{}","url":null,"entities":{{"description":{{"urls":[]}}}},"protected":false,"followers_count":{},"friends_count":{},"listed_count":{},"created_at":"Thu Jul 04 08:32:11 +0000 2013","favourites_count":{},"utc_offset":null,"time_zone":null,"geo_enabled":{},"verified":false,"statuses_count":{},"lang":"ja","profile_background_color":"C0DEED","profile_image_url_https":"https://pbs.example.com/profile_images/{}/normal.jpeg","default_profile":true,"following":false}},"geo":null,"coordinates":null,"place":null,"contributors":null,"retweet_count":{},"favorite_count":{},"entities":{{"hashtags":[{{"text":"{}","indices":[{},{}]}}],"symbols":[],"urls":[],"user_mentions":[{{"screen_name":"user_{}","name":"user","id":{},"id_str":"{}","indices":[0,{}]}}]}},"favorited":false,"retweeted":false,"lang":"ja"}}"#,
+            i % 60,
+            rng.below(60),
+            id,
+            id,
+            text.join(" "),
+            rng.pick(WORDS),
+            if rng.below(4) == 0 {
+                user_id.to_string()
+            } else {
+                "null".into()
+            },
+            user_id,
+            user_id,
+            i,
+            i,
+            rng.pick(WORDS),
+            text[..4].join(" "),
+            rng.below(100_000),
+            rng.below(5_000),
+            rng.below(100),
+            rng.below(10_000),
+            rng.below(2) == 0,
+            rng.below(50_000),
+            user_id,
+            rng.below(1_000),
+            rng.below(1_000),
+            hashtag,
+            10,
+            11 + hashtag.chars().count(),
+            rng.below(1_000),
+            rng.below(2_000_000_000),
+            rng.below(2_000_000_000),
+            rng.below(20),
+        );
+    }
+    let _ = write!(
+        out,
+        r#"],"search_metadata":{{"completed_in":0.087,"max_id":505874924095815681,"max_id_str":"505874924095815681","query":"%E4%B8%80","refresh_url":"?since_id=505874924095815681&q=%E4%B8%80&include_entities=1","count":100,"since_id":0,"since_id_str":"0"}}}}"#
+    );
+    out
+}
+
+/// Generates about 230 KB of event listings in the shape of `citm_catalog.json`
+pub fn citm() -> String {
+    let mut rng = Rng(0x6369_746d);
+    let area_ids: Vec<u64> = (0..20).map(|i| 205_705_993 + i * 4).collect();
+    let event_ids: Vec<u64> = (0..184).map(|i| 138_586_341 + i * 8).collect();
+    let topic_ids: Vec<u64> = (0..30).map(|i| 107_888_604 + i * 3).collect();
+
+    let mut out = String::from(r#"{"areaNames":{"#);
+    for (i, id) in area_ids.iter().enumerate() {
+        let _ = write!(out, r#"{}"{}":"Area {}""#, comma(i), id, i);
+    }
+    out.push_str(
+        r#"},"audienceSubCategoryNames":{"337100890":"Abonné"},"blockNames":{},"events":{"#,
+    );
+    for (i, id) in event_ids.iter().enumerate() {
+        let topics: Vec<String> = (0..1 + rng.below(4))
+            .map(|_| rng.pick(&topic_ids).to_string())
+            .collect();
+        let _ = write!(
+            out,
+            r#"{}"{}":{{"description":null,"id":{},"logo":{},"name":"Event {} – {}","subTopicIds":[337184284,337184263,{}],"subjectCode":null,"subtitle":null,"topicIds":[{}]}}"#,
+            comma(i),
+            id,
+            id,
+            if rng.below(3) == 0 {
+                format!(r#""/images/UE0AAAAACEKo6QAAAAZDSVRN/{}""#, id)
+            } else {
+                "null".into()
+            },
+            i,
+            ["Opéra", "Concert", "Ballet", "Récital"][rng.below(4) as usize],
+            rng.pick(&topic_ids),
+            topics.join(","),
+        );
+    }
+    out.push_str(r#"},"performances":["#);
+    for i in 0..243 {
+        let event_id = rng.pick(&event_ids);
+        let _ = write!(
+            out,
+            r#"{}{{"eventId":{},"id":{},"logo":null,"name":null,"prices":["#,
+            comma(i),
+            event_id,
+            339_887_544 + i * 5,
+        );
+        for j in 0..rng.below(5) + 1 {
+            let _ = write!(
+                out,
+                r#"{}{{"amount":{},"audienceSubCategoryId":337100890,"seatCategoryId":{}}}"#,
+                comma(j as usize),
+                rng.below(100) * 1000 + 9500,
+                338_937_295 + j * 2,
+            );
+        }
+        out.push_str(r#"],"seatCategories":["#);
+        for j in 0..rng.below(4) + 1 {
+            let areas: Vec<String> = (0..1 + rng.below(6))
+                .map(|_| format!(r#"{{"areaId":{},"blockIds":[]}}"#, rng.pick(&area_ids)))
+                .collect();
+            let _ = write!(
+                out,
+                r#"{}{{"areas":[{}],"seatCategoryId":{}}}"#,
+                comma(j as usize),
+                areas.join(","),
+                338_937_295 + j * 2,
+            );
+        }
+        let _ = write!(
+            out,
+            r#"],"seatMapImage":null,"start":{},"venueCode":"PLEYEL_PLEYEL"}}"#,
+            1_372_615_200_000 + i as u64 * 86_400_000,
+        );
+    }
+    out.push_str(r#"],"seatCategoryNames":{"338937295":"1ère catégorie"},"subTopicNames":{"337184262":"Musique amplifiée","337184263":"Musique baroque"},"subjectNames":{},"topicNames":{"#);
+    for (i, id) in topic_ids.iter().enumerate() {
+        let _ = write!(out, r#"{}"{}":"Topic {}""#, comma(i), id, i);
+    }
+    out.push_str(r#"},"topicSubTopics":{},"venueNames":{"PLEYEL_PLEYEL":"Salle Pleyel"}}"#);
+    out
+}
+
+/// Generates about 2.2 MB of polygons in the shape of `canada.json`, mostly
+/// floats with many significant digits
+pub fn canada() -> String {
+    let mut rng = Rng(0x6361_6e61);
+    let mut out = String::from(
+        r#"{"type":"FeatureCollection","features":[{"type":"Feature","properties":{"name":"Canada"},"geometry":{"type":"Polygon","coordinates":["#,
+    );
+    for ring in 0..480 {
+        out.push_str(comma(ring));
+        out.push('[');
+        let (mut lon, mut lat) = (-141.0 + rng.unit() * 90.0, 42.0 + rng.unit() * 40.0);
+        for point in 0..116 {
+            lon += (rng.unit() - 0.5) * 0.01;
+            lat += (rng.unit() - 0.5) * 0.01;
+            let _ = write!(out, "{}[{},{}]", comma(point), lon, lat);
+        }
+        out.push(']');
+    }
+    out.push_str("]}}]}");
+    out
+}
+
+fn comma(i: usize) -> &'static str {
+    if i == 0 {
+        ""
+    } else {
+        ","
+    }
+}
+
+/// A xorshift generator, so the documents do not depend on an outside crate's
+/// algorithm staying the same
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+
+    /// A float in `[0, 1)`
+    fn unit(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn pick<T: Copy>(&mut self, items: &[T]) -> T {
+        items[self.below(items.len() as u64) as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{from_str, Bump};
+
+    #[test]
+    fn test_documents() {
+        let arena = Bump::new();
+        for (name, text) in documents() {
+            let value = from_str(&arena, &text).unwrap_or_else(|e| panic!("{}: {}", name, e));
+            assert!(!pointers(name).is_empty());
+            for pointer in pointers(name) {
+                assert!(value.pointer(pointer).is_some(), "{} {}", name, pointer);
+            }
+        }
+        assert_eq!(canada(), canada());
+        assert!(pointers("missing").is_empty());
+    }
+}
@@ -0,0 +1,474 @@
+//! Arbitrary-precision numbers
+//!
+//! With [`NumberMode::Arbitrary`](crate::NumberMode::Arbitrary), integers outside
+//! the `i64` range are parsed into [`Number::BigInt`] and decimals that an `f64`
+//! cannot hold without losing digits into [`Number::BigDecimal`]. Their digits are
+//! stored in the arena, like strings.
+//!
+//! Comparisons between numbers of any kind are exact. Arithmetic operators cannot
+//! allocate, so they work on the nearest `f64`.
+
+use std::cmp::Ordering;
+use std::fmt;
+
+use bumpalo::Bump;
+
+use crate::datavalue::Number;
+
+/// An integer of any size, stored as decimal digits in the arena
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::{BigInt, Bump};
+///
+/// let arena = Bump::new();
+/// let n = BigInt::parse(&arena, "-000123456789012345678901234567890").unwrap();
+/// assert!(n.is_negative());
+/// assert_eq!(n.digits(), "123456789012345678901234567890");
+/// assert_eq!(n.to_string(), "-123456789012345678901234567890");
+/// assert_eq!(n.to_i64(), None);
+/// assert_eq!(BigInt::parse(&arena, "-0").unwrap().to_i64(), Some(0));
+/// assert!(BigInt::parse(&arena, "1.5").is_none());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BigInt<'a> {
+    negative: bool,
+    /// Magnitude without leading zeros, `"0"` for zero
+    digits: &'a str,
+}
+
+impl<'a> BigInt<'a> {
+    /// Parses an optionally negative run of decimal digits
+    ///
+    /// Returns None if `text` is not an integer.
+    pub fn parse(arena: &'a Bump, text: &str) -> Option<Self> {
+        let (negative, digits) = match text.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, text),
+        };
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let digits = match digits.trim_start_matches('0') {
+            "" => "0",
+            trimmed => trimmed,
+        };
+        Some(BigInt {
+            negative: negative && digits != "0",
+            digits: arena.alloc_str(digits),
+        })
+    }
+
+    /// Returns true if the integer is less than zero
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    /// Returns the decimal digits of the magnitude, without leading zeros
+    pub fn digits(&self) -> &'a str {
+        self.digits
+    }
+
+    /// Returns the integer as an `i64`, if it is in range
+    pub fn to_i64(&self) -> Option<i64> {
+        self.to_string().parse().ok()
+    }
+
+    /// Returns the nearest `f64`, which is infinite for very large integers
+    pub fn to_f64(&self) -> f64 {
+        self.to_string().parse().unwrap_or(f64::NAN)
+    }
+
+    /// Copies the digits into `arena`
+    pub fn clone_in<'b>(&self, arena: &'b Bump) -> BigInt<'b> {
+        BigInt {
+            negative: self.negative,
+            digits: arena.alloc_str(self.digits),
+        }
+    }
+}
+
+impl fmt::Display for BigInt<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.negative {
+            f.write_str("-")?;
+        }
+        f.write_str(self.digits)
+    }
+}
+
+/// A decimal of any size and precision, stored as digits in the arena
+///
+/// The value is `unscaled * 10^-scale`. It is kept normalized, without trailing
+/// zeros in the unscaled digits, so equal values have equal representations.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::{BigDecimal, Bump};
+///
+/// let arena = Bump::new();
+/// let pi = BigDecimal::parse(&arena, "3.14159265358979323846264338327950").unwrap();
+/// assert_eq!(pi.unscaled().digits(), "31415926535897932384626433832795");
+/// assert_eq!(pi.scale(), 31);
+/// assert_eq!(pi.to_string(), "3.1415926535897932384626433832795");
+/// assert_eq!(pi.to_f64(), std::f64::consts::PI);
+///
+/// let tiny = BigDecimal::parse(&arena, "-12.50e-400").unwrap();
+/// assert_eq!(tiny.to_string(), "-1.25e-399");
+/// assert_eq!(BigDecimal::parse(&arena, "1200").unwrap().scale(), -2);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BigDecimal<'a> {
+    unscaled: BigInt<'a>,
+    scale: i64,
+}
+
+impl<'a> BigDecimal<'a> {
+    /// Parses JSON number text
+    ///
+    /// Returns None if `text` is not a JSON number or its exponent does not fit
+    /// an `i64`.
+    pub fn parse(arena: &'a Bump, text: &str) -> Option<Self> {
+        let parts = Parts::parse(text)?;
+        Some(BigDecimal {
+            unscaled: BigInt {
+                negative: parts.negative,
+                digits: arena.alloc_str(&parts.digits),
+            },
+            scale: parts.exponent.checked_neg()?,
+        })
+    }
+
+    /// Returns the digits of the value as an integer, without trailing zeros
+    pub fn unscaled(&self) -> BigInt<'a> {
+        self.unscaled
+    }
+
+    /// Returns the power of ten the unscaled integer is divided by
+    pub fn scale(&self) -> i64 {
+        self.scale
+    }
+
+    /// Copies the digits into `arena`
+    pub fn clone_in<'b>(&self, arena: &'b Bump) -> BigDecimal<'b> {
+        BigDecimal {
+            unscaled: self.unscaled.clone_in(arena),
+            scale: self.scale,
+        }
+    }
+
+    /// Returns the nearest `f64`, which may be infinite or zero for extreme exponents
+    pub fn to_f64(&self) -> f64 {
+        format!("{}e{}", self.unscaled, -(self.scale as i128))
+            .parse()
+            .unwrap_or(f64::NAN)
+    }
+}
+
+/// Writes the value as a JSON number, in exponent notation when plain notation
+/// would need more than 20 zeros
+impl fmt::Display for BigDecimal<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.unscaled.negative {
+            f.write_str("-")?;
+        }
+        let digits = self.unscaled.digits;
+        // Number of digits before the decimal point
+        let point = digits.len() as i128 - self.scale as i128;
+        if self.scale <= 0 && self.scale >= -20 {
+            write!(f, "{}{}", digits, "0".repeat(-self.scale as usize))
+        } else if self.scale > 0 && point > 0 {
+            let (whole, fraction) = digits.split_at(point as usize);
+            write!(f, "{}.{}", whole, fraction)
+        } else if point <= 0 && point > -20 {
+            write!(f, "0.{}{}", "0".repeat(-point as usize), digits)
+        } else {
+            let (first, rest) = digits.split_at(1);
+            f.write_str(first)?;
+            if !rest.is_empty() {
+                write!(f, ".{}", rest)?;
+            }
+            write!(f, "e{}", point - 1)
+        }
+    }
+}
+
+/// A JSON number split into sign, significant digits and exponent
+///
+/// The value is `digits * 10^exponent`, with no leading or trailing zeros in
+/// `digits`. Zero is `"0"` with exponent 0 and no sign.
+#[derive(Debug, PartialEq, Eq)]
+struct Parts {
+    negative: bool,
+    digits: String,
+    exponent: i64,
+}
+
+impl Parts {
+    /// Splits JSON number text, or the `{:e}` formatting of an `f64`
+    fn parse(text: &str) -> Option<Self> {
+        let (negative, rest) = match text.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, text),
+        };
+        let (mantissa, exponent) = match rest.find(['e', 'E']) {
+            Some(i) => (&rest[..i], rest[i + 1..].parse::<i64>().ok()?),
+            None => (rest, 0),
+        };
+        let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+        if whole.is_empty()
+            || !whole
+                .bytes()
+                .chain(fraction.bytes())
+                .all(|b| b.is_ascii_digit())
+        {
+            return None;
+        }
+        let all = format!("{}{}", whole, fraction);
+        let significant = all.trim_start_matches('0');
+        let trimmed = significant.trim_end_matches('0');
+        if trimmed.is_empty() {
+            return Some(Parts {
+                negative: false,
+                digits: "0".to_string(),
+                exponent: 0,
+            });
+        }
+        let trailing = (significant.len() - trimmed.len()) as i64;
+        let exponent = exponent
+            .checked_sub(fraction.len() as i64)?
+            .checked_add(trailing)?;
+        Some(Parts {
+            negative,
+            digits: trimmed.to_string(),
+            exponent,
+        })
+    }
+
+    /// Returns the parts of a number, or None for non-finite floats
+    fn of(number: Number<'_>) -> Option<Self> {
+        match number {
+            Number::Integer(i) => Parts::parse(&i.to_string()),
+            Number::Float(f) if f.is_finite() => Parts::parse(&format!("{:e}", f)),
+            Number::Float(_) => None,
+            Number::Raw(text) => Parts::parse(text),
+            Number::BigInt(n) => Parts::parse(&n.to_string()),
+            Number::BigDecimal(n) => Some(Parts {
+                negative: n.unscaled.negative,
+                digits: n.unscaled.digits.to_string(),
+                exponent: n.scale.checked_neg()?,
+            }),
+        }
+    }
+
+    fn is_zero(&self) -> bool {
+        self.digits == "0"
+    }
+
+    fn cmp_magnitude(&self, other: &Parts) -> Ordering {
+        match (self.is_zero(), other.is_zero()) {
+            (true, true) => return Ordering::Equal,
+            (true, false) => return Ordering::Less,
+            (false, true) => return Ordering::Greater,
+            (false, false) => {}
+        }
+        // Position of the leading digit, then the digits themselves; without
+        // trailing zeros a prefix is the smaller number
+        let magnitude = |p: &Parts| p.digits.len() as i128 + p.exponent as i128;
+        magnitude(self)
+            .cmp(&magnitude(other))
+            .then_with(|| self.digits.cmp(&other.digits))
+    }
+}
+
+impl Ord for Parts {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => self.cmp_magnitude(other),
+            (true, true) => other.cmp_magnitude(self),
+        }
+    }
+}
+
+impl PartialOrd for Parts {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Compares two numbers exactly
+///
+/// Returns None if either number is NaN.
+pub(crate) fn compare(a: Number<'_>, b: Number<'_>) -> Option<Ordering> {
+    match (Parts::of(a), Parts::of(b)) {
+        (Some(a), Some(b)) => Some(a.cmp(&b)),
+        _ => {
+            // Only non-finite floats have no parts, and every finite number lies
+            // between the infinities
+            let float = |n: Number<'_>| match n {
+                Number::Float(f) if !f.is_finite() => f,
+                _ => 0.0,
+            };
+            float(a).partial_cmp(&float(b))
+        }
+    }
+}
+
+/// Parses JSON number text into the narrowest number that holds it exactly
+///
+/// Integers in the `i64` range become [`Number::Integer`], other integers
+/// [`Number::BigInt`]. Decimals become [`Number::Float`] when the shortest
+/// representation of the nearest `f64` has the same value, and
+/// [`Number::BigDecimal`] otherwise.
+pub(crate) fn parse_number<'a>(arena: &'a Bump, text: &str, is_float: bool) -> Option<Number<'a>> {
+    if !is_float {
+        return match text.parse::<i64>() {
+            Ok(i) => Some(Number::Integer(i)),
+            Err(_) => BigInt::parse(arena, text).map(Number::BigInt),
+        };
+    }
+    match text.parse::<f64>() {
+        Ok(f) if f.is_finite() && Parts::parse(&format!("{:e}", f)) == Parts::parse(text) => {
+            Some(Number::Float(f))
+        }
+        _ => BigDecimal::parse(arena, text).map(Number::BigDecimal),
+    }
+}
+
+/// Converts big-endian digits in `radix` to decimal digits without leading zeros
+#[cfg_attr(not(any(feature = "ion", feature = "cbor")), allow(dead_code))]
+pub(crate) fn to_decimal(digits: impl IntoIterator<Item = u32>, radix: u32) -> String {
+    // Little-endian decimal digits
+    let mut decimal = vec![0u32];
+    for digit in digits {
+        let mut carry = digit;
+        for d in decimal.iter_mut() {
+            let v = *d * radix + carry;
+            *d = v % 10;
+            carry = v / 10;
+        }
+        while carry > 0 {
+            decimal.push(carry % 10);
+            carry /= 10;
+        }
+    }
+    while decimal.len() > 1 && decimal.last() == Some(&0) {
+        decimal.pop();
+    }
+    decimal
+        .iter()
+        .rev()
+        .map(|&d| char::from(b'0' + d as u8))
+        .collect()
+}
+
+/// Converts decimal digits to big-endian bytes without leading zeros, empty for zero
+#[cfg_attr(not(any(feature = "ion", feature = "cbor")), allow(dead_code))]
+pub(crate) fn to_bytes(decimal: &str) -> Vec<u8> {
+    // Little-endian bytes
+    let mut bytes: Vec<u8> = Vec::new();
+    for digit in decimal.bytes() {
+        let mut carry = u32::from(digit - b'0');
+        for b in bytes.iter_mut() {
+            let v = u32::from(*b) * 10 + carry;
+            *b = v as u8;
+            carry = v >> 8;
+        }
+        if carry > 0 {
+            bytes.push(carry as u8);
+        }
+    }
+    bytes.reverse();
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_number_picks_narrowest() {
+        let arena = Bump::new();
+        let kind = |text: &str| match parse_number(&arena, text, text.contains(['.', 'e'])) {
+            Some(Number::Integer(_)) => "int",
+            Some(Number::Float(_)) => "float",
+            Some(Number::BigInt(_)) => "bigint",
+            Some(Number::BigDecimal(_)) => "bigdecimal",
+            _ => "none",
+        };
+        assert_eq!(kind("-9223372036854775808"), "int");
+        assert_eq!(kind("9223372036854775808"), "bigint");
+        assert_eq!(kind("0.1"), "float");
+        assert_eq!(kind("-0.0"), "float");
+        assert_eq!(kind("1e300"), "float");
+        assert_eq!(kind("1e400"), "bigdecimal");
+        assert_eq!(kind("0.10000000000000000001"), "bigdecimal");
+        assert_eq!(kind("9007199254740993.0"), "bigdecimal");
+    }
+
+    #[test]
+    fn test_display_round_trips() {
+        let arena = Bump::new();
+        for text in ["0", "-1.5", "1200", "0.000001", "1e-30", "-1.23e45", "1e21"] {
+            let decimal = BigDecimal::parse(&arena, text).unwrap();
+            let again = BigDecimal::parse(&arena, &decimal.to_string()).unwrap();
+            assert_eq!(decimal, again, "{} -> {}", text, decimal);
+        }
+        let decimal = BigDecimal::parse(&arena, "0.0001200").unwrap();
+        assert_eq!(decimal.to_string(), "0.00012");
+        assert_eq!(
+            BigDecimal::parse(&arena, "1e25").unwrap().to_string(),
+            "1e25"
+        );
+    }
+
+    #[test]
+    fn test_compare_is_exact() {
+        let arena = Bump::new();
+        let big = |text: &str| Number::BigDecimal(BigDecimal::parse(&arena, text).unwrap());
+        let int = |text: &str| Number::BigInt(BigInt::parse(&arena, text).unwrap());
+
+        let above = int("9007199254740993");
+        assert_eq!(
+            compare(above, Number::Float(9007199254740992.0)),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(
+            compare(big("1e2"), Number::Integer(100)),
+            Some(Ordering::Equal)
+        );
+        assert_eq!(
+            compare(big("-0.5"), Number::Integer(0)),
+            Some(Ordering::Less)
+        );
+        assert_eq!(compare(big("-0.5"), big("-0.49")), Some(Ordering::Less));
+        assert_eq!(compare(big("0.12"), big("0.123")), Some(Ordering::Less));
+        assert_eq!(
+            compare(int("-1"), Number::Raw("-1.0")),
+            Some(Ordering::Equal)
+        );
+        assert_eq!(
+            compare(big("1e400"), Number::Float(f64::INFINITY)),
+            Some(Ordering::Less)
+        );
+        assert_eq!(compare(big("1"), Number::Float(f64::NAN)), None);
+    }
+
+    #[test]
+    fn test_radix_conversion() {
+        assert_eq!(to_decimal([0x01, 0x00], 256), "256");
+        assert_eq!(to_decimal([], 16), "0");
+        assert_eq!(to_decimal([0xF, 0xF], 16), "255");
+        let big = "340282366920938463463374607431768211456";
+        assert_eq!(
+            to_decimal(to_bytes(big).into_iter().map(u32::from), 256),
+            big
+        );
+        assert_eq!(to_bytes("0"), b"");
+        assert_eq!(to_bytes("65535"), [0xFF, 0xFF]);
+    }
+}
@@ -0,0 +1,447 @@
+//! Compact binary snapshots of parsed trees
+//!
+//! [`DataValue::to_binary`] writes a value in a length-prefixed encoding that
+//! mirrors the arena layout, and [`DataValue::from_binary`] reads it back. Each
+//! value is a tag byte followed by its payload. Arrays and objects start with
+//! their element count, so the reader allocates each slice once at its final
+//! size, and strings carry their byte length, so they are copied into the arena
+//! without scanning for escapes. Loading a snapshot is therefore much cheaper
+//! than parsing the JSON it came from, which makes snapshots suitable for
+//! caching parsed documents on disk.
+//!
+//! Integers, counts and lengths are LEB128 varints, with signed integers
+//! zigzag-encoded. Floats are 8 little-endian bytes. Raw numbers, raw values and
+//! arbitrary-precision numbers are stored as their text, and DateTimes and
+//! durations as seconds and nanoseconds, so every value comes back unchanged.
+//!
+//! A snapshot starts with a four-byte header that includes the format version.
+//! The encoding is not meant for interchange: read snapshots with the version
+//! of the crate that wrote them.
+
+use bumpalo::Bump;
+use chrono::{DateTime, Duration};
+
+use crate::datavalue::{DataValue, Number};
+use crate::error::{Error, Result};
+
+/// Magic bytes and format version at the start of every snapshot
+const HEADER: &[u8; 4] = b"DVB\x01";
+
+const TAG_NULL: u8 = 0;
+const TAG_FALSE: u8 = 1;
+const TAG_TRUE: u8 = 2;
+const TAG_INTEGER: u8 = 3;
+const TAG_FLOAT: u8 = 4;
+const TAG_RAW_NUMBER: u8 = 5;
+const TAG_BIG_INT: u8 = 6;
+const TAG_BIG_DECIMAL: u8 = 7;
+const TAG_STRING: u8 = 8;
+const TAG_ARRAY: u8 = 9;
+const TAG_OBJECT: u8 = 10;
+const TAG_DATETIME: u8 = 11;
+const TAG_DURATION: u8 = 12;
+const TAG_RAW: u8 = 13;
+
+impl<'a> DataValue<'a> {
+    /// Encodes the value as a binary snapshot
+    ///
+    /// The snapshot can be stored and loaded later with
+    /// [`from_binary`](DataValue::from_binary), which is much faster than
+    /// parsing JSON.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use datavalue_rs::{Bump, DataValue};
+    /// let arena = Bump::new();
+    /// let value = DataValue::from_str(&arena, r#"{"id": 7, "tags": ["a", "b"]}"#).unwrap();
+    /// let snapshot = value.to_binary();
+    ///
+    /// let cache = Bump::new();
+    /// let loaded = DataValue::from_binary(&cache, &snapshot).unwrap();
+    /// assert_eq!(loaded, value);
+    /// ```
+    pub fn to_binary(&self) -> Vec<u8> {
+        /// What remains to be written, in reverse order
+        enum Item<'v, 'a> {
+            Value(&'v DataValue<'a>),
+            Key(&'a str),
+        }
+
+        let mut output = HEADER.to_vec();
+        let mut stack = vec![Item::Value(self)];
+        while let Some(item) = stack.pop() {
+            let value = match item {
+                Item::Key(key) => {
+                    write_str(&mut output, key);
+                    continue;
+                }
+                Item::Value(value) => value,
+            };
+            match value {
+                DataValue::Null => output.push(TAG_NULL),
+                DataValue::Bool(false) => output.push(TAG_FALSE),
+                DataValue::Bool(true) => output.push(TAG_TRUE),
+                DataValue::Number(Number::Integer(i)) => {
+                    output.push(TAG_INTEGER);
+                    write_varint(&mut output, zigzag(*i));
+                }
+                DataValue::Number(Number::Float(f)) => {
+                    output.push(TAG_FLOAT);
+                    output.extend_from_slice(&f.to_le_bytes());
+                }
+                DataValue::Number(Number::Raw(text)) => {
+                    output.push(TAG_RAW_NUMBER);
+                    write_str(&mut output, text);
+                }
+                #[cfg(feature = "bignum")]
+                DataValue::Number(Number::BigInt(n)) => {
+                    output.push(TAG_BIG_INT);
+                    write_str(&mut output, &n.to_string());
+                }
+                #[cfg(feature = "bignum")]
+                DataValue::Number(Number::BigDecimal(n)) => {
+                    output.push(TAG_BIG_DECIMAL);
+                    write_str(&mut output, &n.to_string());
+                }
+                DataValue::String(s) => {
+                    output.push(TAG_STRING);
+                    write_str(&mut output, s);
+                }
+                DataValue::Array(items) => {
+                    output.push(TAG_ARRAY);
+                    write_varint(&mut output, items.len() as u64);
+                    stack.extend(items.iter().rev().map(Item::Value));
+                }
+                DataValue::Object(entries) => {
+                    output.push(TAG_OBJECT);
+                    write_varint(&mut output, entries.len() as u64);
+                    for (key, value) in entries.iter().rev() {
+                        stack.push(Item::Value(value));
+                        stack.push(Item::Key(key));
+                    }
+                }
+                DataValue::DateTime(dt) => {
+                    output.push(TAG_DATETIME);
+                    write_varint(&mut output, zigzag(dt.timestamp()));
+                    write_varint(&mut output, dt.timestamp_subsec_nanos().into());
+                }
+                DataValue::Duration(dur) => {
+                    output.push(TAG_DURATION);
+                    write_varint(&mut output, zigzag(dur.num_seconds()));
+                    write_varint(&mut output, zigzag(dur.subsec_nanos().into()));
+                }
+                DataValue::Raw(text) => {
+                    output.push(TAG_RAW);
+                    write_str(&mut output, text);
+                }
+            }
+        }
+        output
+    }
+
+    /// Decodes a binary snapshot written by [`to_binary`](DataValue::to_binary)
+    /// into the arena
+    ///
+    /// # Errors
+    ///
+    /// Returns a syntax error with the byte offset of the problem if the header
+    /// is missing or from another format version, or the snapshot is truncated,
+    /// has trailing bytes or holds invalid data.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use datavalue_rs::{Bump, DataValue};
+    /// let arena = Bump::new();
+    /// let value = DataValue::from_str(&arena, "[1.5, null]").unwrap();
+    /// let snapshot = value.to_binary();
+    ///
+    /// assert_eq!(DataValue::from_binary(&arena, &snapshot).unwrap(), value);
+    /// assert!(DataValue::from_binary(&arena, &snapshot[..snapshot.len() - 1]).is_err());
+    /// assert!(DataValue::from_binary(&arena, b"[1.5, null]").is_err());
+    /// ```
+    pub fn from_binary(arena: &'a Bump, bytes: &[u8]) -> Result<DataValue<'a>> {
+        /// An array or object whose elements are still being read
+        enum Frame<'a> {
+            Array(usize, Vec<DataValue<'a>>),
+            /// The entry count, the entries read and the key of the entry
+            /// being read
+            Object(usize, Vec<(&'a str, DataValue<'a>)>, &'a str),
+        }
+
+        if !bytes.starts_with(HEADER) {
+            return Err(Error::syntax("Missing binary snapshot header at byte 0"));
+        }
+        let mut reader = Reader {
+            arena,
+            bytes,
+            pos: HEADER.len(),
+        };
+        let mut stack: Vec<Frame<'a>> = Vec::new();
+        loop {
+            if let Some(Frame::Object(_, _, key)) = stack.last_mut() {
+                *key = reader.string()?;
+            }
+            let start = reader.pos;
+            let mut value = match reader.byte()? {
+                TAG_NULL => DataValue::Null,
+                TAG_FALSE => DataValue::Bool(false),
+                TAG_TRUE => DataValue::Bool(true),
+                TAG_INTEGER => DataValue::Number(Number::Integer(unzigzag(reader.varint()?))),
+                TAG_FLOAT => {
+                    let bytes = reader.take(8)?;
+                    let f = f64::from_le_bytes(bytes.try_into().expect("eight bytes"));
+                    DataValue::Number(Number::Float(f))
+                }
+                TAG_RAW_NUMBER => DataValue::Number(Number::Raw(reader.string()?)),
+                tag @ (TAG_BIG_INT | TAG_BIG_DECIMAL) => {
+                    DataValue::Number(reader.big_number(tag == TAG_BIG_DECIMAL, start)?)
+                }
+                TAG_STRING => DataValue::String(reader.string()?),
+                TAG_ARRAY => match reader.count()? {
+                    0 => DataValue::Array(&[]),
+                    len => {
+                        stack.push(Frame::Array(len, Vec::with_capacity(len)));
+                        continue;
+                    }
+                },
+                TAG_OBJECT => match reader.count()? {
+                    0 => DataValue::Object(&[]),
+                    len => {
+                        stack.push(Frame::Object(len, Vec::with_capacity(len), ""));
+                        continue;
+                    }
+                },
+                TAG_DATETIME => {
+                    let secs = unzigzag(reader.varint()?);
+                    let nanos = u32::try_from(reader.varint()?).ok();
+                    let dt = nanos.and_then(|nanos| DateTime::from_timestamp(secs, nanos));
+                    DataValue::DateTime(dt.ok_or_else(|| reader.error("Invalid DateTime", start))?)
+                }
+                TAG_DURATION => {
+                    let secs = unzigzag(reader.varint()?);
+                    let nanos = unzigzag(reader.varint()?);
+                    let dur = Duration::try_seconds(secs)
+                        .and_then(|dur| dur.checked_add(&Duration::nanoseconds(nanos)));
+                    DataValue::Duration(dur.ok_or_else(|| reader.error("Invalid duration", start))?)
+                }
+                TAG_RAW => DataValue::Raw(reader.string()?),
+                _ => return Err(reader.error("Unknown tag", start)),
+            };
+
+            // Hand the value to its container, closing finished containers
+            loop {
+                match stack.last_mut() {
+                    None if reader.pos < bytes.len() => {
+                        return Err(reader.error("Trailing bytes", reader.pos));
+                    }
+                    None => return Ok(value),
+                    Some(Frame::Array(len, items)) => {
+                        items.push(value);
+                        if items.len() < *len {
+                            break;
+                        }
+                    }
+                    Some(Frame::Object(len, entries, key)) => {
+                        entries.push((key, value));
+                        if entries.len() < *len {
+                            break;
+                        }
+                    }
+                }
+                value = match stack.pop() {
+                    Some(Frame::Array(_, items)) => {
+                        DataValue::Array(arena.alloc_slice_fill_iter(items))
+                    }
+                    Some(Frame::Object(_, entries, _)) => {
+                        DataValue::Object(arena.alloc_slice_fill_iter(entries))
+                    }
+                    None => unreachable!("a frame was open"),
+                };
+            }
+        }
+    }
+}
+
+fn zigzag(i: i64) -> u64 {
+    ((i << 1) ^ (i >> 63)) as u64
+}
+
+fn unzigzag(n: u64) -> i64 {
+    (n >> 1) as i64 ^ -((n & 1) as i64)
+}
+
+fn write_varint(output: &mut Vec<u8>, mut n: u64) {
+    while n >= 0x80 {
+        output.push(n as u8 | 0x80);
+        n >>= 7;
+    }
+    output.push(n as u8);
+}
+
+fn write_str(output: &mut Vec<u8>, s: &str) {
+    write_varint(output, s.len() as u64);
+    output.extend_from_slice(s.as_bytes());
+}
+
+struct Reader<'a, 'b> {
+    arena: &'a Bump,
+    bytes: &'b [u8],
+    pos: usize,
+}
+
+impl<'a, 'b> Reader<'a, 'b> {
+    fn error(&self, msg: &str, pos: usize) -> Error {
+        Error::syntax(format!("{} at byte {}", msg, pos))
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'b [u8]> {
+        let bytes = self
+            .bytes
+            .get(self.pos..)
+            .and_then(|rest| rest.get(..len))
+            .ok_or_else(|| self.error("Unexpected end of snapshot", self.bytes.len()))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn varint(&mut self) -> Result<u64> {
+        let start = self.pos;
+        let mut n = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            n |= u64::from(byte & 0x7F) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(n);
+            }
+        }
+        Err(self.error("Varint is too long", start))
+    }
+
+    /// Reads an element count, which cannot exceed the bytes left since every
+    /// element takes at least one
+    fn count(&mut self) -> Result<usize> {
+        let start = self.pos;
+        let count = self.varint()?;
+        usize::try_from(count)
+            .ok()
+            .filter(|&count| count <= self.bytes.len() - self.pos)
+            .ok_or_else(|| self.error("Element count exceeds the snapshot", start))
+    }
+
+    fn string(&mut self) -> Result<&'a str> {
+        let start = self.pos;
+        let len = usize::try_from(self.varint()?)
+            .map_err(|_| self.error("String length exceeds the snapshot", start))?;
+        let bytes = self.take(len)?;
+        let text =
+            std::str::from_utf8(bytes).map_err(|_| self.error("Invalid UTF-8 in string", start))?;
+        Ok(self.arena.alloc_str(text))
+    }
+
+    /// Reads the text of an arbitrary-precision number, kept as raw number
+    /// text when the `bignum` feature is disabled
+    fn big_number(&mut self, is_float: bool, start: usize) -> Result<Number<'a>> {
+        let text = self.string()?;
+        #[cfg(feature = "bignum")]
+        {
+            crate::bignum::parse_number(self.arena, text, is_float)
+                .ok_or_else(|| self.error("Invalid number", start))
+        }
+        #[cfg(not(feature = "bignum"))]
+        {
+            let _ = (is_float, start);
+            Ok(Number::Raw(text))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let arena = Bump::new();
+        let value = DataValue::from_str(
+            &arena,
+            r#"{"a": [null, true, false, -1, 9223372036854775807, -9223372036854775808, 0.1, -0.0],
+                "s": "tab\tquote\"é🚀", "": {}, "e": [], "nested": [[{"k": [[]]}]], "a": 1}"#,
+        )
+        .unwrap();
+        let snapshot = value.to_binary();
+        let loaded = DataValue::from_binary(&arena, &snapshot).unwrap();
+        // Repeated keys make objects unequal to themselves, so compare encodings
+        assert_eq!(loaded.to_binary(), snapshot);
+        assert_eq!(loaded["s"], value["s"]);
+        assert_eq!(
+            loaded["a"][7].as_f64().map(f64::is_sign_negative),
+            Some(true)
+        );
+
+        let special = [
+            DataValue::DateTime("2024-02-29T12:30:45.123456789Z".parse().unwrap()),
+            DataValue::DateTime("1900-01-01T00:00:00Z".parse().unwrap()),
+            DataValue::Duration(crate::helpers::parse_duration("-P1DT0.25S").unwrap()),
+            DataValue::Raw(r#"{"x": [1, 2]}"#),
+            DataValue::Number(Number::Raw("1.50")),
+        ];
+        let special = DataValue::Array(arena.alloc_slice_fill_iter(special));
+        assert_eq!(
+            DataValue::from_binary(&arena, &special.to_binary()).unwrap(),
+            special
+        );
+
+        #[cfg(feature = "bignum")]
+        {
+            let options = crate::ParseOptions {
+                number_mode: crate::NumberMode::Arbitrary,
+                ..Default::default()
+            };
+            let big = crate::from_str_with_options(
+                &arena,
+                "[-123456789012345678901234567890, 1.23456789012345678901234567890, 1e400]",
+                &options,
+            )
+            .unwrap();
+            assert_eq!(
+                DataValue::from_binary(&arena, &big.to_binary()).unwrap(),
+                big
+            );
+        }
+
+        // Nesting depth is limited only by memory in both directions
+        let mut deep = HEADER.to_vec();
+        for _ in 0..100_000 {
+            deep.extend_from_slice(&[TAG_ARRAY, 1]);
+        }
+        deep.extend_from_slice(&[TAG_ARRAY, 0]);
+        let loaded = DataValue::from_binary(&arena, &deep).unwrap();
+        assert_eq!(loaded.to_binary(), deep);
+    }
+
+    #[test]
+    fn test_malformed() {
+        let arena = Bump::new();
+        for bad in [
+            &b""[..],
+            b"DVB\x02\x00",
+            b"DVB\x01",
+            b"DVB\x01\x00\x00",
+            b"DVB\x01\x0e",
+            b"DVB\x01\x08\x05abc",
+            b"DVB\x01\x08\x01\xff",
+            b"DVB\x01\x09\xff\xff\xff\xff\x0f",
+            b"DVB\x01\x0a\x01\x01k",
+            b"DVB\x01\x03\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\x01",
+            b"DVB\x01\x0b\x00\xff\xff\xff\xff\x0f",
+        ] {
+            assert!(DataValue::from_binary(&arena, bad).is_err(), "{:?}", bad);
+        }
+    }
+}
@@ -0,0 +1,873 @@
+//! Incremental construction of documents
+//!
+//! [`DocumentBuilder`] assembles an object one key or JSON pointer path at a time
+//! and allocates it in the arena when it is built. A builder can be given a
+//! [`Shape`], in which case every edit is checked against it as it is made, so a
+//! document store can reject a bad write where it happens instead of validating the
+//! finished document.
+//!
+//! [`ValueBuilder`] writes a value front to back with chained calls, allocating
+//! keys and strings in the arena as it goes. It fills an [`ObjectBuilder`] or
+//! [`ArrayBuilder`], which collect entries in arena-backed vectors that become the
+//! finished value without being copied.
+
+use bumpalo::collections::Vec as BumpVec;
+use bumpalo::Bump;
+
+use crate::access::{escape_pointer_token, unescape_pointer_token};
+use crate::datavalue::{DataValue, DataValueType};
+use crate::error::{Error, Result};
+
+/// The expected structure of a document
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::builder::Shape;
+/// use datavalue_rs::DataValueType;
+///
+/// let user = Shape::object()
+///     .field("name", Shape::Type(DataValueType::String))
+///     .field("tags", Shape::array(Shape::Type(DataValueType::String)))
+///     .field("manager", Shape::nullable(Shape::Type(DataValueType::String)))
+///     .closed();
+/// assert!(user.field_shape("name").is_some());
+/// assert!(user.field_shape("email").is_none());
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum Shape {
+    /// Any value
+    Any,
+    /// A value of one type; `Float` also accepts integers
+    Type(DataValueType),
+    /// `null`, or a value of the inner shape
+    Nullable(Box<Shape>),
+    /// An array whose elements all have the inner shape
+    Array(Box<Shape>),
+    /// An object with the given fields
+    Object {
+        /// Declared fields and their shapes
+        fields: Vec<(String, Shape)>,
+        /// Whether fields that are not declared are rejected
+        closed: bool,
+    },
+}
+
+impl Shape {
+    /// Returns an open object shape with no declared fields
+    pub fn object() -> Self {
+        Shape::Object {
+            fields: Vec::new(),
+            closed: false,
+        }
+    }
+
+    /// Returns an array shape with the given element shape
+    pub fn array(element: Shape) -> Self {
+        Shape::Array(Box::new(element))
+    }
+
+    /// Returns a shape that also accepts `null`
+    pub fn nullable(inner: Shape) -> Self {
+        Shape::Nullable(Box::new(inner))
+    }
+
+    /// Declares a field of an object shape; has no effect on other shapes
+    pub fn field(mut self, name: &str, shape: Shape) -> Self {
+        if let Shape::Object { fields, .. } = &mut self {
+            fields.push((name.to_string(), shape));
+        }
+        self
+    }
+
+    /// Rejects fields that are not declared; has no effect on non-object shapes
+    pub fn closed(mut self) -> Self {
+        if let Shape::Object { closed, .. } = &mut self {
+            *closed = true;
+        }
+        self
+    }
+
+    /// Returns the shape of an object field
+    ///
+    /// Returns None if this is a closed object shape that does not declare the
+    /// field, or a shape that cannot hold fields at all.
+    pub fn field_shape(&self, name: &str) -> Option<&Shape> {
+        match self {
+            Shape::Any => Some(&Shape::Any),
+            Shape::Nullable(inner) => inner.field_shape(name),
+            Shape::Object { fields, closed } => {
+                match fields.iter().find(|(field, _)| field == name) {
+                    Some((_, shape)) => Some(shape),
+                    None if *closed => None,
+                    None => Some(&Shape::Any),
+                }
+            }
+            Shape::Type(_) | Shape::Array(_) => None,
+        }
+    }
+
+    /// Returns the shape of an array element, or None for shapes that are not arrays
+    fn element_shape(&self) -> Option<&Shape> {
+        match self {
+            Shape::Any => Some(&Shape::Any),
+            Shape::Nullable(inner) => inner.element_shape(),
+            Shape::Array(element) => Some(element),
+            Shape::Type(_) | Shape::Object { .. } => None,
+        }
+    }
+
+    /// Checks a value against the shape
+    ///
+    /// # Errors
+    ///
+    /// Returns an error naming the JSON pointer path of the first value that does
+    /// not match.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use datavalue_rs::{builder::Shape, from_str, Bump, DataValueType};
+    ///
+    /// let arena = Bump::new();
+    /// let shape = Shape::array(Shape::Type(DataValueType::Integer));
+    /// assert!(shape.check(&from_str(&arena, "[1, 2]").unwrap()).is_ok());
+    ///
+    /// let err = shape.check(&from_str(&arena, r#"[1, "2"]"#).unwrap()).unwrap_err();
+    /// assert_eq!(err.to_string(), "/1: expected integer, found string");
+    /// ```
+    pub fn check(&self, value: &DataValue<'_>) -> Result<()> {
+        self.check_at(value, String::new())
+    }
+
+    /// Checks a value found at `path`
+    fn check_at(&self, value: &DataValue<'_>, path: String) -> Result<()> {
+        let mut pending = vec![(self, value, path)];
+        while let Some((shape, value, path)) = pending.pop() {
+            let mismatch = |expected: &str| {
+                Error::custom(format!(
+                    "{}: expected {}, found {}",
+                    path,
+                    expected,
+                    type_name(value.get_type())
+                ))
+            };
+            match (shape, value) {
+                (Shape::Any, _) => {}
+                (Shape::Nullable(_), DataValue::Null) => {}
+                (Shape::Nullable(inner), value) => pending.push((inner, value, path)),
+                (Shape::Type(DataValueType::Float), DataValue::Number(_)) => {}
+                (Shape::Type(expected), value) => {
+                    if value.get_type() != *expected {
+                        return Err(mismatch(&type_name(*expected)));
+                    }
+                }
+                (Shape::Array(element), DataValue::Array(items)) => {
+                    for (i, item) in items.iter().enumerate() {
+                        pending.push((element, item, format!("{}/{}", path, i)));
+                    }
+                }
+                (Shape::Array(_), _) => return Err(mismatch("array")),
+                (Shape::Object { .. }, DataValue::Object(entries)) => {
+                    for (key, item) in entries.iter() {
+                        let item_path = format!("{}/{}", path, escape_pointer_token(key));
+                        let Some(field) = shape.field_shape(key) else {
+                            return Err(Error::custom(format!(
+                                "{}: field is not allowed by the shape",
+                                item_path
+                            )));
+                        };
+                        pending.push((field, item, item_path));
+                    }
+                }
+                (Shape::Object { .. }, _) => return Err(mismatch("object")),
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns true if the shape admits an object, so a path can be created through it
+    fn accepts_object(&self) -> bool {
+        match self {
+            Shape::Any | Shape::Object { .. } => true,
+            Shape::Nullable(inner) => inner.accepts_object(),
+            Shape::Type(_) | Shape::Array(_) => false,
+        }
+    }
+}
+
+fn type_name(value_type: DataValueType) -> String {
+    format!("{:?}", value_type).to_lowercase()
+}
+
+/// A node of the document being built
+#[derive(Debug)]
+enum Node<'a> {
+    Value(DataValue<'a>),
+    Object(Vec<(String, Node<'a>)>),
+    Array(Vec<Node<'a>>),
+}
+
+impl<'a> Node<'a> {
+    /// Turns a stored array or object value into an editable node
+    fn expand(&mut self) {
+        match self {
+            Node::Value(DataValue::Object(entries)) => {
+                *self = Node::Object(
+                    entries
+                        .iter()
+                        .map(|(key, value)| (key.to_string(), Node::Value(value.clone())))
+                        .collect(),
+                );
+            }
+            Node::Value(DataValue::Array(items)) => {
+                *self = Node::Array(items.iter().cloned().map(Node::Value).collect());
+            }
+            _ => {}
+        }
+    }
+
+    /// Allocates the node in the arena
+    fn build(self, arena: &'a Bump) -> DataValue<'a> {
+        match self {
+            Node::Value(value) => value,
+            Node::Object(entries) => {
+                let entries: Vec<(&'a str, DataValue<'a>)> = entries
+                    .into_iter()
+                    .map(|(key, node)| (&*arena.alloc_str(&key), node.build(arena)))
+                    .collect();
+                DataValue::Object(arena.alloc_slice_clone(&entries))
+            }
+            Node::Array(items) => {
+                let items: Vec<DataValue<'a>> =
+                    items.into_iter().map(|node| node.build(arena)).collect();
+                DataValue::Array(arena.alloc_slice_clone(&items))
+            }
+        }
+    }
+}
+
+/// Builds an object document one key or path at a time
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::{builder::{DocumentBuilder, Shape}, helpers, to_string, Bump, DataValueType};
+///
+/// let arena = Bump::new();
+/// let shape = Shape::object()
+///     .field("id", Shape::Type(DataValueType::Integer))
+///     .field("owner", Shape::object().field("name", Shape::Type(DataValueType::String)))
+///     .closed();
+///
+/// let mut builder = DocumentBuilder::new(&arena).with_shape(shape);
+/// builder
+///     .insert("id", helpers::int(7))
+///     .unwrap()
+///     .set_path("/owner/name", helpers::string(&arena, "ada"))
+///     .unwrap();
+///
+/// // Edits that do not fit the shape are rejected and leave the builder unchanged
+/// assert!(builder.insert("id", helpers::string(&arena, "7")).is_err());
+/// assert!(builder.insert("extra", helpers::null()).is_err());
+///
+/// assert_eq!(to_string(&builder.build()), r#"{"id":7,"owner":{"name":"ada"}}"#);
+/// ```
+#[derive(Debug)]
+pub struct DocumentBuilder<'a> {
+    arena: &'a Bump,
+    root: Node<'a>,
+    shape: Option<Shape>,
+}
+
+impl<'a> DocumentBuilder<'a> {
+    /// Creates a builder for an empty object
+    pub fn new(arena: &'a Bump) -> Self {
+        DocumentBuilder {
+            arena,
+            root: Node::Object(Vec::new()),
+            shape: None,
+        }
+    }
+
+    /// Validates every later edit against `shape`
+    pub fn with_shape(mut self, shape: Shape) -> Self {
+        self.shape = Some(shape);
+        self
+    }
+
+    /// Sets a top-level key, replacing an earlier value for the same key
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the builder has a shape and the edit does not match it.
+    pub fn insert(&mut self, key: &str, value: DataValue<'a>) -> Result<&mut Self> {
+        self.set_tokens(&[key.to_string()], value)
+    }
+
+    /// Sets the value at a JSON pointer path, creating intermediate objects
+    ///
+    /// Existing array elements can be replaced by index, and `-` or the array
+    /// length appends an element.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the pointer is malformed, runs through a scalar or past
+    /// the end of an array, or, when the builder has a shape, if the edit does not
+    /// match it. The builder is unchanged after an error.
+    pub fn set_path(&mut self, pointer: &str, value: DataValue<'a>) -> Result<&mut Self> {
+        let Some(tokens) = pointer.strip_prefix('/') else {
+            return Err(Error::custom(format!(
+                "invalid JSON pointer {:?}: must start with '/'",
+                pointer
+            )));
+        };
+        let tokens: Vec<String> = tokens
+            .split('/')
+            .map(|token| unescape_pointer_token(token).into_owned())
+            .collect();
+        self.set_tokens(&tokens, value)
+    }
+
+    /// Checks the edit against the shape, then applies it
+    fn set_tokens(&mut self, tokens: &[String], value: DataValue<'a>) -> Result<&mut Self> {
+        if let Some(shape) = &self.shape {
+            let mut shape = shape;
+            let mut path = String::new();
+            for token in tokens {
+                // Every container on the path is, or will be created as, an array
+                // or object; only existing arrays are indexed
+                let is_array = self.is_array(&path);
+                let next = if is_array {
+                    shape.element_shape()
+                } else if shape.accepts_object() {
+                    shape.field_shape(token)
+                } else {
+                    None
+                };
+                path = format!("{}/{}", path, escape_pointer_token(token));
+                shape = next.ok_or_else(|| {
+                    Error::custom(format!("{}: path is not allowed by the shape", path))
+                })?;
+            }
+            shape.check_at(&value, path)?;
+        }
+
+        let mut node = &mut self.root;
+        let mut path = String::new();
+        for token in tokens {
+            path = format!("{}/{}", path, escape_pointer_token(token));
+            node.expand();
+            node = match node {
+                Node::Object(entries) => match entries.iter().position(|(key, _)| key == token) {
+                    Some(i) => &mut entries[i].1,
+                    None => {
+                        entries.push((token.clone(), Node::Object(Vec::new())));
+                        &mut entries.last_mut().expect("just pushed").1
+                    }
+                },
+                Node::Array(items) => {
+                    let index = match token.as_str() {
+                        "-" => items.len(),
+                        token => token
+                            .parse::<usize>()
+                            .map_err(|_| Error::custom(format!("{}: invalid array index", path)))?,
+                    };
+                    if index == items.len() {
+                        items.push(Node::Object(Vec::new()));
+                    }
+                    items
+                        .get_mut(index)
+                        .ok_or_else(|| Error::out_of_bounds(index))?
+                }
+                Node::Value(value) => {
+                    return Err(Error::custom(format!(
+                        "{}: cannot set a path inside a {}",
+                        path,
+                        type_name(value.get_type())
+                    )))
+                }
+            };
+        }
+        *node = Node::Value(value);
+        Ok(self)
+    }
+
+    /// Returns true if an array exists at an escaped JSON pointer path
+    fn is_array(&self, pointer: &str) -> bool {
+        let tokens: Vec<&str> = pointer.split('/').skip(1).collect();
+        let mut node = &self.root;
+        for (i, token) in tokens.iter().enumerate() {
+            let token = unescape_pointer_token(token);
+            node = match node {
+                Node::Object(entries) => match entries.iter().find(|(key, _)| *key == token) {
+                    Some((_, child)) => child,
+                    None => return false,
+                },
+                Node::Array(items) => {
+                    match token.parse::<usize>().ok().and_then(|i| items.get(i)) {
+                        Some(child) => child,
+                        None => return false,
+                    }
+                }
+                Node::Value(value) => {
+                    let rest = format!("/{}", tokens[i..].join("/"));
+                    return matches!(value.pointer(&rest), Some(DataValue::Array(_)));
+                }
+            };
+        }
+        matches!(node, Node::Array(_) | Node::Value(DataValue::Array(_)))
+    }
+
+    /// Allocates the document in the arena
+    pub fn build(self) -> DataValue<'a> {
+        self.root.build(self.arena)
+    }
+}
+
+/// Collects the entries of an object in the arena
+///
+/// Unlike [`helpers::object`](crate::helpers::object), which copies a `Vec` of
+/// entries into the arena, the entries are pushed straight into an arena-backed
+/// vector, and [`finish`](ObjectBuilder::finish) turns that vector into the
+/// object's slice in place. Repeated keys are kept, as the parser keeps them.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::{builder::{ArrayBuilder, ObjectBuilder}, helpers, to_string, Bump};
+///
+/// let arena = Bump::new();
+/// let tags = ArrayBuilder::new(&arena)
+///     .push(helpers::string(&arena, "admin"))
+///     .push(7)
+///     .finish();
+/// let user = ObjectBuilder::with_capacity(&arena, 2)
+///     .insert("name", helpers::string(&arena, "John"))
+///     .insert("tags", tags)
+///     .finish();
+/// assert_eq!(to_string(&user), r#"{"name":"John","tags":["admin",7]}"#);
+/// ```
+#[derive(Debug)]
+pub struct ObjectBuilder<'a> {
+    arena: &'a Bump,
+    entries: BumpVec<'a, (&'a str, DataValue<'a>)>,
+}
+
+impl<'a> ObjectBuilder<'a> {
+    /// Creates a builder for an empty object
+    pub fn new(arena: &'a Bump) -> Self {
+        Self::with_capacity(arena, 0)
+    }
+
+    /// Creates a builder with room for `capacity` entries before it reallocates
+    pub fn with_capacity(arena: &'a Bump, capacity: usize) -> Self {
+        ObjectBuilder {
+            arena,
+            entries: BumpVec::with_capacity_in(capacity, arena),
+        }
+    }
+
+    /// Appends an entry, copying the key into the arena
+    pub fn insert(mut self, key: &str, value: impl Into<DataValue<'a>>) -> Self {
+        self.push(key, value);
+        self
+    }
+
+    /// Appends an entry through a reference, for filling the builder in a loop
+    pub fn push(&mut self, key: &str, value: impl Into<DataValue<'a>>) {
+        let key = &*self.arena.alloc_str(key);
+        self.entries.push((key, value.into()));
+    }
+
+    /// Returns the number of entries added so far
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if no entry has been added
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the object, whose entries stay where they were collected
+    pub fn finish(self) -> DataValue<'a> {
+        DataValue::Object(self.entries.into_bump_slice())
+    }
+}
+
+/// Collects the elements of an array in the arena
+///
+/// The array counterpart of [`ObjectBuilder`]: elements are pushed into an
+/// arena-backed vector, which [`finish`](ArrayBuilder::finish) turns into the
+/// array's slice without copying.
+#[derive(Debug)]
+pub struct ArrayBuilder<'a> {
+    items: BumpVec<'a, DataValue<'a>>,
+}
+
+impl<'a> ArrayBuilder<'a> {
+    /// Creates a builder for an empty array
+    pub fn new(arena: &'a Bump) -> Self {
+        Self::with_capacity(arena, 0)
+    }
+
+    /// Creates a builder with room for `capacity` elements before it reallocates
+    pub fn with_capacity(arena: &'a Bump, capacity: usize) -> Self {
+        ArrayBuilder {
+            items: BumpVec::with_capacity_in(capacity, arena),
+        }
+    }
+
+    /// Appends an element
+    pub fn push(mut self, value: impl Into<DataValue<'a>>) -> Self {
+        self.items.push(value.into());
+        self
+    }
+
+    /// Appends every element of `values`
+    pub fn extend<I>(mut self, values: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<DataValue<'a>>,
+    {
+        self.items.extend(values.into_iter().map(Into::into));
+        self
+    }
+
+    /// Returns the number of elements added so far
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Returns true if no element has been added
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Returns the array, whose elements stay where they were collected
+    pub fn finish(self) -> DataValue<'a> {
+        DataValue::Array(self.items.into_bump_slice())
+    }
+}
+
+/// The array or object a [`ValueBuilder`] is filling
+#[derive(Debug)]
+enum Container<'a> {
+    Object {
+        entries: ObjectBuilder<'a>,
+        key: Option<&'a str>,
+    },
+    Array(ArrayBuilder<'a>),
+}
+
+/// Builds an object or array with chained calls
+///
+/// In an object, each value follows the [`key`](ValueBuilder::key) it belongs to;
+/// in an array, values are appended in order. Nested arrays and objects are
+/// built by closures that receive a builder of their own. Keys and strings are
+/// copied into the arena, and entries are collected there as by
+/// [`ObjectBuilder`] and [`ArrayBuilder`].
+///
+/// # Panics
+///
+/// The methods panic when the calls do not describe a valid value: a key in an
+/// array, a value in an object without a key before it, two keys in a row, or a
+/// key with no value when the object is built.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::{builder::ValueBuilder, to_string, Bump};
+///
+/// let arena = Bump::new();
+/// let user = ValueBuilder::new(&arena)
+///     .key("name").string("John")
+///     .key("age").value(30)
+///     .key("tags").array(|a| a.string("admin").string("ops"))
+///     .key("address").object(|o| o.key("city").string("Paris").key("zip").null())
+///     .build();
+/// assert_eq!(
+///     to_string(&user),
+///     r#"{"name":"John","age":30,"tags":["admin","ops"],"address":{"city":"Paris","zip":null}}"#
+/// );
+/// ```
+#[derive(Debug)]
+pub struct ValueBuilder<'a> {
+    arena: &'a Bump,
+    container: Container<'a>,
+}
+
+impl<'a> ValueBuilder<'a> {
+    /// Creates a builder for an object
+    pub fn new(arena: &'a Bump) -> Self {
+        ValueBuilder {
+            arena,
+            container: Container::Object {
+                entries: ObjectBuilder::new(arena),
+                key: None,
+            },
+        }
+    }
+
+    /// Creates a builder for an array
+    pub fn new_array(arena: &'a Bump) -> Self {
+        ValueBuilder {
+            arena,
+            container: Container::Array(ArrayBuilder::new(arena)),
+        }
+    }
+
+    /// Sets the key of the next object entry
+    ///
+    /// # Panics
+    ///
+    /// Panics if this builds an array, or the previous key has no value yet.
+    pub fn key(mut self, key: &str) -> Self {
+        match &mut self.container {
+            Container::Object {
+                key: Some(pending), ..
+            } => {
+                panic!(
+                    "key {:?} follows key {:?}, which has no value",
+                    key, pending
+                )
+            }
+            Container::Object { key: pending, .. } => *pending = Some(self.arena.alloc_str(key)),
+            Container::Array(_) => panic!("key {:?} in an array", key),
+        }
+        self
+    }
+
+    /// Adds a value: the next array element, or the value of the pending key
+    ///
+    /// # Panics
+    ///
+    /// Panics if this builds an object and no key is pending.
+    pub fn value(mut self, value: impl Into<DataValue<'a>>) -> Self {
+        let value = value.into();
+        match &mut self.container {
+            Container::Object { entries, key } => {
+                let key = key.take().expect("object value without a key");
+                entries.entries.push((key, value));
+            }
+            Container::Array(items) => items.items.push(value),
+        }
+        self
+    }
+
+    /// Adds `null`
+    pub fn null(self) -> Self {
+        self.value(DataValue::Null)
+    }
+
+    /// Adds a boolean
+    pub fn bool(self, value: bool) -> Self {
+        self.value(DataValue::Bool(value))
+    }
+
+    /// Adds an integer
+    pub fn int(self, value: i64) -> Self {
+        self.value(value)
+    }
+
+    /// Adds a float
+    pub fn float(self, value: f64) -> Self {
+        self.value(value)
+    }
+
+    /// Adds a string, copying it into the arena
+    pub fn string(self, value: &str) -> Self {
+        let value = DataValue::String(self.arena.alloc_str(value));
+        self.value(value)
+    }
+
+    /// Adds an array whose elements are added by `build`
+    pub fn array(self, build: impl FnOnce(ValueBuilder<'a>) -> ValueBuilder<'a>) -> Self {
+        let value = build(ValueBuilder::new_array(self.arena)).build();
+        self.value(value)
+    }
+
+    /// Adds an object whose entries are added by `build`
+    pub fn object(self, build: impl FnOnce(ValueBuilder<'a>) -> ValueBuilder<'a>) -> Self {
+        let value = build(ValueBuilder::new(self.arena)).build();
+        self.value(value)
+    }
+
+    /// Returns the finished object or array
+    ///
+    /// # Panics
+    ///
+    /// Panics if the last key of an object has no value.
+    pub fn build(self) -> DataValue<'a> {
+        match self.container {
+            Container::Object { key: Some(key), .. } => {
+                panic!("key {:?} has no value", key)
+            }
+            Container::Object { entries, .. } => entries.finish(),
+            Container::Array(items) => items.finish(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{from_str, helpers, to_string};
+
+    #[test]
+    fn test_set_path() {
+        let arena = Bump::new();
+        let mut builder = DocumentBuilder::new(&arena);
+        builder
+            .insert("list", from_str(&arena, r#"[{"a": 1}, 2]"#).unwrap())
+            .unwrap()
+            .set_path("/list/0/b", helpers::int(3))
+            .unwrap()
+            .set_path("/list/-", helpers::int(4))
+            .unwrap()
+            .set_path("/x~1y/z", helpers::boolean(true))
+            .unwrap()
+            .insert("list2", helpers::null())
+            .unwrap();
+        assert!(builder.set_path("/list/1/c", helpers::int(0)).is_err());
+        assert!(builder.set_path("/list/9", helpers::int(0)).is_err());
+        assert!(builder.set_path("list", helpers::int(0)).is_err());
+        assert_eq!(
+            to_string(&builder.build()),
+            r#"{"list":[{"a":1,"b":3},2,4],"x/y":{"z":true},"list2":null}"#
+        );
+    }
+
+    #[test]
+    fn test_shape_validation() {
+        let arena = Bump::new();
+        let shape = Shape::object()
+            .field("tags", Shape::array(Shape::Type(DataValueType::String)))
+            .field("score", Shape::nullable(Shape::Type(DataValueType::Float)))
+            .field(
+                "meta",
+                Shape::object()
+                    .field("v", Shape::Type(DataValueType::Integer))
+                    .closed(),
+            )
+            .field(
+                "nested",
+                Shape::object().field("list", Shape::array(Shape::Type(DataValueType::Integer))),
+            )
+            .closed();
+        let mut builder = DocumentBuilder::new(&arena).with_shape(shape);
+        builder
+            .insert("tags", from_str(&arena, r#"["a"]"#).unwrap())
+            .unwrap()
+            .set_path("/tags/-", helpers::string(&arena, "b"))
+            .unwrap()
+            .insert("score", helpers::int(3))
+            .unwrap()
+            .insert("score", helpers::null())
+            .unwrap()
+            .set_path("/meta/v", helpers::int(1))
+            .unwrap()
+            .insert("nested", from_str(&arena, r#"{"list": [1]}"#).unwrap())
+            .unwrap()
+            .set_path("/nested/list/-", helpers::int(2))
+            .unwrap();
+
+        let err = |result: Result<&mut DocumentBuilder<'_>>| result.unwrap_err().to_string();
+        assert_eq!(
+            err(builder.set_path("/tags/0", helpers::int(1))),
+            "/tags/0: expected string, found integer"
+        );
+        assert_eq!(
+            err(builder.insert("tags", from_str(&arena, r#"["a", 1]"#).unwrap())),
+            "/tags/1: expected string, found integer"
+        );
+        assert_eq!(
+            err(builder.set_path("/meta/w", helpers::int(1))),
+            "/meta/w: path is not allowed by the shape"
+        );
+        assert_eq!(
+            err(builder.insert("meta", from_str(&arena, r#"{"w": 1}"#).unwrap())),
+            "/meta/w: field is not allowed by the shape"
+        );
+        assert_eq!(
+            err(builder.set_path("/score/x", helpers::int(1))),
+            "/score/x: path is not allowed by the shape"
+        );
+        assert_eq!(
+            to_string(&builder.build()),
+            r#"{"tags":["a","b"],"score":null,"meta":{"v":1},"nested":{"list":[1,2]}}"#
+        );
+    }
+
+    #[test]
+    fn test_value_builder() {
+        let arena = Bump::new();
+        let value = ValueBuilder::new(&arena)
+            .key("a")
+            .bool(true)
+            .key("b")
+            .float(1.5)
+            .key("c")
+            .array(|a| {
+                a.int(1)
+                    .value(helpers::string(&arena, "x"))
+                    .array(|a| a)
+                    .object(|o| o.key("d").null())
+            })
+            .key("e")
+            .object(|o| o)
+            .key("a")
+            .int(2)
+            .build();
+        assert_eq!(
+            to_string(&value),
+            r#"{"a":true,"b":1.5,"c":[1,"x",[],{"d":null}],"e":{},"a":2}"#
+        );
+        assert_eq!(to_string(&ValueBuilder::new_array(&arena).build()), "[]");
+    }
+
+    #[test]
+    #[should_panic(expected = "object value without a key")]
+    fn test_value_builder_value_without_key() {
+        let arena = Bump::new();
+        ValueBuilder::new(&arena).int(1);
+    }
+
+    #[test]
+    #[should_panic(expected = "key \"k\" in an array")]
+    fn test_value_builder_key_in_array() {
+        let arena = Bump::new();
+        ValueBuilder::new_array(&arena).key("k");
+    }
+
+    #[test]
+    #[should_panic(expected = "key \"k\" has no value")]
+    fn test_value_builder_dangling_key() {
+        let arena = Bump::new();
+        ValueBuilder::new(&arena).key("k").build();
+    }
+
+    #[test]
+    fn test_object_and_array_builders() {
+        let arena = Bump::new();
+        let mut object = ObjectBuilder::new(&arena);
+        assert!(object.is_empty());
+        for (i, key) in ["a", "b", "a"].iter().enumerate() {
+            object.push(key, i as i64);
+        }
+        let items = ArrayBuilder::with_capacity(&arena, 4)
+            .push(true)
+            .extend([1.5, 2.5])
+            .push(helpers::null());
+        assert_eq!(items.len(), 4);
+        let value = object.insert("items", items.finish()).finish();
+        assert_eq!(
+            to_string(&value),
+            r#"{"a":0,"b":1,"a":2,"items":[true,1.5,2.5,null]}"#
+        );
+        assert_eq!(value.get_all("a").count(), 2);
+        assert_eq!(to_string(&ArrayBuilder::new(&arena).finish()), "[]");
+        assert_eq!(to_string(&ObjectBuilder::new(&arena).finish()), "{}");
+    }
+}
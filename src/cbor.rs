@@ -0,0 +1,920 @@
+//! CBOR input and output, enabled by the `cbor` feature
+//!
+//! [CBOR](https://www.rfc-editor.org/rfc/rfc8949) is a binary encoding of the
+//! JSON data model with a few extras. [`from_slice`] decodes one CBOR data item
+//! and [`to_vec`] encodes a value, so the same DataValue can travel as CBOR and
+//! be printed as JSON.
+//!
+//! CBOR items map onto DataValue as follows:
+//!
+//! | CBOR | DataValue |
+//! |---|---|
+//! | unsigned and negative integers, bignums (tags 2 and 3) | [`Number::Integer`], or [`Number::BigInt`] outside the `i64` range |
+//! | decimal fractions (tag 4) | [`Number::BigDecimal`] |
+//! | half, single and double floats | [`Number::Float`] |
+//! | `false`, `true`, `null`, `undefined` | [`DataValue::Bool`], [`DataValue::Null`] |
+//! | text string | [`DataValue::String`] |
+//! | byte string | `{"$bytes": base64}` |
+//! | array | [`DataValue::Array`] |
+//! | map | [`DataValue::Object`], with integer keys as their decimal text |
+//! | date/time string (tag 0), epoch time (tag 1), extended time (tag 1001) | [`DataValue::DateTime`] |
+//! | duration (tag 1002) | [`DataValue::Duration`] |
+//! | any other tag | `{"$tag": number, "$value": value}` |
+//!
+//! The encoder turns those objects back into byte strings and tags, writes
+//! integers in the smallest head that holds them and bignums only when they do
+//! not fit in 64 bits, and writes floats as single precision when that is exact.
+//! DateTimes are written as tag 0 strings in UTC, and durations as tag 1002 maps
+//! of seconds and nanoseconds (RFC 9581). The self-described CBOR tag 55799 is
+//! skipped on input.
+//!
+//! # Example
+//!
+//! ```
+//! use datavalue_rs::{cbor, from_str, Bump};
+//!
+//! let arena = Bump::new();
+//! let value = from_str(&arena, r#"{"id": 18446744073709551615, "tags": ["a"]}"#).unwrap();
+//! let bytes = cbor::to_vec(&value).unwrap();
+//! assert_eq!(bytes[..4], [0xA2, 0x62, b'i', b'd']);
+//! assert_eq!(cbor::from_slice(&arena, &bytes).unwrap(), value);
+//! ```
+
+use std::borrow::Cow;
+
+use bumpalo::Bump;
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+
+use crate::base64;
+use crate::bignum::{parse_number, to_bytes, to_decimal};
+use crate::datavalue::{DataValue, Number};
+use crate::error::{Error, Result};
+
+const BYTES_KEY: &str = "$bytes";
+const TAG_KEY: &str = "$tag";
+const VALUE_KEY: &str = "$value";
+
+/// Tags this module reads or writes
+const TAG_DATETIME_STRING: u64 = 0;
+const TAG_EPOCH_TIME: u64 = 1;
+const TAG_POSITIVE_BIGNUM: u64 = 2;
+const TAG_NEGATIVE_BIGNUM: u64 = 3;
+const TAG_DECIMAL_FRACTION: u64 = 4;
+const TAG_EXTENDED_TIME: u64 = 1001;
+const TAG_DURATION: u64 = 1002;
+const TAG_SELF_DESCRIBED: u64 = 55799;
+
+/// The additional information value for indefinite lengths
+const INDEFINITE: u8 = 31;
+const BREAK: u8 = 0xFF;
+
+/// Decodes one CBOR data item
+///
+/// # Errors
+///
+/// Returns a syntax error with the byte offset of the problem if the input is
+/// not well-formed CBOR, is followed by more bytes, or contains a text string
+/// that is not UTF-8, a map key that is not a string or integer, a simple value
+/// other than `false`, `true`, `null` and `undefined`, or a malformed time,
+/// bignum or decimal fraction.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::{cbor, Bump};
+///
+/// let arena = Bump::new();
+/// // {1: h'0102', "t": 1(0)}
+/// let value = cbor::from_slice(&arena, b"\xA2\x01\x42\x01\x02\x61t\xC1\x00").unwrap();
+/// assert_eq!(value["1"]["$bytes"].as_str(), Some("AQI="));
+/// assert_eq!(value["t"].as_datetime().unwrap().timestamp(), 0);
+/// ```
+pub fn from_slice<'a>(arena: &'a Bump, bytes: &[u8]) -> Result<DataValue<'a>> {
+    let mut reader = Reader {
+        arena,
+        bytes,
+        pos: 0,
+    };
+    let value = reader.read_item()?;
+    if reader.pos < bytes.len() {
+        return Err(reader.error("Trailing bytes after CBOR item"));
+    }
+    Ok(value)
+}
+
+/// Encodes a value as one CBOR data item
+///
+/// # Errors
+///
+/// Returns an error if a `$bytes` or `$tag` object is malformed, or a raw value
+/// is not valid JSON.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::{cbor, from_str, Bump};
+///
+/// let arena = Bump::new();
+/// let value = from_str(&arena, r#"[1, -1, 1.5, {"$bytes": "AQI="}]"#).unwrap();
+/// assert_eq!(
+///     cbor::to_vec(&value).unwrap(),
+///     b"\x84\x01\x20\xFA\x3F\xC0\x00\x00\x42\x01\x02"
+/// );
+/// ```
+pub fn to_vec(value: &DataValue<'_>) -> Result<Vec<u8>> {
+    let mut output = Vec::new();
+    write_item(value, &mut output)?;
+    Ok(output)
+}
+
+/// An open array, map or tag
+enum Frame<'a> {
+    Array {
+        /// Items still to read, or None until a break for indefinite lengths
+        remaining: Option<u64>,
+        items: Vec<DataValue<'a>>,
+    },
+    Map {
+        /// Entries still to read, or None until a break for indefinite lengths
+        remaining: Option<u64>,
+        entries: Vec<(&'a str, DataValue<'a>)>,
+        /// Key of the entry whose value is being read
+        key: Option<&'a str>,
+    },
+    Tag(u64),
+}
+
+struct Reader<'a, 'b> {
+    arena: &'a Bump,
+    bytes: &'b [u8],
+    pos: usize,
+}
+
+impl<'a, 'b> Reader<'a, 'b> {
+    fn error(&self, msg: &str) -> Error {
+        Error::syntax(format!("{} at byte {}", msg, self.pos))
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        let b = *self
+            .bytes
+            .get(self.pos)
+            .ok_or_else(|| self.error("Truncated CBOR item"))?;
+        self.pos += 1;
+        Ok(b)
+    }
+
+    fn take(&mut self, len: u64) -> Result<&'b [u8]> {
+        let available = self.bytes.len() - self.pos;
+        let len = usize::try_from(len)
+            .ok()
+            .filter(|&len| len <= available)
+            .ok_or_else(|| self.error("Truncated CBOR item"))?;
+        self.pos += len;
+        Ok(&self.bytes[self.pos - len..self.pos])
+    }
+
+    /// Reads the argument that follows an initial byte with additional
+    /// information `info`, or None for an indefinite length
+    fn argument(&mut self, info: u8) -> Result<Option<u64>> {
+        let len = match info {
+            0..=23 => return Ok(Some(u64::from(info))),
+            24 => 1,
+            25 => 2,
+            26 => 4,
+            27 => 8,
+            INDEFINITE => return Ok(None),
+            _ => {
+                self.pos -= 1;
+                return Err(self.error("Reserved CBOR additional information"));
+            }
+        };
+        let bytes = self.take(len)?;
+        Ok(Some(bytes.iter().fold(0u64, |n, &b| n << 8 | u64::from(b))))
+    }
+
+    /// Reads the contents of a byte or text string after its initial byte,
+    /// joining the chunks of an indefinite-length string
+    fn string(&mut self, major: u8, length: Option<u64>) -> Result<Cow<'b, [u8]>> {
+        if let Some(len) = length {
+            return Ok(Cow::Borrowed(self.take(len)?));
+        }
+        let mut joined = Vec::new();
+        loop {
+            let initial = self.byte()?;
+            if initial == BREAK {
+                return Ok(Cow::Owned(joined));
+            }
+            if initial >> 5 != major || initial & 0x1F == INDEFINITE {
+                self.pos -= 1;
+                return Err(self.error("Invalid chunk in indefinite-length CBOR string"));
+            }
+            let len = self.argument(initial & 0x1F)?.unwrap_or_default();
+            joined.extend_from_slice(self.take(len)?);
+        }
+    }
+
+    /// Returns the value of an integer; `negative` items stand for -1 - `n`
+    fn integer(&self, negative: bool, n: u64) -> DataValue<'a> {
+        let value = if negative {
+            -1 - i128::from(n)
+        } else {
+            i128::from(n)
+        };
+        match i64::try_from(value) {
+            Ok(i) => DataValue::Number(Number::Integer(i)),
+            Err(_) => self.big_integer(&value.to_string()),
+        }
+    }
+
+    /// Returns the value of decimal digits with an optional sign
+    fn big_integer(&self, text: &str) -> DataValue<'a> {
+        let number = parse_number(self.arena, text, false).expect("text is an integer");
+        DataValue::Number(number)
+    }
+
+    /// Reads a bignum's byte string
+    fn bignum(&mut self, negative: bool) -> Result<DataValue<'a>> {
+        let initial = self.byte()?;
+        if initial >> 5 != 2 {
+            self.pos -= 1;
+            return Err(self.error("CBOR bignum must be a byte string"));
+        }
+        let length = self.argument(initial & 0x1F)?;
+        let mut magnitude = self.string(2, length)?.into_owned();
+        if negative {
+            // The value is -1 - n, so the magnitude is n + 1
+            let carry = magnitude.iter_mut().rev().all(|b| {
+                let (sum, overflow) = b.overflowing_add(1);
+                *b = sum;
+                overflow
+            });
+            if carry {
+                magnitude.insert(0, 1);
+            }
+        }
+        let digits = to_decimal(magnitude.iter().map(|&b| u32::from(b)), 256);
+        let sign = if negative { "-" } else { "" };
+        Ok(self.big_integer(&format!("{}{}", sign, digits)))
+    }
+
+    /// Reads one data item; arrays, maps and tags are kept on an explicit stack
+    fn read_item(&mut self) -> Result<DataValue<'a>> {
+        let mut stack: Vec<Frame<'a>> = Vec::new();
+        loop {
+            let closed = match stack.last_mut() {
+                Some(Frame::Array { remaining, items }) if self.at_end(*remaining)? => Some(
+                    DataValue::Array(self.arena.alloc_slice_fill_iter(std::mem::take(items))),
+                ),
+                Some(Frame::Map {
+                    remaining,
+                    entries,
+                    key: None,
+                }) if self.at_end(*remaining)? => Some(DataValue::Object(
+                    self.arena.alloc_slice_fill_iter(std::mem::take(entries)),
+                )),
+                _ => None,
+            };
+            let mut value = match closed {
+                Some(value) => {
+                    stack.pop();
+                    value
+                }
+                None => match self.read_head(&mut stack)? {
+                    Some(value) => value,
+                    None => continue,
+                },
+            };
+
+            // Hand the value to the containers and tags it completes
+            loop {
+                match stack.last_mut() {
+                    None => return Ok(value),
+                    Some(Frame::Tag(tag)) => {
+                        let tag = *tag;
+                        stack.pop();
+                        value = self.tagged(tag, value)?;
+                    }
+                    Some(Frame::Array { remaining, items }) => {
+                        items.push(value);
+                        if let Some(remaining) = remaining {
+                            *remaining -= 1;
+                        }
+                        break;
+                    }
+                    Some(Frame::Map {
+                        remaining,
+                        entries,
+                        key,
+                    }) => {
+                        match key.take() {
+                            None => *key = Some(self.key(value)?),
+                            Some(name) => {
+                                entries.push((name, value));
+                                if let Some(remaining) = remaining {
+                                    *remaining -= 1;
+                                }
+                            }
+                        }
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns whether the open container has no more items, consuming the break
+    /// that ends an indefinite length
+    fn at_end(&mut self, remaining: Option<u64>) -> Result<bool> {
+        match remaining {
+            Some(remaining) => Ok(remaining == 0),
+            None if self.bytes.get(self.pos) == Some(&BREAK) => {
+                self.pos += 1;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Reads the head of the next item, returning its value if it is complete,
+    /// or opening it on the stack
+    fn read_head(&mut self, stack: &mut Vec<Frame<'a>>) -> Result<Option<DataValue<'a>>> {
+        let initial = self.byte()?;
+        let (major, info) = (initial >> 5, initial & 0x1F);
+        if initial == BREAK {
+            self.pos -= 1;
+            return Err(self.error("Unexpected CBOR break"));
+        }
+        if major == 7 {
+            return self.simple(info).map(Some);
+        }
+        let argument = self.argument(info)?;
+        let value = match (major, argument) {
+            (0, Some(n)) => self.integer(false, n),
+            (1, Some(n)) => self.integer(true, n),
+            (2, length) => {
+                let bytes = self.string(2, length)?;
+                bytes_object(self.arena, &bytes)
+            }
+            (3, length) => {
+                let bytes = self.string(3, length)?;
+                let text = std::str::from_utf8(&bytes)
+                    .map_err(|_| self.error("Invalid UTF-8 in CBOR text string"))?;
+                DataValue::String(self.arena.alloc_str(text))
+            }
+            (4, remaining) => {
+                let capacity = remaining.map_or(0, |n| n.min(self.bytes.len() as u64) as usize);
+                stack.push(Frame::Array {
+                    remaining,
+                    items: Vec::with_capacity(capacity),
+                });
+                return Ok(None);
+            }
+            (5, remaining) => {
+                let capacity = remaining.map_or(0, |n| n.min(self.bytes.len() as u64) as usize);
+                stack.push(Frame::Map {
+                    remaining,
+                    entries: Vec::with_capacity(capacity),
+                    key: None,
+                });
+                return Ok(None);
+            }
+            (6, Some(tag)) => match tag {
+                TAG_POSITIVE_BIGNUM => self.bignum(false)?,
+                TAG_NEGATIVE_BIGNUM => self.bignum(true)?,
+                TAG_SELF_DESCRIBED => return Ok(None),
+                tag => {
+                    stack.push(Frame::Tag(tag));
+                    return Ok(None);
+                }
+            },
+            _ => {
+                self.pos -= 1;
+                return Err(self.error("Invalid indefinite length"));
+            }
+        };
+        Ok(Some(value))
+    }
+
+    /// Reads a float or simple value after its initial byte
+    fn simple(&mut self, info: u8) -> Result<DataValue<'a>> {
+        let float = |f: f64| DataValue::Number(Number::Float(f));
+        let value = match info {
+            20 | 21 => DataValue::Bool(info == 21),
+            22 | 23 => DataValue::Null,
+            25 => {
+                let bits = self.take(2)?;
+                float(f16_to_f64(u16::from_be_bytes([bits[0], bits[1]])))
+            }
+            26 => {
+                let bits = <[u8; 4]>::try_from(self.take(4)?).expect("length is 4");
+                float(f64::from(f32::from_be_bytes(bits)))
+            }
+            27 => {
+                let bits = <[u8; 8]>::try_from(self.take(8)?).expect("length is 8");
+                float(f64::from_be_bytes(bits))
+            }
+            _ => {
+                self.pos -= 1;
+                return Err(self.error("Unsupported CBOR simple value"));
+            }
+        };
+        Ok(value)
+    }
+
+    /// Returns the text of a map key
+    fn key(&self, key: DataValue<'a>) -> Result<&'a str> {
+        match key {
+            DataValue::String(s) => Ok(s),
+            DataValue::Number(n @ (Number::Integer(_) | Number::BigInt(_))) => {
+                Ok(self.arena.alloc_str(&DataValue::Number(n).to_string()))
+            }
+            _ => Err(self.error("CBOR map keys must be strings or integers")),
+        }
+    }
+
+    /// Applies a tag to its content
+    fn tagged(&self, tag: u64, content: DataValue<'a>) -> Result<DataValue<'a>> {
+        let invalid = |what: &str| self.error(&format!("Invalid CBOR {}", what));
+        let value = match (tag, &content) {
+            (TAG_DATETIME_STRING, DataValue::String(text)) => DataValue::DateTime(
+                DateTime::parse_from_rfc3339(text)
+                    .map_err(|_| invalid("date/time string"))?
+                    .with_timezone(&Utc),
+            ),
+            (TAG_EPOCH_TIME, DataValue::Number(_)) => {
+                let duration = seconds(&content, None).ok_or_else(|| invalid("epoch time"))?;
+                DataValue::DateTime(epoch(duration).ok_or_else(|| invalid("epoch time"))?)
+            }
+            (TAG_EXTENDED_TIME, DataValue::Object(entries)) => {
+                let duration = time_map(entries).ok_or_else(|| invalid("extended time"))?;
+                DataValue::DateTime(epoch(duration).ok_or_else(|| invalid("extended time"))?)
+            }
+            (TAG_DURATION, DataValue::Object(entries)) => {
+                DataValue::Duration(time_map(entries).ok_or_else(|| invalid("duration"))?)
+            }
+            (TAG_DECIMAL_FRACTION, DataValue::Array([exponent, mantissa])) => {
+                let (DataValue::Number(exponent @ Number::Integer(_)), DataValue::Number(mantissa)) =
+                    (exponent, mantissa)
+                else {
+                    return Err(invalid("decimal fraction"));
+                };
+                if !matches!(mantissa, Number::Integer(_) | Number::BigInt(_)) {
+                    return Err(invalid("decimal fraction"));
+                }
+                let text = format!(
+                    "{}e{}",
+                    DataValue::Number(*mantissa),
+                    DataValue::Number(*exponent)
+                );
+                let decimal = crate::bignum::BigDecimal::parse(self.arena, &text)
+                    .ok_or_else(|| invalid("decimal fraction"))?;
+                DataValue::Number(Number::BigDecimal(decimal))
+            }
+            (
+                TAG_DATETIME_STRING | TAG_EPOCH_TIME | TAG_EXTENDED_TIME | TAG_DURATION
+                | TAG_DECIMAL_FRACTION,
+                _,
+            ) => return Err(self.error(&format!("Invalid content for CBOR tag {}", tag))),
+            _ => {
+                let tag = match i64::try_from(tag) {
+                    Ok(tag) => DataValue::Number(Number::Integer(tag)),
+                    Err(_) => self.big_integer(&tag.to_string()),
+                };
+                let entries = [(TAG_KEY, tag), (VALUE_KEY, content)];
+                DataValue::Object(self.arena.alloc_slice_fill_iter(entries))
+            }
+        };
+        Ok(value)
+    }
+}
+
+/// Converts an IEEE 754 half-precision float (RFC 8949 appendix D)
+fn f16_to_f64(bits: u16) -> f64 {
+    let exponent = i32::from(bits >> 10 & 0x1F);
+    let mantissa = f64::from(bits & 0x3FF);
+    let magnitude = match exponent {
+        0 => mantissa * 2f64.powi(-24),
+        31 if mantissa == 0.0 => f64::INFINITY,
+        31 => f64::NAN,
+        _ => (mantissa + 1024.0) * 2f64.powi(exponent - 25),
+    };
+    if bits & 0x8000 != 0 {
+        -magnitude
+    } else {
+        magnitude
+    }
+}
+
+/// Returns the time since the epoch as a DateTime
+fn epoch(duration: Duration) -> Option<DateTime<Utc>> {
+    DateTime::UNIX_EPOCH.checked_add_signed(duration)
+}
+
+/// Converts a number of seconds, plus a fraction in units of `10^-digits`
+/// seconds, to a Duration
+fn seconds(value: &DataValue<'_>, fraction: Option<(i64, u32)>) -> Option<Duration> {
+    let whole = match value {
+        DataValue::Number(Number::Integer(i)) => Duration::try_seconds(*i)?,
+        DataValue::Number(Number::Float(f)) if f.is_finite() => {
+            let secs = f.floor();
+            let nanos = ((f - secs) * 1e9).round() as i64;
+            if secs.abs() >= 9.2e15 {
+                return None;
+            }
+            Duration::try_seconds(secs as i64)?.checked_add(&Duration::nanoseconds(nanos))?
+        }
+        _ => return None,
+    };
+    let Some((fraction, digits)) = fraction else {
+        return Some(whole);
+    };
+    let nanos = fraction.checked_mul(10i64.pow(9 - digits))?;
+    whole.checked_add(&Duration::nanoseconds(nanos))
+}
+
+/// Reads the `{1: seconds, -3/-6/-9: fraction}` map of extended times and
+/// durations
+fn time_map(entries: &[(&str, DataValue<'_>)]) -> Option<Duration> {
+    let mut whole = None;
+    let mut fraction = None;
+    for (key, value) in entries {
+        let digits = match *key {
+            "1" => {
+                whole = Some(value);
+                continue;
+            }
+            "-3" => 3,
+            "-6" => 6,
+            "-9" => 9,
+            // Other keys describe time scales and uncertainty, which do not
+            // change the instant
+            _ => continue,
+        };
+        match value {
+            DataValue::Number(Number::Integer(n)) if fraction.is_none() => {
+                fraction = Some((*n, digits))
+            }
+            _ => return None,
+        }
+    }
+    seconds(whole?, fraction)
+}
+
+/// Returns the object that stands for a byte string
+fn bytes_object<'a>(arena: &'a Bump, bytes: &[u8]) -> DataValue<'a> {
+    let text = DataValue::String(arena.alloc_str(&base64::encode(bytes)));
+    DataValue::Object(arena.alloc_slice_fill_iter([(BYTES_KEY, text)]))
+}
+
+/// How a value is encoded
+enum Shape<'v, 'a> {
+    /// A tag and its content
+    Tagged(u64, &'v DataValue<'a>),
+    Bytes(Vec<u8>),
+    Plain,
+}
+
+impl<'v, 'a> Shape<'v, 'a> {
+    /// Recognizes the objects that stand for byte strings and tags
+    fn of(value: &'v DataValue<'a>) -> Result<Self> {
+        let shape = match value.as_object() {
+            Some([(TAG_KEY, tag), (VALUE_KEY, content)])
+            | Some([(VALUE_KEY, content), (TAG_KEY, tag)]) => {
+                let tag = match tag {
+                    DataValue::Number(Number::Integer(i)) => u64::try_from(*i).ok(),
+                    DataValue::Number(Number::BigInt(n)) if !n.is_negative() => {
+                        n.digits().parse().ok()
+                    }
+                    _ => None,
+                }
+                .ok_or_else(|| Error::custom("CBOR tags must be unsigned integers"))?;
+                Shape::Tagged(tag, content)
+            }
+            Some([(BYTES_KEY, DataValue::String(text))]) => Shape::Bytes(base64::decode(text)?),
+            _ => Shape::Plain,
+        };
+        Ok(shape)
+    }
+}
+
+/// Writes an initial byte and its argument in the shortest form
+fn write_head(output: &mut Vec<u8>, major: u8, argument: u64) {
+    let major = major << 5;
+    match argument {
+        0..=23 => output.push(major | argument as u8),
+        24..=0xFF => output.extend([major | 24, argument as u8]),
+        0x100..=0xFFFF => {
+            output.push(major | 25);
+            output.extend((argument as u16).to_be_bytes());
+        }
+        0x1_0000..=0xFFFF_FFFF => {
+            output.push(major | 26);
+            output.extend((argument as u32).to_be_bytes());
+        }
+        _ => {
+            output.push(major | 27);
+            output.extend(argument.to_be_bytes());
+        }
+    }
+}
+
+/// An array or map being written: its remaining elements, with keys for maps
+type Open<'v, 'a> = std::vec::IntoIter<(Option<&'a str>, &'v DataValue<'a>)>;
+
+/// Writes one value, keeping open arrays and maps on an explicit stack
+fn write_item(value: &DataValue<'_>, output: &mut Vec<u8>) -> Result<()> {
+    let mut stack: Vec<Open<'_, '_>> = Vec::new();
+    let mut next = Some(value);
+    loop {
+        if let Some(mut value) = next.take() {
+            loop {
+                match Shape::of(value)? {
+                    Shape::Tagged(tag, content) => {
+                        write_head(output, 6, tag);
+                        value = content;
+                    }
+                    Shape::Bytes(bytes) => {
+                        write_head(output, 2, bytes.len() as u64);
+                        output.extend(bytes);
+                        break;
+                    }
+                    Shape::Plain => {
+                        match value {
+                            DataValue::Array(items) => {
+                                write_head(output, 4, items.len() as u64);
+                                let items: Vec<_> = items.iter().map(|item| (None, item)).collect();
+                                stack.push(items.into_iter());
+                            }
+                            DataValue::Object(entries) => {
+                                write_head(output, 5, entries.len() as u64);
+                                let items: Vec<_> = entries
+                                    .iter()
+                                    .map(|(key, value)| (Some(*key), value))
+                                    .collect();
+                                stack.push(items.into_iter());
+                            }
+                            DataValue::Raw(text) => {
+                                let arena = Bump::new();
+                                write_item(&DataValue::Raw(text).parse_raw(&arena)?, output)?;
+                            }
+                            scalar => write_scalar(scalar, output),
+                        }
+                        break;
+                    }
+                }
+            }
+        }
+
+        let Some(open) = stack.last_mut() else {
+            return Ok(());
+        };
+        match open.next() {
+            Some((key, item)) => {
+                if let Some(key) = key {
+                    write_head(output, 3, key.len() as u64);
+                    output.extend_from_slice(key.as_bytes());
+                }
+                next = Some(item);
+            }
+            None => {
+                stack.pop();
+            }
+        }
+    }
+}
+
+fn write_scalar(value: &DataValue<'_>, output: &mut Vec<u8>) {
+    match value {
+        DataValue::Null => output.push(0xF6),
+        DataValue::Bool(b) => output.push(if *b { 0xF5 } else { 0xF4 }),
+        DataValue::Number(number) => write_number(*number, output),
+        DataValue::String(s) => {
+            write_head(output, 3, s.len() as u64);
+            output.extend_from_slice(s.as_bytes());
+        }
+        DataValue::DateTime(dt) => {
+            let text = dt.to_rfc3339_opts(SecondsFormat::AutoSi, true);
+            write_head(output, 6, TAG_DATETIME_STRING);
+            write_head(output, 3, text.len() as u64);
+            output.extend_from_slice(text.as_bytes());
+        }
+        DataValue::Duration(dur) => {
+            let (secs, nanos) = (dur.num_seconds(), dur.subsec_nanos());
+            write_head(output, 6, TAG_DURATION);
+            write_head(output, 5, if nanos == 0 { 1 } else { 2 });
+            write_head(output, 0, 1);
+            write_number(Number::Integer(secs), output);
+            if nanos != 0 {
+                write_head(output, 1, 8);
+                write_number(Number::Integer(nanos.into()), output);
+            }
+        }
+        DataValue::Array(_) | DataValue::Object(_) | DataValue::Raw(_) => {
+            unreachable!("containers and raw values are written by write_item")
+        }
+    }
+}
+
+fn write_number(number: Number<'_>, output: &mut Vec<u8>) {
+    match number {
+        Number::Integer(i) if i < 0 => write_head(output, 1, !i as u64),
+        Number::Integer(i) => write_head(output, 0, i as u64),
+        Number::Float(f) if f64::from(f as f32) == f || f.is_nan() => {
+            output.push(0xFA);
+            output.extend((f as f32).to_be_bytes());
+        }
+        Number::Float(f) => {
+            output.push(0xFB);
+            output.extend(f.to_be_bytes());
+        }
+        Number::Raw(_) => write_number(number.parsed(), output),
+        Number::BigInt(n) => {
+            // Negative integers are stored as -1 - n
+            let mut magnitude = to_bytes(n.digits());
+            if n.is_negative() {
+                for b in magnitude.iter_mut().rev() {
+                    let (difference, borrow) = b.overflowing_sub(1);
+                    *b = difference;
+                    if !borrow {
+                        break;
+                    }
+                }
+                let zeros = magnitude.iter().take_while(|&&b| b == 0).count();
+                magnitude.drain(..zeros);
+            }
+            let major = u8::from(n.is_negative());
+            if magnitude.len() <= 8 {
+                let n = magnitude.iter().fold(0u64, |n, &b| n << 8 | u64::from(b));
+                write_head(output, major, n);
+            } else {
+                let tag = if n.is_negative() {
+                    TAG_NEGATIVE_BIGNUM
+                } else {
+                    TAG_POSITIVE_BIGNUM
+                };
+                write_head(output, 6, tag);
+                write_head(output, 2, magnitude.len() as u64);
+                output.extend(magnitude);
+            }
+        }
+        Number::BigDecimal(n) => {
+            write_head(output, 6, TAG_DECIMAL_FRACTION);
+            write_head(output, 4, 2);
+            write_number(Number::Integer(-n.scale()), output);
+            let unscaled = n.unscaled();
+            match unscaled.to_i64() {
+                Some(i) => write_number(Number::Integer(i), output),
+                None => write_number(Number::BigInt(unscaled), output),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_str;
+
+    /// Decodes hex from RFC 8949 appendix A
+    fn hex(text: &str) -> Vec<u8> {
+        (0..text.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&text[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_decode_rfc_examples() {
+        let arena = Bump::new();
+        let cases = [
+            ("00", "0"),
+            ("17", "23"),
+            ("1818", "24"),
+            ("1903e8", "1000"),
+            ("1bffffffffffffffff", "18446744073709551615"),
+            ("c249010000000000000000", "18446744073709551616"),
+            ("3bffffffffffffffff", "-18446744073709551616"),
+            ("c349010000000000000000", "-18446744073709551617"),
+            ("20", "-1"),
+            ("3903e7", "-1000"),
+            ("f90000", "0.0"),
+            ("f93c00", "1.0"),
+            ("f97bff", "65504.0"),
+            ("f90001", "5.960464477539063e-8"),
+            ("fa47c35000", "100000.0"),
+            ("fb3ff199999999999a", "1.1"),
+            ("c48221196ab3", "273.15"),
+            ("f4", "false"),
+            ("f7", "null"),
+            ("6449455446", r#""IETF""#),
+            ("62c3bc", r#""ü""#),
+            ("4401020304", r#"{"$bytes":"AQIDBA=="}"#),
+            ("8301820203820405", "[1,[2,3],[4,5]]"),
+            ("a201020304", r#"{"1":2,"3":4}"#),
+            ("a26161016162820203", r#"{"a":1,"b":[2,3]}"#),
+            (
+                "d74401020304",
+                r#"{"$tag":23,"$value":{"$bytes":"AQIDBA=="}}"#,
+            ),
+            (
+                "d818456449455446",
+                r#"{"$tag":24,"$value":{"$bytes":"ZElFVEY="}}"#,
+            ),
+            ("5f42010243030405ff", r#"{"$bytes":"AQIDBAU="}"#),
+            ("7f657374726561646d696e67ff", r#""streaming""#),
+            ("9f018202039f0405ffff", "[1,[2,3],[4,5]]"),
+            ("9fff", "[]"),
+            ("bf61610161629f0203ffff", r#"{"a":1,"b":[2,3]}"#),
+            ("d9d9f7820102", "[1,2]"),
+        ];
+        for (bytes, expected) in cases {
+            let value = from_slice(&arena, &hex(bytes)).unwrap();
+            let expected = match expected.parse::<f64>() {
+                Ok(f) if expected.contains('.') => DataValue::Number(Number::Float(f)),
+                _ => crate::from_str_with_options(
+                    &arena,
+                    expected,
+                    &crate::ParseOptions {
+                        number_mode: crate::NumberMode::Arbitrary,
+                        ..Default::default()
+                    },
+                )
+                .unwrap(),
+            };
+            assert_eq!(value, expected, "{}", bytes);
+        }
+
+        let time =
+            from_slice(&arena, &hex("c074323031332d30332d32315432303a30343a30305a")).unwrap();
+        assert_eq!(time.as_datetime().unwrap().timestamp(), 1363896240);
+        let time = from_slice(&arena, &hex("c1fb41d452d9ec200000")).unwrap();
+        assert_eq!(
+            time.as_datetime().unwrap().timestamp_millis(),
+            1363896240500
+        );
+        // 1001({1: 1363896240, -3: 500})
+        let time = from_slice(&arena, &hex("d903e9a2011a514b67b0221901f4")).unwrap();
+        assert_eq!(
+            time.as_datetime().unwrap().timestamp_millis(),
+            1363896240500
+        );
+
+        for bad in [
+            "", "18", "1c", "5f01ff", "7f61ff", "62c3", "ff", "8201", "a20102", "a1f601", "f8ff",
+            "c0f6", "c44101", "0000", "bf01ff", "c0c0",
+        ] {
+            assert!(from_slice(&arena, &hex(bad)).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let arena = Bump::new();
+        let options = crate::ParseOptions {
+            number_mode: crate::NumberMode::Arbitrary,
+            ..Default::default()
+        };
+        let text = r#"{
+            "ints": [0, 23, 24, 255, 256, 65536, -1, -24, -25, 4294967296,
+                     9223372036854775807, -9223372036854775808, 18446744073709551615,
+                     -18446744073709551616, 18446744073709551616, -18446744073709551617,
+                     123456789012345678901234567890],
+            "floats": [1.5, -0.1, 1e300, 0.30000000000000004],
+            "decimals": [1.23456789012345678901234567890, 1e400, -0.000000000000000000001],
+            "misc": [null, true, false, "", "text ü", {}, [], [[]]],
+            "bytes": {"$bytes": "AAEC/w=="},
+            "tagged": {"$tag": 32, "$value": "http://example.com"},
+            "nested": {"$tag": 99, "$value": {"$tag": 100, "$value": [1]}}
+        }"#;
+        let value = crate::from_str_with_options(&arena, text, &options).unwrap();
+        let bytes = to_vec(&value).unwrap();
+        assert_eq!(from_slice(&arena, &bytes).unwrap(), value);
+
+        let times = crate::helpers::array(
+            &arena,
+            vec![
+                DataValue::DateTime("2024-02-29T12:30:45.123456789Z".parse().unwrap()),
+                DataValue::Duration(crate::helpers::parse_duration("PT1.5S").unwrap()),
+                DataValue::Duration(crate::helpers::parse_duration("-P1DT0.25S").unwrap()),
+            ],
+        );
+        let bytes = to_vec(&times).unwrap();
+        assert_eq!(bytes[1..3], [0xC0, 0x78]);
+        assert_eq!(from_slice(&arena, &bytes).unwrap(), times);
+
+        let raw = DataValue::Raw(r#"{"k": [1, 2]}"#);
+        let parsed = from_str(&arena, r#"{"k": [1, 2]}"#).unwrap();
+        assert_eq!(to_vec(&raw).unwrap(), to_vec(&parsed).unwrap());
+        assert_eq!(to_vec(&parsed).unwrap(), hex("a1616b820102"));
+        assert_eq!(
+            to_vec(&DataValue::Number(Number::Float(f64::NAN))).unwrap(),
+            hex("fa7fc00000")
+        );
+
+        let bad_tag = from_str(&arena, r#"{"$tag": -1, "$value": 1}"#).unwrap();
+        assert!(to_vec(&bad_tag).is_err());
+        let bad_bytes = from_str(&arena, r#"{"$bytes": "!"}"#).unwrap();
+        assert!(to_vec(&bad_bytes).is_err());
+    }
+}
@@ -0,0 +1,349 @@
+//! Pagination-aware fetching of JSON APIs
+//!
+//! Most list endpoints return one page of results per request together with a way
+//! to ask for the next one: a cursor, an offset, or a full "next" link. This module
+//! walks those pages and concatenates the item arrays into a single arena-backed
+//! array.
+//!
+//! The crate does not ship an HTTP stack. Requests go through the [`Fetch`] trait,
+//! which is implemented for any `Fn(&str) -> Result<String>` closure, so the client
+//! your service already uses (with its TLS, auth and retry settings) plugs in
+//! directly.
+
+use bumpalo::Bump;
+
+use crate::datavalue::DataValue;
+use crate::de::from_str;
+use crate::error::{Error, Result};
+
+/// Performs GET requests and returns the response body
+pub trait Fetch {
+    /// Fetches `url` and returns the response body as text
+    fn get(&self, url: &str) -> Result<String>;
+}
+
+impl<F> Fetch for F
+where
+    F: Fn(&str) -> Result<String>,
+{
+    fn get(&self, url: &str) -> Result<String> {
+        self(url)
+    }
+}
+
+/// How the API exposes the next page
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Pagination {
+    /// The response carries an opaque cursor that is sent back as a query parameter
+    Cursor {
+        /// Query parameter the cursor is sent in, e.g. `cursor`
+        param: String,
+        /// JSON pointer to the next cursor in the response, e.g. `/next_cursor`
+        next: String,
+    },
+    /// Pages are addressed by item offset and page size
+    Offset {
+        /// Query parameter for the offset, e.g. `offset`
+        offset_param: String,
+        /// Query parameter for the page size, e.g. `limit`
+        limit_param: String,
+        /// Number of items requested per page
+        limit: usize,
+    },
+    /// The response carries the full URL of the next page
+    NextLink {
+        /// JSON pointer to the next URL in the response, e.g. `/links/next`
+        next: String,
+    },
+}
+
+/// A paginated GET request
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::{client::PaginatedRequest, Bump, Result};
+///
+/// let pages = |url: &str| -> Result<String> {
+///     Ok(match url {
+///         "https://api.test/users" => r#"{"data": [{"id": 1}], "next": "c2"}"#,
+///         "https://api.test/users?cursor=c2" => r#"{"data": [{"id": 2}], "next": null}"#,
+///         other => panic!("unexpected request {}", other),
+///     }
+///     .to_string())
+/// };
+///
+/// let arena = Bump::new();
+/// let users = PaginatedRequest::new("https://api.test/users")
+///     .items("/data")
+///     .cursor("cursor", "/next")
+///     .fetch_all(&arena, &pages)
+///     .unwrap();
+///
+/// assert_eq!(users.as_array().map(|a| a.len()), Some(2));
+/// assert_eq!(users[1]["id"].as_i64(), Some(2));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaginatedRequest {
+    url: String,
+    items: String,
+    pagination: Pagination,
+    max_pages: usize,
+}
+
+impl PaginatedRequest {
+    /// Default limit on the number of pages fetched
+    pub const DEFAULT_MAX_PAGES: usize = 1000;
+
+    /// Creates a request for `url`, reading items from `/items` and following
+    /// `next` cursors sent back as the `cursor` query parameter
+    pub fn new(url: impl Into<String>) -> Self {
+        PaginatedRequest {
+            url: url.into(),
+            items: "/items".to_string(),
+            pagination: Pagination::Cursor {
+                param: "cursor".to_string(),
+                next: "/next".to_string(),
+            },
+            max_pages: Self::DEFAULT_MAX_PAGES,
+        }
+    }
+
+    /// Sets the JSON pointer of the item array in each response
+    ///
+    /// An empty pointer means the response body itself is the array.
+    pub fn items(mut self, pointer: impl Into<String>) -> Self {
+        self.items = pointer.into();
+        self
+    }
+
+    /// Uses cursor pagination
+    pub fn cursor(self, param: impl Into<String>, next: impl Into<String>) -> Self {
+        self.pagination(Pagination::Cursor {
+            param: param.into(),
+            next: next.into(),
+        })
+    }
+
+    /// Uses offset pagination; stops at the first page shorter than `limit`
+    pub fn offset(
+        self,
+        offset_param: impl Into<String>,
+        limit_param: impl Into<String>,
+        limit: usize,
+    ) -> Self {
+        self.pagination(Pagination::Offset {
+            offset_param: offset_param.into(),
+            limit_param: limit_param.into(),
+            limit,
+        })
+    }
+
+    /// Follows full next-page URLs found at `next`
+    pub fn next_link(self, next: impl Into<String>) -> Self {
+        self.pagination(Pagination::NextLink { next: next.into() })
+    }
+
+    /// Sets the pagination convention
+    pub fn pagination(mut self, pagination: Pagination) -> Self {
+        self.pagination = pagination;
+        self
+    }
+
+    /// Limits the number of pages fetched
+    ///
+    /// Reaching the limit while more pages are available is an error, so a
+    /// misconfigured cursor cannot loop forever or silently truncate results.
+    pub fn max_pages(mut self, max_pages: usize) -> Self {
+        self.max_pages = max_pages;
+        self
+    }
+
+    /// Fetches every page and returns the concatenated items
+    ///
+    /// # Arguments
+    ///
+    /// * `arena` - The arena allocator to store the pages and the result
+    /// * `fetch` - The transport used to perform each request
+    ///
+    /// # Returns
+    ///
+    /// A Result containing an array of all items in page order, or an Error if a
+    /// request fails, a response is not JSON, a response has no item array at the
+    /// configured pointer, or the page limit is exceeded.
+    pub fn fetch_all<'a>(&self, arena: &'a Bump, fetch: &dyn Fetch) -> Result<DataValue<'a>> {
+        let mut items: Vec<DataValue<'a>> = Vec::new();
+        let mut url = match &self.pagination {
+            Pagination::Offset {
+                offset_param,
+                limit_param,
+                limit,
+            } => with_query(
+                &self.url,
+                &[(offset_param, "0"), (limit_param, &limit.to_string())],
+            ),
+            _ => self.url.clone(),
+        };
+
+        for _ in 0..self.max_pages {
+            let body = fetch.get(&url)?;
+            let page = from_str(arena, &body)?;
+            let page_items = page
+                .pointer(&self.items)
+                .ok_or_else(|| Error::missing_field(self.items.clone()))?;
+            let page_items = match page_items {
+                DataValue::Array(values) => *values,
+                other => {
+                    return Err(Error::expected_type(
+                        "array",
+                        format!("{:?}", other.get_type()).to_lowercase(),
+                    ))
+                }
+            };
+            items.extend_from_slice(page_items);
+
+            let next = match &self.pagination {
+                Pagination::Cursor { param, next } => {
+                    next_string(&page, next).map(|cursor| with_query(&self.url, &[(param, cursor)]))
+                }
+                Pagination::NextLink { next } => {
+                    next_string(&page, next).map(|link| link.to_string())
+                }
+                Pagination::Offset {
+                    offset_param,
+                    limit_param,
+                    limit,
+                } => (page_items.len() >= *limit && !page_items.is_empty()).then(|| {
+                    with_query(
+                        &self.url,
+                        &[
+                            (offset_param, &items.len().to_string()),
+                            (limit_param, &limit.to_string()),
+                        ],
+                    )
+                }),
+            };
+            match next {
+                Some(next) if next == url => {
+                    return Err(Error::custom(format!(
+                        "Pagination did not advance past {}",
+                        url
+                    )))
+                }
+                Some(next) => url = next,
+                None => return Ok(DataValue::Array(arena.alloc_slice_clone(&items))),
+            }
+        }
+
+        Err(Error::custom(format!(
+            "Pagination exceeded {} pages",
+            self.max_pages
+        )))
+    }
+}
+
+/// Returns the non-empty string at `pointer`, treating null or absence as the end
+fn next_string<'a>(page: &DataValue<'a>, pointer: &str) -> Option<&'a str> {
+    page.pointer(pointer)
+        .and_then(DataValue::as_str)
+        .filter(|s| !s.is_empty())
+}
+
+/// Appends percent-encoded query parameters to a URL
+fn with_query(url: &str, params: &[(&String, &str)]) -> String {
+    let mut result = url.to_string();
+    for (name, value) in params {
+        result.push(if result.contains('?') { '&' } else { '?' });
+        percent_encode(name, &mut result);
+        result.push('=');
+        percent_encode(value, &mut result);
+    }
+    result
+}
+
+fn percent_encode(text: &str, output: &mut String) {
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                output.push(byte as char)
+            }
+            _ => output.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    #[test]
+    fn test_offset_pagination() {
+        let requested = RefCell::new(Vec::new());
+        let fetch = |url: &str| -> Result<String> {
+            requested.borrow_mut().push(url.to_string());
+            let offset: usize = url
+                .split("offset=")
+                .nth(1)
+                .and_then(|rest| rest.split('&').next())
+                .unwrap()
+                .parse()
+                .unwrap();
+            let page: Vec<usize> = (offset..(offset + 2).min(5)).collect();
+            Ok(format!("{:?}", page))
+        };
+
+        let arena = Bump::new();
+        let all = PaginatedRequest::new("http://api.test/n?sort=asc")
+            .items("")
+            .offset("offset", "limit", 2)
+            .fetch_all(&arena, &fetch)
+            .unwrap();
+        assert_eq!(all.as_array().map(|a| a.len()), Some(5));
+        assert_eq!(all[4].as_i64(), Some(4));
+        assert_eq!(
+            requested.borrow().last().unwrap(),
+            "http://api.test/n?sort=asc&offset=4&limit=2"
+        );
+    }
+
+    #[test]
+    fn test_next_link_and_errors() {
+        let fetch = |url: &str| -> Result<String> {
+            Ok(match url {
+                "http://a/1" => r#"{"items": [1], "links": {"next": "http://a/2"}}"#,
+                "http://a/2" => r#"{"items": [2], "links": {}}"#,
+                "http://loop" => r#"{"items": [], "links": {"next": "http://loop"}}"#,
+                _ => r#"{"items": "nope"}"#,
+            }
+            .to_string())
+        };
+
+        let arena = Bump::new();
+        let linked = PaginatedRequest::new("http://a/1").next_link("/links/next");
+        let all = linked.fetch_all(&arena, &fetch).unwrap();
+        assert_eq!(all.as_array().map(|a| a.len()), Some(2));
+        assert!(linked
+            .clone()
+            .max_pages(1)
+            .fetch_all(&arena, &fetch)
+            .is_err());
+
+        let looping = PaginatedRequest::new("http://loop").next_link("/links/next");
+        assert!(looping.fetch_all(&arena, &fetch).is_err());
+
+        let invalid = PaginatedRequest::new("http://other");
+        assert!(invalid.fetch_all(&arena, &fetch).is_err());
+    }
+
+    #[test]
+    fn test_cursor_is_encoded() {
+        let mut out = String::new();
+        percent_encode("a b/c=", &mut out);
+        assert_eq!(out, "a%20b%2Fc%3D");
+        assert_eq!(
+            with_query("http://x/?q=1", &[(&"cursor".to_string(), "a&b")]),
+            "http://x/?q=1&cursor=a%26b"
+        );
+    }
+}
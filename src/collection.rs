@@ -0,0 +1,609 @@
+//! Collections of many documents spread over sharded arenas
+//!
+//! A [`DocumentSet`] is meant for in-memory caches that hold thousands of JSON
+//! documents under string keys. Each key is hashed to one of a fixed number of
+//! shards. A shard keeps the validated text of its documents, which is smaller
+//! than their trees, and an arena that bulk operations parse the documents into.
+//! The arena is reset once the operation is done, and the results are copied into
+//! an arena the caller provides, so memory stays proportional to the text held.
+//! Evicting a shard drops a whole group of documents at once.
+//!
+//! For batch jobs whose documents do not all fit in memory, a [`SpillPolicy`]
+//! caps the bytes of text kept resident. Least recently used shards are written
+//! to files and read back when one of their documents is accessed.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use bumpalo::Bump;
+
+use crate::datavalue::DataValue;
+use crate::de::{from_str_with_options, ParseOptions};
+use crate::error::{Error, Result};
+use crate::operations::{aggregate, Aggregation};
+
+/// Distinguishes the spill files of sets in the same process
+static NEXT_SET_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Many JSON documents under string keys, grouped into shards
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::collection::DocumentSet;
+/// use datavalue_rs::operations::Aggregation;
+/// use datavalue_rs::Bump;
+///
+/// let mut set = DocumentSet::new(4);
+/// set.insert("order-1", r#"{"total": 20, "status": "paid"}"#).unwrap();
+/// set.insert("order-2", r#"{"total": 5}"#).unwrap();
+/// set.insert("order-3", r#"{"total": 12.5, "status": "paid"}"#).unwrap();
+///
+/// let arena = Bump::new();
+/// let paid = set.query(&arena, "/status").unwrap();
+/// assert_eq!(paid.len(), 2);
+/// let sum = set.aggregate(&arena, Aggregation::Sum("/total")).unwrap();
+/// assert_eq!(sum.as_f64(), Some(37.5));
+///
+/// let shard = set.shard_of("order-2");
+/// set.evict_shard(shard);
+/// assert!(!set.contains_key("order-2"));
+/// ```
+#[derive(Debug)]
+pub struct DocumentSet {
+    shards: Vec<Shard>,
+    options: ParseOptions,
+    spill: Option<SpillPolicy>,
+    id: u64,
+    /// Incremented on every access, to find the least recently used shard
+    clock: u64,
+}
+
+#[derive(Debug, Default)]
+struct Shard {
+    /// Document text by key, in key order
+    ///
+    /// While the shard is spilled the keys stay, but the texts are empty; an empty
+    /// string is never valid JSON, so it cannot be mistaken for a document.
+    documents: BTreeMap<String, String>,
+    /// Scratch arena that bulk operations parse this shard's documents into
+    arena: Bump,
+    /// Bytes of keys and text held in memory
+    bytes: usize,
+    last_used: u64,
+    /// The file holding the texts, while the shard is spilled
+    spilled: Option<PathBuf>,
+}
+
+/// When and where a [`DocumentSet`] moves cold shards to disk
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::collection::{DocumentSet, SpillPolicy};
+/// use datavalue_rs::Bump;
+///
+/// let dir = std::env::temp_dir();
+/// let mut set = DocumentSet::new(8).with_spill(SpillPolicy::new(&dir, 4096));
+/// for i in 0..200 {
+///     set.insert(format!("row-{}", i), &format!(r#"{{"n": {}}}"#, i)).unwrap();
+/// }
+/// assert!(set.resident_bytes() <= 4096);
+///
+/// let arena = Bump::new();
+/// assert_eq!(set.get(&arena, "row-42").unwrap()["n"].as_i64(), Some(42));
+/// assert_eq!(set.query(&arena, "/n").unwrap().len(), 200);
+/// ```
+#[derive(Debug, Clone)]
+pub struct SpillPolicy {
+    dir: PathBuf,
+    max_resident_bytes: usize,
+}
+
+impl SpillPolicy {
+    /// Keeps at most `max_resident_bytes` of keys and text in memory, spilling
+    /// shards to files in `dir`
+    ///
+    /// The shard being accessed always stays resident, so a single shard larger
+    /// than the limit is still loaded when needed.
+    pub fn new(dir: impl Into<PathBuf>, max_resident_bytes: usize) -> Self {
+        SpillPolicy {
+            dir: dir.into(),
+            max_resident_bytes,
+        }
+    }
+}
+
+impl DocumentSet {
+    /// Creates an empty set with `shard_count` shards
+    ///
+    /// # Panics
+    ///
+    /// Panics if `shard_count` is zero.
+    pub fn new(shard_count: usize) -> Self {
+        Self::with_options(shard_count, ParseOptions::default())
+    }
+
+    /// Creates an empty set whose documents are parsed with `options`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `shard_count` is zero.
+    pub fn with_options(shard_count: usize, options: ParseOptions) -> Self {
+        assert!(shard_count > 0, "a DocumentSet needs at least one shard");
+        DocumentSet {
+            shards: (0..shard_count).map(|_| Shard::default()).collect(),
+            options,
+            spill: None,
+            id: NEXT_SET_ID.fetch_add(1, Ordering::Relaxed),
+            clock: 0,
+        }
+    }
+
+    /// Spills least recently used shards to disk to stay within `policy`
+    ///
+    /// Spill files are removed when the shard is loaded again, evicted, or the
+    /// set is dropped.
+    pub fn with_spill(mut self, policy: SpillPolicy) -> Self {
+        self.spill = Some(policy);
+        self
+    }
+
+    /// Returns the number of shards
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Returns the shard that `key` is stored in
+    pub fn shard_of(&self, key: &str) -> usize {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        (hasher.finish() % self.shards.len() as u64) as usize
+    }
+
+    /// Returns the number of documents in the set
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|shard| shard.documents.len()).sum()
+    }
+
+    /// Returns true if the set holds no documents
+    pub fn is_empty(&self) -> bool {
+        self.shards.iter().all(|shard| shard.documents.is_empty())
+    }
+
+    /// Returns the number of documents in `shard`
+    pub fn shard_len(&self, shard: usize) -> usize {
+        self.shards
+            .get(shard)
+            .map_or(0, |shard| shard.documents.len())
+    }
+
+    /// Returns true if `shard` is currently spilled to disk
+    pub fn is_spilled(&self, shard: usize) -> bool {
+        self.shards
+            .get(shard)
+            .is_some_and(|shard| shard.spilled.is_some())
+    }
+
+    /// Returns the bytes of keys and document text held in memory
+    pub fn resident_bytes(&self) -> usize {
+        self.shards.iter().map(|shard| shard.bytes).sum()
+    }
+
+    /// Returns true if a document is stored under `key`
+    ///
+    /// Keys of spilled shards stay in memory, so this never reads from disk.
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.shards[self.shard_of(key)].documents.contains_key(key)
+    }
+
+    /// Validates JSON text and stores it under `key`, replacing any previous document
+    ///
+    /// Returns true if a document was replaced.
+    ///
+    /// # Errors
+    ///
+    /// Returns a syntax error if the text is not valid JSON under the set's options,
+    /// or an I/O error if spilling or loading a shard fails.
+    pub fn insert(&mut self, key: impl Into<String>, json: &str) -> Result<bool> {
+        let key = key.into();
+        let index = self.shard_of(&key);
+        let shard = &mut self.shards[index];
+        from_str_with_options(&shard.arena, json, &self.options)?;
+        shard.arena.reset();
+        self.store(index, key, json.to_string())
+    }
+
+    /// Stores a value under `key`, replacing any previous document
+    ///
+    /// Returns true if a document was replaced.
+    ///
+    /// # Errors
+    ///
+    /// Returns an I/O error if spilling or loading a shard fails.
+    pub fn insert_value(&mut self, key: impl Into<String>, value: &DataValue<'_>) -> Result<bool> {
+        let key = key.into();
+        let index = self.shard_of(&key);
+        self.store(index, key, crate::to_string(value))
+    }
+
+    /// Removes the document stored under `key`, returning true if there was one
+    ///
+    /// # Errors
+    ///
+    /// Returns an I/O error if the document's shard is spilled and cannot be loaded.
+    pub fn remove(&mut self, key: &str) -> Result<bool> {
+        let index = self.shard_of(key);
+        if !self.shards[index].documents.contains_key(key) {
+            return Ok(false);
+        }
+        self.load(index)?;
+        let shard = &mut self.shards[index];
+        if let Some(text) = shard.documents.remove(key) {
+            shard.bytes -= key.len() + text.len();
+        }
+        Ok(true)
+    }
+
+    /// Parses the document stored under `key` into `arena`
+    ///
+    /// A spilled shard is loaded back into memory first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no document is stored under `key`, or an I/O error if
+    /// loading or spilling a shard fails.
+    pub fn get<'b>(&mut self, arena: &'b Bump, key: &str) -> Result<DataValue<'b>> {
+        let index = self.shard_of(key);
+        if !self.shards[index].documents.contains_key(key) {
+            return Err(Error::custom(format!("no document under key \"{}\"", key)));
+        }
+        self.load(index)?;
+        self.enforce_budget(index)?;
+        from_str_with_options(arena, &self.shards[index].documents[key], &self.options)
+    }
+
+    /// Drops every document in `shard`, returning how many there were
+    pub fn evict_shard(&mut self, shard: usize) -> usize {
+        match self.shards.get_mut(shard) {
+            Some(shard) => {
+                let evicted = shard.documents.len();
+                if let Some(path) = shard.spilled.take() {
+                    let _ = fs::remove_file(path);
+                }
+                *shard = Shard::default();
+                evicted
+            }
+            None => 0,
+        }
+    }
+
+    /// Calls `f` with the key and value of every document, shard by shard and in
+    /// key order within a shard
+    ///
+    /// Each shard is parsed into its own arena, which is reset before the next
+    /// shard, so at most one shard is materialized at a time. Spilled shards are
+    /// read from disk for the call without being made resident.
+    ///
+    /// # Errors
+    ///
+    /// Returns an I/O error if a spill file cannot be read, or a syntax error if a
+    /// stored document no longer parses, which can only happen for documents added
+    /// with [`DocumentSet::insert_value`] whose values the set's options reject.
+    pub fn for_each<F>(&mut self, mut f: F) -> Result<()>
+    where
+        F: FnMut(&str, &DataValue<'_>),
+    {
+        for shard in &mut self.shards {
+            let loaded;
+            let documents = match &shard.spilled {
+                Some(path) => {
+                    loaded = read_spill(path)?;
+                    &loaded
+                }
+                None => &shard.documents,
+            };
+            let result = documents.iter().try_for_each(|(key, text)| -> Result<()> {
+                let value = from_str_with_options(&shard.arena, text, &self.options)?;
+                f(key, &value);
+                Ok(())
+            });
+            shard.arena.reset();
+            result?;
+        }
+        Ok(())
+    }
+
+    /// Returns the key and the value at `pointer` of every document where the
+    /// pointer resolves, copied into `arena`
+    ///
+    /// Results are ordered as in [`DocumentSet::for_each`].
+    ///
+    /// # Errors
+    ///
+    /// See [`DocumentSet::for_each`].
+    pub fn query<'b>(
+        &mut self,
+        arena: &'b Bump,
+        pointer: &str,
+    ) -> Result<Vec<(&'b str, DataValue<'b>)>> {
+        let mut matches = Vec::new();
+        self.for_each(|key, value| {
+            if let Some(found) = value.pointer(pointer) {
+                matches.push((&*arena.alloc_str(key), found.clone_in(arena)));
+            }
+        })?;
+        Ok(matches)
+    }
+
+    /// Aggregates over every document in the set, copying the result into `arena`
+    ///
+    /// Documents are the records passed to [`operations::aggregate`]. All shards,
+    /// including spilled ones, are materialized for the duration of the call.
+    ///
+    /// [`operations::aggregate`]: crate::operations::aggregate
+    ///
+    /// # Errors
+    ///
+    /// See [`DocumentSet::for_each`].
+    pub fn aggregate<'b>(
+        &mut self,
+        arena: &'b Bump,
+        aggregation: Aggregation<'_>,
+    ) -> Result<DataValue<'b>> {
+        let result = self.aggregate_shards(arena, aggregation);
+        for shard in &mut self.shards {
+            shard.arena.reset();
+        }
+        result
+    }
+
+    fn aggregate_shards<'b>(
+        &self,
+        arena: &'b Bump,
+        aggregation: Aggregation<'_>,
+    ) -> Result<DataValue<'b>> {
+        let loaded = self
+            .shards
+            .iter()
+            .map(|shard| shard.spilled.as_deref().map(read_spill).transpose())
+            .collect::<Result<Vec<_>>>()?;
+        let mut records = Vec::with_capacity(self.len());
+        for (shard, loaded) in self.shards.iter().zip(&loaded) {
+            for text in loaded.as_ref().unwrap_or(&shard.documents).values() {
+                let value = from_str_with_options(&shard.arena, text, &self.options)?;
+                records.push(&*shard.arena.alloc(value));
+            }
+        }
+        Ok(aggregate(&records, aggregation).clone_in(arena))
+    }
+
+    /// Stores a document's text in shard `index` and spills other shards if the
+    /// set is now over its budget
+    fn store(&mut self, index: usize, key: String, text: String) -> Result<bool> {
+        self.load(index)?;
+        let shard = &mut self.shards[index];
+        let (key_len, text_len) = (key.len(), text.len());
+        let replaced = match shard.documents.insert(key, text) {
+            // The key was already counted
+            Some(old) => {
+                shard.bytes = shard.bytes + text_len - old.len();
+                true
+            }
+            None => {
+                shard.bytes += key_len + text_len;
+                false
+            }
+        };
+        self.enforce_budget(index)?;
+        Ok(replaced)
+    }
+
+    /// Reads shard `index` back from its spill file, if it is spilled, and marks
+    /// it as just used
+    fn load(&mut self, index: usize) -> Result<()> {
+        self.clock += 1;
+        let shard = &mut self.shards[index];
+        shard.last_used = self.clock;
+        if let Some(path) = &shard.spilled {
+            let documents = read_spill(path)?;
+            let _ = fs::remove_file(path);
+            shard.bytes = documents.iter().map(|(k, v)| k.len() + v.len()).sum();
+            shard.documents = documents;
+            shard.spilled = None;
+        }
+        Ok(())
+    }
+
+    /// Spills least recently used shards other than `keep` until the resident
+    /// bytes fit the spill policy
+    fn enforce_budget(&mut self, keep: usize) -> Result<()> {
+        let Some(policy) = &self.spill else {
+            return Ok(());
+        };
+        while self.resident_bytes() > policy.max_resident_bytes {
+            let coldest = self
+                .shards
+                .iter()
+                .enumerate()
+                .filter(|(i, shard)| *i != keep && shard.spilled.is_none() && shard.bytes > 0)
+                .min_by_key(|(_, shard)| shard.last_used)
+                .map(|(i, _)| i);
+            let Some(index) = coldest else {
+                break;
+            };
+            let path = policy.dir.join(format!(
+                "datavalue-{}-{}-shard-{}.spill",
+                std::process::id(),
+                self.id,
+                index
+            ));
+            let shard = &mut self.shards[index];
+            write_spill(&path, &shard.documents)?;
+            for text in shard.documents.values_mut() {
+                *text = String::new();
+            }
+            shard.bytes = 0;
+            shard.arena = Bump::new();
+            shard.spilled = Some(path);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for DocumentSet {
+    fn drop(&mut self) {
+        for shard in &mut self.shards {
+            if let Some(path) = shard.spilled.take() {
+                let _ = fs::remove_file(path);
+            }
+        }
+    }
+}
+
+/// Writes documents as a sequence of length-prefixed keys and texts
+fn write_spill(path: &Path, documents: &BTreeMap<String, String>) -> Result<()> {
+    let mut bytes = Vec::new();
+    for (key, text) in documents {
+        for part in [key, text] {
+            bytes.extend_from_slice(&(part.len() as u64).to_le_bytes());
+            bytes.extend_from_slice(part.as_bytes());
+        }
+    }
+    fs::write(path, bytes).map_err(Error::from)
+}
+
+/// Reads documents written by [`write_spill`]
+fn read_spill(path: &Path) -> Result<BTreeMap<String, String>> {
+    let bytes = fs::read(path)?;
+    let corrupt = || Error::custom(format!("corrupt spill file {}", path.display()));
+    let mut documents = BTreeMap::new();
+    let mut rest = &bytes[..];
+    while !rest.is_empty() {
+        let mut parts = [String::new(), String::new()];
+        for part in &mut parts {
+            let (len, tail) = rest.split_first_chunk::<8>().ok_or_else(corrupt)?;
+            let len = usize::try_from(u64::from_le_bytes(*len)).map_err(|_| corrupt())?;
+            let text = tail.get(..len).ok_or_else(corrupt)?;
+            *part = String::from_utf8(text.to_vec()).map_err(|_| corrupt())?;
+            rest = &tail[len..];
+        }
+        let [key, text] = parts;
+        documents.insert(key, text);
+    }
+    Ok(documents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shards() {
+        let mut set = DocumentSet::new(3);
+        for i in 0..30 {
+            let json = format!(r#"{{"id": {}, "even": {}}}"#, i, i % 2 == 0);
+            assert!(!set.insert(format!("doc-{}", i), &json).unwrap());
+        }
+        assert!(set.insert("doc-0", r#"{"id": 0, "even": true}"#).unwrap());
+        assert!(set.insert("bad", "{").is_err());
+        assert_eq!(set.len(), 30);
+        assert_eq!((0..3).map(|shard| set.shard_len(shard)).sum::<usize>(), 30);
+        assert!((0..3).all(|shard| set.shard_len(shard) > 0));
+
+        let arena = Bump::new();
+        assert_eq!(set.get(&arena, "doc-7").unwrap()["id"].as_i64(), Some(7));
+        assert!(set.get(&arena, "doc-99").is_err());
+
+        let count = set.aggregate(&arena, Aggregation::Count).unwrap();
+        assert_eq!(count.as_i64(), Some(30));
+        let max = set.aggregate(&arena, Aggregation::Max("/id")).unwrap();
+        assert_eq!(max.as_i64(), Some(29));
+
+        let mut evens = 0;
+        set.for_each(|_, value| evens += usize::from(value["even"] == DataValue::Bool(true)))
+            .unwrap();
+        assert_eq!(evens, 15);
+
+        let shard = set.shard_of("doc-3");
+        let in_shard = set.shard_len(shard);
+        assert_eq!(set.evict_shard(shard), in_shard);
+        assert_eq!(set.len(), 30 - in_shard);
+        assert!(!set.contains_key("doc-3"));
+        assert_eq!(set.query(&arena, "/id").unwrap().len(), 30 - in_shard);
+        assert_eq!(set.evict_shard(7), 0);
+
+        assert!(set.remove("doc-0").unwrap() || set.shard_of("doc-0") == shard);
+        assert!(!set.remove("doc-0").unwrap());
+    }
+
+    #[test]
+    fn test_insert_value() {
+        let arena = Bump::new();
+        let mut set = DocumentSet::new(1);
+        let value = crate::from_str(&arena, r#"{"tags": ["a", "b"]}"#).unwrap();
+        assert!(!set.insert_value("k", &value).unwrap());
+        let found = set.query(&arena, "/tags/1").unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].0, "k");
+        assert_eq!(found[0].1.as_str(), Some("b"));
+    }
+
+    #[test]
+    fn test_spill() {
+        let dir = std::env::temp_dir().join(format!("datavalue-spill-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let spill_files = || fs::read_dir(&dir).unwrap().count();
+        let arena = Bump::new();
+        {
+            let mut set = DocumentSet::new(4).with_spill(SpillPolicy::new(&dir, 300));
+            for i in 0..40 {
+                let json = format!(r#"{{"id": {}, "name": "item {}"}}"#, i, i);
+                set.insert(format!("k{}", i), &json).unwrap();
+                assert!(
+                    set.resident_bytes() <= 300
+                        || (0..4).filter(|&s| !set.is_spilled(s)).count() == 1
+                );
+            }
+            assert!(set.insert("k3", r#"{"id": 3, "name": "three"}"#).unwrap());
+            let spilled = (0..4).filter(|&shard| set.is_spilled(shard)).count();
+            assert!(spilled > 0);
+            assert_eq!(spill_files(), spilled);
+            assert_eq!(set.len(), 40);
+            assert!(set.contains_key("k39"));
+
+            // Bulk operations read spilled shards without loading them
+            assert_eq!(set.query(&arena, "/id").unwrap().len(), 40);
+            let sum = set.aggregate(&arena, Aggregation::Sum("/id")).unwrap();
+            assert_eq!(sum.as_i64(), Some((0..40).sum()));
+            assert_eq!(spill_files(), spilled);
+
+            // Access loads a shard back and spills a colder one instead
+            let cold = (0..4).find(|&shard| set.is_spilled(shard)).unwrap();
+            let key = (0..40)
+                .map(|i| format!("k{}", i))
+                .find(|key| set.shard_of(key) == cold)
+                .unwrap();
+            assert!(set.get(&arena, &key).unwrap()["name"].is_string());
+            assert!(!set.is_spilled(cold));
+            assert_eq!(
+                set.get(&arena, "k3").unwrap()["name"].as_str(),
+                Some("three")
+            );
+            assert!(set.remove(&key).unwrap());
+            assert_eq!(set.len(), 39);
+
+            let other = (0..4).find(|&shard| set.is_spilled(shard)).unwrap();
+            let count = set.shard_len(other);
+            assert_eq!(set.evict_shard(other), count);
+            assert_eq!(set.len(), 39 - count);
+        }
+        // Dropping the set removes its spill files
+        assert_eq!(spill_files(), 0);
+        fs::remove_dir(&dir).unwrap();
+    }
+}
@@ -0,0 +1,339 @@
+//! Configuration sources
+//!
+//! Builds nested DataValue objects from flat configuration sources such as
+//! environment variables and command-line arguments, so they can be layered over
+//! file-based configuration.
+
+use bumpalo::Bump;
+
+use crate::datavalue::{DataValue, Number};
+use crate::error::{Error, Result};
+
+/// Builds a nested object from environment variables
+///
+/// Variables starting with `prefix` are included with the prefix removed. The rest
+/// of the name is split on `separator` into a path of keys, and each key is
+/// lowercased, so `APP_DB__HOST` with prefix `APP_` and separator `__` becomes
+/// `{"db": {"host": ...}}`.
+///
+/// Values are typed the same way as [`from_vars`] describes.
+///
+/// # Arguments
+///
+/// * `arena` - The arena allocator to store keys and values
+/// * `prefix` - Prefix selecting the variables to include
+/// * `separator` - Separator between nested keys
+///
+/// # Returns
+///
+/// A Result containing the object, or an Error if one variable is both a value
+/// and a parent of another (e.g. `APP_DB` and `APP_DB__HOST`), or if the name or
+/// value of a variable starting with `prefix` is not valid UTF-8. Variables
+/// without the prefix are skipped whatever they contain.
+pub fn from_env<'a>(arena: &'a Bump, prefix: &str, separator: &str) -> Result<DataValue<'a>> {
+    let mut vars = Vec::new();
+    for (name, value) in std::env::vars_os() {
+        if !name.as_encoded_bytes().starts_with(prefix.as_bytes()) {
+            continue;
+        }
+        let not_utf8 = |name: &std::ffi::OsStr| {
+            Error::custom(format!(
+                "Environment variable {:?} is not valid UTF-8",
+                name
+            ))
+        };
+        let value = value.into_string().map_err(|_| not_utf8(&name))?;
+        let name = name.into_string().map_err(|name| not_utf8(&name))?;
+        vars.push((name, value));
+    }
+    from_vars(arena, vars, prefix, separator)
+}
+
+/// Builds a nested object from `(name, value)` pairs using the rules of [`from_env`]
+///
+/// Values are typed conservatively: `true` and `false` become booleans, integers
+/// without leading zeros and finite decimal numbers become numbers, and
+/// everything else (including `0123` and the empty string) stays a string. Keys
+/// are sorted so the result does not depend on the iteration order of the source.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::{config, Bump};
+///
+/// let arena = Bump::new();
+/// let vars = [
+///     ("APP_DB__HOST", "localhost"),
+///     ("APP_DB__PORT", "5432"),
+///     ("APP_DEBUG", "true"),
+///     ("APP_ZIP", "01234"),
+///     ("HOME", "/root"),
+/// ];
+/// let config = config::from_vars(&arena, vars, "APP_", "__").unwrap();
+///
+/// assert_eq!(config["db"]["host"].as_str(), Some("localhost"));
+/// assert_eq!(config["db"]["port"].as_i64(), Some(5432));
+/// assert_eq!(config["debug"].as_bool(), Some(true));
+/// assert_eq!(config["zip"].as_str(), Some("01234"));
+/// assert!(config.get("home").is_none());
+/// ```
+pub fn from_vars<'a, I, K, V>(
+    arena: &'a Bump,
+    vars: I,
+    prefix: &str,
+    separator: &str,
+) -> Result<DataValue<'a>>
+where
+    I: IntoIterator<Item = (K, V)>,
+    K: AsRef<str>,
+    V: AsRef<str>,
+{
+    if separator.is_empty() {
+        return Err(Error::custom("Separator must not be empty"));
+    }
+    let mut root = Node::Branch(Vec::new());
+    for (name, value) in vars {
+        let name = name.as_ref();
+        let rest = match name.strip_prefix(prefix) {
+            Some(rest) if !rest.is_empty() => rest,
+            _ => continue,
+        };
+        let path: Vec<String> = rest.split(separator).map(str::to_lowercase).collect();
+        if path.iter().any(String::is_empty) {
+            return Err(Error::custom(format!("Empty key segment in {}", name)));
+        }
+        root.insert(&path, infer_scalar(arena, value.as_ref()), name)?;
+    }
+    Ok(root.build(arena))
+}
+
+/// Builds a nested object from command-line arguments
+///
+/// Each option starts with `--` and names a dotted path of keys. Three forms are
+/// accepted: `--db.host=x`, `--db.host x`, and a bare `--verbose`, which sets the
+/// key to `true`. Values are typed the same way as [`from_vars`] describes, and a
+/// repeated option overrides the earlier one.
+///
+/// # Arguments
+///
+/// * `arena` - The arena allocator to store keys and values
+/// * `args` - The arguments, without the program name
+///
+/// # Returns
+///
+/// A Result containing the object, or an Error if an argument is not an option,
+/// has an empty key segment, or conflicts with another option (e.g. `--db=x` and
+/// `--db.host=y`).
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::{from_args, Bump};
+///
+/// let arena = Bump::new();
+/// let args = ["--db.host=x", "--retries=3", "--db.port", "5432", "--dry-run"];
+/// let config = from_args(&arena, &args).unwrap();
+///
+/// assert_eq!(config["db"]["host"].as_str(), Some("x"));
+/// assert_eq!(config["db"]["port"].as_i64(), Some(5432));
+/// assert_eq!(config["retries"].as_i64(), Some(3));
+/// assert_eq!(config["dry-run"].as_bool(), Some(true));
+/// ```
+pub fn from_args<'a, S: AsRef<str>>(arena: &'a Bump, args: &[S]) -> Result<DataValue<'a>> {
+    let mut root = Node::Branch(Vec::new());
+    let mut args = args.iter().map(AsRef::as_ref).peekable();
+    while let Some(arg) = args.next() {
+        let option = arg
+            .strip_prefix("--")
+            .filter(|option| !option.is_empty())
+            .ok_or_else(|| Error::custom(format!("Unexpected argument {}", arg)))?;
+        let (name, value) = match option.split_once('=') {
+            Some((name, value)) => (name, infer_scalar(arena, value)),
+            None => match args.next_if(|next| !next.starts_with("--")) {
+                Some(value) => (option, infer_scalar(arena, value)),
+                None => (option, DataValue::Bool(true)),
+            },
+        };
+        let path: Vec<String> = name.split('.').map(str::to_string).collect();
+        if path.iter().any(String::is_empty) {
+            return Err(Error::custom(format!("Empty key segment in {}", arg)));
+        }
+        root.insert(&path, value, arg)?;
+    }
+    Ok(root.build(arena))
+}
+
+/// Types a textual configuration value, see [`from_vars`]
+pub(crate) fn infer_scalar<'a>(arena: &'a Bump, text: &str) -> DataValue<'a> {
+    match text {
+        "true" => return DataValue::Bool(true),
+        "false" => return DataValue::Bool(false),
+        _ => {}
+    }
+    let digits = text.strip_prefix('-').unwrap_or(text);
+    let leading_zero = digits.len() > 1 && digits.starts_with('0') && !digits[1..].starts_with('.');
+    let numeric = !digits.is_empty()
+        && digits.bytes().next().is_some_and(|b| b.is_ascii_digit())
+        && digits
+            .bytes()
+            .all(|b| b.is_ascii_digit() || matches!(b, b'.' | b'e' | b'E' | b'+' | b'-'));
+    if numeric && !leading_zero {
+        if let Ok(i) = text.parse::<i64>() {
+            return DataValue::Number(Number::Integer(i));
+        }
+        if let Ok(f) = text.parse::<f64>() {
+            if f.is_finite() {
+                return DataValue::Number(Number::Float(f));
+            }
+        }
+    }
+    DataValue::String(arena.alloc_str(text))
+}
+
+/// Intermediate tree used while collecting flat key paths
+pub(crate) enum Node<'a> {
+    Leaf(DataValue<'a>),
+    Branch(Vec<(String, Node<'a>)>),
+}
+
+impl<'a> Node<'a> {
+    /// Inserts a value at `path`, replacing an earlier value at the same path
+    pub(crate) fn insert(
+        &mut self,
+        path: &[String],
+        value: DataValue<'a>,
+        source: &str,
+    ) -> Result<()> {
+        let conflict = || Error::custom(format!("Conflicting configuration key {}", source));
+        let Node::Branch(children) = self else {
+            return Err(conflict());
+        };
+        let (head, rest) = path.split_first().ok_or_else(conflict)?;
+        let index = match children.iter().position(|(key, _)| key == head) {
+            Some(index) => index,
+            None => {
+                let child = if rest.is_empty() {
+                    Node::Leaf(DataValue::Null)
+                } else {
+                    Node::Branch(Vec::new())
+                };
+                children.push((head.clone(), child));
+                children.len() - 1
+            }
+        };
+        match (&mut children[index].1, rest.is_empty()) {
+            (Node::Leaf(slot), true) => {
+                *slot = value;
+                Ok(())
+            }
+            (child @ Node::Branch(_), false) => child.insert(rest, value, source),
+            _ => Err(conflict()),
+        }
+    }
+
+    /// Allocates the tree in the arena, with object keys sorted
+    pub(crate) fn build(self, arena: &'a Bump) -> DataValue<'a> {
+        match self {
+            Node::Leaf(value) => value,
+            Node::Branch(mut children) => {
+                children.sort_by(|a, b| a.0.cmp(&b.0));
+                let entries: Vec<(&'a str, DataValue<'a>)> = children
+                    .into_iter()
+                    .map(|(key, child)| (&*arena.alloc_str(&key), child.build(arena)))
+                    .collect();
+                DataValue::Object(arena.alloc_slice_clone(&entries))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_infer_scalar() {
+        let arena = Bump::new();
+        let cases = [
+            ("42", "42"),
+            ("-7", "-7"),
+            ("0", "0"),
+            ("2.5", "2.5"),
+            ("0.5", "0.5"),
+            ("1e3", "1000.0"),
+            ("007", "\"007\""),
+            ("", "\"\""),
+            ("inf", "\"inf\""),
+            ("1.2.3", "\"1.2.3\""),
+            ("True", "\"True\""),
+            ("false", "false"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(
+                crate::to_string(&infer_scalar(&arena, input)),
+                expected,
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_conflicts_and_sorting() {
+        let arena = Bump::new();
+        let vars = [("X_B", "1"), ("X_A__C", "2"), ("X_A__B", "3")];
+        let value = from_vars(&arena, vars, "X_", "__").unwrap();
+        assert_eq!(crate::to_string(&value), r#"{"a":{"b":3,"c":2},"b":1}"#);
+
+        let conflicting = [("X_DB", "x"), ("X_DB__HOST", "y")];
+        assert!(from_vars(&arena, conflicting, "X_", "__").is_err());
+        assert!(from_vars(&arena, [("X_A____B", "1")], "X_", "__").is_err());
+        assert!(from_vars(&arena, [("X_A", "1")], "X_", "").is_err());
+    }
+
+    #[test]
+    fn test_from_env() {
+        std::env::set_var("DATAVALUE_TEST_ENV__NESTED__KEY", "value");
+        let arena = Bump::new();
+        let value = from_env(&arena, "DATAVALUE_TEST_ENV__", "__").unwrap();
+        assert_eq!(value["nested"]["key"].as_str(), Some("value"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_from_env_with_non_utf8_variables() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let invalid = std::ffi::OsStr::from_bytes(b"\xff\xfe");
+        std::env::set_var("DATAVALUE_TEST_BYTES__VALUE", invalid);
+        let arena = Bump::new();
+        // Variables outside the prefix are skipped
+        assert!(from_env(&arena, "DATAVALUE_TEST_OTHER__", "__").is_ok());
+        let err = from_env(&arena, "DATAVALUE_TEST_BYTES__", "__").unwrap_err();
+        assert!(err.to_string().contains("not valid UTF-8"), "{}", err);
+        std::env::remove_var("DATAVALUE_TEST_BYTES__VALUE");
+    }
+
+    #[test]
+    fn test_from_args() {
+        let arena = Bump::new();
+        let args = [
+            "--a.b", "--a.c=-1", "--name", "x y", "--a.c", "2", "--empty=",
+        ];
+        let value = from_args(&arena, &args).unwrap();
+        assert_eq!(
+            crate::to_string(&value),
+            r#"{"a":{"b":true,"c":2},"empty":"","name":"x y"}"#
+        );
+
+        assert!(from_args(&arena, &["positional"]).is_err());
+        assert!(from_args(&arena, &["--"]).is_err());
+        assert!(from_args(&arena, &["--a..b=1"]).is_err());
+        assert!(from_args(&arena, &["--a=1", "--a.b=2"]).is_err());
+        assert!(from_args::<&str>(&arena, &[])
+            .unwrap()
+            .as_object()
+            .unwrap()
+            .is_empty());
+    }
+}
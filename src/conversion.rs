@@ -2,9 +2,16 @@
 //!
 //! This module provides implementations of the `From` trait for various Rust primitive types,
 //! allowing easy conversion to `DataValue`. Note that string conversions require arena allocation
-//! and thus can't be implemented directly with the `From` trait.
+//! and thus can't be implemented directly with the `From` trait; [`IntoDataValue`] covers
+//! those conversions by taking the arena as an argument. [`ToArenaValue`] does the
+//! same for Rust values, strings included, and backs the [`dv!`](crate::dv) macro.
 
+use bumpalo::Bump;
+use chrono::{DateTime, Duration, Utc};
+
+use crate::builder::ArrayBuilder;
 use crate::datavalue::{DataValue, Number};
+use crate::error::Result;
 
 /// Create DataValue from i8
 ///
@@ -135,3 +142,171 @@ impl From<bool> for DataValue<'_> {
 
 // Note: From<&str> or From<String> cannot be implemented here
 // because DataValue requires arena-based allocation for strings
+
+/// Conversion into a DataValue allocated in an arena
+///
+/// Puts the source first and the arena second, so conversions read left to right
+/// at the call site. Implemented for JSON text (`str` and UTF-8 `[u8]`), which is
+/// parsed, and, with the `serde_json-compat` feature, for `serde_json::Value`.
+///
+/// # Example
+///
+/// ```
+/// # use datavalue_rs::{Bump, IntoDataValue};
+/// let arena = Bump::new();
+///
+/// let from_text = r#"{"id": 1}"#.to_datavalue(&arena).unwrap();
+/// let from_bytes = b"{\"id\": 1}".to_datavalue(&arena).unwrap();
+/// assert_eq!(from_text, from_bytes);
+///
+/// # #[cfg(feature = "serde_json-compat")]
+/// # {
+/// let from_json = serde_json::json!({"id": 1}).to_datavalue(&arena).unwrap();
+/// assert_eq!(from_text, from_json);
+/// # }
+/// ```
+pub trait IntoDataValue {
+    /// Converts `self` into a DataValue allocated in `arena`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `self` is not valid JSON.
+    fn to_datavalue<'a>(&self, arena: &'a Bump) -> Result<DataValue<'a>>;
+}
+
+/// Parses the string as JSON, like [`crate::from_str`]
+impl IntoDataValue for str {
+    fn to_datavalue<'a>(&self, arena: &'a Bump) -> Result<DataValue<'a>> {
+        crate::from_str(arena, self)
+    }
+}
+
+/// Parses the bytes as UTF-8 JSON, like [`DataValue::from_slice`]
+impl IntoDataValue for [u8] {
+    fn to_datavalue<'a>(&self, arena: &'a Bump) -> Result<DataValue<'a>> {
+        DataValue::from_slice(arena, self)
+    }
+}
+
+/// Byte string literals such as `b"[1]"` are arrays rather than slices
+impl<const N: usize> IntoDataValue for [u8; N] {
+    fn to_datavalue<'a>(&self, arena: &'a Bump) -> Result<DataValue<'a>> {
+        DataValue::from_slice(arena, self)
+    }
+}
+
+/// Copies the value into the arena, like [`crate::from_json`]
+#[cfg(feature = "serde_json-compat")]
+impl IntoDataValue for serde_json::Value {
+    fn to_datavalue<'a>(&self, arena: &'a Bump) -> Result<DataValue<'a>> {
+        crate::from_json(arena, self)
+    }
+}
+
+/// Conversion of a Rust value into a DataValue in a given arena, used for the
+/// expressions interpolated by [`dv!`](crate::dv)
+///
+/// Strings are copied into the arena, numbers and booleans convert as their
+/// `From` impls do, `None` becomes `null`, and slices and vectors become arrays.
+/// A DataValue from the same arena is used as it is, without copying.
+///
+/// # Example
+///
+/// ```
+/// # use datavalue_rs::{Bump, ToArenaValue};
+/// let arena = Bump::new();
+/// let tags = vec!["a".to_string(), "b".to_string()];
+/// assert_eq!(datavalue_rs::to_string(&tags.to_arena_value(&arena)), r#"["a","b"]"#);
+/// assert!(None::<i64>.to_arena_value(&arena).is_null());
+/// ```
+pub trait ToArenaValue<'a> {
+    /// Converts `self` into a DataValue that lives in `arena`
+    fn to_arena_value(&self, arena: &'a Bump) -> DataValue<'a>;
+}
+
+impl<'a> ToArenaValue<'a> for DataValue<'a> {
+    fn to_arena_value(&self, _arena: &'a Bump) -> DataValue<'a> {
+        self.clone()
+    }
+}
+
+impl<'a> ToArenaValue<'a> for str {
+    fn to_arena_value(&self, arena: &'a Bump) -> DataValue<'a> {
+        DataValue::String(arena.alloc_str(self))
+    }
+}
+
+impl<'a> ToArenaValue<'a> for String {
+    fn to_arena_value(&self, arena: &'a Bump) -> DataValue<'a> {
+        self.as_str().to_arena_value(arena)
+    }
+}
+
+impl<'a> ToArenaValue<'a> for char {
+    fn to_arena_value(&self, arena: &'a Bump) -> DataValue<'a> {
+        self.encode_utf8(&mut [0; 4]).to_arena_value(arena)
+    }
+}
+
+macro_rules! to_arena_value_via_from {
+    ($($ty:ty),*) => {
+        $(
+            impl<'a> ToArenaValue<'a> for $ty {
+                fn to_arena_value(&self, _arena: &'a Bump) -> DataValue<'a> {
+                    DataValue::from(*self)
+                }
+            }
+        )*
+    };
+}
+
+to_arena_value_via_from!(bool, i8, i16, i32, i64, u8, u16, u32, u64, usize, f32, f64);
+
+impl<'a> ToArenaValue<'a> for DateTime<Utc> {
+    fn to_arena_value(&self, _arena: &'a Bump) -> DataValue<'a> {
+        DataValue::DateTime(*self)
+    }
+}
+
+impl<'a> ToArenaValue<'a> for Duration {
+    fn to_arena_value(&self, _arena: &'a Bump) -> DataValue<'a> {
+        DataValue::Duration(*self)
+    }
+}
+
+impl<'a, T: ToArenaValue<'a>> ToArenaValue<'a> for Option<T> {
+    fn to_arena_value(&self, arena: &'a Bump) -> DataValue<'a> {
+        match self {
+            Some(value) => value.to_arena_value(arena),
+            None => DataValue::Null,
+        }
+    }
+}
+
+impl<'a, T: ToArenaValue<'a>> ToArenaValue<'a> for [T] {
+    fn to_arena_value(&self, arena: &'a Bump) -> DataValue<'a> {
+        let mut items = ArrayBuilder::with_capacity(arena, self.len());
+        for item in self {
+            items = items.push(item.to_arena_value(arena));
+        }
+        items.finish()
+    }
+}
+
+impl<'a, T: ToArenaValue<'a>, const N: usize> ToArenaValue<'a> for [T; N] {
+    fn to_arena_value(&self, arena: &'a Bump) -> DataValue<'a> {
+        self.as_slice().to_arena_value(arena)
+    }
+}
+
+impl<'a, T: ToArenaValue<'a>> ToArenaValue<'a> for Vec<T> {
+    fn to_arena_value(&self, arena: &'a Bump) -> DataValue<'a> {
+        self.as_slice().to_arena_value(arena)
+    }
+}
+
+impl<'a, T: ToArenaValue<'a> + ?Sized> ToArenaValue<'a> for &T {
+    fn to_arena_value(&self, arena: &'a Bump) -> DataValue<'a> {
+        (**self).to_arena_value(arena)
+    }
+}
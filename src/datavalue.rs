@@ -3,6 +3,9 @@
 //! This module defines the primary `DataValue` enum and related types,
 //! which serve as an arena-based equivalent to `serde_json::Value`.
 
+use crate::helpers::format_duration;
+use crate::ser::{fmt_f64, fmt_json_string, write_tree, Layout};
+use bumpalo::Bump;
 use chrono::{DateTime, Duration, Utc};
 use std::fmt;
 use std::ops::Index;
@@ -35,7 +38,7 @@ pub enum DataValue<'a> {
     /// Represents a JSON boolean value.
     Bool(bool),
     /// Represents a JSON number value (either integer or floating point).
-    Number(Number),
+    Number(Number<'a>),
     /// Represents a JSON string value, stored as a reference to a string in the arena.
     String(&'a str),
     /// Represents a JSON array, containing a list of DataValue elements.
@@ -46,6 +49,7 @@ pub enum DataValue<'a> {
     DateTime(DateTime<Utc>),
     /// Represents a JSON duration value, stored as a reference to a string in the arena.
     Duration(Duration),
+    Raw(&'a str),
 }
 
 /// Represents the type of a DataValue
@@ -105,11 +109,71 @@ pub enum DataValueType {
 /// assert_eq!(float_val.as_f64(), Some(3.14));
 /// ```
 #[derive(Debug, Clone, Copy)]
-pub enum Number {
+pub enum Number<'a> {
     /// Integer number representation
     Integer(i64),
     /// Floating point number representation
     Float(f64),
+    /// The exact text of a number in the input, kept by [`NumberMode::Raw`]
+    ///
+    /// Serializers write the text unchanged, so numbers that do not fit an `i64`
+    /// or `f64` survive a round trip. Accessors and operations use the parsed value
+    /// returned by [`Number::parsed`].
+    ///
+    /// [`NumberMode::Raw`]: crate::NumberMode::Raw
+    Raw(&'a str),
+    /// An integer outside the `i64` range, kept by [`NumberMode::Arbitrary`]
+    ///
+    /// [`NumberMode::Arbitrary`]: crate::NumberMode::Arbitrary
+    #[cfg(feature = "bignum")]
+    BigInt(crate::bignum::BigInt<'a>),
+    /// A decimal with more precision or range than an `f64`, kept by
+    /// [`NumberMode::Arbitrary`]
+    ///
+    /// [`NumberMode::Arbitrary`]: crate::NumberMode::Arbitrary
+    #[cfg(feature = "bignum")]
+    BigDecimal(crate::bignum::BigDecimal<'a>),
+}
+
+impl Number<'_> {
+    /// Returns the number as an integer or float, parsing raw number text
+    ///
+    /// Raw text that is an integer in the `i64` range becomes [`Number::Integer`];
+    /// anything else becomes the nearest [`Number::Float`]. Arbitrary-precision
+    /// numbers are converted the same way.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use datavalue_rs::Number;
+    ///
+    /// assert!(matches!(Number::Raw("42").parsed(), Number::Integer(42)));
+    /// assert!(matches!(Number::Raw("1.50").parsed(), Number::Float(f) if f == 1.5));
+    /// assert!(matches!(Number::Raw("18446744073709551616").parsed(), Number::Float(_)));
+    /// ```
+    pub fn parsed(self) -> Number<'static> {
+        match self {
+            Number::Integer(i) => Number::Integer(i),
+            Number::Float(f) => Number::Float(f),
+            Number::Raw(text) => match text.parse::<i64>() {
+                Ok(i) => Number::Integer(i),
+                Err(_) => Number::Float(text.parse::<f64>().unwrap_or(f64::NAN)),
+            },
+            #[cfg(feature = "bignum")]
+            Number::BigInt(n) => match n.to_i64() {
+                Some(i) => Number::Integer(i),
+                None => Number::Float(n.to_f64()),
+            },
+            #[cfg(feature = "bignum")]
+            Number::BigDecimal(n) => Number::Float(n.to_f64()),
+        }
+    }
+
+    /// Returns true for [`Number::BigInt`] and [`Number::BigDecimal`]
+    #[cfg(feature = "bignum")]
+    pub fn is_big(&self) -> bool {
+        matches!(self, Number::BigInt(_) | Number::BigDecimal(_))
+    }
 }
 
 impl<'a> DataValue<'a> {
@@ -134,13 +198,185 @@ impl<'a> DataValue<'a> {
         match self {
             DataValue::Null => DataValueType::Null,
             DataValue::Bool(_) => DataValueType::Bool,
-            DataValue::Number(Number::Integer(_)) => DataValueType::Integer,
-            DataValue::Number(Number::Float(_)) => DataValueType::Float,
+            #[cfg(feature = "bignum")]
+            DataValue::Number(Number::BigInt(_)) => DataValueType::Integer,
+            DataValue::Number(n) => match n.parsed() {
+                Number::Integer(_) => DataValueType::Integer,
+                _ => DataValueType::Float,
+            },
             DataValue::String(_) => DataValueType::String,
             DataValue::Array(_) => DataValueType::Array,
             DataValue::Object(_) => DataValueType::Object,
             DataValue::DateTime(_) => DataValueType::DateTime,
             DataValue::Duration(_) => DataValueType::Duration,
+            DataValue::Raw(text) => match text.trim_start().as_bytes().first() {
+                Some(b'{') => DataValueType::Object,
+                Some(b'[') => DataValueType::Array,
+                Some(b'"') => DataValueType::String,
+                Some(b't' | b'f') => DataValueType::Bool,
+                Some(b'-' | b'0'..=b'9') => DataValue::Number(Number::Raw(text.trim())).get_type(),
+                _ => DataValueType::Null,
+            },
+        }
+    }
+
+    /// Parses a [`DataValue::Raw`] value, returning any other value unchanged
+    ///
+    /// Strings without escapes borrow from the raw text. Comments and trailing
+    /// commas are accepted, since the text may have been kept from a JSONC document.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use datavalue_rs::{from_str_with_options, Bump, DataValueType, ParseOptions};
+    ///
+    /// let arena = Bump::new();
+    /// let options = ParseOptions {
+    ///     raw_keys: vec!["payload".to_string()],
+    ///     ..ParseOptions::default()
+    /// };
+    /// let value = from_str_with_options(&arena, r#"{"payload": {"id": 7}}"#, &options).unwrap();
+    ///
+    /// let payload = &value["payload"];
+    /// assert_eq!(payload.get_type(), DataValueType::Object);
+    /// assert_eq!(payload.get("id"), None);
+    /// assert_eq!(payload.parse_raw(&arena).unwrap()["id"].as_i64(), Some(7));
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns a syntax error if the raw text is not valid JSON.
+    pub fn parse_raw(&self, arena: &'a Bump) -> crate::Result<DataValue<'a>> {
+        match *self {
+            DataValue::Raw(text) => {
+                let options = crate::ParseOptions {
+                    allow_comments: true,
+                    allow_trailing_commas: true,
+                    ..crate::ParseOptions::default()
+                };
+                crate::de::from_str_borrowed_with_options(arena, text, &options)
+            }
+            ref value => Ok(value.clone()),
+        }
+    }
+
+    /// Copies the value, and every array, object and string it references, into `arena`
+    ///
+    /// `clone` copies only the top-level value, so the copy still borrows from the
+    /// arena the original lives in. Use `clone_in` to keep part of a document after
+    /// its arena is dropped or reset. Nested values are copied with an explicit
+    /// stack, so any depth is safe.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use datavalue_rs::{from_str, Bump, DataValue};
+    ///
+    /// let keep = Bump::new();
+    /// let copy: DataValue<'_> = {
+    ///     let scratch = Bump::new();
+    ///     let value = from_str(&scratch, r#"{"user": {"name": "ada", "tags": [1, 2]}}"#).unwrap();
+    ///     value["user"].clone_in(&keep)
+    /// };
+    /// assert_eq!(copy["name"].as_str(), Some("ada"));
+    /// assert_eq!(copy["tags"][1].as_i64(), Some(2));
+    /// ```
+    pub fn clone_in<'b>(&self, arena: &'b Bump) -> DataValue<'b> {
+        /// An array or object whose children are being copied
+        enum Frame<'v, 'a, 'b> {
+            Array(std::slice::Iter<'v, DataValue<'a>>, Vec<DataValue<'b>>),
+            /// The entries left, the copied entries and the key of the value being copied
+            Object(
+                std::slice::Iter<'v, (&'a str, DataValue<'a>)>,
+                Vec<(&'b str, DataValue<'b>)>,
+                &'b str,
+            ),
+        }
+
+        let mut stack: Vec<Frame<'_, 'a, 'b>> = Vec::new();
+        let mut next = Some(self);
+        loop {
+            let mut copied = match next.take() {
+                Some(DataValue::Array(items)) => {
+                    stack.push(Frame::Array(items.iter(), Vec::with_capacity(items.len())));
+                    None
+                }
+                Some(DataValue::Object(entries)) => {
+                    let copied = Vec::with_capacity(entries.len());
+                    stack.push(Frame::Object(entries.iter(), copied, ""));
+                    None
+                }
+                Some(scalar) => Some(scalar.clone_scalar_in(arena)),
+                None => None,
+            };
+            // Store the copy in its parent, and finish every container whose
+            // children have all been copied
+            loop {
+                let Some(frame) = stack.last_mut() else {
+                    // The root is always copied by the time the stack is empty
+                    return copied.unwrap_or(DataValue::Null);
+                };
+                let child = match frame {
+                    Frame::Array(items, done) => {
+                        done.extend(copied.take());
+                        items.next()
+                    }
+                    Frame::Object(entries, done, key) => {
+                        done.extend(copied.take().map(|value| (*key, value)));
+                        entries.next().map(|(next_key, value)| {
+                            *key = arena.alloc_str(next_key);
+                            value
+                        })
+                    }
+                };
+                if child.is_some() {
+                    next = child;
+                    break;
+                }
+                copied = match stack.pop() {
+                    Some(Frame::Array(_, done)) => {
+                        Some(DataValue::Array(arena.alloc_slice_clone(&done)))
+                    }
+                    Some(Frame::Object(_, done, _)) => {
+                        Some(DataValue::Object(arena.alloc_slice_clone(&done)))
+                    }
+                    None => None,
+                };
+            }
+        }
+    }
+
+    /// Copies a value that is not an array or object into `arena`
+    fn clone_scalar_in<'b>(&self, arena: &'b Bump) -> DataValue<'b> {
+        match *self {
+            DataValue::Null => DataValue::Null,
+            DataValue::Bool(b) => DataValue::Bool(b),
+            DataValue::Number(Number::Integer(i)) => DataValue::Number(Number::Integer(i)),
+            DataValue::Number(Number::Float(f)) => DataValue::Number(Number::Float(f)),
+            DataValue::Number(Number::Raw(text)) => {
+                DataValue::Number(Number::Raw(arena.alloc_str(text)))
+            }
+            #[cfg(feature = "bignum")]
+            DataValue::Number(Number::BigInt(n)) => {
+                DataValue::Number(Number::BigInt(n.clone_in(arena)))
+            }
+            #[cfg(feature = "bignum")]
+            DataValue::Number(Number::BigDecimal(n)) => {
+                DataValue::Number(Number::BigDecimal(n.clone_in(arena)))
+            }
+            DataValue::String(s) => DataValue::String(arena.alloc_str(s)),
+            DataValue::DateTime(dt) => DataValue::DateTime(dt),
+            DataValue::Duration(dur) => DataValue::Duration(dur),
+            DataValue::Raw(text) => DataValue::Raw(arena.alloc_str(text)),
+            DataValue::Array(_) | DataValue::Object(_) => DataValue::Null,
+        }
+    }
+
+    /// Replaces raw number text with its parsed value, leaving other values unchanged
+    pub(crate) fn with_parsed_number(self) -> Self {
+        match self {
+            DataValue::Number(n) => DataValue::Number(n.parsed()),
+            value => value,
         }
     }
 
@@ -168,6 +404,74 @@ impl<'a> DataValue<'a> {
         }
     }
 
+    /// Returns the boolean meaning of common truthy and falsy encodings
+    ///
+    /// Unlike [`DataValue::as_bool`], this accepts the encodings often found in
+    /// feature flags and form data:
+    ///
+    /// | Value                                  | Result        |
+    /// |----------------------------------------|---------------|
+    /// | `true` / `false`                       | `Some(value)` |
+    /// | `1` / `0` (integer or float)           | `Some(true)` / `Some(false)` |
+    /// | `"true"` / `"false"`                   | `Some(true)` / `Some(false)` |
+    /// | `"yes"` / `"no"`                       | `Some(true)` / `Some(false)` |
+    /// | `"1"` / `"0"`                          | `Some(true)` / `Some(false)` |
+    /// | anything else                          | `None`        |
+    ///
+    /// String matching ignores ASCII case but not surrounding whitespace, so
+    /// `"Yes"` is true while `" yes"` and `"2"` are `None`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use datavalue_rs::{Bump, from_str};
+    /// let arena = Bump::new();
+    /// let flags = from_str(&arena, r#"[true, 1, "yes", "FALSE", 0.0, "maybe", 2]"#).unwrap();
+    /// let parsed: Vec<Option<bool>> = flags
+    ///     .as_array()
+    ///     .unwrap()
+    ///     .iter()
+    ///     .map(|flag| flag.as_bool_lenient())
+    ///     .collect();
+    ///
+    /// assert_eq!(
+    ///     parsed,
+    ///     [Some(true), Some(true), Some(true), Some(false), Some(false), None, None]
+    /// );
+    /// ```
+    pub fn as_bool_lenient(&self) -> Option<bool> {
+        match self {
+            DataValue::Bool(b) => Some(*b),
+            DataValue::Number(Number::Integer(1)) => Some(true),
+            DataValue::Number(Number::Integer(0)) => Some(false),
+            DataValue::Number(Number::Float(f)) if *f == 1.0 => Some(true),
+            DataValue::Number(Number::Float(f)) if *f == 0.0 => Some(false),
+            DataValue::Number(n @ Number::Raw(_)) => {
+                DataValue::Number(n.parsed()).as_bool_lenient()
+            }
+            #[cfg(feature = "bignum")]
+            DataValue::Number(n @ (Number::BigInt(_) | Number::BigDecimal(_))) => {
+                DataValue::Number(n.parsed()).as_bool_lenient()
+            }
+            DataValue::String(s) => {
+                if ["true", "yes", "1"]
+                    .iter()
+                    .any(|t| s.eq_ignore_ascii_case(t))
+                {
+                    Some(true)
+                } else if ["false", "no", "0"]
+                    .iter()
+                    .any(|f| s.eq_ignore_ascii_case(f))
+                {
+                    Some(false)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+
     /// Returns the integer value if this DataValue is an integer number, otherwise None.
     ///
     /// # Example
@@ -188,6 +492,9 @@ impl<'a> DataValue<'a> {
     pub fn as_i64(&self) -> Option<i64> {
         match self {
             DataValue::Number(Number::Integer(i)) => Some(*i),
+            DataValue::Number(Number::Raw(text)) => text.parse().ok(),
+            #[cfg(feature = "bignum")]
+            DataValue::Number(Number::BigInt(n)) => n.to_i64(),
             _ => None,
         }
     }
@@ -214,6 +521,11 @@ impl<'a> DataValue<'a> {
         match self {
             DataValue::Number(Number::Integer(i)) => Some(*i as f64),
             DataValue::Number(Number::Float(f)) => Some(*f),
+            DataValue::Number(Number::Raw(text)) => text.parse().ok(),
+            #[cfg(feature = "bignum")]
+            DataValue::Number(Number::BigInt(n)) => Some(n.to_f64()),
+            #[cfg(feature = "bignum")]
+            DataValue::Number(Number::BigDecimal(n)) => Some(n.to_f64()),
             _ => None,
         }
     }
@@ -349,12 +661,67 @@ impl<'a> DataValue<'a> {
     ///
     /// Equivalent to serde_json::Value::get
     pub fn get(&self, key: &str) -> Option<&DataValue<'a>> {
+        self.get_bytes(key.as_bytes())
+    }
+
+    /// Gets the value for a key given as raw bytes, if this DataValue is an object
+    ///
+    /// Keys are compared byte for byte, so a key sliced out of a network buffer can
+    /// be looked up without first checking that it is UTF-8 with
+    /// `std::str::from_utf8`. This needs no `unsafe`: object keys are always valid
+    /// UTF-8, so a slice that is not simply matches nothing and the result is `None`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use datavalue_rs::{Bump, from_str_borrowed};
+    /// let arena = Bump::new();
+    /// let value = from_str_borrowed(&arena, r#"{"id": 7, "näme": "x"}"#).unwrap();
+    ///
+    /// let frame: &[u8] = b"GET id\r\n";
+    /// assert_eq!(value.get_bytes(&frame[4..6]).and_then(|v| v.as_i64()), Some(7));
+    /// assert_eq!(value.get_bytes("näme".as_bytes()).and_then(|v| v.as_str()), Some("x"));
+    /// assert!(value.get_bytes(b"n\xE4me").is_none());
+    /// ```
+    pub fn get_bytes(&self, key: &[u8]) -> Option<&DataValue<'a>> {
         match self {
-            DataValue::Object(o) => o.iter().find(|(k, _)| *k == key).map(|(_, v)| v),
+            DataValue::Object(o) => o.iter().find(|(k, _)| k.as_bytes() == key).map(|(_, v)| v),
             _ => None,
         }
     }
 
+    /// Returns every value stored under `key`, in object order
+    ///
+    /// Objects are stored as slices of entries, so a key may intentionally appear
+    /// more than once, as in HTTP headers or query parameters. [`DataValue::get`]
+    /// returns only the first value; this returns all of them. The iterator is empty
+    /// if the key is absent or this DataValue is not an object.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use datavalue_rs::{Bump, from_str};
+    /// let arena = Bump::new();
+    /// let headers = from_str(&arena, r#"{"Accept": "text/html", "Set-Cookie": "a=1", "Set-Cookie": "b=2"}"#).unwrap();
+    ///
+    /// let cookies: Vec<&str> = headers
+    ///     .get_all("Set-Cookie")
+    ///     .filter_map(|v| v.as_str())
+    ///     .collect();
+    /// assert_eq!(cookies, ["a=1", "b=2"]);
+    /// assert_eq!(headers.get_all("Host").count(), 0);
+    /// ```
+    pub fn get_all<'s>(&'s self, key: &'s str) -> impl Iterator<Item = &'s DataValue<'a>> + 's {
+        let entries: &'s [(&'a str, DataValue<'a>)] = match self {
+            DataValue::Object(o) => o,
+            _ => &[],
+        };
+        entries
+            .iter()
+            .filter(move |(k, _)| *k == key)
+            .map(|(_, v)| v)
+    }
+
     /// Checks if this DataValue object contains the specified key.
     ///
     /// # Example
@@ -413,40 +780,33 @@ impl fmt::Display for DataValue<'_> {
     /// Formats the DataValue as a JSON string.
     ///
     /// This provides a compact JSON representation of the value without extra whitespace.
+    /// DateTime values are written as RFC 3339 strings and Duration values as ISO 8601
+    /// durations in the format of [`crate::helpers::format_duration`], and NaN and
+    /// infinite floats as `null`, so the output is always valid JSON. Set
+    /// [`crate::ParseOptions::revive_datetimes`] to read the timestamps back as
+    /// DateTimes; durations are recognized by default.
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
+        let scalar = |value: &DataValue<'_>, f: &mut fmt::Formatter<'_>| match value {
             DataValue::Null => write!(f, "null"),
             DataValue::Bool(b) => write!(f, "{}", b),
-            DataValue::Number(Number::Integer(i)) => write!(f, "{}", i),
-            DataValue::Number(Number::Float(fl)) => write!(f, "{}", fl),
-            DataValue::String(s) => write!(f, "\"{}\"", s.replace('\"', "\\\"")),
-            DataValue::Array(arr) => {
-                write!(f, "[")?;
-                let mut first = true;
-                for item in arr.iter() {
-                    if !first {
-                        write!(f, ",")?;
-                    }
-                    write!(f, "{}", item)?;
-                    first = false;
-                }
-                write!(f, "]")
-            }
-            DataValue::Object(obj) => {
-                write!(f, "{{")?;
-                let mut first = true;
-                for (key, value) in obj.iter() {
-                    if !first {
-                        write!(f, ",")?;
-                    }
-                    write!(f, "\"{}\":{}", key, value)?;
-                    first = false;
-                }
-                write!(f, "}}")
-            }
-            DataValue::Duration(dur) => write!(f, "{}", dur),
-            DataValue::DateTime(dt) => write!(f, "{}", dt),
-        }
+            DataValue::Number(Number::Integer(i)) => f.write_str(itoa::Buffer::new().format(*i)),
+            DataValue::Number(Number::Float(fl)) => fmt_f64(*fl, f),
+            DataValue::Number(Number::Raw(text)) => f.write_str(text),
+            #[cfg(feature = "bignum")]
+            DataValue::Number(Number::BigInt(n)) => write!(f, "{}", n),
+            #[cfg(feature = "bignum")]
+            DataValue::Number(Number::BigDecimal(n)) => write!(f, "{}", n),
+            DataValue::String(s) => fmt_json_string(s, f),
+            DataValue::Duration(dur) => fmt_json_string(&format_duration(dur), f),
+            DataValue::DateTime(dt) => fmt_json_string(&dt.to_rfc3339(), f),
+            DataValue::Raw(text) => f.write_str(text),
+            // write_tree only leaves empty arrays and objects to this closure
+            DataValue::Array(_) => f.write_str("[]"),
+            DataValue::Object(_) => f.write_str("{}"),
+        };
+        write_tree(self, Layout::default(), f, scalar, |(key, _), f| {
+            fmt_json_string(key, f)
+        })
     }
 }
 
@@ -523,7 +883,7 @@ mod tests {
             DataValueType::Integer
         );
         assert_eq!(
-            DataValue::Number(Number::Float(3.14)).get_type(),
+            DataValue::Number(Number::Float(2.5)).get_type(),
             DataValueType::Float
         );
 
@@ -547,4 +907,25 @@ mod tests {
         let dur_val = DataValue::Duration(Duration::seconds(10));
         assert_eq!(dur_val.get_type(), DataValueType::Duration);
     }
+
+    #[test]
+    fn test_as_bool_lenient() {
+        let cases = [
+            (DataValue::Bool(false), Some(false)),
+            (DataValue::Number(Number::Integer(0)), Some(false)),
+            (DataValue::Number(Number::Float(1.0)), Some(true)),
+            (DataValue::Number(Number::Float(0.5)), None),
+            (DataValue::Number(Number::Integer(-1)), None),
+            (DataValue::String("No"), Some(false)),
+            (DataValue::String("TRUE"), Some(true)),
+            (DataValue::String("1"), Some(true)),
+            (DataValue::String(" yes"), None),
+            (DataValue::String(""), None),
+            (DataValue::Null, None),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(value.as_bool_lenient(), expected, "{:?}", value);
+        }
+        assert_eq!(DataValue::String("1").as_bool(), None);
+    }
 }
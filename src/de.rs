@@ -1,18 +1,62 @@
 //! Deserialization functionality for DataValue
 //!
 //! This module provides functions to deserialize JSON strings into DataValue instances
-//! and to convert serde_json::Value structures to DataValue.
+//! and to convert serde_json::Value structures to DataValue. The [`events`] module
+//! parses documents as a stream of events without building a tree, the
+//! [`ndjson`] module reads newline-delimited JSON, and [`lenient`] repairs
+//! malformed input. [`FeedParser`] parses input that arrives in chunks, and
+//! [`from_value`] extracts typed data from a parsed tree, [`DataValueSeed`]
+//! reads a tree from any serde format, and [`JsonDeserializer`] feeds JSON text to
+//! serde without building a tree at all. With the `json5` feature,
+//! [`json5::from_str_json5`] parses JSON5, and with the `async` feature,
+//! [`DataValue::from_reader_async`] and [`DataValue::from_stream`] feed a
+//! `FeedParser` from a tokio `AsyncRead` or a stream of byte chunks.
 
+use crate::access::escape_pointer_token;
 use crate::datavalue::{DataValue, Number};
-use crate::error::{Error, Result};
+use crate::error::{Error, Location, Result};
+use crate::helpers;
+use crate::metrics;
+use crate::ser::NonFiniteFloats;
+use crate::unicode::{self, NormalizationForm};
 use bumpalo::Bump;
-use serde::de::Deserializer;
+use std::borrow::Cow;
+use std::collections::HashMap;
 use std::io::Read;
+use std::ops::Range;
 
-/// Parse a JSON string into a DataValue using serde_json for parsing
+pub use adaptive::{ParseReport, ParseStrategy, SampleStats, AUTO_SAMPLE_BYTES};
+pub use feed::FeedParser;
+#[cfg(feature = "serde_json-compat")]
+pub use seed::DataValueSeed;
+#[cfg(feature = "serde_json-compat")]
+pub use stream::JsonDeserializer;
+#[cfg(feature = "serde_json-compat")]
+pub use value::from_value;
+
+mod adaptive;
+#[cfg(feature = "async")]
+mod async_reader;
+pub mod events;
+mod feed;
+#[cfg(feature = "json5")]
+pub mod json5;
+pub mod lenient;
+pub mod ndjson;
+#[cfg(feature = "serde_json-compat")]
+mod seed;
+#[cfg(feature = "serde_json-compat")]
+mod stream;
+#[cfg(feature = "serde_json-compat")]
+mod value;
+
+/// Parse a JSON string into a DataValue
 ///
-/// This function uses serde_json to parse the JSON string, then converts
-/// the resulting serde_json::Value into a DataValue.
+/// The input is parsed by a single-pass parser that allocates strings, arrays
+/// and objects directly in the arena, without building an intermediate
+/// `serde_json::Value`. Object keys keep the order they have in the input. Nesting
+/// is tracked on the heap rather than the thread stack, so deeply nested input is
+/// limited only by [`ParseOptions::max_depth`].
 ///
 /// # Arguments
 ///
@@ -35,17 +79,18 @@ use std::io::Read;
 /// assert_eq!(value["age"].as_i64(), Some(30));
 /// ```
 pub fn from_str<'a>(arena: &'a Bump, s: &str) -> Result<DataValue<'a>> {
-    // Parse the string using serde_json
-    let json_value: serde_json::Value = serde_json::from_str(s)?;
-
-    // Convert the serde_json::Value to DataValue
-    from_json(arena, &json_value)
+    from_str_with_options(arena, s, &ParseOptions::default())
 }
 
 /// Convert a serde_json::Value into a DataValue
 ///
-/// This function recursively converts a serde_json::Value into a DataValue,
-/// allocating strings, arrays, and objects in the provided arena.
+/// This function converts a serde_json::Value into a DataValue, allocating
+/// strings, arrays, and objects in the provided arena. Nesting is tracked on the
+/// heap, so deeply nested values cannot overflow the stack; use
+/// [`from_json_with_options`] with [`ParseOptions::max_depth`] to bound it.
+///
+/// Strings holding an ISO 8601 duration (such as `"PT1H30M"`) are converted into
+/// `DataValue::Duration`, so durations survive a serialize/parse round trip.
 ///
 /// # Arguments
 ///
@@ -74,53 +119,1769 @@ pub fn from_str<'a>(arena: &'a Bump, s: &str) -> Result<DataValue<'a>> {
 /// assert_eq!(value["name"].as_str(), Some("John"));
 /// assert_eq!(value["hobbies"][0].as_str(), Some("reading"));
 /// ```
+#[cfg(feature = "serde_json-compat")]
 pub fn from_json<'a>(arena: &'a Bump, json: &serde_json::Value) -> Result<DataValue<'a>> {
+    convert(arena, json, &ParseOptions::default())
+}
+
+/// Options controlling how JSON input is turned into a DataValue
+///
+/// # Example
+///
+/// ```
+/// # use datavalue_rs::{Bump, ParseOptions, from_str_with_options};
+/// let arena = Bump::new();
+/// let options = ParseOptions {
+///     temporal_key_patterns: vec!["*_at".to_string(), "timestamp".to_string()],
+///     ..ParseOptions::default()
+/// };
+///
+/// let json = r#"{"created_at": "2023-01-01T00:00:00Z", "title": "2023-01-01"}"#;
+/// let value = from_str_with_options(&arena, json, &options).unwrap();
+///
+/// assert!(value["created_at"].as_datetime().is_some());
+/// assert_eq!(value["title"].as_str(), Some("2023-01-01"));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    /// Key patterns whose string values are parsed as DateTimes
+    ///
+    /// Patterns are matched against object keys at any depth. `*` matches any run
+    /// of characters, so `*_at` matches `created_at` and `updated_at`. Values are
+    /// parsed with the formats accepted by [`helpers::datetime`]; strings that do not
+    /// parse, and non-string values, are left unchanged.
+    pub temporal_key_patterns: Vec<String>,
+
+    /// Parse every string holding an RFC 3339 timestamp as a DateTime, whatever
+    /// key it sits under
+    ///
+    /// The serializers write DateTime values as RFC 3339 strings, so this revives
+    /// them when reading the output back. Only full timestamps with an offset are
+    /// converted; bare dates and other strings are left unchanged. Strings holding
+    /// ISO 8601 durations, which is how Duration values are written, are parsed as
+    /// Durations unless the options are [lossless](ParseOptions::is_lossless).
+    ///
+    /// ```
+    /// # use datavalue_rs::{Bump, ParseOptions, from_str_with_options, helpers};
+    /// let arena = Bump::new();
+    /// let value = helpers::array(&arena, vec![
+    ///     helpers::datetime("2023-05-01T12:30:00Z").unwrap(),
+    ///     helpers::string(&arena, "2023-05-01"),
+    /// ]);
+    /// let text = value.to_string();
+    /// assert_eq!(text, r#"["2023-05-01T12:30:00+00:00","2023-05-01"]"#);
+    ///
+    /// let options = ParseOptions {
+    ///     revive_datetimes: true,
+    ///     ..ParseOptions::default()
+    /// };
+    /// let revived = from_str_with_options(&arena, &text, &options).unwrap();
+    /// assert_eq!(revived[0].as_datetime(), value[0].as_datetime());
+    /// assert_eq!(revived[1].as_str(), Some("2023-05-01"));
+    /// ```
+    pub revive_datetimes: bool,
+
+    /// Key patterns whose values are kept unparsed as [`DataValue::Raw`]
+    ///
+    /// Patterns are matched like `temporal_key_patterns`. The value's syntax and
+    /// nesting depth are still checked, but nothing is allocated for its contents,
+    /// and serializing it writes the source text unchanged. Use this to forward a
+    /// payload that only needs to be routed, not read. Applies to [`from_str`], its
+    /// variants and [`crate::Document`]; the other parsers ignore it.
+    ///
+    /// ```
+    /// # use datavalue_rs::{Bump, ParseOptions, from_str_with_options, to_string};
+    /// let arena = Bump::new();
+    /// let options = ParseOptions {
+    ///     raw_keys: vec!["payload".to_string()],
+    ///     ..ParseOptions::default()
+    /// };
+    /// let input = r#"{"route": "orders", "payload": {"items": [1.50, 2e3]}}"#;
+    /// let value = from_str_with_options(&arena, input, &options).unwrap();
+    /// assert_eq!(value["route"].as_str(), Some("orders"));
+    /// assert_eq!(to_string(&value["payload"]), r#"{"items": [1.50, 2e3]}"#);
+    /// ```
+    pub raw_keys: Vec<String>,
+
+    /// Parse floats with no fractional part, such as `30.0`, as integers
+    ///
+    /// Only values that fit in an `i64` are converted.
+    pub normalize_integral_floats: bool,
+
+    /// Parse every integer as a float
+    ///
+    /// Takes precedence over `normalize_integral_floats` when both are set, so all
+    /// numbers come out as floats.
+    pub ints_as_floats: bool,
+
+    /// Unicode normalization applied to string values and object keys
+    ///
+    /// `None` (the default) keeps strings exactly as they appear in the input.
+    pub unicode_normalization: Option<NormalizationForm>,
+
+    /// Handling of `\u` escapes that encode half of a surrogate pair without the
+    /// other half, such as `"\uD800"`
+    pub lone_surrogates: SurrogatePolicy,
+
+    /// Maximum nesting depth of arrays and objects
+    ///
+    /// A top-level `[]` has depth 1. `None` (the default) places no limit. The
+    /// parsers, the `serde_json::Value` conversion and the serializers keep open
+    /// containers on the heap, so any depth fits in the thread
+    /// stack; set a limit when parsing untrusted input to bound the work and memory
+    /// a document can demand.
+    pub max_depth: Option<usize>,
+
+    /// Maximum length in bytes of a string value or object key after unescaping
+    ///
+    /// `None` (the default) places no limit.
+    pub max_string_length: Option<usize>,
+
+    /// Handling of object keys that appear more than once in the same object
+    pub duplicate_keys: DuplicateKeyPolicy,
+
+    /// Handling of numbers that cannot be represented exactly
+    pub number_mode: NumberMode,
+
+    /// Ignore whatever follows the first complete value instead of rejecting it
+    ///
+    /// Useful for inputs that append data after a JSON header. Trailing whitespace
+    /// is always accepted.
+    pub allow_trailing_data: bool,
+
+    /// Accept `//` line comments and `/* */` block comments wherever whitespace
+    /// is allowed, as in JSONC (JSON with comments) files
+    pub allow_comments: bool,
+
+    /// Accept a comma after the last element of an array or the last entry of
+    /// an object, as in `[1, 2,]`
+    pub allow_trailing_commas: bool,
+
+    /// Handling of the tokens `NaN`, `Infinity` and `-Infinity`, and of numbers too
+    /// large for an `f64`, such as `1e999`
+    ///
+    /// JSON allows neither, so both are rejected by default. `Literal` parses them
+    /// as non-finite floats and `Null` as `null`. Raw and arbitrary-precision
+    /// number modes keep large numbers exactly regardless.
+    pub non_finite: NonFiniteFloats,
+}
+
+/// How the parser treats an object key that repeats within the same object
+///
+/// JSON leaves the meaning of duplicate keys undefined, and different consumers
+/// disagree on which value wins. Keeping them all (the default) preserves the
+/// input; [`DataValue::get`] then returns the first value and
+/// [`DataValue::get_all`] returns every value.
+///
+/// The policy applies to every parser that reads JSON text: [`from_str`] and its
+/// variants, JSON5, NDJSON and [`events::read_value_with_options`]. A
+/// `serde_json::Value` passed to `from_json_with_options` has already kept the
+/// last of any repeated keys, so there is nothing left for the policy to act on.
+///
+/// # Example
+///
+/// ```
+/// # use datavalue_rs::{Bump, DuplicateKeyPolicy, ParseOptions, from_str_with_options};
+/// let arena = Bump::new();
+/// let input = r#"{"role": "user", "name": "x", "role": "admin"}"#;
+///
+/// let last = ParseOptions {
+///     duplicate_keys: DuplicateKeyPolicy::LastWins,
+///     ..ParseOptions::default()
+/// };
+/// let value = from_str_with_options(&arena, input, &last).unwrap();
+/// assert_eq!(value["role"].as_str(), Some("admin"));
+/// assert_eq!(value.as_object().unwrap().len(), 2);
+///
+/// let strict = ParseOptions {
+///     duplicate_keys: DuplicateKeyPolicy::Error,
+///     ..ParseOptions::default()
+/// };
+/// assert!(from_str_with_options(&arena, input, &strict).is_err());
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicateKeyPolicy {
+    /// Keep every entry in input order
+    #[default]
+    KeepAll,
+    /// Keep the first value and drop later ones
+    FirstWins,
+    /// Keep the last value, at the position of the first occurrence
+    LastWins,
+    /// Reject the document with a syntax error
+    Error,
+}
+
+/// How the parser treats numbers
+///
+/// # Example
+///
+/// ```
+/// # use datavalue_rs::{Bump, NumberMode, ParseOptions, from_str, from_str_with_options};
+/// let arena = Bump::new();
+/// let input = "[9007199254740993, 18446744073709551615]";
+/// assert!(from_str(&arena, input).unwrap()[1].as_f64().is_some());
+///
+/// let strict = ParseOptions {
+///     number_mode: NumberMode::Strict,
+///     ..ParseOptions::default()
+/// };
+/// assert!(from_str_with_options(&arena, input, &strict).is_err());
+///
+/// let raw = ParseOptions {
+///     number_mode: NumberMode::Raw,
+///     ..ParseOptions::default()
+/// };
+/// let value = from_str_with_options(&arena, input, &raw).unwrap();
+/// assert_eq!(datavalue_rs::to_string(&value), "[9007199254740993,18446744073709551615]");
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NumberMode {
+    /// Integers that fit in an `i64` become [`Number::Integer`], and every other
+    /// number becomes the nearest [`Number::Float`]
+    #[default]
+    Auto,
+    /// Like `Auto`, but integers outside the `i64` range are rejected instead of
+    /// being rounded to a float
+    Strict,
+    /// Keep the text of every number as [`Number::Raw`], so that serializing the
+    /// value writes each number exactly as it appeared in the input
+    ///
+    /// `normalize_integral_floats` and `ints_as_floats` do not apply to raw numbers.
+    Raw,
+    /// Keep every number exactly: integers outside the `i64` range become
+    /// [`Number::BigInt`] and decimals that would lose digits as an `f64` become
+    /// [`Number::BigDecimal`]
+    ///
+    /// `normalize_integral_floats` and `ints_as_floats` apply only to numbers that
+    /// stay an integer or float.
+    ///
+    /// ```
+    /// # use datavalue_rs::{Bump, Number, NumberMode, ParseOptions, from_str_with_options};
+    /// let arena = Bump::new();
+    /// let options = ParseOptions {
+    ///     number_mode: NumberMode::Arbitrary,
+    ///     ..ParseOptions::default()
+    /// };
+    /// let input = "[18446744073709551616,0.1,3.14159265358979323846]";
+    /// let value = from_str_with_options(&arena, input, &options).unwrap();
+    /// assert!(matches!(value[0], datavalue_rs::DataValue::Number(Number::BigInt(_))));
+    /// assert!(matches!(value[1], datavalue_rs::DataValue::Number(Number::Float(_))));
+    /// assert_eq!(datavalue_rs::to_string(&value), input);
+    /// ```
+    #[cfg(feature = "bignum")]
+    Arbitrary,
+}
+
+/// How the parser treats a `\u` escape for an unpaired UTF-16 surrogate
+///
+/// A high surrogate (`\uD800`-`\uDBFF`) must be followed by an escaped low
+/// surrogate (`\uDC00`-`\uDFFF`), and the pair decodes to one character outside
+/// the Basic Multilingual Plane. Anything else cannot be represented in a Rust
+/// string. Such input is produced by some JavaScript and Java serializers that
+/// split strings between surrogates.
+///
+/// # Example
+///
+/// ```
+/// # use datavalue_rs::{Bump, ParseOptions, SurrogatePolicy, from_str, from_str_with_options};
+/// let arena = Bump::new();
+/// let input = r#""emoji: \ud83d\ude00, truncated: \ud83d""#;
+/// assert!(from_str(&arena, input).is_err());
+///
+/// let options = ParseOptions {
+///     lone_surrogates: SurrogatePolicy::Replace,
+///     ..ParseOptions::default()
+/// };
+/// let value = from_str_with_options(&arena, input, &options).unwrap();
+/// assert_eq!(value.as_str(), Some("emoji: \u{1f600}, truncated: \u{fffd}"));
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SurrogatePolicy {
+    /// Reject the document with a syntax error
+    #[default]
+    Error,
+    /// Decode the lone surrogate as U+FFFD REPLACEMENT CHARACTER
+    Replace,
+}
+
+impl ParseOptions {
+    /// Key patterns commonly used for timestamps: `*_at`, `*_time` and `timestamp`
+    pub const COMMON_TEMPORAL_KEY_PATTERNS: &'static [&'static str] =
+        &["*_at", "*_time", "timestamp"];
+
+    /// Returns options that treat the [`ParseOptions::COMMON_TEMPORAL_KEY_PATTERNS`]
+    /// as datetime-bearing keys
+    pub fn with_common_temporal_keys() -> Self {
+        ParseOptions {
+            temporal_key_patterns: Self::COMMON_TEMPORAL_KEY_PATTERNS
+                .iter()
+                .map(|p| p.to_string())
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Returns options that keep everything needed to write the input back unchanged
+    ///
+    /// Numbers are kept as their source text ([`NumberMode::Raw`]), duplicate keys
+    /// are all kept and strings are not normalized or converted to datetimes or
+    /// durations.
+    /// Together with [`crate::Document::to_string_lossless`] this gives
+    /// byte-identical round trips.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use datavalue_rs::{Bump, ParseOptions, from_str_with_options, to_string};
+    /// let arena = Bump::new();
+    /// let input = r#"{"id":12345678901234567890,"id":1.50}"#;
+    /// let value = from_str_with_options(&arena, input, &ParseOptions::lossless()).unwrap();
+    /// assert_eq!(to_string(&value), input);
+    /// assert!(ParseOptions::lossless().is_lossless());
+    /// assert!(!ParseOptions::default().is_lossless());
+    /// ```
+    pub fn lossless() -> Self {
+        ParseOptions {
+            number_mode: NumberMode::Raw,
+            ..Default::default()
+        }
+    }
+
+    /// Returns true if values parsed with these options keep every detail of the input
+    ///
+    /// That is the case when numbers are kept raw, all duplicate keys are kept,
+    /// and no option rewrites strings or non-finite numbers. Comments and trailing
+    /// data, which have no place in the value, are not allowed either. Duration
+    /// strings are kept as strings when this holds, since `PT60M` would otherwise
+    /// be written back as `PT1H`.
+    pub fn is_lossless(&self) -> bool {
+        self.number_mode == NumberMode::Raw
+            && self.duplicate_keys == DuplicateKeyPolicy::KeepAll
+            && self.lone_surrogates == SurrogatePolicy::Error
+            && self.unicode_normalization.is_none()
+            && self.temporal_key_patterns.is_empty()
+            && !self.revive_datetimes
+            && !self.allow_trailing_data
+            && !self.allow_comments
+            && !self.allow_trailing_commas
+            && self.non_finite != NonFiniteFloats::Null
+    }
+
+    /// Normalizes parsed strings and keys to `form` before they are allocated
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use datavalue_rs::{Bump, NormalizationForm, ParseOptions, from_str_with_options};
+    /// let arena = Bump::new();
+    /// let options = ParseOptions::default().normalize_unicode(NormalizationForm::Nfc);
+    ///
+    /// // "cafe" followed by a combining acute accent, as some producers emit it
+    /// let value = from_str_with_options(&arena, r#"{"cafe\u0301": "cafe\u0301"}"#, &options).unwrap();
+    /// assert_eq!(value["caf\u{e9}"].as_str(), Some("caf\u{e9}"));
+    /// ```
+    pub fn normalize_unicode(mut self, form: NormalizationForm) -> Self {
+        self.unicode_normalization = Some(form);
+        self
+    }
+
+    /// Applies the configured Unicode normalization to a parsed string or key
+    fn normalize<'s>(&self, s: &'s str) -> Cow<'s, str> {
+        match self.unicode_normalization {
+            Some(form) => unicode::normalize(s, form),
+            None => Cow::Borrowed(s),
+        }
+    }
+
+    /// Applies the configured integer/float normalization to a parsed number
+    fn number<'n>(&self, number: Number<'n>) -> Number<'n> {
+        match number {
+            Number::Integer(i) if self.ints_as_floats => Number::Float(i as f64),
+            Number::Float(f)
+                if self.normalize_integral_floats
+                    && !self.ints_as_floats
+                    && f.fract() == 0.0
+                    && (i64::MIN as f64..i64::MAX as f64).contains(&f) =>
+            {
+                Number::Integer(f as i64)
+            }
+            number => number,
+        }
+    }
+
+    /// Returns true if values under `key` should be parsed as DateTimes
+    fn is_temporal_key(&self, key: &str) -> bool {
+        self.temporal_key_patterns
+            .iter()
+            .any(|pattern| glob_match(pattern, key))
+    }
+
+    /// Returns true if values under `key` should be kept as raw text
+    fn is_raw_key(&self, key: &str) -> bool {
+        self.raw_keys.iter().any(|pattern| glob_match(pattern, key))
+    }
+}
+
+/// Parse a JSON string into a DataValue with the given options
+///
+/// Behaves like [`from_str`], applying the conversions configured in `options`.
+///
+/// # Example
+///
+/// ```
+/// # use datavalue_rs::{Bump, ParseOptions, from_str_with_options};
+/// let arena = Bump::new();
+/// let options = ParseOptions::with_common_temporal_keys();
+/// let value = from_str_with_options(&arena, r#"{"timestamp": "2023-05-01"}"#, &options).unwrap();
+/// assert!(value["timestamp"].as_datetime().is_some());
+/// ```
+pub fn from_str_with_options<'a>(
+    arena: &'a Bump,
+    s: &str,
+    options: &ParseOptions,
+) -> Result<DataValue<'a>> {
+    metrics::parse(arena, s.len(), || Parser::new(arena, s, options).parse())
+}
+
+/// Parse JSON with comments and trailing commas into a DataValue
+///
+/// Accepts JSONC, the format of VS Code settings and `tsconfig.json` files:
+/// JSON plus `//` and `/* */` comments and trailing commas. It is shorthand for
+/// [`from_str_with_options`] with [`ParseOptions::allow_comments`] and
+/// [`ParseOptions::allow_trailing_commas`] set.
+///
+/// # Example
+///
+/// ```
+/// # use datavalue_rs::{Bump, from_str, from_str_jsonc};
+/// let arena = Bump::new();
+/// let settings = r#"{
+///     // Editor
+///     "editor.tabSize": 4,
+///     /* "editor.wordWrap": "on", */
+///     "files.exclude": ["target", "node_modules",],
+/// }"#;
+///
+/// let value = from_str_jsonc(&arena, settings).unwrap();
+/// assert_eq!(value["editor.tabSize"].as_i64(), Some(4));
+/// assert!(value.get("editor.wordWrap").is_none());
+/// assert!(from_str(&arena, settings).is_err());
+/// ```
+pub fn from_str_jsonc<'a>(arena: &'a Bump, s: &str) -> Result<DataValue<'a>> {
+    let options = ParseOptions {
+        allow_comments: true,
+        allow_trailing_commas: true,
+        ..ParseOptions::default()
+    };
+    from_str_with_options(arena, s, &options)
+}
+
+/// Parse a JSON string into a DataValue that borrows from the input
+///
+/// Like [`from_str`], but string values and object keys without escape sequences
+/// point directly into `s` instead of being copied into the arena. Use this when
+/// the input buffer lives at least as long as the arena, such as a file read once
+/// at startup; only strings with escapes and the containers are allocated.
+///
+/// # Example
+///
+/// ```
+/// # use datavalue_rs::{Bump, from_str_borrowed};
+/// let arena = Bump::new();
+/// let input = String::from(r#"{"name": "John", "quote": "say \"hi\""}"#);
+/// let value = from_str_borrowed(&arena, &input).unwrap();
+///
+/// let name = value["name"].as_str().unwrap();
+/// assert!(input.as_bytes().as_ptr_range().contains(&name.as_ptr()));
+/// assert_eq!(value["quote"].as_str(), Some("say \"hi\""));
+/// ```
+pub fn from_str_borrowed<'a>(arena: &'a Bump, s: &'a str) -> Result<DataValue<'a>> {
+    from_str_borrowed_with_options(arena, s, &ParseOptions::default())
+}
+
+/// Parse a JSON string into a DataValue that borrows from the input, with options
+///
+/// See [`from_str_borrowed`] and [`from_str_with_options`].
+pub fn from_str_borrowed_with_options<'a>(
+    arena: &'a Bump,
+    s: &'a str,
+    options: &ParseOptions,
+) -> Result<DataValue<'a>> {
+    metrics::parse(arena, s.len(), || {
+        Parser::borrowing(arena, s, options).parse()
+    })
+}
+
+/// Convert a serde_json::Value into a DataValue with the given options
+///
+/// Behaves like [`from_json`], applying the conversions configured in `options`.
+#[cfg(feature = "serde_json-compat")]
+pub fn from_json_with_options<'a>(
+    arena: &'a Bump,
+    json: &serde_json::Value,
+    options: &ParseOptions,
+) -> Result<DataValue<'a>> {
+    convert(arena, json, options)
+}
+
+/// Conversion shared by all serde_json-based entry points
+///
+/// Open arrays and objects are kept on an explicit stack, so a deeply nested
+/// `serde_json::Value` built in code cannot overflow the thread stack; nesting
+/// beyond [`ParseOptions::max_depth`] is rejected.
+#[cfg(feature = "serde_json-compat")]
+fn convert<'a>(
+    arena: &'a Bump,
+    json: &serde_json::Value,
+    options: &ParseOptions,
+) -> Result<DataValue<'a>> {
+    /// An array or object whose elements are still being converted
+    enum Frame<'j, 'a> {
+        Array(std::slice::Iter<'j, serde_json::Value>, Vec<DataValue<'a>>),
+        /// The remaining entries, the converted ones and the key of the value
+        /// being converted
+        Object(
+            serde_json::map::Iter<'j>,
+            Vec<(&'a str, DataValue<'a>)>,
+            &'a str,
+        ),
+    }
+
+    let mut stack: Vec<Frame<'_, 'a>> = Vec::new();
+    let mut next = Some((json, None));
+    loop {
+        let mut value = match next.take() {
+            Some((serde_json::Value::Array(items), _)) => {
+                check_depth(&stack, options)?;
+                stack.push(Frame::Array(items.iter(), Vec::with_capacity(items.len())));
+                None
+            }
+            Some((serde_json::Value::Object(map), _)) => {
+                check_depth(&stack, options)?;
+                stack.push(Frame::Object(map.iter(), Vec::with_capacity(map.len()), ""));
+                None
+            }
+            Some((scalar, key)) => Some(convert_scalar(arena, scalar, key, options)?),
+            None => None,
+        };
+
+        // Attach finished values to their parent until one has elements left
+        loop {
+            match (value.take(), stack.last_mut()) {
+                (Some(value), None) => return Ok(value),
+                (Some(value), Some(Frame::Array(_, values))) => values.push(value),
+                (Some(value), Some(Frame::Object(_, entries, key))) => entries.push((*key, value)),
+                (None, _) => {}
+            }
+            let child = match stack.last_mut() {
+                Some(Frame::Array(items, _)) => items.next().map(|item| (item, None)),
+                Some(Frame::Object(entries, _, key)) => entries.next().map(|(k, v)| {
+                    *key = arena.alloc_str(&options.normalize(k));
+                    (v, Some(k.as_str()))
+                }),
+                None => unreachable!("the root value returns above"),
+            };
+            match child {
+                Some(child) => {
+                    next = Some(child);
+                    break;
+                }
+                None => {
+                    value = Some(match stack.pop() {
+                        Some(Frame::Array(_, values)) => {
+                            DataValue::Array(arena.alloc_slice_clone(&values))
+                        }
+                        Some(Frame::Object(_, entries, _)) => {
+                            DataValue::Object(arena.alloc_slice_clone(&entries))
+                        }
+                        None => unreachable!("a container is open"),
+                    })
+                }
+            }
+        }
+    }
+}
+
+/// Rejects opening another container inside the `open` ones if that exceeds the
+/// depth limit
+#[cfg(feature = "serde_json-compat")]
+fn check_depth<T>(open: &[T], options: &ParseOptions) -> Result<()> {
+    match options.max_depth {
+        Some(max) if open.len() >= max => Err(Error::syntax("nesting too deep".to_string())),
+        _ => Ok(()),
+    }
+}
+
+/// Converts a serde_json value that is not an array or object
+///
+/// `key` is the object key the value sits under, for temporal key matching.
+#[cfg(feature = "serde_json-compat")]
+fn convert_scalar<'a>(
+    arena: &'a Bump,
+    json: &serde_json::Value,
+    key: Option<&str>,
+    options: &ParseOptions,
+) -> Result<DataValue<'a>> {
     match json {
         serde_json::Value::Null => Ok(DataValue::Null),
         serde_json::Value::Bool(b) => Ok(DataValue::Bool(*b)),
         serde_json::Value::Number(n) => {
-            if let Some(i) = n.as_i64() {
-                Ok(DataValue::Number(Number::Integer(i)))
+            #[cfg(feature = "bignum")]
+            if options.number_mode == NumberMode::Arbitrary {
+                let is_float = n.is_f64();
+                return crate::bignum::parse_number(arena, &n.to_string(), is_float)
+                    .map(|number| DataValue::Number(options.number(number)))
+                    .ok_or_else(|| Error::syntax("number out of range".to_string()));
+            }
+            if options.number_mode == NumberMode::Raw {
+                Ok(DataValue::Number(Number::Raw(
+                    arena.alloc_str(&n.to_string()),
+                )))
+            } else if let Some(i) = n.as_i64() {
+                Ok(DataValue::Number(options.number(Number::Integer(i))))
+            } else if n.is_u64() && options.number_mode == NumberMode::Strict {
+                Err(Error::syntax("integer out of range".to_string()))
             } else if let Some(f) = n.as_f64() {
-                Ok(DataValue::Number(Number::Float(f)))
+                Ok(DataValue::Number(options.number(Number::Float(f))))
             } else {
                 Err(Error::syntax("Unsupported number type".to_string()))
             }
         }
-        serde_json::Value::String(s) => {
-            let s_ref = arena.alloc_str(s);
-            Ok(DataValue::String(s_ref))
+        serde_json::Value::String(s) => Ok(string_value(arena, s, None, key, options)),
+        serde_json::Value::Array(_) | serde_json::Value::Object(_) => {
+            unreachable!("containers are converted by convert")
+        }
+    }
+}
+
+/// Builds the DataValue for a JSON string
+///
+/// Strings under temporal keys that parse as datetimes become DateTimes, as do RFC
+/// 3339 timestamps anywhere when `revive_datetimes` is set, and ISO 8601
+/// durations (what the serializer emits for Duration values) become Durations
+/// unless the options are lossless.
+///
+/// `borrowed` is the same text already living for `'a`, used instead of copying.
+fn string_value<'a>(
+    arena: &'a Bump,
+    s: &str,
+    borrowed: Option<&'a str>,
+    key: Option<&str>,
+    options: &ParseOptions,
+) -> DataValue<'a> {
+    let normalized = options.normalize(s);
+    let borrowed = borrowed.filter(|_| matches!(normalized, Cow::Borrowed(_)));
+    let s: &str = &normalized;
+    if key.is_some_and(|key| options.is_temporal_key(key)) {
+        if let Ok(dt) = helpers::parse_datetime(s) {
+            return DataValue::DateTime(dt);
+        }
+    }
+    if options.revive_datetimes {
+        if let Some(dt) = helpers::detect_datetime(s) {
+            return DataValue::DateTime(dt);
+        }
+    }
+    if !options.is_lossless() {
+        if let Some(duration) = helpers::detect_duration(s) {
+            return DataValue::Duration(duration);
+        }
+    }
+    DataValue::String(borrowed.unwrap_or_else(|| arena.alloc_str(s)))
+}
+
+/// Matches `text` against a glob pattern where `*` matches any run of characters
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or("");
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    let remaining: Vec<&str> = parts.collect();
+    let Some((last, middle)) = remaining.split_last() else {
+        // No wildcard: the whole pattern must match exactly
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(pos) => rest = &rest[pos + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+/// Returns the JSON pointer of the value being parsed at byte offset `pos`
+///
+/// The input up to `pos` is scanned again, so paths cost nothing until an error is
+/// reported. The scan only tracks brackets, commas, colons and keys, which makes it
+/// work for JSONC and JSON5 as well.
+fn path_at(input: &str, pos: usize) -> String {
+    /// An array with the index of its current item, or an object with the key of
+    /// its current entry
+    enum Open {
+        Array(usize),
+        Object(Option<String>),
+    }
+
+    let bytes = &input.as_bytes()[..pos];
+    let mut open: Vec<Open> = Vec::new();
+    let mut expecting_key = false;
+    let mut i = 0;
+    while i < bytes.len() {
+        let mut key = None;
+        match bytes[i] {
+            b'[' => open.push(Open::Array(0)),
+            b'{' => {
+                open.push(Open::Object(None));
+                expecting_key = true;
+            }
+            b']' | b'}' => {
+                open.pop();
+                expecting_key = false;
+            }
+            b',' => match open.last_mut() {
+                Some(Open::Array(index)) => *index += 1,
+                Some(Open::Object(key)) => {
+                    *key = None;
+                    expecting_key = true;
+                }
+                None => {}
+            },
+            b':' => expecting_key = false,
+            b'/' if bytes.get(i + 1) == Some(&b'/') => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                i += 2;
+                while i < bytes.len() && !bytes[i..].starts_with(b"*/") {
+                    i += 1;
+                }
+                i += 1;
+            }
+            quote @ (b'"' | b'\'') => {
+                let start = i;
+                i += 1;
+                while i < bytes.len() && bytes[i] != quote {
+                    i += if bytes[i] == b'\\' { 2 } else { 1 };
+                }
+                if i >= bytes.len() {
+                    // The position is inside this string
+                    break;
+                }
+                let raw = &input[start + 1..i];
+                key = Some(if quote == b'"' && raw.contains('\\') {
+                    let mut lexer = Lexer {
+                        pos: start,
+                        ..Lexer::new(input)
+                    };
+                    lexer
+                        .parse_string()
+                        .map_or_else(|_| raw.to_string(), Cow::into_owned)
+                } else {
+                    raw.to_string()
+                });
+            }
+            b if b.is_ascii_alphanumeric() || b == b'_' || b == b'$' => {
+                // A literal, a number or an unquoted JSON5 key
+                let start = i;
+                while i + 1 < bytes.len()
+                    && (bytes[i + 1].is_ascii_alphanumeric() || matches!(bytes[i + 1], b'_' | b'$'))
+                {
+                    i += 1;
+                }
+                key = Some(input[start..=i].to_string());
+            }
+            _ => {}
+        }
+        if let (Some(key), true, Some(Open::Object(current))) =
+            (key, expecting_key, open.last_mut())
+        {
+            *current = Some(key);
+        }
+        i += 1;
+    }
+
+    let mut path = String::new();
+    for frame in &open {
+        match frame {
+            Open::Array(index) => {
+                path.push('/');
+                path.push_str(&index.to_string());
+            }
+            Open::Object(Some(key)) => {
+                path.push('/');
+                path.push_str(&escape_pointer_token(key));
+            }
+            Open::Object(None) => break,
+        }
+    }
+    path
+}
+
+/// Byte spans of parsed nodes, keyed by the address of the node in the arena
+pub(crate) type SpanTable = HashMap<usize, Range<usize>>;
+
+/// Byte-level JSON lexer shared by the parser and [`Tokenizer`]
+pub(crate) struct Lexer<'s> {
+    input: &'s str,
+    bytes: &'s [u8],
+    pos: usize,
+    lone_surrogates: SurrogatePolicy,
+    /// Whether comments count as whitespace
+    comments: bool,
+}
+
+impl<'s> Lexer<'s> {
+    pub(crate) fn new(input: &'s str) -> Self {
+        Lexer {
+            input,
+            bytes: input.as_bytes(),
+            pos: 0,
+            lone_surrogates: SurrogatePolicy::Error,
+            comments: false,
+        }
+    }
+
+    fn error(&self, msg: &str) -> Error {
+        let byte_offset = self.pos.min(self.input.len());
+        let consumed = &self.input[..byte_offset];
+        let line = consumed.matches('\n').count() + 1;
+        let column = consumed
+            .rfind('\n')
+            .map_or(consumed, |nl| &consumed[nl + 1..])
+            .chars()
+            .count()
+            + 1;
+        let location = Location {
+            line,
+            column,
+            byte_offset,
+            path: path_at(self.input, byte_offset),
+        };
+        Error::syntax_at(msg, location)
+    }
+
+    fn skip_whitespace(&mut self) {
+        loop {
+            while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.bytes.get(self.pos) {
+                self.pos += 1;
+            }
+            if !self.comments || !self.skip_comment() {
+                return;
+            }
+        }
+    }
+
+    /// Skips a comment at the current position, returning false if there is none
+    ///
+    /// An unterminated block comment is left in place, so that the caller reports
+    /// an error at its start.
+    fn skip_comment(&mut self) -> bool {
+        let rest = &self.input[self.pos..];
+        if rest.starts_with("//") {
+            self.pos += rest.find('\n').unwrap_or(rest.len());
+            true
+        } else if let Some(body) = rest.strip_prefix("/*") {
+            match body.find("*/") {
+                Some(end) => {
+                    self.pos += 2 + end + 2;
+                    true
+                }
+                None => false,
+            }
+        } else {
+            false
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+
+    fn expect_literal(&mut self, literal: &str) -> Result<()> {
+        if self.input[self.pos..].starts_with(literal) {
+            self.pos += literal.len();
+            Ok(())
+        } else {
+            Err(self.error("invalid literal"))
+        }
+    }
+
+    fn skip_digits(&mut self) {
+        while self.peek().is_some_and(|b| b.is_ascii_digit()) {
+            self.pos += 1;
+        }
+    }
+
+    /// Parses a string literal, borrowing from the input when it has no escapes
+    fn parse_string(&mut self) -> Result<Cow<'s, str>> {
+        self.pos += 1;
+        let start = self.pos;
+        loop {
+            match self.peek() {
+                None => return Err(self.error("unterminated string")),
+                Some(b'"') => {
+                    let s = &self.input[start..self.pos];
+                    self.pos += 1;
+                    return Ok(Cow::Borrowed(s));
+                }
+                Some(b'\\') => break,
+                Some(0x00..=0x1f) => return Err(self.error("control character in string")),
+                Some(_) => self.pos += 1,
+            }
+        }
+
+        // Slow path: the string contains escapes
+        let mut output = String::from(&self.input[start..self.pos]);
+        loop {
+            match self.peek() {
+                None => return Err(self.error("unterminated string")),
+                Some(b'"') => {
+                    self.pos += 1;
+                    return Ok(Cow::Owned(output));
+                }
+                Some(b'\\') => {
+                    self.pos += 1;
+                    let escaped = match self.peek() {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'b') => '\u{8}',
+                        Some(b'f') => '\u{c}',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'u') => {
+                            self.pos += 1;
+                            let c = self.parse_unicode_escape()?;
+                            output.push(c);
+                            continue;
+                        }
+                        _ => return Err(self.error("invalid escape")),
+                    };
+                    output.push(escaped);
+                    self.pos += 1;
+                }
+                Some(0x00..=0x1f) => return Err(self.error("control character in string")),
+                Some(_) => {
+                    // Copy the run of plain characters up to the next quote or escape
+                    let run_start = self.pos;
+                    while let Some(b) = self.peek() {
+                        if b == b'"' || b == b'\\' || b < 0x20 {
+                            break;
+                        }
+                        self.pos += 1;
+                    }
+                    output.push_str(&self.input[run_start..self.pos]);
+                }
+            }
+        }
+    }
+
+    /// Parses the four hex digits after `\u`, combining surrogate pairs
+    fn parse_unicode_escape(&mut self) -> Result<char> {
+        let code = self.parse_hex4()?;
+        if !(0xD800..0xE000).contains(&code) {
+            return char::from_u32(code).ok_or_else(|| self.error("invalid unicode escape"));
+        }
+        if code < 0xDC00 && self.input[self.pos..].starts_with("\\u") {
+            let resume = self.pos;
+            self.pos += 2;
+            let low = self.parse_hex4()?;
+            if (0xDC00..0xE000).contains(&low) {
+                let combined = 0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00);
+                return char::from_u32(combined)
+                    .ok_or_else(|| self.error("invalid unicode escape"));
+            }
+            // Not a low surrogate: leave the second escape to be decoded on its own
+            self.pos = resume;
+        }
+        match self.lone_surrogates {
+            SurrogatePolicy::Error => Err(self.error("unpaired surrogate in string")),
+            SurrogatePolicy::Replace => Ok(char::REPLACEMENT_CHARACTER),
+        }
+    }
+
+    fn parse_hex4(&mut self) -> Result<u32> {
+        let digits = self
+            .input
+            .get(self.pos..self.pos + 4)
+            .filter(|d| d.bytes().all(|b| b.is_ascii_hexdigit()))
+            .ok_or_else(|| self.error("invalid unicode escape"))?;
+        self.pos += 4;
+        u32::from_str_radix(digits, 16).map_err(|_| self.error("invalid unicode escape"))
+    }
+
+    /// Scans a number, returning true if it has a fraction or exponent
+    fn scan_number(&mut self) -> Result<bool> {
+        let mut is_float = false;
+        if self.peek() == Some(b'-') {
+            self.pos += 1;
+        }
+        match self.peek() {
+            Some(b'0') => self.pos += 1,
+            Some(b'1'..=b'9') => self.skip_digits(),
+            _ => return Err(self.error("invalid number")),
+        }
+        if self.peek() == Some(b'.') {
+            is_float = true;
+            self.pos += 1;
+            if !self.peek().is_some_and(|b| b.is_ascii_digit()) {
+                return Err(self.error("invalid number"));
+            }
+            self.skip_digits();
+        }
+        if let Some(b'e' | b'E') = self.peek() {
+            is_float = true;
+            self.pos += 1;
+            if let Some(b'+' | b'-') = self.peek() {
+                self.pos += 1;
+            }
+            if !self.peek().is_some_and(|b| b.is_ascii_digit()) {
+                return Err(self.error("invalid number"));
+            }
+            self.skip_digits();
+        }
+        Ok(is_float)
+    }
+}
+
+/// Kind of a JSON token produced by [`Tokenizer`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    /// `{`
+    BeginObject,
+    /// `}`
+    EndObject,
+    /// `[`
+    BeginArray,
+    /// `]`
+    EndArray,
+    /// `:`
+    Colon,
+    /// `,`
+    Comma,
+    /// A string literal, including its quotes
+    String,
+    /// A number literal
+    Number,
+    /// `true`
+    True,
+    /// `false`
+    False,
+    /// `null`
+    Null,
+}
+
+/// A JSON token: its kind, its source text and its byte offset in the input
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Token<'s> {
+    /// What the token is
+    pub kind: TokenKind,
+    /// The exact source text of the token; string tokens include their quotes
+    pub text: &'s str,
+    /// Byte offset of the first byte of the token
+    pub offset: usize,
+}
+
+impl<'s> Token<'s> {
+    /// Returns the byte range of the token in the input
+    pub fn span(&self) -> Range<usize> {
+        self.offset..self.offset + self.text.len()
+    }
+
+    /// Returns the decoded contents of a string token, borrowing when it has no
+    /// escapes, or None for other kinds
+    pub fn as_str(&self) -> Option<Cow<'s, str>> {
+        match self.kind {
+            TokenKind::String => Lexer::new(self.text).parse_string().ok(),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of a number token, or None for other kinds
+    pub fn as_number(&self) -> Option<Number<'static>> {
+        match self.kind {
+            TokenKind::Number => match self.text.parse::<i64>() {
+                Ok(i) => Some(Number::Integer(i)),
+                Err(_) => self.text.parse::<f64>().ok().map(Number::Float),
+            },
+            _ => None,
+        }
+    }
+}
+
+/// Low-level JSON lexer yielding tokens with their source spans
+///
+/// Uses the same lexing rules as [`from_str`]: strings are checked for valid
+/// escapes and control characters, and numbers and literals must be well formed.
+/// The tokenizer does not check the grammar (it accepts `]]` or `1 2`), which
+/// makes it a building block for custom extractors that skip tree construction.
+/// Whitespace is skipped; the iterator yields an `Err` at the first invalid
+/// token and then ends.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::de::{TokenKind, Tokenizer};
+///
+/// let input = r#"{"id": 42, "name": "caf\u00e9"}"#;
+/// let tokens: Vec<_> = Tokenizer::new(input).collect::<Result<_, _>>().unwrap();
+///
+/// assert_eq!(tokens[1].kind, TokenKind::String);
+/// assert_eq!(tokens[1].text, "\"id\"");
+/// assert_eq!(tokens[3].span(), 7..9);
+/// assert_eq!(tokens[3].as_number().and_then(|n| match n {
+///     datavalue_rs::Number::Integer(i) => Some(i),
+///     _ => None,
+/// }), Some(42));
+/// assert_eq!(tokens[7].as_str().unwrap(), "caf\u{e9}");
+/// ```
+pub struct Tokenizer<'s> {
+    lexer: Lexer<'s>,
+    failed: bool,
+}
+
+impl<'s> Tokenizer<'s> {
+    /// Creates a tokenizer over `input`
+    pub fn new(input: &'s str) -> Self {
+        Tokenizer {
+            lexer: Lexer::new(input),
+            failed: false,
+        }
+    }
+
+    /// Returns the byte offset the next token will be read from
+    pub fn offset(&self) -> usize {
+        self.lexer.pos
+    }
+
+    fn token(&mut self) -> Result<Option<Token<'s>>> {
+        self.lexer.skip_whitespace();
+        let offset = self.lexer.pos;
+        let kind = match self.lexer.peek() {
+            None => return Ok(None),
+            Some(b'"') => {
+                self.lexer.parse_string()?;
+                TokenKind::String
+            }
+            Some(b'-' | b'0'..=b'9') => {
+                self.lexer.scan_number()?;
+                TokenKind::Number
+            }
+            Some(b't') => {
+                self.lexer.expect_literal("true")?;
+                TokenKind::True
+            }
+            Some(b'f') => {
+                self.lexer.expect_literal("false")?;
+                TokenKind::False
+            }
+            Some(b'n') => {
+                self.lexer.expect_literal("null")?;
+                TokenKind::Null
+            }
+            Some(b) => {
+                let kind = match b {
+                    b'{' => TokenKind::BeginObject,
+                    b'}' => TokenKind::EndObject,
+                    b'[' => TokenKind::BeginArray,
+                    b']' => TokenKind::EndArray,
+                    b':' => TokenKind::Colon,
+                    b',' => TokenKind::Comma,
+                    _ => return Err(self.lexer.error("unexpected character")),
+                };
+                self.lexer.pos += 1;
+                kind
+            }
+        };
+        Ok(Some(Token {
+            kind,
+            text: &self.lexer.input[offset..self.lexer.pos],
+            offset,
+        }))
+    }
+}
+
+impl<'s> Iterator for Tokenizer<'s> {
+    type Item = Result<Token<'s>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let token = self.token().transpose();
+        self.failed = matches!(token, Some(Err(_)));
+        token
+    }
+}
+
+/// JSON parser that allocates directly into the arena
+///
+/// Optionally records the byte span of every node it produces, which is what
+/// [`crate::Document`] uses to map values back to their source location.
+pub(crate) struct Parser<'a, 's, 'o> {
+    arena: &'a Bump,
+    lexer: Lexer<'s>,
+    options: &'o ParseOptions,
+    spans: Option<SpanTable>,
+    /// Byte spans of object keys, keyed by the address of their entry
+    key_spans: SpanTable,
+    /// The input, when it outlives the arena and strings can be borrowed from it
+    source: Option<&'a str>,
+    /// Number of arrays and objects enclosing the current position
+    depth: usize,
+}
+
+/// The keys of an object being parsed, for applying the duplicate-key policy
+pub(crate) struct ObjectKeys<'a> {
+    policy: DuplicateKeyPolicy,
+    /// Entry index of each key, tracked only when duplicates are not all kept
+    seen: Option<HashMap<&'a str, usize>>,
+}
+
+impl<'a> ObjectKeys<'a> {
+    pub(crate) fn new(policy: DuplicateKeyPolicy) -> Self {
+        ObjectKeys {
+            policy,
+            seen: (policy != DuplicateKeyPolicy::KeepAll).then(HashMap::new),
         }
-        serde_json::Value::Array(arr) => {
-            let mut values = Vec::with_capacity(arr.len());
-            for item in arr {
-                values.push(from_json(arena, item)?);
+    }
+
+    /// Adds an entry to `entries`, applying the duplicate-key policy
+    ///
+    /// Returns the index the value was stored at, `None` if it was dropped, or
+    /// `Err` if the policy rejects the repeated key.
+    #[allow(clippy::result_unit_err)]
+    pub(crate) fn add(
+        &mut self,
+        entries: &mut Vec<(&'a str, DataValue<'a>)>,
+        (key, value): (&'a str, DataValue<'a>),
+    ) -> std::result::Result<Option<usize>, ()> {
+        let earlier = match self.seen.as_mut() {
+            Some(seen) => match seen.get(key) {
+                Some(&index) => Some(index),
+                None => {
+                    seen.insert(key, entries.len());
+                    None
+                }
+            },
+            None => None,
+        };
+        match (earlier, self.policy) {
+            (None, _) => {
+                entries.push((key, value));
+                Ok(Some(entries.len() - 1))
+            }
+            (Some(_), DuplicateKeyPolicy::Error) => Err(()),
+            (Some(index), DuplicateKeyPolicy::LastWins) => {
+                entries[index].1 = value;
+                Ok(Some(index))
             }
+            (Some(_), _) => Ok(None),
+        }
+    }
+}
+
+impl<'a, 's, 'o> Parser<'a, 's, 'o> {
+    pub(crate) fn new(arena: &'a Bump, input: &'s str, options: &'o ParseOptions) -> Self {
+        Parser {
+            arena,
+            lexer: Lexer {
+                lone_surrogates: options.lone_surrogates,
+                comments: options.allow_comments,
+                ..Lexer::new(input)
+            },
+            options,
+            spans: None,
+            key_spans: SpanTable::new(),
+            source: None,
+            depth: 0,
+        }
+    }
+
+    /// Returns the input slice of the string literal that just ended at the lexer position,
+    /// starting after the opening quote at `start`, if it can be borrowed
+    fn borrow(&self, escaped: bool, start: usize) -> Option<&'a str> {
+        match self.source {
+            Some(source) if !escaped => Some(&source[start..self.lexer.pos - 1]),
+            _ => None,
+        }
+    }
+
+    /// Enables span recording for the nodes produced by this parser
+    pub(crate) fn record_spans(mut self) -> Self {
+        self.spans = Some(SpanTable::new());
+        self
+    }
+
+    /// Returns the recorded spans, leaving an empty table behind
+    pub(crate) fn take_spans(&mut self) -> SpanTable {
+        self.spans.take().unwrap_or_default()
+    }
+
+    /// Returns the recorded object key spans, leaving an empty table behind
+    pub(crate) fn take_key_spans(&mut self) -> SpanTable {
+        std::mem::take(&mut self.key_spans)
+    }
+
+    /// Parses a complete document, rejecting anything but whitespace after the value
+    ///
+    /// The root is allocated in the arena so that its address is stable and can be
+    /// used as a span table key.
+    pub(crate) fn parse_document(&mut self) -> Result<&'a DataValue<'a>> {
+        let (value, span) = self.parse_root()?;
+        let root: &'a DataValue<'a> = self.arena.alloc(value);
+        self.record(root, span);
+        Ok(root)
+    }
+
+    /// Parses a complete document without allocating the root in the arena
+    pub(crate) fn parse(&mut self) -> Result<DataValue<'a>> {
+        self.parse_root().map(|(value, _)| value)
+    }
+
+    fn parse_root(&mut self) -> Result<(DataValue<'a>, Range<usize>)> {
+        let (value, span) = self.parse_value(None)?;
+        self.lexer.skip_whitespace();
+        if self.lexer.pos < self.lexer.bytes.len() && !self.options.allow_trailing_data {
+            return Err(self.lexer.error("trailing characters"));
+        }
+        Ok((value, span))
+    }
+
+    fn record(&mut self, node: &DataValue<'a>, span: Range<usize>) {
+        if let Some(spans) = self.spans.as_mut() {
+            spans.insert(node as *const DataValue<'a> as usize, span);
+        }
+    }
+
+    /// Returns a syntax error located at byte offset `pos`
+    pub(crate) fn error_at(&mut self, pos: usize, msg: &str) -> Error {
+        self.lexer.pos = pos;
+        self.lexer.error(msg)
+    }
 
-            // Allocate the values in the arena
-            let values_slice = arena.alloc_slice_clone(&values);
-            Ok(DataValue::Array(values_slice))
+    /// Enters the array or object that opens at byte offset `pos`, enforcing the
+    /// depth limit
+    pub(crate) fn enter(&mut self, pos: usize) -> Result<()> {
+        self.depth += 1;
+        match self.options.max_depth {
+            Some(max) if self.depth > max => Err(self.error_at(pos, "nesting too deep")),
+            _ => Ok(()),
         }
-        serde_json::Value::Object(map) => {
-            // Create the entries with explicit type
-            let mut entries: Vec<(&'a str, DataValue<'a>)> = Vec::with_capacity(map.len());
+    }
+
+    /// Leaves the innermost array or object
+    pub(crate) fn leave(&mut self) {
+        self.depth -= 1;
+    }
+
+    /// Enforces the string length limit on a string or key starting at `pos`
+    fn check_length(&mut self, s: &str, pos: usize) -> Result<()> {
+        match self.options.max_string_length {
+            Some(max) if s.len() > max => Err(self.error_at(pos, "string too long")),
+            _ => Ok(()),
+        }
+    }
+
+    /// Starts tracking the keys of a new object
+    pub(crate) fn object_keys(&self) -> ObjectKeys<'a> {
+        ObjectKeys::new(self.options.duplicate_keys)
+    }
+
+    /// Adds an entry for the key that starts at byte offset `key_pos`, applying the
+    /// duplicate-key policy
+    ///
+    /// Returns the index the value was stored at, or `None` if it was dropped.
+    pub(crate) fn add_entry(
+        &mut self,
+        keys: &mut ObjectKeys<'a>,
+        entries: &mut Vec<(&'a str, DataValue<'a>)>,
+        entry: (&'a str, DataValue<'a>),
+        key_pos: usize,
+    ) -> Result<Option<usize>> {
+        let key = entry.0;
+        keys.add(entries, entry)
+            .map_err(|()| self.error_at(key_pos, &format!("duplicate key \"{}\"", key)))
+    }
+
+    /// After a comma, consumes `close` if trailing commas are allowed and it follows
+    fn trailing_comma(&mut self, close: u8) -> bool {
+        if !self.options.allow_trailing_commas {
+            return false;
+        }
+        self.lexer.skip_whitespace();
+        let found = self.lexer.peek() == Some(close);
+        if found {
+            self.lexer.pos += 1;
+        }
+        found
+    }
+
+    /// Parses one value; `key` is the object key the value belongs to, if any
+    ///
+    /// Arrays and objects are parsed with an explicit stack of the containers
+    /// still open, so the nesting depth is bounded by `max_depth` and memory, not
+    /// by the size of the thread stack.
+    fn parse_value(&mut self, key: Option<&str>) -> Result<(DataValue<'a>, Range<usize>)> {
+        /// An array or object whose closing bracket has not been reached yet
+        enum Frame<'a> {
+            Array {
+                start: usize,
+                items: Vec<DataValue<'a>>,
+                spans: Vec<Range<usize>>,
+            },
+            Object {
+                start: usize,
+                entries: Vec<(&'a str, DataValue<'a>)>,
+                /// Value and key span of each entry
+                spans: Vec<(Range<usize>, Range<usize>)>,
+                keys: ObjectKeys<'a>,
+                /// The key of the value being parsed, and its span
+                key: (&'a str, Range<usize>),
+            },
+        }
+
+        let mut stack: Vec<Frame<'a>> = Vec::new();
+        loop {
+            self.lexer.skip_whitespace();
+            let start = self.lexer.pos;
+            let value_key = match stack.last() {
+                Some(Frame::Object { key, .. }) => Some(key.0),
+                Some(Frame::Array { .. }) => None,
+                None => key,
+            };
+            let mut value = match self.lexer.peek() {
+                None => return Err(self.lexer.error("unexpected end of input")),
+                Some(_) if value_key.is_some_and(|key| self.options.is_raw_key(key)) => {
+                    self.raw_value()?
+                }
+                Some(b'n') => self.lexer.expect_literal("null").map(|_| DataValue::Null)?,
+                Some(b't') => self
+                    .lexer
+                    .expect_literal("true")
+                    .map(|_| DataValue::Bool(true))?,
+                Some(b'f') => self
+                    .lexer
+                    .expect_literal("false")
+                    .map(|_| DataValue::Bool(false))?,
+                Some(b'"') => {
+                    let s = self.lexer.parse_string()?;
+                    self.check_length(&s, start)?;
+                    let borrowed = self.borrow(matches!(s, Cow::Owned(_)), start + 1);
+                    string_value(self.arena, &s, borrowed, value_key, self.options)
+                }
+                Some(b'[') => {
+                    self.enter(start)?;
+                    self.lexer.pos += 1;
+                    self.lexer.skip_whitespace();
+                    if self.lexer.peek() == Some(b']') {
+                        self.lexer.pos += 1;
+                        self.leave();
+                        DataValue::Array(&[])
+                    } else {
+                        stack.push(Frame::Array {
+                            start,
+                            items: Vec::new(),
+                            spans: Vec::new(),
+                        });
+                        continue;
+                    }
+                }
+                Some(b'{') => {
+                    self.enter(start)?;
+                    self.lexer.pos += 1;
+                    self.lexer.skip_whitespace();
+                    if self.lexer.peek() == Some(b'}') {
+                        self.lexer.pos += 1;
+                        self.leave();
+                        DataValue::Object(&[])
+                    } else {
+                        stack.push(Frame::Object {
+                            start,
+                            entries: Vec::new(),
+                            spans: Vec::new(),
+                            keys: self.object_keys(),
+                            key: self.parse_entry_key()?,
+                        });
+                        continue;
+                    }
+                }
+                Some(b'-') if self.lexer.input[start..].starts_with("-I") => {
+                    self.parse_non_finite()?
+                }
+                Some(b'-' | b'0'..=b'9') => self.parse_number()?,
+                Some(b'N' | b'I') => self.parse_non_finite()?,
+                Some(_) => return Err(self.lexer.error("expected value")),
+            };
+            let mut span = start..self.lexer.pos;
 
-            for (key, value) in map {
-                // Allocate the key in the arena
-                let key_ref = arena.alloc_str(key);
+            // Add the value to its container, then close every container that ends
+            // right after it
+            loop {
+                let close = match stack.last_mut() {
+                    None => return Ok((value, span)),
+                    Some(Frame::Array { items, spans, .. }) => {
+                        items.push(value);
+                        spans.push(span);
+                        b']'
+                    }
+                    Some(Frame::Object {
+                        entries,
+                        spans,
+                        keys,
+                        key: (key, key_span),
+                        ..
+                    }) => {
+                        let entry = (*key, value);
+                        match self.add_entry(keys, entries, entry, key_span.start)? {
+                            Some(index) if index < spans.len() => spans[index].0 = span,
+                            Some(_) => spans.push((span, key_span.clone())),
+                            None => {}
+                        }
+                        b'}'
+                    }
+                };
+                self.lexer.skip_whitespace();
+                match self.lexer.peek() {
+                    Some(b',') => {
+                        self.lexer.pos += 1;
+                        if !self.trailing_comma(close) {
+                            if let Some(Frame::Object { key, .. }) = stack.last_mut() {
+                                *key = self.parse_entry_key()?;
+                            }
+                            break;
+                        }
+                    }
+                    Some(b) if b == close => self.lexer.pos += 1,
+                    _ if close == b']' => return Err(self.lexer.error("expected ',' or ']'")),
+                    _ => return Err(self.lexer.error("expected ',' or '}'")),
+                }
+                self.leave();
+                (value, span) = match stack.pop() {
+                    Some(Frame::Array {
+                        start,
+                        items,
+                        spans,
+                    }) => {
+                        let slice = self.arena.alloc_slice_clone(&items);
+                        if self.spans.is_some() {
+                            for (node, span) in slice.iter().zip(spans) {
+                                self.record(node, span);
+                            }
+                        }
+                        (DataValue::Array(slice), start..self.lexer.pos)
+                    }
+                    Some(Frame::Object {
+                        start,
+                        entries,
+                        spans,
+                        ..
+                    }) => {
+                        let slice = self.arena.alloc_slice_clone(&entries);
+                        if self.spans.is_some() {
+                            for (entry, (span, key_span)) in slice.iter().zip(spans) {
+                                self.record(&entry.1, span);
+                                self.key_spans.insert(
+                                    entry as *const (&'a str, DataValue<'a>) as usize,
+                                    key_span,
+                                );
+                            }
+                        }
+                        (DataValue::Object(slice), start..self.lexer.pos)
+                    }
+                    None => unreachable!("a container was just closed"),
+                };
+            }
+        }
+    }
 
-                // Convert the value
-                let value_data = from_json(arena, value)?;
+    /// Checks the syntax of the value at the lexer position and returns its text
+    /// as a [`DataValue::Raw`], without allocating its contents
+    fn raw_value(&mut self) -> Result<DataValue<'a>> {
+        let start = self.lexer.pos;
+        // The containers still open inside the value; true for objects
+        let mut open = Vec::new();
+        loop {
+            self.lexer.skip_whitespace();
+            match self.lexer.peek() {
+                None => return Err(self.lexer.error("unexpected end of input")),
+                Some(b'n') => self.lexer.expect_literal("null")?,
+                Some(b't') => self.lexer.expect_literal("true")?,
+                Some(b'f') => self.lexer.expect_literal("false")?,
+                Some(b'"') => {
+                    self.lexer.parse_string()?;
+                }
+                Some(b'-' | b'0'..=b'9') => {
+                    self.lexer.scan_number()?;
+                }
+                Some(b @ (b'[' | b'{')) => {
+                    let close = if b == b'[' { b']' } else { b'}' };
+                    self.enter(self.lexer.pos)?;
+                    self.lexer.pos += 1;
+                    self.lexer.skip_whitespace();
+                    if self.lexer.peek() == Some(close) {
+                        self.lexer.pos += 1;
+                        self.leave();
+                    } else {
+                        open.push(b == b'{');
+                        if b == b'{' {
+                            self.skip_entry_key()?;
+                        }
+                        continue;
+                    }
+                }
+                Some(_) => return Err(self.lexer.error("expected value")),
+            }
 
-                // Add the pair to entries
-                entries.push((key_ref, value_data));
+            // Close every container that ends right after the value
+            loop {
+                let Some(&object) = open.last() else {
+                    let text = &self.lexer.input[start..self.lexer.pos];
+                    return Ok(DataValue::Raw(match self.source {
+                        Some(source) => &source[start..self.lexer.pos],
+                        None => self.arena.alloc_str(text),
+                    }));
+                };
+                let close = if object { b'}' } else { b']' };
+                self.lexer.skip_whitespace();
+                match self.lexer.peek() {
+                    Some(b',') => {
+                        self.lexer.pos += 1;
+                        if !self.trailing_comma(close) {
+                            if object {
+                                self.skip_entry_key()?;
+                            }
+                            break;
+                        }
+                    }
+                    Some(b) if b == close => self.lexer.pos += 1,
+                    _ if object => return Err(self.lexer.error("expected ',' or '}'")),
+                    _ => return Err(self.lexer.error("expected ',' or ']'")),
+                }
+                self.leave();
+                open.pop();
             }
+        }
+    }
+
+    /// Checks an object key and the colon after it inside a raw value
+    fn skip_entry_key(&mut self) -> Result<()> {
+        self.lexer.skip_whitespace();
+        if self.lexer.peek() != Some(b'"') {
+            return Err(self.lexer.error("expected string key"));
+        }
+        self.lexer.parse_string()?;
+        self.lexer.skip_whitespace();
+        if self.lexer.peek() != Some(b':') {
+            return Err(self.lexer.error("expected ':'"));
+        }
+        self.lexer.pos += 1;
+        Ok(())
+    }
+
+    /// Parses an object key and the colon after it, returning the key and its span
+    fn parse_entry_key(&mut self) -> Result<(&'a str, Range<usize>)> {
+        self.lexer.skip_whitespace();
+        let key_pos = self.lexer.pos;
+        let key = self.parse_key()?;
+        let key_span = key_pos..self.lexer.pos;
+        self.lexer.skip_whitespace();
+        if self.lexer.peek() != Some(b':') {
+            return Err(self.lexer.error("expected ':'"));
+        }
+        self.lexer.pos += 1;
+        Ok((key, key_span))
+    }
+
+    /// Parses an object key, allocating it unless it can be borrowed
+    fn parse_key(&mut self) -> Result<&'a str> {
+        if self.lexer.peek() != Some(b'"') {
+            return Err(self.lexer.error("expected string key"));
+        }
+        let key_start = self.lexer.pos + 1;
+        let key = self.lexer.parse_string()?;
+        self.check_length(&key, key_start - 1)?;
+        let normalized = self.options.normalize(&key);
+        let copied = matches!(key, Cow::Owned(_)) || matches!(normalized, Cow::Owned(_));
+        Ok(match self.borrow(copied, key_start) {
+            Some(key) => key,
+            None => self.arena.alloc_str(&normalized),
+        })
+    }
 
-            // Allocate the entries in the arena
-            let entries_slice = arena.alloc_slice_clone(&entries);
-            Ok(DataValue::Object(entries_slice))
+    fn parse_number(&mut self) -> Result<DataValue<'a>> {
+        let start = self.lexer.pos;
+        let is_float = self.lexer.scan_number()?;
+        let text = &self.lexer.input[start..self.lexer.pos];
+        if self.options.number_mode == NumberMode::Raw {
+            let text = match self.source {
+                Some(source) => &source[start..self.lexer.pos],
+                None => self.arena.alloc_str(text),
+            };
+            return Ok(DataValue::Number(Number::Raw(text)));
+        }
+        #[cfg(feature = "bignum")]
+        if self.options.number_mode == NumberMode::Arbitrary {
+            return match crate::bignum::parse_number(self.arena, text, is_float) {
+                Some(number) => Ok(DataValue::Number(self.options.number(number))),
+                None => Err(self.error_at(start, "number out of range")),
+            };
+        }
+        if !is_float {
+            if let Ok(i) = text.parse::<i64>() {
+                return Ok(DataValue::Number(self.options.number(Number::Integer(i))));
+            }
+            if self.options.number_mode == NumberMode::Strict {
+                return Err(self.error_at(start, "integer out of range"));
+            }
+        }
+        match text.parse::<f64>() {
+            Ok(f) if f.is_finite() => Ok(DataValue::Number(self.options.number(Number::Float(f)))),
+            Ok(f) => match self.options.non_finite {
+                NonFiniteFloats::Reject => Err(self.lexer.error("number out of range")),
+                NonFiniteFloats::Null => Ok(DataValue::Null),
+                NonFiniteFloats::Literal => Ok(DataValue::Number(Number::Float(f))),
+            },
+            Err(_) => Err(self.lexer.error("number out of range")),
         }
     }
+
+    /// Parses `NaN`, `Infinity` or `-Infinity` as [`ParseOptions::non_finite`] directs
+    fn parse_non_finite(&mut self) -> Result<DataValue<'a>> {
+        let rest = &self.lexer.input[self.lexer.pos..];
+        let Some((token, f)) = [
+            ("NaN", f64::NAN),
+            ("Infinity", f64::INFINITY),
+            ("-Infinity", f64::NEG_INFINITY),
+        ]
+        .into_iter()
+        .find(|(token, _)| rest.starts_with(token)) else {
+            return Err(self.lexer.error("expected value"));
+        };
+        let value = match self.options.non_finite {
+            NonFiniteFloats::Reject => return Err(self.lexer.error("non-finite number")),
+            NonFiniteFloats::Null => DataValue::Null,
+            NonFiniteFloats::Literal => DataValue::Number(Number::Float(f)),
+        };
+        self.lexer.pos += token.len();
+        Ok(value)
+    }
+}
+
+impl<'a, 'o> Parser<'a, 'a, 'o> {
+    /// Creates a parser whose strings and keys borrow from `input` unless they
+    /// contain escapes
+    pub(crate) fn borrowing(arena: &'a Bump, input: &'a str, options: &'o ParseOptions) -> Self {
+        let mut parser = Parser::new(arena, input, options);
+        parser.source = Some(input);
+        parser
+    }
 }
 
 impl<'a> DataValue<'a> {
@@ -249,39 +2010,12 @@ impl<'a> DataValue<'a> {
     /// let value = DataValue::from_json(&arena, &json_value).unwrap();
     /// assert_eq!(value["name"].as_str(), Some("John"));
     /// ```
+    #[cfg(feature = "serde_json-compat")]
     pub fn from_json(arena: &'a Bump, json: &serde_json::Value) -> Result<Self> {
         from_json(arena, json)
     }
 }
 
-// Implementation for serde Deserialize
-impl<'de, 'a> serde::Deserialize<'de> for DataValue<'a>
-where
-    'de: 'a,
-{
-    /// Deserialize a DataValue from a serde Deserializer
-    ///
-    /// This implementation creates a leaked arena for DataValue allocation,
-    /// which may cause memory leaks if used repeatedly. For most cases,
-    /// prefer using from_str or from_json with an explicitly managed arena.
-    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
-    where
-        D: Deserializer<'de>,
-    {
-        // First deserialize into a serde_json::Value
-        let json = serde_json::Value::deserialize(deserializer)?;
-
-        // Create a new arena for this deserialization
-        // This isn't ideal as it causes a memory leak, but it's
-        // needed because we can't store the arena reference
-        let bump = Box::leak(Box::new(Bump::new()));
-
-        // Convert to DataValue using the leaked arena
-        from_json(bump, &json)
-            .map_err(|e| serde::de::Error::custom(format!("Error converting to DataValue: {}", e)))
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -310,10 +2044,10 @@ mod tests {
         }
 
         // Test float
-        let json = "3.14";
+        let json = "2.75";
         let value = from_str(&arena, json).unwrap();
         if let DataValue::Number(Number::Float(f)) = value {
-            assert!((f - 3.14).abs() < f64::EPSILON);
+            assert!((f - 2.75).abs() < f64::EPSILON);
         } else {
             panic!("Expected float");
         }
@@ -429,6 +2163,7 @@ mod tests {
         }
     }
 
+    #[cfg(feature = "serde_json-compat")]
     #[test]
     fn test_from_json() {
         let arena = Bump::new();
@@ -466,4 +2201,729 @@ mod tests {
             panic!("Expected object");
         }
     }
+
+    #[cfg(feature = "serde_json-compat")]
+    #[test]
+    fn test_duration_round_trip() {
+        let arena = Bump::new();
+        let original = helpers::object(
+            &arena,
+            vec![
+                (
+                    "timeout",
+                    DataValue::Duration(chrono::Duration::seconds(90)),
+                ),
+                ("name", helpers::string(&arena, "PARIS")),
+            ],
+        );
+
+        let json = serde_json::to_string(&original).unwrap();
+        assert_eq!(json, r#"{"timeout":"PT1M30S","name":"PARIS"}"#);
+
+        let parsed = from_str(&arena, &json).unwrap();
+        assert_eq!(
+            parsed["timeout"].as_duration(),
+            Some(chrono::Duration::seconds(90))
+        );
+        assert_eq!(parsed["name"].as_str(), Some("PARIS"));
+    }
+
+    #[test]
+    fn test_temporal_key_patterns() {
+        let arena = Bump::new();
+        let json = r#"{
+            "created_at": "2023-01-01T10:00:00Z",
+            "events": [{"start_time": "2023-01-02 08:30:00", "note": "2023-01-02"}],
+            "timestamp": "not a date",
+            "updated_at": 1672531200,
+            "at": "2023-01-01"
+        }"#;
+
+        let value = from_str_with_options(&arena, json, &ParseOptions::with_common_temporal_keys())
+            .unwrap();
+        assert!(value["created_at"].as_datetime().is_some());
+        assert!(value["events"][0]["start_time"].as_datetime().is_some());
+        assert_eq!(value["events"][0]["note"].as_str(), Some("2023-01-02"));
+        assert_eq!(value["timestamp"].as_str(), Some("not a date"));
+        assert_eq!(value["updated_at"].as_i64(), Some(1672531200));
+        assert_eq!(value["at"].as_str(), Some("2023-01-01"));
+
+        // Without options nothing is promoted
+        let plain = from_str(&arena, json).unwrap();
+        assert!(plain["created_at"].is_string());
+    }
+
+    #[test]
+    fn test_raw_keys() {
+        let arena = Bump::new();
+        let options = ParseOptions {
+            raw_keys: vec!["*payload".to_string()],
+            max_depth: Some(4),
+            ..ParseOptions::default()
+        };
+        let input = r#"{"id": 1, "payload": {"a": [1.0, "x\"y", {}], "b": null} , "list": [{"payload": "s"}], "empty_payload": []}"#;
+        let value = from_str_with_options(&arena, input, &options).unwrap();
+        assert!(matches!(
+            value["payload"],
+            DataValue::Raw(r#"{"a": [1.0, "x\"y", {}], "b": null}"#)
+        ));
+        assert!(matches!(
+            value["list"][0]["payload"],
+            DataValue::Raw(r#""s""#)
+        ));
+        assert!(matches!(value["empty_payload"], DataValue::Raw("[]")));
+        assert_eq!(value["payload"].get_type(), crate::DataValueType::Object);
+        assert_eq!(
+            value["list"][0]["payload"].get_type(),
+            crate::DataValueType::String
+        );
+        assert_eq!(
+            crate::to_string(&value),
+            r#"{"id":1,"payload":{"a": [1.0, "x\"y", {}], "b": null},"list":[{"payload":"s"}],"empty_payload":[]}"#
+        );
+
+        // Raw values compare, hash and canonicalize like what they parse to
+        let parsed = from_str(&arena, input).unwrap();
+        assert_eq!(value, parsed);
+        let copied = from_str_with_options(
+            &arena,
+            r#"{"payload": {"a": [1]}, "copy": {"a": [1]}}"#,
+            &options,
+        )
+        .unwrap();
+        let duplicates = crate::operations::find_duplicates(&copied, 2);
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(
+            (duplicates[0].size, &duplicates[0].paths[..]),
+            (3, &["/payload".to_string(), "/copy".to_string()][..])
+        );
+        let mut canonical = String::new();
+        crate::ser::write_canonical(&value["payload"], &mut canonical);
+        assert_eq!(canonical, r#"{"a":[1,"x\"y",{}],"b":null}"#);
+
+        // Strings in the raw text are borrowed when the input is
+        let borrowed = from_str_borrowed_with_options(&arena, input, &options).unwrap();
+        let DataValue::Raw(text) = borrowed["payload"] else {
+            panic!("payload should be raw");
+        };
+        assert!(input.as_bytes().as_ptr_range().contains(&text.as_ptr()));
+
+        // The syntax and depth of raw values are still checked
+        for bad in [
+            r#"{"payload": [1,]}"#,
+            r#"{"payload": {"a" 1}}"#,
+            r#"{"payload": [1 2]}"#,
+            r#"{"payload": [[[[1]]]]}"#,
+            r#"{"payload": tru}"#,
+            r#"{"payload": [1]x}"#,
+            r#"{"payload": "open"#,
+        ] {
+            assert!(
+                from_str_with_options(&arena, bad, &options).is_err(),
+                "{}",
+                bad
+            );
+        }
+        let error =
+            from_str_with_options(&arena, r#"{"payload": {"a": 1, 2}}"#, &options).unwrap_err();
+        assert_eq!(error.column(), Some(22));
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*_at", "created_at"));
+        assert!(!glob_match("*_at", "created_at_utc"));
+        assert!(glob_match("timestamp", "timestamp"));
+        assert!(!glob_match("timestamp", "timestamps"));
+        assert!(glob_match("ts_*_utc", "ts_event_utc"));
+        assert!(glob_match("*", ""));
+        assert!(!glob_match("a*b*c", "acb"));
+    }
+
+    #[cfg(feature = "serde_json-compat")]
+    #[test]
+    fn test_native_parser_matches_serde() {
+        let arena = Bump::new();
+        let options = ParseOptions::default();
+        let inputs = [
+            "null",
+            "[true, false, -0, 12, -3.5e2, 1E-2, 18446744073709551615]",
+            r#"{"a": {"b": [1, {"c": "d"}]}, "e": ""}"#,
+            r#""esc \" \\ \/ \b \f \n \r \t \u00e9 \ud83d\ude00 tail""#,
+            "  {  }  ",
+            "[[], [[]]]",
+        ];
+        for input in inputs {
+            let json: serde_json::Value = serde_json::from_str(input).unwrap();
+            let expected = from_json(&arena, &json).unwrap();
+            let actual = Parser::new(&arena, input, &options)
+                .parse_document()
+                .unwrap();
+            assert_eq!(actual, &expected, "mismatch for {}", input);
+        }
+    }
+
+    #[test]
+    fn test_native_parser_errors() {
+        let arena = Bump::new();
+        let options = ParseOptions::default();
+        let inputs = [
+            "",
+            "[1,]",
+            "{\"a\" 1}",
+            "01",
+            "1.",
+            "-",
+            "\"abc",
+            "[1] x",
+            "{1: 2}",
+            "tru",
+            "\"\\ud800\"",
+            "\"\\x\"",
+            "\"a\u{1}b\"",
+            "1e400",
+        ];
+        for input in inputs {
+            let result = Parser::new(&arena, input, &options).parse_document();
+            assert!(result.is_err(), "accepted {:?}", input);
+        }
+
+        let err = Parser::new(&arena, "{\n  \"a\": ?\n}", &options)
+            .parse_document()
+            .unwrap_err();
+        assert!(err.to_string().contains("line 2 column 8"), "{}", err);
+    }
+
+    #[test]
+    fn test_error_paths() {
+        let cases = [
+            ("?", ""),
+            ("[1, [2, ?]]", "/1/1"),
+            (r#"{"a": {"b/c": [true, ?]}}"#, "/a/b~1c/1"),
+            (r#"{"a": 1, ?}"#, ""),
+            (r#"{"a": 1, "b" ?}"#, "/b"),
+            (r#"{"\u00e9": [1 ?]}"#, "/\u{e9}/0"),
+            (r#"{"a": "x,]}", "b": ["y", ?]}"#, "/b/1"),
+            ("{// c, ]\n a: [1, /* ] */ 2, ?]}", "/a/2"),
+            ("{'a': {b: [?]}}", "/a/b/0"),
+        ];
+        for (input, path) in cases {
+            let end = input.find('?').unwrap_or(input.len());
+            assert_eq!(path_at(input, end), path, "{}", input);
+        }
+
+        let arena = Bump::new();
+        let err = from_str(&arena, r#"{"a": [{"b": 1}, {"b": ]}"#).unwrap_err();
+        assert_eq!(err.path(), Some("/a/1/b"));
+        assert_eq!(err.byte_offset(), Some(23));
+        assert!(err.to_string().ends_with("(path /a/1/b)"), "{}", err);
+        let err = from_str(&arena, "\"unterminated").unwrap_err();
+        assert_eq!(
+            (err.line(), err.column(), err.path()),
+            (Some(1), Some(14), Some(""))
+        );
+    }
+
+    #[test]
+    fn test_from_str_borrowed() {
+        let arena = Bump::new();
+        let input = r#"{"plain": "abc", "esc\u0041": "x\ny", "list": ["é", ""]}"#;
+        let value = from_str_borrowed(&arena, input).unwrap();
+        let range = input.as_bytes().as_ptr_range();
+        let borrowed = |s: &str| range.contains(&s.as_ptr());
+
+        let (plain_key, plain) = &value.as_object().unwrap()[0];
+        assert!(borrowed(plain_key));
+        assert!(borrowed(plain.as_str().unwrap()));
+        let (escaped_key, escaped) = &value.as_object().unwrap()[1];
+        assert_eq!(*escaped_key, "escA");
+        assert!(!borrowed(escaped_key));
+        assert_eq!(escaped.as_str(), Some("x\ny"));
+        assert!(borrowed(value["list"][0].as_str().unwrap()));
+        assert_eq!(value, from_str(&arena, input).unwrap());
+    }
+
+    #[test]
+    fn test_number_normalization() {
+        use crate::DataValueType::{self, Float, Integer};
+
+        let arena = Bump::new();
+        let input = "[30.0, 2.5, 7, -0.0, 1e300]";
+
+        let normalize = ParseOptions {
+            normalize_integral_floats: true,
+            ..ParseOptions::default()
+        };
+        let value = from_str_with_options(&arena, input, &normalize).unwrap();
+        let types: Vec<DataValueType> = value
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|v| v.get_type())
+            .collect();
+        assert_eq!(types, vec![Integer, Float, Integer, Integer, Float]);
+        assert_eq!(value[0].as_i64(), Some(30));
+        #[cfg(feature = "serde_json-compat")]
+        {
+            let json: serde_json::Value = serde_json::from_str(input).unwrap();
+            let converted = from_json_with_options(&arena, &json, &normalize).unwrap();
+            assert_eq!(value, converted);
+        }
+
+        let floats = ParseOptions {
+            ints_as_floats: true,
+            normalize_integral_floats: true,
+            ..ParseOptions::default()
+        };
+        let value = from_str_with_options(&arena, input, &floats).unwrap();
+        assert!(value
+            .as_array()
+            .unwrap()
+            .iter()
+            .all(|v| v.get_type() == Float));
+    }
+
+    #[test]
+    fn test_tokenizer() {
+        let input = " [1, -2.5e3,\n\"a\\\"b\", true, null, false, {}] ";
+        let tokens: Vec<Token> = Tokenizer::new(input).collect::<Result<_>>().unwrap();
+        let kinds: Vec<TokenKind> = tokens.iter().map(|t| t.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                TokenKind::BeginArray,
+                TokenKind::Number,
+                TokenKind::Comma,
+                TokenKind::Number,
+                TokenKind::Comma,
+                TokenKind::String,
+                TokenKind::Comma,
+                TokenKind::True,
+                TokenKind::Comma,
+                TokenKind::Null,
+                TokenKind::Comma,
+                TokenKind::False,
+                TokenKind::Comma,
+                TokenKind::BeginObject,
+                TokenKind::EndObject,
+                TokenKind::EndArray,
+            ]
+        );
+        for token in &tokens {
+            assert_eq!(&input[token.span()], token.text);
+        }
+        assert_eq!(tokens[3].text, "-2.5e3");
+        assert!(matches!(tokens[3].as_number(), Some(Number::Float(f)) if f == -2500.0));
+        assert_eq!(tokens[5].as_str().unwrap(), "a\"b");
+        assert_eq!(tokens[1].as_str(), None);
+
+        // Grammar is not checked, but lexing errors end the stream
+        assert_eq!(Tokenizer::new("]] 1 2").count(), 4);
+        for input in ["[-]", "[tru]", "[\"\\x\"]", "[?]", "\"open"] {
+            let results: Vec<_> = Tokenizer::new(input).collect();
+            assert!(results.last().unwrap().is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_unicode_normalization() {
+        let arena = Bump::new();
+        let input = "{\"\u{fb01}le\": \"A\u{30a}\", \"plain\": \"x\"}";
+        let options = ParseOptions::default().normalize_unicode(NormalizationForm::Nfkc);
+
+        let parsed = from_str_with_options(&arena, input, &options).unwrap();
+        assert_eq!(parsed["file"].as_str(), Some("\u{c5}"));
+
+        let borrowed = from_str_borrowed_with_options(&arena, input, &options).unwrap();
+        assert_eq!(borrowed["file"].as_str(), Some("\u{c5}"));
+        let plain = borrowed["plain"].as_str().unwrap();
+        assert!(input.as_bytes().as_ptr_range().contains(&plain.as_ptr()));
+
+        #[cfg(feature = "serde_json-compat")]
+        {
+            let json: serde_json::Value = serde_json::from_str(input).unwrap();
+            let converted = from_json_with_options(&arena, &json, &options).unwrap();
+            assert_eq!(converted["file"].as_str(), Some("\u{c5}"));
+        }
+
+        let untouched = from_str(&arena, input).unwrap();
+        assert!(untouched.get("file").is_none());
+    }
+
+    #[test]
+    fn test_parse_limits() {
+        let arena = Bump::new();
+        let parse = |input: &str, options: &ParseOptions| {
+            from_str_with_options(&arena, input, options)
+                .map(|value| crate::to_string(&value))
+                .map_err(|e| e.to_string())
+        };
+
+        let depth = ParseOptions {
+            max_depth: Some(2),
+            ..ParseOptions::default()
+        };
+        assert_eq!(
+            parse(r#"[{"a": 1}, []]"#, &depth).unwrap(),
+            r#"[{"a":1},[]]"#
+        );
+        let error = parse(r#"[{"a": [1]}]"#, &depth).unwrap_err();
+        assert!(
+            error.contains("nesting too deep at line 1 column 8"),
+            "{}",
+            error
+        );
+        let deep = "[".repeat(100_000);
+        assert!(parse(&deep, &depth).is_err());
+
+        let length = ParseOptions {
+            max_string_length: Some(3),
+            ..ParseOptions::default()
+        };
+        assert!(parse(r#"{"abc": "\u00e9"}"#, &length).is_ok());
+        assert!(parse(r#"["abcd"]"#, &length).is_err());
+        assert!(parse(r#"{"abcd": 1}"#, &length).is_err());
+        assert!(parse(r#"["\u00e9\u00e9"]"#, &length).is_err());
+
+        let input = r#"{"a": 1, "b": {"c": 1, "c": 2}, "a": 3, "a": 4}"#;
+        let cases = [
+            (
+                DuplicateKeyPolicy::KeepAll,
+                r#"{"a":1,"b":{"c":1,"c":2},"a":3,"a":4}"#,
+            ),
+            (DuplicateKeyPolicy::FirstWins, r#"{"a":1,"b":{"c":1}}"#),
+            (DuplicateKeyPolicy::LastWins, r#"{"a":4,"b":{"c":2}}"#),
+        ];
+        for (duplicate_keys, expected) in cases {
+            let options = ParseOptions {
+                duplicate_keys,
+                ..ParseOptions::default()
+            };
+            assert_eq!(parse(input, &options).unwrap(), expected);
+            let document = crate::Document::parse_with_options(&arena, input, &options).unwrap();
+            assert_eq!(crate::to_string(document.root()), expected);
+            let span = document.span_of_pointer("/b/c").unwrap();
+            let winner = document.root()["b"]["c"].as_i64().unwrap();
+            assert_eq!(input[span].parse::<i64>().unwrap(), winner);
+        }
+        let reject = ParseOptions {
+            duplicate_keys: DuplicateKeyPolicy::Error,
+            ..ParseOptions::default()
+        };
+        let error = parse(input, &reject).unwrap_err();
+        assert!(error.contains("duplicate key \"c\""), "{}", error);
+
+        let strict = ParseOptions {
+            number_mode: NumberMode::Strict,
+            ..ParseOptions::default()
+        };
+        assert!(parse("[-9223372036854775808, 1e300, 0.1]", &strict).is_ok());
+        assert!(parse("[9223372036854775808]", &strict).is_err());
+        assert!(parse("[9223372036854775808]", &ParseOptions::default()).is_ok());
+
+        let trailing = ParseOptions {
+            allow_trailing_data: true,
+            ..ParseOptions::default()
+        };
+        assert_eq!(parse("{\"a\": 1} {\"b\"", &trailing).unwrap(), r#"{"a":1}"#);
+        assert_eq!(parse("1 x", &trailing).unwrap(), "1");
+        assert!(parse("{\"a\": 1} x", &ParseOptions::default()).is_err());
+    }
+
+    #[test]
+    fn test_jsonc() {
+        let arena = Bump::new();
+        let input = "// leading\n{/* a */\"a\": [1, /* b */ 2,], // c\n\"b\": \"// not a comment\",}\n// end";
+        let value = from_str_jsonc(&arena, input).unwrap();
+        assert_eq!(
+            crate::to_string(&value),
+            r#"{"a":[1,2],"b":"// not a comment"}"#
+        );
+
+        for input in [
+            "[1,,]",
+            "[,]",
+            "{,}",
+            "[1] /* open",
+            "[1 /* open ]",
+            "/ 1",
+            "[1,]]",
+        ] {
+            assert!(from_str_jsonc(&arena, input).is_err(), "{}", input);
+        }
+        let comments = ParseOptions {
+            allow_comments: true,
+            ..ParseOptions::default()
+        };
+        assert!(from_str_with_options(&arena, "[1 /* x */]", &comments).is_ok());
+        assert!(from_str_with_options(&arena, "[1,]", &comments).is_err());
+        assert!(from_str(&arena, "[1 /* x */]").is_err());
+    }
+
+    #[test]
+    fn test_non_finite_numbers() {
+        let arena = Bump::new();
+        let input = "[NaN, Infinity, -Infinity, 1e999, -1.5]";
+        let err = from_str(&arena, input).unwrap_err();
+        assert!(err.to_string().contains("non-finite number"), "{}", err);
+        assert!(from_str(&arena, "[1e999]").is_err());
+
+        let options = ParseOptions {
+            non_finite: NonFiniteFloats::Null,
+            ..ParseOptions::default()
+        };
+        let value = from_str_with_options(&arena, input, &options).unwrap();
+        assert_eq!(crate::to_string(&value), "[null,null,null,null,-1.5]");
+        assert!(!options.is_lossless());
+
+        let options = ParseOptions {
+            non_finite: NonFiniteFloats::Literal,
+            ..ParseOptions::default()
+        };
+        let value = from_str_with_options(&arena, input, &options).unwrap();
+        assert!(value[0].as_f64().unwrap().is_nan());
+        assert_eq!(value[3].as_f64(), Some(f64::INFINITY));
+        let literal = crate::SerializeOptions {
+            non_finite: NonFiniteFloats::Literal,
+            ..crate::SerializeOptions::default()
+        };
+        assert_eq!(
+            crate::to_string_with_options(&value, &literal).unwrap(),
+            "[NaN,Infinity,-Infinity,Infinity,-1.5]"
+        );
+        assert!(crate::to_string_with_options(&value, &Default::default()).is_err());
+        assert!(from_str_with_options(&arena, "[-Inf]", &options).is_err());
+        assert!(from_str_with_options(&arena, "[NaNa]", &options).is_err());
+    }
+
+    #[test]
+    fn test_lossless_keeps_duration_strings() {
+        let arena = Bump::new();
+        for input in [
+            r#"{"a":"PT60M"}"#,
+            r#"["P1W","PT0.50S","-P1DT0H","+PT90S","PT0,5S"]"#,
+            r#"{"P1D":["P0D",{"x":"PT1H30M"}]}"#,
+        ] {
+            let value = from_str_with_options(&arena, input, &ParseOptions::lossless()).unwrap();
+            assert_eq!(crate::to_string(&value), input);
+            let document = crate::Document::parse_lossless(&arena, input).unwrap();
+            assert!(document.is_lossless_roundtrip(), "{}", input);
+        }
+        let value = from_str(&arena, r#"["PT60M"]"#).unwrap();
+        assert!(value[0].as_duration().is_some());
+    }
+
+    #[test]
+    fn test_deep_nesting_without_recursion() {
+        let depth = 100_000;
+        let input = format!("{}1{}", r#"[{"a":"#.repeat(depth), "}]".repeat(depth));
+        let arena = Bump::new();
+        let value = from_str(&arena, &input).unwrap();
+        assert_eq!(crate::to_string(&value), input);
+        let options = crate::SerializeOptions {
+            repeated_keys: crate::RepeatedKeys::Group,
+            ..crate::SerializeOptions::default()
+        };
+        assert_eq!(
+            crate::to_string_with_options(&value, &options).unwrap(),
+            input
+        );
+        let mut written = Vec::new();
+        crate::ser::write_json(&value, &mut written).unwrap();
+        assert_eq!(written, input.as_bytes());
+        let mut canonical = String::new();
+        crate::ser::write_canonical(&value, &mut canonical);
+        assert_eq!(canonical, input);
+
+        let other = Bump::new();
+        let copy = value.clone_in(&other);
+        assert!(copy == value);
+        let document = crate::Document::parse_lossless(&arena, &input).unwrap();
+        assert!(document.is_lossless_roundtrip());
+        // Indentation makes pretty output quadratic in depth, so use a shallower value
+        let shallow = format!("{}1{}", "[".repeat(5000), "]".repeat(5000));
+        let pretty = crate::to_string_pretty(&from_str(&arena, &shallow).unwrap());
+        assert!(pretty.ends_with("\n]"));
+        #[cfg(feature = "json5")]
+        assert!(crate::from_str_json5(&arena, &input).unwrap() == value);
+
+        // The depth limit is the only guard
+        let options = ParseOptions {
+            max_depth: Some(1000),
+            ..ParseOptions::default()
+        };
+        let err = from_str_with_options(&arena, &input, &options).unwrap_err();
+        assert!(err.to_string().contains("nesting too deep"), "{}", err);
+    }
+
+    #[cfg(feature = "serde_json-compat")]
+    #[test]
+    fn test_from_json_depth() {
+        let mut json = serde_json::json!("2023-01-01T00:00:00Z");
+        for _ in 0..500 {
+            json = serde_json::json!([{ "at": json, "n": 1 }]);
+        }
+        let arena = Bump::new();
+        let options = ParseOptions {
+            temporal_key_patterns: vec!["at".to_string()],
+            ..ParseOptions::default()
+        };
+        let value = from_json_with_options(&arena, &json, &options).unwrap();
+        let mut inner = &value;
+        for _ in 0..500 {
+            inner = &inner[0]["at"];
+        }
+        assert!(inner.as_datetime().is_some());
+        assert_eq!(value[0]["n"].as_i64(), Some(1));
+
+        let limited = ParseOptions {
+            max_depth: Some(1000),
+            ..ParseOptions::default()
+        };
+        assert!(from_json_with_options(&arena, &json, &limited).is_ok());
+        let limited = ParseOptions {
+            max_depth: Some(999),
+            ..ParseOptions::default()
+        };
+        let err = from_json_with_options(&arena, &json, &limited).unwrap_err();
+        assert!(err.to_string().contains("nesting too deep"), "{}", err);
+    }
+
+    #[cfg(feature = "bignum")]
+    #[test]
+    fn test_arbitrary_numbers() {
+        use crate::DataValueType;
+
+        let arena = Bump::new();
+        let options = ParseOptions {
+            number_mode: NumberMode::Arbitrary,
+            ..ParseOptions::default()
+        };
+        let input = r#"{"big":-123456789012345678901234567890,"pi":3.14159265358979323846,"huge":1e400,"small":7,"half":0.5}"#;
+        let value = from_str_with_options(&arena, input, &options).unwrap();
+        assert_eq!(crate::to_string(&value), input);
+        assert_eq!(value["big"].get_type(), DataValueType::Integer);
+        assert_eq!(value["pi"].get_type(), DataValueType::Float);
+        assert_eq!(value["big"].as_i64(), None);
+        assert_eq!(value["huge"].as_f64(), Some(f64::INFINITY));
+        assert!(matches!(
+            value["small"],
+            DataValue::Number(Number::Integer(7))
+        ));
+        assert!(matches!(value["half"], DataValue::Number(Number::Float(_))));
+
+        // Comparisons are exact, arithmetic goes through f64
+        let pi = &value["pi"];
+        assert!(*pi > DataValue::Number(Number::Float(std::f64::consts::PI)));
+        assert!(value["big"] < DataValue::Number(Number::Integer(i64::MIN)));
+        let other = from_str_with_options(&arena, "3.141592653589793238460", &options).unwrap();
+        assert_eq!(*pi, other);
+        assert_eq!(
+            (pi.clone() * crate::helpers::int(2)).unwrap().as_f64(),
+            Some(2.0 * std::f64::consts::PI)
+        );
+
+        // The borrowing and serde_json paths agree
+        let borrowed = from_str_borrowed_with_options(&arena, input, &options).unwrap();
+        assert_eq!(crate::to_string(&borrowed), input);
+        #[cfg(feature = "serde_json-compat")]
+        {
+            let json: serde_json::Value =
+                serde_json::from_str("[18446744073709551615, 0.25]").unwrap();
+            let value = from_json_with_options(&arena, &json, &options).unwrap();
+            assert!(matches!(value[0], DataValue::Number(Number::BigInt(_))));
+            assert_eq!(crate::to_string(&value), "[18446744073709551615,0.25]");
+        }
+    }
+
+    #[test]
+    fn test_raw_numbers() {
+        use crate::DataValueType;
+
+        let arena = Bump::new();
+        let raw = ParseOptions {
+            number_mode: NumberMode::Raw,
+            ..ParseOptions::default()
+        };
+        let input =
+            r#"{"price": 0.1000000000000000055, "id": 12345678901234567890, "n": -7, "e": 1E+2}"#;
+        let value = from_str_with_options(&arena, input, &raw).unwrap();
+        assert_eq!(
+            crate::to_string(&value),
+            r#"{"price":0.1000000000000000055,"id":12345678901234567890,"n":-7,"e":1E+2}"#
+        );
+        assert!(crate::to_string_pretty(&value).contains("\"id\": 12345678901234567890"));
+        let mut written = Vec::new();
+        crate::ser::write_json(&value, &mut written).unwrap();
+        assert_eq!(
+            String::from_utf8(written).unwrap(),
+            crate::to_string(&value)
+        );
+
+        assert_eq!(value["n"].as_i64(), Some(-7));
+        assert_eq!(value["n"].get_type(), DataValueType::Integer);
+        assert_eq!(value["id"].as_i64(), None);
+        assert_eq!(value["id"].get_type(), DataValueType::Float);
+        assert_eq!(value["e"].as_f64(), Some(100.0));
+        assert_eq!(value["n"], DataValue::Number(Number::Integer(-7)));
+        assert!(value["e"] > value["n"]);
+        assert_eq!(
+            (value["n"].clone() + value["e"].clone()).unwrap().as_f64(),
+            Some(93.0)
+        );
+
+        let borrowed = from_str_borrowed_with_options(&arena, input, &raw).unwrap();
+        let DataValue::Number(Number::Raw(text)) = borrowed["id"] else {
+            panic!("expected a raw number");
+        };
+        assert!(input.as_bytes().as_ptr_range().contains(&text.as_ptr()));
+    }
+
+    #[test]
+    fn test_unicode_escapes_and_surrogates() {
+        // Cases from the JSONTestSuite string tests
+        let cases = [
+            (r#""\uD801\udc37""#, Some("\u{10437}"), Some("\u{10437}")),
+            (
+                r#""\ud83d\ude39\ud83d\udc8d""#,
+                Some("\u{1f639}\u{1f48d}"),
+                Some("\u{1f639}\u{1f48d}"),
+            ),
+            (
+                r#""\u0061\u30af\u30EA\u30b9""#,
+                Some("a\u{30af}\u{30ea}\u{30b9}"),
+                Some("a\u{30af}\u{30ea}\u{30b9}"),
+            ),
+            (
+                r#""\u0022\u0000\uFFFF""#,
+                Some("\"\u{0}\u{ffff}"),
+                Some("\"\u{0}\u{ffff}"),
+            ),
+            (r#""\uDADA""#, None, Some("\u{fffd}")),
+            (r#""\uD888\u1234""#, None, Some("\u{fffd}\u{1234}")),
+            (r#""\uD800\n""#, None, Some("\u{fffd}\n")),
+            (r#""\uD800\uD800\n""#, None, Some("\u{fffd}\u{fffd}\n")),
+            (r#""\uDd1e\uD834""#, None, Some("\u{fffd}\u{fffd}")),
+            (r#""\uDFAA""#, None, Some("\u{fffd}")),
+            (r#""\uD834\uDd""#, None, None),
+            (r#""\u00A""#, None, None),
+            (r#""\uqqqq""#, None, None),
+        ];
+        let arena = Bump::new();
+        let replace = ParseOptions {
+            lone_surrogates: SurrogatePolicy::Replace,
+            ..ParseOptions::default()
+        };
+        for (input, strict, replaced) in cases {
+            let value = from_str(&arena, input).ok();
+            assert_eq!(value.as_ref().and_then(|v| v.as_str()), strict, "{}", input);
+            let value = from_str_with_options(&arena, input, &replace).ok();
+            assert_eq!(
+                value.as_ref().and_then(|v| v.as_str()),
+                replaced,
+                "{}",
+                input
+            );
+        }
+    }
 }
@@ -0,0 +1,153 @@
+//! Choosing a parse strategy from a sample of the input
+
+/// Number of bytes at the start of the input that automatic strategy selection scans
+pub const AUTO_SAMPLE_BYTES: usize = 16 * 1024;
+
+/// How a document was parsed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ParseStrategy {
+    /// Single pass over the input, copying every string into the arena
+    Copying,
+    /// Single pass over the input, with strings that contain no escapes pointing
+    /// into the source text instead of being copied
+    Borrowing,
+}
+
+/// Counts taken from the sampled start of an input
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SampleStats {
+    /// Number of bytes scanned
+    pub bytes: usize,
+    /// Brackets, braces, commas and colons outside strings
+    pub structural_chars: usize,
+    /// Number of strings, including object keys
+    pub strings: usize,
+    /// Bytes between the quotes of those strings
+    pub string_bytes: usize,
+    /// Backslash escapes inside strings
+    pub escapes: usize,
+    /// Deepest nesting of arrays and objects reached
+    pub max_depth: usize,
+}
+
+impl SampleStats {
+    /// Scans `input`, which may end anywhere, including inside a string
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use datavalue_rs::de::SampleStats;
+    ///
+    /// let stats = SampleStats::scan(br#"{"a": ["x\"y", 1]}"#);
+    /// assert_eq!(stats.structural_chars, 6);
+    /// assert_eq!((stats.strings, stats.string_bytes, stats.escapes), (2, 5, 1));
+    /// assert_eq!(stats.max_depth, 2);
+    /// ```
+    pub fn scan(input: &[u8]) -> Self {
+        let mut stats = SampleStats {
+            bytes: input.len(),
+            ..SampleStats::default()
+        };
+        let mut depth = 0usize;
+        let mut in_string = false;
+        let mut escaped = false;
+        for &b in input {
+            if in_string {
+                if escaped {
+                    escaped = false;
+                } else if b == b'\\' {
+                    escaped = true;
+                    stats.escapes += 1;
+                } else if b == b'"' {
+                    in_string = false;
+                    continue;
+                }
+                stats.string_bytes += 1;
+                continue;
+            }
+            match b {
+                b'"' => {
+                    in_string = true;
+                    stats.strings += 1;
+                }
+                b'[' | b'{' => {
+                    depth += 1;
+                    stats.max_depth = stats.max_depth.max(depth);
+                    stats.structural_chars += 1;
+                }
+                b']' | b'}' => {
+                    depth = depth.saturating_sub(1);
+                    stats.structural_chars += 1;
+                }
+                b',' | b':' => stats.structural_chars += 1,
+                _ => {}
+            }
+        }
+        stats
+    }
+}
+
+/// The strategy a document was parsed with, and the sample it was chosen from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseReport {
+    /// The strategy used
+    pub strategy: ParseStrategy,
+    /// Length of the whole input in bytes
+    pub input_bytes: usize,
+    /// Counts from the sampled start of the input, when the strategy was chosen
+    /// automatically
+    pub sample: Option<SampleStats>,
+}
+
+impl ParseReport {
+    /// Report for a document parsed with a fixed strategy
+    pub(crate) fn fixed(strategy: ParseStrategy, input: &str) -> Self {
+        ParseReport {
+            strategy,
+            input_bytes: input.len(),
+            sample: None,
+        }
+    }
+
+    /// Samples the first [`AUTO_SAMPLE_BYTES`] of `input` and picks a strategy
+    ///
+    /// Every input is parsed in a single pass with borrowed strings, since the
+    /// source text is kept anyway; the sample is reported alongside.
+    pub(crate) fn sample(input: &str) -> Self {
+        let sample = SampleStats::scan(&input.as_bytes()[..input.len().min(AUTO_SAMPLE_BYTES)]);
+        ParseReport {
+            strategy: ParseStrategy::Borrowing,
+            input_bytes: input.len(),
+            sample: Some(sample),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample() {
+        let stats = SampleStats::scan(br#"[{"k": "a\\b"}, "[{,:", "cut"#);
+        assert_eq!(stats.structural_chars, 6);
+        assert_eq!(stats.strings, 4);
+        assert_eq!(stats.string_bytes, 1 + 4 + 4 + 3);
+        assert_eq!(stats.escapes, 1);
+        assert_eq!(stats.max_depth, 2);
+
+        let small = ParseReport::sample("[1, 2]");
+        assert_eq!(small.strategy, ParseStrategy::Borrowing);
+        assert_eq!(small.sample.map(|s| s.bytes), Some(6));
+
+        let large = format!(
+            "[{}\"end\"]",
+            "\"lorem ipsum dolor sit amet\", ".repeat(4000)
+        );
+        let report = ParseReport::sample(&large);
+        assert_eq!(report.input_bytes, large.len());
+        assert_eq!(report.sample.map(|s| s.bytes), Some(AUTO_SAMPLE_BYTES));
+        assert_eq!(report.strategy, ParseStrategy::Borrowing);
+    }
+}
@@ -0,0 +1,186 @@
+//! Parsing JSON from a tokio `AsyncRead` or a stream of byte chunks, enabled by
+//! the `async` feature
+
+use std::pin::pin;
+
+use bumpalo::Bump;
+use futures_core::Stream;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use super::FeedParser;
+use crate::datavalue::DataValue;
+use crate::error::{Error, Result};
+
+/// Size of the chunks read from an `AsyncRead`
+const CHUNK_SIZE: usize = 8 * 1024;
+
+impl<'a> DataValue<'a> {
+    /// Parse JSON from an async reader
+    ///
+    /// Reads the input in chunks and feeds each one to a [`FeedParser`] as it
+    /// arrives, so the document is scanned while the rest is still in flight
+    /// and only the bytes of the value in progress are buffered.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading fails, the input is not a single valid JSON
+    /// document, or it is not valid UTF-8.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use datavalue_rs::{Bump, DataValue};
+    /// # use tokio::io::AsyncRead;
+    /// async fn read_config<'a>(
+    ///     arena: &'a Bump,
+    ///     file: impl AsyncRead,
+    /// ) -> datavalue_rs::Result<DataValue<'a>> {
+    ///     DataValue::from_reader_async(arena, file).await
+    /// }
+    /// ```
+    pub async fn from_reader_async<R: AsyncRead>(arena: &'a Bump, reader: R) -> Result<Self> {
+        let mut reader = pin!(reader);
+        let mut document = Document::new(arena);
+        let mut chunk = vec![0; CHUNK_SIZE];
+        loop {
+            let read = reader.read(&mut chunk).await?;
+            if read == 0 {
+                return document.finish();
+            }
+            document.feed(&chunk[..read])?;
+        }
+    }
+
+    /// Parse JSON from a stream of byte chunks
+    ///
+    /// Accepts the body streams of HTTP clients and servers, such as
+    /// `reqwest::Response::bytes_stream` or a hyper body turned into a stream of
+    /// data frames, and parses the document as the chunks arrive, like
+    /// [`from_reader_async`](DataValue::from_reader_async).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the stream yields an error, the input is not a single
+    /// valid JSON document, or it is not valid UTF-8. Stream errors are converted
+    /// with their `Display` message.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use datavalue_rs::{Bump, DataValue};
+    /// # use futures_core::Stream;
+    /// async fn read_body<'a>(
+    ///     arena: &'a Bump,
+    ///     body: impl Stream<Item = Result<Vec<u8>, std::io::Error>>,
+    /// ) -> datavalue_rs::Result<DataValue<'a>> {
+    ///     DataValue::from_stream(arena, body).await
+    /// }
+    /// ```
+    pub async fn from_stream<S, B, E>(arena: &'a Bump, stream: S) -> Result<Self>
+    where
+        S: Stream<Item = std::result::Result<B, E>>,
+        B: AsRef<[u8]>,
+        E: std::fmt::Display,
+    {
+        let mut stream = pin!(stream);
+        let mut document = Document::new(arena);
+        while let Some(chunk) = std::future::poll_fn(|cx| stream.as_mut().poll_next(cx)).await {
+            let chunk = chunk.map_err(|e| Error::custom(format!("Stream error: {}", e)))?;
+            document.feed(chunk.as_ref())?;
+        }
+        document.finish()
+    }
+}
+
+/// A [`FeedParser`] that expects exactly one value
+struct Document<'a> {
+    parser: FeedParser<'a>,
+    value: Option<DataValue<'a>>,
+}
+
+impl<'a> Document<'a> {
+    fn new(arena: &'a Bump) -> Self {
+        Document {
+            parser: FeedParser::new(arena),
+            value: None,
+        }
+    }
+
+    fn feed(&mut self, chunk: &[u8]) -> Result<()> {
+        for value in self.parser.feed(chunk)? {
+            if self.value.replace(value).is_some() {
+                return Err(Error::syntax("trailing characters"));
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<DataValue<'a>> {
+        match (self.value, self.parser.finish()?) {
+            (Some(_), Some(_)) => Err(Error::syntax("trailing characters")),
+            (Some(value), None) | (None, Some(value)) => Ok(value),
+            (None, None) => Err(Error::syntax("unexpected end of input")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::task::{Context, Poll, Waker};
+
+    use super::*;
+
+    /// Runs a future that never waits, as reading from memory does not
+    fn run<F: Future>(future: F) -> F::Output {
+        let mut future = pin!(future);
+        match future
+            .as_mut()
+            .poll(&mut Context::from_waker(Waker::noop()))
+        {
+            Poll::Ready(output) => output,
+            Poll::Pending => panic!("reading from memory does not wait"),
+        }
+    }
+
+    /// A stream of chunks that are already available
+    struct Chunks<I>(I);
+
+    impl<I: Iterator + Unpin> Stream for Chunks<I> {
+        type Item = I::Item;
+
+        fn poll_next(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Option<I::Item>> {
+            Poll::Ready(self.0.next())
+        }
+    }
+
+    #[test]
+    fn test_from_reader_async() {
+        let arena = Bump::new();
+        let text = format!(r#"{{"a": [{}], "b": "é"}}"#, vec!["1"; 10_000].join(", "));
+        let value = run(DataValue::from_reader_async(&arena, text.as_bytes())).unwrap();
+        assert_eq!(value["a"].as_array().map(|a| a.len()), Some(10_000));
+        assert_eq!(value["b"].as_str(), Some("é"));
+
+        let number = run(DataValue::from_reader_async(&arena, &b" 42 "[..])).unwrap();
+        assert_eq!(number.as_i64(), Some(42));
+        for bad in [&b""[..], b" ", b"1 2", b"{} []", b"{", b"[1,]"] {
+            assert!(run(DataValue::from_reader_async(&arena, bad)).is_err());
+        }
+    }
+
+    #[test]
+    fn test_from_stream() {
+        let arena = Bump::new();
+        // Chunks split inside a string and inside a multi-byte character
+        let chunks = [&br#"{"name": "ca"#[..], b"f\xc3", b"\xa9\"}"];
+        let stream = Chunks(chunks.into_iter().map(Ok::<_, std::io::Error>));
+        let value = run(DataValue::from_stream(&arena, stream)).unwrap();
+        assert_eq!(value["name"].as_str(), Some("café"));
+
+        let failing = Chunks([Ok(&b"[1"[..]), Err("connection reset")].into_iter());
+        let error = run(DataValue::from_stream(&arena, failing)).unwrap_err();
+        assert!(error.to_string().contains("connection reset"));
+    }
+}
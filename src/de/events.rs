@@ -0,0 +1,941 @@
+//! Streaming event parser
+//!
+//! Reports a JSON document as a flat sequence of [`JsonEvent`]s instead of building
+//! a tree, SAX style. Memory use depends on the nesting depth and the largest
+//! single string, not on the size of the document, so multi-gigabyte inputs can be
+//! processed from any [`Read`] source. [`read_value`] materializes one value from
+//! the stream, which makes it easy to process a huge top-level array one element at
+//! a time, and [`value_events`] goes the other way, from a value to its events.
+//!
+//! # Example
+//!
+//! ```
+//! use datavalue_rs::de::events::{EventParser, JsonEvent};
+//!
+//! let mut keys = Vec::new();
+//! for event in EventParser::new(r#"{"a": 1, "b": [true, null]}"#) {
+//!     if let JsonEvent::Key(key) = event.unwrap() {
+//!         keys.push(key.into_owned());
+//!     }
+//! }
+//! assert_eq!(keys, ["a", "b"]);
+//! ```
+
+use std::borrow::Cow;
+use std::io::Read;
+
+use bumpalo::Bump;
+
+use super::{string_value, ObjectKeys, ParseOptions};
+use crate::datavalue::{DataValue, Number};
+use crate::error::{Error, Result};
+use crate::helpers::format_duration;
+
+/// One step of a JSON document
+#[derive(Debug, Clone)]
+pub enum JsonEvent<'s> {
+    /// `{`
+    StartObject,
+    /// `}`
+    EndObject,
+    /// `[`
+    StartArray,
+    /// `]`
+    EndArray,
+    /// An object key; the event for its value follows
+    Key(Cow<'s, str>),
+    /// A string value
+    String(Cow<'s, str>),
+    /// A number value
+    Number(Number<'static>),
+    /// `true` or `false`
+    Bool(bool),
+    /// `null`
+    Null,
+}
+
+impl JsonEvent<'_> {
+    /// Copies any borrowed key or string so the event no longer borrows its input
+    pub fn into_owned(self) -> JsonEvent<'static> {
+        match self {
+            JsonEvent::StartObject => JsonEvent::StartObject,
+            JsonEvent::EndObject => JsonEvent::EndObject,
+            JsonEvent::StartArray => JsonEvent::StartArray,
+            JsonEvent::EndArray => JsonEvent::EndArray,
+            JsonEvent::Key(key) => JsonEvent::Key(Cow::Owned(key.into_owned())),
+            JsonEvent::String(s) => JsonEvent::String(Cow::Owned(s.into_owned())),
+            JsonEvent::Number(n) => JsonEvent::Number(n),
+            JsonEvent::Bool(b) => JsonEvent::Bool(b),
+            JsonEvent::Null => JsonEvent::Null,
+        }
+    }
+}
+
+/// Iterator over the events of one JSON document
+///
+/// Created with [`EventParser::new`] for text in memory, whose events borrow
+/// strings without escapes from the input, or with [`EventParser::from_reader`]
+/// for any [`Read`] source. The document is validated as it is read: the iterator
+/// yields an `Err` at the first syntax error and then ends. Nesting depth is not
+/// limited, since the parser keeps its own stack.
+pub struct EventParser<I> {
+    input: I,
+    stack: Vec<Container>,
+    state: State,
+}
+
+/// Input for [`EventParser::new`]
+pub struct StrInput<'s> {
+    input: &'s str,
+    pos: usize,
+}
+
+/// Input for [`EventParser::from_reader`]
+pub struct ReadInput<R> {
+    reader: R,
+    buffer: Box<[u8]>,
+    pos: usize,
+    len: usize,
+    /// Bytes consumed before the current buffer
+    consumed: usize,
+}
+
+#[derive(Clone, Copy)]
+enum Container {
+    Object,
+    Array,
+}
+
+#[derive(Clone, Copy)]
+enum State {
+    /// Expecting the root value, an array element, or the value after a key
+    Value,
+    /// After `[`: expecting a value or `]`
+    FirstElement,
+    /// After `{`: expecting a key or `}`
+    FirstKey,
+    /// After `,` in an object
+    Key,
+    /// After a complete value
+    AfterValue,
+    /// After the root value; only whitespace may follow
+    Done,
+    /// An error was reported or the input ended
+    Finished,
+}
+
+impl<'s> EventParser<StrInput<'s>> {
+    /// Creates an event parser over text in memory
+    pub fn new(input: &'s str) -> Self {
+        EventParser::with_input(StrInput { input, pos: 0 })
+    }
+}
+
+impl<R: Read> EventParser<ReadInput<R>> {
+    /// Creates an event parser reading from `reader` through an internal buffer
+    ///
+    /// Strings in the resulting events are always owned.
+    pub fn from_reader(reader: R) -> Self {
+        EventParser::with_input(ReadInput {
+            reader,
+            buffer: vec![0; 64 * 1024].into_boxed_slice(),
+            pos: 0,
+            len: 0,
+            consumed: 0,
+        })
+    }
+}
+
+impl<I> EventParser<I> {
+    fn with_input(input: I) -> Self {
+        EventParser {
+            input,
+            stack: Vec::new(),
+            state: State::Value,
+        }
+    }
+
+    /// Returns the current nesting depth: the number of open objects and arrays
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    fn step<'s>(&mut self) -> Option<Result<JsonEvent<'s>>>
+    where
+        I: Input<'s>,
+    {
+        match self.advance() {
+            Ok(Some(event)) => Some(Ok(event)),
+            Ok(None) => {
+                self.state = State::Finished;
+                None
+            }
+            Err(e) => {
+                self.state = State::Finished;
+                Some(Err(e))
+            }
+        }
+    }
+
+    fn advance<'s>(&mut self) -> Result<Option<JsonEvent<'s>>>
+    where
+        I: Input<'s>,
+    {
+        loop {
+            match self.state {
+                State::Finished => return Ok(None),
+                State::Done => {
+                    self.skip_whitespace()?;
+                    return match self.input.peek()? {
+                        None => Ok(None),
+                        Some(_) => Err(self.error("trailing characters")),
+                    };
+                }
+                State::Value => return self.value().map(Some),
+                State::FirstElement => {
+                    self.skip_whitespace()?;
+                    if self.input.peek()? == Some(b']') {
+                        return self.close().map(Some);
+                    }
+                    return self.value().map(Some);
+                }
+                State::FirstKey => {
+                    self.skip_whitespace()?;
+                    if self.input.peek()? == Some(b'}') {
+                        return self.close().map(Some);
+                    }
+                    return self.key().map(Some);
+                }
+                State::Key => return self.key().map(Some),
+                State::AfterValue => {
+                    self.skip_whitespace()?;
+                    let Some(container) = self.stack.last().copied() else {
+                        self.state = State::Done;
+                        continue;
+                    };
+                    match (container, self.input.peek()?) {
+                        (Container::Array, Some(b',')) => {
+                            self.input.bump();
+                            self.state = State::Value;
+                        }
+                        (Container::Object, Some(b',')) => {
+                            self.input.bump();
+                            self.state = State::Key;
+                        }
+                        (Container::Array, Some(b']')) | (Container::Object, Some(b'}')) => {
+                            return self.close().map(Some)
+                        }
+                        (Container::Array, _) => return Err(self.error("expected ',' or ']'")),
+                        (Container::Object, _) => return Err(self.error("expected ',' or '}'")),
+                    }
+                }
+            }
+        }
+    }
+
+    fn value<'s>(&mut self) -> Result<JsonEvent<'s>>
+    where
+        I: Input<'s>,
+    {
+        self.skip_whitespace()?;
+        let event = match self.input.peek()? {
+            None => return Err(self.error("unexpected end of input")),
+            Some(b'{') => {
+                self.input.bump();
+                self.stack.push(Container::Object);
+                self.state = State::FirstKey;
+                return Ok(JsonEvent::StartObject);
+            }
+            Some(b'[') => {
+                self.input.bump();
+                self.stack.push(Container::Array);
+                self.state = State::FirstElement;
+                return Ok(JsonEvent::StartArray);
+            }
+            Some(b'"') => JsonEvent::String(self.input.string()?),
+            Some(b'-' | b'0'..=b'9' | b'a'..=b'z') => {
+                let start = self.input.offset();
+                let token = self.input.token()?;
+                match token.as_ref() {
+                    "null" => JsonEvent::Null,
+                    "true" => JsonEvent::Bool(true),
+                    "false" => JsonEvent::Bool(false),
+                    text => match number(text) {
+                        Some(n) => JsonEvent::Number(n),
+                        None => return Err(syntax("invalid value", start)),
+                    },
+                }
+            }
+            Some(_) => return Err(self.error("expected value")),
+        };
+        self.state = State::AfterValue;
+        Ok(event)
+    }
+
+    fn key<'s>(&mut self) -> Result<JsonEvent<'s>>
+    where
+        I: Input<'s>,
+    {
+        self.skip_whitespace()?;
+        if self.input.peek()? != Some(b'"') {
+            return Err(self.error("expected string key"));
+        }
+        let key = self.input.string()?;
+        self.skip_whitespace()?;
+        if self.input.peek()? != Some(b':') {
+            return Err(self.error("expected ':'"));
+        }
+        self.input.bump();
+        self.state = State::Value;
+        Ok(JsonEvent::Key(key))
+    }
+
+    fn close<'s>(&mut self) -> Result<JsonEvent<'s>>
+    where
+        I: Input<'s>,
+    {
+        self.input.bump();
+        self.state = State::AfterValue;
+        Ok(match self.stack.pop() {
+            Some(Container::Object) => JsonEvent::EndObject,
+            _ => JsonEvent::EndArray,
+        })
+    }
+
+    fn skip_whitespace<'s>(&mut self) -> Result<()>
+    where
+        I: Input<'s>,
+    {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.input.peek()? {
+            self.input.bump();
+        }
+        Ok(())
+    }
+
+    fn error<'s>(&self, msg: &str) -> Error
+    where
+        I: Input<'s>,
+    {
+        syntax(msg, self.input.offset())
+    }
+}
+
+impl<'s> Iterator for EventParser<StrInput<'s>> {
+    type Item = Result<JsonEvent<'s>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.step()
+    }
+}
+
+impl<R: Read> Iterator for EventParser<ReadInput<R>> {
+    type Item = Result<JsonEvent<'static>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.step()
+    }
+}
+
+/// Reads the next complete value from an event stream into the arena
+///
+/// Consumes the events of one value, so after a `StartArray` event, repeated calls
+/// return the elements of the array one at a time. Returns `Ok(None)` when the
+/// stream ends or the next event closes the enclosing container (the closing event
+/// is consumed).
+///
+/// String values are converted the same way as [`crate::from_str`] does, so ISO
+/// 8601 durations become Durations.
+///
+/// # Errors
+///
+/// Returns an error if the stream reports one, or if it yields a key where a value
+/// is expected.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::de::events::{read_value, EventParser, JsonEvent};
+/// use datavalue_rs::Bump;
+///
+/// let input = r#"[{"id": 1}, {"id": 2}, {"id": 3}]"#;
+/// let mut events = EventParser::from_reader(input.as_bytes());
+/// assert!(matches!(events.next(), Some(Ok(JsonEvent::StartArray))));
+///
+/// let mut ids = Vec::new();
+/// loop {
+///     // A fresh arena per element keeps memory flat
+///     let arena = Bump::new();
+///     match read_value(&arena, &mut events).unwrap() {
+///         Some(item) => ids.push(item["id"].as_i64().unwrap()),
+///         None => break,
+///     }
+/// }
+/// assert_eq!(ids, [1, 2, 3]);
+/// ```
+pub fn read_value<'a, 's, E>(arena: &'a Bump, events: &mut E) -> Result<Option<DataValue<'a>>>
+where
+    E: Iterator<Item = Result<JsonEvent<'s>>>,
+{
+    read_value_with_options(arena, events, &ParseOptions::default())
+}
+
+/// Reads the next complete value from an event stream with the given options
+///
+/// Options that shape values, such as the duplicate-key policy and temporal key
+/// patterns, apply as they do in [`crate::from_str_with_options`]; numbers keep the
+/// form the event stream reports them in.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::de::events::{read_value_with_options, EventParser};
+/// use datavalue_rs::{Bump, DuplicateKeyPolicy, ParseOptions};
+///
+/// let options = ParseOptions {
+///     duplicate_keys: DuplicateKeyPolicy::Error,
+///     ..ParseOptions::default()
+/// };
+/// let mut events = EventParser::from_reader(r#"{"a": 1, "a": 2}"#.as_bytes());
+/// assert!(read_value_with_options(&Bump::new(), &mut events, &options).is_err());
+/// ```
+pub fn read_value_with_options<'a, 's, E>(
+    arena: &'a Bump,
+    events: &mut E,
+    options: &ParseOptions,
+) -> Result<Option<DataValue<'a>>>
+where
+    E: Iterator<Item = Result<JsonEvent<'s>>>,
+{
+    enum Frame<'a> {
+        Array(Vec<DataValue<'a>>),
+        Object(
+            Vec<(&'a str, DataValue<'a>)>,
+            ObjectKeys<'a>,
+            Option<&'a str>,
+        ),
+    }
+
+    let mut stack: Vec<Frame<'a>> = Vec::new();
+    for event in events {
+        let key = match stack.last() {
+            Some(Frame::Object(_, _, key)) => *key,
+            _ => None,
+        };
+        let value = match event? {
+            JsonEvent::StartObject => {
+                let keys = ObjectKeys::new(options.duplicate_keys);
+                stack.push(Frame::Object(Vec::new(), keys, None));
+                continue;
+            }
+            JsonEvent::StartArray => {
+                stack.push(Frame::Array(Vec::new()));
+                continue;
+            }
+            JsonEvent::Key(k) => match stack.last_mut() {
+                Some(Frame::Object(_, _, key)) => {
+                    *key = Some(arena.alloc_str(&k));
+                    continue;
+                }
+                _ => return Err(Error::syntax("unexpected key in event stream")),
+            },
+            JsonEvent::EndObject | JsonEvent::EndArray => match stack.pop() {
+                None => return Ok(None),
+                Some(Frame::Array(items)) => DataValue::Array(arena.alloc_slice_clone(&items)),
+                Some(Frame::Object(entries, _, _)) => {
+                    DataValue::Object(arena.alloc_slice_clone(&entries))
+                }
+            },
+            JsonEvent::String(s) => string_value(arena, &s, None, key, options),
+            JsonEvent::Number(n) => DataValue::Number(n),
+            JsonEvent::Bool(b) => DataValue::Bool(b),
+            JsonEvent::Null => DataValue::Null,
+        };
+        match stack.last_mut() {
+            None => return Ok(Some(value)),
+            Some(Frame::Array(items)) => items.push(value),
+            Some(Frame::Object(entries, keys, key)) => match key.take() {
+                Some(key) => {
+                    if keys.add(entries, (key, value)).is_err() {
+                        return Err(Error::syntax(format!("duplicate key \"{}\"", key)));
+                    }
+                }
+                None => return Err(Error::syntax("missing key in event stream")),
+            },
+        }
+    }
+    if stack.is_empty() {
+        Ok(None)
+    } else {
+        Err(Error::syntax("unexpected end of event stream"))
+    }
+}
+
+/// Returns the events that describe `value`, in document order
+///
+/// Strings and keys borrow from the value. Numbers are reported as
+/// [`Number::parsed`] reports them, so raw and arbitrary-precision numbers
+/// become an integer or the nearest float. DateTime and Duration values become
+/// the strings they serialize to, and a [`DataValue::Raw`] is parsed first.
+///
+/// # Errors
+///
+/// Returns an error if a raw value inside `value` is not valid JSON.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::de::events::{read_value, value_events};
+/// use datavalue_rs::{from_str, Bump};
+///
+/// let arena = Bump::new();
+/// let value = from_str(&arena, r#"{"a": [1, "x"], "b": null}"#).unwrap();
+/// let events = value_events(&value).unwrap();
+/// assert_eq!(events.len(), 9);
+///
+/// let copy = read_value(&arena, &mut events.into_iter().map(Ok)).unwrap();
+/// assert_eq!(copy, Some(value));
+/// ```
+pub fn value_events<'v>(value: &'v DataValue<'_>) -> Result<Vec<JsonEvent<'v>>> {
+    enum Work<'v, 'a> {
+        Value(&'v DataValue<'a>),
+        Key(&'v str),
+        End(JsonEvent<'static>),
+    }
+
+    let mut events = Vec::new();
+    let mut work = vec![Work::Value(value)];
+    while let Some(item) = work.pop() {
+        let value = match item {
+            Work::Value(value) => value,
+            Work::Key(key) => {
+                events.push(JsonEvent::Key(Cow::Borrowed(key)));
+                continue;
+            }
+            Work::End(event) => {
+                events.push(event);
+                continue;
+            }
+        };
+        match value {
+            DataValue::Null => events.push(JsonEvent::Null),
+            DataValue::Bool(b) => events.push(JsonEvent::Bool(*b)),
+            DataValue::Number(n) => events.push(JsonEvent::Number(n.parsed())),
+            DataValue::String(s) => events.push(JsonEvent::String(Cow::Borrowed(s))),
+            DataValue::Array(items) => {
+                events.push(JsonEvent::StartArray);
+                work.push(Work::End(JsonEvent::EndArray));
+                work.extend(items.iter().rev().map(Work::Value));
+            }
+            DataValue::Object(entries) => {
+                events.push(JsonEvent::StartObject);
+                work.push(Work::End(JsonEvent::EndObject));
+                for (key, value) in entries.iter().rev() {
+                    work.push(Work::Value(value));
+                    work.push(Work::Key(key));
+                }
+            }
+            DataValue::DateTime(dt) => events.push(JsonEvent::String(Cow::Owned(dt.to_rfc3339()))),
+            DataValue::Duration(dur) => {
+                events.push(JsonEvent::String(Cow::Owned(format_duration(dur))))
+            }
+            DataValue::Raw(text) => {
+                let arena = Bump::new();
+                let parsed = DataValue::Raw(text).parse_raw(&arena)?;
+                for event in value_events(&parsed)? {
+                    events.push(event.into_owned());
+                }
+            }
+        }
+    }
+    Ok(events)
+}
+
+/// Byte source shared by the in-memory and reader-based parsers
+trait Input<'s> {
+    fn peek(&mut self) -> Result<Option<u8>>;
+    /// Consumes the byte returned by the last `peek`
+    fn bump(&mut self);
+    /// Byte offset of the next byte, for error messages
+    fn offset(&self) -> usize;
+    /// Reads a string literal, starting at its opening quote
+    fn string(&mut self) -> Result<Cow<'s, str>>;
+    /// Reads a run of bytes that may form a number or literal
+    fn token(&mut self) -> Result<Cow<'s, str>>;
+}
+
+fn is_token_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || matches!(b, b'-' | b'+' | b'.')
+}
+
+impl<'s> Input<'s> for StrInput<'s> {
+    fn peek(&mut self) -> Result<Option<u8>> {
+        Ok(self.input.as_bytes().get(self.pos).copied())
+    }
+
+    fn bump(&mut self) {
+        self.pos += 1;
+    }
+
+    fn offset(&self) -> usize {
+        self.pos
+    }
+
+    fn string(&mut self) -> Result<Cow<'s, str>> {
+        let start = self.pos;
+        let bytes = self.input.as_bytes();
+        let mut pos = start + 1;
+        let mut escaped = false;
+        loop {
+            match bytes.get(pos) {
+                None => return Err(syntax("unterminated string", start)),
+                Some(b'"') => break,
+                Some(b'\\') => {
+                    escaped = true;
+                    pos += 2;
+                }
+                Some(_) => pos += 1,
+            }
+        }
+        self.pos = pos + 1;
+        let raw = &self.input[start + 1..pos];
+        if escaped {
+            decode(raw.as_bytes(), start).map(Cow::Owned)
+        } else if raw.bytes().any(|b| b < 0x20) {
+            Err(syntax("control character in string", start))
+        } else {
+            Ok(Cow::Borrowed(raw))
+        }
+    }
+
+    fn token(&mut self) -> Result<Cow<'s, str>> {
+        let start = self.pos;
+        let bytes = self.input.as_bytes();
+        while bytes.get(self.pos).copied().is_some_and(is_token_byte) {
+            self.pos += 1;
+        }
+        Ok(Cow::Borrowed(&self.input[start..self.pos]))
+    }
+}
+
+impl<R: Read> Input<'static> for ReadInput<R> {
+    fn peek(&mut self) -> Result<Option<u8>> {
+        if self.pos == self.len {
+            self.consumed += self.len;
+            self.pos = 0;
+            self.len = loop {
+                match self.reader.read(&mut self.buffer) {
+                    Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                    result => break result?,
+                }
+            };
+            if self.len == 0 {
+                return Ok(None);
+            }
+        }
+        Ok(Some(self.buffer[self.pos]))
+    }
+
+    fn bump(&mut self) {
+        self.pos += 1;
+    }
+
+    fn offset(&self) -> usize {
+        self.consumed + self.pos
+    }
+
+    fn string(&mut self) -> Result<Cow<'static, str>> {
+        let start = self.offset();
+        self.bump();
+        let mut raw = Vec::new();
+        loop {
+            let Some(b) = self.peek()? else {
+                return Err(syntax("unterminated string", start));
+            };
+            self.bump();
+            match b {
+                b'"' => break,
+                b'\\' => {
+                    raw.push(b);
+                    let Some(next) = self.peek()? else {
+                        return Err(syntax("unterminated string", start));
+                    };
+                    self.bump();
+                    raw.push(next);
+                }
+                _ => raw.push(b),
+            }
+        }
+        decode(&raw, start).map(Cow::Owned)
+    }
+
+    fn token(&mut self) -> Result<Cow<'static, str>> {
+        let mut token = String::new();
+        while let Some(b) = self.peek()?.filter(|&b| is_token_byte(b)) {
+            token.push(b as char);
+            self.bump();
+        }
+        Ok(Cow::Owned(token))
+    }
+}
+
+/// Decodes the raw contents of a string literal (between the quotes)
+fn decode(raw: &[u8], start: usize) -> Result<String> {
+    let mut output = Vec::with_capacity(raw.len());
+    let mut i = 0;
+    while i < raw.len() {
+        let b = raw[i];
+        i += 1;
+        match b {
+            0x00..=0x1f => return Err(syntax("control character in string", start)),
+            b'\\' => {
+                let escaped = match raw.get(i) {
+                    Some(b'"') => '"',
+                    Some(b'\\') => '\\',
+                    Some(b'/') => '/',
+                    Some(b'b') => '\u{8}',
+                    Some(b'f') => '\u{c}',
+                    Some(b'n') => '\n',
+                    Some(b'r') => '\r',
+                    Some(b't') => '\t',
+                    Some(b'u') => {
+                        let (c, len) = unicode_escape(&raw[i + 1..])
+                            .ok_or_else(|| syntax("invalid unicode escape", start))?;
+                        i += len;
+                        c
+                    }
+                    _ => return Err(syntax("invalid escape", start)),
+                };
+                i += 1;
+                let mut utf8 = [0; 4];
+                output.extend_from_slice(escaped.encode_utf8(&mut utf8).as_bytes());
+            }
+            _ => output.push(b),
+        }
+    }
+    String::from_utf8(output).map_err(|_| syntax("invalid UTF-8 in string", start))
+}
+
+/// Decodes the hex digits after `\u`, combining surrogate pairs
+///
+/// Returns the character and the number of bytes consumed after the `u`.
+fn unicode_escape(raw: &[u8]) -> Option<(char, usize)> {
+    let hex4 = |digits: &[u8]| {
+        let digits = std::str::from_utf8(digits.get(..4)?).ok()?;
+        if !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return None;
+        }
+        u32::from_str_radix(digits, 16).ok()
+    };
+    let high = hex4(raw)?;
+    if !(0xD800..0xDC00).contains(&high) {
+        return char::from_u32(high).map(|c| (c, 4));
+    }
+    if raw.get(4..6) != Some(b"\\u") {
+        return None;
+    }
+    let low = hex4(&raw[6..])?;
+    if !(0xDC00..0xE000).contains(&low) {
+        return None;
+    }
+    char::from_u32(0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)).map(|c| (c, 10))
+}
+
+/// Parses a number token, enforcing the JSON number grammar
+fn number(text: &str) -> Option<Number<'static>> {
+    let bytes = text.as_bytes();
+    let mut i = usize::from(bytes.first() == Some(&b'-'));
+    let digits = |i: &mut usize| {
+        let start = *i;
+        while bytes.get(*i).is_some_and(u8::is_ascii_digit) {
+            *i += 1;
+        }
+        *i > start
+    };
+    match bytes.get(i) {
+        Some(b'0') => i += 1,
+        Some(b'1'..=b'9') => {
+            digits(&mut i);
+        }
+        _ => return None,
+    }
+    let mut is_float = false;
+    if bytes.get(i) == Some(&b'.') {
+        is_float = true;
+        i += 1;
+        if !digits(&mut i) {
+            return None;
+        }
+    }
+    if let Some(b'e' | b'E') = bytes.get(i) {
+        is_float = true;
+        i += 1;
+        if let Some(b'+' | b'-') = bytes.get(i) {
+            i += 1;
+        }
+        if !digits(&mut i) {
+            return None;
+        }
+    }
+    if i != bytes.len() {
+        return None;
+    }
+    if !is_float {
+        if let Ok(i) = text.parse::<i64>() {
+            return Some(Number::Integer(i));
+        }
+    }
+    text.parse::<f64>()
+        .ok()
+        .filter(|f| f.is_finite())
+        .map(Number::Float)
+}
+
+fn syntax(msg: &str, offset: usize) -> Error {
+    Error::syntax(format!("{} at byte {}", msg, offset))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn describe(event: &JsonEvent) -> String {
+        match event {
+            JsonEvent::StartObject => "{".to_string(),
+            JsonEvent::EndObject => "}".to_string(),
+            JsonEvent::StartArray => "[".to_string(),
+            JsonEvent::EndArray => "]".to_string(),
+            JsonEvent::Key(k) => format!("key:{}", k),
+            JsonEvent::String(s) => format!("str:{}", s),
+            JsonEvent::Number(Number::Integer(i)) => format!("int:{}", i),
+            JsonEvent::Number(Number::Float(f)) => format!("float:{}", f),
+            JsonEvent::Number(other) => format!("{:?}", other),
+            JsonEvent::Bool(b) => b.to_string(),
+            JsonEvent::Null => "null".to_string(),
+        }
+    }
+
+    fn collect<'s>(events: impl Iterator<Item = Result<JsonEvent<'s>>>) -> Result<Vec<String>> {
+        events.map(|e| e.map(|e| describe(&e))).collect()
+    }
+
+    #[test]
+    fn test_event_sequence() {
+        let input = r#" {"a": [1, -2.5, "x\u00e9\n"], "b": {}, "c": [], "d": null, "e": true} "#;
+        let expected = [
+            "{",
+            "key:a",
+            "[",
+            "int:1",
+            "float:-2.5",
+            "str:x\u{e9}\n",
+            "]",
+            "key:b",
+            "{",
+            "}",
+            "key:c",
+            "[",
+            "]",
+            "key:d",
+            "null",
+            "key:e",
+            "true",
+            "}",
+        ];
+        assert_eq!(collect(EventParser::new(input)).unwrap(), expected);
+        assert_eq!(
+            collect(EventParser::from_reader(input.as_bytes())).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_borrowing_and_small_reads() {
+        let input = r#"["plain", "esc\"aped", "\ud83d\ude00"]"#;
+        let events: Vec<_> = EventParser::new(input).collect::<Result<_>>().unwrap();
+        assert!(matches!(
+            &events[1],
+            JsonEvent::String(Cow::Borrowed("plain"))
+        ));
+        assert!(matches!(&events[2], JsonEvent::String(Cow::Owned(s)) if s == "esc\"aped"));
+        assert!(matches!(&events[3], JsonEvent::String(s) if s == "\u{1F600}"));
+
+        // A reader that returns one byte at a time exercises buffer refills
+        struct OneByte<'a>(&'a [u8]);
+        impl Read for OneByte<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                match self.0.split_first() {
+                    Some((&b, rest)) if !buf.is_empty() => {
+                        buf[0] = b;
+                        self.0 = rest;
+                        Ok(1)
+                    }
+                    _ => Ok(0),
+                }
+            }
+        }
+        assert_eq!(
+            collect(EventParser::from_reader(OneByte(input.as_bytes()))).unwrap(),
+            collect(EventParser::new(input)).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_errors_end_the_stream() {
+        for input in [
+            "",
+            "[1,]",
+            "[1 2]",
+            "{\"a\" 1}",
+            "{1: 2}",
+            "tru",
+            "01",
+            "1.",
+            "[\"\\x\"]",
+            "[",
+            "{}}",
+            "\"a\tb\"",
+            "[-]",
+            "nul",
+        ] {
+            assert!(collect(EventParser::new(input)).is_err(), "{}", input);
+            assert!(
+                collect(EventParser::from_reader(input.as_bytes())).is_err(),
+                "{}",
+                input
+            );
+        }
+        let mut events = EventParser::new("[1, ?]");
+        assert!(events.by_ref().any(|e| e.is_err()));
+        assert!(events.next().is_none());
+    }
+
+    #[test]
+    fn test_deep_nesting_and_read_value() {
+        let depth = 100_000;
+        let input = format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+        let mut events = EventParser::new(&input);
+        events.by_ref().take(depth).for_each(|e| drop(e.unwrap()));
+        assert_eq!(events.depth(), depth);
+        assert_eq!(events.count(), depth);
+
+        let arena = Bump::new();
+        let input = r#"{"items": [{"a": [1, {"b": "PT1H"}]}, 2]}"#;
+        let mut events = EventParser::new(input);
+        let value = read_value(&arena, &mut events).unwrap().unwrap();
+        assert_eq!(
+            crate::to_string(&value),
+            crate::to_string(&crate::from_str(&arena, input).unwrap())
+        );
+        assert!(read_value(&arena, &mut events).unwrap().is_none());
+
+        let options = ParseOptions {
+            duplicate_keys: crate::DuplicateKeyPolicy::FirstWins,
+            ..ParseOptions::default()
+        };
+        let mut events = EventParser::new(r#"[{"a": 1, "b": [2], "a": 3}]"#);
+        let value = read_value_with_options(&arena, &mut events, &options)
+            .unwrap()
+            .unwrap();
+        assert_eq!(crate::to_string(&value), r#"[{"a":1,"b":[2]}]"#);
+    }
+}
@@ -0,0 +1,249 @@
+//! Incremental parsing of input that arrives in chunks
+
+use bumpalo::Bump;
+
+use super::{from_str_with_options, ParseOptions};
+use crate::datavalue::DataValue;
+use crate::error::{Error, Result};
+
+/// Parser that is fed bytes as they arrive and yields each value once complete
+///
+/// Input may be one document or a stream of whitespace-separated documents, as
+/// with NDJSON. Chunks can be split anywhere, including inside a string or a
+/// multi-byte character. Bytes are buffered only until the value they belong to
+/// is complete, and each byte is scanned once, so feeding a document in many
+/// small chunks costs about as much as parsing it at once.
+///
+/// A number at the top level is only known to be complete once a delimiter or
+/// the end of the input follows it, so the last value may come from
+/// [`FeedParser::finish`]. Error locations are relative to the start of the
+/// value that failed to parse. Comments are not recognized, whatever
+/// [`ParseOptions::allow_comments`] says. After an error, the parser should be
+/// discarded.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::{de::FeedParser, Bump};
+///
+/// let arena = Bump::new();
+/// let mut parser = FeedParser::new(&arena);
+///
+/// assert!(parser.feed(br#"{"event": "lo"#).unwrap().is_empty());
+/// let values = parser.feed(b"gin\"}\n{\"event\": \"logout\"}\n4").unwrap();
+/// assert_eq!(values.len(), 2);
+/// assert_eq!(values[1]["event"].as_str(), Some("logout"));
+///
+/// assert_eq!(parser.finish().unwrap().and_then(|v| v.as_i64()), Some(4));
+/// ```
+pub struct FeedParser<'a> {
+    arena: &'a Bump,
+    options: ParseOptions,
+    /// Bytes of the value in progress, and possibly whitespace before it
+    buffer: Vec<u8>,
+    /// Number of buffered bytes already scanned
+    scanned: usize,
+    /// Offset in `buffer` where the value in progress starts
+    start: Option<usize>,
+    /// Number of arrays and objects open at the scan position
+    depth: usize,
+    in_string: bool,
+    /// Whether the previous byte in a string was an unescaped backslash
+    escaped: bool,
+    /// Whether a top-level number or literal is being scanned
+    in_token: bool,
+}
+
+impl<'a> FeedParser<'a> {
+    /// Creates a parser that allocates values in `arena`
+    pub fn new(arena: &'a Bump) -> Self {
+        Self::with_options(arena, ParseOptions::default())
+    }
+
+    /// Creates a parser that applies `options` to each value
+    ///
+    /// [`ParseOptions::max_depth`] is also checked while scanning, so an endless
+    /// run of opening brackets is rejected before it fills the buffer.
+    pub fn with_options(arena: &'a Bump, options: ParseOptions) -> Self {
+        FeedParser {
+            arena,
+            options,
+            buffer: Vec::new(),
+            scanned: 0,
+            start: None,
+            depth: 0,
+            in_string: false,
+            escaped: false,
+            in_token: false,
+        }
+    }
+
+    /// Adds the next chunk of input, returning the values it completes in order
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a completed value is not valid JSON or UTF-8, if a
+    /// closing bracket has no opening one, or if nesting exceeds the depth limit.
+    pub fn feed(&mut self, chunk: &[u8]) -> Result<Vec<DataValue<'a>>> {
+        self.buffer.extend_from_slice(chunk);
+        let mut values = Vec::new();
+        let mut i = self.scanned;
+        while i < self.buffer.len() {
+            let b = self.buffer[i];
+            i += 1;
+            if self.in_string {
+                if self.escaped {
+                    self.escaped = false;
+                } else if b == b'\\' {
+                    self.escaped = true;
+                } else if b == b'"' {
+                    self.in_string = false;
+                    if self.depth == 0 {
+                        values.push(self.complete(i)?);
+                        i = 0;
+                    }
+                }
+                continue;
+            }
+            if self.in_token {
+                if !is_delimiter(b) {
+                    continue;
+                }
+                // The delimiter belongs to whatever follows the token
+                i -= 1;
+                self.in_token = false;
+                values.push(self.complete(i)?);
+                i = 0;
+                continue;
+            }
+            match b {
+                b' ' | b'\t' | b'\n' | b'\r' => {}
+                b'"' => {
+                    self.start.get_or_insert(i - 1);
+                    self.in_string = true;
+                }
+                b'[' | b'{' => {
+                    self.start.get_or_insert(i - 1);
+                    self.depth += 1;
+                    if self.options.max_depth.is_some_and(|max| self.depth > max) {
+                        return Err(Error::syntax("nesting too deep"));
+                    }
+                }
+                b']' | b'}' => {
+                    if self.depth == 0 {
+                        return Err(Error::syntax(format!(
+                            "unexpected '{}' outside of a value",
+                            b as char
+                        )));
+                    }
+                    self.depth -= 1;
+                    if self.depth == 0 {
+                        values.push(self.complete(i)?);
+                        i = 0;
+                    }
+                }
+                _ if self.depth == 0 => {
+                    self.start = Some(i - 1);
+                    self.in_token = true;
+                }
+                _ => {}
+            }
+        }
+        if self.start.is_none() {
+            self.buffer.clear();
+            i = 0;
+        }
+        self.scanned = i;
+        Ok(values)
+    }
+
+    /// Signals the end of the input, returning the last value if one is pending
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the input ends inside a value.
+    pub fn finish(mut self) -> Result<Option<DataValue<'a>>> {
+        match self.start {
+            Some(_) => self.complete(self.buffer.len()).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Parses the value that ends at `end` and drops its bytes from the buffer
+    fn complete(&mut self, end: usize) -> Result<DataValue<'a>> {
+        let start = self.start.take().unwrap_or(0);
+        let text = std::str::from_utf8(&self.buffer[start..end])
+            .map_err(|e| Error::syntax(format!("Invalid UTF-8: {}", e)))?;
+        let value = from_str_with_options(self.arena, text, &self.options)?;
+        self.buffer.drain(..end);
+        self.scanned = 0;
+        Ok(value)
+    }
+}
+
+/// Returns true for bytes that end a number or literal
+fn is_delimiter(b: u8) -> bool {
+    matches!(
+        b,
+        b' ' | b'\t' | b'\n' | b'\r' | b'"' | b'[' | b']' | b'{' | b'}' | b','
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_byte_at_a_time() {
+        let input = "{\"a\": [1, \"x]\\\"}\", {}], \"caf\u{e9}\": null}\n\"s\" [2]\ttrue -1.5e3 {}";
+        let arena = Bump::new();
+        let mut parser = FeedParser::new(&arena);
+        let mut values = Vec::new();
+        for byte in input.as_bytes() {
+            values.extend(parser.feed(std::slice::from_ref(byte)).unwrap());
+            assert!(parser.buffer.len() <= 40);
+        }
+        values.extend(parser.finish().unwrap());
+        let texts: Vec<String> = values.iter().map(crate::to_string).collect();
+        assert_eq!(
+            texts,
+            [
+                "{\"a\":[1,\"x]\\\"}\",{}],\"caf\u{e9}\":null}",
+                "\"s\"",
+                "[2]",
+                "true",
+                "-1500.0",
+                "{}"
+            ]
+        );
+
+        let mut whole = FeedParser::new(&arena);
+        let mut all = whole.feed(input.as_bytes()).unwrap();
+        all.extend(whole.finish().unwrap());
+        assert_eq!(all, values);
+    }
+
+    #[test]
+    fn test_errors() {
+        let arena = Bump::new();
+        let mut parser = FeedParser::new(&arena);
+        assert!(parser.feed(b"[1, 2").unwrap().is_empty());
+        let error = parser.finish().unwrap_err();
+        assert!(error.to_string().contains("line 1 column 6"), "{}", error);
+
+        assert!(FeedParser::new(&arena).feed(b"[1,]").is_err());
+        assert!(FeedParser::new(&arena).feed(b"1 ]").is_err());
+        assert!(FeedParser::new(&arena).feed(b"tru ").is_err());
+        assert!(FeedParser::new(&arena).finish().unwrap().is_none());
+        assert!(FeedParser::new(&arena).feed(b"\"\xff\"").is_err());
+
+        let options = ParseOptions {
+            max_depth: Some(2),
+            ..ParseOptions::default()
+        };
+        let mut parser = FeedParser::with_options(&arena, options);
+        assert_eq!(parser.feed(b"[[1]] ").unwrap().len(), 1);
+        let error = parser.feed(b"[[[").unwrap_err();
+        assert!(error.to_string().contains("nesting too deep"), "{}", error);
+    }
+}
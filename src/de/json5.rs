@@ -0,0 +1,578 @@
+//! JSON5 input, enabled by the `json5` feature
+//!
+//! [JSON5](https://spec.json5.org/) is a superset of JSON that is popular for
+//! hand-written configuration files. On top of JSON it accepts:
+//!
+//! * `//` line comments and `/* */` block comments
+//! * object keys written as ECMAScript identifiers, without quotes
+//! * single-quoted strings, and the escapes `\'`, `\v`, `\0`, `\xHH`, escaped line
+//!   breaks and escaped ordinary characters
+//! * a trailing comma after the last array element or object entry
+//! * hexadecimal integers, a leading or trailing decimal point, an explicit `+`
+//!   sign, and `Infinity` and `NaN`
+//! * additional whitespace, such as the byte order mark and non-breaking spaces
+//!
+//! The result is an ordinary [`DataValue`]; once parsed, nothing distinguishes it
+//! from a value parsed from JSON.
+
+use bumpalo::Bump;
+
+use super::{string_value, Lexer, ObjectKeys, ParseOptions};
+use crate::datavalue::{DataValue, Number};
+use crate::error::Result;
+
+/// Parse a JSON5 string into a DataValue
+///
+/// # Arguments
+///
+/// * `arena` - The arena allocator to store strings, arrays, and objects
+/// * `s` - The JSON5 text to parse
+///
+/// # Returns
+///
+/// Result containing the parsed DataValue, or a syntax error with the line and
+/// column of the problem
+///
+/// # Example
+///
+/// ```
+/// # use datavalue_rs::{Bump, from_str_json5};
+/// let arena = Bump::new();
+/// let config = from_str_json5(&arena, "
+///     // Service settings
+///     {
+///         name: 'api',
+///         port: 0x1F90,
+///         ratio: .5,
+///         hosts: ['a', 'b',],
+///     }
+/// ").unwrap();
+///
+/// assert_eq!(config["name"].as_str(), Some("api"));
+/// assert_eq!(config["port"].as_i64(), Some(8080));
+/// assert_eq!(config["ratio"].as_f64(), Some(0.5));
+/// assert_eq!(config["hosts"][1].as_str(), Some("b"));
+/// ```
+pub fn from_str_json5<'a>(arena: &'a Bump, s: &str) -> Result<DataValue<'a>> {
+    from_str_json5_with_options(arena, s, &ParseOptions::default())
+}
+
+/// Parse a JSON5 string into a DataValue with the given options
+///
+/// The options that shape values apply as they do to JSON, including the
+/// duplicate-key policy and temporal key patterns. Comments and trailing commas
+/// are always accepted, as JSON5 allows them.
+///
+/// # Example
+///
+/// ```
+/// # use datavalue_rs::{Bump, DuplicateKeyPolicy, ParseOptions};
+/// # use datavalue_rs::de::json5::from_str_json5_with_options;
+/// let arena = Bump::new();
+/// let options = ParseOptions {
+///     duplicate_keys: DuplicateKeyPolicy::LastWins,
+///     ..ParseOptions::default()
+/// };
+/// let value = from_str_json5_with_options(&arena, "{a: 1, b: 2, a: 3}", &options).unwrap();
+/// assert_eq!(datavalue_rs::to_string(&value), r#"{"a":3,"b":2}"#);
+/// ```
+pub fn from_str_json5_with_options<'a>(
+    arena: &'a Bump,
+    s: &str,
+    options: &ParseOptions,
+) -> Result<DataValue<'a>> {
+    let mut parser = Json5Parser {
+        arena,
+        lexer: Lexer::new(s),
+        options,
+    };
+    let value = parser.parse_value(None)?;
+    parser.skip_insignificant()?;
+    if parser.lexer.pos < parser.lexer.bytes.len() {
+        return Err(parser.lexer.error("trailing characters"));
+    }
+    Ok(value)
+}
+
+/// JSON5 parser on top of the JSON lexer
+struct Json5Parser<'a, 's, 'o> {
+    arena: &'a Bump,
+    lexer: Lexer<'s>,
+    options: &'o ParseOptions,
+}
+
+impl<'a> Json5Parser<'a, '_, '_> {
+    /// Returns the character at the current position
+    fn peek_char(&self) -> Option<char> {
+        self.lexer.input[self.lexer.pos..].chars().next()
+    }
+
+    /// Skips whitespace and comments
+    fn skip_insignificant(&mut self) -> Result<()> {
+        loop {
+            match self.lexer.peek() {
+                Some(b' ' | b'\t' | b'\n' | b'\r' | 0x0b | 0x0c) => self.lexer.pos += 1,
+                Some(b'/') => match self.lexer.bytes.get(self.lexer.pos + 1) {
+                    Some(b'/') => {
+                        while self.peek_char().is_some_and(|c| !is_line_terminator(c)) {
+                            self.lexer.pos += self.peek_char().map_or(1, char::len_utf8);
+                        }
+                    }
+                    Some(b'*') => {
+                        let start = self.lexer.pos;
+                        match self.lexer.input[start + 2..].find("*/") {
+                            Some(end) => self.lexer.pos = start + 2 + end + 2,
+                            None => return Err(self.lexer.error("unterminated comment")),
+                        }
+                    }
+                    _ => return Ok(()),
+                },
+                Some(0x80..) => match self.peek_char() {
+                    Some(c) if c.is_whitespace() || c == '\u{feff}' => {
+                        self.lexer.pos += c.len_utf8()
+                    }
+                    _ => return Ok(()),
+                },
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    /// Parses one value; `key` is the object key the value belongs to, if any
+    ///
+    /// Open arrays and objects are kept on an explicit stack rather than the
+    /// thread stack.
+    fn parse_value(&mut self, key: Option<&str>) -> Result<DataValue<'a>> {
+        /// An array or object whose closing bracket has not been reached yet
+        enum Frame<'a> {
+            Array(Vec<DataValue<'a>>),
+            /// The entries so far, their keys and the key of the value being parsed
+            /// with its offset
+            Object(
+                Vec<(&'a str, DataValue<'a>)>,
+                ObjectKeys<'a>,
+                (&'a str, usize),
+            ),
+        }
+
+        let mut stack: Vec<Frame<'a>> = Vec::new();
+        loop {
+            self.skip_insignificant()?;
+            let value_key = match stack.last() {
+                Some(Frame::Object(_, _, (key, _))) => Some(*key),
+                Some(Frame::Array(_)) => None,
+                None => key,
+            };
+            let mut value = match self.lexer.peek() {
+                None => return Err(self.lexer.error("unexpected end of input")),
+                Some(b'{') => {
+                    self.lexer.pos += 1;
+                    match self.parse_entry_key()? {
+                        Some(key) => {
+                            let keys = ObjectKeys::new(self.options.duplicate_keys);
+                            stack.push(Frame::Object(Vec::new(), keys, key));
+                            continue;
+                        }
+                        None => DataValue::Object(&[]),
+                    }
+                }
+                Some(b'[') => {
+                    self.lexer.pos += 1;
+                    self.skip_insignificant()?;
+                    if self.lexer.peek() == Some(b']') {
+                        self.lexer.pos += 1;
+                        DataValue::Array(&[])
+                    } else {
+                        stack.push(Frame::Array(Vec::new()));
+                        continue;
+                    }
+                }
+                Some(quote @ (b'"' | b'\'')) => {
+                    let s = self.parse_string(quote)?;
+                    string_value(self.arena, &s, None, value_key, self.options)
+                }
+                Some(b'n') => self.lexer.expect_literal("null").map(|_| DataValue::Null)?,
+                Some(b't') => self
+                    .lexer
+                    .expect_literal("true")
+                    .map(|_| DataValue::Bool(true))?,
+                Some(b'f') => self
+                    .lexer
+                    .expect_literal("false")
+                    .map(|_| DataValue::Bool(false))?,
+                Some(b'+' | b'-' | b'.' | b'0'..=b'9' | b'I' | b'N') => self.parse_number()?,
+                Some(_) => return Err(self.lexer.error("expected value")),
+            };
+
+            // Add the value to its container, then close every container that ends
+            // after it
+            loop {
+                let close = match stack.last_mut() {
+                    None => return Ok(value),
+                    Some(Frame::Array(items)) => {
+                        items.push(value);
+                        b']'
+                    }
+                    Some(Frame::Object(entries, keys, (key, key_pos))) => {
+                        if keys.add(entries, (key, value)).is_err() {
+                            self.lexer.pos = *key_pos;
+                            return Err(self.lexer.error(&format!("duplicate key \"{}\"", key)));
+                        }
+                        b'}'
+                    }
+                };
+                self.skip_insignificant()?;
+                match self.lexer.peek() {
+                    Some(b',') => {
+                        self.lexer.pos += 1;
+                        // A trailing comma may be followed by the closing bracket
+                        let more = match stack.last_mut() {
+                            Some(Frame::Object(_, _, key)) => match self.parse_entry_key()? {
+                                Some(next) => {
+                                    *key = next;
+                                    true
+                                }
+                                None => false,
+                            },
+                            _ => {
+                                self.skip_insignificant()?;
+                                let closed = self.lexer.peek() == Some(b']');
+                                if closed {
+                                    self.lexer.pos += 1;
+                                }
+                                !closed
+                            }
+                        };
+                        if more {
+                            break;
+                        }
+                    }
+                    Some(b) if b == close => self.lexer.pos += 1,
+                    _ if close == b']' => return Err(self.lexer.error("expected ',' or ']'")),
+                    _ => return Err(self.lexer.error("expected ',' or '}'")),
+                }
+                value = match stack.pop() {
+                    Some(Frame::Array(items)) => {
+                        DataValue::Array(self.arena.alloc_slice_clone(&items))
+                    }
+                    Some(Frame::Object(entries, _, _)) => {
+                        DataValue::Object(self.arena.alloc_slice_clone(&entries))
+                    }
+                    None => unreachable!("a container was just closed"),
+                };
+            }
+        }
+    }
+
+    /// Parses the next object key and the colon after it, returning the key and its
+    /// offset, or consumes the closing brace and returns None
+    fn parse_entry_key(&mut self) -> Result<Option<(&'a str, usize)>> {
+        self.skip_insignificant()?;
+        if self.lexer.peek() == Some(b'}') {
+            self.lexer.pos += 1;
+            return Ok(None);
+        }
+        let key_pos = self.lexer.pos;
+        let key = match self.lexer.peek() {
+            Some(quote @ (b'"' | b'\'')) => self.parse_string(quote)?,
+            _ => self.parse_identifier()?,
+        };
+        let key: &'a str = self.arena.alloc_str(&key);
+        self.skip_insignificant()?;
+        if self.lexer.peek() != Some(b':') {
+            return Err(self.lexer.error("expected ':'"));
+        }
+        self.lexer.pos += 1;
+        Ok(Some((key, key_pos)))
+    }
+
+    /// Parses an unquoted object key
+    fn parse_identifier(&mut self) -> Result<String> {
+        let mut name = String::new();
+        loop {
+            let c = match self.peek_char() {
+                Some('\\') => {
+                    if self.lexer.bytes.get(self.lexer.pos + 1) != Some(&b'u') {
+                        return Err(self.lexer.error("invalid escape"));
+                    }
+                    self.lexer.pos += 2;
+                    let c = self.lexer.parse_unicode_escape()?;
+                    if !is_identifier_char(c, name.is_empty()) {
+                        return Err(self.lexer.error("invalid character in identifier"));
+                    }
+                    name.push(c);
+                    continue;
+                }
+                Some(c) if is_identifier_char(c, name.is_empty()) => c,
+                _ => break,
+            };
+            name.push(c);
+            self.lexer.pos += c.len_utf8();
+        }
+        if name.is_empty() {
+            return Err(self.lexer.error("expected key"));
+        }
+        Ok(name)
+    }
+
+    /// Parses a string delimited by `quote`, which is `"` or `'`
+    fn parse_string(&mut self, quote: u8) -> Result<String> {
+        self.lexer.pos += 1;
+        let mut output = String::new();
+        loop {
+            let Some(c) = self.peek_char() else {
+                return Err(self.lexer.error("unterminated string"));
+            };
+            if c as u32 == quote as u32 {
+                self.lexer.pos += 1;
+                return Ok(output);
+            }
+            match c {
+                '\\' => {
+                    self.lexer.pos += 1;
+                    self.parse_escape(&mut output)?;
+                }
+                '\n' | '\r' => return Err(self.lexer.error("line break in string")),
+                c => {
+                    output.push(c);
+                    self.lexer.pos += c.len_utf8();
+                }
+            }
+        }
+    }
+
+    /// Decodes the escape sequence after a backslash, appending it to `output`
+    fn parse_escape(&mut self, output: &mut String) -> Result<()> {
+        let Some(c) = self.peek_char() else {
+            return Err(self.lexer.error("unterminated string"));
+        };
+        self.lexer.pos += c.len_utf8();
+        let decoded = match c {
+            'b' => '\u{8}',
+            'f' => '\u{c}',
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            'v' => '\u{b}',
+            '0' if !self.lexer.peek().is_some_and(|b| b.is_ascii_digit()) => '\0',
+            'x' => {
+                let digits = self
+                    .lexer
+                    .input
+                    .get(self.lexer.pos..self.lexer.pos + 2)
+                    .filter(|d| d.bytes().all(|b| b.is_ascii_hexdigit()))
+                    .ok_or_else(|| self.lexer.error("invalid hex escape"))?;
+                self.lexer.pos += 2;
+                char::from(u8::from_str_radix(digits, 16).unwrap_or_default())
+            }
+            'u' => self.lexer.parse_unicode_escape()?,
+            // Line continuation: the escaped line break is dropped
+            '\r' => {
+                if self.lexer.peek() == Some(b'\n') {
+                    self.lexer.pos += 1;
+                }
+                return Ok(());
+            }
+            c if is_line_terminator(c) => return Ok(()),
+            '0'..='9' => {
+                self.lexer.pos -= 1;
+                return Err(self.lexer.error("invalid escape"));
+            }
+            c => c,
+        };
+        output.push(decoded);
+        Ok(())
+    }
+
+    fn parse_number(&mut self) -> Result<DataValue<'a>> {
+        let start = self.lexer.pos;
+        let negative = self.lexer.peek() == Some(b'-');
+        if let Some(b'+' | b'-') = self.lexer.peek() {
+            self.lexer.pos += 1;
+        }
+        let rest = &self.lexer.input[self.lexer.pos..];
+        let number = if rest.starts_with("Infinity") {
+            self.lexer.pos += "Infinity".len();
+            Number::Float(if negative {
+                f64::NEG_INFINITY
+            } else {
+                f64::INFINITY
+            })
+        } else if rest.starts_with("NaN") {
+            self.lexer.pos += "NaN".len();
+            Number::Float(f64::NAN)
+        } else if rest.starts_with("0x") || rest.starts_with("0X") {
+            self.lexer.pos += 2;
+            let digits_start = self.lexer.pos;
+            while self.lexer.peek().is_some_and(|b| b.is_ascii_hexdigit()) {
+                self.lexer.pos += 1;
+            }
+            let digits = &self.lexer.input[digits_start..self.lexer.pos];
+            if digits.is_empty() {
+                return Err(self.lexer.error("invalid number"));
+            }
+            let magnitude = u128::from_str_radix(digits, 16).ok();
+            let signed = magnitude.and_then(|m| {
+                let m = i128::try_from(m).ok()?;
+                i64::try_from(if negative { -m } else { m }).ok()
+            });
+            match signed {
+                Some(i) => Number::Integer(i),
+                None => {
+                    let f = digits.bytes().fold(0.0, |acc, b| {
+                        acc * 16.0 + f64::from((b as char).to_digit(16).unwrap_or(0))
+                    });
+                    Number::Float(if negative { -f } else { f })
+                }
+            }
+        } else {
+            self.scan_decimal(start)?
+        };
+        // A number must not run into an identifier, as in `1x` or `Infinityx`
+        if self
+            .peek_char()
+            .is_some_and(|c| is_identifier_char(c, false))
+        {
+            self.lexer.pos = start;
+            return Err(self.lexer.error("invalid number"));
+        }
+        Ok(DataValue::Number(self.options.number(number)))
+    }
+
+    /// Scans a decimal number whose sign, if any, starts at `sign_start`
+    fn scan_decimal(&mut self, sign_start: usize) -> Result<Number<'static>> {
+        let start = self.lexer.pos;
+        let mut is_float = false;
+        match self.lexer.peek() {
+            Some(b'0') => self.lexer.pos += 1,
+            Some(b'1'..=b'9') => self.lexer.skip_digits(),
+            Some(b'.') => {}
+            _ => return Err(self.lexer.error("invalid number")),
+        }
+        let has_integer_part = self.lexer.pos > start;
+        if self.lexer.peek() == Some(b'.') {
+            is_float = true;
+            self.lexer.pos += 1;
+            let fraction_start = self.lexer.pos;
+            self.lexer.skip_digits();
+            if !has_integer_part && self.lexer.pos == fraction_start {
+                return Err(self.lexer.error("invalid number"));
+            }
+        }
+        if let Some(b'e' | b'E') = self.lexer.peek() {
+            is_float = true;
+            self.lexer.pos += 1;
+            if let Some(b'+' | b'-') = self.lexer.peek() {
+                self.lexer.pos += 1;
+            }
+            if !self.lexer.peek().is_some_and(|b| b.is_ascii_digit()) {
+                return Err(self.lexer.error("invalid number"));
+            }
+            self.lexer.skip_digits();
+        }
+        // Rust's parsers accept the sign, a leading `+` and a bare leading or
+        // trailing decimal point
+        let text = &self.lexer.input[sign_start..self.lexer.pos];
+        if !is_float {
+            if let Ok(i) = text.parse::<i64>() {
+                return Ok(Number::Integer(i));
+            }
+        }
+        text.parse::<f64>()
+            .map(Number::Float)
+            .map_err(|_| self.lexer.error("invalid number"))
+    }
+}
+
+fn is_line_terminator(c: char) -> bool {
+    matches!(c, '\n' | '\r' | '\u{2028}' | '\u{2029}')
+}
+
+/// Returns true if `c` may appear in an unquoted key, at the start if `first`
+fn is_identifier_char(c: char, first: bool) -> bool {
+    match c {
+        '$' | '_' => true,
+        '\u{200c}' | '\u{200d}' => !first,
+        c if first => c.is_alphabetic(),
+        c => c.is_alphanumeric(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json5_syntax() {
+        let arena = Bump::new();
+        let input = "\u{feff}/* header */ {
+            unquoted: 'single \\' quote',
+            $id_2: \"double\",
+            'quoted key': [1, 2, 3,],
+            hex: -0xff,
+            big: 0x10000000000000000,
+            half: .5, whole: 5., plus: +7, exp: 1e3,
+            special: [Infinity, -Infinity, NaN],
+            escapes: '\\x41\\v\\0\\u00e9\\d\\\n tail',
+            nested: {a: null, b: true, c: false,}, // trailing
+        }";
+        let value = from_str_json5(&arena, input).unwrap();
+        assert_eq!(value["unquoted"].as_str(), Some("single ' quote"));
+        assert_eq!(value["$id_2"].as_str(), Some("double"));
+        assert_eq!(value["quoted key"].as_array().map(|a| a.len()), Some(3));
+        assert_eq!(value["hex"].as_i64(), Some(-255));
+        assert_eq!(value["big"].as_f64(), Some(18446744073709551616.0));
+        assert_eq!(value["half"].as_f64(), Some(0.5));
+        assert_eq!(value["whole"].as_f64(), Some(5.0));
+        assert_eq!(value["plus"].as_i64(), Some(7));
+        assert_eq!(value["exp"].as_f64(), Some(1000.0));
+        assert_eq!(value["special"][1].as_f64(), Some(f64::NEG_INFINITY));
+        assert!(value["special"][2].as_f64().unwrap().is_nan());
+        assert_eq!(value["escapes"].as_str(), Some("A\u{b}\0\u{e9}d tail"));
+        assert_eq!(value["nested"]["b"].as_bool(), Some(true));
+
+        // Plain JSON parses the same way
+        let json = r#"{"a": [1, -2.5, "x\n", {"b": null}]}"#;
+        assert_eq!(
+            from_str_json5(&arena, json).unwrap(),
+            crate::from_str(&arena, json).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_json5_errors() {
+        let arena = Bump::new();
+        let inputs = [
+            "[1,,]",
+            "[,]",
+            "{a: 1,,}",
+            "{1a: 1}",
+            "{'a' 1}",
+            "'unterminated",
+            "'line\nbreak'",
+            "/* open",
+            "01",
+            "0x",
+            "1x",
+            ".",
+            "Infinityx",
+            "'\\1'",
+            "'\\xZZ'",
+            "[1] 2",
+            "undefined",
+        ];
+        for input in inputs {
+            assert!(from_str_json5(&arena, input).is_err(), "{}", input);
+        }
+        let error = from_str_json5(&arena, "{\n  a: 1\n  b: 2\n}").unwrap_err();
+        assert!(error.to_string().contains("line 3 column 3"), "{}", error);
+
+        let options = ParseOptions {
+            duplicate_keys: crate::DuplicateKeyPolicy::Error,
+            ..ParseOptions::default()
+        };
+        let input = "{a: {b: 1,\n 'b': 2}}";
+        let error = from_str_json5_with_options(&arena, input, &options).unwrap_err();
+        assert!(error.to_string().contains("line 2 column 2"), "{}", error);
+    }
+}
@@ -0,0 +1,569 @@
+//! Best-effort parsing of malformed JSON
+//!
+//! [`from_str_lenient`] never fails. It repairs the mistakes commonly found in
+//! hand-edited files, truncated logs and half-typed editor buffers, and reports
+//! every repair as a [`Diagnostic`] with its location:
+//!
+//! * trailing commas, missing commas and missing colons
+//! * arrays and objects left open at the end of the input, or closed with the
+//!   wrong bracket
+//! * invalid escapes, unpaired surrogates, control characters and unterminated
+//!   strings
+//! * unquoted object keys, comments, and stray characters where a value belongs
+//! * anything after the first complete value
+//!
+//! Values that cannot be recovered, such as `tru` or `1.`, become `null`.
+
+use std::fmt;
+
+use bumpalo::Bump;
+
+use super::{string_value, Lexer, ParseOptions};
+use crate::datavalue::{DataValue, Number};
+use crate::error::{Error, Location};
+
+/// A problem that [`from_str_lenient`] repaired
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// Description of the problem
+    pub message: String,
+    /// Where the problem was found
+    pub location: Location,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at line {} column {}",
+            self.message, self.location.line, self.location.column
+        )
+    }
+}
+
+/// Parse a JSON string, repairing common errors instead of failing
+///
+/// # Returns
+///
+/// The best-effort value and the list of repairs in the order they were made.
+/// The list is empty exactly when the input is valid JSON, in which case the
+/// value is the one [`crate::from_str`] returns.
+///
+/// # Example
+///
+/// ```
+/// # use datavalue_rs::{Bump, from_str_lenient, to_string};
+/// let arena = Bump::new();
+/// let (value, diagnostics) = from_str_lenient(&arena, r#"{"level": "warn", "tags": ["a", "b",], "msg": "bad \q"#);
+///
+/// assert_eq!(to_string(&value), r#"{"level":"warn","tags":["a","b"],"msg":"bad q"}"#);
+/// let messages: Vec<String> = diagnostics.iter().map(|d| d.to_string()).collect();
+/// assert_eq!(messages, [
+///     "trailing comma at line 1 column 36",
+///     "invalid escape at line 1 column 52",
+///     "unterminated string at line 1 column 47",
+///     "missing '}' at line 1 column 54",
+/// ]);
+/// ```
+pub fn from_str_lenient<'a>(arena: &'a Bump, s: &str) -> (DataValue<'a>, Vec<Diagnostic>) {
+    let options = ParseOptions::default();
+    let mut parser = LenientParser {
+        arena,
+        lexer: Lexer::new(s),
+        options: &options,
+        diagnostics: Vec::new(),
+    };
+    let value = parser.parse_value();
+    parser.skip_insignificant();
+    if parser.lexer.pos < parser.lexer.bytes.len() {
+        parser.report(parser.lexer.pos, "trailing characters");
+    }
+    (value, parser.diagnostics)
+}
+
+struct LenientParser<'a, 's, 'o> {
+    arena: &'a Bump,
+    lexer: Lexer<'s>,
+    options: &'o ParseOptions,
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// An array or object whose closing bracket has not been reached yet
+enum Frame<'a> {
+    Array(Vec<DataValue<'a>>),
+    /// The entries so far and the key of the value being parsed
+    Object(Vec<(&'a str, DataValue<'a>)>, &'a str),
+}
+
+/// What follows an opening brace or a comma in an object
+enum EntryKey<'a> {
+    Key(&'a str),
+    Close,
+    End,
+}
+
+impl<'a> LenientParser<'a, '_, '_> {
+    /// Records a diagnostic at byte offset `pos`
+    fn report(&mut self, pos: usize, msg: &str) {
+        let resume = self.lexer.pos;
+        self.lexer.pos = pos;
+        if let Error::SyntaxAt { msg, location } = self.lexer.error(msg) {
+            self.diagnostics.push(Diagnostic {
+                message: msg,
+                location: *location,
+            });
+        }
+        self.lexer.pos = resume;
+    }
+
+    /// Skips whitespace, reporting and skipping comments as well
+    fn skip_insignificant(&mut self) {
+        loop {
+            self.lexer.skip_whitespace();
+            let start = self.lexer.pos;
+            if self.lexer.skip_comment() {
+                self.report(start, "comment");
+            } else if self.lexer.input[start..].starts_with("/*") {
+                self.report(start, "unterminated comment");
+                self.lexer.pos = self.lexer.bytes.len();
+            } else {
+                return;
+            }
+        }
+    }
+
+    /// Skips the character or word at the current position
+    fn skip_token(&mut self) {
+        let rest = &self.lexer.input[self.lexer.pos..];
+        let word = rest
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        self.lexer.pos += if word > 0 {
+            word
+        } else {
+            rest.chars().next().map_or(0, char::len_utf8)
+        };
+    }
+
+    /// Parses one value, keeping open arrays and objects on an explicit stack
+    fn parse_value(&mut self) -> DataValue<'a> {
+        let mut stack: Vec<Frame<'a>> = Vec::new();
+        loop {
+            self.skip_insignificant();
+            let start = self.lexer.pos;
+            let key = match stack.last() {
+                Some(Frame::Object(_, key)) => Some(*key),
+                _ => None,
+            };
+            let mut value = match self.lexer.peek() {
+                None => {
+                    self.report(start, "unexpected end of input");
+                    DataValue::Null
+                }
+                Some(b'[') => {
+                    self.lexer.pos += 1;
+                    stack.push(Frame::Array(Vec::new()));
+                    self.skip_insignificant();
+                    match self.lexer.peek() {
+                        Some(b']') => {
+                            self.lexer.pos += 1;
+                            stack.pop();
+                            DataValue::Array(&[])
+                        }
+                        None => {
+                            self.report(self.lexer.pos, "missing ']'");
+                            stack.pop();
+                            DataValue::Array(&[])
+                        }
+                        _ => continue,
+                    }
+                }
+                Some(b'{') => {
+                    self.lexer.pos += 1;
+                    match self.parse_entry_key() {
+                        EntryKey::Key(key) => {
+                            stack.push(Frame::Object(Vec::new(), key));
+                            continue;
+                        }
+                        EntryKey::Close => DataValue::Object(&[]),
+                        EntryKey::End => {
+                            self.report(self.lexer.pos, "missing '}'");
+                            DataValue::Object(&[])
+                        }
+                    }
+                }
+                Some(b'"') => {
+                    let s = self.parse_string();
+                    string_value(self.arena, &s, None, key, self.options)
+                }
+                Some(b'-' | b'0'..=b'9') => self.parse_number(),
+                Some(b',' | b']' | b'}') => {
+                    self.report(start, "missing value");
+                    DataValue::Null
+                }
+                Some(_) => {
+                    let literal = [
+                        ("null", DataValue::Null),
+                        ("true", DataValue::Bool(true)),
+                        ("false", DataValue::Bool(false)),
+                    ]
+                    .into_iter()
+                    .find(|(literal, _)| self.lexer.input[start..].starts_with(literal));
+                    match literal {
+                        Some((literal, value)) => {
+                            self.lexer.pos += literal.len();
+                            value
+                        }
+                        None => {
+                            self.report(start, "expected value");
+                            self.skip_token();
+                            continue;
+                        }
+                    }
+                }
+            };
+
+            // Add the value to its container, then close every container that ends
+            // right after it
+            loop {
+                let close = match stack.last_mut() {
+                    None => return value,
+                    Some(Frame::Array(items)) => {
+                        items.push(value);
+                        b']'
+                    }
+                    Some(Frame::Object(entries, key)) => {
+                        entries.push((*key, value));
+                        b'}'
+                    }
+                };
+                let missing = if close == b']' {
+                    "missing ']'"
+                } else {
+                    "missing '}'"
+                };
+                self.skip_insignificant();
+                let pos = self.lexer.pos;
+                let more = match self.lexer.peek() {
+                    Some(b',') => {
+                        self.lexer.pos += 1;
+                        self.skip_insignificant();
+                        self.next_element(stack.last_mut(), pos, close)
+                    }
+                    Some(b) if b == close => {
+                        self.lexer.pos += 1;
+                        false
+                    }
+                    Some(b']' | b'}') | None => {
+                        self.report(pos, missing);
+                        false
+                    }
+                    Some(_) => {
+                        self.report(pos, "missing ','");
+                        self.next_element(stack.last_mut(), pos, close)
+                    }
+                };
+                if more {
+                    break;
+                }
+                value = match stack.pop() {
+                    Some(Frame::Array(items)) => {
+                        DataValue::Array(self.arena.alloc_slice_clone(&items))
+                    }
+                    Some(Frame::Object(entries, _)) => {
+                        DataValue::Object(self.arena.alloc_slice_clone(&entries))
+                    }
+                    None => unreachable!("a container was just closed"),
+                };
+            }
+        }
+    }
+
+    /// After a separator at `comma`, prepares the next element of the innermost
+    /// container, returning false if the container ends instead
+    fn next_element(&mut self, frame: Option<&mut Frame<'a>>, comma: usize, close: u8) -> bool {
+        match frame {
+            Some(Frame::Object(_, key)) => match self.parse_entry_key() {
+                EntryKey::Key(next) => {
+                    *key = next;
+                    true
+                }
+                EntryKey::Close => {
+                    self.report(comma, "trailing comma");
+                    false
+                }
+                EntryKey::End => {
+                    self.report(self.lexer.pos, "missing '}'");
+                    false
+                }
+            },
+            _ => match self.lexer.peek() {
+                Some(b) if b == close => {
+                    self.report(comma, "trailing comma");
+                    self.lexer.pos += 1;
+                    false
+                }
+                None => {
+                    self.report(self.lexer.pos, "missing ']'");
+                    false
+                }
+                _ => true,
+            },
+        }
+    }
+
+    /// Parses the next object key and the colon after it, or consumes the closing
+    /// brace
+    fn parse_entry_key(&mut self) -> EntryKey<'a> {
+        loop {
+            self.skip_insignificant();
+            let start = self.lexer.pos;
+            let key = match self.lexer.peek() {
+                // A closing bracket of an enclosing array ends the object too
+                None | Some(b']') => return EntryKey::End,
+                Some(b'}') => {
+                    self.lexer.pos += 1;
+                    return EntryKey::Close;
+                }
+                Some(b'"') => self.parse_string(),
+                Some(b) if b.is_ascii_alphabetic() || b == b'_' || b == b'$' => {
+                    self.report(start, "unquoted key");
+                    self.skip_token();
+                    self.lexer.input[start..self.lexer.pos].to_string()
+                }
+                Some(_) => {
+                    self.report(start, "expected key");
+                    self.skip_token();
+                    continue;
+                }
+            };
+            self.skip_insignificant();
+            if self.lexer.peek() == Some(b':') {
+                self.lexer.pos += 1;
+            } else {
+                self.report(self.lexer.pos, "missing ':'");
+            }
+            return EntryKey::Key(self.arena.alloc_str(&key));
+        }
+    }
+
+    /// Parses a string literal, keeping what it can of invalid escapes and running
+    /// to the end of the input if the closing quote is missing
+    fn parse_string(&mut self) -> String {
+        let start = self.lexer.pos;
+        self.lexer.pos += 1;
+        let mut output = String::new();
+        loop {
+            let pos = self.lexer.pos;
+            match self.lexer.peek() {
+                None => {
+                    self.report(start, "unterminated string");
+                    return output;
+                }
+                Some(b'"') => {
+                    self.lexer.pos += 1;
+                    return output;
+                }
+                Some(b'\\') => {
+                    self.lexer.pos += 1;
+                    let escaped = match self.lexer.peek() {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'b') => '\u{8}',
+                        Some(b'f') => '\u{c}',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'u') => {
+                            self.lexer.pos += 1;
+                            match self.lexer.parse_hex4() {
+                                Ok(code) => {
+                                    let c =
+                                        self.surrogate_pair(code).or_else(|| char::from_u32(code));
+                                    if c.is_none() {
+                                        self.report(pos, "unpaired surrogate");
+                                    }
+                                    output.push(c.unwrap_or(char::REPLACEMENT_CHARACTER));
+                                }
+                                Err(_) => {
+                                    self.report(pos, "invalid unicode escape");
+                                    output.push('u');
+                                }
+                            }
+                            continue;
+                        }
+                        None => continue,
+                        Some(_) => {
+                            self.report(pos, "invalid escape");
+                            continue;
+                        }
+                    };
+                    output.push(escaped);
+                    self.lexer.pos += 1;
+                }
+                Some(_) => {
+                    let rest = &self.lexer.input[pos..];
+                    let run = rest.find(['"', '\\']).unwrap_or(rest.len());
+                    if rest[..run].contains(|c: char| c < ' ') {
+                        self.report(pos, "control character in string");
+                    }
+                    output.push_str(&rest[..run]);
+                    self.lexer.pos += run;
+                }
+            }
+        }
+    }
+
+    /// Combines a high surrogate with an escaped low surrogate that follows it
+    fn surrogate_pair(&mut self, high: u32) -> Option<char> {
+        if !(0xD800..0xDC00).contains(&high)
+            || !self.lexer.input[self.lexer.pos..].starts_with("\\u")
+        {
+            return None;
+        }
+        let resume = self.lexer.pos;
+        self.lexer.pos += 2;
+        match self.lexer.parse_hex4() {
+            Ok(low) if (0xDC00..0xE000).contains(&low) => {
+                char::from_u32(0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00))
+            }
+            _ => {
+                self.lexer.pos = resume;
+                None
+            }
+        }
+    }
+
+    /// Parses a number, or skips a malformed one and returns null
+    fn parse_number(&mut self) -> DataValue<'a> {
+        let start = self.lexer.pos;
+        match self.lexer.scan_number() {
+            Ok(is_float) => {
+                let text = &self.lexer.input[start..self.lexer.pos];
+                if !is_float {
+                    if let Ok(i) = text.parse::<i64>() {
+                        return DataValue::Number(Number::Integer(i));
+                    }
+                }
+                match text.parse::<f64>() {
+                    Ok(f) if f.is_finite() => DataValue::Number(Number::Float(f)),
+                    _ => {
+                        self.report(start, "number out of range");
+                        DataValue::Null
+                    }
+                }
+            }
+            Err(_) => {
+                self.report(start, "invalid number");
+                let rest = &self.lexer.input[self.lexer.pos..];
+                self.lexer.pos += rest
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '.' | '+' | '-')))
+                    .unwrap_or(rest.len());
+                DataValue::Null
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repair(input: &str) -> (String, Vec<String>) {
+        let arena = Bump::new();
+        let (value, diagnostics) = from_str_lenient(&arena, input);
+        (
+            crate::to_string(&value),
+            diagnostics.into_iter().map(|d| d.message).collect(),
+        )
+    }
+
+    #[test]
+    fn test_valid_input_has_no_diagnostics() {
+        let input = r#"{"a": [1, -2.5e3, "x\né😀", {"b": null}], "c": true, "d": {}}"#;
+        let arena = Bump::new();
+        let (value, diagnostics) = from_str_lenient(&arena, input);
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+        assert_eq!(value, crate::from_str(&arena, input).unwrap());
+    }
+
+    #[test]
+    fn test_repairs() {
+        let cases: [(&str, &str, &[&str]); 14] = [
+            ("[1, 2,]", "[1,2]", &["trailing comma"]),
+            (r#"{"a": 1,}"#, r#"{"a":1}"#, &["trailing comma"]),
+            ("[1 2]", "[1,2]", &["missing ','"]),
+            (
+                r#"{"a" 1 "b": 2}"#,
+                r#"{"a":1,"b":2}"#,
+                &["missing ':'", "missing ','"],
+            ),
+            (
+                r#"{"a": [1, {"b": 2"#,
+                r#"{"a":[1,{"b":2}]}"#,
+                &["missing '}'", "missing ']'", "missing '}'"],
+            ),
+            ("[{]", "[{}]", &["missing '}'"]),
+            (r#"{"a": [1}"#, r#"{"a":[1]}"#, &["missing ']'"]),
+            (
+                r#"["\x41\uZZ\ud800"]"#,
+                r#"["x41uZZ�"]"#,
+                &[
+                    "invalid escape",
+                    "invalid unicode escape",
+                    "unpaired surrogate",
+                ],
+            ),
+            (
+                "[\"a\u{1}\"]",
+                r#"["a\u0001"]"#,
+                &["control character in string"],
+            ),
+            (
+                "{a: tru, b: 1.}",
+                r#"{"a":null,"b":null}"#,
+                &[
+                    "unquoted key",
+                    "expected value",
+                    "missing value",
+                    "unquoted key",
+                    "invalid number",
+                ],
+            ),
+            (
+                "[1, @ 2, , 3]",
+                "[1,2,null,3]",
+                &["expected value", "missing value"],
+            ),
+            ("// header\n[1 /* one */]", "[1]", &["comment", "comment"]),
+            (
+                "{\"a\": 1} {\"b\": 2}",
+                r#"{"a":1}"#,
+                &["trailing characters"],
+            ),
+            ("", "null", &["unexpected end of input"]),
+        ];
+        for (input, expected, messages) in cases {
+            let (value, diagnostics) = repair(input);
+            assert_eq!(value, expected, "{}", input);
+            assert_eq!(diagnostics, messages, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_diagnostic_locations() {
+        let arena = Bump::new();
+        let (_, diagnostics) = from_str_lenient(&arena, "{\n  \"items\": [1 2],\n}");
+        let locations: Vec<(usize, usize, &str)> = diagnostics
+            .iter()
+            .map(|d| (d.location.line, d.location.column, d.location.path.as_str()))
+            .collect();
+        assert_eq!(locations, [(2, 15, "/items/0"), (2, 17, "/items")]);
+
+        let depth = 1000;
+        let (value, diagnostics) = from_str_lenient(&arena, &"[".repeat(depth));
+        assert_eq!(diagnostics.len(), depth);
+        assert!(value.is_array());
+    }
+}
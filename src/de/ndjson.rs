@@ -0,0 +1,313 @@
+//! Newline-delimited JSON (JSON Lines) input
+//!
+//! Each non-blank line holds one JSON document. [`read`] parses a whole input into
+//! one arena; with the `rayon` feature, [`par_read`] splits the input at line
+//! boundaries and parses the pieces in parallel, each into its own arena.
+//! [`query_stream`] looks up a JSON pointer in each record of a reader, keeping
+//! only one record in memory at a time.
+
+use std::io::BufRead;
+
+use bumpalo::Bump;
+
+use super::{from_str_with_options, ParseOptions};
+use crate::datavalue::DataValue;
+use crate::error::{Error, Result};
+
+/// Parses newline-delimited JSON into one value per non-blank line
+///
+/// # Errors
+///
+/// Returns an error naming the 1-based line number of the first line that is not
+/// valid JSON.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::{de::ndjson, Bump};
+///
+/// let arena = Bump::new();
+/// let records = ndjson::read(&arena, "{\"id\": 1}\n\n{\"id\": 2}\n").unwrap();
+/// assert_eq!(records.len(), 2);
+/// assert_eq!(records[1]["id"].as_i64(), Some(2));
+/// ```
+pub fn read<'a>(arena: &'a Bump, input: &str) -> Result<Vec<DataValue<'a>>> {
+    read_with_options(arena, input, &ParseOptions::default())
+}
+
+/// Parses newline-delimited JSON with the given options applied to every line
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::{de::ndjson, Bump, DuplicateKeyPolicy, ParseOptions};
+///
+/// let arena = Bump::new();
+/// let options = ParseOptions {
+///     duplicate_keys: DuplicateKeyPolicy::Error,
+///     ..ParseOptions::default()
+/// };
+/// let error = ndjson::read_with_options(&arena, "{}\n{\"a\": 1, \"a\": 2}\n", &options);
+/// assert!(error.unwrap_err().to_string().contains("line 2"));
+/// ```
+pub fn read_with_options<'a>(
+    arena: &'a Bump,
+    input: &str,
+    options: &ParseOptions,
+) -> Result<Vec<DataValue<'a>>> {
+    read_lines(arena, input, (1, 0), options)
+}
+
+/// Parses the lines of `input`, which starts at the given 1-based line number and
+/// byte offset of the whole input, and locates errors relative to the whole input
+fn read_lines<'a>(
+    arena: &'a Bump,
+    input: &str,
+    (first_line, first_byte): (usize, usize),
+    options: &ParseOptions,
+) -> Result<Vec<DataValue<'a>>> {
+    let mut values = Vec::new();
+    for (number, line) in input.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let value = from_str_with_options(arena, line, options).map_err(|e| {
+            let byte = first_byte + (line.as_ptr() as usize - input.as_ptr() as usize);
+            at_line(e, first_line + number, byte)
+        })?;
+        values.push(value);
+    }
+    Ok(values)
+}
+
+/// Locates an error in a record that starts at the given 1-based line number and
+/// byte offset of the whole input
+fn at_line(error: Error, line: usize, byte: usize) -> Error {
+    match error {
+        Error::SyntaxAt { msg, mut location } => {
+            location.line += line - 1;
+            location.byte_offset += byte;
+            Error::SyntaxAt { msg, location }
+        }
+        e => Error::syntax(format!("line {}: {}", line, e)),
+    }
+}
+
+/// Looks up `pointer` in each record read from `reader`, calling `f` with the
+/// 1-based line number and the value of every record where it resolves
+///
+/// Records are parsed one at a time into a scratch arena that is reset before
+/// the next line, so memory use depends on the largest record rather than the
+/// size of the input. Values passed to `f` borrow from that arena; copy out what
+/// should outlive the call, for example with [`DataValue::clone_in`]. Returns
+/// the number of matches.
+///
+/// # Errors
+///
+/// Returns an error if reading fails, or names the line of the first record that
+/// is not valid UTF-8 or valid JSON.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::de::ndjson;
+///
+/// let log = "{\"level\": \"error\", \"ctx\": {\"user\": \"ada\"}}\n{\"level\": \"info\"}\n";
+/// let mut users = Vec::new();
+/// let matches = ndjson::query_stream(log.as_bytes(), "/ctx/user", |line, user| {
+///     users.push((line, user.as_str().unwrap().to_string()));
+/// })
+/// .unwrap();
+/// assert_eq!(matches, 1);
+/// assert_eq!(users, [(1, "ada".to_string())]);
+/// ```
+pub fn query_stream<R, F>(mut reader: R, pointer: &str, mut f: F) -> Result<usize>
+where
+    R: BufRead,
+    F: FnMut(usize, &DataValue<'_>),
+{
+    let mut arena = Bump::new();
+    let mut line = Vec::new();
+    let (mut number, mut byte, mut matches) = (0, 0, 0);
+    loop {
+        line.clear();
+        let read = reader.read_until(b'\n', &mut line)?;
+        if read == 0 {
+            return Ok(matches);
+        }
+        number += 1;
+        let text = std::str::from_utf8(&line)
+            .map_err(|_| Error::syntax(format!("line {}: invalid UTF-8", number)))?;
+        if !text.trim().is_empty() {
+            arena.reset();
+            let value = from_str_with_options(&arena, text, &ParseOptions::default())
+                .map_err(|e| at_line(e, number, byte))?;
+            if let Some(found) = value.pointer(pointer) {
+                matches += 1;
+                f(number, found);
+            }
+        }
+        byte += read;
+    }
+}
+
+/// Parses newline-delimited JSON in parallel
+///
+/// The input is split at line boundaries into roughly equal chunks, several per
+/// rayon worker thread. Each chunk is validated as UTF-8 and parsed into a fresh
+/// arena, which is appended to `arenas`; the returned values borrow from those
+/// arenas and are in input order. Keeping the arenas with the caller, rather than
+/// returning each value next to its own arena, lets the values be used directly
+/// without any self-referential wrapper, and dropping `arenas` frees everything.
+///
+/// # Errors
+///
+/// Returns an error naming the 1-based line number of the first line that is not
+/// valid UTF-8 or valid JSON.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::de::ndjson;
+///
+/// let input: String = (0..1000).map(|i| format!("{{\"n\": {}}}\n", i)).collect();
+///
+/// let mut arenas = Vec::new();
+/// let records = ndjson::par_read(input.as_bytes(), &mut arenas).unwrap();
+/// assert_eq!(records.len(), 1000);
+/// assert_eq!(records[999]["n"].as_i64(), Some(999));
+/// ```
+#[cfg(feature = "rayon")]
+pub fn par_read<'a>(input: &[u8], arenas: &'a mut Vec<Bump>) -> Result<Vec<DataValue<'a>>> {
+    use rayon::prelude::*;
+
+    let chunks = split_lines(input, rayon::current_num_threads() * 4);
+    let first = arenas.len();
+    arenas.extend(chunks.iter().map(|_| Bump::new()));
+
+    let parsed: Vec<Result<Vec<DataValue<'a>>>> = arenas[first..]
+        .par_iter_mut()
+        .zip(chunks.par_iter())
+        .map(|(arena, &(first_line, chunk))| {
+            let arena: &'a Bump = arena;
+            let text = std::str::from_utf8(chunk).map_err(|e| {
+                let line = first_line
+                    + chunk[..e.valid_up_to()]
+                        .iter()
+                        .filter(|&&b| b == b'\n')
+                        .count();
+                Error::syntax(format!("line {}: invalid UTF-8", line))
+            })?;
+            let first_byte = chunk.as_ptr() as usize - input.as_ptr() as usize;
+            read_lines(
+                arena,
+                text,
+                (first_line, first_byte),
+                &ParseOptions::default(),
+            )
+        })
+        .collect();
+
+    let mut values = Vec::new();
+    for chunk in parsed {
+        values.extend(chunk?);
+    }
+    Ok(values)
+}
+
+/// Splits `input` into about `count` pieces that end on line boundaries, paired
+/// with the 1-based number of their first line
+#[cfg(feature = "rayon")]
+fn split_lines(input: &[u8], count: usize) -> Vec<(usize, &[u8])> {
+    let target = input.len().div_ceil(count.max(1)).max(1);
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    let mut line = 1;
+    while start < input.len() {
+        let end = match input[(start + target).min(input.len())..]
+            .iter()
+            .position(|&b| b == b'\n')
+        {
+            Some(offset) => (start + target + offset + 1).min(input.len()),
+            None => input.len(),
+        };
+        let chunk = &input[start..end];
+        chunks.push((line, chunk));
+        line += chunk.iter().filter(|&&b| b == b'\n').count();
+        start = end;
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_reports_line_numbers() {
+        let arena = Bump::new();
+        let values = read(&arena, "1\r\n\n[2]\n").unwrap();
+        assert_eq!(values.len(), 2);
+        let error = read(&arena, "1\n2\n{\n").unwrap_err().to_string();
+        assert!(error.contains("line 3"), "{}", error);
+        let error = read(&arena, "{}\r\n[1, {\"a\": x}]\n").unwrap_err();
+        assert_eq!(error.line(), Some(2));
+        assert_eq!(error.column(), Some(11));
+        assert_eq!(error.byte_offset(), Some(14));
+        assert_eq!(error.path(), Some("/1/a"));
+    }
+
+    #[test]
+    fn test_query_stream() {
+        let input = "{\"a\": [1, {\"b\": 2}]}\n\n[0]\r\n{\"a\": [3, {\"b\": \"x\"}]}";
+        let mut found = Vec::new();
+        let matches = query_stream(input.as_bytes(), "/a/1/b", |line, value| {
+            found.push((line, value.to_string()));
+        })
+        .unwrap();
+        assert_eq!(matches, 2);
+        assert_eq!(found, [(1, "2".to_string()), (4, r#""x""#.to_string())]);
+
+        let error = query_stream("{}\n[1, x]\n".as_bytes(), "", |_, _| {}).unwrap_err();
+        assert_eq!(error.line(), Some(2));
+        assert_eq!(error.byte_offset(), Some(7));
+        let error = query_stream(&b"{}\n\"\xFF\"\n"[..], "", |_, _| {}).unwrap_err();
+        assert!(error.to_string().contains("line 2"), "{}", error);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_par_read_matches_read() {
+        let input: String = (0..5000)
+            .map(|i| match i % 3 {
+                0 => format!("{{\"i\": {}, \"s\": \"caf\u{e9}\"}}\n", i),
+                1 => format!("[{}]\n", i),
+                _ => "\n".to_string(),
+            })
+            .collect();
+        let arena = Bump::new();
+        let expected = read(&arena, &input).unwrap();
+        let mut arenas = Vec::new();
+        let actual = par_read(input.as_bytes(), &mut arenas).unwrap();
+        assert_eq!(actual, expected);
+        assert!(arenas.len() > 1);
+
+        for (start, chunk) in split_lines(input.as_bytes(), 7) {
+            assert!(chunk.ends_with(b"\n"));
+            assert_eq!(
+                input.lines().nth(start - 1).unwrap().as_bytes(),
+                chunk.split(|&b| b == b'\n').next().unwrap()
+            );
+        }
+
+        let mut bad = input.clone().into_bytes();
+        bad.extend_from_slice(b"{\"broken\": \n");
+        let error = par_read(&bad, &mut arenas).unwrap_err().to_string();
+        assert!(error.contains("line 5001"), "{}", error);
+        let mut invalid = input.into_bytes();
+        invalid.extend_from_slice(b"\"\xff\"\n");
+        let error = par_read(&invalid, &mut arenas).unwrap_err().to_string();
+        assert!(error.contains("line 5001"), "{}", error);
+        assert!(par_read(b"", &mut arenas).unwrap().is_empty());
+    }
+}
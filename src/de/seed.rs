@@ -0,0 +1,188 @@
+//! Deserializing a DataValue out of any serde `Deserializer`
+
+use std::fmt;
+
+use bumpalo::Bump;
+use serde::de::{self, DeserializeSeed, Visitor};
+
+use crate::datavalue::{DataValue, Number};
+
+/// Deserializes a DataValue into the wrapped arena
+///
+/// DataValue borrows from an arena, so it cannot implement `Deserialize`, which
+/// has nowhere to put the allocations. Pass this seed to
+/// `DeserializeSeed::deserialize` instead to read a value from any serde
+/// format, or from a field of a larger document, straight into an arena you
+/// manage. Integers outside the `i64` range become [`Number::BigInt`] with the
+/// `bignum` feature, and floats otherwise.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::{de::DataValueSeed, Bump};
+/// use serde::de::DeserializeSeed;
+///
+/// let arena = Bump::new();
+/// let mut deserializer = serde_json::Deserializer::from_str(r#"{"tags": ["a", "b"], "n": 2}"#);
+/// let value = DataValueSeed(&arena).deserialize(&mut deserializer).unwrap();
+/// assert_eq!(value["tags"][1].as_str(), Some("b"));
+/// assert_eq!(value["n"].as_i64(), Some(2));
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct DataValueSeed<'a>(pub &'a Bump);
+
+impl<'de, 'a> DeserializeSeed<'de> for DataValueSeed<'a> {
+    type Value = DataValue<'a>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de, 'a> Visitor<'de> for DataValueSeed<'a> {
+    type Value = DataValue<'a>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("any JSON value")
+    }
+
+    fn visit_bool<E: de::Error>(self, b: bool) -> Result<Self::Value, E> {
+        Ok(DataValue::Bool(b))
+    }
+
+    fn visit_i64<E: de::Error>(self, i: i64) -> Result<Self::Value, E> {
+        Ok(DataValue::Number(Number::Integer(i)))
+    }
+
+    fn visit_u64<E: de::Error>(self, u: u64) -> Result<Self::Value, E> {
+        self.visit_u128(u128::from(u))
+    }
+
+    fn visit_i128<E: de::Error>(self, i: i128) -> Result<Self::Value, E> {
+        if let Ok(i) = i64::try_from(i) {
+            return Ok(DataValue::Number(Number::Integer(i)));
+        }
+        #[cfg(feature = "bignum")]
+        if let Some(n) = crate::BigInt::parse(self.0, &i.to_string()) {
+            return Ok(DataValue::Number(Number::BigInt(n)));
+        }
+        Ok(DataValue::Number(Number::Float(i as f64)))
+    }
+
+    fn visit_u128<E: de::Error>(self, u: u128) -> Result<Self::Value, E> {
+        match i128::try_from(u) {
+            Ok(i) => self.visit_i128(i),
+            #[cfg(feature = "bignum")]
+            Err(_) => Ok(crate::BigInt::parse(self.0, &u.to_string())
+                .map_or(DataValue::Number(Number::Float(u as f64)), |n| {
+                    DataValue::Number(Number::BigInt(n))
+                })),
+            #[cfg(not(feature = "bignum"))]
+            Err(_) => Ok(DataValue::Number(Number::Float(u as f64))),
+        }
+    }
+
+    fn visit_f64<E: de::Error>(self, f: f64) -> Result<Self::Value, E> {
+        Ok(DataValue::Number(Number::Float(f)))
+    }
+
+    fn visit_str<E: de::Error>(self, s: &str) -> Result<Self::Value, E> {
+        Ok(DataValue::String(self.0.alloc_str(s)))
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(DataValue::Null)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(DataValue::Null)
+    }
+
+    fn visit_some<D: de::Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<Self::Value, D::Error> {
+        self.deserialize(deserializer)
+    }
+
+    fn visit_newtype_struct<D: de::Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<Self::Value, D::Error> {
+        self.deserialize(deserializer)
+    }
+
+    fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut items = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(item) = seq.next_element_seed(self)? {
+            items.push(item);
+        }
+        Ok(DataValue::Array(self.0.alloc_slice_fill_iter(items)))
+    }
+
+    fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut entries = Vec::with_capacity(map.size_hint().unwrap_or(0));
+        while let Some(key) = map.next_key_seed(KeySeed(self.0))? {
+            entries.push((key, map.next_value_seed(self)?));
+        }
+        Ok(DataValue::Object(self.0.alloc_slice_fill_iter(entries)))
+    }
+}
+
+/// Copies an object key into the arena
+struct KeySeed<'a>(&'a Bump);
+
+impl<'de, 'a> DeserializeSeed<'de> for KeySeed<'a> {
+    type Value = &'a str;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_str(self)
+    }
+}
+
+impl<'de, 'a> Visitor<'de> for KeySeed<'a> {
+    type Value = &'a str;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a string key")
+    }
+
+    fn visit_str<E: de::Error>(self, s: &str) -> Result<Self::Value, E> {
+        Ok(self.0.alloc_str(s))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_data_value_seed() {
+        let arena = Bump::new();
+        let text = r#"{"a": [1, -2, 1.5, true, null, "s"], "b": {"c": 18446744073709551615}}"#;
+        let mut deserializer = serde_json::Deserializer::from_str(text);
+        let value = DataValueSeed(&arena)
+            .deserialize(&mut deserializer)
+            .unwrap();
+        assert_eq!(value["a"], crate::from_str(&arena, text).unwrap()["a"]);
+        if cfg!(feature = "bignum") {
+            assert_eq!(value["b"]["c"].to_string(), "18446744073709551615");
+        } else {
+            assert_eq!(value["b"]["c"].as_f64(), Some(u64::MAX as f64));
+        }
+
+        // Any self-describing format works, here a serde_json::Value
+        let json = serde_json::json!({"k": [{"x": "y"}]});
+        let value = DataValueSeed(&arena).deserialize(&json).unwrap();
+        assert_eq!(value["k"][0]["x"].as_str(), Some("y"));
+
+        let mut bad = serde_json::Deserializer::from_str("[1,");
+        assert!(DataValueSeed(&arena).deserialize(&mut bad).is_err());
+    }
+}
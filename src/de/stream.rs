@@ -0,0 +1,332 @@
+//! A serde `Deserializer` over JSON text that does not build a tree
+
+use std::borrow::Cow;
+use std::io::Read;
+
+use serde::de::value::{BorrowedStrDeserializer, StringDeserializer};
+use serde::de::{self, DeserializeSeed, IntoDeserializer, Visitor};
+
+use super::events::{EventParser, JsonEvent, ReadInput, StrInput};
+use super::value::visit_number;
+use crate::error::{Error, Result};
+
+/// Deserializes straight from JSON text, one [`JsonEvent`] at a time
+///
+/// Built on [`EventParser`], so memory use depends on the nesting depth and the
+/// largest single string, not on the size of the document. That makes it the
+/// input half of a streaming conversion: pair it with any serde `Serializer`
+/// through `serde_transcode`, or with [`JsonSerializer`](crate::ser::JsonSerializer)
+/// for the reverse direction, and no DataValue or arena is involved. It can also
+/// deserialize typed data directly, and strings without escapes are lent out
+/// from the input, so `&str` fields borrow from it.
+///
+/// Integers outside the `i64` range are read as floats. Call [`end`](Self::end)
+/// after deserializing to check that only whitespace follows the value.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::de::JsonDeserializer;
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct Event<'s> {
+///     kind: &'s str,
+///     ids: Vec<u32>,
+/// }
+///
+/// let mut deserializer = JsonDeserializer::new(r#"{"kind": "click", "ids": [1, 2]}"#);
+/// let event = Event::deserialize(&mut deserializer).unwrap();
+/// deserializer.end().unwrap();
+/// assert_eq!((event.kind, event.ids.len()), ("click", 2));
+/// ```
+pub struct JsonDeserializer<'de, I> {
+    events: EventParser<I>,
+    /// Reads the next event; the two inputs yield events of different lifetimes
+    next: fn(&mut EventParser<I>) -> Option<Result<JsonEvent<'de>>>,
+    /// Event read ahead by [`peek`](Self::peek)
+    peeked: Option<JsonEvent<'de>>,
+}
+
+impl<'de> JsonDeserializer<'de, StrInput<'de>> {
+    /// Creates a deserializer over text in memory
+    pub fn new(input: &'de str) -> Self {
+        JsonDeserializer {
+            events: EventParser::new(input),
+            next: Iterator::next,
+            peeked: None,
+        }
+    }
+}
+
+impl<'de, R: Read> JsonDeserializer<'de, ReadInput<R>> {
+    /// Creates a deserializer reading from `reader` through an internal buffer
+    ///
+    /// Strings are always handed to the visitor as owned.
+    pub fn from_reader(reader: R) -> Self {
+        JsonDeserializer {
+            events: EventParser::from_reader(reader),
+            next: |events| events.next(),
+            peeked: None,
+        }
+    }
+}
+
+impl<'de, I> JsonDeserializer<'de, I> {
+    /// Checks that nothing but whitespace follows the value
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the input continues, or is invalid after the value.
+    pub fn end(&mut self) -> Result<()> {
+        match self.peeked.take() {
+            Some(_) => Err(Error::syntax("trailing characters after the value")),
+            None => (self.next)(&mut self.events).transpose().map(|_| ()),
+        }
+    }
+
+    fn peek(&mut self) -> Result<Option<&JsonEvent<'de>>> {
+        if self.peeked.is_none() {
+            self.peeked = (self.next)(&mut self.events).transpose()?;
+        }
+        Ok(self.peeked.as_ref())
+    }
+
+    fn next_event(&mut self) -> Result<JsonEvent<'de>> {
+        match self.peeked.take() {
+            Some(event) => Ok(event),
+            None => (self.next)(&mut self.events)
+                .unwrap_or_else(|| Err(Error::syntax("unexpected end of input"))),
+        }
+    }
+
+    /// Consumes the event that must close a container after its visitor returns
+    fn expect_end(&mut self, close: &str) -> Result<()> {
+        match self.next_event()? {
+            JsonEvent::EndArray if close == "]" => Ok(()),
+            JsonEvent::EndObject if close == "}" => Ok(()),
+            _ => Err(Error::syntax(format!("expected '{}'", close))),
+        }
+    }
+}
+
+impl<'de, I> de::Deserializer<'de> for &mut JsonDeserializer<'de, I> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.next_event()? {
+            JsonEvent::Null => visitor.visit_unit(),
+            JsonEvent::Bool(b) => visitor.visit_bool(b),
+            JsonEvent::Number(n) => visit_number(&n, visitor),
+            JsonEvent::String(Cow::Borrowed(s)) => visitor.visit_borrowed_str(s),
+            JsonEvent::String(Cow::Owned(s)) => visitor.visit_string(s),
+            JsonEvent::StartArray => {
+                let value = visitor.visit_seq(SeqAccess { de: &mut *self })?;
+                self.expect_end("]")?;
+                Ok(value)
+            }
+            JsonEvent::StartObject => {
+                let value = visitor.visit_map(MapAccess { de: &mut *self })?;
+                self.expect_end("}")?;
+                Ok(value)
+            }
+            JsonEvent::Key(_) | JsonEvent::EndArray | JsonEvent::EndObject => {
+                Err(Error::syntax("expected value"))
+            }
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        if let Some(JsonEvent::Null) = self.peek()? {
+            self.peeked = None;
+            return visitor.visit_none();
+        }
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        match self.next_event()? {
+            JsonEvent::String(Cow::Borrowed(variant)) => {
+                visitor.visit_enum(BorrowedStrDeserializer::<Error>::new(variant))
+            }
+            JsonEvent::String(Cow::Owned(variant)) => {
+                visitor.visit_enum(variant.into_deserializer())
+            }
+            JsonEvent::StartObject => {
+                let value = visitor.visit_enum(EnumAccess { de: &mut *self })?;
+                self.expect_end("}")?;
+                Ok(value)
+            }
+            _ => Err(Error::expected_type(
+                "string or single-key object for an enum",
+                "other value",
+            )),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct identifier
+        ignored_any
+    }
+}
+
+/// Hands an object key to `seed` as a string
+fn key<'de, K: DeserializeSeed<'de>>(key: Cow<'de, str>, seed: K) -> Result<K::Value> {
+    match key {
+        Cow::Borrowed(key) => seed.deserialize(BorrowedStrDeserializer::new(key)),
+        Cow::Owned(key) => seed.deserialize(StringDeserializer::new(key)),
+    }
+}
+
+struct SeqAccess<'d, 'de, I> {
+    de: &'d mut JsonDeserializer<'de, I>,
+}
+
+impl<'de, I> de::SeqAccess<'de> for SeqAccess<'_, 'de, I> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+        match self.de.peek()? {
+            Some(JsonEvent::EndArray) => Ok(None),
+            _ => seed.deserialize(&mut *self.de).map(Some),
+        }
+    }
+}
+
+struct MapAccess<'d, 'de, I> {
+    de: &'d mut JsonDeserializer<'de, I>,
+}
+
+impl<'de, I> de::MapAccess<'de> for MapAccess<'_, 'de, I> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        if let Some(JsonEvent::EndObject) = self.de.peek()? {
+            return Ok(None);
+        }
+        match self.de.next_event()? {
+            JsonEvent::Key(name) => key(name, seed).map(Some),
+            _ => Err(Error::syntax("expected object key")),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        seed.deserialize(&mut *self.de)
+    }
+}
+
+/// An enum variant written as `{variant: value}`
+struct EnumAccess<'d, 'de, I> {
+    de: &'d mut JsonDeserializer<'de, I>,
+}
+
+impl<'d, 'de, I> de::EnumAccess<'de> for EnumAccess<'d, 'de, I> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self)> {
+        match self.de.next_event()? {
+            JsonEvent::Key(name) => Ok((key(name, seed)?, self)),
+            _ => Err(Error::syntax("expected enum variant name")),
+        }
+    }
+}
+
+impl<'de, I> de::VariantAccess<'de> for EnumAccess<'_, 'de, I> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        de::Deserialize::deserialize(&mut *self.de)
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value> {
+        seed.deserialize(&mut *self.de)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        de::Deserializer::deserialize_seq(&mut *self.de, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        de::Deserializer::deserialize_map(&mut *self.de, visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::de::DataValueSeed;
+    use bumpalo::Bump;
+    use serde::Deserialize;
+    use std::collections::BTreeMap;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    enum Shape {
+        Empty,
+        Circle(f64),
+        Point(i32, i32),
+        Rect { w: u8, h: u8 },
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Drawing<'a> {
+        title: &'a str,
+        shapes: Vec<Shape>,
+        layers: BTreeMap<String, Option<char>>,
+    }
+
+    #[test]
+    fn test_json_deserializer() {
+        let text = r#"{
+            "title": "plan",
+            "shapes": ["Empty", {"Circle": 1.5}, {"Point": [-1, 2]}, {"Rect": {"w": 3, "h": 4}}],
+            "layers": {"base": "a", "top\n": null},
+            "unknown": [{"skipped": true}]
+        }"#;
+        let mut deserializer = JsonDeserializer::new(text);
+        let drawing = Drawing::deserialize(&mut deserializer).unwrap();
+        deserializer.end().unwrap();
+        assert_eq!(drawing.title, "plan");
+        assert_eq!(drawing.shapes[2], Shape::Point(-1, 2));
+        assert_eq!(drawing.shapes[3], Shape::Rect { w: 3, h: 4 });
+        assert_eq!(drawing.layers["top\n"], None);
+
+        // Readers yield owned strings, which suit owned types
+        let mut deserializer = JsonDeserializer::from_reader(text.as_bytes());
+        let json = serde_json::Value::deserialize(&mut deserializer).unwrap();
+        assert_eq!(
+            json,
+            serde_json::from_str::<serde_json::Value>(text).unwrap()
+        );
+        let arena = Bump::new();
+        let value = DataValueSeed(&arena)
+            .deserialize(&mut JsonDeserializer::new(text))
+            .unwrap();
+        assert_eq!(value, crate::from_str(&arena, text).unwrap());
+
+        let mut trailing = JsonDeserializer::new("[1] 2");
+        Vec::<u8>::deserialize(&mut trailing).unwrap();
+        assert!(trailing.end().is_err());
+        assert!(<(u8, u8)>::deserialize(&mut JsonDeserializer::new("[1, 2, 3]")).is_err());
+        assert!(Vec::<u8>::deserialize(&mut JsonDeserializer::new("[1, 2")).is_err());
+        assert!(Shape::deserialize(&mut JsonDeserializer::new("[1]")).is_err());
+    }
+}
@@ -0,0 +1,313 @@
+//! Deserializing `Deserialize` types out of a DataValue
+
+use bumpalo::Bump;
+use serde::de::value::BorrowedStrDeserializer;
+use serde::de::{self, Deserialize, DeserializeSeed, IntoDeserializer, Visitor};
+
+use crate::datavalue::{DataValue, Number};
+use crate::error::{Error, Result};
+use crate::helpers::format_duration;
+
+/// Converts a DataValue into any type implementing `Deserialize`
+///
+/// This mirrors `serde_json::from_value`, except that the value is only
+/// borrowed. Strings are handed to the visitor as borrowed from the arena, so
+/// `&str` fields point into it instead of being copied. DateTime and Duration
+/// values deserialize as the strings they serialize to, and a
+/// [`DataValue::Raw`] is parsed first.
+///
+/// # Errors
+///
+/// Returns an error if the value does not have the shape `T` expects.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::{de::from_value, from_str, Bump};
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct User<'a> {
+///     name: &'a str,
+///     roles: Vec<String>,
+///     manager: Option<u32>,
+/// }
+///
+/// let arena = Bump::new();
+/// let value = from_str(&arena, r#"{"name": "John", "roles": ["admin"], "manager": null}"#).unwrap();
+/// let user: User = from_value(&value).unwrap();
+/// assert_eq!((user.name, user.roles.len(), user.manager), ("John", 1, None));
+/// ```
+pub fn from_value<'de, T>(value: &DataValue<'de>) -> Result<T>
+where
+    T: Deserialize<'de>,
+{
+    T::deserialize(value)
+}
+
+impl<'de> de::Deserializer<'de> for &DataValue<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self {
+            DataValue::Null => visitor.visit_unit(),
+            DataValue::Bool(b) => visitor.visit_bool(*b),
+            DataValue::Number(n) => visit_number(n, visitor),
+            DataValue::String(s) => visitor.visit_borrowed_str(s),
+            DataValue::Array(items) => {
+                let mut seq = SeqAccess {
+                    items: items.iter(),
+                };
+                let value = visitor.visit_seq(&mut seq)?;
+                match seq.items.len() {
+                    0 => Ok(value),
+                    _ => Err(de::Error::invalid_length(items.len(), &"fewer elements")),
+                }
+            }
+            DataValue::Object(entries) => visitor.visit_map(MapAccess {
+                entries: entries.iter(),
+                value: None,
+            }),
+            DataValue::DateTime(dt) => visitor.visit_string(dt.to_rfc3339()),
+            DataValue::Duration(dur) => visitor.visit_string(format_duration(dur)),
+            DataValue::Raw(_) => {
+                // The parsed value lives in a temporary arena, so it cannot be
+                // lent out for 'de; go through an owned serde_json::Value instead
+                let arena = Bump::new();
+                let json = serde_json::to_value(self.parse_raw(&arena)?)?;
+                Ok(de::Deserializer::deserialize_any(json, visitor)?)
+            }
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self {
+            DataValue::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self {
+            DataValue::String(s) => visitor.visit_borrowed_bytes(s.as_bytes()),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        match self {
+            DataValue::String(variant) => {
+                visitor.visit_enum(BorrowedStrDeserializer::<Error>::new(variant))
+            }
+            DataValue::Object([(variant, value)]) => {
+                visitor.visit_enum(EnumAccess { variant, value })
+            }
+            _ => Err(Error::expected_type(
+                "string or single-key object for an enum",
+                format!("{:?}", self.get_type()),
+            )),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        unit unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+pub(super) fn visit_number<'de, V: Visitor<'de>>(
+    number: &Number<'de>,
+    visitor: V,
+) -> Result<V::Value> {
+    match number {
+        Number::Integer(i) => visitor.visit_i64(*i),
+        Number::Float(f) => visitor.visit_f64(*f),
+        Number::Raw(_) => visit_number(&number.parsed(), visitor),
+        #[cfg(feature = "bignum")]
+        Number::BigInt(n) => match n.to_string().parse::<u64>() {
+            Ok(u) => visitor.visit_u64(u),
+            Err(_) => visitor.visit_f64(n.to_f64()),
+        },
+        #[cfg(feature = "bignum")]
+        Number::BigDecimal(n) => visitor.visit_f64(n.to_f64()),
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for &DataValue<'de> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+struct SeqAccess<'de> {
+    items: std::slice::Iter<'de, DataValue<'de>>,
+}
+
+impl<'de> de::SeqAccess<'de> for SeqAccess<'de> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+        self.items
+            .next()
+            .map(|item| seed.deserialize(item))
+            .transpose()
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.items.len())
+    }
+}
+
+struct MapAccess<'de> {
+    entries: std::slice::Iter<'de, (&'de str, DataValue<'de>)>,
+    /// Value of the entry whose key was just returned
+    value: Option<&'de DataValue<'de>>,
+}
+
+impl<'de> de::MapAccess<'de> for MapAccess<'de> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        let Some((key, value)) = self.entries.next() else {
+            return Ok(None);
+        };
+        self.value = Some(value);
+        seed.deserialize(BorrowedStrDeserializer::new(key))
+            .map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        let value = self
+            .value
+            .take()
+            .ok_or_else(|| Error::custom("next_value_seed called before next_key_seed"))?;
+        seed.deserialize(value)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.entries.len())
+    }
+}
+
+/// An enum variant written as `{variant: value}`
+struct EnumAccess<'de> {
+    variant: &'de str,
+    value: &'de DataValue<'de>,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumAccess<'de> {
+    type Error = Error;
+    type Variant = &'de DataValue<'de>;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self::Variant)> {
+        let variant = seed.deserialize(BorrowedStrDeserializer::<Error>::new(self.variant))?;
+        Ok((variant, self.value))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for &DataValue<'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        Deserialize::deserialize(self)
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        de::Deserializer::deserialize_map(self, visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{from_str, ser::to_value};
+    use serde::{Deserialize, Serialize};
+    use std::collections::BTreeMap;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Shape {
+        Empty,
+        Circle(f64),
+        Point(i32, i32),
+        Rect { w: u8, h: u8 },
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Drawing<'a> {
+        title: &'a str,
+        id: u64,
+        shapes: Vec<Shape>,
+        layers: BTreeMap<String, char>,
+        offset: (i8, f32),
+        hidden: Option<bool>,
+    }
+
+    #[test]
+    fn test_from_value() {
+        let arena = Bump::new();
+        let text = r#"{
+            "title": "plan",
+            "id": 7,
+            "shapes": ["Empty", {"Circle": 1.5}, {"Point": [-1, 2]}, {"Rect": {"w": 3, "h": 4}}],
+            "layers": {"base": "a"},
+            "offset": [0, 2],
+            "hidden": null
+        }"#;
+        let value = from_str(&arena, text).unwrap();
+        let drawing: Drawing = from_value(&value).unwrap();
+        assert_eq!(drawing.shapes[3], Shape::Rect { w: 3, h: 4 });
+        assert_eq!(drawing.offset, (0, 2.0));
+        // Borrowed fields point into the arena
+        assert_eq!(
+            drawing.title.as_ptr(),
+            value["title"].as_str().unwrap().as_ptr()
+        );
+        assert_eq!(
+            to_value(&arena, &drawing).unwrap()["layers"],
+            value["layers"]
+        );
+
+        let raw = DataValue::Raw(r#"{"w": 1, "h": 2, /* size */}"#);
+        assert_eq!(
+            from_value::<Shape>(&crate::helpers::object(&arena, vec![("Rect", raw)])).unwrap(),
+            Shape::Rect { w: 1, h: 2 }
+        );
+
+        let wrong = from_str(&arena, r#"{"w": -1, "h": 2}"#).unwrap();
+        assert!(from_value::<BTreeMap<String, u8>>(&wrong).is_err());
+        let long = from_str(&arena, "[1, 2, 3]").unwrap();
+        assert!(from_value::<(u8, u8)>(&long).is_err());
+        assert!(from_value::<Shape>(&long).is_err());
+    }
+}
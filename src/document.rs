@@ -0,0 +1,1060 @@
+//! Parsed documents with source locations
+//!
+//! A [`Document`] is a parsed DataValue together with a side table recording the
+//! byte span each node was parsed from, so tools built on DataValue can report
+//! problems as "field /a/b at line 12" instead of just a path.
+//!
+//! [`NodeHandle`]s are small copyable references to nodes of a [`Document`] that
+//! can be stored in indexes and graphs built next to the document, and turned
+//! back into nodes through it.
+//!
+//! A [`SharedDocument`] keeps the source text behind an `Arc` and hands out
+//! [`NodeRef`] handles that stay valid after the original arena is dropped.
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+
+use bumpalo::Bump;
+
+use crate::access::{escape_pointer_token, unescape_pointer_token};
+use crate::datavalue::{DataValue, Number};
+use crate::de::{ParseOptions, ParseReport, ParseStrategy, Parser, SpanTable};
+use crate::error::{Error, Result};
+use crate::format::{save_document, Compression, Format, Loader, SaveOptions, TextStyle};
+use crate::ser::{push_json_string, write_json_scalar, write_tree, Layout};
+
+/// A parsed JSON document that remembers where each node came from
+///
+/// Spans are looked up by node identity, so they are available for any reference
+/// obtained by navigating from [`Document::root`] (indexing, `get`, `pointer`,
+/// iteration), but not for clones of those nodes.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::{Bump, Document};
+///
+/// let arena = Bump::new();
+/// let source = "{\n  \"name\": \"John\",\n  \"tags\": [1, 2]\n}";
+/// let doc = Document::parse(&arena, source).unwrap();
+///
+/// let tags = &doc.root()["tags"];
+/// assert_eq!(doc.span_of(tags), Some(30..36));
+/// assert_eq!(doc.location_of(tags), Some((3, 11)));
+/// assert_eq!(&source[doc.span_of(&tags[1]).unwrap()], "2");
+/// ```
+#[derive(Debug)]
+pub struct Document<'a> {
+    root: &'a DataValue<'a>,
+    spans: SpanTable,
+    /// Spans of object keys, keyed by the address of their entry
+    key_spans: SpanTable,
+    line_starts: Vec<usize>,
+    source: &'a str,
+    format: Format,
+    /// Compression of the file the document was loaded from
+    compression: Option<Compression>,
+    style: TextStyle,
+    lossless: bool,
+    report: ParseReport,
+    /// Identifies the document in the handles it hands out
+    id: u64,
+    /// Built on the first handle lookup
+    nodes: OnceLock<NodeTable>,
+}
+
+/// Deepest nesting [`Document::validate_invariants`] accepts
+const MAX_VALID_DEPTH: usize = 1024;
+
+/// Source of document ids, so a handle never resolves in the wrong document
+static NEXT_DOCUMENT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Every node of a document in pre-order, by address and by position
+///
+/// Nodes are recorded as their parent's position and their index within the
+/// parent rather than as references, which keeps [`Document`] covariant.
+#[derive(Debug)]
+struct NodeTable {
+    /// Parent position and child index of each node; the root is its own parent
+    nodes: Vec<(u32, u32)>,
+    positions: HashMap<usize, u32>,
+}
+
+impl NodeTable {
+    fn build(root: &DataValue<'_>) -> Self {
+        let mut nodes = Vec::new();
+        let mut positions = HashMap::new();
+        let mut stack = vec![(root, 0, 0)];
+        while let Some((node, parent, child)) = stack.pop() {
+            let position = nodes.len() as u32;
+            positions.insert(node as *const DataValue<'_> as usize, position);
+            nodes.push((parent, child));
+            match node {
+                DataValue::Array(items) => stack.extend(
+                    items
+                        .iter()
+                        .enumerate()
+                        .rev()
+                        .map(|(i, item)| (item, position, i as u32)),
+                ),
+                DataValue::Object(entries) => stack.extend(
+                    entries
+                        .iter()
+                        .enumerate()
+                        .rev()
+                        .map(|(i, (_, value))| (value, position, i as u32)),
+                ),
+                _ => {}
+            }
+        }
+        NodeTable { nodes, positions }
+    }
+
+    /// Returns the node at `position` by walking down from the root
+    fn resolve<'a>(&self, root: &'a DataValue<'a>, position: u32) -> Option<&'a DataValue<'a>> {
+        let mut path = Vec::new();
+        let mut position = position;
+        while position != 0 {
+            let &(parent, child) = self.nodes.get(position as usize)?;
+            path.push(child as usize);
+            position = parent;
+        }
+        path.iter().rev().try_fold(root, |node, &child| match node {
+            DataValue::Array(items) => items.get(child),
+            DataValue::Object(entries) => entries.get(child).map(|(_, v)| v),
+            _ => None,
+        })
+    }
+}
+
+/// A compact reference to a node of a [`Document`]
+///
+/// A handle is the node's position in the document together with the id of the
+/// document, so it is `Copy`, hashable and has no lifetime. It can be kept in
+/// maps, indexes and graph structures built over a document and resolved with
+/// [`Document::resolve`] while the document is alive. Resolving in a different
+/// document returns None rather than an unrelated node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeHandle {
+    document: u64,
+    index: u32,
+}
+
+impl<'a> Document<'a> {
+    /// Parses JSON text, recording the byte span of every node
+    ///
+    /// The source text is copied into the arena so that spans can be resolved to
+    /// text later with [`Document::source`].
+    ///
+    /// # Errors
+    ///
+    /// Returns a syntax error with the line and column of the problem if the input
+    /// is not valid JSON.
+    pub fn parse(arena: &'a Bump, source: &str) -> Result<Self> {
+        Self::parse_with_options(arena, source, &ParseOptions::default())
+    }
+
+    /// Parses JSON text with the given options, recording the byte span of every node
+    pub fn parse_with_options(
+        arena: &'a Bump,
+        source: &str,
+        options: &ParseOptions,
+    ) -> Result<Self> {
+        crate::metrics::parse(arena, source.len(), || {
+            let source: &'a str = arena.alloc_str(source);
+            let mut parser = Parser::new(arena, source, options).record_spans();
+            let root = parser.parse_document()?;
+            let spans = parser.take_spans();
+            let document = Self::from_parts(root, spans, source, Format::Json);
+            Ok(document.with_key_spans(parser.take_key_spans(), options))
+        })
+    }
+
+    /// Parses JSON text with a strategy chosen from a sample of the input
+    ///
+    /// The first [`AUTO_SAMPLE_BYTES`](crate::de::AUTO_SAMPLE_BYTES) are scanned
+    /// for how much of the text is strings and structure, and
+    /// [`Document::parse_report`] returns the counts with the strategy used.
+    /// Strings without escapes borrow from the copy of the source kept in the
+    /// arena instead of being copied again.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use datavalue_rs::de::ParseStrategy;
+    /// use datavalue_rs::{Bump, Document};
+    ///
+    /// let arena = Bump::new();
+    /// let doc = Document::parse_auto(&arena, r#"{"name": "John", "tags": ["a", "b"]}"#).unwrap();
+    /// let report = doc.parse_report();
+    /// assert_eq!(report.strategy, ParseStrategy::Borrowing);
+    /// assert_eq!(report.sample.unwrap().strings, 5);
+    /// assert_eq!(doc.span_of_pointer("/name"), Some(9..15));
+    /// ```
+    pub fn parse_auto(arena: &'a Bump, source: &str) -> Result<Self> {
+        Self::parse_auto_with_options(arena, source, &ParseOptions::default())
+    }
+
+    /// Parses JSON text with the given options and an automatically chosen strategy
+    ///
+    /// See [`Document::parse_auto`].
+    pub fn parse_auto_with_options(
+        arena: &'a Bump,
+        source: &str,
+        options: &ParseOptions,
+    ) -> Result<Self> {
+        crate::metrics::parse(arena, source.len(), || {
+            Self::parse_auto_sampled(arena, source, options)
+        })
+    }
+
+    fn parse_auto_sampled(arena: &'a Bump, source: &str, options: &ParseOptions) -> Result<Self> {
+        let source: &'a str = arena.alloc_str(source);
+        let report = ParseReport::sample(source);
+        let mut parser = Parser::borrowing(arena, source, options).record_spans();
+        let root = parser.parse_document()?;
+        let spans = parser.take_spans();
+        let document = Self::from_parts(root, spans, source, Format::Json)
+            .with_key_spans(parser.take_key_spans(), options);
+        Ok(Document { report, ..document })
+    }
+
+    /// Parses JSON text so that it can be written back byte for byte
+    ///
+    /// Uses [`ParseOptions::lossless`], so numbers keep their source text and
+    /// duplicate keys are all kept. See [`Document::to_string_lossless`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use datavalue_rs::{Bump, Document};
+    ///
+    /// let arena = Bump::new();
+    /// let source = r#"{"z":1.0e3,"a":"caf\u00e9","a":[12345678901234567890]}"#;
+    /// let doc = Document::parse_lossless(&arena, source).unwrap();
+    /// assert!(doc.is_lossless());
+    /// assert!(doc.is_lossless_roundtrip());
+    /// assert_eq!(doc.to_string_lossless(), source);
+    /// ```
+    pub fn parse_lossless(arena: &'a Bump, source: &str) -> Result<Self> {
+        Self::parse_with_options(arena, source, &ParseOptions::lossless())
+    }
+
+    /// Loads a data file, choosing the format from its extension
+    ///
+    /// JSON, NDJSON, CSV and HOCON are supported out of the box, and YAML, TOML
+    /// and `.gz`, `.zst` or `.bz2` files when their features are enabled; use a
+    /// [`Loader`](crate::format::Loader) to add backends for other formats.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use datavalue_rs::{Bump, Document};
+    ///
+    /// let arena = Bump::new();
+    /// let doc = Document::load(&arena, "users.csv").unwrap();
+    /// println!("{} users", doc.root().as_array().map_or(0, |a| a.len()));
+    /// ```
+    pub fn load(arena: &'a Bump, path: impl AsRef<Path>) -> Result<Self> {
+        Loader::new().load(arena, path)
+    }
+
+    pub(crate) fn from_parts(
+        root: &'a DataValue<'a>,
+        spans: SpanTable,
+        source: &'a str,
+        format: Format,
+    ) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Document {
+            root,
+            spans,
+            line_starts,
+            source,
+            format,
+            compression: None,
+            style: TextStyle::detect(source),
+            key_spans: SpanTable::new(),
+            lossless: false,
+            report: ParseReport::fixed(ParseStrategy::Copying, source),
+            id: NEXT_DOCUMENT_ID.fetch_add(1, Ordering::Relaxed),
+            nodes: OnceLock::new(),
+        }
+    }
+
+    /// Records the compression of the file the document was loaded from
+    pub(crate) fn with_compression(mut self, compression: Option<Compression>) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Adds the object key spans recorded by the parser, and whether the options
+    /// it ran with were lossless
+    pub(crate) fn with_key_spans(mut self, key_spans: SpanTable, options: &ParseOptions) -> Self {
+        self.key_spans = key_spans;
+        self.lossless = options.is_lossless();
+        self
+    }
+
+    /// Writes the document to `path`, replacing the file atomically
+    ///
+    /// The document is written in the format and layout it was loaded with
+    /// (indentation and trailing newline) unless `options` override them. It is
+    /// compressed as the extension of `path` says, so a document loaded from
+    /// `data.json.gz` is gzipped again when saved there; a path without an
+    /// extension keeps the compression the document was loaded with. The contents
+    /// go to a temporary file next to `path` that is then renamed over it, so
+    /// readers see either the old or the new file, never a partial one. The file
+    /// keeps the permissions of the one it replaces.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use datavalue_rs::format::SaveOptions;
+    /// use datavalue_rs::{Bump, Document};
+    ///
+    /// let arena = Bump::new();
+    /// let doc = Document::load(&arena, "config.json").unwrap();
+    /// doc.save("config.json", &SaveOptions::new()).unwrap();
+    /// doc.save("config.min.json", &SaveOptions::new().compact()).unwrap();
+    /// ```
+    pub fn save(&self, path: impl AsRef<Path>, options: &SaveOptions) -> Result<()> {
+        save_document(self, path.as_ref(), options)
+    }
+
+    /// Returns the root value of the document
+    pub fn root(&self) -> &'a DataValue<'a> {
+        self.root
+    }
+
+    /// Returns the source text the document was parsed from
+    pub fn source(&self) -> &'a str {
+        self.source
+    }
+
+    /// Returns the format the document was parsed from
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// Returns the compression of the file the document was loaded from, if any
+    pub fn compression(&self) -> Option<Compression> {
+        self.compression
+    }
+
+    /// Returns the layout detected in the source text
+    pub fn style(&self) -> &TextStyle {
+        &self.style
+    }
+
+    /// Returns the strategy the document was parsed with
+    ///
+    /// Documents from [`Document::parse_auto`] also carry the sample statistics
+    /// the strategy was chosen from; the other constructors always copy strings.
+    pub fn parse_report(&self) -> &ParseReport {
+        &self.report
+    }
+
+    /// Returns true if the document was parsed with [lossless](ParseOptions::is_lossless)
+    /// options, so its values carry every detail of the source
+    pub fn is_lossless(&self) -> bool {
+        self.lossless
+    }
+
+    /// Serializes the document, reusing the source text of every node it came from
+    ///
+    /// Strings and keys keep their original escapes, numbers their original
+    /// spelling, and objects their key order and duplicate keys. The layout follows
+    /// [`Document::style`]: compact, or one entry per line with the detected
+    /// indentation. Nodes without a span (for example in documents loaded from
+    /// CSV) are written as compact JSON.
+    pub fn to_string_lossless(&self) -> String {
+        let mut output = String::with_capacity(self.source.len());
+        let layout = Layout {
+            indent: self.style.indent.as_deref(),
+            ..Layout::default()
+        };
+        // Writing to a String cannot fail
+        let _ = write_tree(
+            self.root,
+            layout,
+            &mut output,
+            |leaf, output| {
+                match self.span_of(leaf) {
+                    Some(span) => output.push_str(&self.source[span]),
+                    None => {
+                        let mut bytes = Vec::new();
+                        // Writing to a Vec cannot fail, and the output is valid UTF-8
+                        if write_json_scalar(leaf, &mut bytes).is_ok() {
+                            output.push_str(&String::from_utf8_lossy(&bytes));
+                        }
+                    }
+                }
+                Ok(())
+            },
+            |entry, output| {
+                match self.key_spans.get(&(entry as *const _ as usize)) {
+                    Some(span) => output.push_str(&self.source[span.clone()]),
+                    None => push_json_string(entry.0, output),
+                }
+                Ok(())
+            },
+        );
+        if self.style.trailing_newline {
+            output.push('\n');
+        }
+        output
+    }
+
+    /// Returns true if [`Document::to_string_lossless`] reproduces the source exactly
+    ///
+    /// This holds for lossless documents whose whitespace matches the compact or
+    /// indented layout the writer produces. Use it to check that a document can be
+    /// re-emitted without changing bytes nobody touched.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use datavalue_rs::{Bump, Document};
+    ///
+    /// let arena = Bump::new();
+    /// let source = "{\n    \"price\": 10.50,\n    \"tags\": []\n}\n";
+    /// assert!(Document::parse_lossless(&arena, source).unwrap().is_lossless_roundtrip());
+    ///
+    /// // Default options parse 10.50 as a float, but the source text is still reused
+    /// assert!(Document::parse(&arena, source).unwrap().is_lossless_roundtrip());
+    ///
+    /// // Irregular whitespace cannot be reproduced
+    /// assert!(!Document::parse_lossless(&arena, "[1,  2]").unwrap().is_lossless_roundtrip());
+    /// ```
+    pub fn is_lossless_roundtrip(&self) -> bool {
+        self.to_string_lossless() == self.source
+    }
+
+    /// Returns the byte range the node was parsed from
+    ///
+    /// Returns None if the node does not belong to this document, or if the
+    /// document was loaded from a format that does not track spans.
+    pub fn span_of(&self, node: &DataValue<'_>) -> Option<Range<usize>> {
+        self.spans
+            .get(&(node as *const DataValue<'_> as usize))
+            .cloned()
+    }
+
+    /// Returns the 1-based line and column where the node starts
+    ///
+    /// Columns count characters, not bytes.
+    pub fn location_of(&self, node: &DataValue<'_>) -> Option<(usize, usize)> {
+        self.span_of(node).map(|span| self.line_col(span.start))
+    }
+
+    /// Returns the byte range of the node addressed by a JSON pointer
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use datavalue_rs::{Bump, Document};
+    ///
+    /// let arena = Bump::new();
+    /// let doc = Document::parse(&arena, r#"{"a": {"b": true}}"#).unwrap();
+    /// assert_eq!(doc.span_of_pointer("/a/b"), Some(12..16));
+    /// assert_eq!(doc.span_of_pointer("/missing"), None);
+    /// ```
+    pub fn span_of_pointer(&self, pointer: &str) -> Option<Range<usize>> {
+        self.root
+            .pointer(pointer)
+            .and_then(|node| self.span_of(node))
+    }
+
+    /// Returns a handle to a node of this document
+    ///
+    /// Like [`Document::span_of`], this works for any reference obtained by
+    /// navigating from [`Document::root`], and returns None for other values,
+    /// including clones. The first call indexes every node of the document.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    ///
+    /// use datavalue_rs::{Bump, Document};
+    ///
+    /// let arena = Bump::new();
+    /// let doc = Document::parse(&arena, r#"{"users": [{"id": "a1"}, {"id": "b2"}]}"#).unwrap();
+    ///
+    /// // Index users by id without borrowing the document in the map
+    /// let mut by_id = HashMap::new();
+    /// for user in doc.root()["users"].as_array().unwrap() {
+    ///     by_id.insert(user["id"].as_str().unwrap(), doc.handle(user).unwrap());
+    /// }
+    ///
+    /// let user = doc.resolve(by_id["b2"]).unwrap();
+    /// assert!(std::ptr::eq(user, &doc.root()["users"][1]));
+    /// ```
+    pub fn handle(&self, node: &DataValue<'_>) -> Option<NodeHandle> {
+        let index = *self
+            .node_table()
+            .positions
+            .get(&(node as *const DataValue<'_> as usize))?;
+        Some(NodeHandle {
+            document: self.id,
+            index,
+        })
+    }
+
+    /// Returns the node a handle refers to
+    ///
+    /// Returns None if the handle was created by another document.
+    pub fn resolve(&self, handle: NodeHandle) -> Option<&'a DataValue<'a>> {
+        if handle.document != self.id {
+            return None;
+        }
+        self.node_table().resolve(self.root, handle.index)
+    }
+
+    fn node_table(&self) -> &NodeTable {
+        self.nodes.get_or_init(|| NodeTable::build(self.root))
+    }
+
+    /// Checks the structural invariants that the rest of the crate relies on
+    ///
+    /// The check is meant for trees assembled by other means, such as custom format
+    /// backends or values built with `unsafe` or FFI code, and verifies that:
+    ///
+    /// - every float is finite, since NaN and infinities have no JSON form
+    /// - every raw number and raw value holds valid JSON
+    /// - nesting is at most 1024 levels deep
+    /// - every recorded span lies within the source, on character boundaries
+    ///
+    /// Parsed documents can fail too: a source nested deeper than 1024 levels, or
+    /// one read with [`crate::NonFiniteFloats::Literal`], builds a tree that breaks
+    /// these invariants.
+    ///
+    /// Strings and references into the arena need no check: `&str` is always valid
+    /// UTF-8, and the `'a` lifetime already guarantees that they outlive neither the
+    /// arena nor the source.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error naming the JSON pointer of the first node that breaks an
+    /// invariant.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use datavalue_rs::{Bump, Document};
+    ///
+    /// let arena = Bump::new();
+    /// let doc = Document::parse(&arena, r#"{"a": [1, "x", {"b": null}]}"#).unwrap();
+    /// assert!(doc.validate_invariants().is_ok());
+    /// ```
+    pub fn validate_invariants(&self) -> Result<()> {
+        let scratch = Bump::new();
+        let mut stack = vec![(self.root, String::new(), 0)];
+        while let Some((node, pointer, depth)) = stack.pop() {
+            let violated = |msg: &str| {
+                let pointer = if pointer.is_empty() { "/" } else { &pointer };
+                Err(Error::custom(format!(
+                    "Invariant violated at {}: {}",
+                    pointer, msg
+                )))
+            };
+            if depth > MAX_VALID_DEPTH {
+                return violated(&format!("nested deeper than {} levels", MAX_VALID_DEPTH));
+            }
+            if let Some(span) = self.span_of(node) {
+                let inside = span.start <= span.end
+                    && span.end <= self.source.len()
+                    && self.source.is_char_boundary(span.start)
+                    && self.source.is_char_boundary(span.end);
+                if !inside {
+                    return violated(&format!("span {:?} is outside the source", span));
+                }
+            }
+            match node {
+                DataValue::Number(Number::Float(f)) if !f.is_finite() => {
+                    return violated(&format!("float {} is not finite", f));
+                }
+                DataValue::Number(Number::Raw(text)) => {
+                    let parsed = DataValue::Raw(text).parse_raw(&scratch);
+                    if !matches!(parsed, Ok(DataValue::Number(_))) {
+                        return violated(&format!("raw number {:?} is not a JSON number", text));
+                    }
+                }
+                DataValue::Raw(text) if DataValue::Raw(text).parse_raw(&scratch).is_err() => {
+                    return violated("raw value is not valid JSON");
+                }
+                DataValue::Array(items) => {
+                    for (i, item) in items.iter().enumerate().rev() {
+                        stack.push((item, format!("{}/{}", pointer, i), depth + 1));
+                    }
+                }
+                DataValue::Object(entries) => {
+                    for (key, value) in entries.iter().rev() {
+                        let pointer = format!("{}/{}", pointer, escape_pointer_token(key));
+                        stack.push((value, pointer, depth + 1));
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Panics if [`Document::validate_invariants`] fails, in debug builds only
+    ///
+    /// Like `debug_assert!`, this costs nothing in release builds, so it can be
+    /// called after every document a custom backend produces.
+    pub fn debug_validate(&self) {
+        #[cfg(debug_assertions)]
+        if let Err(e) = self.validate_invariants() {
+            panic!("{}", e);
+        }
+    }
+
+    /// Converts a byte offset into a 1-based line and column
+    pub fn line_col(&self, offset: usize) -> (usize, usize) {
+        let line = self.line_starts.partition_point(|&start| start <= offset);
+        let line_start = self.line_starts[line - 1];
+        let end = offset.min(self.source.len());
+        let column = self
+            .source
+            .get(line_start..end)
+            .map_or(end - line_start, |s| s.chars().count());
+        (line, column + 1)
+    }
+}
+
+/// JSON text shared between long-lived [`NodeRef`] handles
+///
+/// Arena-backed values cannot outlive their arena, so a selection like
+/// `&root["data"]["items"][3]` is lost once the arena or root goes out of scope. A
+/// `SharedDocument` keeps the validated source text behind an `Arc` instead, along
+/// with an index of where every node lies in it, and the handles it hands out
+/// re-materialize just their subtree in whatever arena the caller has at hand.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::{Bump, SharedDocument};
+///
+/// let item = {
+///     let doc = SharedDocument::parse(r#"{"data": {"items": [1, 2, 3, {"id": 7}]}}"#).unwrap();
+///     doc.node("/data/items/3").unwrap()
+/// };
+///
+/// // The document variable is gone, but the handle still resolves
+/// let arena = Bump::new();
+/// assert_eq!(item.path(), "/data/items/3");
+/// assert_eq!(item.resolve(&arena).unwrap()["id"].as_i64(), Some(7));
+/// ```
+#[derive(Debug, Clone)]
+pub struct SharedDocument {
+    source: Arc<str>,
+    options: Arc<ParseOptions>,
+    index: Arc<SharedIndex>,
+}
+
+/// Where each node of a [`SharedDocument`] lies in the source, built by the one
+/// parse that validates it
+#[derive(Debug)]
+struct SharedIndex {
+    /// Source span of each node, and whether it is an array
+    nodes: Vec<(Option<Range<usize>>, bool)>,
+    /// Child position by parent position and unescaped pointer token; a repeated
+    /// key maps to its first entry, like [`DataValue::pointer`]
+    children: HashMap<(usize, String), usize>,
+}
+
+impl SharedIndex {
+    fn build(document: &Document<'_>) -> Self {
+        let root = document.root();
+        let mut nodes = vec![(document.span_of(root), root.is_array())];
+        let mut children = HashMap::new();
+        let mut stack = vec![(root, 0)];
+        while let Some((node, parent)) = stack.pop() {
+            let entries: Vec<(String, &DataValue<'_>)> = match node {
+                DataValue::Array(items) => items
+                    .iter()
+                    .enumerate()
+                    .map(|(i, item)| (i.to_string(), item))
+                    .collect(),
+                DataValue::Object(entries) => entries
+                    .iter()
+                    .map(|(key, value)| (key.to_string(), value))
+                    .collect(),
+                _ => continue,
+            };
+            for (token, child) in entries {
+                if let Entry::Vacant(slot) = children.entry((parent, token)) {
+                    slot.insert(nodes.len());
+                    stack.push((child, nodes.len()));
+                    nodes.push((document.span_of(child), child.is_array()));
+                }
+            }
+        }
+        SharedIndex { nodes, children }
+    }
+
+    /// Returns the position of a child, resolving array indexes the way
+    /// [`DataValue::pointer`] does
+    fn child(&self, parent: usize, token: &str) -> Option<usize> {
+        let token = if self.nodes[parent].1 {
+            token.parse::<usize>().ok()?.to_string()
+        } else {
+            token.to_string()
+        };
+        self.children.get(&(parent, token)).copied()
+    }
+}
+
+impl SharedDocument {
+    /// Validates JSON text and wraps it for sharing
+    ///
+    /// # Errors
+    ///
+    /// Returns a syntax error if the input is not valid JSON.
+    pub fn parse(source: impl Into<Arc<str>>) -> Result<Self> {
+        Self::parse_with_options(source, ParseOptions::default())
+    }
+
+    /// Validates JSON text with the given options and wraps it for sharing
+    ///
+    /// The options are reused every time a handle is resolved.
+    pub fn parse_with_options(source: impl Into<Arc<str>>, options: ParseOptions) -> Result<Self> {
+        let source = source.into();
+        let index = SharedIndex::build(&Document::parse_with_options(
+            &Bump::new(),
+            &source,
+            &options,
+        )?);
+        Ok(SharedDocument {
+            source,
+            options: Arc::new(options),
+            index: Arc::new(index),
+        })
+    }
+
+    /// Returns the source text
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Returns a handle to the root of the document
+    pub fn root(&self) -> NodeRef {
+        NodeRef {
+            document: self.clone(),
+            path: String::new(),
+            position: 0,
+        }
+    }
+
+    /// Returns a handle to the node addressed by a JSON pointer
+    ///
+    /// Returns None if the pointer does not resolve in this document.
+    pub fn node(&self, pointer: &str) -> Option<NodeRef> {
+        let mut position = 0;
+        if !pointer.is_empty() {
+            let tokens = pointer.strip_prefix('/')?.split('/');
+            for token in tokens {
+                position = self.index.child(position, &unescape_pointer_token(token))?;
+            }
+        }
+        Some(NodeRef {
+            document: self.clone(),
+            path: pointer.to_string(),
+            position,
+        })
+    }
+
+    /// Parses the whole document into `arena`
+    pub fn materialize<'a>(&self, arena: &'a Bump) -> Result<&'a DataValue<'a>> {
+        Parser::new(arena, &self.source, &self.options).parse_document()
+    }
+}
+
+/// A handle to a subtree of a [`SharedDocument`]
+///
+/// The handle owns a reference to the document text and the JSON pointer of the
+/// node, so it can be stored, cloned and sent across threads independently of any
+/// arena. The subtree is only materialized when [`NodeRef::resolve`] is called.
+#[derive(Debug, Clone)]
+pub struct NodeRef {
+    document: SharedDocument,
+    path: String,
+    /// Position of the node in the document's index
+    position: usize,
+}
+
+impl NodeRef {
+    /// Returns the JSON pointer of the node
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Returns the document the node belongs to
+    pub fn document(&self) -> &SharedDocument {
+        &self.document
+    }
+
+    /// Returns a handle to a child of this node
+    ///
+    /// `token` is an object key or array index; it is escaped as needed. Returns
+    /// None if the child does not exist.
+    pub fn child(&self, token: &str) -> Option<NodeRef> {
+        let position = self.document.index.child(self.position, token)?;
+        Some(NodeRef {
+            document: self.document.clone(),
+            path: format!("{}/{}", self.path, escape_pointer_token(token)),
+            position,
+        })
+    }
+
+    /// Materializes the node in `arena`
+    ///
+    /// Each call parses the node's text into the arena, so resolve once and keep
+    /// the result while the arena is alive.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the pointer does not resolve. Handles created through
+    /// [`SharedDocument`] are validated up front, so this does not happen in practice.
+    pub fn resolve<'a>(&self, arena: &'a Bump) -> Result<&'a DataValue<'a>> {
+        if let (Some(span), _) = &self.document.index.nodes[self.position] {
+            let source = &self.document.source[span.clone()];
+            return Parser::new(arena, source, &self.document.options).parse_document();
+        }
+        self.document
+            .materialize(arena)?
+            .pointer(&self.path)
+            .ok_or_else(|| Error::missing_field(self.path.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_auto() {
+        let arena = Bump::new();
+        let source = r#"{"id": 1, "name": "plain", "note": "esc\"aped"}"#;
+        let doc = Document::parse_auto(&arena, source).unwrap();
+        assert_eq!(doc.root(), Document::parse(&arena, source).unwrap().root());
+        assert_eq!(doc.parse_report().strategy, ParseStrategy::Borrowing);
+        let name = doc.root()["name"].as_str().unwrap();
+        assert!(doc
+            .source()
+            .as_bytes()
+            .as_ptr_range()
+            .contains(&name.as_ptr()));
+        assert_eq!(doc.location_of(&doc.root()["note"]), Some((1, 36)));
+
+        let plain = Document::parse(&arena, source).unwrap();
+        assert_eq!(plain.parse_report().strategy, ParseStrategy::Copying);
+        assert_eq!(plain.parse_report().sample, None);
+
+        let large = format!(
+            "[{}\"end\"]",
+            "\"lorem ipsum dolor sit amet\", ".repeat(4000)
+        );
+        let doc = Document::parse_auto(&arena, &large).unwrap();
+        assert_eq!(doc.root().as_array().map(|a| a.len()), Some(4001));
+        assert_eq!(doc.parse_report().strategy, ParseStrategy::Borrowing);
+        assert!(doc.span_of(&doc.root()[0]).is_some());
+        assert!(Document::parse_auto(&arena, &large[1..]).is_err());
+    }
+
+    #[test]
+    fn test_spans_cover_every_node() {
+        let arena = Bump::new();
+        let source = r#"[{"k": "v\"é"}, -1.5e3, null, [true]]"#;
+        let doc = Document::parse(&arena, source).unwrap();
+        let root = doc.root();
+
+        assert_eq!(doc.span_of(root), Some(0..source.len()));
+        assert_eq!(&source[doc.span_of(&root[0]["k"]).unwrap()], r#""v\"é""#);
+        assert_eq!(&source[doc.span_of(&root[1]).unwrap()], "-1.5e3");
+        assert_eq!(&source[doc.span_of(&root[2]).unwrap()], "null");
+        assert_eq!(&source[doc.span_of(&root[3][0]).unwrap()], "true");
+
+        // Clones are not part of the document
+        let copy = root[1].clone();
+        assert_eq!(doc.span_of(&copy), None);
+    }
+
+    #[test]
+    fn test_node_handles() {
+        let arena = Bump::new();
+        let doc = Document::parse(&arena, r#"[{"k": [1, 2]}, "x", {"k": [1, 2]}]"#).unwrap();
+        let other = Document::parse(&arena, r#"[{"k": [1, 2]}, "x", {"k": [1, 2]}]"#).unwrap();
+        let root = doc.root();
+
+        let nodes = [
+            root,
+            &root[0],
+            &root[0]["k"],
+            &root[0]["k"][1],
+            &root[1],
+            &root[2]["k"][0],
+        ];
+        let handles: Vec<NodeHandle> = nodes.iter().map(|n| doc.handle(n).unwrap()).collect();
+        for (node, handle) in nodes.iter().zip(&handles) {
+            assert!(std::ptr::eq(doc.resolve(*handle).unwrap(), *node));
+            assert_eq!(other.resolve(*handle), None);
+        }
+        // Equal subtrees at different positions get different handles
+        assert_ne!(doc.handle(&root[0]), doc.handle(&root[2]));
+        assert!(handles.windows(2).all(|w| w[0] < w[1]));
+
+        assert_eq!(doc.handle(&root[1].clone()), None);
+        assert_eq!(doc.handle(&other.root()[1]), None);
+    }
+
+    #[test]
+    fn test_lossless_roundtrip() {
+        let arena = Bump::new();
+        let sources = [
+            r#"{"\u0041":"a\/b","n":[-0.0,1E+2,12345678901234567890],"n":{},"e":[ ]}"#,
+            "{\n\t\"a\": [\n\t\t1.50,\n\t\t{\n\t\t\t\"\\\"q\\\"\": null\n\t\t}\n\t],\n\t\"b\": \"\\u00e9\"\n}\n",
+            "\"\\ud83d\\ude00\"",
+        ];
+        for source in sources {
+            let doc = Document::parse_lossless(&arena, source).unwrap();
+            assert!(doc.is_lossless());
+            assert_eq!(doc.to_string_lossless(), source);
+            assert!(doc.is_lossless_roundtrip());
+        }
+
+        // Options that drop duplicates cannot reproduce the source
+        let options = ParseOptions {
+            duplicate_keys: crate::DuplicateKeyPolicy::FirstWins,
+            ..ParseOptions::default()
+        };
+        let doc = Document::parse_with_options(&arena, r#"{"a":1,"a":2}"#, &options).unwrap();
+        assert!(!doc.is_lossless());
+        assert_eq!(doc.to_string_lossless(), r#"{"a":1}"#);
+        assert!(!doc.is_lossless_roundtrip());
+
+        // Whitespace outside the compact and indented layouts is normalized
+        let doc = Document::parse_lossless(&arena, "{ \"a\" : [1 ,2] }").unwrap();
+        assert_eq!(doc.to_string_lossless(), r#"{"a":[1,2]}"#);
+        assert!(!doc.is_lossless_roundtrip());
+    }
+
+    #[test]
+    fn test_validate_invariants() {
+        let arena = Bump::new();
+        let doc = Document::parse(
+            &arena,
+            r#"{"a/b": [1.5, "é", {"n": 12345678901234567890}]}"#,
+        )
+        .unwrap();
+        assert!(doc.validate_invariants().is_ok());
+        doc.debug_validate();
+        let lossless = Document::parse_lossless(&arena, r#"[1.0e3, -0]"#).unwrap();
+        assert!(lossless.validate_invariants().is_ok());
+
+        fn invalid(arena: &Bump, root: DataValue<'_>) -> String {
+            let root = arena.alloc(root);
+            let doc = Document::from_parts(root, SpanTable::new(), "", Format::Json);
+            doc.validate_invariants().unwrap_err().to_string()
+        }
+        let nan = [DataValue::Number(Number::Float(f64::NAN))];
+        let entries = [("a/b", DataValue::Array(&nan))];
+        let error = invalid(&arena, DataValue::Object(&entries));
+        assert!(
+            error.contains("/a~1b/0") && error.contains("not finite"),
+            "{}",
+            error
+        );
+        assert!(invalid(&arena, DataValue::Number(Number::Raw("1x"))).contains("raw number"));
+        assert!(invalid(&arena, DataValue::Raw("[1,")).contains("raw value"));
+
+        let deep = format!("{}{}", "[".repeat(1026), "]".repeat(1026));
+        let doc = Document::parse(&arena, &deep).unwrap();
+        assert!(doc
+            .validate_invariants()
+            .unwrap_err()
+            .to_string()
+            .contains("deeper"));
+        let options = ParseOptions {
+            non_finite: crate::NonFiniteFloats::Literal,
+            ..ParseOptions::default()
+        };
+        let doc = Document::parse_with_options(&arena, "[NaN]", &options).unwrap();
+        assert!(doc.validate_invariants().is_err());
+
+        let mut spans = SpanTable::new();
+        let root = arena.alloc(DataValue::Null);
+        spans.insert(root as *const DataValue<'_> as usize, 0..5);
+        let doc = Document::from_parts(root, spans, "null", Format::Json);
+        assert!(doc.validate_invariants().is_err());
+    }
+
+    #[test]
+    fn test_line_col() {
+        let arena = Bump::new();
+        let doc = Document::parse(&arena, "{\n\t\"é\": [\n  7]\n}").unwrap();
+        assert_eq!(doc.line_col(0), (1, 1));
+        assert_eq!(doc.location_of(&doc.root()["é"]), Some((2, 7)));
+        assert_eq!(doc.location_of(&doc.root()["é"][0]), Some((3, 3)));
+    }
+
+    #[test]
+    fn test_parse_error_location() {
+        let arena = Bump::new();
+        let err = Document::parse(&arena, "{\n  \"a\": [1, 2,]\n}").unwrap_err();
+        assert!(err.to_string().contains("line 2 column 14"), "{}", err);
+    }
+
+    #[test]
+    fn test_node_ref_outlives_arena() {
+        let node = {
+            let arena = Bump::new();
+            let doc = Document::parse(&arena, r#"{"a/b": [{"id": 1}, {"id": 2}]}"#).unwrap();
+            let shared = SharedDocument::parse(doc.source()).unwrap();
+            shared.root().child("a/b").unwrap().child("1").unwrap()
+        };
+        assert_eq!(node.path(), "/a~1b/1");
+
+        let arena = Bump::new();
+        let value = node.resolve(&arena).unwrap();
+        assert_eq!(value["id"].as_i64(), Some(2));
+        assert!(node.child("missing").is_none());
+        assert!(node.document().node("/nope").is_none());
+        assert!(SharedDocument::parse("{").is_err());
+    }
+
+    #[test]
+    fn test_node_ref_resolves_subtree() {
+        let shared =
+            SharedDocument::parse(r#"{"a": [1, "two", {"b": null}], "a": 0, "c": {"": 3.5}}"#)
+                .unwrap();
+        let arena = Bump::new();
+        let resolve = |pointer: &str| {
+            let value = shared.node(pointer)?.resolve(&arena).unwrap();
+            Some(crate::to_string(value))
+        };
+        assert_eq!(resolve("/a/1").as_deref(), Some(r#""two""#));
+        assert_eq!(resolve("/a/02/b").as_deref(), Some("null"));
+        assert_eq!(resolve("/c/").as_deref(), Some("3.5"));
+        assert_eq!(resolve("/c").as_deref(), Some(r#"{"":3.5}"#));
+        assert_eq!(resolve("/a/x"), None);
+        assert_eq!(resolve("a"), None);
+
+        let options = ParseOptions {
+            duplicate_keys: crate::DuplicateKeyPolicy::LastWins,
+            ..ParseOptions::default()
+        };
+        let shared = SharedDocument::parse_with_options(r#"{"k": 1, "k": 2}"#, options).unwrap();
+        let last = shared.root().child("k").unwrap();
+        assert_eq!(last.resolve(&arena).unwrap().as_i64(), Some(2));
+    }
+}
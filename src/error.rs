@@ -26,6 +26,11 @@ use std::fmt;
 pub enum Error {
     /// Syntax error during parsing
     Syntax(String),
+    /// Syntax error at a known position in the input
+    SyntaxAt {
+        msg: String,
+        location: Box<Location>,
+    },
     /// Expected a different type
     ExpectedType { expected: String, found: String },
     /// Missing a required field
@@ -40,6 +45,32 @@ pub enum Error {
     Json(String),
 }
 
+/// Where in the input a parse error was detected
+///
+/// # Example
+///
+/// ```
+/// # use datavalue_rs::{Bump, from_str};
+/// let arena = Bump::new();
+/// let err = from_str(&arena, "{\n  \"items\": [1, 2 3]\n}").unwrap_err();
+///
+/// let location = err.location().unwrap();
+/// assert_eq!((location.line, location.column), (2, 18));
+/// assert_eq!(location.byte_offset, 19);
+/// assert_eq!(location.path, "/items/1");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    /// 1-based line number
+    pub line: usize,
+    /// 1-based column, counted in characters
+    pub column: usize,
+    /// Byte offset from the start of the input
+    pub byte_offset: usize,
+    /// JSON pointer of the value being parsed, empty for the root
+    pub path: String,
+}
+
 impl Error {
     /// Create a new syntax error
     ///
@@ -140,12 +171,70 @@ impl Error {
     pub fn json(msg: impl Into<String>) -> Self {
         Error::Json(msg.into())
     }
+
+    /// Create a new syntax error at a known position in the input
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use datavalue_rs::{Error, Location};
+    /// let location = Location { line: 3, column: 7, byte_offset: 40, path: "/a/0".into() };
+    /// let err = Error::syntax_at("expected value", location);
+    /// assert_eq!(err.line(), Some(3));
+    /// assert_eq!(err.to_string(), "Syntax error: expected value at line 3 column 7 (path /a/0)");
+    /// ```
+    pub fn syntax_at(msg: impl Into<String>, location: Location) -> Self {
+        Error::SyntaxAt {
+            msg: msg.into(),
+            location: Box::new(location),
+        }
+    }
+
+    /// Returns where in the input the error was detected, if known
+    pub fn location(&self) -> Option<&Location> {
+        match self {
+            Error::SyntaxAt { location, .. } => Some(location),
+            _ => None,
+        }
+    }
+
+    /// Returns the 1-based line of the error, if known
+    pub fn line(&self) -> Option<usize> {
+        self.location().map(|location| location.line)
+    }
+
+    /// Returns the 1-based column of the error, if known
+    pub fn column(&self) -> Option<usize> {
+        self.location().map(|location| location.column)
+    }
+
+    /// Returns the byte offset of the error in the input, if known
+    pub fn byte_offset(&self) -> Option<usize> {
+        self.location().map(|location| location.byte_offset)
+    }
+
+    /// Returns the JSON pointer of the value being parsed when the error was
+    /// detected, if known
+    pub fn path(&self) -> Option<&str> {
+        self.location().map(|location| location.path.as_str())
+    }
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Error::Syntax(msg) => write!(f, "Syntax error: {}", msg),
+            Error::SyntaxAt { msg, location } => {
+                write!(
+                    f,
+                    "Syntax error: {} at line {} column {}",
+                    msg, location.line, location.column
+                )?;
+                if !location.path.is_empty() {
+                    write!(f, " (path {})", location.path)?;
+                }
+                Ok(())
+            }
             Error::ExpectedType { expected, found } => {
                 write!(f, "Expected {}, found {}", expected, found)
             }
@@ -173,12 +262,27 @@ impl From<std::io::Error> for Error {
     }
 }
 
+#[cfg(feature = "serde_json-compat")]
 impl From<serde_json::Error> for Error {
     fn from(err: serde_json::Error) -> Self {
         Error::Json(err.to_string())
     }
 }
 
+#[cfg(feature = "serde_json-compat")]
+impl serde::ser::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error::Custom(msg.to_string())
+    }
+}
+
+#[cfg(feature = "serde_json-compat")]
+impl serde::de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error::Custom(msg.to_string())
+    }
+}
+
 /// A specialized Result type for DataValue operations
 ///
 /// This type is used throughout the library for functions that can fail.
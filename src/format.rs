@@ -0,0 +1,1362 @@
+//! Data file formats and the loader that dispatches between them
+//!
+//! [`Loader`] picks a format from a file extension or HTTP content type, undoes
+//! compression, and parses the text into a [`Document`]. JSON, NDJSON and CSV are
+//! built in, as is HOCON configuration through the [`hocon`] reader. YAML and
+//! TOML are read and written by the `format::yaml` and `format::toml` modules
+//! when the `yaml` and `toml` features are enabled, and the `gzip`, `zstd` and
+//! `bzip2` features decompress `.gz`, `.zst` and `.bz2` files. Other parsers and
+//! codecs can be plugged in through the [`FormatBackend`] and [`Decompressor`]
+//! traits, and formats this crate does not know about at all are added at
+//! runtime with a [`FormatPlugin`].
+//!
+//! Documents remember the format and layout they were loaded with, so
+//! [`Document::save`] writes them back the same way.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Write;
+use std::path::Path;
+
+use bumpalo::Bump;
+
+use crate::datavalue::DataValue;
+use crate::de::events::{read_value, value_events, JsonEvent};
+use crate::de::{ParseOptions, Parser, SpanTable};
+use crate::document::Document;
+use crate::error::{Error, Result};
+use crate::ser::{to_string, write_pretty};
+
+#[cfg(feature = "arrow")]
+pub mod arrow;
+#[cfg(feature = "avro")]
+pub mod avro;
+#[cfg(feature = "flexbuffers")]
+pub mod flexbuffers;
+pub mod hocon;
+#[cfg(feature = "toml")]
+pub mod toml;
+#[cfg(feature = "yaml")]
+pub mod yaml;
+
+/// A data file format
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Format {
+    /// A single JSON value
+    Json,
+    /// One JSON value per line, loaded as an array
+    Ndjson,
+    /// Comma-separated values with a header row, loaded as an array of objects
+    Csv,
+    /// YAML, built in with the `yaml` feature
+    Yaml,
+    /// TOML, built in with the `toml` feature
+    Toml,
+    /// HOCON configuration, with includes and substitutions resolved on load
+    Hocon,
+    /// A format added by a [`FormatPlugin`], identified by its name
+    Other(&'static str),
+}
+
+impl Format {
+    /// Returns the format for a file extension, ignoring case
+    pub fn from_extension(extension: &str) -> Option<Format> {
+        match extension.to_ascii_lowercase().as_str() {
+            "json" => Some(Format::Json),
+            "ndjson" | "jsonl" => Some(Format::Ndjson),
+            "csv" => Some(Format::Csv),
+            "yaml" | "yml" => Some(Format::Yaml),
+            "toml" => Some(Format::Toml),
+            "conf" | "hocon" => Some(Format::Hocon),
+            _ => None,
+        }
+    }
+
+    /// Returns the format for an HTTP content type, ignoring parameters
+    ///
+    /// Structured syntax suffixes such as `application/geo+json` are recognized.
+    pub fn from_content_type(content_type: &str) -> Option<Format> {
+        let mime = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        match mime.as_str() {
+            "application/x-ndjson" | "application/ndjson" | "application/jsonl" => {
+                Some(Format::Ndjson)
+            }
+            "application/json" | "text/json" => Some(Format::Json),
+            "text/csv" => Some(Format::Csv),
+            "application/yaml" | "application/x-yaml" | "text/yaml" => Some(Format::Yaml),
+            "application/toml" => Some(Format::Toml),
+            "application/hocon" => Some(Format::Hocon),
+            _ if mime.ends_with("+json") => Some(Format::Json),
+            _ if mime.ends_with("+yaml") => Some(Format::Yaml),
+            _ => None,
+        }
+    }
+
+    /// Returns the format and compression implied by a path such as `data.csv.gz`
+    pub fn from_path(path: &Path) -> Option<(Format, Option<Compression>)> {
+        let extension = path.extension()?.to_str()?;
+        if let Some(compression) = Compression::from_extension(extension) {
+            let inner = Path::new(path.file_stem()?).extension()?.to_str()?;
+            return Format::from_extension(inner).map(|format| (format, Some(compression)));
+        }
+        Format::from_extension(extension).map(|format| (format, None))
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Format::Json => "json",
+            Format::Ndjson => "ndjson",
+            Format::Csv => "csv",
+            Format::Yaml => "yaml",
+            Format::Toml => "toml",
+            Format::Hocon => "hocon",
+            Format::Other(name) => name,
+        };
+        f.write_str(name)
+    }
+}
+
+/// A compression codec wrapped around a data file
+///
+/// Each codec is built in with the cargo feature of the same name (`gzip`,
+/// `zstd` or `bzip2`); otherwise a [`Decompressor`] must be registered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Compression {
+    /// gzip (`.gz`)
+    Gzip,
+    /// Zstandard (`.zst`)
+    Zstd,
+    /// bzip2 (`.bz2`)
+    Bzip2,
+}
+
+impl Compression {
+    /// Returns the codec for a file extension, ignoring case
+    pub fn from_extension(extension: &str) -> Option<Compression> {
+        match extension.to_ascii_lowercase().as_str() {
+            "gz" | "gzip" => Some(Compression::Gzip),
+            "zst" | "zstd" => Some(Compression::Zstd),
+            "bz2" => Some(Compression::Bzip2),
+            _ => None,
+        }
+    }
+
+    /// Returns the cargo feature that builds in the codec
+    fn feature(self) -> &'static str {
+        match self {
+            Compression::Gzip => "gzip",
+            Compression::Zstd => "zstd",
+            Compression::Bzip2 => "bzip2",
+        }
+    }
+}
+
+/// Parses a text format into a DataValue
+pub trait FormatBackend {
+    /// Parses `text` into the arena
+    fn parse<'a>(&self, arena: &'a Bump, text: &str) -> Result<DataValue<'a>>;
+
+    /// Serializes a value back to text, used by [`Loader::save`]
+    ///
+    /// The default implementation reports that the format is read-only.
+    fn write(&self, value: &DataValue<'_>, style: &TextStyle) -> Result<String> {
+        let _ = (value, style);
+        Err(Error::custom(
+            "This format backend does not support writing",
+        ))
+    }
+}
+
+/// A format supplied by another crate, described in terms of [`JsonEvent`]s
+///
+/// Plugins only exchange events with the loader and never see the arena or the
+/// layout of [`DataValue`], so a plugin depends on nothing but this trait and
+/// [`JsonEvent`]. Registering one with [`Loader::plugin`] makes the loader
+/// recognize its extensions and content types, load it as [`Format::Other`] and
+/// save documents in it.
+///
+/// # Example
+///
+/// ```
+/// use std::borrow::Cow;
+///
+/// use datavalue_rs::de::events::JsonEvent;
+/// use datavalue_rs::format::{FormatPlugin, Loader, TextStyle};
+/// use datavalue_rs::{Bump, Result};
+///
+/// /// `key=value` lines, read as an object of strings
+/// struct Properties;
+///
+/// impl FormatPlugin for Properties {
+///     fn name(&self) -> &'static str {
+///         "properties"
+///     }
+///
+///     fn extensions(&self) -> &[&'static str] {
+///         &["properties"]
+///     }
+///
+///     fn parse_events<'s>(&self, text: &'s str) -> Result<Vec<JsonEvent<'s>>> {
+///         let mut events = vec![JsonEvent::StartObject];
+///         for (key, value) in text.lines().filter_map(|line| line.split_once('=')) {
+///             events.push(JsonEvent::Key(Cow::Borrowed(key)));
+///             events.push(JsonEvent::String(Cow::Borrowed(value)));
+///         }
+///         events.push(JsonEvent::EndObject);
+///         Ok(events)
+///     }
+/// }
+///
+/// let arena = Bump::new();
+/// let loader = Loader::new().plugin(Properties);
+/// let (format, _) = loader.format_for_path("app.properties".as_ref()).unwrap();
+/// let doc = loader.load_bytes(&arena, b"port=8080\n", format, None).unwrap();
+/// assert_eq!(doc.root()["port"].as_str(), Some("8080"));
+/// ```
+pub trait FormatPlugin {
+    /// Name of the format, which must be unique among registered plugins
+    fn name(&self) -> &'static str;
+
+    /// File extensions of the format, without the leading dot
+    fn extensions(&self) -> &[&'static str];
+
+    /// HTTP content types of the format, without parameters
+    fn content_types(&self) -> &[&'static str] {
+        &[]
+    }
+
+    /// Parses `text` into the events of exactly one value
+    fn parse_events<'s>(&self, text: &'s str) -> Result<Vec<JsonEvent<'s>>>;
+
+    /// Writes the events of one value as text, used when saving
+    ///
+    /// The default implementation reports that the format is read-only.
+    fn write_events(&self, events: &[JsonEvent<'_>], style: &TextStyle) -> Result<String> {
+        let _ = (events, style);
+        Err(Error::custom(format!(
+            "The {} format plugin does not support writing",
+            self.name()
+        )))
+    }
+}
+
+/// Adapts a [`FormatPlugin`] to the [`FormatBackend`] the loader dispatches to
+struct PluginBackend<P>(P);
+
+impl<P: FormatPlugin> FormatBackend for PluginBackend<P> {
+    fn parse<'a>(&self, arena: &'a Bump, text: &str) -> Result<DataValue<'a>> {
+        let mut events = self.0.parse_events(text)?.into_iter().map(Ok);
+        let value = read_value(arena, &mut events)?.ok_or_else(|| {
+            Error::syntax(format!(
+                "The {} format plugin produced no value",
+                self.0.name()
+            ))
+        })?;
+        if events.next().is_some() {
+            return Err(Error::syntax(format!(
+                "The {} format plugin produced more than one value",
+                self.0.name()
+            )));
+        }
+        Ok(value)
+    }
+
+    fn write(&self, value: &DataValue<'_>, style: &TextStyle) -> Result<String> {
+        self.0.write_events(&value_events(value)?, style)
+    }
+}
+
+/// Layout of a text file, detected at load time and reused when saving
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct TextStyle {
+    /// One level of indentation, or None for compact single-line output
+    pub indent: Option<String>,
+    /// Whether the file ends with a newline
+    pub trailing_newline: bool,
+}
+
+impl TextStyle {
+    /// Detects the layout of JSON text
+    ///
+    /// The indentation unit is taken from the first indented line, so files
+    /// written with two spaces, four spaces or tabs are saved the same way.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use datavalue_rs::format::TextStyle;
+    ///
+    /// let style = TextStyle::detect("{\n    \"a\": 1\n}\n");
+    /// assert_eq!(style.indent.as_deref(), Some("    "));
+    /// assert!(style.trailing_newline);
+    ///
+    /// assert_eq!(TextStyle::detect(r#"{"a": 1}"#).indent, None);
+    /// ```
+    pub fn detect(source: &str) -> Self {
+        let body = source.trim();
+        let indent = body
+            .lines()
+            .skip(1)
+            .map(|line| &line[..line.len() - line.trim_start().len()])
+            .find(|prefix| !prefix.is_empty())
+            .map(str::to_string);
+        TextStyle {
+            indent,
+            trailing_newline: source.ends_with('\n'),
+        }
+    }
+}
+
+/// Options for [`Document::save`] and [`Loader::save`]
+///
+/// By default the document is written in the format and layout it was loaded
+/// with; each setting can be overridden.
+#[derive(Debug, Clone, Default)]
+pub struct SaveOptions {
+    format: Option<Format>,
+    compression: Option<Option<Compression>>,
+    indent: Option<Option<String>>,
+    trailing_newline: Option<bool>,
+}
+
+impl SaveOptions {
+    /// Creates options that preserve the original format and layout
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Writes the document in another format
+    pub fn format(mut self, format: Format) -> Self {
+        self.format = Some(format);
+        self
+    }
+
+    /// Compresses the output with `compression`, or writes it uncompressed for
+    /// `None`, whatever the extension of the path
+    pub fn compression(mut self, compression: Option<Compression>) -> Self {
+        self.compression = Some(compression);
+        self
+    }
+
+    /// Pretty-prints JSON with `indent` as one level of indentation
+    pub fn indent(mut self, indent: impl Into<String>) -> Self {
+        self.indent = Some(Some(indent.into()));
+        self
+    }
+
+    /// Writes JSON on a single line
+    pub fn compact(mut self) -> Self {
+        self.indent = Some(None);
+        self
+    }
+
+    /// Sets whether the file ends with a newline
+    pub fn trailing_newline(mut self, trailing_newline: bool) -> Self {
+        self.trailing_newline = Some(trailing_newline);
+        self
+    }
+
+    /// Returns the format, layout and compression to write `document` to `path` with
+    ///
+    /// Unless overridden, the compression follows the extension of `path`, and the
+    /// document's own when the path has none.
+    fn resolve(
+        &self,
+        document: &Document<'_>,
+        path: &Path,
+    ) -> (Format, TextStyle, Option<Compression>) {
+        let original = document.style();
+        let style = TextStyle {
+            indent: self
+                .indent
+                .clone()
+                .unwrap_or_else(|| original.indent.clone()),
+            trailing_newline: self.trailing_newline.unwrap_or(original.trailing_newline),
+        };
+        let compression = self.compression.unwrap_or_else(|| {
+            match path.extension().and_then(|extension| extension.to_str()) {
+                Some(extension) => Compression::from_extension(extension),
+                None => document.compression(),
+            }
+        });
+        (self.format.unwrap_or(document.format()), style, compression)
+    }
+}
+
+/// Undoes a compression codec
+pub trait Decompressor {
+    /// Returns the decompressed bytes
+    fn decompress(&self, bytes: &[u8]) -> Result<Vec<u8>>;
+}
+
+/// Loads data files of any supported format into documents
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::format::{Format, Loader};
+/// use datavalue_rs::Bump;
+///
+/// let arena = Bump::new();
+/// let loader = Loader::new();
+///
+/// let csv = loader
+///     .load_bytes(&arena, b"name,age\nAda,36\n", Format::Csv, None)
+///     .unwrap();
+/// assert_eq!(csv.root()[0]["name"].as_str(), Some("Ada"));
+///
+/// let format = Format::from_content_type("application/x-ndjson; charset=utf-8").unwrap();
+/// let events = loader.load_bytes(&arena, b"{\"n\": 1}\n{\"n\": 2}\n", format, None).unwrap();
+/// assert_eq!(events.root()[1]["n"].as_i64(), Some(2));
+/// ```
+#[derive(Default)]
+pub struct Loader {
+    options: ParseOptions,
+    backends: HashMap<Format, Box<dyn FormatBackend>>,
+    decompressors: HashMap<Compression, Box<dyn Decompressor>>,
+    /// Lowercased extensions and content types of registered plugins
+    plugin_extensions: HashMap<String, Format>,
+    plugin_content_types: HashMap<String, Format>,
+}
+
+impl Loader {
+    /// Creates a loader with the built-in formats and codecs
+    ///
+    /// JSON, NDJSON, CSV and HOCON are always available; YAML, TOML and the
+    /// compression codecs depend on the enabled features.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the options used for JSON and NDJSON input
+    pub fn options(mut self, options: ParseOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Registers a parser for a format, replacing the built-in one if any
+    pub fn backend(mut self, format: Format, backend: impl FormatBackend + 'static) -> Self {
+        self.backends.insert(format, Box::new(backend));
+        self
+    }
+
+    /// Registers a plugin for a format this crate does not know about
+    ///
+    /// The plugin's extensions and content types take precedence over the
+    /// built-in ones in [`Loader::format_for_path`] and
+    /// [`Loader::format_for_content_type`], and documents loaded through it have
+    /// the format `Format::Other(plugin.name())`.
+    pub fn plugin(mut self, plugin: impl FormatPlugin + 'static) -> Self {
+        let format = Format::Other(plugin.name());
+        for extension in plugin.extensions() {
+            self.plugin_extensions
+                .insert(extension.to_ascii_lowercase(), format);
+        }
+        for content_type in plugin.content_types() {
+            self.plugin_content_types
+                .insert(content_type.to_ascii_lowercase(), format);
+        }
+        self.backends
+            .insert(format, Box::new(PluginBackend(plugin)));
+        self
+    }
+
+    /// Returns the format and compression for a path, including plugin formats
+    ///
+    /// See [`Format::from_path`].
+    pub fn format_for_path(&self, path: &Path) -> Option<(Format, Option<Compression>)> {
+        let extension = path.extension()?.to_str()?;
+        let (extension, compression) = match Compression::from_extension(extension) {
+            Some(compression) => (
+                Path::new(path.file_stem()?).extension()?.to_str()?,
+                Some(compression),
+            ),
+            None => (extension, None),
+        };
+        let format = self
+            .plugin_extensions
+            .get(&extension.to_ascii_lowercase())
+            .copied()
+            .or_else(|| Format::from_extension(extension))?;
+        Some((format, compression))
+    }
+
+    /// Returns the format for an HTTP content type, including plugin formats
+    ///
+    /// See [`Format::from_content_type`].
+    pub fn format_for_content_type(&self, content_type: &str) -> Option<Format> {
+        let mime = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        self.plugin_content_types
+            .get(&mime)
+            .copied()
+            .or_else(|| Format::from_content_type(content_type))
+    }
+
+    /// Registers a decompressor for a codec, replacing the built-in one if any
+    pub fn decompressor(
+        mut self,
+        compression: Compression,
+        decompressor: impl Decompressor + 'static,
+    ) -> Self {
+        self.decompressors
+            .insert(compression, Box::new(decompressor));
+        self
+    }
+
+    /// Reads and parses a file, choosing the format from its extension
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the extension is not recognized, the file cannot be
+    /// read, no backend or decompressor is registered for it, or parsing fails.
+    pub fn load<'a>(&self, arena: &'a Bump, path: impl AsRef<Path>) -> Result<Document<'a>> {
+        let path = path.as_ref();
+        let (format, compression) = self
+            .format_for_path(path)
+            .ok_or_else(|| Error::custom(format!("Unknown data format for {}", path.display())))?;
+        let bytes = std::fs::read(path)?;
+        self.parse(arena, &bytes, format, compression, path.parent())
+    }
+
+    /// Parses bytes in a known format, for example an HTTP response body
+    ///
+    /// HOCON includes are resolved relative to the working directory.
+    pub fn load_bytes<'a>(
+        &self,
+        arena: &'a Bump,
+        bytes: &[u8],
+        format: Format,
+        compression: Option<Compression>,
+    ) -> Result<Document<'a>> {
+        self.parse(arena, bytes, format, compression, None)
+    }
+
+    /// Parses bytes, resolving HOCON includes relative to `dir` if given
+    fn parse<'a>(
+        &self,
+        arena: &'a Bump,
+        bytes: &[u8],
+        format: Format,
+        compression: Option<Compression>,
+        dir: Option<&Path>,
+    ) -> Result<Document<'a>> {
+        let decompressed;
+        let bytes = match compression {
+            Some(compression) => {
+                decompressed = match self.decompressors.get(&compression) {
+                    Some(decompressor) => decompressor.decompress(bytes)?,
+                    None => decompress(compression, bytes)?,
+                };
+                &decompressed[..]
+            }
+            None => bytes,
+        };
+        let document = self.parse_text(arena, bytes, format, dir)?;
+        Ok(document.with_compression(compression))
+    }
+
+    /// Parses uncompressed bytes, resolving HOCON includes relative to `dir` if given
+    fn parse_text<'a>(
+        &self,
+        arena: &'a Bump,
+        bytes: &[u8],
+        format: Format,
+        dir: Option<&Path>,
+    ) -> Result<Document<'a>> {
+        let text = std::str::from_utf8(bytes)
+            .map_err(|e| Error::syntax(format!("Invalid UTF-8: {}", e)))?;
+        let source: &'a str = arena.alloc_str(text);
+
+        if let Some(backend) = self.backends.get(&format) {
+            let root = arena.alloc(backend.parse(arena, source)?);
+            return Ok(Document::from_parts(root, SpanTable::new(), source, format));
+        }
+        let (root, spans) = match format {
+            Format::Json => {
+                let mut parser = Parser::new(arena, source, &self.options).record_spans();
+                let root = parser.parse_document()?;
+                let document = Document::from_parts(root, parser.take_spans(), source, format);
+                return Ok(document.with_key_spans(parser.take_key_spans(), &self.options));
+            }
+            Format::Ndjson => parse_ndjson(arena, source, &self.options)?,
+            Format::Csv => (
+                arena.alloc(parse_csv(arena, source)?) as &_,
+                SpanTable::new(),
+            ),
+            Format::Hocon => {
+                let parser = match dir {
+                    Some(dir) => hocon::HoconParser::new().base_dir(dir),
+                    None => hocon::HoconParser::new(),
+                };
+                (
+                    arena.alloc(parser.parse(arena, source)?) as &_,
+                    SpanTable::new(),
+                )
+            }
+            #[cfg(feature = "yaml")]
+            Format::Yaml => (
+                arena.alloc(yaml::from_str(arena, source)?) as &_,
+                SpanTable::new(),
+            ),
+            #[cfg(feature = "toml")]
+            Format::Toml => (
+                arena.alloc(toml::from_str(arena, source)?) as &_,
+                SpanTable::new(),
+            ),
+            _ => return Err(no_backend(format)),
+        };
+        Ok(Document::from_parts(root, spans, source, format))
+    }
+
+    /// Writes a document back to disk, using registered backends where available
+    ///
+    /// Like [`Document::save`], but formats with a registered backend or plugin
+    /// are serialized through [`FormatBackend::write`]. Compression uses the
+    /// built-in encoders.
+    pub fn save(
+        &self,
+        document: &Document<'_>,
+        path: impl AsRef<Path>,
+        options: &SaveOptions,
+    ) -> Result<()> {
+        let path = path.as_ref();
+        let (format, style, compression) = options.resolve(document, path);
+        let text = match self.backends.get(&format) {
+            Some(backend) => backend.write(document.root(), &style)?,
+            None => render(document.root(), format, &style)?,
+        };
+        write_compressed(path, text.as_bytes(), compression)
+    }
+}
+
+/// Writes a document with the built-in serializers, see [`Document::save`]
+pub(crate) fn save_document(
+    document: &Document<'_>,
+    path: &Path,
+    options: &SaveOptions,
+) -> Result<()> {
+    let (format, style, compression) = options.resolve(document, path);
+    let text = render(document.root(), format, &style)?;
+    write_compressed(path, text.as_bytes(), compression)
+}
+
+/// Compresses `contents` if asked to, then replaces the file at `path` with them
+fn write_compressed(path: &Path, contents: &[u8], compression: Option<Compression>) -> Result<()> {
+    match compression {
+        Some(compression) => write_atomic(path, &compress(compression, contents)?),
+        None => write_atomic(path, contents),
+    }
+}
+
+/// Serializes a value in one of the built-in formats
+fn render(value: &DataValue<'_>, format: Format, style: &TextStyle) -> Result<String> {
+    let mut output = String::new();
+    match format {
+        // JSON is valid HOCON
+        Format::Json | Format::Hocon => match &style.indent {
+            Some(unit) => write_pretty(value, unit, &mut output),
+            None => output.push_str(&to_string(value)),
+        },
+        Format::Ndjson => {
+            let mut lines = Vec::new();
+            crate::ser::ndjson::write_lines(&mut lines, rows(value)?)?;
+            return String::from_utf8(lines).map_err(|e| Error::custom(e.to_string()));
+        }
+        Format::Csv => write_csv(rows(value)?, &mut output)?,
+        #[cfg(feature = "yaml")]
+        Format::Yaml => output.push_str(&yaml::to_string(value)?),
+        #[cfg(feature = "toml")]
+        Format::Toml => output.push_str(&toml::to_string(value)?),
+        _ => return Err(no_backend(format)),
+    }
+    if style.trailing_newline && !output.ends_with('\n') {
+        output.push('\n');
+    }
+    Ok(output)
+}
+
+/// The error for a format that is neither built in nor registered
+fn no_backend(format: Format) -> Error {
+    match format {
+        Format::Yaml | Format::Toml => Error::custom(format!(
+            "No backend registered for {}; enable the {} feature",
+            format, format
+        )),
+        _ => Error::custom(format!("No backend registered for {}", format)),
+    }
+}
+
+/// Applies a compression codec with the built-in encoder enabled by its feature
+fn compress(compression: Compression, bytes: &[u8]) -> Result<Vec<u8>> {
+    match compression {
+        #[cfg(feature = "gzip")]
+        Compression::Gzip => {
+            let mut encoder =
+                flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(bytes)?;
+            Ok(encoder.finish()?)
+        }
+        #[cfg(feature = "zstd")]
+        Compression::Zstd => Ok(ruzstd::encoding::compress_to_vec(
+            bytes,
+            ruzstd::encoding::CompressionLevel::Fastest,
+        )),
+        #[cfg(feature = "bzip2")]
+        Compression::Bzip2 => {
+            let mut encoder =
+                bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+            encoder.write_all(bytes)?;
+            Ok(encoder.finish()?)
+        }
+        #[allow(unreachable_patterns)]
+        _ => {
+            let _ = bytes;
+            Err(Error::custom(format!(
+                "No compressor for {:?}; enable the {} feature",
+                compression,
+                compression.feature()
+            )))
+        }
+    }
+}
+
+/// Undoes a compression codec with the built-in decoder enabled by its feature
+fn decompress(compression: Compression, bytes: &[u8]) -> Result<Vec<u8>> {
+    #[cfg(any(feature = "gzip", feature = "zstd", feature = "bzip2"))]
+    use std::io::Read;
+
+    match compression {
+        #[cfg(feature = "gzip")]
+        Compression::Gzip => {
+            let mut output = Vec::new();
+            flate2::read::MultiGzDecoder::new(bytes).read_to_end(&mut output)?;
+            Ok(output)
+        }
+        #[cfg(feature = "zstd")]
+        Compression::Zstd => {
+            // A file may hold several frames, decoded one after another
+            let mut output = Vec::new();
+            let mut input = bytes;
+            while !input.is_empty() {
+                ruzstd::decoding::StreamingDecoder::new(&mut input)
+                    .map_err(|e| Error::custom(format!("Invalid zstd data: {}", e)))?
+                    .read_to_end(&mut output)?;
+            }
+            Ok(output)
+        }
+        #[cfg(feature = "bzip2")]
+        Compression::Bzip2 => {
+            let mut output = Vec::new();
+            bzip2::read::MultiBzDecoder::new(bytes).read_to_end(&mut output)?;
+            Ok(output)
+        }
+        #[allow(unreachable_patterns)]
+        _ => {
+            let _ = bytes;
+            Err(Error::custom(format!(
+                "No decompressor registered for {:?}; enable the {} feature",
+                compression,
+                compression.feature()
+            )))
+        }
+    }
+}
+
+fn rows<'v, 'a>(value: &'v DataValue<'a>) -> Result<&'v [DataValue<'a>]> {
+    value.as_array().ok_or_else(|| {
+        Error::expected_type("array", format!("{:?}", value.get_type()).to_lowercase())
+    })
+}
+
+/// Writes an array of objects as CSV, with the union of keys as columns in
+/// first-seen order
+fn write_csv(rows: &[DataValue<'_>], output: &mut String) -> Result<()> {
+    let mut columns: Vec<&str> = Vec::new();
+    for row in rows {
+        let entries = row.as_object().ok_or_else(|| {
+            Error::expected_type("object", format!("{:?}", row.get_type()).to_lowercase())
+        })?;
+        for (key, _) in entries {
+            if !columns.contains(key) {
+                columns.push(key);
+            }
+        }
+    }
+
+    let mut push_record = |fields: &mut dyn Iterator<Item = String>| {
+        for (i, field) in fields.enumerate() {
+            if i > 0 {
+                output.push(',');
+            }
+            if field.contains([',', '"', '\n', '\r']) {
+                output.push('"');
+                output.push_str(&field.replace('"', "\"\""));
+                output.push('"');
+            } else {
+                output.push_str(&field);
+            }
+        }
+        output.push('\n');
+    };
+    push_record(&mut columns.iter().map(|c| c.to_string()));
+    for row in rows {
+        push_record(&mut columns.iter().map(|column| match row.get(column) {
+            None | Some(DataValue::Null) => String::new(),
+            Some(DataValue::String(s)) => s.to_string(),
+            Some(other) => to_string(other),
+        }));
+    }
+    Ok(())
+}
+
+/// Replaces the file at `path` by writing a temporary sibling and renaming it, so
+/// readers never observe a partially written file
+pub(crate) fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| Error::custom(format!("Not a file path: {}", path.display())))?;
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(file_name);
+    temp_name.push(format!(".{}.tmp", std::process::id()));
+    let temp = path.with_file_name(temp_name);
+
+    let result = (|| -> std::io::Result<()> {
+        let mut file = std::fs::File::create(&temp)?;
+        // Keep the permissions of the file being replaced, before anything is
+        // written to the more permissive new file
+        if let Ok(metadata) = std::fs::metadata(path) {
+            file.set_permissions(metadata.permissions())?;
+        }
+        file.write_all(contents)?;
+        file.sync_all()?;
+        std::fs::rename(&temp, path)
+    })();
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp);
+    }
+    Ok(result?)
+}
+
+/// Parses one JSON value per non-blank line, keeping spans relative to the whole text
+fn parse_ndjson<'a>(
+    arena: &'a Bump,
+    source: &'a str,
+    options: &ParseOptions,
+) -> Result<(&'a DataValue<'a>, SpanTable)> {
+    let mut values = Vec::new();
+    let mut spans = SpanTable::new();
+    let mut offset = 0;
+    for (number, line) in source.split_inclusive('\n').enumerate() {
+        let start = offset;
+        offset += line.len();
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut parser = Parser::new(arena, line, options).record_spans();
+        let value = parser
+            .parse_document()
+            .map_err(|e| Error::syntax(format!("line {}: {}", number + 1, e)))?;
+        for (node, span) in parser.take_spans() {
+            spans.insert(node, span.start + start..span.end + start);
+        }
+        values.push(value);
+    }
+
+    // Array elements are copies, so re-key the line roots by their final address
+    let items = arena.alloc_slice_fill_iter(values.iter().map(|v| (*v).clone()));
+    for (item, value) in items.iter().zip(&values) {
+        if let Some(span) = spans.remove(&(*value as *const DataValue<'a> as usize)) {
+            spans.insert(item as *const DataValue<'a> as usize, span);
+        }
+    }
+    let root = arena.alloc(DataValue::Array(items));
+    spans.insert(root as *const DataValue<'a> as usize, 0..source.len());
+    Ok((root, spans))
+}
+
+/// Parses RFC 4180 CSV with a header row into an array of objects with string values
+fn parse_csv<'a>(arena: &'a Bump, source: &'a str) -> Result<DataValue<'a>> {
+    let mut rows = csv_records(source)?.into_iter();
+    let header = match rows.next() {
+        Some(header) => header,
+        None => return Ok(DataValue::Array(&[])),
+    };
+    let keys: Vec<&'a str> = header.iter().map(|k| &*arena.alloc_str(k)).collect();
+
+    let mut objects = Vec::new();
+    for (number, row) in rows.enumerate() {
+        if row.len() != keys.len() {
+            return Err(Error::syntax(format!(
+                "CSV record {} has {} fields, expected {}",
+                number + 2,
+                row.len(),
+                keys.len()
+            )));
+        }
+        let entries: Vec<(&'a str, DataValue<'a>)> = keys
+            .iter()
+            .zip(row)
+            .map(|(key, field)| (*key, DataValue::String(arena.alloc_str(&field))))
+            .collect();
+        objects.push(DataValue::Object(arena.alloc_slice_clone(&entries)));
+    }
+    Ok(DataValue::Array(arena.alloc_slice_clone(&objects)))
+}
+
+fn csv_records(source: &str) -> Result<Vec<Vec<String>>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut chars = source.chars().peekable();
+    let mut quoted = false;
+    let mut at_field_start = true;
+
+    while let Some(c) = chars.next() {
+        if quoted {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => quoted = false,
+                _ => field.push(c),
+            }
+            continue;
+        }
+        match c {
+            '"' if at_field_start => {
+                quoted = true;
+                at_field_start = false;
+            }
+            ',' => {
+                record.push(std::mem::take(&mut field));
+                at_field_start = true;
+            }
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+                at_field_start = true;
+            }
+            _ => {
+                field.push(c);
+                at_field_start = false;
+            }
+        }
+    }
+    if quoted {
+        return Err(Error::syntax("Unterminated quoted CSV field"));
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct KeyValue;
+
+    impl FormatBackend for KeyValue {
+        fn parse<'a>(&self, arena: &'a Bump, text: &str) -> Result<DataValue<'a>> {
+            let entries: Vec<(&'a str, DataValue<'a>)> = text
+                .lines()
+                .filter_map(|line| line.split_once(": "))
+                .map(|(k, v)| (&*arena.alloc_str(k), DataValue::String(arena.alloc_str(v))))
+                .collect();
+            Ok(DataValue::Object(arena.alloc_slice_clone(&entries)))
+        }
+    }
+
+    /// `key=value` lines, written back in the same form
+    struct Properties;
+
+    impl FormatPlugin for Properties {
+        fn name(&self) -> &'static str {
+            "properties"
+        }
+
+        fn extensions(&self) -> &[&'static str] {
+            &["properties", "yaml"]
+        }
+
+        fn content_types(&self) -> &[&'static str] {
+            &["text/x-java-properties"]
+        }
+
+        fn parse_events<'s>(&self, text: &'s str) -> Result<Vec<JsonEvent<'s>>> {
+            let mut events = vec![JsonEvent::StartObject];
+            for line in text.lines() {
+                let (key, value) = line
+                    .split_once('=')
+                    .ok_or_else(|| Error::syntax(format!("no '=' in {:?}", line)))?;
+                events.push(JsonEvent::Key(key.into()));
+                events.push(JsonEvent::String(value.into()));
+            }
+            events.push(JsonEvent::EndObject);
+            Ok(events)
+        }
+
+        fn write_events(&self, events: &[JsonEvent<'_>], _style: &TextStyle) -> Result<String> {
+            let mut output = String::new();
+            for event in events {
+                match event {
+                    JsonEvent::Key(key) => output.push_str(&format!("{}=", key)),
+                    JsonEvent::String(s) => output.push_str(&format!("{}\n", s)),
+                    JsonEvent::Number(n) => {
+                        output.push_str(&format!("{}\n", DataValue::Number(*n)))
+                    }
+                    JsonEvent::StartObject | JsonEvent::EndObject => {}
+                    _ => return Err(Error::custom("properties values must be scalars")),
+                }
+            }
+            Ok(output)
+        }
+    }
+
+    struct Reverse;
+
+    impl Decompressor for Reverse {
+        fn decompress(&self, bytes: &[u8]) -> Result<Vec<u8>> {
+            Ok(bytes.iter().rev().copied().collect())
+        }
+    }
+
+    #[test]
+    fn test_format_detection() {
+        assert_eq!(
+            Format::from_path(Path::new("dir/events.JSONL")),
+            Some((Format::Ndjson, None))
+        );
+        assert_eq!(
+            Format::from_path(Path::new("data.csv.gz")),
+            Some((Format::Csv, Some(Compression::Gzip)))
+        );
+        assert_eq!(Format::from_path(Path::new("archive.gz")), None);
+        assert_eq!(
+            Format::from_path(Path::new("application.conf")),
+            Some((Format::Hocon, None))
+        );
+        assert_eq!(
+            Format::from_content_type("application/geo+json"),
+            Some(Format::Json)
+        );
+        assert_eq!(Format::from_content_type("text/plain"), None);
+    }
+
+    #[test]
+    fn test_csv_quoting() {
+        let records = csv_records("a,b\r\n\"x, \"\"y\"\"\",\n\"multi\nline\",z").unwrap();
+        assert_eq!(
+            records,
+            vec![
+                vec!["a", "b"],
+                vec!["x, \"y\"", ""],
+                vec!["multi\nline", "z"]
+            ]
+        );
+        assert!(csv_records("\"open").is_err());
+
+        let arena = Bump::new();
+        assert!(parse_csv(&arena, "a,b\n1\n").is_err());
+    }
+
+    #[test]
+    fn test_ndjson_spans_and_errors() {
+        let arena = Bump::new();
+        let loader = Loader::new();
+        let source = "{\"a\": 1}\n\n[true]\n";
+        let doc = loader
+            .load_bytes(&arena, source.as_bytes(), Format::Ndjson, None)
+            .unwrap();
+        assert_eq!(doc.format(), Format::Ndjson);
+        assert_eq!(doc.location_of(&doc.root()[1]), Some((3, 1)));
+        assert_eq!(doc.location_of(&doc.root()[1][0]), Some((3, 2)));
+
+        let err = loader
+            .load_bytes(&arena, b"{}\n{\n", Format::Ndjson, None)
+            .unwrap_err();
+        assert!(err.to_string().contains("line 2"), "{}", err);
+    }
+
+    #[test]
+    fn test_plugged_backends() {
+        let arena = Bump::new();
+        let plain = Loader::new();
+        #[cfg(not(feature = "yaml"))]
+        assert!(plain
+            .load_bytes(&arena, b"a: 1", Format::Yaml, None)
+            .is_err());
+
+        let loader = Loader::new()
+            .backend(Format::Yaml, KeyValue)
+            .decompressor(Compression::Gzip, Reverse);
+        let doc = loader
+            .load_bytes(&arena, b"1 :a", Format::Yaml, Some(Compression::Gzip))
+            .unwrap();
+        assert_eq!(doc.root()["a"].as_str(), Some("1"));
+        assert_eq!(doc.source(), "a: 1");
+        assert!(plain
+            .load_bytes(&arena, b"{}", Format::Json, Some(Compression::Zstd))
+            .is_err());
+    }
+
+    #[test]
+    #[cfg(all(feature = "yaml", feature = "toml"))]
+    fn test_builtin_yaml_and_toml() {
+        let arena = Bump::new();
+        let loader = Loader::new();
+        let doc = loader
+            .load_bytes(&arena, b"name: api\nports: [80, 443]\n", Format::Yaml, None)
+            .unwrap();
+        assert_eq!(doc.root()["ports"][1].as_i64(), Some(443));
+
+        let dir = std::env::temp_dir();
+        let toml_path = dir.join(format!("datavalue-load-{}.toml", std::process::id()));
+        std::fs::write(&toml_path, "[server]\nport = 8080\n").unwrap();
+        let doc = Document::load(&arena, &toml_path).unwrap();
+        std::fs::remove_file(&toml_path).unwrap();
+        assert_eq!(doc.format(), Format::Toml);
+        assert_eq!(doc.root()["server"]["port"].as_i64(), Some(8080));
+
+        // Saving keeps the format, and another format can be chosen
+        let yaml_path = dir.join(format!("datavalue-save-{}.yaml", std::process::id()));
+        doc.save(&yaml_path, &SaveOptions::new().format(Format::Yaml))
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&yaml_path).unwrap(),
+            "server:\n  port: 8080\n"
+        );
+        let reloaded = Document::load(&arena, &yaml_path).unwrap();
+        std::fs::remove_file(&yaml_path).unwrap();
+        assert_eq!(reloaded.root(), doc.root());
+    }
+
+    #[test]
+    #[cfg(all(feature = "gzip", feature = "zstd", feature = "bzip2"))]
+    fn test_builtin_decompression() {
+        use std::io::Write as _;
+
+        let json = br#"{"compressed": true}"#;
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gzip.write_all(json).unwrap();
+        let gzip = gzip.finish().unwrap();
+        let mut bzip2 = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+        bzip2.write_all(json).unwrap();
+        let bzip2 = bzip2.finish().unwrap();
+        let zstd = ruzstd::encoding::compress_to_vec(
+            &json[..],
+            ruzstd::encoding::CompressionLevel::Fastest,
+        );
+
+        let arena = Bump::new();
+        let loader = Loader::new();
+        for (bytes, compression) in [
+            (&gzip, Compression::Gzip),
+            (&bzip2, Compression::Bzip2),
+            (&zstd, Compression::Zstd),
+        ] {
+            let doc = loader
+                .load_bytes(&arena, bytes, Format::Json, Some(compression))
+                .unwrap();
+            assert_eq!(doc.root()["compressed"].as_bool(), Some(true));
+            assert!(loader
+                .load_bytes(&arena, json, Format::Json, Some(compression))
+                .is_err());
+        }
+
+        let path =
+            std::env::temp_dir().join(format!("datavalue-load-{}.json.gz", std::process::id()));
+        std::fs::write(&path, &gzip).unwrap();
+        let doc = Document::load(&arena, &path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(doc.root()["compressed"].as_bool(), Some(true));
+    }
+
+    #[test]
+    fn test_format_plugin() {
+        let arena = Bump::new();
+        let plain = Loader::new();
+        let loader = Loader::new().plugin(Properties);
+        let format = Format::Other("properties");
+        assert_eq!(plain.format_for_path(Path::new("a.properties")), None);
+        assert_eq!(
+            loader.format_for_path(Path::new("a.PROPERTIES.gz")),
+            Some((format, Some(Compression::Gzip)))
+        );
+        // Plugin extensions shadow the built-in ones
+        assert_eq!(
+            loader.format_for_path(Path::new("a.yaml")),
+            Some((format, None))
+        );
+        assert_eq!(
+            loader.format_for_content_type("text/x-java-properties; charset=utf-8"),
+            Some(format)
+        );
+        assert_eq!(
+            loader.format_for_content_type("application/json"),
+            Some(Format::Json)
+        );
+
+        let doc = loader
+            .load_bytes(&arena, b"host=example.org\nport=8080", format, None)
+            .unwrap();
+        assert_eq!(doc.format(), format);
+        assert_eq!(doc.root()["port"].as_str(), Some("8080"));
+        assert!(loader.load_bytes(&arena, b"broken", format, None).is_err());
+        assert!(plain.load_bytes(&arena, b"a=1", format, None).is_err());
+
+        let path = std::env::temp_dir().join(format!(
+            "datavalue-plugin-{}.properties",
+            std::process::id()
+        ));
+        let value = crate::from_str(&arena, r#"{"retries": 3, "mode": "fast"}"#).unwrap();
+        let saved = Document::from_parts(arena.alloc(value), SpanTable::new(), "", format);
+        loader.save(&saved, &path, &SaveOptions::new()).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "retries=3\nmode=fast\n"
+        );
+        let doc = loader.load(&arena, &path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(doc.root()["retries"].as_str(), Some("3"));
+        assert!(saved.save(&path, &SaveOptions::new()).is_err());
+    }
+
+    #[test]
+    fn test_load_file() {
+        let path = std::env::temp_dir().join(format!("datavalue-load-{}.json", std::process::id()));
+        std::fs::write(&path, r#"{"ok": true}"#).unwrap();
+        let arena = Bump::new();
+        let doc = Document::load(&arena, &path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(doc.root()["ok"].as_bool(), Some(true));
+        assert_eq!(doc.format(), Format::Json);
+        assert!(Document::load(&arena, "unknown.bin").is_err());
+    }
+
+    #[test]
+    fn test_save_preserves_layout() {
+        let dir = std::env::temp_dir();
+        let path = dir.join(format!("datavalue-save-{}.json", std::process::id()));
+        std::fs::write(&path, "{\n\t\"a\": [1, 2]\n}").unwrap();
+
+        let arena = Bump::new();
+        let doc = Document::load(&arena, &path).unwrap();
+        doc.save(&path, &SaveOptions::new()).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "{\n\t\"a\": [\n\t\t1,\n\t\t2\n\t]\n}"
+        );
+
+        doc.save(&path, &SaveOptions::new().compact().trailing_newline(true))
+            .unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "{\"a\":[1,2]}\n");
+        std::fs::remove_file(&path).unwrap();
+        assert!(doc
+            .save(dir.join("missing-dir/x.json"), &SaveOptions::new())
+            .is_err());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_save_keeps_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = std::env::temp_dir().join(format!("datavalue-mode-{}.json", std::process::id()));
+        std::fs::write(&path, r#"{"token": "x"}"#).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+        let arena = Bump::new();
+        let doc = Document::load(&arena, &path).unwrap();
+        doc.save(&path, &SaveOptions::new()).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[test]
+    fn test_save_compressed() {
+        let dir = std::env::temp_dir();
+        let arena = Bump::new();
+        let doc = Loader::new()
+            .load_bytes(&arena, br#"{"saved": [1, 2]}"#, Format::Json, None)
+            .unwrap();
+        assert_eq!(doc.compression(), None);
+        for extension in ["gz", "zst", "bz2"] {
+            let path = dir.join(format!(
+                "datavalue-save-{}.json.{}",
+                std::process::id(),
+                extension
+            ));
+            let compression = Compression::from_extension(extension);
+            let enabled = match compression {
+                Some(Compression::Gzip) => cfg!(feature = "gzip"),
+                Some(Compression::Zstd) => cfg!(feature = "zstd"),
+                Some(Compression::Bzip2) => cfg!(feature = "bzip2"),
+                None => unreachable!(),
+            };
+            let saved = doc.save(&path, &SaveOptions::new());
+            if !enabled {
+                assert!(saved.is_err());
+                assert!(!path.exists());
+                continue;
+            }
+            saved.unwrap();
+            assert_ne!(std::fs::read(&path).unwrap(), br#"{"saved":[1,2]}"#);
+            let loaded = Document::load(&arena, &path).unwrap();
+            assert_eq!(loaded.compression(), compression);
+            assert_eq!(loaded.root(), doc.root());
+
+            // Saving back to the same file compresses it again
+            loaded.save(&path, &SaveOptions::new().compact()).unwrap();
+            let reloaded = Document::load(&arena, &path).unwrap();
+            assert_eq!(reloaded.root(), doc.root());
+            std::fs::remove_file(&path).unwrap();
+
+            let plain = dir.join(format!("datavalue-save-plain-{}.json", std::process::id()));
+            loaded.save(&plain, &SaveOptions::new()).unwrap();
+            assert!(Document::load(&arena, &plain).is_ok());
+            std::fs::remove_file(&plain).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_render_rows() {
+        let arena = Bump::new();
+        let loader = Loader::new();
+        let doc = loader
+            .load_bytes(
+                &arena,
+                b"{\"a\": \"x,y\"}\n{\"b\": 2, \"a\": null}\n",
+                Format::Ndjson,
+                None,
+            )
+            .unwrap();
+        let style = TextStyle::default();
+        assert_eq!(
+            render(doc.root(), Format::Csv, &style).unwrap(),
+            "a,b\n\"x,y\",\n,2\n"
+        );
+        assert_eq!(
+            render(doc.root(), Format::Ndjson, &style).unwrap(),
+            "{\"a\":\"x,y\"}\n{\"b\":2,\"a\":null}\n"
+        );
+        assert!(render(&doc.root()[0], Format::Csv, &style).is_err());
+        assert!(render(doc.root(), Format::Toml, &style).is_err());
+    }
+}
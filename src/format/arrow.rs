@@ -0,0 +1,594 @@
+//! Apache Arrow record batches, enabled by the `arrow` feature
+//!
+//! Analytics engines such as DataFusion and Polars consume columnar
+//! [`RecordBatch`]es. [`to_record_batch`] turns an array of objects into one,
+//! with a column for every key and the schema inferred from the values, so JSON
+//! records can be loaded into those engines straight from the arena.
+//! [`from_record_batch`] converts query results back into an array of objects.
+//!
+//! Each column takes the type that fits all of its values: booleans, 64-bit
+//! integers, 64-bit floats when integers and floats are mixed, UTF-8 strings,
+//! UTC timestamps and durations in microseconds. Nested arrays and objects, and
+//! columns whose values have different types, are stored as JSON text in the
+//! canonical `arrow.json` extension type, which converts back into values.
+//!
+//! Arrow does not tell a null from a missing value: nulls and missing keys
+//! become null cells, and null cells are left out of the converted objects.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use arrow_array::builder::{
+    BooleanBuilder, DurationMicrosecondBuilder, Float64Builder, Int64Builder, StringBuilder,
+    TimestampMicrosecondBuilder,
+};
+use arrow_array::cast::AsArray;
+use arrow_array::types::{
+    DurationMicrosecondType, DurationMillisecondType, DurationNanosecondType, DurationSecondType,
+    Float32Type, Float64Type, Int16Type, Int32Type, Int64Type, Int8Type, TimestampMicrosecondType,
+    TimestampMillisecondType, TimestampNanosecondType, TimestampSecondType, UInt16Type, UInt32Type,
+    UInt64Type, UInt8Type,
+};
+use arrow_array::{
+    Array, ArrayRef, ArrowPrimitiveType, NullArray, RecordBatch, RecordBatchOptions,
+};
+use arrow_schema::extension::EXTENSION_TYPE_NAME_KEY;
+use arrow_schema::{DataType, Field, Schema, TimeUnit};
+use bumpalo::Bump;
+use chrono::{DateTime, Duration};
+
+use crate::datavalue::{DataValue, Number};
+use crate::de::from_str;
+use crate::error::{Error, Result};
+use crate::ser::write_json;
+
+/// Extension type name of columns holding JSON text
+const JSON_EXTENSION: &str = "arrow.json";
+
+/// Infers the schema [`to_record_batch`] would use for an array of objects
+///
+/// Columns are in the order their keys first appear. Every column is nullable.
+///
+/// # Errors
+///
+/// Returns an error if `value` is not an array of objects, or a raw value is
+/// not valid JSON.
+///
+/// # Example
+///
+/// ```
+/// use arrow_schema::DataType;
+/// use datavalue_rs::{format::arrow, from_str, Bump};
+///
+/// let arena = Bump::new();
+/// let rows = from_str(&arena, r#"[{"id": 1, "score": 2}, {"id": 2, "score": 0.5, "tag": "x"}]"#).unwrap();
+/// let schema = arrow::infer_schema(&rows).unwrap();
+/// assert_eq!(schema.field(0).data_type(), &DataType::Int64);
+/// assert_eq!(schema.field(1).data_type(), &DataType::Float64);
+/// assert_eq!(schema.field(2).name(), "tag");
+/// ```
+pub fn infer_schema(value: &DataValue<'_>) -> Result<Schema> {
+    let arena = Bump::new();
+    let rows = rows(&arena, value)?;
+    Ok(schema(&infer(&rows)))
+}
+
+/// Converts an array of objects into a record batch with one row per object
+///
+/// # Errors
+///
+/// Returns an error if `value` is not an array of objects, a raw value is not
+/// valid JSON, or a duration does not fit in 64-bit microseconds.
+///
+/// # Example
+///
+/// ```
+/// use arrow_array::cast::AsArray;
+/// use arrow_array::Array;
+/// use arrow_array::types::Int64Type;
+/// use datavalue_rs::{format::arrow, from_str, Bump};
+///
+/// let arena = Bump::new();
+/// let rows = from_str(&arena, r#"[{"user": "ada", "clicks": 3}, {"user": "bob"}]"#).unwrap();
+/// let batch = arrow::to_record_batch(&rows).unwrap();
+/// assert_eq!(batch.num_rows(), 2);
+///
+/// let clicks = batch.column_by_name("clicks").unwrap().as_primitive::<Int64Type>();
+/// assert_eq!(clicks.value(0), 3);
+/// assert!(clicks.is_null(1));
+/// ```
+pub fn to_record_batch(value: &DataValue<'_>) -> Result<RecordBatch> {
+    let arena = Bump::new();
+    let rows = rows(&arena, value)?;
+    let columns = infer(&rows);
+    let index: HashMap<&str, usize> = columns
+        .iter()
+        .enumerate()
+        .map(|(i, (name, _))| (*name, i))
+        .collect();
+
+    let mut builders: Vec<Builder> = columns.iter().map(|(_, column)| column.builder()).collect();
+    let mut cells: Vec<Option<&DataValue<'_>>> = vec![None; columns.len()];
+    for row in &rows {
+        // The first of duplicate keys wins, as with DataValue::get
+        for (name, value) in row.iter() {
+            let cell = &mut cells[index[name]];
+            if cell.is_none() {
+                *cell = Some(value);
+            }
+        }
+        for (builder, cell) in builders.iter_mut().zip(&mut cells) {
+            builder.append(cell.take())?;
+        }
+    }
+
+    let arrays: Vec<ArrayRef> = builders.into_iter().map(Builder::finish).collect();
+    let options = RecordBatchOptions::new().with_row_count(Some(rows.len()));
+    RecordBatch::try_new_with_options(Arc::new(schema(&columns)), arrays, &options)
+        .map_err(|e| Error::custom(e.to_string()))
+}
+
+/// Converts a record batch into an array of objects in the arena
+///
+/// Integer, float, boolean, string, timestamp and duration columns of any
+/// width and unit are supported, as are `arrow.json` columns. Timestamps are
+/// read as UTC whatever their time zone, since Arrow stores them as UTC.
+///
+/// # Errors
+///
+/// Returns an error for columns of other types, such as nested lists and
+/// structs, for `arrow.json` cells that are not valid JSON, and for timestamps
+/// or durations outside the range of DateTime and Duration.
+///
+/// # Example
+///
+/// ```
+/// use std::sync::Arc;
+///
+/// use arrow_array::{ArrayRef, Int32Array, RecordBatch, StringArray};
+/// use datavalue_rs::{format::arrow, Bump};
+///
+/// let batch = RecordBatch::try_from_iter([
+///     ("city", Arc::new(StringArray::from(vec!["Oslo", "Lima"])) as ArrayRef),
+///     ("temp", Arc::new(Int32Array::from(vec![Some(-3), None])) as ArrayRef),
+/// ])
+/// .unwrap();
+///
+/// let arena = Bump::new();
+/// let rows = arrow::from_record_batch(&arena, &batch).unwrap();
+/// assert_eq!(datavalue_rs::to_string(&rows), r#"[{"city":"Oslo","temp":-3},{"city":"Lima"}]"#);
+/// ```
+pub fn from_record_batch<'a>(arena: &'a Bump, batch: &RecordBatch) -> Result<DataValue<'a>> {
+    let mut rows: Vec<Vec<(&'a str, DataValue<'a>)>> =
+        vec![Vec::with_capacity(batch.num_columns()); batch.num_rows()];
+    for (field, array) in batch.schema().fields().iter().zip(batch.columns()) {
+        let name: &'a str = arena.alloc_str(field.name());
+        for (row, cell) in rows.iter_mut().zip(column(arena, field, array)?) {
+            if let Some(value) = cell {
+                row.push((name, value));
+            }
+        }
+    }
+    let objects: Vec<DataValue<'a>> = rows
+        .into_iter()
+        .map(|row| DataValue::Object(arena.alloc_slice_fill_iter(row)))
+        .collect();
+    Ok(DataValue::Array(arena.alloc_slice_fill_iter(objects)))
+}
+
+/// Type of a column, widened as values are seen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Column {
+    Null,
+    Bool,
+    Int,
+    Float,
+    String,
+    DateTime,
+    Duration,
+    Json,
+}
+
+impl Column {
+    fn of(value: &DataValue<'_>) -> Self {
+        match value {
+            DataValue::Null => Column::Null,
+            DataValue::Bool(_) => Column::Bool,
+            DataValue::Number(_) if value.as_i64().is_some() => Column::Int,
+            DataValue::Number(_) => Column::Float,
+            DataValue::String(_) => Column::String,
+            DataValue::DateTime(_) => Column::DateTime,
+            DataValue::Duration(_) => Column::Duration,
+            DataValue::Array(_) | DataValue::Object(_) | DataValue::Raw(_) => Column::Json,
+        }
+    }
+
+    fn merge(self, other: Self) -> Self {
+        match (self, other) {
+            (a, b) if a == b => a,
+            (Column::Null, other) | (other, Column::Null) => other,
+            (Column::Int, Column::Float) | (Column::Float, Column::Int) => Column::Float,
+            _ => Column::Json,
+        }
+    }
+
+    fn field(self, name: &str) -> Field {
+        let data_type = match self {
+            Column::Null => DataType::Null,
+            Column::Bool => DataType::Boolean,
+            Column::Int => DataType::Int64,
+            Column::Float => DataType::Float64,
+            Column::String | Column::Json => DataType::Utf8,
+            Column::DateTime => DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+            Column::Duration => DataType::Duration(TimeUnit::Microsecond),
+        };
+        let field = Field::new(name, data_type, true);
+        if self == Column::Json {
+            field.with_metadata(HashMap::from([(
+                EXTENSION_TYPE_NAME_KEY.to_string(),
+                JSON_EXTENSION.to_string(),
+            )]))
+        } else {
+            field
+        }
+    }
+
+    fn builder(self) -> Builder {
+        match self {
+            Column::Null => Builder::Null(0),
+            Column::Bool => Builder::Bool(BooleanBuilder::new()),
+            Column::Int => Builder::Int(Int64Builder::new()),
+            Column::Float => Builder::Float(Float64Builder::new()),
+            Column::String => Builder::String(StringBuilder::new()),
+            Column::DateTime => {
+                Builder::DateTime(TimestampMicrosecondBuilder::new().with_timezone("UTC"))
+            }
+            Column::Duration => Builder::Duration(DurationMicrosecondBuilder::new()),
+            Column::Json => Builder::Json(StringBuilder::new(), Vec::new()),
+        }
+    }
+}
+
+/// Accumulates the cells of one column
+enum Builder {
+    Null(usize),
+    Bool(BooleanBuilder),
+    Int(Int64Builder),
+    Float(Float64Builder),
+    String(StringBuilder),
+    DateTime(TimestampMicrosecondBuilder),
+    Duration(DurationMicrosecondBuilder),
+    /// The cells and a buffer for writing each one as JSON
+    Json(StringBuilder, Vec<u8>),
+}
+
+impl Builder {
+    /// Appends a cell, which is null for null and missing values
+    ///
+    /// The column type was inferred from these values, so every non-null value
+    /// has the type of the builder.
+    fn append(&mut self, value: Option<&DataValue<'_>>) -> Result<()> {
+        let value = value.filter(|value| !value.is_null());
+        match self {
+            Builder::Null(len) => *len += 1,
+            Builder::Bool(builder) => builder.append_option(value.and_then(DataValue::as_bool)),
+            Builder::Int(builder) => builder.append_option(value.and_then(DataValue::as_i64)),
+            Builder::Float(builder) => builder.append_option(value.and_then(DataValue::as_f64)),
+            Builder::String(builder) => builder.append_option(value.and_then(DataValue::as_str)),
+            Builder::DateTime(builder) => builder.append_option(match value {
+                Some(DataValue::DateTime(dt)) => Some(dt.timestamp_micros()),
+                _ => None,
+            }),
+            Builder::Duration(builder) => match value {
+                Some(DataValue::Duration(dur)) => {
+                    let micros = dur.num_microseconds().ok_or_else(|| {
+                        Error::custom("Duration is too long for Arrow microseconds")
+                    })?;
+                    builder.append_value(micros);
+                }
+                _ => builder.append_null(),
+            },
+            Builder::Json(builder, text) => match value {
+                Some(value) => {
+                    text.clear();
+                    write_json(value, &mut *text)?;
+                    builder.append_value(std::str::from_utf8(text).expect("JSON output is UTF-8"));
+                }
+                None => builder.append_null(),
+            },
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> ArrayRef {
+        match self {
+            Builder::Null(len) => Arc::new(NullArray::new(len)),
+            Builder::Bool(mut builder) => Arc::new(builder.finish()),
+            Builder::Int(mut builder) => Arc::new(builder.finish()),
+            Builder::Float(mut builder) => Arc::new(builder.finish()),
+            Builder::String(mut builder) | Builder::Json(mut builder, _) => {
+                Arc::new(builder.finish())
+            }
+            Builder::DateTime(mut builder) => Arc::new(builder.finish()),
+            Builder::Duration(mut builder) => Arc::new(builder.finish()),
+        }
+    }
+}
+
+/// Returns the objects of an array, with raw items and entries parsed into `arena`
+fn rows<'s>(arena: &'s Bump, value: &DataValue<'s>) -> Result<Vec<&'s [(&'s str, DataValue<'s>)]>> {
+    let value = match value {
+        DataValue::Raw(text) => DataValue::Raw(text).parse_raw(arena)?,
+        value => value.clone(),
+    };
+    let DataValue::Array(items) = value else {
+        return Err(Error::expected_type(
+            "array",
+            format!("{:?}", value.get_type()).to_lowercase(),
+        ));
+    };
+
+    items
+        .iter()
+        .map(|item| {
+            let item = match item {
+                DataValue::Raw(text) => DataValue::Raw(text).parse_raw(arena)?,
+                item => item.clone(),
+            };
+            let DataValue::Object(entries) = item else {
+                return Err(Error::expected_type(
+                    "object",
+                    format!("{:?}", item.get_type()).to_lowercase(),
+                ));
+            };
+            if !entries
+                .iter()
+                .any(|(_, value)| matches!(value, DataValue::Raw(_)))
+            {
+                return Ok(entries);
+            }
+            let entries = entries
+                .iter()
+                .map(|(name, value)| match value {
+                    DataValue::Raw(text) => Ok((*name, DataValue::Raw(text).parse_raw(arena)?)),
+                    value => Ok((*name, value.clone())),
+                })
+                .collect::<Result<Vec<_>>>()?;
+            Ok(&*arena.alloc_slice_fill_iter(entries))
+        })
+        .collect()
+}
+
+/// Returns the columns of the rows in the order their keys first appear
+fn infer<'s>(rows: &[&'s [(&'s str, DataValue<'s>)]]) -> Vec<(&'s str, Column)> {
+    let mut columns: Vec<(&'s str, Column)> = Vec::new();
+    let mut index: HashMap<&'s str, usize> = HashMap::new();
+    for row in rows {
+        for (name, value) in row.iter() {
+            let column = Column::of(value);
+            match index.get(name) {
+                Some(&i) => columns[i].1 = columns[i].1.merge(column),
+                None => {
+                    index.insert(name, columns.len());
+                    columns.push((name, column));
+                }
+            }
+        }
+    }
+    columns
+}
+
+fn schema(columns: &[(&str, Column)]) -> Schema {
+    Schema::new(
+        columns
+            .iter()
+            .map(|(name, column)| column.field(name))
+            .collect::<Vec<_>>(),
+    )
+}
+
+/// Converts the cells of a column, with None for nulls
+fn column<'a>(
+    arena: &'a Bump,
+    field: &Field,
+    array: &dyn Array,
+) -> Result<Vec<Option<DataValue<'a>>>> {
+    let int = |i: i64| Some(DataValue::Number(Number::Integer(i)));
+    let float = |f: f64| Some(DataValue::Number(Number::Float(f)));
+    let string = |s: &str| Some(DataValue::String(arena.alloc_str(s)));
+    let datetime = |dt: Option<DateTime<chrono::Utc>>| dt.map(DataValue::DateTime);
+    let duration = |dur: Option<Duration>| dur.map(DataValue::Duration);
+
+    if field.extension_type_name() == Some(JSON_EXTENSION) {
+        let texts: Vec<Option<&str>> = match array.data_type() {
+            DataType::Utf8 => array.as_string::<i32>().iter().collect(),
+            DataType::LargeUtf8 => array.as_string::<i64>().iter().collect(),
+            DataType::Utf8View => array.as_string_view().iter().collect(),
+            other => {
+                return Err(Error::custom(format!(
+                    "Unsupported storage type {} for arrow.json column {}",
+                    other,
+                    field.name()
+                )))
+            }
+        };
+        return texts
+            .into_iter()
+            .map(|text| text.map(|text| from_str(arena, text)).transpose())
+            .collect();
+    }
+
+    let cells = match array.data_type() {
+        DataType::Null => vec![None; array.len()],
+        DataType::Boolean => array
+            .as_boolean()
+            .iter()
+            .map(|b| b.map(DataValue::Bool))
+            .collect(),
+        DataType::Int8 => primitive::<Int8Type>(array, |i| int(i.into()))?,
+        DataType::Int16 => primitive::<Int16Type>(array, |i| int(i.into()))?,
+        DataType::Int32 => primitive::<Int32Type>(array, |i| int(i.into()))?,
+        DataType::Int64 => primitive::<Int64Type>(array, int)?,
+        DataType::UInt8 => primitive::<UInt8Type>(array, |i| int(i.into()))?,
+        DataType::UInt16 => primitive::<UInt16Type>(array, |i| int(i.into()))?,
+        DataType::UInt32 => primitive::<UInt32Type>(array, |i| int(i.into()))?,
+        DataType::UInt64 => primitive::<UInt64Type>(array, |i| match i64::try_from(i) {
+            Ok(i) => int(i),
+            Err(_) => float(i as f64),
+        })?,
+        DataType::Float32 => primitive::<Float32Type>(array, |f| float(f.into()))?,
+        DataType::Float64 => primitive::<Float64Type>(array, float)?,
+        DataType::Utf8 => array
+            .as_string::<i32>()
+            .iter()
+            .map(|s| s.and_then(string))
+            .collect(),
+        DataType::LargeUtf8 => array
+            .as_string::<i64>()
+            .iter()
+            .map(|s| s.and_then(string))
+            .collect(),
+        DataType::Utf8View => array
+            .as_string_view()
+            .iter()
+            .map(|s| s.and_then(string))
+            .collect(),
+        DataType::Timestamp(TimeUnit::Second, _) => {
+            primitive::<TimestampSecondType>(array, |t| datetime(DateTime::from_timestamp(t, 0)))?
+        }
+        DataType::Timestamp(TimeUnit::Millisecond, _) => {
+            primitive::<TimestampMillisecondType>(array, |t| {
+                datetime(DateTime::from_timestamp_millis(t))
+            })?
+        }
+        DataType::Timestamp(TimeUnit::Microsecond, _) => {
+            primitive::<TimestampMicrosecondType>(array, |t| {
+                datetime(DateTime::from_timestamp_micros(t))
+            })?
+        }
+        DataType::Timestamp(TimeUnit::Nanosecond, _) => {
+            primitive::<TimestampNanosecondType>(array, |t| {
+                datetime(Some(DateTime::from_timestamp_nanos(t)))
+            })?
+        }
+        DataType::Duration(TimeUnit::Second) => {
+            primitive::<DurationSecondType>(array, |d| duration(Duration::try_seconds(d)))?
+        }
+        DataType::Duration(TimeUnit::Millisecond) => {
+            primitive::<DurationMillisecondType>(array, |d| {
+                duration(Duration::try_milliseconds(d))
+            })?
+        }
+        DataType::Duration(TimeUnit::Microsecond) => {
+            primitive::<DurationMicrosecondType>(array, |d| {
+                duration(Some(Duration::microseconds(d)))
+            })?
+        }
+        DataType::Duration(TimeUnit::Nanosecond) => {
+            primitive::<DurationNanosecondType>(array, |d| {
+                duration(Some(Duration::nanoseconds(d)))
+            })?
+        }
+        other => {
+            return Err(Error::custom(format!(
+                "Unsupported Arrow type {} for column {}",
+                other,
+                field.name()
+            )))
+        }
+    };
+    Ok(cells)
+}
+
+/// Converts the cells of a primitive column, failing if `convert` returns None
+fn primitive<'a, T: ArrowPrimitiveType>(
+    array: &dyn Array,
+    convert: impl Fn(T::Native) -> Option<DataValue<'a>>,
+) -> Result<Vec<Option<DataValue<'a>>>> {
+    array
+        .as_primitive::<T>()
+        .iter()
+        .map(|cell| {
+            cell.map(|native| {
+                convert(native).ok_or_else(|| Error::custom("Arrow value is out of range"))
+            })
+            .transpose()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_batch_round_trip() {
+        let arena = Bump::new();
+        let rows = from_str(
+            &arena,
+            r#"[
+                {"id": 1, "ok": true, "tags": ["a"], "mixed": 1, "gone": null},
+                {"id": 2, "ok": null, "meta": {"k": "v\"w"}, "mixed": "one", "id": 9},
+                {"id": 3, "score": 1.5}
+            ]"#,
+        )
+        .unwrap();
+
+        let batch = to_record_batch(&rows).unwrap();
+        assert_eq!(batch.num_rows(), 3);
+        let schema = batch.schema();
+        let types: Vec<(&str, &DataType)> = schema
+            .fields()
+            .iter()
+            .map(|f| (f.name().as_str(), f.data_type()))
+            .collect();
+        assert_eq!(
+            types,
+            [
+                ("id", &DataType::Int64),
+                ("ok", &DataType::Boolean),
+                ("tags", &DataType::Utf8),
+                ("mixed", &DataType::Utf8),
+                ("gone", &DataType::Null),
+                ("meta", &DataType::Utf8),
+                ("score", &DataType::Float64),
+            ]
+        );
+        assert_eq!(schema.field(3).extension_type_name(), Some(JSON_EXTENSION));
+        assert_eq!(schema.field(1).extension_type_name(), None);
+
+        let back = from_record_batch(&arena, &batch).unwrap();
+        let expected = r#"[
+            {"id": 1, "ok": true, "tags": ["a"], "mixed": 1},
+            {"id": 2, "mixed": "one", "meta": {"k": "v\"w"}},
+            {"id": 3, "score": 1.5}
+        ]"#;
+        assert_eq!(back, from_str(&arena, expected).unwrap());
+
+        let empty = to_record_batch(&from_str(&arena, "[{}, {}]").unwrap()).unwrap();
+        assert_eq!((empty.num_rows(), empty.num_columns()), (2, 0));
+        assert!(to_record_batch(&from_str(&arena, "[1]").unwrap()).is_err());
+        assert!(to_record_batch(&from_str(&arena, "{}").unwrap()).is_err());
+        let raw = DataValue::Raw(r#"[{"a": 1}]"#);
+        assert_eq!(to_record_batch(&raw).unwrap().num_columns(), 1);
+    }
+
+    #[test]
+    fn test_temporal_columns() {
+        let arena = Bump::new();
+        let when = DateTime::from_timestamp(1_700_000_000, 123_000).unwrap();
+        let items = [
+            ("at", DataValue::DateTime(when)),
+            ("took", DataValue::Duration(Duration::milliseconds(1500))),
+        ];
+        let row = DataValue::Object(arena.alloc_slice_fill_iter(items));
+        let rows = DataValue::Array(arena.alloc_slice_fill_iter([row]));
+
+        let batch = to_record_batch(&rows).unwrap();
+        assert_eq!(
+            batch.schema().field(0).data_type(),
+            &DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into()))
+        );
+        assert_eq!(from_record_batch(&arena, &batch).unwrap(), rows);
+    }
+}
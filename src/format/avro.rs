@@ -0,0 +1,1026 @@
+//! Apache Avro binary encoding, enabled by the `avro` feature
+//!
+//! [Avro](https://avro.apache.org/docs/current/specification/) data carries no
+//! field names or types: a datum can only be read with the schema it was written
+//! with. [`Schema::parse`] reads a schema from its JSON definition,
+//! [`from_slice`] decodes one datum with it and [`to_vec`] encodes one, so Avro
+//! records can be queried and transformed like any other DataValue.
+//! [`AvroCodec`] plugs this into the [`kafka`](crate::kafka) wire format, where
+//! each message names the id of its writer's schema.
+//!
+//! Avro types map onto DataValue as follows:
+//!
+//! | Avro | DataValue |
+//! |---|---|
+//! | `null`, `boolean` | [`DataValue::Null`], [`DataValue::Bool`] |
+//! | `int`, `long` | [`Number::Integer`] |
+//! | `float`, `double` | [`Number::Float`] |
+//! | `string`, `enum` | [`DataValue::String`] |
+//! | `bytes`, `fixed` | `{"$bytes": base64}` |
+//! | `record`, `map` | [`DataValue::Object`], with record fields in schema order |
+//! | `array` | [`DataValue::Array`] |
+//! | `long` with logical type `timestamp-millis` or `timestamp-micros` | [`DataValue::DateTime`] |
+//! | union | the value of the branch that was written |
+//!
+//! When encoding, a union takes the first branch the value fits, so `null`
+//! goes to a `"null"` branch and an object to the first record or map branch.
+//! Record fields missing from an object take their schema default. Other logical
+//! types, such as `decimal` and `date`, are read and written as their underlying
+//! type. Object container files, which add a header and blocks around many
+//! datums, are not supported.
+
+use std::collections::HashMap;
+
+use bumpalo::Bump;
+use chrono::DateTime;
+
+use crate::base64;
+use crate::datavalue::{DataValue, Number};
+use crate::de::from_str;
+use crate::error::{Error, Result};
+use crate::helpers::format_duration;
+use crate::kafka::PayloadCodec;
+use crate::ser::write_json;
+
+const BYTES_KEY: &str = "$bytes";
+
+/// A parsed Avro schema
+///
+/// Named types may refer to themselves, so recursive records such as linked
+/// lists and trees are supported.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::format::avro::{self, Schema};
+/// use datavalue_rs::Bump;
+///
+/// let list = Schema::parse(r#"{
+///     "type": "record",
+///     "name": "Node",
+///     "fields": [
+///         {"name": "value", "type": "int"},
+///         {"name": "next", "type": ["null", "Node"], "default": null}
+///     ]
+/// }"#).unwrap();
+///
+/// let arena = Bump::new();
+/// let value = avro::from_slice(&arena, &list, b"\x02\x02\x04\x00").unwrap();
+/// assert_eq!(value["next"]["value"].as_i64(), Some(2));
+/// assert!(value["next"]["next"].is_null());
+/// ```
+#[derive(Debug, Clone)]
+pub struct Schema {
+    nodes: Vec<Node>,
+    root: usize,
+}
+
+/// A schema type; child types are indexes into the nodes of the schema
+#[derive(Debug, Clone)]
+enum Node {
+    Null,
+    Boolean,
+    Int,
+    Long,
+    Float,
+    Double,
+    Bytes,
+    String,
+    TimestampMillis,
+    TimestampMicros,
+    Record(Vec<Field>),
+    Enum(Vec<String>),
+    Array(usize),
+    Map(usize),
+    Union(Vec<usize>),
+    Fixed(usize),
+}
+
+impl Node {
+    fn name(&self) -> &'static str {
+        match self {
+            Node::Null => "null",
+            Node::Boolean => "boolean",
+            Node::Int => "int",
+            Node::Long | Node::TimestampMillis | Node::TimestampMicros => "long",
+            Node::Float => "float",
+            Node::Double => "double",
+            Node::Bytes => "bytes",
+            Node::String => "string",
+            Node::Record(_) => "record",
+            Node::Enum(_) => "enum",
+            Node::Array(_) => "array",
+            Node::Map(_) => "map",
+            Node::Union(_) => "union",
+            Node::Fixed(_) => "fixed",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Field {
+    name: String,
+    node: usize,
+    /// The default value as JSON text
+    default: Option<String>,
+}
+
+impl Schema {
+    /// Parses a schema from its JSON definition
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the text is not JSON, or does not define a valid
+    /// schema: unknown type names, duplicate names, or records, enums and fixed
+    /// types without their required attributes.
+    pub fn parse(text: &str) -> Result<Self> {
+        let arena = Bump::new();
+        let definition = from_str(&arena, text)?;
+        let mut parser = SchemaParser {
+            nodes: Vec::new(),
+            names: HashMap::new(),
+        };
+        let root = parser.node(&definition, None)?;
+        Ok(Schema {
+            nodes: parser.nodes,
+            root,
+        })
+    }
+}
+
+/// Builds the nodes of a schema, remembering named types by full name
+struct SchemaParser {
+    nodes: Vec<Node>,
+    names: HashMap<String, usize>,
+}
+
+impl SchemaParser {
+    fn push(&mut self, node: Node) -> usize {
+        self.nodes.push(node);
+        self.nodes.len() - 1
+    }
+
+    /// Parses a type definition; schemas are small, so this recurses
+    fn node(&mut self, definition: &DataValue<'_>, namespace: Option<&str>) -> Result<usize> {
+        match definition {
+            DataValue::String(name) => self.named(name, namespace),
+            DataValue::Array(branches) => {
+                let branches = branches
+                    .iter()
+                    .map(|branch| self.node(branch, namespace))
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(self.push(Node::Union(branches)))
+            }
+            DataValue::Object(_) => self.complex(definition, namespace),
+            other => Err(invalid_schema(&format!(
+                "a type must be a name, array or object, not {}",
+                other
+            ))),
+        }
+    }
+
+    /// Resolves a primitive type name or a reference to a named type
+    fn named(&mut self, name: &str, namespace: Option<&str>) -> Result<usize> {
+        let node = match name {
+            "null" => Node::Null,
+            "boolean" => Node::Boolean,
+            "int" => Node::Int,
+            "long" => Node::Long,
+            "float" => Node::Float,
+            "double" => Node::Double,
+            "bytes" => Node::Bytes,
+            "string" => Node::String,
+            _ => {
+                let qualified = match namespace {
+                    Some(namespace) if !name.contains('.') => format!("{}.{}", namespace, name),
+                    _ => name.to_string(),
+                };
+                return self
+                    .names
+                    .get(&qualified)
+                    .or_else(|| self.names.get(name))
+                    .copied()
+                    .ok_or_else(|| invalid_schema(&format!("unknown type {}", name)));
+            }
+        };
+        Ok(self.push(node))
+    }
+
+    fn complex(&mut self, definition: &DataValue<'_>, namespace: Option<&str>) -> Result<usize> {
+        let kind = match definition.get("type") {
+            Some(DataValue::String(kind)) => *kind,
+            Some(nested) => return self.node(nested, namespace),
+            None => return Err(invalid_schema("a type object needs a type attribute")),
+        };
+        match kind {
+            "record" | "error" | "enum" | "fixed" => {}
+            "array" => {
+                let items = attribute(definition, "items")?;
+                let items = self.node(items, namespace)?;
+                return Ok(self.push(Node::Array(items)));
+            }
+            "map" => {
+                let values = attribute(definition, "values")?;
+                let values = self.node(values, namespace)?;
+                return Ok(self.push(Node::Map(values)));
+            }
+            _ => {
+                let node = match (kind, definition.get("logicalType").and_then(|t| t.as_str())) {
+                    ("long", Some("timestamp-millis")) => Node::TimestampMillis,
+                    ("long", Some("timestamp-micros")) => Node::TimestampMicros,
+                    _ => return self.named(kind, namespace),
+                };
+                return Ok(self.push(node));
+            }
+        }
+
+        // Named types are registered before their contents, so records can
+        // refer to themselves
+        let name = attribute(definition, "name")?
+            .as_str()
+            .ok_or_else(|| invalid_schema("a name must be a string"))?;
+        let namespace = match (name.rsplit_once('.'), definition.get("namespace")) {
+            (Some((namespace, _)), _) => Some(namespace),
+            (None, Some(DataValue::String(namespace))) if !namespace.is_empty() => Some(*namespace),
+            (None, Some(DataValue::String(_))) => None,
+            (None, _) => namespace,
+        };
+        let full_name = match namespace {
+            Some(namespace) if !name.contains('.') => format!("{}.{}", namespace, name),
+            _ => name.to_string(),
+        };
+        let index = self.push(Node::Null);
+        if self.names.insert(full_name, index).is_some() {
+            return Err(invalid_schema(&format!("{} is defined twice", name)));
+        }
+
+        let node = match kind {
+            "enum" => {
+                let symbols = attribute(definition, "symbols")?
+                    .as_array()
+                    .and_then(|symbols| {
+                        symbols
+                            .iter()
+                            .map(|s| s.as_str().map(str::to_string))
+                            .collect::<Option<Vec<_>>>()
+                    })
+                    .ok_or_else(|| invalid_schema("enum symbols must be an array of strings"))?;
+                Node::Enum(symbols)
+            }
+            "fixed" => {
+                let size = attribute(definition, "size")?
+                    .as_i64()
+                    .and_then(|size| usize::try_from(size).ok())
+                    .ok_or_else(|| invalid_schema("fixed size must be a non-negative integer"))?;
+                Node::Fixed(size)
+            }
+            _ => {
+                let fields = attribute(definition, "fields")?
+                    .as_array()
+                    .ok_or_else(|| invalid_schema("record fields must be an array"))?;
+                let fields = fields
+                    .iter()
+                    .map(|field| {
+                        let name = attribute(field, "name")?
+                            .as_str()
+                            .ok_or_else(|| invalid_schema("a field name must be a string"))?;
+                        let node = self.node(attribute(field, "type")?, namespace)?;
+                        let default = match field.get("default") {
+                            Some(default) => {
+                                let mut text = Vec::new();
+                                write_json(default, &mut text)?;
+                                Some(String::from_utf8(text).expect("JSON output is UTF-8"))
+                            }
+                            None => None,
+                        };
+                        Ok(Field {
+                            name: name.to_string(),
+                            node,
+                            default,
+                        })
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                Node::Record(fields)
+            }
+        };
+        self.nodes[index] = node;
+        Ok(index)
+    }
+}
+
+fn attribute<'v, 'a>(definition: &'v DataValue<'a>, name: &str) -> Result<&'v DataValue<'a>> {
+    definition
+        .get(name)
+        .ok_or_else(|| invalid_schema(&format!("missing {} attribute", name)))
+}
+
+fn invalid_schema(msg: &str) -> Error {
+    Error::custom(format!("Invalid Avro schema: {}", msg))
+}
+
+/// Decodes one Avro datum written with `schema`
+///
+/// # Errors
+///
+/// Returns a syntax error with the byte offset of the problem if the input is
+/// truncated or followed by more bytes, or holds a union branch, enum symbol,
+/// length or timestamp that is out of range, or a string that is not UTF-8.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::format::avro::{self, Schema};
+/// use datavalue_rs::Bump;
+///
+/// let schema = Schema::parse(r#"{"type": "record", "name": "Click", "fields": [
+///     {"name": "user", "type": "string"},
+///     {"name": "count", "type": "long"}
+/// ]}"#).unwrap();
+///
+/// let arena = Bump::new();
+/// let click = avro::from_slice(&arena, &schema, b"\x06ada\x06").unwrap();
+/// assert_eq!(click["user"].as_str(), Some("ada"));
+/// assert_eq!(click["count"].as_i64(), Some(3));
+/// ```
+pub fn from_slice<'a>(arena: &'a Bump, schema: &Schema, bytes: &[u8]) -> Result<DataValue<'a>> {
+    let mut reader = Reader {
+        arena,
+        bytes,
+        pos: 0,
+    };
+    let value = reader.read_datum(schema)?;
+    if reader.pos < bytes.len() {
+        return Err(reader.error("Trailing bytes after Avro datum"));
+    }
+    Ok(value)
+}
+
+/// Encodes a value as one Avro datum with `schema`
+///
+/// # Errors
+///
+/// Returns an error if the value does not match the schema: a value of the
+/// wrong type, a number out of range for an `int`, a string that is not an
+/// enum symbol, a record field without a value or default, a value that fits
+/// no union branch, or a `fixed` value of the wrong size.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::format::avro::{self, Schema};
+/// use datavalue_rs::{from_str, Bump};
+///
+/// let schema = Schema::parse(r#"{"type": "record", "name": "Click", "fields": [
+///     {"name": "user", "type": "string"},
+///     {"name": "count", "type": "long", "default": 0}
+/// ]}"#).unwrap();
+///
+/// let arena = Bump::new();
+/// let click = from_str(&arena, r#"{"user": "ada"}"#).unwrap();
+/// assert_eq!(avro::to_vec(&schema, &click).unwrap(), b"\x06ada\x00");
+/// ```
+pub fn to_vec(schema: &Schema, value: &DataValue<'_>) -> Result<Vec<u8>> {
+    let scratch = Bump::new();
+    let mut output = Vec::new();
+    write_datum(schema, value, &scratch, &mut output)?;
+    Ok(output)
+}
+
+/// An open record, array or map
+enum Frame<'s, 'a> {
+    Record {
+        fields: &'s [Field],
+        entries: Vec<(&'a str, DataValue<'a>)>,
+    },
+    Array {
+        item: usize,
+        /// Items left in the current block
+        remaining: u64,
+        items: Vec<DataValue<'a>>,
+    },
+    Map {
+        value: usize,
+        /// Entries left in the current block
+        remaining: u64,
+        entries: Vec<(&'a str, DataValue<'a>)>,
+        /// Key of the entry whose value is being read
+        key: &'a str,
+    },
+}
+
+struct Reader<'a, 'b> {
+    arena: &'a Bump,
+    bytes: &'b [u8],
+    pos: usize,
+}
+
+impl<'a, 'b> Reader<'a, 'b> {
+    fn error(&self, msg: &str) -> Error {
+        Error::syntax(format!("{} at byte {}", msg, self.pos))
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'b [u8]> {
+        if len > self.bytes.len() - self.pos {
+            return Err(self.error("Truncated Avro datum"));
+        }
+        self.pos += len;
+        Ok(&self.bytes[self.pos - len..self.pos])
+    }
+
+    /// Reads a zigzag-encoded variable-length integer
+    fn long(&mut self) -> Result<i64> {
+        let mut n = 0u64;
+        for shift in (0..64).step_by(7) {
+            let b = self.take(1)?[0];
+            n |= u64::from(b & 0x7F) << shift;
+            if b & 0x80 == 0 {
+                return Ok((n >> 1) as i64 ^ -((n & 1) as i64));
+            }
+        }
+        Err(self.error("Avro integer is too long"))
+    }
+
+    fn length(&mut self) -> Result<usize> {
+        let len = self.long()?;
+        usize::try_from(len).map_err(|_| self.error("Negative Avro length"))
+    }
+
+    fn string(&mut self) -> Result<&'a str> {
+        let len = self.length()?;
+        let bytes = self.take(len)?;
+        let text =
+            std::str::from_utf8(bytes).map_err(|_| self.error("Invalid UTF-8 in Avro string"))?;
+        Ok(self.arena.alloc_str(text))
+    }
+
+    /// Reads the item count of the next array or map block; the count is
+    /// negative when followed by the block's size in bytes
+    fn block_count(&mut self, item: &Node) -> Result<u64> {
+        let count = self.long()?;
+        if count < 0 {
+            self.long()?;
+        }
+        let count = count.unsigned_abs();
+        // Every item takes at least a byte, except nulls and empty records
+        let empty = matches!(item, Node::Null) || matches!(item, Node::Record(f) if f.is_empty());
+        if !empty && count > (self.bytes.len() - self.pos) as u64 {
+            return Err(self.error("Avro block count exceeds the input"));
+        }
+        Ok(count)
+    }
+
+    /// Reads one datum; records, arrays and maps are kept on an explicit stack
+    fn read_datum<'s>(&mut self, schema: &'s Schema) -> Result<DataValue<'a>> {
+        let mut stack: Vec<Frame<'s, 'a>> = Vec::new();
+        let mut next = schema.root;
+        loop {
+            let mut value = self.read_head(schema, next, &mut stack)?;
+
+            // Hand the value to its container and find the next one to read,
+            // closing finished containers
+            loop {
+                let Some(frame) = stack.last_mut() else {
+                    return Ok(value.expect("a value was read"));
+                };
+                match frame {
+                    Frame::Record { fields, entries } => {
+                        if let Some(value) = value.take() {
+                            let name = self.arena.alloc_str(&fields[entries.len()].name);
+                            entries.push((name, value));
+                        }
+                        if let Some(field) = fields.get(entries.len()) {
+                            next = field.node;
+                            break;
+                        }
+                    }
+                    Frame::Array {
+                        item,
+                        remaining,
+                        items,
+                    } => {
+                        if let Some(value) = value.take() {
+                            items.push(value);
+                            *remaining -= 1;
+                        }
+                        if *remaining == 0 {
+                            *remaining = self.block_count(&schema.nodes[*item])?;
+                        }
+                        if *remaining > 0 {
+                            next = *item;
+                            break;
+                        }
+                    }
+                    Frame::Map {
+                        value: value_node,
+                        remaining,
+                        entries,
+                        key,
+                    } => {
+                        if let Some(value) = value.take() {
+                            entries.push((key, value));
+                            *remaining -= 1;
+                        }
+                        if *remaining == 0 {
+                            *remaining = self.block_count(&schema.nodes[*value_node])?;
+                        }
+                        if *remaining > 0 {
+                            *key = self.string()?;
+                            next = *value_node;
+                            break;
+                        }
+                    }
+                }
+                value = Some(match stack.pop() {
+                    Some(Frame::Record { entries, .. }) | Some(Frame::Map { entries, .. }) => {
+                        DataValue::Object(self.arena.alloc_slice_fill_iter(entries))
+                    }
+                    Some(Frame::Array { items, .. }) => {
+                        DataValue::Array(self.arena.alloc_slice_fill_iter(items))
+                    }
+                    None => unreachable!("a frame was open"),
+                });
+            }
+        }
+    }
+
+    /// Reads a value of type `node`, or opens it on the stack and returns None
+    fn read_head<'s>(
+        &mut self,
+        schema: &'s Schema,
+        node: usize,
+        stack: &mut Vec<Frame<'s, 'a>>,
+    ) -> Result<Option<DataValue<'a>>> {
+        let mut node = &schema.nodes[node];
+        while let Node::Union(branches) = node {
+            let branch = self.long()?;
+            let branch = usize::try_from(branch)
+                .ok()
+                .and_then(|branch| branches.get(branch))
+                .ok_or_else(|| self.error("Avro union branch out of range"))?;
+            node = &schema.nodes[*branch];
+        }
+
+        let integer = |i: i64| DataValue::Number(Number::Integer(i));
+        let float = |f: f64| DataValue::Number(Number::Float(f));
+        let value = match node {
+            Node::Null => DataValue::Null,
+            Node::Boolean => match self.take(1)?[0] {
+                0 => DataValue::Bool(false),
+                1 => DataValue::Bool(true),
+                _ => return Err(self.error("Invalid Avro boolean")),
+            },
+            Node::Int | Node::Long => integer(self.long()?),
+            Node::Float => {
+                let bits = <[u8; 4]>::try_from(self.take(4)?).expect("length is 4");
+                float(f64::from(f32::from_le_bytes(bits)))
+            }
+            Node::Double => {
+                let bits = <[u8; 8]>::try_from(self.take(8)?).expect("length is 8");
+                float(f64::from_le_bytes(bits))
+            }
+            Node::Bytes => {
+                let len = self.length()?;
+                bytes_object(self.arena, self.take(len)?)
+            }
+            Node::Fixed(size) => bytes_object(self.arena, self.take(*size)?),
+            Node::String => DataValue::String(self.string()?),
+            Node::Enum(symbols) => {
+                let index = self.long()?;
+                let symbol = usize::try_from(index)
+                    .ok()
+                    .and_then(|index| symbols.get(index))
+                    .ok_or_else(|| self.error("Avro enum index out of range"))?;
+                DataValue::String(self.arena.alloc_str(symbol))
+            }
+            Node::TimestampMillis | Node::TimestampMicros => {
+                let n = self.long()?;
+                let datetime = match node {
+                    Node::TimestampMillis => DateTime::from_timestamp_millis(n),
+                    _ => DateTime::from_timestamp_micros(n),
+                };
+                DataValue::DateTime(
+                    datetime.ok_or_else(|| self.error("Avro timestamp out of range"))?,
+                )
+            }
+            Node::Record(fields) => {
+                if fields.is_empty() {
+                    return Ok(Some(DataValue::Object(&[])));
+                }
+                stack.push(Frame::Record {
+                    fields,
+                    entries: Vec::with_capacity(fields.len()),
+                });
+                return Ok(None);
+            }
+            Node::Array(item) => {
+                stack.push(Frame::Array {
+                    item: *item,
+                    remaining: 0,
+                    items: Vec::new(),
+                });
+                return Ok(None);
+            }
+            Node::Map(value) => {
+                stack.push(Frame::Map {
+                    value: *value,
+                    remaining: 0,
+                    entries: Vec::new(),
+                    key: "",
+                });
+                return Ok(None);
+            }
+            Node::Union(_) => unreachable!("unions were resolved"),
+        };
+        Ok(Some(value))
+    }
+}
+
+/// Returns the object that stands for a byte string
+fn bytes_object<'a>(arena: &'a Bump, bytes: &[u8]) -> DataValue<'a> {
+    let text = DataValue::String(arena.alloc_str(&base64::encode(bytes)));
+    DataValue::Object(arena.alloc_slice_fill_iter([(BYTES_KEY, text)]))
+}
+
+/// Returns the base64 text of a `{"$bytes": base64}` object
+fn bytes_text<'v>(value: &DataValue<'v>) -> Option<&'v str> {
+    match value {
+        DataValue::Object([(BYTES_KEY, DataValue::String(text))]) => Some(text),
+        _ => None,
+    }
+}
+
+fn write_long(output: &mut Vec<u8>, n: i64) {
+    let mut n = ((n << 1) ^ (n >> 63)) as u64;
+    while n >= 0x80 {
+        output.push(n as u8 | 0x80);
+        n >>= 7;
+    }
+    output.push(n as u8);
+}
+
+fn write_bytes(output: &mut Vec<u8>, bytes: &[u8]) {
+    write_long(output, bytes.len() as i64);
+    output.extend_from_slice(bytes);
+}
+
+/// An open record, array or map: its remaining fields or elements
+enum Open<'s, 'v> {
+    Record {
+        fields: std::slice::Iter<'s, Field>,
+        entries: &'v [(&'v str, DataValue<'v>)],
+    },
+    Array {
+        item: usize,
+        items: std::slice::Iter<'v, DataValue<'v>>,
+    },
+    Map {
+        value: usize,
+        entries: std::slice::Iter<'v, (&'v str, DataValue<'v>)>,
+    },
+}
+
+/// Writes one datum, keeping open records, arrays and maps on an explicit stack
+///
+/// Raw values and field defaults are parsed into `scratch`.
+fn write_datum<'v>(
+    schema: &Schema,
+    value: &'v DataValue<'v>,
+    scratch: &'v Bump,
+    output: &mut Vec<u8>,
+) -> Result<()> {
+    let mut stack: Vec<Open<'_, 'v>> = Vec::new();
+    let mut next = Some((schema.root, value));
+    loop {
+        if let Some((node, value)) = next.take() {
+            write_head(schema, node, value, scratch, output, &mut stack)?;
+        }
+        match stack.last_mut() {
+            None => return Ok(()),
+            Some(Open::Record { fields, entries }) => match fields.next() {
+                Some(field) => {
+                    let value = match entries.iter().find(|(name, _)| *name == field.name) {
+                        Some((_, value)) => value,
+                        None => {
+                            let default = field
+                                .default
+                                .as_deref()
+                                .ok_or_else(|| Error::missing_field(field.name.clone()))?;
+                            scratch.alloc(from_str(scratch, default)?)
+                        }
+                    };
+                    next = Some((field.node, value));
+                }
+                None => {
+                    stack.pop();
+                }
+            },
+            Some(Open::Array { item, items }) => match items.next() {
+                Some(value) => next = Some((*item, value)),
+                None => {
+                    output.push(0);
+                    stack.pop();
+                }
+            },
+            Some(Open::Map { value, entries }) => match entries.next() {
+                Some((key, entry)) => {
+                    write_bytes(output, key.as_bytes());
+                    next = Some((*value, entry));
+                }
+                None => {
+                    output.push(0);
+                    stack.pop();
+                }
+            },
+        }
+    }
+}
+
+/// Writes a value of type `node`, or opens it on the stack
+fn write_head<'s, 'v>(
+    schema: &'s Schema,
+    node: usize,
+    value: &'v DataValue<'v>,
+    scratch: &'v Bump,
+    output: &mut Vec<u8>,
+    stack: &mut Vec<Open<'s, 'v>>,
+) -> Result<()> {
+    let value: &'v DataValue<'v> = match value {
+        DataValue::Raw(text) => scratch.alloc(DataValue::Raw(text).parse_raw(scratch)?),
+        value => value,
+    };
+    let mut node = &schema.nodes[node];
+    if let Node::Union(branches) = node {
+        let (index, branch) = branches
+            .iter()
+            .enumerate()
+            .find(|(_, branch)| fits(&schema.nodes[**branch], value))
+            .ok_or_else(|| {
+                Error::custom(format!(
+                    "No branch of the Avro union fits a {} value",
+                    type_name(value)
+                ))
+            })?;
+        write_long(output, index as i64);
+        node = &schema.nodes[*branch];
+    }
+
+    let mismatch = || Error::expected_type(node.name(), type_name(value));
+    match (node, value) {
+        (Node::Null, DataValue::Null) => {}
+        (Node::Boolean, DataValue::Bool(b)) => output.push(u8::from(*b)),
+        (Node::Int, DataValue::Number(_)) => {
+            let n = value
+                .as_i64()
+                .filter(|n| i32::try_from(*n).is_ok())
+                .ok_or_else(|| Error::custom(format!("{} does not fit in an Avro int", value)))?;
+            write_long(output, n);
+        }
+        (Node::Long, DataValue::Number(_))
+        | (Node::TimestampMillis | Node::TimestampMicros, DataValue::Number(_)) => {
+            write_long(output, value.as_i64().ok_or_else(mismatch)?)
+        }
+        (Node::TimestampMillis, DataValue::DateTime(dt)) => {
+            write_long(output, dt.timestamp_millis())
+        }
+        (Node::TimestampMicros, DataValue::DateTime(dt)) => {
+            write_long(output, dt.timestamp_micros())
+        }
+        (Node::Float, DataValue::Number(_)) => {
+            let f = value.as_f64().ok_or_else(mismatch)? as f32;
+            output.extend(f.to_le_bytes());
+        }
+        (Node::Double, DataValue::Number(_)) => {
+            output.extend(value.as_f64().ok_or_else(mismatch)?.to_le_bytes())
+        }
+        (Node::String, DataValue::String(s)) => write_bytes(output, s.as_bytes()),
+        (Node::String, DataValue::DateTime(dt)) => write_bytes(output, dt.to_rfc3339().as_bytes()),
+        (Node::String, DataValue::Duration(dur)) => {
+            write_bytes(output, format_duration(dur).as_bytes())
+        }
+        (Node::Enum(symbols), DataValue::String(s)) => {
+            let index = symbols
+                .iter()
+                .position(|symbol| symbol == s)
+                .ok_or_else(|| Error::custom(format!("{} is not a symbol of the Avro enum", s)))?;
+            write_long(output, index as i64);
+        }
+        (Node::Bytes, _) if bytes_text(value).is_some() => {
+            let bytes = base64::decode(bytes_text(value).expect("checked above"))?;
+            write_bytes(output, &bytes);
+        }
+        (Node::Fixed(size), _) if bytes_text(value).is_some() => {
+            let bytes = base64::decode(bytes_text(value).expect("checked above"))?;
+            if bytes.len() != *size {
+                return Err(Error::custom(format!(
+                    "Avro fixed type holds {} bytes, not {}",
+                    size,
+                    bytes.len()
+                )));
+            }
+            output.extend(bytes);
+        }
+        (Node::Record(fields), DataValue::Object(entries)) => stack.push(Open::Record {
+            fields: fields.iter(),
+            entries,
+        }),
+        (Node::Array(item), DataValue::Array(items)) => {
+            if !items.is_empty() {
+                write_long(output, items.len() as i64);
+            }
+            stack.push(Open::Array {
+                item: *item,
+                items: items.iter(),
+            });
+        }
+        (Node::Map(value), DataValue::Object(entries)) => {
+            if !entries.is_empty() {
+                write_long(output, entries.len() as i64);
+            }
+            stack.push(Open::Map {
+                value: *value,
+                entries: entries.iter(),
+            });
+        }
+        _ => return Err(mismatch()),
+    }
+    Ok(())
+}
+
+/// Returns whether a value can be written as a union branch of type `node`
+fn fits(node: &Node, value: &DataValue<'_>) -> bool {
+    match (node, value) {
+        (Node::Null, DataValue::Null) | (Node::Boolean, DataValue::Bool(_)) => true,
+        (Node::Int, DataValue::Number(_)) => {
+            value.as_i64().is_some_and(|n| i32::try_from(n).is_ok())
+        }
+        (Node::Long, DataValue::Number(_)) => value.as_i64().is_some(),
+        (Node::Float | Node::Double, DataValue::Number(_)) => true,
+        (Node::TimestampMillis | Node::TimestampMicros, DataValue::DateTime(_)) => true,
+        (Node::TimestampMillis | Node::TimestampMicros, DataValue::Number(_)) => {
+            value.as_i64().is_some()
+        }
+        (Node::String, DataValue::String(_) | DataValue::DateTime(_) | DataValue::Duration(_)) => {
+            true
+        }
+        (Node::Enum(symbols), DataValue::String(s)) => symbols.iter().any(|symbol| symbol == s),
+        (Node::Bytes | Node::Fixed(_), _) => bytes_text(value).is_some(),
+        (Node::Record(_) | Node::Map(_), DataValue::Object(_)) => bytes_text(value).is_none(),
+        (Node::Array(_), DataValue::Array(_)) => true,
+        _ => false,
+    }
+}
+
+fn type_name(value: &DataValue<'_>) -> String {
+    format!("{:?}", value.get_type()).to_lowercase()
+}
+
+/// Codec for Avro payloads, with the writer schemas it knows by id
+///
+/// Schemas are usually fetched from the registry the first time an id is seen
+/// and then added with [`AvroCodec::insert`].
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::format::avro::{AvroCodec, Schema};
+/// use datavalue_rs::{from_str, kafka, Bump};
+///
+/// let schema = Schema::parse(r#"{"type": "map", "values": "long"}"#).unwrap();
+/// let codec = AvroCodec::new().with_schema(12, schema);
+///
+/// let arena = Bump::new();
+/// let counts = from_str(&arena, r#"{"a": 1}"#).unwrap();
+/// let message = kafka::encode(12, &counts, &codec).unwrap();
+/// assert_eq!(message, b"\x00\x00\x00\x00\x0c\x02\x02a\x02\x00");
+/// assert_eq!(kafka::decode(&arena, &message, &codec).unwrap(), (12, counts));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct AvroCodec {
+    schemas: HashMap<u32, Schema>,
+}
+
+impl AvroCodec {
+    /// Creates a codec that knows no schemas
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the schema with id `schema_id`
+    pub fn with_schema(mut self, schema_id: u32, schema: Schema) -> Self {
+        self.insert(schema_id, schema);
+        self
+    }
+
+    /// Adds or replaces the schema with id `schema_id`
+    pub fn insert(&mut self, schema_id: u32, schema: Schema) {
+        self.schemas.insert(schema_id, schema);
+    }
+
+    /// Returns whether the schema with id `schema_id` is known
+    pub fn contains(&self, schema_id: u32) -> bool {
+        self.schemas.contains_key(&schema_id)
+    }
+
+    fn schema(&self, schema_id: u32) -> Result<&Schema> {
+        self.schemas
+            .get(&schema_id)
+            .ok_or_else(|| Error::custom(format!("No Avro schema with id {}", schema_id)))
+    }
+}
+
+impl PayloadCodec for AvroCodec {
+    fn decode<'a>(&self, arena: &'a Bump, schema_id: u32, payload: &[u8]) -> Result<DataValue<'a>> {
+        from_slice(arena, self.schema(schema_id)?, payload)
+    }
+
+    fn encode(&self, schema_id: u32, value: &DataValue<'_>) -> Result<Vec<u8>> {
+        to_vec(self.schema(schema_id)?, value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EVENT: &str = r#"{
+        "type": "record",
+        "name": "Event",
+        "namespace": "com.example",
+        "fields": [
+            {"name": "id", "type": "long"},
+            {"name": "kind", "type": {"type": "enum", "name": "Kind", "symbols": ["OPEN", "CLOSE"]}},
+            {"name": "score", "type": ["null", "int", "double"]},
+            {"name": "at", "type": {"type": "long", "logicalType": "timestamp-millis"}},
+            {"name": "tags", "type": {"type": "array", "items": "string"}},
+            {"name": "attrs", "type": {"type": "map", "values": "boolean"}},
+            {"name": "digest", "type": {"type": "fixed", "name": "Digest", "size": 2}},
+            {"name": "blob", "type": "bytes"},
+            {"name": "ratio", "type": "float"},
+            {"name": "parent", "type": ["null", "com.example.Event"], "default": null},
+            {"name": "next_kind", "type": "Kind", "default": "CLOSE"}
+        ]
+    }"#;
+
+    #[test]
+    fn test_round_trip() {
+        let arena = Bump::new();
+        let schema = Schema::parse(EVENT).unwrap();
+        let event = from_str(
+            &arena,
+            r#"{
+                "id": -1, "kind": "CLOSE", "score": 2.5, "at": 0, "tags": ["a", "é"],
+                "attrs": {"x": true}, "digest": {"$bytes": "AQI="}, "blob": {"$bytes": ""},
+                "ratio": 0.5,
+                "parent": {"id": 1, "kind": "OPEN", "score": 7, "at": 1, "tags": [], "attrs": {},
+                           "digest": {"$bytes": "AAA="}, "blob": {"$bytes": "/w=="}, "ratio": 1}
+            }"#,
+        )
+        .unwrap();
+
+        let bytes = to_vec(&schema, &event).unwrap();
+        // id -1, enum index 1, union branch 2 then the double
+        assert_eq!(bytes[..3], [0x01, 0x02, 0x04]);
+        let back = from_slice(&arena, &schema, &bytes).unwrap();
+        assert_eq!(back["kind"].as_str(), Some("CLOSE"));
+        assert_eq!(back["score"].as_f64(), Some(2.5));
+        assert_eq!(back["at"].as_datetime().unwrap().timestamp(), 0);
+        assert_eq!(back["tags"][1].as_str(), Some("é"));
+        assert_eq!(back["digest"], event["digest"]);
+        assert_eq!(back["parent"]["score"].as_i64(), Some(7));
+        assert_eq!(back["parent"]["parent"], DataValue::Null);
+        assert_eq!(back["next_kind"].as_str(), Some("CLOSE"));
+        assert_eq!(to_vec(&schema, &back).unwrap(), bytes);
+
+        // Blocks with a negative count are followed by their size in bytes
+        let array = Schema::parse(r#"{"type": "array", "items": "int"}"#).unwrap();
+        let value = from_slice(&arena, &array, b"\x03\x04\x02\x04\x02\x06\x00").unwrap();
+        assert_eq!(crate::to_string(&value), "[1,2,3]");
+    }
+
+    #[test]
+    fn test_errors() {
+        let arena = Bump::new();
+        let schema = Schema::parse(EVENT).unwrap();
+        let missing = from_str(&arena, r#"{"id": 1}"#).unwrap();
+        assert!(matches!(to_vec(&schema, &missing), Err(Error::MissingField(f)) if f == "kind"));
+
+        let int = Schema::parse(r#""int""#).unwrap();
+        assert!(to_vec(&int, &from_str(&arena, "4294967296").unwrap()).is_err());
+        assert!(to_vec(&int, &from_str(&arena, "\"1\"").unwrap()).is_err());
+        let union = Schema::parse(r#"["null", "string"]"#).unwrap();
+        assert!(to_vec(&union, &from_str(&arena, "1").unwrap()).is_err());
+
+        assert!(from_slice(&arena, &int, b"\x80").is_err());
+        assert!(from_slice(&arena, &int, b"\x02\x02").is_err());
+        assert!(from_slice(&arena, &union, b"\x04").is_err());
+        let strings = Schema::parse(r#"{"type": "array", "items": "string"}"#).unwrap();
+        assert!(from_slice(&arena, &strings, b"\xfe\xff\xff\xff\x0f\x00").is_err());
+
+        assert!(Schema::parse(r#"{"type": "fixed", "name": "F"}"#).is_err());
+        assert!(Schema::parse(r#"["R", {"type": "record", "name": "R", "fields": []}]"#).is_err());
+        assert!(Schema::parse(
+            r#"[{"type": "enum", "name": "E", "symbols": []}, {"type": "fixed", "name": "E", "size": 1}]"#
+        )
+        .is_err());
+    }
+}
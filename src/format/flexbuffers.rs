@@ -0,0 +1,824 @@
+//! FlexBuffers input and output, enabled by the `flexbuffers` feature
+//!
+//! [FlexBuffers](https://flatbuffers.dev/flexbuffers/) is the schema-less binary
+//! format of the FlatBuffers project. A buffer is read from its end: the last
+//! two bytes give the width and type of the root, and containers refer to their
+//! elements by backward offsets. [`from_slice`] decodes a buffer into the arena,
+//! where it can be navigated with [`get`](DataValue::get) and
+//! [`pointer`](DataValue::pointer), and [`to_vec`] encodes a value.
+//!
+//! FlexBuffers values map onto DataValue as follows:
+//!
+//! | FlexBuffers | DataValue |
+//! |---|---|
+//! | null, bool | [`DataValue::Null`], [`DataValue::Bool`] |
+//! | int, uint and their indirect forms | [`Number::Integer`], or [`Number::BigInt`] for uints above `i64::MAX` |
+//! | float and indirect float | [`Number::Float`] |
+//! | string, key | [`DataValue::String`] |
+//! | blob | `{"$bytes": base64}` |
+//! | vector, typed and fixed-length vectors | [`DataValue::Array`] |
+//! | map | [`DataValue::Object`] |
+//!
+//! The encoder writes integers and floats inline in the narrowest width that
+//! holds them, arrays as untyped vectors and objects as maps. Map keys are
+//! sorted, as FlexBuffers readers look them up by binary search, so objects
+//! come back sorted by key; only the first of duplicate keys is kept. Numbers
+//! that fit neither 64-bit integer type are written as floats, and DateTimes
+//! and durations as strings, as in JSON.
+//!
+//! # Example
+//!
+//! ```
+//! use datavalue_rs::format::flexbuffers;
+//! use datavalue_rs::{from_str, Bump};
+//!
+//! let arena = Bump::new();
+//! let value = from_str(&arena, r#"{"id": 7, "tags": ["a", "b"]}"#).unwrap();
+//! let bytes = flexbuffers::to_vec(&value).unwrap();
+//!
+//! let decoded = flexbuffers::from_slice(&arena, &bytes).unwrap();
+//! assert_eq!(decoded.pointer("/tags/1").and_then(|t| t.as_str()), Some("b"));
+//! assert_eq!(decoded, value);
+//! ```
+
+use std::collections::HashSet;
+
+use bumpalo::Bump;
+
+use crate::base64;
+use crate::bignum::parse_number;
+use crate::datavalue::{DataValue, Number};
+use crate::error::{Error, Result};
+use crate::helpers::format_duration;
+
+const BYTES_KEY: &str = "$bytes";
+
+/// Value types, stored in the upper six bits of a packed type byte
+const TYPE_NULL: u8 = 0;
+const TYPE_INT: u8 = 1;
+const TYPE_UINT: u8 = 2;
+const TYPE_FLOAT: u8 = 3;
+const TYPE_KEY: u8 = 4;
+const TYPE_STRING: u8 = 5;
+const TYPE_INDIRECT_INT: u8 = 6;
+const TYPE_INDIRECT_UINT: u8 = 7;
+const TYPE_INDIRECT_FLOAT: u8 = 8;
+const TYPE_MAP: u8 = 9;
+const TYPE_VECTOR: u8 = 10;
+const TYPE_VECTOR_INT: u8 = 11;
+const TYPE_VECTOR_KEY: u8 = 14;
+const TYPE_VECTOR_STRING_DEPRECATED: u8 = 15;
+const TYPE_VECTOR_INT2: u8 = 16;
+const TYPE_VECTOR_FLOAT4: u8 = 24;
+const TYPE_BLOB: u8 = 25;
+const TYPE_BOOL: u8 = 26;
+const TYPE_VECTOR_BOOL: u8 = 36;
+
+/// Decodes a FlexBuffers buffer
+///
+/// # Errors
+///
+/// Returns a syntax error with the byte offset of the problem if the buffer is
+/// truncated, an offset or length points outside it, a container is referenced
+/// twice, or it holds an unknown type, a 16-bit float or a string that is not
+/// UTF-8.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::format::flexbuffers;
+/// use datavalue_rs::Bump;
+///
+/// let arena = Bump::new();
+/// // The integer 300 as the root, two bytes wide
+/// let value = flexbuffers::from_slice(&arena, b"\x2c\x01\x05\x02").unwrap();
+/// assert_eq!(value.as_i64(), Some(300));
+/// ```
+pub fn from_slice<'a>(arena: &'a Bump, bytes: &[u8]) -> Result<DataValue<'a>> {
+    let mut reader = Reader {
+        arena,
+        bytes,
+        containers: HashSet::new(),
+    };
+    if bytes.len() < 3 {
+        return Err(reader.error("FlexBuffers buffer is too short", bytes.len()));
+    }
+    let width = usize::from(bytes[bytes.len() - 1]);
+    let packed = bytes[bytes.len() - 2];
+    if !matches!(width, 1 | 2 | 4 | 8) || bytes.len() < width + 2 {
+        return Err(reader.error("Invalid FlexBuffers root width", bytes.len() - 1));
+    }
+    reader.read_root(Ref {
+        pos: bytes.len() - 2 - width,
+        width,
+        packed,
+    })
+}
+
+/// Encodes a value as a FlexBuffers buffer
+///
+/// # Errors
+///
+/// Returns an error if a `$bytes` object is malformed, a raw value is not
+/// valid JSON, or an object key contains a NUL character, which FlexBuffers
+/// keys cannot hold.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::format::flexbuffers;
+/// use datavalue_rs::{from_str, Bump};
+///
+/// let arena = Bump::new();
+/// let value = from_str(&arena, "[1, -1]").unwrap();
+/// // Length, two one-byte ints, their types, then the root offset, type and width
+/// assert_eq!(
+///     flexbuffers::to_vec(&value).unwrap(),
+///     b"\x02\x01\xff\x04\x04\x04\x28\x01"
+/// );
+/// ```
+pub fn to_vec(value: &DataValue<'_>) -> Result<Vec<u8>> {
+    let mut writer = Writer { output: Vec::new() };
+    let root = writer.write_value(value)?;
+    writer.finish(root);
+    Ok(writer.output)
+}
+
+/// Where a value is stored: its position, the width of the slot, and its
+/// packed type
+#[derive(Debug, Clone, Copy)]
+struct Ref {
+    pos: usize,
+    width: usize,
+    packed: u8,
+}
+
+impl Ref {
+    fn value_type(&self) -> u8 {
+        self.packed >> 2
+    }
+
+    /// Width of the value an offset points to
+    fn child_width(&self) -> usize {
+        1 << (self.packed & 3)
+    }
+}
+
+/// An array or map whose elements are still being read
+enum Frame<'a> {
+    Array {
+        elements: std::vec::IntoIter<Ref>,
+        items: Vec<DataValue<'a>>,
+    },
+    Map {
+        keys: std::vec::IntoIter<&'a str>,
+        values: std::vec::IntoIter<Ref>,
+        entries: Vec<(&'a str, DataValue<'a>)>,
+        /// Key of the entry whose value is being read
+        key: &'a str,
+    },
+}
+
+/// A value read from a reference: a scalar, or the elements of a container
+enum Read<'a> {
+    Value(DataValue<'a>),
+    Array(Vec<Ref>),
+    Map(Vec<&'a str>, Vec<Ref>),
+}
+
+struct Reader<'a, 'b> {
+    arena: &'a Bump,
+    bytes: &'b [u8],
+    /// Positions of the containers read so far, which may not be shared
+    containers: HashSet<usize>,
+}
+
+impl<'a, 'b> Reader<'a, 'b> {
+    fn error(&self, msg: &str, pos: usize) -> Error {
+        Error::syntax(format!("{} at byte {}", msg, pos))
+    }
+
+    fn slice(&self, pos: usize, len: usize) -> Result<&'b [u8]> {
+        pos.checked_add(len)
+            .and_then(|end| self.bytes.get(pos..end))
+            .ok_or_else(|| self.error("FlexBuffers data out of bounds", pos))
+    }
+
+    fn uint(&self, pos: usize, width: usize) -> Result<u64> {
+        let bytes = self.slice(pos, width)?;
+        Ok(bytes.iter().rev().fold(0u64, |n, &b| n << 8 | u64::from(b)))
+    }
+
+    fn int(&self, pos: usize, width: usize) -> Result<i64> {
+        let n = self.uint(pos, width)?;
+        let shift = 64 - 8 * width as u32;
+        Ok(((n << shift) as i64) >> shift)
+    }
+
+    fn float(&self, pos: usize, width: usize) -> Result<f64> {
+        match width {
+            4 => Ok(f64::from(f32::from_bits(self.uint(pos, 4)? as u32))),
+            8 => Ok(f64::from_bits(self.uint(pos, 8)?)),
+            _ => Err(self.error("Unsupported FlexBuffers float width", pos)),
+        }
+    }
+
+    /// Follows the backward offset stored in a slot
+    fn target(&self, slot: Ref) -> Result<usize> {
+        let offset = self.uint(slot.pos, slot.width)?;
+        usize::try_from(offset)
+            .ok()
+            .and_then(|offset| slot.pos.checked_sub(offset))
+            .ok_or_else(|| self.error("FlexBuffers offset out of bounds", slot.pos))
+    }
+
+    /// Reads the length stored before a string, blob, vector or map
+    fn length(&self, target: usize, width: usize) -> Result<usize> {
+        let pos = target
+            .checked_sub(width)
+            .ok_or_else(|| self.error("FlexBuffers length out of bounds", target))?;
+        let len = self.uint(pos, width)?;
+        usize::try_from(len)
+            .ok()
+            .filter(|&len| len <= self.bytes.len())
+            .ok_or_else(|| self.error("FlexBuffers length out of bounds", pos))
+    }
+
+    fn text(&self, bytes: &[u8], pos: usize) -> Result<&'a str> {
+        let text = std::str::from_utf8(bytes)
+            .map_err(|_| self.error("Invalid UTF-8 in FlexBuffers string", pos))?;
+        Ok(self.arena.alloc_str(text))
+    }
+
+    /// Reads the NUL-terminated key an offset slot points to
+    fn key(&self, slot: Ref) -> Result<&'a str> {
+        let target = self.target(slot)?;
+        let len = self
+            .bytes
+            .get(target..)
+            .and_then(|rest| rest.iter().position(|&b| b == 0))
+            .ok_or_else(|| self.error("Unterminated FlexBuffers key", target))?;
+        self.text(&self.bytes[target..target + len], target)
+    }
+
+    /// Returns the slots of `len` elements of `width` bytes starting at `base`,
+    /// with their packed types
+    fn elements(
+        &self,
+        base: usize,
+        len: usize,
+        width: usize,
+        types: impl Fn(usize) -> Result<u8>,
+    ) -> Result<Vec<Ref>> {
+        self.slice(base, len * width)?;
+        (0..len)
+            .map(|i| {
+                Ok(Ref {
+                    pos: base + i * width,
+                    width,
+                    packed: types(i)?,
+                })
+            })
+            .collect()
+    }
+
+    /// Marks a container as read, refusing one seen before so that shared or
+    /// cyclic offsets cannot make decoding blow up
+    fn open(&mut self, target: usize) -> Result<()> {
+        if !self.containers.insert(target) {
+            return Err(self.error("FlexBuffers container is referenced twice", target));
+        }
+        Ok(())
+    }
+
+    fn read(&mut self, slot: Ref) -> Result<Read<'a>> {
+        let integer = |i: i64| DataValue::Number(Number::Integer(i));
+        let value = match slot.value_type() {
+            TYPE_NULL => DataValue::Null,
+            TYPE_BOOL => DataValue::Bool(self.uint(slot.pos, slot.width)? != 0),
+            TYPE_INT => integer(self.int(slot.pos, slot.width)?),
+            TYPE_UINT => self.unsigned(self.uint(slot.pos, slot.width)?),
+            TYPE_FLOAT => DataValue::Number(Number::Float(self.float(slot.pos, slot.width)?)),
+            TYPE_INDIRECT_INT => integer(self.int(self.target(slot)?, slot.child_width())?),
+            TYPE_INDIRECT_UINT => self.unsigned(self.uint(self.target(slot)?, slot.child_width())?),
+            TYPE_INDIRECT_FLOAT => DataValue::Number(Number::Float(
+                self.float(self.target(slot)?, slot.child_width())?,
+            )),
+            TYPE_KEY => DataValue::String(self.key(slot)?),
+            TYPE_STRING | TYPE_BLOB => {
+                let target = self.target(slot)?;
+                let len = self.length(target, slot.child_width())?;
+                let bytes = self.slice(target, len)?;
+                if slot.value_type() == TYPE_STRING {
+                    DataValue::String(self.text(bytes, target)?)
+                } else {
+                    let text = DataValue::String(self.arena.alloc_str(&base64::encode(bytes)));
+                    DataValue::Object(self.arena.alloc_slice_fill_iter([(BYTES_KEY, text)]))
+                }
+            }
+            TYPE_VECTOR => {
+                let target = self.target(slot)?;
+                self.open(target)?;
+                let width = slot.child_width();
+                let len = self.length(target, width)?;
+                let types = self.slice(target + len * width, len)?;
+                return Ok(Read::Array(
+                    self.elements(target, len, width, |i| Ok(types[i]))?,
+                ));
+            }
+            TYPE_VECTOR_INT..=TYPE_VECTOR_STRING_DEPRECATED | TYPE_VECTOR_BOOL => {
+                let target = self.target(slot)?;
+                self.open(target)?;
+                let width = slot.child_width();
+                let len = self.length(target, width)?;
+                let element = match slot.value_type() {
+                    TYPE_VECTOR_BOOL => TYPE_BOOL,
+                    TYPE_VECTOR_STRING_DEPRECATED => TYPE_STRING,
+                    vector => vector - TYPE_VECTOR_INT + TYPE_INT,
+                };
+                let packed = element << 2 | (slot.packed & 3);
+                return Ok(Read::Array(
+                    self.elements(target, len, width, |_| Ok(packed))?,
+                ));
+            }
+            TYPE_VECTOR_INT2..=TYPE_VECTOR_FLOAT4 => {
+                let target = self.target(slot)?;
+                self.open(target)?;
+                let fixed = slot.value_type() - TYPE_VECTOR_INT2;
+                let (len, element) = (2 + usize::from(fixed / 3), TYPE_INT + fixed % 3);
+                let packed = element << 2 | (slot.packed & 3);
+                let width = slot.child_width();
+                return Ok(Read::Array(
+                    self.elements(target, len, width, |_| Ok(packed))?,
+                ));
+            }
+            TYPE_MAP => {
+                let target = self.target(slot)?;
+                self.open(target)?;
+                let width = slot.child_width();
+                let len = self.length(target, width)?;
+                let types = self.slice(target + len * width, len)?;
+                let values = self.elements(target, len, width, |i| Ok(types[i]))?;
+
+                let prefix = target
+                    .checked_sub(3 * width)
+                    .ok_or_else(|| self.error("FlexBuffers map out of bounds", target))?;
+                let keys = self.target(Ref {
+                    pos: prefix,
+                    width,
+                    packed: 0,
+                })?;
+                let key_width = self.uint(prefix + width, width)?;
+                let key_width = match key_width {
+                    1 | 2 | 4 | 8 => key_width as usize,
+                    _ => return Err(self.error("Invalid FlexBuffers key width", prefix + width)),
+                };
+                if self.length(keys, key_width)? != len {
+                    return Err(self.error("FlexBuffers map keys do not match its values", keys));
+                }
+                let packed = TYPE_KEY << 2;
+                let keys = self
+                    .elements(keys, len, key_width, |_| Ok(packed))?
+                    .into_iter()
+                    .map(|key| self.key(key))
+                    .collect::<Result<Vec<_>>>()?;
+                return Ok(Read::Map(keys, values));
+            }
+            _ => return Err(self.error("Unknown FlexBuffers type", slot.pos)),
+        };
+        Ok(Read::Value(value))
+    }
+
+    fn unsigned(&self, n: u64) -> DataValue<'a> {
+        match i64::try_from(n) {
+            Ok(i) => DataValue::Number(Number::Integer(i)),
+            Err(_) => {
+                let number = parse_number(self.arena, &n.to_string(), false);
+                DataValue::Number(number.expect("text is an integer"))
+            }
+        }
+    }
+
+    /// Reads the root value; containers are kept on an explicit stack
+    fn read_root(&mut self, root: Ref) -> Result<DataValue<'a>> {
+        let mut stack: Vec<Frame<'a>> = Vec::new();
+        let mut next = root;
+        loop {
+            let mut value = match self.read(next)? {
+                Read::Value(value) => Some(value),
+                Read::Array(elements) => {
+                    stack.push(Frame::Array {
+                        items: Vec::with_capacity(elements.len()),
+                        elements: elements.into_iter(),
+                    });
+                    None
+                }
+                Read::Map(keys, values) => {
+                    stack.push(Frame::Map {
+                        entries: Vec::with_capacity(keys.len()),
+                        keys: keys.into_iter(),
+                        values: values.into_iter(),
+                        key: "",
+                    });
+                    None
+                }
+            };
+
+            // Hand the value to its container and find the next one to read,
+            // closing finished containers
+            loop {
+                match stack.last_mut() {
+                    None => return Ok(value.expect("a value was read")),
+                    Some(Frame::Array { elements, items }) => {
+                        items.extend(value.take());
+                        if let Some(element) = elements.next() {
+                            next = element;
+                            break;
+                        }
+                    }
+                    Some(Frame::Map {
+                        keys,
+                        values,
+                        entries,
+                        key,
+                    }) => {
+                        if let Some(value) = value.take() {
+                            entries.push((key, value));
+                        }
+                        if let (Some(name), Some(element)) = (keys.next(), values.next()) {
+                            *key = name;
+                            next = element;
+                            break;
+                        }
+                    }
+                }
+                value = Some(match stack.pop() {
+                    Some(Frame::Array { items, .. }) => {
+                        DataValue::Array(self.arena.alloc_slice_fill_iter(items))
+                    }
+                    Some(Frame::Map { entries, .. }) => {
+                        DataValue::Object(self.arena.alloc_slice_fill_iter(entries))
+                    }
+                    None => unreachable!("a frame was open"),
+                });
+            }
+        }
+    }
+}
+
+/// A value that is stored inline in its slot
+#[derive(Debug, Clone, Copy)]
+enum Scalar {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Uint(u64),
+    Float(f64),
+}
+
+/// A written value, as its parent will store it
+#[derive(Debug, Clone, Copy)]
+enum Slot {
+    Inline(Scalar),
+    /// A value elsewhere in the buffer: its type, position and width
+    Offset(u8, usize, usize),
+}
+
+impl Slot {
+    fn value_type(&self) -> u8 {
+        match self {
+            Slot::Inline(Scalar::Null) => TYPE_NULL,
+            Slot::Inline(Scalar::Bool(_)) => TYPE_BOOL,
+            Slot::Inline(Scalar::Int(_)) => TYPE_INT,
+            Slot::Inline(Scalar::Uint(_)) => TYPE_UINT,
+            Slot::Inline(Scalar::Float(_)) => TYPE_FLOAT,
+            Slot::Offset(value_type, _, _) => *value_type,
+        }
+    }
+
+    /// Returns whether the slot can be written `width` bytes wide at `pos`
+    fn fits(&self, pos: usize, width: usize) -> bool {
+        let needed = match *self {
+            Slot::Inline(Scalar::Null | Scalar::Bool(_)) => 1,
+            Slot::Inline(Scalar::Int(i)) => int_width(i),
+            Slot::Inline(Scalar::Uint(n)) => uint_width(n),
+            Slot::Inline(Scalar::Float(f)) if f64::from(f as f32) == f || f.is_nan() => 4,
+            Slot::Inline(Scalar::Float(_)) => 8,
+            Slot::Offset(_, target, _) => uint_width((pos - target) as u64),
+        };
+        needed <= width
+    }
+
+    /// The packed type byte of the slot when written `width` bytes wide
+    fn packed(&self, width: usize) -> u8 {
+        let width = match self {
+            Slot::Inline(_) => width,
+            Slot::Offset(_, _, child_width) => *child_width,
+        };
+        self.value_type() << 2 | width.trailing_zeros() as u8
+    }
+}
+
+fn int_width(i: i64) -> usize {
+    [1, 2, 4]
+        .into_iter()
+        .find(|&width| matches!(i >> (8 * width - 1), 0 | -1))
+        .unwrap_or(8)
+}
+
+fn uint_width(n: u64) -> usize {
+    [1, 2, 4]
+        .into_iter()
+        .find(|&width| n >> (8 * width) == 0)
+        .unwrap_or(8)
+}
+
+/// An array or object whose elements are still being written
+enum Open<'v, 'a> {
+    Array(std::slice::Iter<'v, DataValue<'a>>, Vec<Slot>),
+    /// The remaining entries in key order, and the keys and values written
+    Map(
+        std::vec::IntoIter<&'v (&'a str, DataValue<'a>)>,
+        Vec<&'a str>,
+        Vec<Slot>,
+    ),
+}
+
+struct Writer {
+    output: Vec<u8>,
+}
+
+impl Writer {
+    /// Pads the output to a multiple of `width`
+    fn align(&mut self, width: usize) {
+        let padding = self.output.len().next_multiple_of(width) - self.output.len();
+        self.output.resize(self.output.len() + padding, 0);
+    }
+
+    fn write_slot(&mut self, slot: Slot, width: usize) {
+        let pos = self.output.len();
+        let bits = match slot {
+            Slot::Inline(Scalar::Null) => 0,
+            Slot::Inline(Scalar::Bool(b)) => u64::from(b),
+            Slot::Inline(Scalar::Int(i)) => i as u64,
+            Slot::Inline(Scalar::Uint(n)) => n,
+            Slot::Inline(Scalar::Float(f)) if width == 4 => u64::from((f as f32).to_bits()),
+            Slot::Inline(Scalar::Float(f)) => f.to_bits(),
+            Slot::Offset(_, target, _) => (pos - target) as u64,
+        };
+        self.output.extend_from_slice(&bits.to_le_bytes()[..width]);
+    }
+
+    /// Writes a string or blob with its length before it, returning its slot
+    fn write_string(&mut self, value_type: u8, bytes: &[u8]) -> Slot {
+        let width = uint_width(bytes.len() as u64);
+        self.align(width);
+        self.write_slot(Slot::Inline(Scalar::Uint(bytes.len() as u64)), width);
+        let pos = self.output.len();
+        self.output.extend_from_slice(bytes);
+        if value_type == TYPE_STRING {
+            self.output.push(0);
+        }
+        Slot::Offset(value_type, pos, width)
+    }
+
+    /// Writes the prefix and elements of a vector or map in the narrowest width
+    /// that holds them all, followed by the element types if `typed` is false,
+    /// and returns the position of the first element and the width
+    fn write_vector(&mut self, prefix: &[Slot], elements: &[Slot], typed: bool) -> (usize, usize) {
+        let width = [1, 2, 4, 8]
+            .into_iter()
+            .find(|&width| {
+                let start = self.output.len().next_multiple_of(width);
+                prefix
+                    .iter()
+                    .chain(elements)
+                    .enumerate()
+                    .all(|(i, slot)| slot.fits(start + i * width, width))
+            })
+            .unwrap_or(8);
+        self.align(width);
+        for slot in prefix.iter().chain(elements) {
+            self.write_slot(*slot, width);
+        }
+        let pos = self.output.len() - elements.len() * width;
+        if !typed {
+            self.output
+                .extend(elements.iter().map(|slot| slot.packed(width)));
+        }
+        (pos, width)
+    }
+
+    fn write_map(&mut self, keys: &[&str], values: &[Slot]) -> Slot {
+        let key_slots: Vec<Slot> = keys
+            .iter()
+            .map(|key| {
+                let pos = self.output.len();
+                self.output.extend_from_slice(key.as_bytes());
+                self.output.push(0);
+                Slot::Offset(TYPE_KEY, pos, 1)
+            })
+            .collect();
+        let length = Slot::Inline(Scalar::Uint(keys.len() as u64));
+        let (keys_pos, key_width) = self.write_vector(&[length], &key_slots, true);
+
+        let prefix = [
+            Slot::Offset(TYPE_VECTOR_KEY, keys_pos, key_width),
+            Slot::Inline(Scalar::Uint(key_width as u64)),
+            length,
+        ];
+        let (pos, width) = self.write_vector(&prefix, values, false);
+        Slot::Offset(TYPE_MAP, pos, width)
+    }
+
+    /// Writes a value's children and then the value, returning its slot;
+    /// arrays and objects are kept on an explicit stack
+    fn write_value(&mut self, value: &DataValue<'_>) -> Result<Slot> {
+        let scratch = Bump::new();
+        let mut stack: Vec<Open<'_, '_>> = Vec::new();
+        let mut next = Some(value);
+        loop {
+            let mut slot = match next.take() {
+                Some(value) => self.write_leaf(value, &scratch, &mut stack)?,
+                None => None,
+            };
+
+            loop {
+                match stack.last_mut() {
+                    None => return Ok(slot.expect("a value was written")),
+                    Some(Open::Array(items, slots)) => {
+                        slots.extend(slot.take());
+                        if let Some(item) = items.next() {
+                            next = Some(item);
+                            break;
+                        }
+                    }
+                    Some(Open::Map(entries, _, slots)) => {
+                        slots.extend(slot.take());
+                        if let Some((_, value)) = entries.next() {
+                            next = Some(value);
+                            break;
+                        }
+                    }
+                }
+                slot = Some(match stack.pop() {
+                    Some(Open::Array(_, slots)) => {
+                        let length = Slot::Inline(Scalar::Uint(slots.len() as u64));
+                        let (pos, width) = self.write_vector(&[length], &slots, false);
+                        Slot::Offset(TYPE_VECTOR, pos, width)
+                    }
+                    Some(Open::Map(_, keys, slots)) => self.write_map(&keys, &slots),
+                    None => unreachable!("a frame was open"),
+                });
+            }
+        }
+    }
+
+    /// Writes a scalar, string or blob and returns its slot, or opens an array
+    /// or object on the stack
+    fn write_leaf<'v, 'a>(
+        &mut self,
+        value: &'v DataValue<'a>,
+        scratch: &'v Bump,
+        stack: &mut Vec<Open<'v, 'a>>,
+    ) -> Result<Option<Slot>>
+    where
+        'v: 'a,
+    {
+        let slot = match value {
+            DataValue::Raw(text) => {
+                let parsed: &'v DataValue<'v> =
+                    scratch.alloc(DataValue::Raw(text).parse_raw(scratch)?);
+                return self.write_leaf(parsed, scratch, stack);
+            }
+            DataValue::Null => Slot::Inline(Scalar::Null),
+            DataValue::Bool(b) => Slot::Inline(Scalar::Bool(*b)),
+            DataValue::Number(number) => Slot::Inline(match value.as_i64() {
+                Some(i) => Scalar::Int(i),
+                None => match number {
+                    Number::BigInt(n) if !n.is_negative() => match n.digits().parse() {
+                        Ok(n) => Scalar::Uint(n),
+                        Err(_) => Scalar::Float(n.to_f64()),
+                    },
+                    _ => Scalar::Float(value.as_f64().unwrap_or(f64::NAN)),
+                },
+            }),
+            DataValue::String(s) => self.write_string(TYPE_STRING, s.as_bytes()),
+            DataValue::DateTime(dt) => self.write_string(TYPE_STRING, dt.to_rfc3339().as_bytes()),
+            DataValue::Duration(dur) => {
+                self.write_string(TYPE_STRING, format_duration(dur).as_bytes())
+            }
+            DataValue::Object([(BYTES_KEY, DataValue::String(text))]) => {
+                self.write_string(TYPE_BLOB, &base64::decode(text)?)
+            }
+            DataValue::Array(items) => {
+                stack.push(Open::Array(items.iter(), Vec::with_capacity(items.len())));
+                return Ok(None);
+            }
+            DataValue::Object(entries) => {
+                let mut sorted: Vec<&(&str, DataValue<'_>)> = entries.iter().collect();
+                sorted.sort_by(|a, b| a.0.cmp(b.0));
+                sorted.dedup_by(|b, a| a.0 == b.0);
+                if sorted.iter().any(|(key, _)| key.contains('\0')) {
+                    return Err(Error::custom("FlexBuffers keys cannot contain NUL"));
+                }
+                let keys = sorted.iter().map(|(key, _)| *key).collect();
+                stack.push(Open::Map(sorted.into_iter(), keys, Vec::new()));
+                return Ok(None);
+            }
+        };
+        Ok(Some(slot))
+    }
+
+    /// Writes the root slot, its packed type and its width
+    fn finish(&mut self, root: Slot) {
+        let width = [1, 2, 4, 8]
+            .into_iter()
+            .find(|&width| root.fits(self.output.len().next_multiple_of(width), width))
+            .unwrap_or(8);
+        self.align(width);
+        self.write_slot(root, width);
+        self.output.push(root.packed(width));
+        self.output.push(width as u8);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_str;
+
+    #[test]
+    fn test_round_trip() {
+        let arena = Bump::new();
+        let options = crate::ParseOptions {
+            number_mode: crate::NumberMode::Arbitrary,
+            ..Default::default()
+        };
+        let value = crate::from_str_with_options(
+            &arena,
+            r#"{
+                "z": [null, true, -129, 70000, 1.5, 0.1, "é", {"$bytes": "AQID"}],
+                "a": {"nested": [[], {}], "big": 18446744073709551615, "min": -9223372036854775808},
+                "long": "xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx",
+                "a": "duplicate"
+            }"#,
+            &options,
+        )
+        .unwrap();
+        let bytes = to_vec(&value).unwrap();
+        let decoded = from_slice(&arena, &bytes).unwrap();
+
+        let keys: Vec<&str> = decoded
+            .as_object()
+            .unwrap()
+            .iter()
+            .map(|(k, _)| *k)
+            .collect();
+        assert_eq!(keys, ["a", "long", "z"]);
+        assert_eq!(decoded["a"], value["a"]);
+        assert_eq!(decoded["z"], value["z"]);
+        assert_eq!(decoded["long"], value["long"]);
+        assert!(matches!(
+            decoded["a"]["big"],
+            DataValue::Number(Number::BigInt(_))
+        ));
+
+        for scalar in ["null", "false", "-1", "3.25", r#""""#, "[]", "{}"] {
+            let value = from_str(&arena, scalar).unwrap();
+            assert_eq!(from_slice(&arena, &to_vec(&value).unwrap()).unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn test_typed_vectors() {
+        let arena = Bump::new();
+        // A typed vector of 1-byte ints, and a fixed-length vector of two
+        // 4-byte floats
+        let ints = b"\x02\x01\x02\x02\x2c\x01";
+        assert_eq!(
+            crate::to_string(&from_slice(&arena, ints).unwrap()),
+            "[1,2]"
+        );
+        let floats = b"\x00\x00\x00\x3f\x00\x00\x80\x3f\x08\x4a\x01";
+        assert_eq!(
+            crate::to_string(&from_slice(&arena, floats).unwrap()),
+            "[0.5,1.0]"
+        );
+    }
+
+    #[test]
+    fn test_malformed() {
+        let arena = Bump::new();
+        assert!(from_slice(&arena, b"\x01").is_err());
+        assert!(from_slice(&arena, b"\x00\x04\x03").is_err());
+        // A vector whose offset points past the start of the buffer
+        assert!(from_slice(&arena, b"\x09\x28\x01").is_err());
+        // A vector that contains itself
+        assert!(from_slice(&arena, b"\x01\x00\x28\x02\x28\x01").is_err());
+        assert!(from_slice(&arena, b"\x00\xfc\x01").is_err());
+
+        let key = from_str(&arena, r#"{"a\u0000b": 1}"#).unwrap();
+        assert!(to_vec(&key).is_err());
+    }
+}
@@ -0,0 +1,1154 @@
+//! HOCON configuration files
+//!
+//! [HOCON](https://github.com/lightbend/config/blob/main/HOCON.md) is a superset
+//! of JSON for configuration: braces around the root and commas are optional,
+//! keys may be dotted paths, values may be unquoted, and files can include other
+//! files and refer to other settings with `${path}` substitutions. The reader
+//! applies all of that and returns the fully resolved configuration as a plain
+//! DataValue, ready to be layered with [`config::from_env`](crate::config::from_env)
+//! and [`config::from_args`](crate::config::from_args).
+//!
+//! Repeated keys follow the HOCON merge rules: two objects are merged key by key,
+//! and any other value replaces the earlier one. `key += value` appends to an
+//! array. A substitution that refers to the key being defined sees its earlier
+//! value, so `path = ${path} ["extra"]` extends a setting. Substitutions that are
+//! not defined in the file fall back to environment variables, and `${?path}`
+//! leaves the setting out if neither exists.
+//!
+//! Includes are read from files relative to the including file, or through a
+//! custom resolver set with [`HoconParser::resolver`]. `url(...)` includes are
+//! not supported, and `classpath(...)` includes are resolved like files.
+//!
+//! Values are kept as JSON types; HOCON duration and size strings such as `10s`
+//! stay strings and can be read with the [`units`](crate::units) helpers.
+
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+
+use bumpalo::Bump;
+
+use crate::datavalue::{DataValue, Number};
+use crate::error::{Error, Location, Result};
+
+/// Nesting depth of objects and arrays past which input is rejected, since the
+/// reader is recursive
+const MAX_DEPTH: usize = 256;
+/// Depth of nested includes past which input is rejected, which also stops
+/// include cycles
+const MAX_INCLUDE_DEPTH: usize = 32;
+
+/// Parses HOCON text, resolving includes relative to the working directory
+///
+/// # Errors
+///
+/// Returns a syntax error with the line and column of the problem if the text is
+/// not valid HOCON, and an error if an include cannot be read, a substitution
+/// is undefined or refers to itself, or values of different types are
+/// concatenated.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::{format::hocon, Bump};
+///
+/// let arena = Bump::new();
+/// let config = hocon::from_str(&arena, r#"
+///     // Defaults
+///     server { host = localhost, port = 8080 }
+///     server.port = 9090
+///     base-url = "http://"${server.host}":"${server.port}
+///     features = [search]
+///     features += billing
+///     timeout = 30s
+/// "#).unwrap();
+///
+/// assert_eq!(config["server"]["port"].as_i64(), Some(9090));
+/// assert_eq!(config["base-url"].as_str(), Some("http://localhost:9090"));
+/// assert_eq!(config["features"][1].as_str(), Some("billing"));
+/// assert_eq!(config["timeout"].as_str(), Some("30s"));
+/// ```
+pub fn from_str<'a>(arena: &'a Bump, text: &str) -> Result<DataValue<'a>> {
+    HoconParser::new().parse(arena, text)
+}
+
+/// Reads and parses a HOCON file, resolving includes relative to it
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read, or as [`from_str`] describes.
+pub fn from_path<'a>(arena: &'a Bump, path: impl AsRef<Path>) -> Result<DataValue<'a>> {
+    HoconParser::new().parse_file(arena, path)
+}
+
+/// Reads included files' text given their names
+type IncludeResolver = dyn Fn(&str) -> Result<Option<String>>;
+
+/// HOCON reader settings
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::{format::hocon::HoconParser, Bump};
+///
+/// let arena = Bump::new();
+/// let parser = HoconParser::new()
+///     .env(false)
+///     .resolver(|name| Ok((name == "defaults.conf").then(|| "retries = 3".to_string())));
+/// let config = parser
+///     .parse(&arena, "include \"defaults.conf\"\nretries = ${retries}0\nuser = ${?USER}")
+///     .unwrap();
+/// assert_eq!(config["retries"].as_str(), Some("30"));
+/// assert!(config.get("user").is_none());
+/// ```
+pub struct HoconParser {
+    base_dir: Option<PathBuf>,
+    resolver: Option<Box<IncludeResolver>>,
+    env: bool,
+}
+
+impl Default for HoconParser {
+    fn default() -> Self {
+        HoconParser {
+            base_dir: None,
+            resolver: None,
+            env: true,
+        }
+    }
+}
+
+impl HoconParser {
+    /// Creates a parser that reads includes from files and falls back to
+    /// environment variables for substitutions
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the directory that includes in the top-level text are relative to
+    pub fn base_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.base_dir = Some(dir.into());
+        self
+    }
+
+    /// Reads includes with `resolver` instead of from files
+    ///
+    /// The resolver receives the name in the include statement and returns the
+    /// text, or None if there is no such file. Names without an extension are
+    /// also tried with `.conf` and `.json` appended.
+    pub fn resolver(mut self, resolver: impl Fn(&str) -> Result<Option<String>> + 'static) -> Self {
+        self.resolver = Some(Box::new(resolver));
+        self
+    }
+
+    /// Sets whether undefined substitutions are looked up in the environment
+    pub fn env(mut self, env: bool) -> Self {
+        self.env = env;
+        self
+    }
+
+    /// Parses HOCON text into the arena
+    ///
+    /// # Errors
+    ///
+    /// See [`from_str`].
+    pub fn parse<'a>(&self, arena: &'a Bump, text: &str) -> Result<DataValue<'a>> {
+        let mut parser = Parser {
+            text,
+            bytes: text.as_bytes(),
+            pos: 0,
+            options: self,
+            dir: self.base_dir.clone(),
+            prefix: Vec::new(),
+            depth: 0,
+            include_depth: 0,
+        };
+        let root = parser.parse_root()?;
+        let mut resolver = Resolver {
+            root: &root,
+            env: self.env,
+            resolving: Vec::new(),
+        };
+        let resolved = resolver.resolve(&root)?.unwrap_or(Node::Object(Vec::new()));
+        Ok(to_value(arena, &resolved))
+    }
+
+    /// Reads and parses a HOCON file, resolving includes relative to it
+    ///
+    /// # Errors
+    ///
+    /// See [`from_path`].
+    pub fn parse_file<'a>(&self, arena: &'a Bump, path: impl AsRef<Path>) -> Result<DataValue<'a>> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)?;
+        let mut parser = Parser {
+            text: &text,
+            bytes: text.as_bytes(),
+            pos: 0,
+            options: self,
+            dir: Some(path.parent().unwrap_or(Path::new("")).to_path_buf()),
+            prefix: Vec::new(),
+            depth: 0,
+            include_depth: 0,
+        };
+        let root = parser.parse_root()?;
+        let mut resolver = Resolver {
+            root: &root,
+            env: self.env,
+            resolving: Vec::new(),
+        };
+        let resolved = resolver.resolve(&root)?.unwrap_or(Node::Object(Vec::new()));
+        Ok(to_value(arena, &resolved))
+    }
+}
+
+/// A parsed value, before substitutions are resolved
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Null,
+    Bool(bool),
+    /// The text of a number
+    Number(String),
+    String(String),
+    Array(Vec<Node>),
+    Object(Vec<(String, Node)>),
+    /// Values written next to each other, including the whitespace between them
+    Concat(Vec<Node>),
+    /// Whitespace inside a concatenation
+    Space(String),
+    /// `${path}`, or `${?path}` if optional
+    Subst(Vec<String>, bool),
+}
+
+struct Parser<'s, 'o> {
+    text: &'s str,
+    bytes: &'s [u8],
+    pos: usize,
+    options: &'o HoconParser,
+    /// Directory that includes are relative to
+    dir: Option<PathBuf>,
+    /// Path of the object an included file is merged into, which its
+    /// substitutions are relative to
+    prefix: Vec<String>,
+    depth: usize,
+    include_depth: usize,
+}
+
+impl Parser<'_, '_> {
+    fn error(&self, msg: &str) -> Error {
+        let byte_offset = self.pos.min(self.text.len());
+        let consumed = &self.text[..byte_offset];
+        let line = consumed.matches('\n').count() + 1;
+        let column = consumed
+            .rfind('\n')
+            .map_or(consumed, |nl| &consumed[nl + 1..])
+            .chars()
+            .count()
+            + 1;
+        let location = Location {
+            line,
+            column,
+            byte_offset,
+            path: String::new(),
+        };
+        Error::syntax_at(msg, location)
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+
+    fn rest(&self) -> &str {
+        &self.text[self.pos..]
+    }
+
+    /// Skips spaces and tabs, but not newlines
+    fn skip_spaces(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t')) || self.rest().starts_with('\u{feff}') {
+            self.pos += if self.peek() == Some(b' ') || self.peek() == Some(b'\t') {
+                1
+            } else {
+                3
+            };
+        }
+    }
+
+    fn at_comment(&self) -> bool {
+        self.peek() == Some(b'#') || self.rest().starts_with("//")
+    }
+
+    /// Skips whitespace, newlines and comments
+    fn skip_blank(&mut self) {
+        loop {
+            self.skip_spaces();
+            if self.at_comment() {
+                self.pos += self.rest().find('\n').unwrap_or(self.rest().len());
+            } else if matches!(self.peek(), Some(b'\n' | b'\r')) {
+                self.pos += 1;
+            } else {
+                return;
+            }
+        }
+    }
+
+    fn parse_root(&mut self) -> Result<Node> {
+        self.skip_blank();
+        let root = match self.peek() {
+            Some(b'[') => {
+                self.pos += 1;
+                self.parse_array()?
+            }
+            Some(b'{') => {
+                self.pos += 1;
+                let prefix = self.prefix.clone();
+                Node::Object(self.parse_object(true, &prefix)?)
+            }
+            _ => {
+                let prefix = self.prefix.clone();
+                return Ok(Node::Object(self.parse_object(false, &prefix)?));
+            }
+        };
+        self.skip_blank();
+        if self.pos < self.bytes.len() {
+            return Err(self.error("unexpected text after the root value"));
+        }
+        Ok(root)
+    }
+
+    /// Parses the fields of an object, after its opening brace if it has one;
+    /// `path` is the object's path from the root
+    fn parse_object(&mut self, braced: bool, path: &[String]) -> Result<Vec<(String, Node)>> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(self.error("nesting too deep"));
+        }
+        let mut fields = Vec::new();
+        loop {
+            self.skip_blank();
+            match self.peek() {
+                None if braced => return Err(self.error("expected '}'")),
+                None => break,
+                Some(b'}') if braced => {
+                    self.pos += 1;
+                    break;
+                }
+                _ => {}
+            }
+            if self.rest().starts_with("include")
+                && matches!(self.bytes.get(self.pos + 7), Some(b' ' | b'\t' | b'"'))
+            {
+                self.pos += "include".len();
+                self.parse_include(&mut fields, path)?;
+            } else {
+                let key = self.parse_key()?;
+                self.skip_spaces();
+                let append = self.rest().starts_with("+=");
+                if append {
+                    self.pos += 2;
+                } else if matches!(self.peek(), Some(b':' | b'=')) {
+                    self.pos += 1;
+                } else if self.peek() != Some(b'{') {
+                    return Err(self.error("expected ':', '=', '+=' or '{' after key"));
+                }
+                self.skip_blank();
+                let full: Vec<String> = path.iter().chain(&key).cloned().collect();
+                let value = self.parse_value(&full)?;
+                merge_field(&mut fields, &key, append, value, path);
+            }
+
+            self.skip_spaces();
+            if self.at_comment() {
+                continue;
+            }
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b'\n' | b'\r') | None => {}
+                Some(b'}') if braced => {}
+                _ => return Err(self.error("expected ',' or a new line")),
+            }
+        }
+        self.depth -= 1;
+        Ok(fields)
+    }
+
+    /// Parses the elements of an array, after its opening bracket
+    fn parse_array(&mut self) -> Result<Node> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(self.error("nesting too deep"));
+        }
+        let mut items = Vec::new();
+        loop {
+            self.skip_blank();
+            match self.peek() {
+                None => return Err(self.error("expected ']'")),
+                Some(b']') => {
+                    self.pos += 1;
+                    break;
+                }
+                _ => {}
+            }
+            items.push(self.parse_value(&[])?);
+            self.skip_spaces();
+            if self.at_comment() {
+                continue;
+            }
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b'\n' | b'\r' | b']') => {}
+                _ => return Err(self.error("expected ',' or a new line")),
+            }
+        }
+        self.depth -= 1;
+        Ok(Node::Array(items))
+    }
+
+    /// Parses a key path such as `a.b."c.d"`
+    fn parse_key(&mut self) -> Result<Vec<String>> {
+        let mut keys = Vec::new();
+        let mut key = String::new();
+        let mut quoted = false;
+        loop {
+            match self.peek() {
+                Some(b'"') => {
+                    key.push_str(&self.parse_string()?);
+                    quoted = true;
+                }
+                Some(b'.') => {
+                    keys.push(std::mem::take(&mut key));
+                    self.pos += 1;
+                }
+                Some(b' ' | b'\t') => {
+                    let start = self.pos;
+                    self.skip_spaces();
+                    if self.rest().starts_with("+=")
+                        || matches!(self.peek(), Some(b':' | b'=' | b'{'))
+                    {
+                        break;
+                    }
+                    key.push_str(&self.text[start..self.pos]);
+                }
+                Some(b':' | b'=' | b'{' | b'}') => break,
+                _ if self.rest().starts_with("+=") => break,
+                _ => {
+                    let text = self.unquoted();
+                    if text.is_empty() {
+                        return Err(self.error("expected key"));
+                    }
+                    key.push_str(text);
+                }
+            }
+        }
+        if key.is_empty() && !quoted && keys.is_empty() {
+            return Err(self.error("expected key"));
+        }
+        keys.push(key);
+        Ok(keys)
+    }
+
+    /// Consumes the longest run of characters allowed in unquoted text; `.` ends
+    /// the run, since it separates keys in paths
+    fn unquoted(&mut self) -> &str {
+        let start = self.pos;
+        while let Some(c) = self.rest().chars().next() {
+            if c.is_whitespace()
+                || "$\"{}[]:=,+#`^?!@*&\\.".contains(c)
+                || self.rest().starts_with("//")
+            {
+                break;
+            }
+            self.pos += c.len_utf8();
+        }
+        &self.text[start..self.pos]
+    }
+
+    /// Parses a value, which may be a concatenation of several; `path` is where
+    /// it is assigned, or empty in arrays
+    fn parse_value(&mut self, path: &[String]) -> Result<Node> {
+        // Pieces, flagged if they are unquoted text
+        let mut pieces: Vec<(Node, bool)> = Vec::new();
+        loop {
+            let start = self.pos;
+            let piece = match self.peek() {
+                None | Some(b'\n' | b'\r' | b',' | b'}' | b']') => break,
+                _ if self.at_comment() => break,
+                Some(b' ' | b'\t') => {
+                    self.skip_spaces();
+                    Node::Space(self.text[start..self.pos].to_string())
+                }
+                Some(b'{') => {
+                    self.pos += 1;
+                    Node::Object(self.parse_object(true, path)?)
+                }
+                Some(b'[') => {
+                    self.pos += 1;
+                    self.parse_array()?
+                }
+                Some(b'"') => Node::String(self.parse_string()?),
+                Some(b'$') if self.rest().starts_with("${") => self.parse_substitution()?,
+                _ => {
+                    // Unquoted text may contain dots in values
+                    while self.peek() == Some(b'.') || !self.unquoted().is_empty() {
+                        if self.peek() == Some(b'.') {
+                            self.pos += 1;
+                        }
+                    }
+                    if self.pos == start {
+                        return Err(self.error("unexpected character"));
+                    }
+                    pieces.push((Node::String(self.text[start..self.pos].to_string()), true));
+                    continue;
+                }
+            };
+            pieces.push((piece, false));
+        }
+        while matches!(pieces.last(), Some((Node::Space(_), _))) {
+            pieces.pop();
+        }
+        match pieces.len() {
+            0 => Err(self.error("expected value")),
+            1 => Ok(match pieces.pop() {
+                Some((Node::String(text), true)) => classify(text),
+                Some((piece, _)) => piece,
+                None => unreachable!("one piece"),
+            }),
+            _ => Ok(Node::Concat(
+                pieces.into_iter().map(|(piece, _)| piece).collect(),
+            )),
+        }
+    }
+
+    /// Parses a quoted or triple-quoted string
+    fn parse_string(&mut self) -> Result<String> {
+        if self.rest().starts_with("\"\"\"") {
+            let body = &self.rest()[3..];
+            let end = body
+                .find("\"\"\"")
+                .ok_or_else(|| self.error("unterminated string"))?;
+            // Quotes just before the closing three belong to the string
+            let extra = body[end + 3..].bytes().take_while(|&b| b == b'"').count();
+            let text = body[..end + extra].to_string();
+            self.pos += 3 + end + extra + 3;
+            return Ok(text);
+        }
+        self.pos += 1;
+        let mut output = String::new();
+        loop {
+            let start = self.pos;
+            match self.peek() {
+                None | Some(b'\n' | b'\r') => return Err(self.error("unterminated string")),
+                Some(b'"') => {
+                    self.pos += 1;
+                    return Ok(output);
+                }
+                Some(b'\\') => {
+                    self.pos += 2;
+                    let c = match self.bytes.get(start + 1) {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'b') => '\u{8}',
+                        Some(b'f') => '\u{c}',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'u') => {
+                            let code = self
+                                .text
+                                .get(self.pos..self.pos + 4)
+                                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                                .and_then(char::from_u32);
+                            self.pos += 4;
+                            code.ok_or_else(|| {
+                                self.pos = start;
+                                self.error("invalid unicode escape")
+                            })?
+                        }
+                        _ => {
+                            self.pos = start;
+                            return Err(self.error("invalid escape"));
+                        }
+                    };
+                    output.push(c);
+                }
+                Some(_) => {
+                    let c = self.rest().chars().next().unwrap_or_default();
+                    output.push(c);
+                    self.pos += c.len_utf8();
+                }
+            }
+        }
+    }
+
+    /// Parses `${path}` or `${?path}`
+    fn parse_substitution(&mut self) -> Result<Node> {
+        self.pos += 2;
+        let optional = self.peek() == Some(b'?');
+        if optional {
+            self.pos += 1;
+        }
+        self.skip_spaces();
+        let mut path = self.prefix.clone();
+        path.extend(
+            self.parse_key()?
+                .into_iter()
+                .map(|key| key.trim_end().to_string()),
+        );
+        self.skip_spaces();
+        if self.peek() != Some(b'}') {
+            return Err(self.error("expected '}' after substitution"));
+        }
+        self.pos += 1;
+        Ok(Node::Subst(path, optional))
+    }
+
+    /// Parses an include statement after `include` and merges the included
+    /// object into `fields`
+    fn parse_include(&mut self, fields: &mut Vec<(String, Node)>, path: &[String]) -> Result<()> {
+        self.skip_spaces();
+        let mut closing = 0;
+        let mut required = false;
+        loop {
+            let rest = self.rest();
+            if rest.starts_with("required(") {
+                required = true;
+                self.pos += "required(".len();
+            } else if rest.starts_with("file(") || rest.starts_with("classpath(") {
+                self.pos += rest.find('(').unwrap_or_default() + 1;
+            } else if rest.starts_with("url(") {
+                return Err(self.error("url includes are not supported"));
+            } else {
+                break;
+            }
+            closing += 1;
+            self.skip_spaces();
+        }
+        if self.peek() != Some(b'"') {
+            return Err(self.error("expected quoted include name"));
+        }
+        let name = self.parse_string()?;
+        for _ in 0..closing {
+            self.skip_spaces();
+            if self.peek() != Some(b')') {
+                return Err(self.error("expected ')'"));
+            }
+            self.pos += 1;
+        }
+
+        if self.include_depth >= MAX_INCLUDE_DEPTH {
+            return Err(self.error("includes nested too deeply"));
+        }
+        let Some((text, dir)) = self.read_include(&name)? else {
+            if required {
+                return Err(Error::custom(format!(
+                    "Required include {:?} not found",
+                    name
+                )));
+            }
+            return Ok(());
+        };
+        let mut parser = Parser {
+            text: &text,
+            bytes: text.as_bytes(),
+            pos: 0,
+            options: self.options,
+            dir,
+            prefix: path.to_vec(),
+            depth: self.depth,
+            include_depth: self.include_depth + 1,
+        };
+        let included = parser
+            .parse_root()
+            .map_err(|e| Error::syntax(format!("In included {:?}: {}", name, e)))?;
+        let Node::Object(included) = included else {
+            return Err(Error::custom(format!(
+                "Included {:?} is not an object",
+                name
+            )));
+        };
+        for (key, value) in included {
+            merge_field(fields, &[key], false, value, path);
+        }
+        Ok(())
+    }
+
+    /// Returns the text of an included file and the directory its own includes
+    /// are relative to
+    fn read_include(&self, name: &str) -> Result<Option<(String, Option<PathBuf>)>> {
+        let mut candidates = vec![name.to_string()];
+        if Path::new(name).extension().is_none() {
+            candidates.extend([format!("{}.conf", name), format!("{}.json", name)]);
+        }
+        for candidate in candidates {
+            if let Some(resolver) = &self.options.resolver {
+                if let Some(text) = resolver(&candidate)? {
+                    return Ok(Some((text, None)));
+                }
+                continue;
+            }
+            let path = match &self.dir {
+                Some(dir) => dir.join(&candidate),
+                None => PathBuf::from(&candidate),
+            };
+            match std::fs::read_to_string(&path) {
+                Ok(text) => {
+                    let dir = path.parent().map(Path::to_path_buf);
+                    return Ok(Some((text, dir)));
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Returns the value of unquoted text: a number, keyword or string
+fn classify(text: String) -> Node {
+    match text.as_str() {
+        "true" => Node::Bool(true),
+        "false" => Node::Bool(false),
+        "null" => Node::Null,
+        _ if is_number(&text) => Node::Number(text),
+        _ => Node::String(text),
+    }
+}
+
+/// Checks JSON number syntax
+fn is_number(text: &str) -> bool {
+    let text = text.strip_prefix('-').unwrap_or(text);
+    let (mantissa, exponent) = match text.find(['e', 'E']) {
+        Some(i) => (&text[..i], Some(&text[i + 1..])),
+        None => (text, None),
+    };
+    let (whole, fraction) = match mantissa.split_once('.') {
+        Some((whole, fraction)) => (whole, Some(fraction)),
+        None => (mantissa, None),
+    };
+    let digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    digits(whole)
+        && (whole == "0" || !whole.starts_with('0'))
+        && fraction.is_none_or(digits)
+        && exponent.is_none_or(|e| digits(e.strip_prefix(['+', '-']).unwrap_or(e)))
+}
+
+/// Assigns `value` to the relative `key` path in an object at `path`, applying
+/// the HOCON merge rules
+fn merge_field(
+    fields: &mut Vec<(String, Node)>,
+    key: &[String],
+    append: bool,
+    value: Node,
+    path: &[String],
+) {
+    let (first, rest) = key.split_first().expect("keys are never empty");
+    let index = fields.iter().position(|(k, _)| k == first);
+    let full: Vec<String> = path.iter().chain([first]).cloned().collect();
+    if !rest.is_empty() {
+        let index = match index {
+            Some(i) if matches!(fields[i].1, Node::Object(_)) => i,
+            Some(i) => {
+                fields[i].1 = Node::Object(Vec::new());
+                i
+            }
+            None => {
+                fields.push((first.clone(), Node::Object(Vec::new())));
+                fields.len() - 1
+            }
+        };
+        if let Node::Object(children) = &mut fields[index].1 {
+            merge_field(children, rest, append, value, &full);
+        }
+        return;
+    }
+
+    let old = index.map(|i| std::mem::replace(&mut fields[i].1, Node::Null));
+    let mut value = match append {
+        // `a += b` is `a = ${?a} [b]`
+        true => Node::Concat(vec![
+            Node::Subst(full.clone(), true),
+            Node::Array(vec![value]),
+        ]),
+        false => value,
+    };
+    if let Some(old) = &old {
+        replace_self_references(&mut value, &full, old);
+    }
+    let value = match (old, value) {
+        (Some(Node::Object(mut merged)), Node::Object(new)) => {
+            for (key, child) in new {
+                merge_field(&mut merged, &[key], false, child, &full);
+            }
+            Node::Object(merged)
+        }
+        (_, value) => value,
+    };
+    match index {
+        Some(i) => fields[i].1 = value,
+        None => fields.push((first.clone(), value)),
+    }
+}
+
+/// Replaces substitutions of `path`, or of paths inside it, with the earlier
+/// value `old`
+fn replace_self_references(node: &mut Node, path: &[String], old: &Node) {
+    match node {
+        Node::Subst(target, _) if target.starts_with(path) => {
+            let mut found = Some(old);
+            for key in &target[path.len()..] {
+                found = match found {
+                    Some(Node::Object(fields)) => {
+                        fields.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+                    }
+                    _ => None,
+                };
+            }
+            if let Some(found) = found {
+                *node = found.clone();
+            }
+        }
+        Node::Array(items) | Node::Concat(items) => {
+            for item in items {
+                replace_self_references(item, path, old);
+            }
+        }
+        Node::Object(fields) => {
+            for (_, value) in fields {
+                replace_self_references(value, path, old);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Resolves substitutions against the whole configuration
+struct Resolver<'r> {
+    root: &'r Node,
+    env: bool,
+    /// Paths being looked up, to detect cycles
+    resolving: Vec<Vec<String>>,
+}
+
+impl<'r> Resolver<'r> {
+    /// Returns the resolved value, or None if it is an undefined optional
+    /// substitution
+    fn resolve(&mut self, node: &Node) -> Result<Option<Node>> {
+        let resolved = match node {
+            Node::Object(fields) => {
+                let mut resolved = Vec::with_capacity(fields.len());
+                for (key, value) in fields {
+                    if let Some(value) = self.resolve(value)? {
+                        resolved.push((key.clone(), value));
+                    }
+                }
+                Node::Object(resolved)
+            }
+            Node::Array(items) => {
+                let mut resolved = Vec::with_capacity(items.len());
+                for item in items {
+                    resolved.extend(self.resolve(item)?);
+                }
+                Node::Array(resolved)
+            }
+            Node::Concat(pieces) => {
+                let mut resolved = Vec::with_capacity(pieces.len());
+                for piece in pieces {
+                    resolved.extend(self.resolve(piece)?);
+                }
+                return concatenate(resolved);
+            }
+            Node::Subst(path, optional) => {
+                if let Some(value) = self.lookup(path)? {
+                    return Ok(Some(value));
+                }
+                let name = path.join(".");
+                match std::env::var(&name) {
+                    Ok(value) if self.env => Node::String(value),
+                    _ if *optional => return Ok(None),
+                    _ => {
+                        return Err(Error::custom(format!(
+                            "Unresolved substitution ${{{}}}",
+                            name
+                        )))
+                    }
+                }
+            }
+            scalar => scalar.clone(),
+        };
+        Ok(Some(resolved))
+    }
+
+    /// Finds and resolves the value at `path`; a path that is already being
+    /// resolved refers to itself and is treated as undefined
+    fn lookup(&mut self, path: &[String]) -> Result<Option<Node>> {
+        if self.resolving.iter().any(|p| p == path) {
+            return Ok(None);
+        }
+        self.resolving.push(path.to_vec());
+        let mut current = Cow::Borrowed(self.root);
+        for key in path {
+            if !matches!(*current, Node::Object(_)) {
+                match self.resolve(&current)? {
+                    Some(node) => current = Cow::Owned(node),
+                    None => break,
+                }
+            }
+            let child = match current {
+                Cow::Borrowed(Node::Object(fields)) => fields
+                    .iter()
+                    .find(|(k, _)| k == key)
+                    .map(|(_, v)| Cow::Borrowed(v)),
+                Cow::Owned(Node::Object(fields)) => fields
+                    .into_iter()
+                    .find(|(k, _)| k == key)
+                    .map(|(_, v)| Cow::Owned(v)),
+                _ => None,
+            };
+            match child {
+                Some(child) => current = child,
+                None => {
+                    self.resolving.pop();
+                    return Ok(None);
+                }
+            }
+        }
+        let resolved = self.resolve(&current);
+        self.resolving.pop();
+        resolved
+    }
+}
+
+/// Combines the resolved pieces of a concatenation
+fn concatenate(mut pieces: Vec<Node>) -> Result<Option<Node>> {
+    while matches!(pieces.first(), Some(Node::Space(_))) {
+        pieces.remove(0);
+    }
+    while matches!(pieces.last(), Some(Node::Space(_))) {
+        pieces.pop();
+    }
+    let values = || {
+        pieces
+            .iter()
+            .filter(|piece| !matches!(piece, Node::Space(_)))
+    };
+    if values().count() <= 1 {
+        return Ok(pieces
+            .into_iter()
+            .find(|piece| !matches!(piece, Node::Space(_))));
+    }
+    if values().all(|piece| matches!(piece, Node::Array(_))) {
+        let items = pieces
+            .into_iter()
+            .flat_map(|piece| match piece {
+                Node::Array(items) => items,
+                _ => Vec::new(),
+            })
+            .collect();
+        return Ok(Some(Node::Array(items)));
+    }
+    if values().all(|piece| matches!(piece, Node::Object(_))) {
+        let mut merged = Vec::new();
+        for piece in pieces {
+            if let Node::Object(fields) = piece {
+                for (key, value) in fields {
+                    merge_field(&mut merged, &[key], false, value, &[]);
+                }
+            }
+        }
+        return Ok(Some(Node::Object(merged)));
+    }
+    let mut text = String::new();
+    for piece in pieces {
+        match piece {
+            Node::Null => text.push_str("null"),
+            Node::Bool(b) => text.push_str(if b { "true" } else { "false" }),
+            Node::Number(s) | Node::String(s) | Node::Space(s) => text.push_str(&s),
+            Node::Array(_) | Node::Object(_) => {
+                return Err(Error::custom(
+                    "Cannot concatenate an array or object with other values",
+                ))
+            }
+            Node::Concat(_) | Node::Subst(..) => unreachable!("pieces are resolved"),
+        }
+    }
+    Ok(Some(Node::String(text)))
+}
+
+/// Copies a resolved value into the arena
+fn to_value<'a>(arena: &'a Bump, node: &Node) -> DataValue<'a> {
+    match node {
+        Node::Null => DataValue::Null,
+        Node::Bool(b) => DataValue::Bool(*b),
+        Node::Number(text) => DataValue::Number(match text.parse::<i64>() {
+            Ok(i) => Number::Integer(i),
+            Err(_) => Number::Float(text.parse().unwrap_or(f64::NAN)),
+        }),
+        Node::String(s) | Node::Space(s) => DataValue::String(arena.alloc_str(s)),
+        Node::Array(items) => {
+            let items: Vec<_> = items.iter().map(|item| to_value(arena, item)).collect();
+            DataValue::Array(arena.alloc_slice_fill_iter(items))
+        }
+        Node::Object(fields) => {
+            let entries: Vec<_> = fields
+                .iter()
+                .map(|(key, value)| (&*arena.alloc_str(key), to_value(arena, value)))
+                .collect();
+            DataValue::Object(arena.alloc_slice_fill_iter(entries))
+        }
+        Node::Concat(_) | Node::Subst(..) => unreachable!("values are resolved"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse<'a>(arena: &'a Bump, text: &str) -> Result<DataValue<'a>> {
+        HoconParser::new().env(false).parse(arena, text)
+    }
+
+    #[test]
+    fn test_syntax() {
+        let arena = Bump::new();
+        let text = r#"
+            // JSON is HOCON
+            "json": {"a": [1, 2.5, -3e2, true, null], "b": "x\ty"},
+            braceless = yes  # comment
+            a.b."c.d" : 1
+            list = [
+                one
+                two,
+                "three" four
+            ]
+            concat = foo bar  "baz"
+            numbers = [0, 01, 1.0.0]
+            multi = """line "one"
+line two"""
+            empty {}
+            with spaces = in key
+        "#;
+        let config = parse(&arena, text).unwrap();
+        assert_eq!(
+            crate::to_string(&config["json"]),
+            r#"{"a":[1,2.5,-300.0,true,null],"b":"x\ty"}"#
+        );
+        assert_eq!(config["braceless"].as_str(), Some("yes"));
+        assert_eq!(config["a"]["b"]["c.d"].as_i64(), Some(1));
+        assert_eq!(
+            crate::to_string(&config["list"]),
+            r#"["one","two","three four"]"#
+        );
+        assert_eq!(config["concat"].as_str(), Some("foo bar  baz"));
+        assert_eq!(crate::to_string(&config["numbers"]), r#"[0,"01","1.0.0"]"#);
+        assert_eq!(config["multi"].as_str(), Some("line \"one\"\nline two"));
+        assert_eq!(config["empty"].as_object().map(<[_]>::len), Some(0));
+        assert_eq!(config["with spaces"].as_str(), Some("in key"));
+
+        let root_array = parse(&arena, "[1, 2]").unwrap();
+        assert_eq!(root_array.as_array().map(<[_]>::len), Some(2));
+
+        for bad in [
+            "a = [1",
+            "a = {",
+            "a 1",
+            "a = \"open",
+            "a = }",
+            "{a = 1} b",
+            "a = b, , c = d",
+        ] {
+            assert!(parse(&arena, bad).is_err(), "{}", bad);
+        }
+        let error = parse(&arena, "a = 1\nb = ^").unwrap_err();
+        assert_eq!(error.location().map(|l| (l.line, l.column)), Some((2, 5)));
+    }
+
+    #[test]
+    fn test_merging_and_substitutions() {
+        let arena = Bump::new();
+        let text = r#"
+            db { host = localhost, port = 5432, pool { min = 1 } }
+            db { port = 6543, pool.max = 10 }
+            db.user = admin
+            replaced = { a = 1 }
+            replaced = 2
+            url = "postgres://"${db.host}":"${db.port}
+            copy = ${db.pool}
+            path = [a]
+            path = ${path} [b]
+            path += c
+            greeting = hello
+            greeting = ${greeting}" world"
+            merged = ${db.pool} { extra = true }
+            optional = ${?missing}
+            items = [1, ${?missing}, 3]
+        "#;
+        let config = parse(&arena, text).unwrap();
+        assert_eq!(
+            crate::to_string(&config["db"]),
+            r#"{"host":"localhost","port":6543,"pool":{"min":1,"max":10},"user":"admin"}"#
+        );
+        assert_eq!(config["replaced"].as_i64(), Some(2));
+        assert_eq!(config["url"].as_str(), Some("postgres://localhost:6543"));
+        assert_eq!(crate::to_string(&config["copy"]), r#"{"min":1,"max":10}"#);
+        assert_eq!(crate::to_string(&config["path"]), r#"["a","b","c"]"#);
+        assert_eq!(config["greeting"].as_str(), Some("hello world"));
+        assert_eq!(
+            crate::to_string(&config["merged"]),
+            r#"{"min":1,"max":10,"extra":true}"#
+        );
+        assert!(config.get("optional").is_none());
+        assert_eq!(crate::to_string(&config["items"]), "[1,3]");
+
+        assert!(parse(&arena, "a = ${b}").is_err());
+        assert!(parse(&arena, "a = ${b}, b = ${a}").is_err());
+        assert!(parse(&arena, "a = [1] x").is_err());
+
+        std::env::set_var("DATAVALUE_HOCON_TEST_HOME", "/home/test");
+        let with_env = HoconParser::new()
+            .parse(&arena, "home = ${DATAVALUE_HOCON_TEST_HOME}/app")
+            .unwrap();
+        assert_eq!(with_env["home"].as_str(), Some("/home/test/app"));
+    }
+
+    #[test]
+    fn test_includes() {
+        let arena = Bump::new();
+        let parser = HoconParser::new().env(false).resolver(|name| {
+            Ok(match name {
+                "base.conf" => Some("name = base\nnested { x = 1 }".to_string()),
+                "inner.json" => Some(r#"{"y": ${x}}"#.to_string()),
+                "loop.conf" => Some("include \"loop.conf\"".to_string()),
+                _ => None,
+            })
+        });
+        let text = r#"
+            include "base"
+            nested { include file("inner") }
+            name = override
+            include "missing.conf"
+        "#;
+        let config = parser.parse(&arena, text).unwrap();
+        assert_eq!(config["name"].as_str(), Some("override"));
+        assert_eq!(crate::to_string(&config["nested"]), r#"{"x":1,"y":1}"#);
+
+        assert!(parser
+            .parse(&arena, "include required(\"missing.conf\")")
+            .is_err());
+        assert!(parser.parse(&arena, "include \"loop.conf\"").is_err());
+        assert!(parser.parse(&arena, "include url(\"http://x\")").is_err());
+
+        let dir = std::env::temp_dir().join(format!("datavalue-hocon-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("conf")).unwrap();
+        std::fs::write(dir.join("app.conf"), "include \"conf/db.conf\"\napp = demo").unwrap();
+        std::fs::write(
+            dir.join("conf/db.conf"),
+            "include \"port.conf\"\ndb.host = h",
+        )
+        .unwrap();
+        std::fs::write(dir.join("conf/port.conf"), "db.port = 1").unwrap();
+        let config = from_path(&arena, dir.join("app.conf")).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            crate::to_string(&config),
+            r#"{"db":{"port":1,"host":"h"},"app":"demo"}"#
+        );
+    }
+}
@@ -0,0 +1,252 @@
+//! TOML input and output, enabled by the `toml` feature
+//!
+//! A TOML document is always a table, so it loads as an object with the keys in
+//! file order. Dates and times are kept as their RFC 3339 text, like dates in
+//! JSON. TOML has no null, so writing a value that contains one is an error.
+
+use bumpalo::Bump;
+
+use crate::datavalue::{DataValue, Number};
+use crate::de::events::{value_events, JsonEvent};
+use crate::error::{Error, Location, Result};
+
+/// Parses a TOML document into an object
+///
+/// # Errors
+///
+/// Returns an error, with the location of the problem, if the input is not
+/// valid TOML.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::{format::toml, Bump};
+///
+/// let arena = Bump::new();
+/// let value = toml::from_str(&arena, r#"
+/// name = "api"
+///
+/// [server]
+/// ports = [80, 443]
+/// started = 2024-05-01T12:00:00Z
+/// "#).unwrap();
+///
+/// assert_eq!(value["server"]["ports"][1].as_i64(), Some(443));
+/// assert_eq!(value["server"]["started"].as_str(), Some("2024-05-01T12:00:00Z"));
+/// ```
+pub fn from_str<'a>(arena: &'a Bump, text: &str) -> Result<DataValue<'a>> {
+    /// A table or array being converted, with the entries converted so far and
+    /// its key in the parent table
+    enum Frame<'t, 'a> {
+        Array(
+            std::slice::Iter<'t, ::toml::Value>,
+            Vec<DataValue<'a>>,
+            &'a str,
+        ),
+        Table(
+            ::toml::map::Iter<'t, String, ::toml::Value>,
+            Vec<(&'a str, DataValue<'a>)>,
+            &'a str,
+        ),
+    }
+
+    let table: ::toml::Table = text.parse().map_err(|e: ::toml::de::Error| {
+        let offset = e.span().map_or(0, |span| span.start);
+        let line_start = text[..offset].rfind('\n').map_or(0, |i| i + 1);
+        Error::syntax_at(
+            e.message(),
+            Location {
+                line: text[..offset].matches('\n').count() + 1,
+                column: text[line_start..offset].chars().count() + 1,
+                byte_offset: offset,
+                path: String::new(),
+            },
+        )
+    })?;
+
+    let mut stack = vec![Frame::Table(table.iter(), Vec::new(), "")];
+    loop {
+        let (mut key, next) = match stack.last_mut() {
+            Some(Frame::Array(items, _, _)) => ("", items.next()),
+            Some(Frame::Table(entries, _, _)) => match entries.next() {
+                Some((key, value)) => (&*arena.alloc_str(key), Some(value)),
+                None => ("", None),
+            },
+            None => unreachable!("the root table is returned when it is closed"),
+        };
+        let value = match next {
+            Some(::toml::Value::Array(items)) => {
+                stack.push(Frame::Array(
+                    items.iter(),
+                    Vec::with_capacity(items.len()),
+                    key,
+                ));
+                continue;
+            }
+            Some(::toml::Value::Table(table)) => {
+                stack.push(Frame::Table(table.iter(), Vec::new(), key));
+                continue;
+            }
+            Some(::toml::Value::String(s)) => DataValue::String(arena.alloc_str(s)),
+            Some(::toml::Value::Integer(i)) => DataValue::Number(Number::Integer(*i)),
+            Some(::toml::Value::Float(f)) => DataValue::Number(Number::Float(*f)),
+            Some(::toml::Value::Boolean(b)) => DataValue::Bool(*b),
+            Some(::toml::Value::Datetime(dt)) => {
+                DataValue::String(arena.alloc_str(&dt.to_string()))
+            }
+            None => {
+                let value = match stack.pop() {
+                    Some(Frame::Array(_, items, own_key)) => {
+                        key = own_key;
+                        DataValue::Array(arena.alloc_slice_clone(&items))
+                    }
+                    Some(Frame::Table(_, entries, own_key)) => {
+                        key = own_key;
+                        DataValue::Object(arena.alloc_slice_clone(&entries))
+                    }
+                    None => unreachable!("a frame was open"),
+                };
+                if stack.is_empty() {
+                    return Ok(value);
+                }
+                value
+            }
+        };
+        match stack.last_mut() {
+            Some(Frame::Array(_, items, _)) => items.push(value),
+            Some(Frame::Table(_, entries, _)) => entries.push((key, value)),
+            None => unreachable!("the root table is returned when it is closed"),
+        }
+    }
+}
+
+/// Serializes an object as a TOML document
+///
+/// Dates, times and durations are written as strings, as in JSON output.
+///
+/// # Errors
+///
+/// Returns an error if the value is not an object, contains a null, or holds raw
+/// JSON that does not parse.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::{format::toml, from_str, Bump};
+///
+/// let arena = Bump::new();
+/// let value = from_str(&arena, r#"{"name": "api", "server": {"port": 80}}"#).unwrap();
+/// assert_eq!(toml::to_string(&value).unwrap(), "name = \"api\"\n\n[server]\nport = 80\n");
+/// ```
+pub fn to_string(value: &DataValue<'_>) -> Result<String> {
+    /// An array or table being built, with the key of the next entry
+    enum Open {
+        Array(::toml::value::Array),
+        Table(::toml::Table, String),
+    }
+
+    if !matches!(value, DataValue::Object(_)) {
+        return Err(Error::expected_type(
+            "object",
+            format!("{:?}", value.get_type()).to_lowercase(),
+        ));
+    }
+
+    let mut stack: Vec<Open> = Vec::new();
+    for event in value_events(value)? {
+        let node = match event {
+            JsonEvent::StartArray => {
+                stack.push(Open::Array(Vec::new()));
+                continue;
+            }
+            JsonEvent::StartObject => {
+                stack.push(Open::Table(::toml::Table::new(), String::new()));
+                continue;
+            }
+            JsonEvent::Key(key) => {
+                if let Some(Open::Table(_, next)) = stack.last_mut() {
+                    *next = key.into_owned();
+                }
+                continue;
+            }
+            JsonEvent::EndArray | JsonEvent::EndObject => match stack.pop() {
+                Some(Open::Array(items)) => ::toml::Value::Array(items),
+                Some(Open::Table(table, _)) => ::toml::Value::Table(table),
+                None => unreachable!("events are balanced"),
+            },
+            JsonEvent::Null => return Err(Error::custom("TOML has no null value")),
+            JsonEvent::Bool(b) => ::toml::Value::Boolean(b),
+            JsonEvent::String(s) => ::toml::Value::String(s.into_owned()),
+            JsonEvent::Number(Number::Integer(i)) => ::toml::Value::Integer(i),
+            JsonEvent::Number(number) => {
+                ::toml::Value::Float(DataValue::Number(number).as_f64().unwrap_or(f64::NAN))
+            }
+        };
+        match stack.last_mut() {
+            None => match node {
+                ::toml::Value::Table(table) => return Ok(table.to_string()),
+                _ => unreachable!("the root is an object"),
+            },
+            Some(Open::Array(items)) => items.push(node),
+            Some(Open::Table(table, key)) => {
+                table.insert(std::mem::take(key), node);
+            }
+        }
+    }
+    unreachable!("the root object is closed by the last event")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_str as from_json;
+
+    #[test]
+    fn test_toml_document() {
+        let arena = Bump::new();
+        let value = from_str(
+            &arena,
+            concat!(
+                "title = \"x\"\n",
+                "zeta = 1\n",
+                "alpha = [1.5, \"two\", [true], { k = 'v' }]\n",
+                "day = 2024-05-01\n",
+                "[owner.info]\n",
+                "name = \"Ada\"\n",
+                "[[items]]\n",
+                "id = 1\n",
+                "[[items]]\n",
+                "id = 2\n",
+            ),
+        )
+        .unwrap();
+        let expected = from_json(
+            &arena,
+            r#"{"title": "x", "zeta": 1, "alpha": [1.5, "two", [true], {"k": "v"}],
+                "day": "2024-05-01", "owner": {"info": {"name": "Ada"}},
+                "items": [{"id": 1}, {"id": 2}]}"#,
+        )
+        .unwrap();
+        assert_eq!(value, expected);
+
+        let error = from_str(&arena, "a = 1\nb = \n").unwrap_err();
+        assert_eq!(error.location().map(|l| l.line), Some(2));
+        assert!(from_str(&arena, "a = 1\na = 2").is_err());
+    }
+
+    #[test]
+    fn test_toml_round_trip() {
+        let arena = Bump::new();
+        let value = from_json(
+            &arena,
+            r#"{"b": "s", "a": [1, 2.5], "t": {"nested": {"x": false}}, "list": [{"id": 1}]}"#,
+        )
+        .unwrap();
+        let text = to_string(&value).unwrap();
+        assert_eq!(from_str(&arena, &text).unwrap(), value);
+
+        assert!(to_string(&crate::helpers::int(1)).is_err());
+        let with_null = from_json(&arena, r#"{"a": [null]}"#).unwrap();
+        assert!(to_string(&with_null).is_err());
+    }
+}
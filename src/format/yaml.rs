@@ -0,0 +1,379 @@
+//! YAML input and output, enabled by the `yaml` feature
+//!
+//! Documents are read from the events of the yaml-rust2 parser and written with
+//! its emitter. Plain scalars are typed with the YAML core schema, so `true`,
+//! `~`, `0x1f` and `.inf` become a bool, null, an integer and a float, while
+//! quoted and block scalars always stay strings. Mapping keys that are numbers or
+//! other scalars are kept as their text, and repeated keys are rejected.
+//!
+//! An alias refers to the value built for its anchor, which is shared in the
+//! arena rather than copied, so nested aliases cannot blow up the size of the
+//! loaded document.
+
+use std::collections::{HashMap, HashSet};
+
+use bumpalo::Bump;
+use yaml_rust2::parser::{Parser, Tag};
+use yaml_rust2::scanner::{Marker, TScalarStyle};
+use yaml_rust2::yaml::Hash;
+use yaml_rust2::{Event, ScanError, Yaml, YamlEmitter};
+
+use crate::datavalue::{DataValue, Number};
+use crate::de::events::{value_events, JsonEvent};
+use crate::error::{Error, Location, Result};
+
+/// Tag handle of the YAML core schema types, as in `!!int`
+const CORE_SCHEMA: &str = "tag:yaml.org,2002:";
+
+/// Parses a single YAML document
+///
+/// Empty input is read as null.
+///
+/// # Errors
+///
+/// Returns an error if the input is not valid YAML, holds more than one
+/// document, uses a mapping or sequence as a key, repeats a key, refers to an
+/// unknown anchor, or tags a scalar with a core type it does not match.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::{format::yaml, Bump};
+///
+/// let arena = Bump::new();
+/// let value = yaml::from_str(&arena, "
+/// defaults: &defaults
+///   retries: 3
+///   timeout: 1.5
+/// service:
+///   name: 'api'
+///   settings: *defaults
+///   ports: [80, 443]
+/// ").unwrap();
+///
+/// assert_eq!(value["service"]["settings"]["retries"].as_i64(), Some(3));
+/// assert_eq!(value["service"]["ports"][1].as_i64(), Some(443));
+/// ```
+pub fn from_str<'a>(arena: &'a Bump, text: &str) -> Result<DataValue<'a>> {
+    /// A sequence or mapping being read, with its anchor id (0 if none)
+    enum Frame<'a> {
+        Array(Vec<DataValue<'a>>, usize),
+        Object(
+            Vec<(&'a str, DataValue<'a>)>,
+            HashSet<&'a str>,
+            Option<&'a str>,
+            usize,
+        ),
+    }
+
+    let mut parser = Parser::new_from_str(text);
+    let mut stack: Vec<Frame<'a>> = Vec::new();
+    let mut anchors: HashMap<usize, DataValue<'a>> = HashMap::new();
+    let mut root = None;
+    let mut documents = 0;
+    loop {
+        let (event, mark) = parser.next_token().map_err(scan_error)?;
+        let (value, anchor) = match event {
+            Event::StreamEnd => break,
+            Event::DocumentStart => {
+                documents += 1;
+                if documents > 1 {
+                    return Err(syntax_error(
+                        "YAML input holds more than one document",
+                        mark,
+                    ));
+                }
+                continue;
+            }
+            Event::Nothing | Event::StreamStart | Event::DocumentEnd => continue,
+            Event::SequenceStart(anchor, _) => {
+                stack.push(Frame::Array(Vec::new(), anchor));
+                continue;
+            }
+            Event::MappingStart(anchor, _) => {
+                stack.push(Frame::Object(Vec::new(), HashSet::new(), None, anchor));
+                continue;
+            }
+            Event::SequenceEnd => match stack.pop() {
+                Some(Frame::Array(items, anchor)) => {
+                    (DataValue::Array(arena.alloc_slice_clone(&items)), anchor)
+                }
+                _ => unreachable!("the parser closes the sequence it opened"),
+            },
+            Event::MappingEnd => match stack.pop() {
+                Some(Frame::Object(entries, _, _, anchor)) => {
+                    (DataValue::Object(arena.alloc_slice_clone(&entries)), anchor)
+                }
+                _ => unreachable!("the parser closes the mapping it opened"),
+            },
+            Event::Scalar(text, style, anchor, tag) => {
+                (scalar(arena, &text, style, tag, mark)?, anchor)
+            }
+            Event::Alias(id) => match anchors.get(&id) {
+                Some(value) => (value.clone(), 0),
+                None => return Err(syntax_error("unknown YAML alias", mark)),
+            },
+        };
+        if anchor != 0 {
+            anchors.insert(anchor, value.clone());
+        }
+
+        match stack.last_mut() {
+            None => root = Some(value),
+            Some(Frame::Array(items, _)) => items.push(value),
+            Some(Frame::Object(entries, keys, key, _)) => match key.take() {
+                Some(key) => entries.push((key, value)),
+                None => {
+                    let name = match value {
+                        DataValue::String(s) => s,
+                        DataValue::Array(_) | DataValue::Object(_) => {
+                            return Err(syntax_error("YAML mapping keys must be scalars", mark))
+                        }
+                        scalar => &*arena.alloc_str(&scalar.to_string()),
+                    };
+                    if !keys.insert(name) {
+                        return Err(syntax_error(format!("duplicate YAML key {:?}", name), mark));
+                    }
+                    *key = Some(name);
+                }
+            },
+        }
+    }
+    Ok(root.unwrap_or(DataValue::Null))
+}
+
+/// Serializes a value as a YAML document
+///
+/// Dates, times and durations are written as strings, as in JSON output.
+///
+/// # Errors
+///
+/// Returns an error if the value holds raw JSON that does not parse.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::{format::yaml, from_str, Bump};
+///
+/// let arena = Bump::new();
+/// let value = from_str(&arena, r#"{"name": "api", "ports": [80, 443]}"#).unwrap();
+/// assert_eq!(yaml::to_string(&value).unwrap(), "name: api\nports:\n  - 80\n  - 443");
+/// ```
+pub fn to_string(value: &DataValue<'_>) -> Result<String> {
+    /// A sequence or mapping being built, with the key of the next entry
+    enum Open {
+        Array(Vec<Yaml>),
+        Object(Hash, Option<Yaml>),
+    }
+
+    let mut stack: Vec<Open> = Vec::new();
+    let mut root = None;
+    for event in value_events(value)? {
+        let node = match event {
+            JsonEvent::StartArray => {
+                stack.push(Open::Array(Vec::new()));
+                continue;
+            }
+            JsonEvent::StartObject => {
+                stack.push(Open::Object(Hash::new(), None));
+                continue;
+            }
+            JsonEvent::Key(key) => {
+                if let Some(Open::Object(_, next)) = stack.last_mut() {
+                    *next = Some(Yaml::String(key.into_owned()));
+                }
+                continue;
+            }
+            JsonEvent::EndArray | JsonEvent::EndObject => match stack.pop() {
+                Some(Open::Array(items)) => Yaml::Array(items),
+                Some(Open::Object(map, _)) => Yaml::Hash(map),
+                None => unreachable!("events are balanced"),
+            },
+            JsonEvent::Null => Yaml::Null,
+            JsonEvent::Bool(b) => Yaml::Boolean(b),
+            JsonEvent::String(s) => Yaml::String(s.into_owned()),
+            JsonEvent::Number(Number::Integer(i)) => Yaml::Integer(i),
+            JsonEvent::Number(number) => {
+                let value = DataValue::Number(number);
+                match value.as_f64() {
+                    Some(f) if f.is_nan() => Yaml::Real(".nan".to_string()),
+                    Some(f) if f.is_infinite() && f > 0.0 => Yaml::Real(".inf".to_string()),
+                    Some(f) if f.is_infinite() => Yaml::Real("-.inf".to_string()),
+                    _ => Yaml::Real(value.to_string()),
+                }
+            }
+        };
+        match stack.last_mut() {
+            None => root = Some(node),
+            Some(Open::Array(items)) => items.push(node),
+            Some(Open::Object(map, key)) => {
+                map.insert(key.take().unwrap_or(Yaml::Null), node);
+            }
+        }
+    }
+
+    let mut output = String::new();
+    YamlEmitter::new(&mut output)
+        .dump(&root.unwrap_or(Yaml::Null))
+        .map_err(|e| Error::custom(format!("YAML output failed: {:?}", e)))?;
+    // Drop the document start marker the emitter always writes
+    Ok(match output.strip_prefix("---\n") {
+        Some(body) => body.to_string(),
+        None => output
+            .strip_prefix("--- ")
+            .map(str::to_string)
+            .unwrap_or(output),
+    })
+}
+
+/// Types a scalar with the YAML core schema
+fn scalar<'a>(
+    arena: &'a Bump,
+    text: &str,
+    style: TScalarStyle,
+    tag: Option<Tag>,
+    mark: Marker,
+) -> Result<DataValue<'a>> {
+    let string = || DataValue::String(arena.alloc_str(text));
+    if style != TScalarStyle::Plain {
+        return Ok(string());
+    }
+    let expected = match &tag {
+        None => None,
+        Some(Tag { handle, suffix }) if handle == CORE_SCHEMA => match suffix.as_str() {
+            "bool" | "int" | "float" | "null" => Some(suffix.as_str()),
+            _ => return Ok(string()),
+        },
+        Some(_) => return Ok(string()),
+    };
+
+    let (kind, value) = match Yaml::from_str(text) {
+        Yaml::Null => ("null", DataValue::Null),
+        Yaml::Boolean(b) => ("bool", DataValue::Bool(b)),
+        Yaml::Integer(i) => ("int", DataValue::Number(Number::Integer(i))),
+        real @ Yaml::Real(_) => (
+            "float",
+            DataValue::Number(Number::Float(real.as_f64().unwrap_or(f64::NAN))),
+        ),
+        _ => ("str", string()),
+    };
+    match expected {
+        // An integer is a valid float
+        Some("float") if kind == "int" => Ok(DataValue::Number(Number::Float(
+            value.as_f64().unwrap_or_default(),
+        ))),
+        Some(expected) if expected != kind => Err(syntax_error(
+            format!("{:?} is not a valid YAML {}", text, expected),
+            mark,
+        )),
+        _ => Ok(value),
+    }
+}
+
+fn syntax_error(msg: impl Into<String>, mark: Marker) -> Error {
+    Error::syntax_at(
+        msg,
+        Location {
+            line: mark.line(),
+            column: mark.col() + 1,
+            byte_offset: mark.index(),
+            path: String::new(),
+        },
+    )
+}
+
+fn scan_error(error: ScanError) -> Error {
+    syntax_error(error.info(), *error.marker())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_str as from_json;
+
+    #[test]
+    fn test_yaml_scalars_and_structure() {
+        let arena = Bump::new();
+        let value = from_str(
+            &arena,
+            concat!(
+                "null_value: ~\n",
+                "empty:\n",
+                "flags: [true, False]\n",
+                "hex: 0x1f\n",
+                "real: 2.5\n",
+                "inf: -.inf\n",
+                "quoted: '12'\n",
+                "block: |\n  line\n",
+                "tagged: !!str 7\n",
+                "float: !!float 3\n",
+                "1: numeric key\n",
+                "nested:\n  - a: 1\n  - [x, y]\n",
+            ),
+        )
+        .unwrap();
+        let expected = from_json(
+            &arena,
+            r#"{"null_value": null, "empty": null, "flags": [true, false], "hex": 31,
+                "real": 2.5, "quoted": "12", "block": "line\n", "tagged": "7",
+                "float": 3.0, "1": "numeric key", "nested": [{"a": 1}, ["x", "y"]]}"#,
+        )
+        .unwrap();
+        for (key, expected) in expected.as_object().unwrap() {
+            assert_eq!(&value[*key], expected, "{}", key);
+        }
+        assert_eq!(value["inf"].as_f64(), Some(f64::NEG_INFINITY));
+        assert_eq!(from_str(&arena, "").unwrap(), DataValue::Null);
+    }
+
+    #[test]
+    fn test_yaml_errors() {
+        let arena = Bump::new();
+        for bad in [
+            "a: 1\na: 2",
+            "? [1]\n: x",
+            "--- 1\n--- 2",
+            "x: *missing",
+            "x: !!int abc",
+            "[1, 2",
+        ] {
+            assert!(from_str(&arena, bad).is_err(), "accepted {:?}", bad);
+        }
+        let error = from_str(&arena, "a: 1\nb: [\n").unwrap_err();
+        assert!(error.location().is_some());
+    }
+
+    #[test]
+    fn test_yaml_aliases_are_shared() {
+        let arena = Bump::new();
+        // Each level refers to the previous one twice, which would be 2^40
+        // values if aliases were copied
+        let mut text = String::from("l0: &l0 [x]\n");
+        for level in 1..=40 {
+            text.push_str(&format!(
+                "l{level}: &l{level} [*l{}, *l{}]\n",
+                level - 1,
+                level - 1
+            ));
+        }
+        let value = from_str(&arena, &text).unwrap();
+        assert_eq!(value["l40"][0][1][0].as_array().map(|a| a.len()), Some(2));
+    }
+
+    #[test]
+    fn test_yaml_round_trip() {
+        let arena = Bump::new();
+        let value = from_json(
+            &arena,
+            r#"{"s": "x: y", "n": [1, -2.5, 1e300], "e": {}, "a": [], "b": true, "z": null, "t": "true"}"#,
+        )
+        .unwrap();
+        let text = to_string(&value).unwrap();
+        assert_eq!(from_str(&arena, &text).unwrap(), value);
+        assert_eq!(
+            to_string(&DataValue::Number(Number::Float(f64::NAN))).unwrap(),
+            ".nan"
+        );
+        assert_eq!(to_string(&crate::helpers::int(3)).unwrap(), "3");
+    }
+}
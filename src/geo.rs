@@ -0,0 +1,435 @@
+//! GeoJSON support for DataValue
+//!
+//! This module recognizes GeoJSON structures (RFC 7946) stored in a DataValue,
+//! validates geometry shapes, computes bounding boxes and performs point-in-polygon
+//! tests without converting into dedicated geometry types.
+//!
+//! Enabled with the `geo` feature.
+
+use crate::datavalue::DataValue;
+use crate::error::{Error, Result};
+
+/// An axis-aligned bounding box in `[min_x, min_y, max_x, max_y]` order
+///
+/// For geographic coordinates this is `[west, south, east, north]`.
+pub type BBox = [f64; 4];
+
+/// Geometry types that carry a `coordinates` member
+const COORDINATE_GEOMETRIES: &[&str] = &[
+    "Point",
+    "MultiPoint",
+    "LineString",
+    "MultiLineString",
+    "Polygon",
+    "MultiPolygon",
+];
+
+/// Returns true if the value is a valid GeoJSON object
+///
+/// Accepts geometries, `Feature` and `FeatureCollection` objects.
+///
+/// # Example
+///
+/// ```
+/// # use datavalue_rs::{geo, Bump, from_str};
+/// let arena = Bump::new();
+/// let point = from_str(&arena, r#"{"type": "Point", "coordinates": [2.35, 48.85]}"#).unwrap();
+/// assert!(geo::is_geojson(&point));
+///
+/// let broken = from_str(&arena, r#"{"type": "Point", "coordinates": [2.35]}"#).unwrap();
+/// assert!(!geo::is_geojson(&broken));
+/// ```
+pub fn is_geojson(value: &DataValue<'_>) -> bool {
+    validate(value).is_ok()
+}
+
+/// Validates a GeoJSON object
+///
+/// Checks the `type` member and the nesting and arity of `coordinates`:
+/// positions need at least two numbers, line strings at least two positions, and
+/// polygon rings at least four positions with matching first and last positions.
+///
+/// # Errors
+///
+/// Returns an error describing the first structural problem found.
+///
+/// # Example
+///
+/// ```
+/// # use datavalue_rs::{geo, Bump, from_str};
+/// let arena = Bump::new();
+/// let open_ring = from_str(&arena, r#"{
+///     "type": "Polygon",
+///     "coordinates": [[[0, 0], [1, 0], [1, 1], [0, 1]]]
+/// }"#).unwrap();
+///
+/// let err = geo::validate(&open_ring).unwrap_err();
+/// assert!(err.to_string().contains("closed"));
+/// ```
+pub fn validate(value: &DataValue<'_>) -> Result<()> {
+    match geo_type(value)? {
+        "Feature" => match value.get("geometry") {
+            Some(DataValue::Null) => Ok(()),
+            Some(geometry) => validate_geometry(geometry),
+            None => Err(Error::missing_field("geometry")),
+        },
+        "FeatureCollection" => {
+            let features = value
+                .get("features")
+                .ok_or_else(|| Error::missing_field("features"))?
+                .as_array()
+                .ok_or_else(|| Error::custom("GeoJSON features must be an array"))?;
+            for feature in features {
+                if geo_type(feature)? != "Feature" {
+                    return Err(Error::custom("FeatureCollection members must be Features"));
+                }
+                validate(feature)?;
+            }
+            Ok(())
+        }
+        _ => validate_geometry(value),
+    }
+}
+
+/// Computes the bounding box of a GeoJSON object
+///
+/// Works on geometries, features and feature collections. Only the first two
+/// coordinates of each position are considered.
+///
+/// # Returns
+///
+/// A Result containing the bounding box, or None for objects with no positions
+/// (an empty collection or a feature with a null geometry). Returns an Error if
+/// the value is not valid GeoJSON.
+///
+/// # Example
+///
+/// ```
+/// # use datavalue_rs::{geo, Bump, from_str};
+/// let arena = Bump::new();
+/// let line = from_str(&arena, r#"{
+///     "type": "LineString",
+///     "coordinates": [[-1, 5], [3, -2], [0, 0]]
+/// }"#).unwrap();
+///
+/// assert_eq!(geo::bbox(&line).unwrap(), Some([-1.0, -2.0, 3.0, 5.0]));
+/// ```
+pub fn bbox(value: &DataValue<'_>) -> Result<Option<BBox>> {
+    validate(value)?;
+    let mut acc: Option<BBox> = None;
+    collect_bbox(value, &mut acc);
+    Ok(acc)
+}
+
+/// Tests whether a point lies inside a polygon
+///
+/// `point` may be a `Point` geometry or a bare `[x, y]` position. `polygon` may be
+/// a `Polygon` or `MultiPolygon` geometry, or a `Feature` wrapping one. Holes are
+/// respected; points exactly on an edge are not guaranteed either way.
+///
+/// # Returns
+///
+/// A Result containing whether the point is inside, or an Error if either operand
+/// has the wrong shape.
+///
+/// # Example
+///
+/// ```
+/// # use datavalue_rs::{geo, Bump, from_str};
+/// let arena = Bump::new();
+/// let square = from_str(&arena, r#"{
+///     "type": "Polygon",
+///     "coordinates": [[[0, 0], [10, 0], [10, 10], [0, 10], [0, 0]]]
+/// }"#).unwrap();
+///
+/// let inside = from_str(&arena, "[5, 5]").unwrap();
+/// let outside = from_str(&arena, r#"{"type": "Point", "coordinates": [15, 5]}"#).unwrap();
+///
+/// assert!(geo::point_in_polygon(&inside, &square).unwrap());
+/// assert!(!geo::point_in_polygon(&outside, &square).unwrap());
+/// ```
+pub fn point_in_polygon(point: &DataValue<'_>, polygon: &DataValue<'_>) -> Result<bool> {
+    let position = match point {
+        DataValue::Array(_) => point,
+        _ => {
+            if geo_type(point)? != "Point" {
+                return Err(Error::custom("Expected a Point geometry or a position"));
+            }
+            point
+                .get("coordinates")
+                .ok_or_else(|| Error::missing_field("coordinates"))?
+        }
+    };
+    validate_position(position)?;
+    let (x, y) = xy(position);
+
+    let geometry = match geo_type(polygon)? {
+        "Feature" => polygon
+            .get("geometry")
+            .ok_or_else(|| Error::missing_field("geometry"))?,
+        _ => polygon,
+    };
+    validate_geometry(geometry)?;
+    let coordinates = geometry
+        .get("coordinates")
+        .ok_or_else(|| Error::missing_field("coordinates"))?;
+
+    match geo_type(geometry)? {
+        "Polygon" => Ok(in_polygon(x, y, coordinates)),
+        "MultiPolygon" => Ok(children(coordinates)
+            .iter()
+            .any(|polygon| in_polygon(x, y, polygon))),
+        other => Err(Error::expected_type("Polygon or MultiPolygon", other)),
+    }
+}
+
+// Private helper functions
+
+fn geo_type<'a>(value: &DataValue<'a>) -> Result<&'a str> {
+    match value.get("type") {
+        Some(DataValue::String(t)) => Ok(t),
+        Some(_) => Err(Error::custom("GeoJSON type must be a string")),
+        None if value.is_object() => Err(Error::missing_field("type")),
+        None => Err(Error::expected_type(
+            "GeoJSON object",
+            format!("{:?}", value.get_type()).to_lowercase(),
+        )),
+    }
+}
+
+fn validate_geometry(value: &DataValue<'_>) -> Result<()> {
+    let kind = geo_type(value)?;
+    if kind == "GeometryCollection" {
+        let geometries = value
+            .get("geometries")
+            .ok_or_else(|| Error::missing_field("geometries"))?
+            .as_array()
+            .ok_or_else(|| Error::custom("GeoJSON geometries must be an array"))?;
+        return geometries.iter().try_for_each(validate_geometry);
+    }
+    if !COORDINATE_GEOMETRIES.contains(&kind) {
+        return Err(Error::custom(format!("Unknown GeoJSON type: {}", kind)));
+    }
+
+    let coordinates = value
+        .get("coordinates")
+        .ok_or_else(|| Error::missing_field("coordinates"))?;
+    match kind {
+        "Point" => validate_position(coordinates),
+        "MultiPoint" => each(coordinates, validate_position),
+        "LineString" => validate_line(coordinates),
+        "MultiLineString" => each(coordinates, validate_line),
+        "Polygon" => validate_polygon(coordinates),
+        _ => each(coordinates, validate_polygon),
+    }
+}
+
+fn children<'v, 'a>(value: &'v DataValue<'a>) -> &'v [DataValue<'a>] {
+    value.as_array().unwrap_or(&[])
+}
+
+fn each(value: &DataValue<'_>, check: fn(&DataValue<'_>) -> Result<()>) -> Result<()> {
+    match value {
+        DataValue::Array(items) => items.iter().try_for_each(check),
+        _ => Err(Error::custom("GeoJSON coordinates must be nested arrays")),
+    }
+}
+
+fn validate_position(value: &DataValue<'_>) -> Result<()> {
+    match value {
+        DataValue::Array(items) if items.len() >= 2 && items.iter().all(|n| n.is_number()) => {
+            Ok(())
+        }
+        _ => Err(Error::custom(
+            "GeoJSON position must be an array of at least two numbers",
+        )),
+    }
+}
+
+fn validate_line(value: &DataValue<'_>) -> Result<()> {
+    each(value, validate_position)?;
+    if children(value).len() < 2 {
+        return Err(Error::custom(
+            "GeoJSON line string needs at least two positions",
+        ));
+    }
+    Ok(())
+}
+
+fn validate_polygon(value: &DataValue<'_>) -> Result<()> {
+    each(value, |ring| {
+        each(ring, validate_position)?;
+        let positions = children(ring);
+        if positions.len() < 4 {
+            return Err(Error::custom(
+                "GeoJSON polygon ring needs at least four positions",
+            ));
+        }
+        if positions.first() != positions.last() {
+            return Err(Error::custom("GeoJSON polygon ring must be closed"));
+        }
+        Ok(())
+    })
+}
+
+fn xy(position: &DataValue<'_>) -> (f64, f64) {
+    let items = children(position);
+    (
+        items[0].as_f64().unwrap_or_default(),
+        items[1].as_f64().unwrap_or_default(),
+    )
+}
+
+fn collect_bbox(value: &DataValue<'_>, acc: &mut Option<BBox>) {
+    match value.get("type").and_then(DataValue::as_str) {
+        Some("Feature") => {
+            if let Some(geometry) = value.get("geometry") {
+                collect_bbox(geometry, acc);
+            }
+        }
+        Some("FeatureCollection") => {
+            for feature in value.get("features").map(children).unwrap_or(&[]) {
+                collect_bbox(feature, acc);
+            }
+        }
+        Some("GeometryCollection") => {
+            for geometry in value.get("geometries").map(children).unwrap_or(&[]) {
+                collect_bbox(geometry, acc);
+            }
+        }
+        Some(_) => {
+            if let Some(coordinates) = value.get("coordinates") {
+                collect_positions(coordinates, acc);
+            }
+        }
+        None => {}
+    }
+}
+
+fn collect_positions(value: &DataValue<'_>, acc: &mut Option<BBox>) {
+    let items = children(value);
+    if items.first().is_some_and(DataValue::is_number) {
+        let (x, y) = xy(value);
+        *acc = Some(match *acc {
+            None => [x, y, x, y],
+            Some([min_x, min_y, max_x, max_y]) => {
+                [min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y)]
+            }
+        });
+    } else {
+        for item in items {
+            collect_positions(item, acc);
+        }
+    }
+}
+
+/// Even-odd test against the outer ring, excluding points inside any hole
+fn in_polygon(x: f64, y: f64, rings: &DataValue<'_>) -> bool {
+    let rings = children(rings);
+    match rings.split_first() {
+        Some((outer, holes)) => in_ring(x, y, outer) && !holes.iter().any(|h| in_ring(x, y, h)),
+        None => false,
+    }
+}
+
+fn in_ring(x: f64, y: f64, ring: &DataValue<'_>) -> bool {
+    let points: Vec<(f64, f64)> = children(ring).iter().map(xy).collect();
+    let mut inside = false;
+    let mut j = points.len().wrapping_sub(1);
+    for i in 0..points.len() {
+        let (xi, yi) = points[i];
+        let (xj, yj) = points[j];
+        if (yi > y) != (yj > y) && x < (xj - xi) * (y - yi) / (yj - yi) + xi {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_str;
+    use bumpalo::Bump;
+
+    #[test]
+    fn test_validate_shapes() {
+        let arena = Bump::new();
+        let valid = [
+            r#"{"type": "Point", "coordinates": [1, 2, 3]}"#,
+            r#"{"type": "MultiPoint", "coordinates": [[1, 2], [3, 4]]}"#,
+            r#"{"type": "MultiLineString", "coordinates": [[[0, 0], [1, 1]]]}"#,
+            r#"{"type": "GeometryCollection", "geometries": []}"#,
+            r#"{"type": "Feature", "geometry": null, "properties": {}}"#,
+            r#"{"type": "FeatureCollection", "features": [
+                {"type": "Feature", "geometry": {"type": "Point", "coordinates": [0, 0]}}
+            ]}"#,
+        ];
+        for json in valid {
+            let value = from_str(&arena, json).unwrap();
+            assert!(validate(&value).is_ok(), "rejected {}", json);
+        }
+
+        let invalid = [
+            r#"{"type": "Point", "coordinates": ["a", "b"]}"#,
+            r#"{"type": "LineString", "coordinates": [[0, 0]]}"#,
+            r#"{"type": "Polygon", "coordinates": [[[0, 0], [1, 1], [0, 0]]]}"#,
+            r#"{"type": "Circle", "coordinates": [0, 0]}"#,
+            r#"{"type": "FeatureCollection", "features": [{"type": "Point", "coordinates": [0, 0]}]}"#,
+            r#"{"coordinates": [0, 0]}"#,
+            r#"[0, 0]"#,
+        ];
+        for json in invalid {
+            let value = from_str(&arena, json).unwrap();
+            assert!(validate(&value).is_err(), "accepted {}", json);
+        }
+    }
+
+    #[test]
+    fn test_bbox_collections() {
+        let arena = Bump::new();
+        let collection = from_str(
+            &arena,
+            r#"{"type": "FeatureCollection", "features": [
+                {"type": "Feature", "geometry": {"type": "Point", "coordinates": [10, 20]}},
+                {"type": "Feature", "geometry": {"type": "MultiPolygon", "coordinates": [
+                    [[[-5, -5], [0, -5], [0, 0], [-5, -5]]]
+                ]}},
+                {"type": "Feature", "geometry": null}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(bbox(&collection).unwrap(), Some([-5.0, -5.0, 10.0, 20.0]));
+
+        let empty = from_str(&arena, r#"{"type": "FeatureCollection", "features": []}"#).unwrap();
+        assert_eq!(bbox(&empty).unwrap(), None);
+    }
+
+    #[test]
+    fn test_point_in_polygon_with_hole() {
+        let arena = Bump::new();
+        let donut = from_str(
+            &arena,
+            r#"{"type": "Feature", "geometry": {"type": "Polygon", "coordinates": [
+                [[0, 0], [10, 0], [10, 10], [0, 10], [0, 0]],
+                [[4, 4], [6, 4], [6, 6], [4, 6], [4, 4]]
+            ]}}"#,
+        )
+        .unwrap();
+
+        let ring = from_str(&arena, "[2, 2]").unwrap();
+        let hole = from_str(&arena, "[5, 5]").unwrap();
+        let outside = from_str(&arena, "[-1, 5]").unwrap();
+        assert!(point_in_polygon(&ring, &donut).unwrap());
+        assert!(!point_in_polygon(&hole, &donut).unwrap());
+        assert!(!point_in_polygon(&outside, &donut).unwrap());
+
+        let line = from_str(
+            &arena,
+            r#"{"type": "LineString", "coordinates": [[0, 0], [1, 1]]}"#,
+        )
+        .unwrap();
+        assert!(point_in_polygon(&ring, &line).is_err());
+    }
+}
@@ -0,0 +1,398 @@
+//! Graph views over DataValue documents
+//!
+//! This module interprets arrays of objects as the nodes and edges of a directed
+//! graph, so dependency graphs and similar JSON structures can be queried for
+//! reachability and neighborhoods without copying them into a graph library.
+
+use std::collections::{HashMap, VecDeque};
+
+use bumpalo::Bump;
+
+use crate::datavalue::DataValue;
+use crate::error::{Error, Result};
+
+/// Field names used to interpret objects as nodes and edges
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::graph::GraphSpec;
+///
+/// let spec = GraphSpec { node_key: "name", source_key: "from", target_key: "to" };
+/// assert_eq!(GraphSpec::default().node_key, "id");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GraphSpec<'k> {
+    /// Field holding the identifier of each node
+    pub node_key: &'k str,
+    /// Field holding the identifier of the node an edge starts from
+    pub source_key: &'k str,
+    /// Field holding the identifier of the node an edge points to
+    pub target_key: &'k str,
+}
+
+impl Default for GraphSpec<'_> {
+    fn default() -> Self {
+        GraphSpec {
+            node_key: "id",
+            source_key: "source",
+            target_key: "target",
+        }
+    }
+}
+
+/// Direction in which edges are followed during traversal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Follow edges from source to target
+    Outgoing,
+    /// Follow edges from target to source
+    Incoming,
+    /// Follow edges either way
+    Both,
+}
+
+/// A directed graph borrowed from DataValue node and edge arrays
+///
+/// Node identifiers may be strings or numbers; they are matched by their JSON
+/// representation, so `1` and `"1"` are different nodes.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::{graph::{Graph, GraphSpec}, Bump, from_str};
+///
+/// let arena = Bump::new();
+/// let doc = from_str(&arena, r#"{
+///     "nodes": [{"id": "app"}, {"id": "http"}, {"id": "tls"}, {"id": "log"}],
+///     "edges": [
+///         {"source": "app", "target": "http"},
+///         {"source": "http", "target": "tls"},
+///         {"source": "app", "target": "log"}
+///     ]
+/// }"#).unwrap();
+///
+/// let graph = Graph::from_document(&doc, GraphSpec::default()).unwrap();
+/// let deps = graph.reachable(&arena, "http").unwrap();
+/// assert_eq!(datavalue_rs::to_string(&deps), r#"[{"id":"tls"}]"#);
+/// ```
+#[derive(Debug)]
+pub struct Graph<'v, 'a> {
+    nodes: &'v [DataValue<'a>],
+    index: HashMap<String, usize>,
+    outgoing: Vec<Vec<usize>>,
+    incoming: Vec<Vec<usize>>,
+}
+
+impl<'v, 'a> Graph<'v, 'a> {
+    /// Builds a graph from a `{"nodes": [...], "edges": [...]}` document
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if either array is missing, or for the conditions listed on
+    /// [`Graph::from_arrays`].
+    pub fn from_document(document: &'v DataValue<'a>, spec: GraphSpec<'_>) -> Result<Self> {
+        let nodes = document
+            .get("nodes")
+            .ok_or_else(|| Error::missing_field("nodes"))?;
+        let edges = document
+            .get("edges")
+            .ok_or_else(|| Error::missing_field("edges"))?;
+        Self::from_arrays(nodes, edges, spec)
+    }
+
+    /// Builds a graph from separate node and edge arrays
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if either value is not an array, a node lacks its key field,
+    /// two nodes share an identifier, or an edge references an unknown node.
+    pub fn from_arrays(
+        nodes: &'v DataValue<'a>,
+        edges: &DataValue<'a>,
+        spec: GraphSpec<'_>,
+    ) -> Result<Self> {
+        let nodes = as_array(nodes)?;
+        let edges = as_array(edges)?;
+
+        let mut index = HashMap::with_capacity(nodes.len());
+        for (i, node) in nodes.iter().enumerate() {
+            let id = node
+                .get(spec.node_key)
+                .ok_or_else(|| Error::missing_field(spec.node_key))?;
+            if index.insert(id.to_string(), i).is_some() {
+                return Err(Error::custom(format!("Duplicate node id: {}", id)));
+            }
+        }
+
+        let mut outgoing = vec![Vec::new(); nodes.len()];
+        let mut incoming = vec![Vec::new(); nodes.len()];
+        for edge in edges {
+            let endpoint = |key: &str| -> Result<usize> {
+                let id = edge.get(key).ok_or_else(|| Error::missing_field(key))?;
+                index
+                    .get(&id.to_string())
+                    .copied()
+                    .ok_or_else(|| Error::custom(format!("Edge references unknown node: {}", id)))
+            };
+            let source = endpoint(spec.source_key)?;
+            let target = endpoint(spec.target_key)?;
+            outgoing[source].push(target);
+            incoming[target].push(source);
+        }
+
+        Ok(Graph {
+            nodes,
+            index,
+            outgoing,
+            incoming,
+        })
+    }
+
+    /// Returns the number of nodes in the graph
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns the node object with the given identifier
+    pub fn node(&self, id: impl Into<NodeId<'a>>) -> Option<&'v DataValue<'a>> {
+        self.lookup(&id.into()).map(|i| &self.nodes[i])
+    }
+
+    /// Returns every node reachable from `start` by following outgoing edges
+    ///
+    /// The start node itself is only included if it lies on a cycle. Nodes are
+    /// returned in breadth-first order.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `start` is not a node of the graph.
+    pub fn reachable(
+        &self,
+        arena: &'a Bump,
+        start: impl Into<NodeId<'a>>,
+    ) -> Result<DataValue<'a>> {
+        let start = self.require(&start.into())?;
+        let found = self.traverse(start, usize::MAX, Direction::Outgoing);
+        Ok(self.collect(arena, found))
+    }
+
+    /// Returns the nodes within `depth` hops of `start`
+    ///
+    /// A depth of 1 gives the direct neighbors. The start node is excluded.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `start` is not a node of the graph.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use datavalue_rs::{graph::{Direction, Graph, GraphSpec}, Bump, from_str};
+    ///
+    /// let arena = Bump::new();
+    /// let nodes = from_str(&arena, r#"[{"id": 1}, {"id": 2}, {"id": 3}]"#).unwrap();
+    /// let edges = from_str(&arena, r#"[{"source": 1, "target": 2}, {"source": 2, "target": 3}]"#).unwrap();
+    /// let graph = Graph::from_arrays(&nodes, &edges, GraphSpec::default()).unwrap();
+    ///
+    /// let around = graph.neighborhood(&arena, 2, 1, Direction::Both).unwrap();
+    /// assert_eq!(around.as_array().unwrap().len(), 2);
+    /// ```
+    pub fn neighborhood(
+        &self,
+        arena: &'a Bump,
+        start: impl Into<NodeId<'a>>,
+        depth: usize,
+        direction: Direction,
+    ) -> Result<DataValue<'a>> {
+        let start = self.require(&start.into())?;
+        let mut found = self.traverse(start, depth, direction);
+        found.retain(|&i| i != start);
+        Ok(self.collect(arena, found))
+    }
+
+    /// Returns true if `to` can be reached from `from` by following outgoing edges
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if either identifier is not a node of the graph.
+    pub fn path_exists(
+        &self,
+        from: impl Into<NodeId<'a>>,
+        to: impl Into<NodeId<'a>>,
+    ) -> Result<bool> {
+        let from = self.require(&from.into())?;
+        let to = self.require(&to.into())?;
+        Ok(from == to
+            || self
+                .traverse(from, usize::MAX, Direction::Outgoing)
+                .contains(&to))
+    }
+
+    fn lookup(&self, id: &NodeId<'_>) -> Option<usize> {
+        self.index.get(&id.key()).copied()
+    }
+
+    fn require(&self, id: &NodeId<'_>) -> Result<usize> {
+        self.lookup(id)
+            .ok_or_else(|| Error::custom(format!("Unknown node: {}", id.key())))
+    }
+
+    /// Breadth-first search returning visited nodes (excluding the start unless revisited)
+    fn traverse(&self, start: usize, depth: usize, direction: Direction) -> Vec<usize> {
+        let mut seen = vec![false; self.nodes.len()];
+        let mut order = Vec::new();
+        let mut queue = VecDeque::from([(start, 0usize)]);
+        while let Some((node, hops)) = queue.pop_front() {
+            if hops == depth {
+                continue;
+            }
+            let forward = matches!(direction, Direction::Outgoing | Direction::Both)
+                .then(|| self.outgoing[node].iter())
+                .into_iter()
+                .flatten();
+            let backward = matches!(direction, Direction::Incoming | Direction::Both)
+                .then(|| self.incoming[node].iter())
+                .into_iter()
+                .flatten();
+            for &next in forward.chain(backward) {
+                if !seen[next] {
+                    seen[next] = true;
+                    order.push(next);
+                    queue.push_back((next, hops + 1));
+                }
+            }
+        }
+        order
+    }
+
+    fn collect(&self, arena: &'a Bump, indices: Vec<usize>) -> DataValue<'a> {
+        let values: Vec<DataValue<'a>> =
+            indices.into_iter().map(|i| self.nodes[i].clone()).collect();
+        DataValue::Array(arena.alloc_slice_clone(&values))
+    }
+}
+
+/// A node identifier used to look up nodes in a [`Graph`]
+///
+/// Strings, integers and DataValues convert into it, so lookups can be written as
+/// `graph.node("app")` or `graph.node(3)`.
+#[derive(Debug, Clone)]
+pub struct NodeId<'a>(DataValue<'a>);
+
+impl NodeId<'_> {
+    fn key(&self) -> String {
+        self.0.to_string()
+    }
+}
+
+impl<'a> From<&'a str> for NodeId<'a> {
+    fn from(id: &'a str) -> Self {
+        NodeId(DataValue::String(id))
+    }
+}
+
+impl From<i64> for NodeId<'_> {
+    fn from(id: i64) -> Self {
+        NodeId(DataValue::from(id))
+    }
+}
+
+impl From<i32> for NodeId<'_> {
+    fn from(id: i32) -> Self {
+        NodeId(DataValue::from(id))
+    }
+}
+
+impl<'a> From<DataValue<'a>> for NodeId<'a> {
+    fn from(id: DataValue<'a>) -> Self {
+        NodeId(id)
+    }
+}
+
+impl<'a> From<&DataValue<'a>> for NodeId<'a> {
+    fn from(id: &DataValue<'a>) -> Self {
+        NodeId(id.clone())
+    }
+}
+
+fn as_array<'v, 'a>(value: &'v DataValue<'a>) -> Result<&'v [DataValue<'a>]> {
+    match value {
+        DataValue::Array(items) => Ok(items),
+        other => Err(Error::expected_type(
+            "array",
+            format!("{:?}", other.get_type()).to_lowercase(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_str;
+
+    fn ids(value: &DataValue) -> Vec<String> {
+        value
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|n| n["name"].as_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_traversals() {
+        let arena = Bump::new();
+        let doc = from_str(
+            &arena,
+            r#"{
+                "nodes": [{"name": "a"}, {"name": "b"}, {"name": "c"}, {"name": "d"}, {"name": "e"}],
+                "edges": [
+                    {"from": "a", "to": "b"},
+                    {"from": "b", "to": "c"},
+                    {"from": "c", "to": "a"},
+                    {"from": "d", "to": "c"}
+                ]
+            }"#,
+        )
+        .unwrap();
+        let spec = GraphSpec {
+            node_key: "name",
+            source_key: "from",
+            target_key: "to",
+        };
+        let graph = Graph::from_document(&doc, spec).unwrap();
+
+        assert_eq!(graph.node_count(), 5);
+        assert_eq!(ids(&graph.reachable(&arena, "a").unwrap()), ["b", "c", "a"]);
+        assert_eq!(
+            ids(&graph.reachable(&arena, "e").unwrap()),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            ids(&graph
+                .neighborhood(&arena, "c", 1, Direction::Incoming)
+                .unwrap()),
+            ["b", "d"]
+        );
+        assert!(graph.path_exists("d", "b").unwrap());
+        assert!(!graph.path_exists("a", "d").unwrap());
+        assert!(graph.reachable(&arena, "zzz").is_err());
+    }
+
+    #[test]
+    fn test_invalid_graphs() {
+        let arena = Bump::new();
+        let spec = GraphSpec::default();
+        let nodes = from_str(&arena, r#"[{"id": 1}, {"id": 1}]"#).unwrap();
+        let edges = from_str(&arena, "[]").unwrap();
+        assert!(Graph::from_arrays(&nodes, &edges, spec).is_err());
+
+        let nodes = from_str(&arena, r#"[{"id": 1}]"#).unwrap();
+        let edges = from_str(&arena, r#"[{"source": 1, "target": 2}]"#).unwrap();
+        assert!(Graph::from_arrays(&nodes, &edges, spec).is_err());
+
+        let doc = from_str(&arena, r#"{"nodes": []}"#).unwrap();
+        assert!(Graph::from_document(&doc, spec).is_err());
+    }
+}
@@ -266,6 +266,360 @@ pub fn duration(value: i64) -> DataValue<'static> {
     DataValue::Duration(dur)
 }
 
+/// Creates a duration DataValue from an ISO 8601 duration string
+///
+/// See [`parse_duration`] for the accepted syntax.
+///
+/// # Arguments
+///
+/// * `value` - The duration string to parse, e.g. `"PT1H30M"`
+///
+/// # Returns
+///
+/// A Result containing a DataValue representing the duration, or an Error if parsing fails.
+///
+/// # Example
+///
+/// ```
+/// # use datavalue_rs::helpers;
+/// # use chrono::Duration;
+/// let value = helpers::duration_iso("P1DT2H").unwrap();
+/// assert_eq!(value.as_duration(), Some(Duration::hours(26)));
+/// ```
+#[inline]
+pub fn duration_iso<'a>(value: &str) -> Result<DataValue<'a>> {
+    parse_duration(value).map(DataValue::Duration)
+}
+
+/// Creates a duration DataValue from an ISO 8601 or human-friendly duration string
+///
+/// Strings starting with `P`, after an optional sign, are parsed as ISO 8601 with
+/// [`parse_duration`]. Anything else is read as a sequence of numbers with units,
+/// such as `1h30m`, `90s`, `1.5d` or `2m 30s`; the units are `w`, `d`, `h`, `m`,
+/// `s`, `ms`, `us` (or `µs`) and `ns`, from largest to smallest, each used at most
+/// once. Serializing the value writes the ISO 8601 form.
+///
+/// # Errors
+///
+/// Returns an error if the string is not a duration in either form or the value
+/// does not fit in a Duration.
+///
+/// # Example
+///
+/// ```
+/// # use datavalue_rs::helpers;
+/// # use chrono::Duration;
+/// let iso = helpers::duration_from_str("PT90S").unwrap();
+/// let human = helpers::duration_from_str("1h30m").unwrap();
+/// assert_eq!(iso.as_duration(), Some(Duration::seconds(90)));
+/// assert_eq!(human.as_duration(), Some(Duration::minutes(90)));
+/// assert_eq!(human.to_string(), r#""PT1H30M""#);
+/// assert!(helpers::duration_from_str("1 fortnight").is_err());
+/// ```
+pub fn duration_from_str<'a>(value: &str) -> Result<DataValue<'a>> {
+    if split_sign(value).1.starts_with('P') {
+        duration_iso(value)
+    } else {
+        parse_human_duration(value).map(DataValue::Duration)
+    }
+}
+
+/// Formats a duration as an ISO 8601 duration string
+///
+/// The output uses days, hours, minutes and (fractional) seconds, omitting zero
+/// components: `P1DT2H`, `PT1M30.5S`, `PT0S`. Negative durations are prefixed with
+/// `-`. The result is accepted by [`parse_duration`], which returns the same duration.
+///
+/// # Example
+///
+/// ```
+/// # use datavalue_rs::helpers;
+/// # use chrono::Duration;
+/// assert_eq!(helpers::format_duration(&Duration::seconds(90)), "PT1M30S");
+/// assert_eq!(helpers::format_duration(&Duration::milliseconds(-1500)), "-PT1.5S");
+/// assert_eq!(helpers::format_duration(&Duration::zero()), "PT0S");
+/// ```
+pub fn format_duration(value: &Duration) -> String {
+    let mut output = String::new();
+    if *value < Duration::zero() {
+        output.push('-');
+    }
+    output.push('P');
+
+    let magnitude = value.abs();
+    let total_secs = magnitude.num_seconds();
+    let nanos = magnitude.subsec_nanos();
+    let (days, rem) = (total_secs / 86_400, total_secs % 86_400);
+    let (hours, minutes, seconds) = (rem / 3_600, (rem % 3_600) / 60, rem % 60);
+
+    if days > 0 {
+        output.push_str(&format!("{}D", days));
+    }
+    if hours > 0 || minutes > 0 || seconds > 0 || nanos > 0 || days == 0 {
+        output.push('T');
+        if hours > 0 {
+            output.push_str(&format!("{}H", hours));
+        }
+        if minutes > 0 {
+            output.push_str(&format!("{}M", minutes));
+        }
+        if seconds > 0 || nanos > 0 || (hours == 0 && minutes == 0) {
+            output.push_str(&seconds.to_string());
+            if nanos > 0 {
+                let fraction = format!("{:09}", nanos);
+                output.push('.');
+                output.push_str(fraction.trim_end_matches('0'));
+            }
+            output.push('S');
+        }
+    }
+    output
+}
+
+/// Parses an ISO 8601 duration string
+///
+/// Accepts an optional sign followed by `P`, weeks (`W`) and days (`D`), then an
+/// optional `T` section with hours (`H`), minutes (`M`) and seconds (`S`). Seconds may
+/// carry a fraction of up to nine digits, separated by `.` or `,`. Components must
+/// appear in that order and at most once.
+///
+/// Years and months are rejected because they do not have a fixed length.
+///
+/// # Errors
+///
+/// Returns an error if the string is not a valid duration or the value does not
+/// fit in a Duration.
+///
+/// # Example
+///
+/// ```
+/// # use datavalue_rs::helpers;
+/// # use chrono::Duration;
+/// assert_eq!(helpers::parse_duration("PT1H30M").unwrap(), Duration::minutes(90));
+/// assert_eq!(helpers::parse_duration("P2W").unwrap(), Duration::days(14));
+/// assert_eq!(helpers::parse_duration("-PT0.25S").unwrap(), Duration::milliseconds(-250));
+/// assert!(helpers::parse_duration("P1Y").is_err());
+/// ```
+pub fn parse_duration(value: &str) -> Result<Duration> {
+    let invalid = |reason: &str| Error::syntax(format!("Invalid duration {:?}: {}", value, reason));
+
+    let (negative, rest) = split_sign(value);
+    let rest = rest
+        .strip_prefix('P')
+        .ok_or_else(|| invalid("must start with 'P'"))?;
+
+    // Units in the order they must appear, with their length in nanoseconds
+    const UNITS: &[(u8, bool, i128)] = &[
+        (b'W', false, 604_800_000_000_000),
+        (b'D', false, 86_400_000_000_000),
+        (b'H', true, 3_600_000_000_000),
+        (b'M', true, 60_000_000_000),
+        (b'S', true, 1_000_000_000),
+    ];
+
+    let bytes = rest.as_bytes();
+    let mut pos = 0;
+    let mut next_unit = 0;
+    let mut in_time = false;
+    let mut components = 0;
+    let mut total: i128 = 0;
+
+    while pos < bytes.len() {
+        if bytes[pos] == b'T' {
+            if in_time {
+                return Err(invalid("repeated 'T'"));
+            }
+            in_time = true;
+            pos += 1;
+            if pos == bytes.len() {
+                return Err(invalid("'T' must be followed by a time component"));
+            }
+            continue;
+        }
+
+        let start = pos;
+        while pos < bytes.len() && bytes[pos].is_ascii_digit() {
+            pos += 1;
+        }
+        let whole = &rest[start..pos];
+        let mut fraction = "";
+        if pos < bytes.len() && (bytes[pos] == b'.' || bytes[pos] == b',') {
+            pos += 1;
+            let frac_start = pos;
+            while pos < bytes.len() && bytes[pos].is_ascii_digit() {
+                pos += 1;
+            }
+            fraction = &rest[frac_start..pos];
+            if fraction.is_empty() || fraction.len() > 9 {
+                return Err(invalid("fraction must have one to nine digits"));
+            }
+        }
+        if whole.is_empty() {
+            return Err(invalid("expected a number"));
+        }
+        let designator = *bytes
+            .get(pos)
+            .ok_or_else(|| invalid("missing unit designator"))?;
+        pos += 1;
+
+        if !in_time && (designator == b'Y' || (designator == b'M')) {
+            return Err(invalid("years and months have no fixed length"));
+        }
+        let offset = UNITS[next_unit..]
+            .iter()
+            .position(|(unit, time, _)| *unit == designator && *time == in_time)
+            .ok_or_else(|| invalid("unexpected or out-of-order component"))?;
+        next_unit += offset + 1;
+        let nanos_per_unit = UNITS[next_unit - 1].2;
+        if !fraction.is_empty() && designator != b'S' {
+            return Err(invalid("only seconds may have a fraction"));
+        }
+
+        total = add_component(total, whole, fraction, nanos_per_unit)
+            .ok_or_else(|| invalid("value out of range"))?;
+        components += 1;
+    }
+
+    if components == 0 {
+        return Err(invalid("no components"));
+    }
+    duration_from_nanos(if negative { -total } else { total })
+        .ok_or_else(|| invalid("value out of range"))
+}
+
+/// Parses a human-friendly duration such as `1h30m`, `90s` or `1.5d`
+///
+/// The input is an optional sign followed by one or more numbers, each with a
+/// unit: `w`, `d`, `h`, `m`, `s`, `ms`, `us` (or `µs`) and `ns`. Numbers may have
+/// a fraction of up to nine digits. Units must appear in that order and at most
+/// once, and may be separated by whitespace. ISO 8601 strings are left to
+/// [`parse_duration`]; this covers only the unit-suffixed form.
+fn parse_human_duration(value: &str) -> Result<Duration> {
+    let invalid = |reason: &str| Error::syntax(format!("Invalid duration {:?}: {}", value, reason));
+
+    let (negative, rest) = split_sign(value);
+
+    // Units from largest to smallest, with their length in nanoseconds
+    const UNITS: &[(&str, i128)] = &[
+        ("w", 604_800_000_000_000),
+        ("d", 86_400_000_000_000),
+        ("h", 3_600_000_000_000),
+        ("m", 60_000_000_000),
+        ("s", 1_000_000_000),
+        ("ms", 1_000_000),
+        ("us", 1_000),
+        ("ns", 1),
+    ];
+
+    let mut rest = rest.trim_start();
+    let mut next_unit = 0;
+    let mut components = 0;
+    let mut total: i128 = 0;
+
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(rest.len());
+        let (number, tail) = rest.split_at(digits);
+        let (whole, fraction) = number.split_once('.').unwrap_or((number, ""));
+        if whole.is_empty() || (number.contains('.') && fraction.is_empty()) {
+            return Err(invalid("expected a number"));
+        }
+        if fraction.len() > 9 || fraction.contains('.') {
+            return Err(invalid("fraction must have one to nine digits"));
+        }
+
+        let unit_len = tail
+            .find(|c: char| !c.is_alphabetic())
+            .unwrap_or(tail.len());
+        let (unit, tail) = tail.split_at(unit_len);
+        let unit = if unit == "µs" { "us" } else { unit };
+        if unit.is_empty() {
+            return Err(invalid("missing unit"));
+        }
+        let offset = UNITS[next_unit..]
+            .iter()
+            .position(|(name, _)| *name == unit)
+            .ok_or_else(|| invalid("unknown, repeated or out-of-order unit"))?;
+        next_unit += offset + 1;
+        let nanos_per_unit = UNITS[next_unit - 1].1;
+
+        total = add_component(total, whole, fraction, nanos_per_unit)
+            .ok_or_else(|| invalid("value out of range"))?;
+        components += 1;
+        rest = tail.trim_start();
+    }
+
+    if components == 0 {
+        return Err(invalid("no components"));
+    }
+    duration_from_nanos(if negative { -total } else { total })
+        .ok_or_else(|| invalid("value out of range"))
+}
+
+/// Splits an optional leading `-` or `+` off a duration string, returning whether
+/// it is negative
+fn split_sign(value: &str) -> (bool, &str) {
+    match value.as_bytes().first() {
+        Some(b'-') => (true, &value[1..]),
+        Some(b'+') => (false, &value[1..]),
+        _ => (false, value),
+    }
+}
+
+/// Adds `whole.fraction` units of `nanos_per_unit` nanoseconds to `total`
+///
+/// The fraction has at most nine digits; anything below a nanosecond is dropped.
+/// Returns `None` on overflow.
+fn add_component(total: i128, whole: &str, fraction: &str, nanos_per_unit: i128) -> Option<i128> {
+    let amount: i128 = whole.parse().ok()?;
+    let fraction_nanos = if fraction.is_empty() {
+        0
+    } else {
+        let scaled: i128 = format!("{:0<9}", fraction).parse().ok()?;
+        scaled * nanos_per_unit / 1_000_000_000
+    };
+    amount
+        .checked_mul(nanos_per_unit)
+        .and_then(|n| n.checked_add(fraction_nanos))
+        .and_then(|n| total.checked_add(n))
+}
+
+/// Converts a signed number of nanoseconds to a Duration, if it fits
+fn duration_from_nanos(total: i128) -> Option<Duration> {
+    let secs = i64::try_from(total.div_euclid(1_000_000_000)).ok()?;
+    let nanos = total.rem_euclid(1_000_000_000) as i64;
+    Duration::try_seconds(secs).and_then(|d| d.checked_add(&Duration::nanoseconds(nanos)))
+}
+
+/// Recognizes strings holding an ISO 8601 duration during deserialization
+///
+/// A cheap prefix check runs first so ordinary strings are not fully parsed.
+pub(crate) fn detect_duration(value: &str) -> Option<Duration> {
+    let (_, body) = split_sign(value);
+    if body.len() < 3 || !body.starts_with('P') {
+        return None;
+    }
+    parse_duration(value).ok()
+}
+
+/// Recognizes strings holding an RFC 3339 timestamp during deserialization
+///
+/// Like [`detect_duration`], a cheap shape check runs before the full parse.
+pub(crate) fn detect_datetime(value: &str) -> Option<DateTime<Utc>> {
+    let bytes = value.as_bytes();
+    if bytes.len() < 20
+        || bytes[4] != b'-'
+        || bytes[7] != b'-'
+        || !bytes[..4].iter().all(u8::is_ascii_digit)
+    {
+        return None;
+    }
+    DateTime::parse_from_rfc3339(value)
+        .ok()
+        .map(|dt| dt.with_timezone(&Utc))
+}
+
 /// Creates a datetime DataValue from a string
 ///
 /// This function parses a datetime string in RFC3339 format and returns a DataValue
@@ -291,6 +645,14 @@ pub fn duration(value: i64) -> DataValue<'static> {
 /// ```
 #[inline]
 pub fn datetime<'a>(value: &str) -> Result<DataValue<'a>> {
+    parse_datetime(value).map(DataValue::DateTime)
+}
+
+/// Parses a datetime string using the formats accepted by [`datetime`]
+///
+/// Tries RFC3339 first, then a bare `YYYY-MM-DD` date (midnight UTC), then
+/// `YYYY-MM-DD HH:MM:SS` interpreted as UTC.
+pub(crate) fn parse_datetime(value: &str) -> Result<DateTime<Utc>> {
     DateTime::parse_from_rfc3339(value)
         .map(|dt| dt.with_timezone(&Utc))
         .or_else(|_| {
@@ -303,7 +665,36 @@ pub fn datetime<'a>(value: &str) -> Result<DataValue<'a>> {
             chrono::NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S").map(|dt| dt.and_utc())
         })
         .map_err(|e| Error::custom(e.to_string()))
-        .map(DataValue::DateTime)
+}
+
+/// Creates an interval DataValue
+///
+/// Intervals are represented as `{"start": ..., "end": ...}` objects. The bounds can
+/// be numbers or anything accepted by [`crate::operations::coerce_datetime`], and are
+/// treated as half-open (`start` inclusive, `end` exclusive) by the interval operations.
+///
+/// # Arguments
+///
+/// * `arena` - The arena allocator to store the object
+/// * `start` - The inclusive lower bound
+/// * `end` - The exclusive upper bound
+///
+/// # Returns
+///
+/// A DataValue representing the interval object.
+///
+/// # Example
+///
+/// ```
+/// # use datavalue_rs::{helpers, Bump};
+/// let arena = Bump::new();
+/// let slot = helpers::interval(&arena, helpers::int(9), helpers::int(17));
+/// assert_eq!(slot["start"].as_i64(), Some(9));
+/// assert_eq!(slot["end"].as_i64(), Some(17));
+/// ```
+#[inline]
+pub fn interval<'a>(arena: &'a Bump, start: DataValue<'a>, end: DataValue<'a>) -> DataValue<'a> {
+    DataValue::Object(arena.alloc_slice_clone(&[("start", start), ("end", end)]))
 }
 
 /// Returns the type of a DataValue
@@ -356,8 +747,8 @@ mod tests {
         }
 
         // Test float
-        match float(3.14) {
-            DataValue::Number(Number::Float(f)) => assert!((f - 3.14).abs() < f64::EPSILON),
+        match float(2.75) {
+            DataValue::Number(Number::Float(f)) => assert!((f - 2.75).abs() < f64::EPSILON),
             _ => panic!("Expected float"),
         }
     }
@@ -421,4 +812,88 @@ mod tests {
             _ => panic!("Expected object"),
         }
     }
+
+    #[test]
+    fn test_duration_iso_round_trip() {
+        let cases = [
+            Duration::zero(),
+            Duration::seconds(1),
+            Duration::minutes(90),
+            Duration::days(3) + Duration::seconds(5),
+            Duration::milliseconds(-1500),
+            Duration::nanoseconds(1),
+            Duration::days(400) + Duration::hours(23) + Duration::nanoseconds(120),
+        ];
+        for duration in cases {
+            let text = format_duration(&duration);
+            assert_eq!(parse_duration(&text).unwrap(), duration, "via {}", text);
+        }
+        assert_eq!(format_duration(&Duration::days(2)), "P2D");
+        assert_eq!(format_duration(&Duration::hours(1)), "PT1H");
+    }
+
+    #[test]
+    fn test_parse_duration_rejects_invalid() {
+        for bad in [
+            "",
+            "P",
+            "PT",
+            "1D",
+            "P1M",
+            "P1Y",
+            "PT1.5M",
+            "PT1H2H",
+            "PT1M1H",
+            "P1DT",
+            "PT.5S",
+            "PT1.S",
+            "P1D2",
+            "PT1.1234567890S",
+        ] {
+            assert!(parse_duration(bad).is_err(), "accepted {:?}", bad);
+        }
+        assert_eq!(
+            parse_duration("PT0,5S").unwrap(),
+            Duration::milliseconds(500)
+        );
+        assert_eq!(parse_duration("P1W1D").unwrap(), Duration::days(8));
+    }
+
+    #[test]
+    fn test_duration_from_str() {
+        let cases = [
+            ("PT90S", Duration::seconds(90)),
+            ("-P1D", Duration::days(-1)),
+            ("1h30m", Duration::minutes(90)),
+            ("90s", Duration::seconds(90)),
+            ("2m 30s", Duration::seconds(150)),
+            ("1.5d", Duration::hours(36)),
+            ("-250ms", Duration::milliseconds(-250)),
+            (
+                "1w2d3h4m5s6ms7us8ns",
+                Duration::nanoseconds(788_645_006_007_008),
+            ),
+            ("3µs", Duration::microseconds(3)),
+        ];
+        for (text, expected) in cases {
+            let value = duration_from_str(text).unwrap();
+            assert_eq!(value.as_duration(), Some(expected), "{}", text);
+        }
+        for bad in [
+            "", "-", "1", "h", "1x", "1m1h", "1s1s", "1.h", ".5h", "1.2.3s", "P1Y",
+        ] {
+            assert!(duration_from_str(bad).is_err(), "accepted {:?}", bad);
+        }
+
+        // ISO strings go through the one ISO parser, errors included
+        for text in [
+            "PT90S", "-P1DT2H", "+P2W", "PT0,5S", "P1Y", "PT", "P1DT", "PT1.S",
+        ] {
+            match (duration_from_str(text), duration_iso(text)) {
+                (Ok(a), Ok(b)) => assert_eq!(a, b, "{}", text),
+                (Err(a), Err(b)) => assert_eq!(a.to_string(), b.to_string(), "{}", text),
+                (a, b) => panic!("{}: {:?} vs {:?}", text, a, b),
+            }
+        }
+    }
 }
@@ -0,0 +1,134 @@
+//! Amazon Ion input and output, enabled by the `ion` feature
+//!
+//! [Ion](https://amazon-ion.github.io/ion-docs/) extends the JSON data model with
+//! exact decimals, timestamps, binary data and annotations, and has both a text
+//! form and a compact binary form. [`from_text`] and [`from_binary`] read a stream
+//! of Ion values, [`from_slice`] tells the two forms apart, and [`to_text`] and
+//! [`to_binary`] write values back.
+//!
+//! Ion values map onto DataValue as follows:
+//!
+//! | Ion | DataValue |
+//! |---|---|
+//! | `null`, typed nulls | [`DataValue::Null`] |
+//! | `bool` | [`DataValue::Bool`] |
+//! | `int` | [`Number::Integer`], or [`Number::BigInt`] outside the `i64` range |
+//! | `decimal` | [`Number::BigDecimal`], which keeps every digit |
+//! | `float` | [`Number::Float`] |
+//! | `timestamp` | [`DataValue::DateTime`], converted to UTC |
+//! | `string`, `symbol` | [`DataValue::String`] |
+//! | `blob`, `clob` | `{"$blob": base64}`, `{"$clob": base64}` |
+//! | `list`, `sexp` | [`DataValue::Array`] |
+//! | `struct` | [`DataValue::Object`] |
+//! | annotated value | `{"$annotations": [names], "$value": value}` |
+//!
+//! The writers turn those objects back into blobs, clobs and annotations, so
+//! values read from Ion are written back as the same Ion data, except that
+//! symbols become strings, s-expressions become lists, and timestamps are written
+//! in UTC with their full precision. Durations are written as ISO 8601 strings,
+//! as in JSON.
+//!
+//! Binary input may define local symbol tables. Shared symbol tables are not
+//! available, so imports other than the current local table are rejected.
+
+use bumpalo::Bump;
+
+use crate::base64;
+use crate::datavalue::DataValue;
+use crate::error::{Error, Result};
+
+mod binary;
+mod text;
+
+pub use binary::{from_binary, to_binary};
+pub use text::{from_text, to_text};
+
+/// Binary version marker that starts every binary Ion 1.0 stream
+const BINARY_VERSION_MARKER: [u8; 4] = [0xE0, 0x01, 0x00, 0xEA];
+
+const ANNOTATIONS_KEY: &str = "$annotations";
+const VALUE_KEY: &str = "$value";
+const BLOB_KEY: &str = "$blob";
+const CLOB_KEY: &str = "$clob";
+
+/// Parses Ion in either form, detected from the binary version marker
+///
+/// # Errors
+///
+/// Returns an error if the input is not valid Ion, or is text that is not UTF-8.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::{ion, Bump};
+///
+/// let arena = Bump::new();
+/// let values = ion::from_slice(&arena, b"{id: 1} {id: 2}").unwrap();
+/// let binary = ion::to_binary(&values).unwrap();
+/// assert_eq!(ion::from_slice(&arena, &binary).unwrap(), values);
+/// ```
+pub fn from_slice<'a>(arena: &'a Bump, bytes: &[u8]) -> Result<Vec<DataValue<'a>>> {
+    if bytes.starts_with(&BINARY_VERSION_MARKER) {
+        return from_binary(arena, bytes);
+    }
+    let text =
+        std::str::from_utf8(bytes).map_err(|e| Error::syntax(format!("Invalid UTF-8: {}", e)))?;
+    from_text(arena, text)
+}
+
+/// Wraps `value` in the object that carries its annotations, if it has any
+fn annotate<'a>(arena: &'a Bump, annotations: Vec<&'a str>, value: DataValue<'a>) -> DataValue<'a> {
+    if annotations.is_empty() {
+        return value;
+    }
+    let names = annotations.into_iter().map(DataValue::String);
+    let entries = [
+        (
+            ANNOTATIONS_KEY,
+            DataValue::Array(arena.alloc_slice_fill_iter(names)),
+        ),
+        (VALUE_KEY, value),
+    ];
+    DataValue::Object(arena.alloc_slice_fill_iter(entries))
+}
+
+/// Returns the object that stands for a blob or clob
+fn lob<'a>(arena: &'a Bump, bytes: &[u8], clob: bool) -> DataValue<'a> {
+    let key = if clob { CLOB_KEY } else { BLOB_KEY };
+    let text = DataValue::String(arena.alloc_str(&base64::encode(bytes)));
+    DataValue::Object(arena.alloc_slice_fill_iter([(key, text)]))
+}
+
+/// How a value is written as Ion
+enum Shape<'v, 'a> {
+    /// The value under `$value`, with the annotations before it
+    Annotated(Vec<&'v str>, &'v DataValue<'a>),
+    /// The bytes of a blob, or of a clob if the flag is set
+    Lob(Vec<u8>, bool),
+    /// Any other value, written as itself
+    Plain,
+}
+
+impl<'v, 'a> Shape<'v, 'a> {
+    /// Recognizes the objects that [`annotate`] and [`lob`] build
+    fn of(value: &'v DataValue<'a>) -> Result<Self> {
+        let shape = match value.as_object() {
+            Some([(ANNOTATIONS_KEY, DataValue::Array(names)), (VALUE_KEY, value)])
+            | Some([(VALUE_KEY, value), (ANNOTATIONS_KEY, DataValue::Array(names))]) => {
+                let names = names
+                    .iter()
+                    .map(|name| {
+                        name.as_str()
+                            .ok_or_else(|| Error::custom("Ion annotations must be strings"))
+                    })
+                    .collect::<Result<_>>()?;
+                Shape::Annotated(names, value)
+            }
+            Some([(key @ (BLOB_KEY | CLOB_KEY), DataValue::String(text))]) => {
+                Shape::Lob(base64::decode(text)?, *key == CLOB_KEY)
+            }
+            _ => Shape::Plain,
+        };
+        Ok(shape)
+    }
+}
@@ -0,0 +1,818 @@
+//! The Ion binary form
+
+use std::collections::HashMap;
+
+use bumpalo::Bump;
+use chrono::{DateTime, Datelike, NaiveDate, Timelike, Utc};
+
+use super::{annotate, lob, Shape, BINARY_VERSION_MARKER};
+use crate::bignum::{to_bytes, to_decimal, BigDecimal, BigInt};
+use crate::datavalue::{DataValue, Number};
+use crate::error::{Error, Result};
+use crate::helpers::format_duration;
+
+/// Text of the symbols every stream starts with, from symbol ID 1
+const SYSTEM_SYMBOLS: [&str; 9] = [
+    "$ion",
+    "$ion_1_0",
+    "$ion_symbol_table",
+    "name",
+    "version",
+    "imports",
+    "symbols",
+    "max_id",
+    "$ion_shared_symbol_table",
+];
+/// Symbol ID of `$ion_symbol_table`
+const SYMBOL_TABLE_SID: usize = 3;
+/// Symbol ID of `symbols`
+const SYMBOLS_SID: usize = 7;
+
+/// Parses a stream of binary Ion values
+///
+/// Local symbol tables are applied as they appear and are not returned.
+///
+/// # Errors
+///
+/// Returns a syntax error with the byte offset of the problem if the input is
+/// not binary Ion 1.0, or if it imports a shared symbol table.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::{ion, Bump};
+///
+/// let arena = Bump::new();
+/// // Version marker, then the int 7 and the string "hi"
+/// let values = ion::from_binary(&arena, b"\xE0\x01\x00\xEA\x21\x07\x82hi").unwrap();
+/// assert_eq!(values[0].as_i64(), Some(7));
+/// assert_eq!(values[1].as_str(), Some("hi"));
+/// ```
+pub fn from_binary<'a>(arena: &'a Bump, bytes: &[u8]) -> Result<Vec<DataValue<'a>>> {
+    if !bytes.starts_with(&BINARY_VERSION_MARKER) {
+        return Err(Error::syntax("Missing Ion binary version marker"));
+    }
+    let mut reader = BinaryReader {
+        arena,
+        bytes,
+        pos: 0,
+        symbols: Vec::new(),
+    };
+    let mut values = Vec::new();
+    while reader.pos < bytes.len() {
+        if bytes[reader.pos..].starts_with(&BINARY_VERSION_MARKER) {
+            reader.pos += BINARY_VERSION_MARKER.len();
+            reader.symbols.clear();
+            continue;
+        }
+        let Some(value) = reader.read_value()? else {
+            continue;
+        };
+        match Shape::of(&value)? {
+            Shape::Annotated(names, table) if names[0] == "$ion_symbol_table" => {
+                reader.load_symbol_table(table)?;
+            }
+            _ => values.push(value),
+        }
+    }
+    Ok(values)
+}
+
+/// Writes values as a binary Ion stream
+///
+/// Field names and annotations are declared in a local symbol table at the start
+/// of the stream.
+///
+/// # Errors
+///
+/// Returns an error if an annotation or lob object is malformed, or a raw value
+/// is not valid JSON.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::{from_str, ion, Bump};
+///
+/// let arena = Bump::new();
+/// let values = [from_str(&arena, r#"{"ids": [1, 2, 3]}"#).unwrap()];
+/// let binary = ion::to_binary(&values).unwrap();
+/// assert!(binary.starts_with(b"\xE0\x01\x00\xEA"));
+/// assert_eq!(ion::from_binary(&arena, &binary).unwrap(), values);
+/// ```
+pub fn to_binary(values: &[DataValue<'_>]) -> Result<Vec<u8>> {
+    let mut writer = BinaryWriter::default();
+    let mut body = Vec::new();
+    for value in values {
+        writer.write_value(value, &mut body)?;
+    }
+
+    let mut output = BINARY_VERSION_MARKER.to_vec();
+    if !writer.symbols.is_empty() {
+        let mut symbols = Vec::new();
+        for symbol in &writer.symbols {
+            symbols.extend(typed(0x8, symbol.as_bytes()));
+        }
+        let mut table = Vec::new();
+        write_var_uint(&mut table, SYMBOLS_SID as u64);
+        table.extend(typed(0xB, &symbols));
+        output.extend(annotated(&[SYMBOL_TABLE_SID], typed(0xD, &table)));
+    }
+    output.extend(body);
+    Ok(output)
+}
+
+/// An open list, s-expression or struct
+struct Frame<'a> {
+    /// Offset just past the container
+    end: usize,
+    /// Elements so far, for lists and s-expressions
+    items: Vec<DataValue<'a>>,
+    /// Fields so far, for structs
+    entries: Option<Vec<(&'a str, DataValue<'a>)>>,
+    /// Name of the field being read
+    field: &'a str,
+    annotations: Vec<&'a str>,
+}
+
+/// A type descriptor, with the annotations of its wrapper
+struct Header<'a> {
+    annotations: Vec<&'a str>,
+    type_code: u8,
+    /// Low nibble of the descriptor
+    low: u8,
+    /// Offset just past the value
+    end: usize,
+}
+
+struct BinaryReader<'a, 'b> {
+    arena: &'a Bump,
+    bytes: &'b [u8],
+    pos: usize,
+    /// Symbols after the system symbols; None for symbols without text
+    symbols: Vec<Option<&'a str>>,
+}
+
+impl<'a, 'b> BinaryReader<'a, 'b> {
+    fn error(&self, msg: &str) -> Error {
+        Error::syntax(format!("{} at byte {}", msg, self.pos))
+    }
+
+    fn byte(&mut self, end: usize) -> Result<u8> {
+        if self.pos >= end {
+            return Err(self.error("Truncated Ion value"));
+        }
+        self.pos += 1;
+        Ok(self.bytes[self.pos - 1])
+    }
+
+    fn take(&mut self, len: usize, end: usize) -> Result<&'b [u8]> {
+        if len > end - self.pos {
+            return Err(self.error("Truncated Ion value"));
+        }
+        self.pos += len;
+        Ok(&self.bytes[self.pos - len..self.pos])
+    }
+
+    fn read_var_uint(&mut self, end: usize) -> Result<u64> {
+        let mut value = 0u64;
+        loop {
+            let b = self.byte(end)?;
+            if value >> 57 != 0 {
+                return Err(self.error("Ion VarUInt too large"));
+            }
+            value = value << 7 | u64::from(b & 0x7F);
+            if b & 0x80 != 0 {
+                return Ok(value);
+            }
+        }
+    }
+
+    fn read_var_int(&mut self, end: usize) -> Result<i64> {
+        let first = self.byte(end)?;
+        let negative = first & 0x40 != 0;
+        let mut magnitude = u64::from(first & 0x3F);
+        let mut b = first;
+        while b & 0x80 == 0 {
+            b = self.byte(end)?;
+            if magnitude >> 56 != 0 {
+                return Err(self.error("Ion VarInt too large"));
+            }
+            magnitude = magnitude << 7 | u64::from(b & 0x7F);
+        }
+        let magnitude = i64::try_from(magnitude).map_err(|_| self.error("Ion VarInt too large"))?;
+        Ok(if negative { -magnitude } else { magnitude })
+    }
+
+    /// Returns the text of a symbol ID
+    fn symbol(&self, sid: u64) -> Result<&'a str> {
+        let sid = usize::try_from(sid).unwrap_or(usize::MAX);
+        let text = match sid.checked_sub(1) {
+            Some(i) if i < SYSTEM_SYMBOLS.len() => Some(SYSTEM_SYMBOLS[i]),
+            Some(i) => *self
+                .symbols
+                .get(i - SYSTEM_SYMBOLS.len())
+                .ok_or_else(|| self.error(&format!("Undefined Ion symbol ID {}", sid)))?,
+            None => None,
+        };
+        text.ok_or_else(|| self.error(&format!("Ion symbol ID {} has no text", sid)))
+    }
+
+    /// Reads a type descriptor and any annotation wrapper around it, leaving the
+    /// position at the start of the value's representation
+    fn read_header(&mut self, limit: usize) -> Result<Header<'a>> {
+        let mut annotations = Vec::new();
+        let mut wrapper_end = None;
+        loop {
+            let descriptor = self.byte(limit)?;
+            let (type_code, low) = (descriptor >> 4, descriptor & 0x0F);
+            let len = match (type_code, low) {
+                (_, 15) | (0x1, _) => 0,
+                (_, 14) | (0xD, 1) => self.read_var_uint(limit)? as usize,
+                _ => usize::from(low),
+            };
+            if len > limit - self.pos {
+                return Err(self.error("Ion value runs past its container"));
+            }
+            let end = self.pos + len;
+            if type_code == 0xE {
+                if wrapper_end.is_some() || low < 3 {
+                    return Err(self.error("Invalid Ion annotation wrapper"));
+                }
+                let annotations_len = self.read_var_uint(end)? as usize;
+                let annotations_end = self.pos + annotations_len;
+                if annotations_len == 0 || annotations_end > end {
+                    return Err(self.error("Invalid Ion annotation wrapper"));
+                }
+                while self.pos < annotations_end {
+                    let sid = self.read_var_uint(annotations_end)?;
+                    annotations.push(self.symbol(sid)?);
+                }
+                wrapper_end = Some(end);
+                continue;
+            }
+            if type_code == 0xF {
+                return Err(self.error("Reserved Ion type code"));
+            }
+            if wrapper_end
+                .is_some_and(|wrapper_end| end != wrapper_end || (type_code == 0 && low != 15))
+            {
+                return Err(self.error("Ion annotation wrapper length mismatch"));
+            }
+            return Ok(Header {
+                annotations,
+                type_code,
+                low,
+                end,
+            });
+        }
+    }
+
+    /// Reads one value, or None for padding; containers are kept on an explicit
+    /// stack
+    fn read_value(&mut self) -> Result<Option<DataValue<'a>>> {
+        let mut stack: Vec<Frame<'a>> = Vec::new();
+        loop {
+            let limit = stack.last().map_or(self.bytes.len(), |frame| frame.end);
+            let value = if !stack.is_empty() && self.pos == limit {
+                let frame = stack.pop().expect("stack is not empty");
+                let value = match frame.entries {
+                    Some(entries) => DataValue::Object(self.arena.alloc_slice_fill_iter(entries)),
+                    None => DataValue::Array(self.arena.alloc_slice_fill_iter(frame.items)),
+                };
+                annotate(self.arena, frame.annotations, value)
+            } else {
+                let field = match stack.last() {
+                    Some(Frame {
+                        entries: Some(_), ..
+                    }) => Some(self.read_var_uint(limit)?),
+                    _ => None,
+                };
+                let header = self.read_header(limit)?;
+                if header.type_code == 0 && header.low != 15 {
+                    // Padding, which may have a field name of symbol zero
+                    self.pos = header.end;
+                    if stack.is_empty() {
+                        return Ok(None);
+                    }
+                    continue;
+                }
+                if let (Some(sid), Some(frame)) = (field, stack.last_mut()) {
+                    frame.field = self.symbol(sid)?;
+                }
+                if (0xB..=0xD).contains(&header.type_code) && header.low != 15 {
+                    stack.push(Frame {
+                        end: header.end,
+                        items: Vec::new(),
+                        entries: (header.type_code == 0xD).then(Vec::new),
+                        field: "",
+                        annotations: header.annotations,
+                    });
+                    continue;
+                }
+                let value = self.read_scalar(&header)?;
+                annotate(self.arena, header.annotations, value)
+            };
+
+            match stack.last_mut() {
+                None => return Ok(Some(value)),
+                Some(frame) => match frame.entries.as_mut() {
+                    Some(entries) => entries.push((frame.field, value)),
+                    None => frame.items.push(value),
+                },
+            }
+        }
+    }
+
+    /// Reads the representation of a value that is not a container
+    fn read_scalar(&mut self, header: &Header<'a>) -> Result<DataValue<'a>> {
+        let start = self.pos;
+        let body = self.take(header.end - start, header.end)?;
+        let value = match (header.type_code, header.low) {
+            (_, 15) => DataValue::Null,
+            (0x1, low @ (0 | 1)) => DataValue::Bool(low == 1),
+            (0x2, _) => DataValue::Number(self.integer(false, body)?),
+            (0x3, _) if !body.is_empty() => DataValue::Number(self.integer(true, body)?),
+            (0x4, 0) => DataValue::Number(Number::Float(0.0)),
+            (0x4, 4) => {
+                let bits = <[u8; 4]>::try_from(body).expect("length is 4");
+                DataValue::Number(Number::Float(f64::from(f32::from_be_bytes(bits))))
+            }
+            (0x4, 8) => {
+                let bits = <[u8; 8]>::try_from(body).expect("length is 8");
+                DataValue::Number(Number::Float(f64::from_be_bytes(bits)))
+            }
+            (0x5, _) => {
+                self.pos = start;
+                let exponent = match header.low {
+                    0 => 0,
+                    _ => self.read_var_int(header.end)?,
+                };
+                let coefficient = self.int_text(&self.bytes[self.pos..header.end]);
+                let text = format!("{}e{}", coefficient, exponent);
+                let decimal = BigDecimal::parse(self.arena, &text)
+                    .ok_or_else(|| self.error("Ion decimal out of range"))?;
+                DataValue::Number(Number::BigDecimal(decimal))
+            }
+            (0x6, _) => {
+                self.pos = start;
+                DataValue::DateTime(self.timestamp(header.end)?)
+            }
+            (0x7, _) => {
+                let sid = body
+                    .iter()
+                    .fold(0u64, |n, &b| n.saturating_mul(256) | u64::from(b));
+                DataValue::String(self.symbol(sid)?)
+            }
+            (0x8, _) => {
+                let text = std::str::from_utf8(body)
+                    .map_err(|_| self.error("Invalid UTF-8 in Ion string"))?;
+                DataValue::String(self.arena.alloc_str(text))
+            }
+            (0x9 | 0xA, _) => lob(self.arena, body, header.type_code == 0x9),
+            _ => {
+                self.pos = start;
+                return Err(self.error("Invalid Ion type descriptor"));
+            }
+        };
+        self.pos = header.end;
+        Ok(value)
+    }
+
+    /// Converts the magnitude of an int to a Number
+    fn integer(&self, negative: bool, magnitude: &[u8]) -> Result<Number<'a>> {
+        let sign = if negative { "-" } else { "" };
+        if magnitude.len() <= 8 {
+            let n = magnitude.iter().fold(0i128, |n, &b| n << 8 | i128::from(b));
+            if let Ok(i) = i64::try_from(if negative { -n } else { n }) {
+                return Ok(Number::Integer(i));
+            }
+        }
+        let text = format!(
+            "{}{}",
+            sign,
+            to_decimal(magnitude.iter().map(|&b| u32::from(b)), 256)
+        );
+        BigInt::parse(self.arena, &text)
+            .map(Number::BigInt)
+            .ok_or_else(|| self.error("Invalid Ion int"))
+    }
+
+    /// Returns the decimal text of a signed-magnitude Int
+    fn int_text(&self, bytes: &[u8]) -> String {
+        match bytes.split_first() {
+            None => "0".to_string(),
+            Some((&first, rest)) => {
+                let sign = if first & 0x80 != 0 { "-" } else { "" };
+                let digits = std::iter::once(first & 0x7F).chain(rest.iter().copied());
+                format!("{}{}", sign, to_decimal(digits.map(u32::from), 256))
+            }
+        }
+    }
+
+    /// Reads a timestamp, whose fields are already in UTC
+    fn timestamp(&mut self, end: usize) -> Result<DateTime<Utc>> {
+        // The local offset only matters for display
+        self.read_var_int(end)?;
+        let year = self.read_var_uint(end)?;
+        let mut fields = [1u64, 1, 0, 0, 0];
+        for (i, field) in fields.iter_mut().enumerate() {
+            // Hour and minute are present together
+            if self.pos == end && i != 3 {
+                break;
+            }
+            *field = self.read_var_uint(end)?;
+        }
+        let mut nanos = 0i128;
+        if self.pos < end {
+            let exponent = self.read_var_int(end)?;
+            let coefficient = self.int_text(&self.bytes[self.pos..end]);
+            let coefficient: i128 = coefficient
+                .parse()
+                .map_err(|_| self.error("Ion timestamp fraction out of range"))?;
+            let shift = exponent + 9;
+            nanos = match shift {
+                0.. => coefficient.checked_mul(10i128.pow(shift.min(38) as u32)),
+                _ => Some(coefficient / 10i128.checked_pow((-shift) as u32).unwrap_or(i128::MAX)),
+            }
+            .filter(|nanos| (0..1_000_000_000).contains(nanos))
+            .ok_or_else(|| self.error("Ion timestamp fraction out of range"))?;
+        }
+        let [month, day, hour, minute, second] = fields;
+        let field = |n: u64| u32::try_from(n).unwrap_or(u32::MAX);
+        let datetime = i32::try_from(year)
+            .ok()
+            .and_then(|year| NaiveDate::from_ymd_opt(year, field(month), field(day)))
+            .and_then(|date| {
+                date.and_hms_nano_opt(field(hour), field(minute), field(second), nanos as u32)
+            })
+            .ok_or_else(|| self.error("Invalid Ion timestamp"))?;
+        self.pos = end;
+        Ok(datetime.and_utc())
+    }
+
+    /// Applies a local symbol table struct
+    fn load_symbol_table(&mut self, table: &DataValue<'a>) -> Result<()> {
+        let Some(entries) = table.as_object() else {
+            return Err(self.error("Ion symbol table must be a struct"));
+        };
+        let mut symbols = Vec::new();
+        let mut append = false;
+        for (key, value) in entries {
+            match (*key, value) {
+                ("imports", DataValue::String("$ion_symbol_table")) => append = true,
+                ("imports", DataValue::Array(_)) => {
+                    return Err(self.error("Ion shared symbol tables are not supported"))
+                }
+                ("symbols", DataValue::Array(items)) => {
+                    symbols = items
+                        .iter()
+                        .map(|item| match item {
+                            DataValue::String(s) => Some(*s),
+                            _ => None,
+                        })
+                        .collect()
+                }
+                _ => {}
+            }
+        }
+        if !append {
+            self.symbols.clear();
+        }
+        self.symbols.extend(symbols);
+        Ok(())
+    }
+}
+
+/// Returns a value's representation with its type descriptor and length
+fn typed(type_code: u8, body: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(body.len() + 3);
+    if body.len() < 14 {
+        output.push(type_code << 4 | body.len() as u8);
+    } else {
+        output.push(type_code << 4 | 14);
+        write_var_uint(&mut output, body.len() as u64);
+    }
+    output.extend_from_slice(body);
+    output
+}
+
+/// Wraps an encoded value in an annotation wrapper, if there are annotations
+fn annotated(sids: &[usize], value: Vec<u8>) -> Vec<u8> {
+    if sids.is_empty() {
+        return value;
+    }
+    let mut names = Vec::new();
+    for &sid in sids {
+        write_var_uint(&mut names, sid as u64);
+    }
+    let mut body = Vec::new();
+    write_var_uint(&mut body, names.len() as u64);
+    body.extend(names);
+    body.extend(value);
+    typed(0xE, &body)
+}
+
+fn write_var_uint(output: &mut Vec<u8>, value: u64) {
+    let groups = (64 - value.leading_zeros()).div_ceil(7).max(1);
+    for i in (0..groups).rev() {
+        let group = (value >> (7 * i)) as u8 & 0x7F;
+        output.push(if i == 0 { group | 0x80 } else { group });
+    }
+}
+
+fn write_var_int(output: &mut Vec<u8>, value: i64) {
+    let magnitude = value.unsigned_abs();
+    // The first byte holds six bits of magnitude after the sign bit
+    let groups = (65 - magnitude.leading_zeros()).div_ceil(7).max(1);
+    for i in (0..groups).rev() {
+        let mut group = (magnitude >> (7 * i)) as u8 & 0x7F;
+        if i == groups - 1 && value < 0 {
+            group |= 0x40;
+        }
+        output.push(if i == 0 { group | 0x80 } else { group });
+    }
+}
+
+/// Writes the signed-magnitude Int of a decimal number given as digits
+fn write_int(output: &mut Vec<u8>, negative: bool, digits: &str) {
+    let mut magnitude = to_bytes(digits);
+    if magnitude.first().is_some_and(|&b| b & 0x80 != 0) || (negative && magnitude.is_empty()) {
+        magnitude.insert(0, 0);
+    }
+    if negative {
+        magnitude[0] |= 0x80;
+    }
+    output.extend(magnitude);
+}
+
+/// An open list or struct: the items left to write, the representation so far and
+/// the annotations of the container
+struct Open<'v, 'a> {
+    items: std::vec::IntoIter<(Option<&'v str>, &'v DataValue<'a>)>,
+    type_code: u8,
+    body: Vec<u8>,
+    annotations: Vec<usize>,
+}
+
+#[derive(Default)]
+struct BinaryWriter {
+    /// Local symbols, from symbol ID 10
+    symbols: Vec<String>,
+    ids: HashMap<String, usize>,
+}
+
+impl BinaryWriter {
+    fn symbol(&mut self, text: &str) -> usize {
+        if let Some(&sid) = self.ids.get(text) {
+            return sid;
+        }
+        let sid = SYSTEM_SYMBOLS.len() + 1 + self.symbols.len();
+        self.symbols.push(text.to_string());
+        self.ids.insert(text.to_string(), sid);
+        sid
+    }
+
+    /// Writes one value, keeping open containers on an explicit stack
+    fn write_value(&mut self, value: &DataValue<'_>, output: &mut Vec<u8>) -> Result<()> {
+        let mut stack = Vec::new();
+        let mut done = self.start(value, &mut stack)?;
+        loop {
+            if let Some(bytes) = done.take() {
+                match stack.last_mut() {
+                    None => {
+                        output.extend(bytes);
+                        return Ok(());
+                    }
+                    Some(open) => open.body.extend(bytes),
+                }
+            }
+            let open = stack.last_mut().expect("a container is open");
+            match open.items.next() {
+                Some((key, item)) => {
+                    if let Some(key) = key {
+                        let sid = self.symbol(key);
+                        write_var_uint(&mut open.body, sid as u64);
+                    }
+                    done = self.start(item, &mut stack)?;
+                }
+                None => {
+                    let open = stack.pop().expect("a container is open");
+                    done = Some(annotated(
+                        &open.annotations,
+                        typed(open.type_code, &open.body),
+                    ));
+                }
+            }
+        }
+    }
+
+    /// Returns the representation of a value, or opens it on the stack if it is a
+    /// container
+    fn start<'v, 'a>(
+        &mut self,
+        mut value: &'v DataValue<'a>,
+        stack: &mut Vec<Open<'v, 'a>>,
+    ) -> Result<Option<Vec<u8>>> {
+        let mut annotations = Vec::new();
+        let bytes = loop {
+            match Shape::of(value)? {
+                Shape::Annotated(names, inner) => {
+                    annotations.extend(names.into_iter().map(|name| self.symbol(name)));
+                    value = inner;
+                }
+                Shape::Lob(bytes, clob) => break typed(if clob { 0x9 } else { 0xA }, &bytes),
+                Shape::Plain => match value {
+                    DataValue::Array(items) => {
+                        let items: Vec<_> = items.iter().map(|item| (None, item)).collect();
+                        stack.push(Open {
+                            items: items.into_iter(),
+                            type_code: 0xB,
+                            body: Vec::new(),
+                            annotations,
+                        });
+                        return Ok(None);
+                    }
+                    DataValue::Object(entries) => {
+                        let items: Vec<_> = entries
+                            .iter()
+                            .map(|(key, value)| (Some(*key), value))
+                            .collect();
+                        stack.push(Open {
+                            items: items.into_iter(),
+                            type_code: 0xD,
+                            body: Vec::new(),
+                            annotations,
+                        });
+                        return Ok(None);
+                    }
+                    DataValue::Raw(text) => {
+                        let arena = Bump::new();
+                        let mut bytes = Vec::new();
+                        self.write_value(&DataValue::Raw(text).parse_raw(&arena)?, &mut bytes)?;
+                        break bytes;
+                    }
+                    scalar => break encode_scalar(scalar),
+                },
+            }
+        };
+        Ok(Some(annotated(&annotations, bytes)))
+    }
+}
+
+fn encode_scalar(value: &DataValue<'_>) -> Vec<u8> {
+    match value {
+        DataValue::Null => vec![0x0F],
+        DataValue::Bool(b) => vec![0x10 | u8::from(*b)],
+        DataValue::Number(number) => encode_number(*number),
+        DataValue::String(s) => typed(0x8, s.as_bytes()),
+        DataValue::DateTime(dt) => {
+            let mut body = vec![0x80];
+            write_var_uint(&mut body, u64::try_from(dt.year()).unwrap_or_default());
+            for field in [dt.month(), dt.day(), dt.hour(), dt.minute(), dt.second()] {
+                write_var_uint(&mut body, u64::from(field));
+            }
+            let mut nanos = dt.nanosecond() % 1_000_000_000;
+            if nanos > 0 {
+                let mut exponent = -9;
+                while nanos % 10 == 0 {
+                    nanos /= 10;
+                    exponent += 1;
+                }
+                write_var_int(&mut body, exponent);
+                write_int(&mut body, false, &nanos.to_string());
+            }
+            typed(0x6, &body)
+        }
+        DataValue::Duration(dur) => typed(0x8, format_duration(dur).as_bytes()),
+        DataValue::Array(_) | DataValue::Object(_) | DataValue::Raw(_) => {
+            unreachable!("containers and raw values are written by write_value")
+        }
+    }
+}
+
+fn encode_number(number: Number<'_>) -> Vec<u8> {
+    match number {
+        Number::Integer(i) => {
+            let type_code = if i < 0 { 0x3 } else { 0x2 };
+            let magnitude = i.unsigned_abs().to_be_bytes();
+            let skip = magnitude.iter().take_while(|&&b| b == 0).count();
+            typed(type_code, &magnitude[skip..])
+        }
+        Number::Float(f) => typed(0x4, &f.to_be_bytes()),
+        Number::Raw(_) => encode_number(number.parsed()),
+        Number::BigInt(n) => {
+            let type_code = if n.is_negative() { 0x3 } else { 0x2 };
+            typed(type_code, &to_bytes(n.digits()))
+        }
+        Number::BigDecimal(n) => {
+            let mut body = Vec::new();
+            let unscaled = n.unscaled();
+            if unscaled.digits() != "0" || n.scale() != 0 {
+                write_var_int(&mut body, -n.scale());
+                write_int(&mut body, unscaled.is_negative(), unscaled.digits());
+            }
+            typed(0x5, &body)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_var_ints() {
+        let arena = Bump::new();
+        for value in [
+            0,
+            1,
+            63,
+            64,
+            127,
+            128,
+            8191,
+            8192,
+            -1,
+            -64,
+            -8192,
+            i64::MAX,
+            -i64::MAX,
+        ] {
+            let mut bytes = Vec::new();
+            write_var_int(&mut bytes, value);
+            write_var_uint(&mut bytes, value.unsigned_abs());
+            let mut reader = BinaryReader {
+                arena: &arena,
+                bytes: &bytes,
+                pos: 0,
+                symbols: Vec::new(),
+            };
+            assert_eq!(reader.read_var_int(bytes.len()).unwrap(), value);
+            assert_eq!(
+                reader.read_var_uint(bytes.len()).unwrap(),
+                value.unsigned_abs()
+            );
+            assert_eq!(reader.pos, bytes.len());
+        }
+        let mut bytes = Vec::new();
+        write_var_int(&mut bytes, -5);
+        assert_eq!(bytes, [0xC5]);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let arena = Bump::new();
+        let text = r#"
+            null true -7 9223372036854775808 -123456789012345678901234567890 2.5e0 -0.5e-3
+            1.50 -2d-3 0. 2007-02-23T12:14:33.079-08:00 2007T "text" {{aGVsbG8=}} {{"c"}}
+            [1, [], [[]], (a b)] {a: 1, b: {c: x::y::"z"}, '': {}} m::[1] m::{}
+        "#;
+        let values = super::super::from_text(&arena, text).unwrap();
+        let binary = to_binary(&values).unwrap();
+        assert_eq!(from_binary(&arena, &binary).unwrap(), values);
+        assert_eq!(to_binary(&[]).unwrap(), BINARY_VERSION_MARKER);
+
+        let raw = DataValue::Raw(r#"{"k": [1, 2]}"#);
+        let parsed = crate::from_str(&arena, r#"{"k": [1, 2]}"#).unwrap();
+        assert_eq!(to_binary(&[raw]).unwrap(), to_binary(&[parsed]).unwrap());
+    }
+
+    #[test]
+    fn test_read_binary() {
+        let arena = Bump::new();
+        let mut bytes = BINARY_VERSION_MARKER.to_vec();
+        bytes.extend([
+            // Local symbol table defining "a" and "b"
+            0xE9, 0x81, 0x83, 0xD6, 0x87, 0xB4, 0x81, b'a', 0x81, b'b',
+            // Sorted struct {a: 1, b: a}, with a padded field
+            0xD1, 0x89, 0x8A, 0x21, 0x01, 0x80, 0x01, 0x00, 0x8B, 0x71, 0x0A,
+            // Padding, then the symbol table appends "c"
+            0x02, 0x00, 0x00, 0xEA, 0x81, 0x83, 0xD7, 0x86, 0x71, 0x03, 0x87, 0xB2, 0x81, b'c',
+            0x71, 0x0C, // A new version marker resets the symbol table
+            0xE0, 0x01, 0x00, 0xEA, 0x71, 0x04,
+        ]);
+        let values = from_binary(&arena, &bytes).unwrap();
+        assert_eq!(values[0].to_string(), r#"{"a":1,"b":"a"}"#);
+        assert_eq!(values[1].as_str(), Some("c"));
+        assert_eq!(values[2].as_str(), Some("name"));
+
+        let import = b"\xE0\x01\x00\xEA\xE9\x81\x83\xD6\x86\xB4\x21\x01\x21\x02";
+        let error = from_binary(&arena, import).unwrap_err();
+        assert!(
+            error.to_string().contains("shared symbol tables"),
+            "{}",
+            error
+        );
+        for bad in [
+            &b"\x21\x01"[..],
+            b"\xE0\x01\x00\xEA\x22\x01",
+            b"\xE0\x01\x00\xEA\x71\x0A",
+            b"\xE0\x01\x00\xEA\x30",
+            b"\xE0\x01\x00\xEA\xF0",
+            b"\xE0\x01\x00\xEA\xB2\x21\x01\x21",
+            b"\xE0\x01\x00\xEA\x82\xFF\xFE",
+        ] {
+            assert!(from_binary(&arena, bad).is_err(), "{:?}", bad);
+        }
+    }
+}
@@ -0,0 +1,964 @@
+//! The Ion text form
+
+use bumpalo::Bump;
+use chrono::{DateTime, NaiveDate, NaiveTime, SecondsFormat, Utc};
+
+use super::{annotate, lob, Shape};
+use crate::base64;
+use crate::bignum::{to_decimal, BigDecimal, BigInt};
+use crate::datavalue::{DataValue, Number};
+use crate::error::{Error, Location, Result};
+use crate::helpers::format_duration;
+
+/// Parses a stream of Ion text values
+///
+/// Version markers and local symbol tables between values are skipped.
+///
+/// # Errors
+///
+/// Returns a syntax error with the line and column of the problem if the input
+/// is not valid Ion text.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::{ion, Bump};
+///
+/// let arena = Bump::new();
+/// let values = ion::from_text(&arena, "
+///     order::{
+///         id: 1024,
+///         total: 12.50,
+///         placed: 2024-03-01T09:30Z,
+///         tags: [rush, 'gift wrap'],
+///     }
+/// ").unwrap();
+///
+/// let order = &values[0]["$value"];
+/// assert_eq!(values[0]["$annotations"][0].as_str(), Some("order"));
+/// assert_eq!(order["total"].to_string(), "12.5");
+/// assert_eq!(order["placed"].as_datetime().unwrap().to_rfc3339(), "2024-03-01T09:30:00+00:00");
+/// assert_eq!(order["tags"][1].as_str(), Some("gift wrap"));
+/// ```
+pub fn from_text<'a>(arena: &'a Bump, text: &str) -> Result<Vec<DataValue<'a>>> {
+    let mut reader = TextReader {
+        arena,
+        input: text,
+        bytes: text.as_bytes(),
+        pos: 0,
+    };
+    let mut values = Vec::new();
+    loop {
+        reader.skip_whitespace()?;
+        if reader.pos == reader.bytes.len() {
+            return Ok(values);
+        }
+        if reader.symbol_at(reader.pos) == Some("$ion_1_0") {
+            reader.pos += "$ion_1_0".len();
+            continue;
+        }
+        let value = reader.read_value()?;
+        match Shape::of(&value)? {
+            Shape::Annotated(names, _) if names[0] == "$ion_symbol_table" => {}
+            _ => values.push(value),
+        }
+    }
+}
+
+/// Writes values as Ion text, one per line
+///
+/// # Errors
+///
+/// Returns an error if an annotation or lob object is malformed, or a raw value
+/// is not valid JSON.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::{from_str, ion, Bump};
+///
+/// let arena = Bump::new();
+/// let value = from_str(&arena, r#"{"name": "a b", "ratio": 0.5, "$value": null}"#).unwrap();
+/// assert_eq!(
+///     ion::to_text(&[value]).unwrap(),
+///     "{name:\"a b\",ratio:5e-1,'$value':null}\n"
+/// );
+/// ```
+pub fn to_text(values: &[DataValue<'_>]) -> Result<String> {
+    let mut output = String::new();
+    for value in values {
+        write_value(value, &mut output)?;
+        output.push('\n');
+    }
+    Ok(output)
+}
+
+/// An open list, s-expression or struct, with the annotations of the container
+enum Frame<'a> {
+    /// Elements so far, and the closing bracket
+    Sequence(Vec<DataValue<'a>>, u8, Vec<&'a str>),
+    /// Fields so far and the name of the field being read
+    Struct(Vec<(&'a str, DataValue<'a>)>, &'a str, Vec<&'a str>),
+}
+
+struct TextReader<'a, 's> {
+    arena: &'a Bump,
+    input: &'s str,
+    bytes: &'s [u8],
+    pos: usize,
+}
+
+impl<'a, 's> TextReader<'a, 's> {
+    fn error(&self, msg: &str) -> Error {
+        let byte_offset = self.pos.min(self.input.len());
+        let consumed = &self.input[..byte_offset];
+        let line = consumed.matches('\n').count() + 1;
+        let column = consumed
+            .rfind('\n')
+            .map_or(consumed, |nl| &consumed[nl + 1..])
+            .chars()
+            .count()
+            + 1;
+        let location = Location {
+            line,
+            column,
+            byte_offset,
+            path: String::new(),
+        };
+        Error::syntax_at(msg, location)
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+
+    /// Skips whitespace and comments
+    fn skip_whitespace(&mut self) -> Result<()> {
+        loop {
+            match self.peek() {
+                Some(b' ' | b'\t' | b'\n' | b'\r' | 0x0b | 0x0c) => self.pos += 1,
+                Some(b'/') if self.bytes.get(self.pos + 1) == Some(&b'/') => {
+                    let rest = &self.input[self.pos..];
+                    self.pos += rest.find('\n').unwrap_or(rest.len());
+                }
+                Some(b'/') if self.bytes.get(self.pos + 1) == Some(&b'*') => {
+                    match self.input[self.pos + 2..].find("*/") {
+                        Some(end) => self.pos += 2 + end + 2,
+                        None => return Err(self.error("unterminated comment")),
+                    }
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    /// Returns the identifier starting at `pos`, if any
+    fn symbol_at(&self, pos: usize) -> Option<&'s str> {
+        let rest = &self.bytes[pos..];
+        if !rest.first().is_some_and(|&b| is_identifier_byte(b, true)) {
+            return None;
+        }
+        let len = rest
+            .iter()
+            .position(|&b| !is_identifier_byte(b, false))
+            .unwrap_or(rest.len());
+        Some(&self.input[pos..pos + len])
+    }
+
+    /// Parses one value; open containers are kept on an explicit stack
+    fn read_value(&mut self) -> Result<DataValue<'a>> {
+        let mut stack: Vec<Frame<'a>> = Vec::new();
+        loop {
+            self.skip_whitespace()?;
+            let annotations = self.read_annotations()?;
+            let in_sexp = matches!(stack.last(), Some(Frame::Sequence(_, b')', _)));
+            let mut value = match self.peek() {
+                None => return Err(self.error("unexpected end of input")),
+                Some(b'{') if self.bytes.get(self.pos + 1) == Some(&b'{') => {
+                    annotate(self.arena, annotations, self.read_lob()?)
+                }
+                Some(open @ (b'[' | b'(' | b'{')) => {
+                    self.pos += 1;
+                    if open == b'{' {
+                        match self.read_field_name()? {
+                            Some(name) => {
+                                stack.push(Frame::Struct(Vec::new(), name, annotations));
+                                continue;
+                            }
+                            None => annotate(self.arena, annotations, DataValue::Object(&[])),
+                        }
+                    } else {
+                        let close = if open == b'[' { b']' } else { b')' };
+                        self.skip_whitespace()?;
+                        if self.peek() == Some(close) {
+                            self.pos += 1;
+                            annotate(self.arena, annotations, DataValue::Array(&[]))
+                        } else {
+                            stack.push(Frame::Sequence(Vec::new(), close, annotations));
+                            continue;
+                        }
+                    }
+                }
+                Some(_) => {
+                    let scalar = self.read_scalar(in_sexp)?;
+                    annotate(self.arena, annotations, scalar)
+                }
+            };
+
+            // Add the value to its container, then close every container that ends
+            // after it
+            loop {
+                match stack.last_mut() {
+                    None => return Ok(value),
+                    Some(Frame::Sequence(items, close, _)) => {
+                        let close = *close;
+                        items.push(value);
+                        self.skip_whitespace()?;
+                        let more = if close == b')' {
+                            self.peek() != Some(b')')
+                        } else {
+                            match self.peek() {
+                                Some(b',') => {
+                                    self.pos += 1;
+                                    self.skip_whitespace()?;
+                                    self.peek() != Some(b']')
+                                }
+                                Some(b']') => false,
+                                _ => return Err(self.error("expected ',' or ']'")),
+                            }
+                        };
+                        if more {
+                            break;
+                        }
+                        self.pos += 1;
+                    }
+                    Some(Frame::Struct(entries, name, _)) => {
+                        entries.push((*name, value));
+                        self.skip_whitespace()?;
+                        match self.peek() {
+                            Some(b',') => {
+                                self.pos += 1;
+                                if let Some(next) = self.read_field_name()? {
+                                    *name = next;
+                                    break;
+                                }
+                            }
+                            Some(b'}') => self.pos += 1,
+                            _ => return Err(self.error("expected ',' or '}'")),
+                        }
+                    }
+                }
+                value = match stack.pop() {
+                    Some(Frame::Sequence(items, _, annotations)) => {
+                        let items = self.arena.alloc_slice_fill_iter(items);
+                        annotate(self.arena, annotations, DataValue::Array(items))
+                    }
+                    Some(Frame::Struct(entries, _, annotations)) => {
+                        let entries = self.arena.alloc_slice_fill_iter(entries);
+                        annotate(self.arena, annotations, DataValue::Object(entries))
+                    }
+                    None => unreachable!("a container was just closed"),
+                };
+            }
+        }
+    }
+
+    /// Parses the next field name and the colon after it, or consumes the closing
+    /// brace and returns None
+    fn read_field_name(&mut self) -> Result<Option<&'a str>> {
+        self.skip_whitespace()?;
+        let name = match self.peek() {
+            Some(b'}') => {
+                self.pos += 1;
+                return Ok(None);
+            }
+            Some(b'"') => self.read_string()?,
+            Some(b'\'') if self.input[self.pos..].starts_with("'''") => self.read_long_string()?,
+            Some(b'\'') => self.read_quoted_symbol()?,
+            _ => match self.symbol_at(self.pos) {
+                Some(name) => {
+                    self.pos += name.len();
+                    name.to_string()
+                }
+                None => return Err(self.error("expected field name")),
+            },
+        };
+        self.skip_whitespace()?;
+        if self.peek() != Some(b':') {
+            return Err(self.error("expected ':'"));
+        }
+        self.pos += 1;
+        Ok(Some(self.arena.alloc_str(&name)))
+    }
+
+    /// Parses any `name::` annotations before a value
+    fn read_annotations(&mut self) -> Result<Vec<&'a str>> {
+        let mut annotations = Vec::new();
+        loop {
+            let start = self.pos;
+            let name = match self.peek() {
+                Some(b'\'') if !self.input[self.pos..].starts_with("'''") => {
+                    self.read_quoted_symbol()?
+                }
+                _ => match self.symbol_at(self.pos) {
+                    Some(name) => {
+                        self.pos += name.len();
+                        name.to_string()
+                    }
+                    None => return Ok(annotations),
+                },
+            };
+            self.skip_whitespace()?;
+            if !self.input[self.pos..].starts_with("::") {
+                self.pos = start;
+                return Ok(annotations);
+            }
+            self.pos += 2;
+            self.skip_whitespace()?;
+            annotations.push(&*self.arena.alloc_str(&name));
+        }
+    }
+
+    /// Parses a value that is not a container or lob
+    fn read_scalar(&mut self, in_sexp: bool) -> Result<DataValue<'a>> {
+        let start = self.pos;
+        let b = self.peek().unwrap_or_default();
+        let arena = self.arena;
+        let string = |s: &str| DataValue::String(arena.alloc_str(s));
+        let value = match b {
+            b'"' => string(&self.read_string()?),
+            b'\'' if self.input[self.pos..].starts_with("'''") => {
+                let mut text = self.read_long_string()?;
+                // Adjacent long strings are one string
+                loop {
+                    let end = self.pos;
+                    self.skip_whitespace()?;
+                    if !self.input[self.pos..].starts_with("'''") {
+                        self.pos = end;
+                        break;
+                    }
+                    text.push_str(&self.read_long_string()?);
+                }
+                DataValue::String(self.arena.alloc_str(&text))
+            }
+            b'\'' => string(&self.read_quoted_symbol()?),
+            b'+' | b'-' if self.input[self.pos + 1..].starts_with("inf") => {
+                self.pos += 4;
+                let f = if b == b'+' {
+                    f64::INFINITY
+                } else {
+                    f64::NEG_INFINITY
+                };
+                DataValue::Number(Number::Float(f))
+            }
+            b'0'..=b'9' | b'-'
+                if b != b'-' || self.bytes.get(self.pos + 1).is_some_and(u8::is_ascii_digit) =>
+            {
+                let len = self.bytes[start..]
+                    .iter()
+                    .position(|&b| {
+                        !(b.is_ascii_alphanumeric()
+                            || matches!(b, b'-' | b'+' | b'.' | b'_' | b':'))
+                    })
+                    .unwrap_or(self.bytes.len() - start);
+                self.pos += len;
+                self.number_or_timestamp(&self.input[start..start + len])
+                    .ok_or_else(|| {
+                        self.pos = start;
+                        self.error("invalid number or timestamp")
+                    })?
+            }
+            _ if in_sexp && is_operator_byte(b) => {
+                let len = self.bytes[start..]
+                    .iter()
+                    .position(|&b| !is_operator_byte(b))
+                    .unwrap_or(self.bytes.len() - start);
+                self.pos += len;
+                string(&self.input[start..start + len])
+            }
+            _ => {
+                let symbol = self
+                    .symbol_at(start)
+                    .ok_or_else(|| self.error("expected value"))?;
+                self.pos += symbol.len();
+                match symbol {
+                    "true" => DataValue::Bool(true),
+                    "false" => DataValue::Bool(false),
+                    "nan" => DataValue::Number(Number::Float(f64::NAN)),
+                    "null" => {
+                        // Typed nulls such as null.int are all null
+                        if self.peek() == Some(b'.') {
+                            self.pos += 1;
+                            let kind = self.symbol_at(self.pos).unwrap_or_default();
+                            if !NULL_TYPES.contains(&kind) {
+                                return Err(self.error("invalid null type"));
+                            }
+                            self.pos += kind.len();
+                        }
+                        DataValue::Null
+                    }
+                    _ => string(symbol),
+                }
+            }
+        };
+        Ok(value)
+    }
+
+    /// Classifies and parses the text of a number or timestamp
+    fn number_or_timestamp(&self, token: &str) -> Option<DataValue<'a>> {
+        let bytes = token.as_bytes();
+        if bytes.len() > 4
+            && bytes[..4].iter().all(u8::is_ascii_digit)
+            && matches!(bytes[4], b'-' | b'T')
+        {
+            return parse_timestamp(token).map(DataValue::DateTime);
+        }
+        let (negative, unsigned) = match token.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, token),
+        };
+        let sign = if negative { "-" } else { "" };
+        let radix_prefix = unsigned.get(..2).map(str::to_ascii_lowercase);
+        let number = match radix_prefix.as_deref() {
+            Some(prefix @ ("0x" | "0b")) => {
+                let radix = if prefix == "0x" { 16 } else { 2 };
+                let digits: Option<Vec<u32>> = digits_without_separators(&unsigned[2..])
+                    .map(|c| c.to_digit(radix))
+                    .collect();
+                let digits = digits.filter(|digits| !digits.is_empty())?;
+                self.integer(&format!("{}{}", sign, to_decimal(digits, radix)))?
+            }
+            _ => {
+                let plain: String = digits_without_separators(token).collect();
+                if plain.contains(['e', 'E']) {
+                    if !is_decimal_syntax(&plain.replace(['e', 'E'], "d")) {
+                        return None;
+                    }
+                    Number::Float(plain.parse().ok()?)
+                } else if plain.contains(['.', 'd', 'D']) {
+                    if !is_decimal_syntax(&plain) {
+                        return None;
+                    }
+                    let text = plain.replace(['d', 'D'], "e").replace(".e", "e");
+                    let text = text.strip_suffix('.').unwrap_or(&text);
+                    Number::BigDecimal(BigDecimal::parse(self.arena, text)?)
+                } else {
+                    self.integer(&plain)?
+                }
+            }
+        };
+        Some(DataValue::Number(number))
+    }
+
+    fn integer(&self, text: &str) -> Option<Number<'a>> {
+        match text.parse::<i64>() {
+            Ok(i) => Some(Number::Integer(i)),
+            Err(_) => BigInt::parse(self.arena, text).map(Number::BigInt),
+        }
+    }
+
+    /// Parses a double-quoted string
+    fn read_string(&mut self) -> Result<String> {
+        self.pos += 1;
+        let mut output = String::new();
+        loop {
+            match self.peek() {
+                None | Some(b'\n' | b'\r') => return Err(self.error("unterminated string")),
+                Some(b'"') => {
+                    self.pos += 1;
+                    return Ok(output);
+                }
+                Some(b'\\') => self.read_escape(&mut output)?,
+                Some(_) => self.push_char(&mut output),
+            }
+        }
+    }
+
+    /// Parses a `'''` string, which may span lines
+    fn read_long_string(&mut self) -> Result<String> {
+        self.pos += 3;
+        let mut output = String::new();
+        loop {
+            if self.input[self.pos..].starts_with("'''") {
+                self.pos += 3;
+                return Ok(output);
+            }
+            match self.peek() {
+                None => return Err(self.error("unterminated long string")),
+                Some(b'\\') => self.read_escape(&mut output)?,
+                Some(_) => self.push_char(&mut output),
+            }
+        }
+    }
+
+    /// Parses a single-quoted symbol
+    fn read_quoted_symbol(&mut self) -> Result<String> {
+        self.pos += 1;
+        let mut output = String::new();
+        loop {
+            match self.peek() {
+                None | Some(b'\n' | b'\r') => return Err(self.error("unterminated symbol")),
+                Some(b'\'') => {
+                    self.pos += 1;
+                    return Ok(output);
+                }
+                Some(b'\\') => self.read_escape(&mut output)?,
+                Some(_) => self.push_char(&mut output),
+            }
+        }
+    }
+
+    fn push_char(&mut self, output: &mut String) {
+        let c = self.input[self.pos..].chars().next().unwrap_or_default();
+        output.push(c);
+        self.pos += c.len_utf8();
+    }
+
+    /// Parses a backslash escape into `output`
+    fn read_escape(&mut self, output: &mut String) -> Result<()> {
+        let start = self.pos;
+        self.pos += 2;
+        let c = match self.bytes.get(start + 1) {
+            Some(b'a') => '\u{7}',
+            Some(b'b') => '\u{8}',
+            Some(b't') => '\t',
+            Some(b'n') => '\n',
+            Some(b'f') => '\u{c}',
+            Some(b'r') => '\r',
+            Some(b'v') => '\u{b}',
+            Some(b'0') => '\0',
+            Some(&c @ (b'"' | b'\'' | b'?' | b'\\' | b'/')) => c as char,
+            Some(b'\n') => return Ok(()),
+            Some(b'\r') => {
+                if self.peek() == Some(b'\n') {
+                    self.pos += 1;
+                }
+                return Ok(());
+            }
+            Some(&kind @ (b'x' | b'u' | b'U')) => {
+                let high = self.read_hex(match kind {
+                    b'x' => 2,
+                    b'u' => 4,
+                    _ => 8,
+                })?;
+                let code = if (0xD800..0xDC00).contains(&high)
+                    && self.input[self.pos..].starts_with("\\u")
+                {
+                    self.pos += 2;
+                    let low = self.read_hex(4)?;
+                    0x10000 + ((high - 0xD800) << 10) + low.wrapping_sub(0xDC00)
+                } else {
+                    high
+                };
+                char::from_u32(code).ok_or_else(|| {
+                    self.pos = start;
+                    self.error("invalid character escape")
+                })?
+            }
+            _ => {
+                self.pos = start;
+                return Err(self.error("invalid escape"));
+            }
+        };
+        output.push(c);
+        Ok(())
+    }
+
+    fn read_hex(&mut self, len: usize) -> Result<u32> {
+        let digits = self
+            .input
+            .get(self.pos..self.pos + len)
+            .ok_or_else(|| self.error("truncated escape"))?;
+        let code = u32::from_str_radix(digits, 16).map_err(|_| self.error("invalid hex escape"))?;
+        self.pos += len;
+        Ok(code)
+    }
+
+    /// Parses `{{ base64 }}` or `{{ "text" }}`
+    fn read_lob(&mut self) -> Result<DataValue<'a>> {
+        self.pos += 2;
+        self.skip_whitespace()?;
+        let (bytes, clob) = match self.peek() {
+            Some(b'"' | b'\'') => {
+                let mut text = String::new();
+                loop {
+                    self.skip_whitespace()?;
+                    match self.peek() {
+                        Some(b'"') => text.push_str(&self.read_string()?),
+                        Some(b'\'') if self.input[self.pos..].starts_with("'''") => {
+                            text.push_str(&self.read_long_string()?)
+                        }
+                        _ => break,
+                    }
+                }
+                let bytes: Option<Vec<u8>> = text.chars().map(|c| u8::try_from(c).ok()).collect();
+                (
+                    bytes.ok_or_else(|| self.error("clob characters must be below U+0100"))?,
+                    true,
+                )
+            }
+            _ => {
+                let len = self.input[self.pos..].find("}}").unwrap_or(0);
+                let text = &self.input[self.pos..self.pos + len];
+                let bytes =
+                    base64::decode(text).map_err(|_| self.error("invalid base64 in blob"))?;
+                self.pos += len;
+                (bytes, false)
+            }
+        };
+        self.skip_whitespace()?;
+        if !self.input[self.pos..].starts_with("}}") {
+            return Err(self.error("expected '}}'"));
+        }
+        self.pos += 2;
+        Ok(lob(self.arena, &bytes, clob))
+    }
+}
+
+const NULL_TYPES: [&str; 13] = [
+    "null",
+    "bool",
+    "int",
+    "float",
+    "decimal",
+    "timestamp",
+    "symbol",
+    "string",
+    "clob",
+    "blob",
+    "list",
+    "sexp",
+    "struct",
+];
+
+fn is_identifier_byte(b: u8, first: bool) -> bool {
+    b.is_ascii_alphabetic() || b == b'_' || b == b'$' || (!first && b.is_ascii_digit())
+}
+
+fn is_operator_byte(b: u8) -> bool {
+    b"!#%&*+-./;<=>?@^`|~".contains(&b)
+}
+
+/// Yields the characters of a number, dropping `_` digit separators
+fn digits_without_separators(text: &str) -> impl Iterator<Item = char> + '_ {
+    text.chars().filter(|&c| c != '_')
+}
+
+/// Checks `-?digits(.digits?)?(d[+-]?digits)?`, where `d` may be either case
+fn is_decimal_syntax(text: &str) -> bool {
+    let text = text.strip_prefix('-').unwrap_or(text);
+    let (mantissa, exponent) = match text.find(['d', 'D']) {
+        Some(i) => (&text[..i], Some(&text[i + 1..])),
+        None => (text, None),
+    };
+    let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+    !whole.is_empty()
+        && digits(whole)
+        && digits(fraction)
+        && exponent.is_none_or(|e| {
+            let e = e.strip_prefix(['+', '-']).unwrap_or(e);
+            !e.is_empty() && digits(e)
+        })
+}
+
+/// Parses an Ion timestamp of any precision, converting it to UTC
+fn parse_timestamp(text: &str) -> Option<DateTime<Utc>> {
+    let (date, time) = text.split_once('T').unwrap_or((text, ""));
+    let mut fields = date.split('-').map(str::parse::<u32>);
+    let year = fields.next()?.ok()? as i32;
+    let month = fields.next().transpose().ok()?;
+    let day = fields.next().transpose().ok()?;
+    if fields.next().is_some() || (time.is_empty() && !text.ends_with('T') && day.is_none()) {
+        return None;
+    }
+    let date = NaiveDate::from_ymd_opt(year, month.unwrap_or(1), day.unwrap_or(1))?;
+    if time.is_empty() {
+        return Some(date.and_time(NaiveTime::MIN).and_utc());
+    }
+    day?;
+    let (clock, offset_minutes) = match time.strip_suffix(['Z', 'z']) {
+        Some(clock) => (clock, 0),
+        None => {
+            let i = time.rfind(['+', '-'])?;
+            let (hours, minutes) = time[i + 1..].split_once(':')?;
+            let minutes = hours.parse::<i64>().ok()? * 60 + minutes.parse::<i64>().ok()?;
+            (
+                &time[..i],
+                if &time[i..=i] == "-" {
+                    -minutes
+                } else {
+                    minutes
+                },
+            )
+        }
+    };
+    let clock = match clock.len() {
+        5 => NaiveTime::parse_from_str(clock, "%H:%M").ok()?,
+        _ => NaiveTime::parse_from_str(clock, "%H:%M:%S%.f").ok()?,
+    };
+    let local = date.and_time(clock).and_utc();
+    Some(local - chrono::Duration::minutes(offset_minutes))
+}
+
+/// Writes one value as Ion text
+fn write_value(value: &DataValue<'_>, output: &mut String) -> Result<()> {
+    type Items<'v, 'a> = std::vec::IntoIter<(Option<&'v str>, &'v DataValue<'a>)>;
+
+    // Open containers: the items left to write, the closing bracket and whether
+    // no item has been written yet
+    let mut stack: Vec<(Items<'_, '_>, char, bool)> = Vec::new();
+    let mut next = Some(value);
+    loop {
+        if let Some(mut value) = next.take() {
+            loop {
+                match Shape::of(value)? {
+                    Shape::Annotated(names, inner) => {
+                        for name in names {
+                            write_symbol(name, output);
+                            output.push_str("::");
+                        }
+                        value = inner;
+                    }
+                    Shape::Lob(bytes, clob) => {
+                        write_lob(&bytes, clob, output);
+                        break;
+                    }
+                    Shape::Plain => {
+                        match value {
+                            DataValue::Array(items) => {
+                                let items: Vec<_> = items.iter().map(|item| (None, item)).collect();
+                                output.push('[');
+                                stack.push((items.into_iter(), ']', true));
+                            }
+                            DataValue::Object(entries) => {
+                                let items: Vec<_> = entries
+                                    .iter()
+                                    .map(|(key, value)| (Some(*key), value))
+                                    .collect();
+                                output.push('{');
+                                stack.push((items.into_iter(), '}', true));
+                            }
+                            DataValue::Raw(text) => {
+                                let arena = Bump::new();
+                                write_value(&DataValue::Raw(text).parse_raw(&arena)?, output)?;
+                            }
+                            scalar => write_scalar(scalar, output),
+                        }
+                        break;
+                    }
+                }
+            }
+        }
+
+        let Some((items, close, first)) = stack.last_mut() else {
+            return Ok(());
+        };
+        match items.next() {
+            Some((key, item)) => {
+                if !std::mem::take(first) {
+                    output.push(',');
+                }
+                if let Some(key) = key {
+                    write_symbol(key, output);
+                    output.push(':');
+                }
+                next = Some(item);
+            }
+            None => {
+                output.push(*close);
+                stack.pop();
+            }
+        }
+    }
+}
+
+fn write_scalar(value: &DataValue<'_>, output: &mut String) {
+    match value {
+        DataValue::Null => output.push_str("null"),
+        DataValue::Bool(b) => output.push_str(if *b { "true" } else { "false" }),
+        DataValue::Number(number) => write_number(*number, output),
+        DataValue::String(s) => write_quoted(s, '"', output),
+        DataValue::DateTime(dt) => {
+            output.push_str(&dt.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+        }
+        DataValue::Duration(dur) => write_quoted(&format_duration(dur), '"', output),
+        DataValue::Array(_) | DataValue::Object(_) | DataValue::Raw(_) => {
+            unreachable!("containers and raw values are written by write_value")
+        }
+    }
+}
+
+fn write_number(number: Number<'_>, output: &mut String) {
+    match number {
+        Number::Integer(i) => output.push_str(&i.to_string()),
+        Number::Float(f) if f.is_nan() => output.push_str("nan"),
+        Number::Float(f) if f.is_infinite() => {
+            output.push_str(if f > 0.0 { "+inf" } else { "-inf" })
+        }
+        Number::Float(f) => output.push_str(&format!("{:e}", f)),
+        Number::Raw(_) => write_number(number.parsed(), output),
+        Number::BigInt(n) => output.push_str(&n.to_string()),
+        Number::BigDecimal(n) => {
+            output.push_str(&format!("{}d{}", n.unscaled(), -(n.scale() as i128)))
+        }
+    }
+}
+
+/// Writes a field name or annotation, quoting it unless it is a plain identifier
+fn write_symbol(name: &str, output: &mut String) {
+    let plain = name
+        .bytes()
+        .enumerate()
+        .all(|(i, b)| is_identifier_byte(b, i == 0))
+        && !name.is_empty()
+        && !matches!(name, "null" | "true" | "false" | "nan")
+        && !name.starts_with('$');
+    if plain {
+        output.push_str(name);
+    } else {
+        write_quoted(name, '\'', output);
+    }
+}
+
+/// Writes a string between `quote`s, escaping the quote, backslashes and control
+/// characters
+fn write_quoted(s: &str, quote: char, output: &mut String) {
+    output.push(quote);
+    for c in s.chars() {
+        match c {
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            c if c == quote => {
+                output.push('\\');
+                output.push(c);
+            }
+            c if c.is_control() => output.push_str(&format!("\\u{:04x}", c as u32)),
+            c => output.push(c),
+        }
+    }
+    output.push(quote);
+}
+
+fn write_lob(bytes: &[u8], clob: bool, output: &mut String) {
+    output.push_str("{{");
+    if clob {
+        output.push('"');
+        for &b in bytes {
+            match b {
+                b'"' | b'\\' => {
+                    output.push('\\');
+                    output.push(b as char);
+                }
+                0x20..=0x7e => output.push(b as char),
+                _ => output.push_str(&format!("\\x{:02x}", b)),
+            }
+        }
+        output.push('"');
+    } else {
+        output.push_str(&base64::encode(bytes));
+    }
+    output.push_str("}}");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_text() {
+        let arena = Bump::new();
+        let text = r#"
+            $ion_1_0
+            $ion_symbol_table::{ symbols: ["x"] }
+            // scalars
+            null.int true 0x1F -0b101 1_000 123456789012345678901234567890
+            1.50 -2d-3 1. 2.5e0 -inf nan
+            2007T 2007-02-23 2007-02-23T12:14-08:00 2007-02-23T12:14:33.079Z
+            "a\tb\u00e9\U0001F600" '''long''' /* joined */ ''' string''' 'quoted sym' sym
+            {{aGVsbG8=}} {{"a\x00"}}
+            ( + 1 a::b::2 ) [1, 2, ] {'a': 1, "b": [], c:{},}
+            top::{x: note::"y"}
+        "#;
+        let values = from_text(&arena, text).unwrap();
+        let texts: Vec<String> = values
+            .iter()
+            .map(|value| match value {
+                DataValue::String(s) => format!("<{}>", s),
+                DataValue::DateTime(dt) => dt.to_rfc3339(),
+                DataValue::Number(Number::Float(f)) if !f.is_finite() => f.to_string(),
+                value => value.to_string(),
+            })
+            .collect();
+        assert_eq!(
+            texts,
+            [
+                "null",
+                "true",
+                "31",
+                "-5",
+                "1000",
+                "123456789012345678901234567890",
+                "1.5",
+                "-0.002",
+                "1",
+                "2.5",
+                "-inf",
+                "NaN",
+                "2007-01-01T00:00:00+00:00",
+                "2007-02-23T00:00:00+00:00",
+                "2007-02-23T20:14:00+00:00",
+                "2007-02-23T12:14:33.079+00:00",
+                "<a\tb\u{e9}\u{1f600}>",
+                "<long string>",
+                "<quoted sym>",
+                "<sym>",
+                r#"{"$blob":"aGVsbG8="}"#,
+                r#"{"$clob":"YQA="}"#,
+                r#"["+",1,{"$annotations":["a","b"],"$value":2}]"#,
+                "[1,2]",
+                r#"{"a":1,"b":[],"c":{}}"#,
+                r#"{"$annotations":["top"],"$value":{"x":{"$annotations":["note"],"$value":"y"}}}"#,
+            ]
+        );
+        assert!(matches!(
+            values[6],
+            DataValue::Number(Number::BigDecimal(_))
+        ));
+
+        for bad in [
+            "[1 2]",
+            "{a 1}",
+            "\"open",
+            "1.2.3",
+            "2007-13-01",
+            "null.nothing",
+            "{{\"€\"}}",
+            "0xZ",
+        ] {
+            assert!(from_text(&arena, bad).is_err(), "{}", bad);
+        }
+        let error = from_text(&arena, "[\n  1,\n  @]").unwrap_err();
+        assert_eq!(error.location().map(|l| (l.line, l.column)), Some((3, 3)));
+    }
+
+    #[test]
+    fn test_write_text() {
+        let arena = Bump::new();
+        let text = concat!(
+            "a::'b c'::{'$x':[1,2.5e0,-3d-1,+inf],\"s\":\"q\\\"\\n\",",
+            "t:2007-02-23T12:14:33.079Z,b:{{/wA=}},c:{{\"a\\x00\"}},n:null}"
+        );
+        let values = from_text(&arena, text).unwrap();
+        let written = to_text(&values).unwrap();
+        assert_eq!(written, format!("{}\n", text.replace("\"s\"", "s")));
+        assert_eq!(from_text(&arena, &written).unwrap(), values);
+
+        let raw = DataValue::Raw("[1, {\"k\": true}]");
+        assert_eq!(to_text(&[raw]).unwrap(), "[1,{k:true}]\n");
+        let bad = crate::from_str(&arena, r#"{"$blob": "!!"}"#).unwrap();
+        assert!(to_text(&[bad]).is_err());
+    }
+}
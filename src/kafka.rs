@@ -0,0 +1,227 @@
+//! Confluent Schema Registry wire format
+//!
+//! Kafka producers that use a schema registry prefix every message with a zero
+//! magic byte and the big-endian 32-bit id of the writer's schema, followed by the
+//! serialized payload. [`Envelope`] splits and joins that framing, and
+//! [`decode`] and [`encode`] convert whole messages to and from DataValue with a
+//! [`PayloadCodec`] for the payload, so a stream processor can read, transform
+//! and re-publish records without leaving DataValue.
+//!
+//! [`JsonSchemaCodec`] handles JSON Schema payloads, which are plain JSON text.
+//! Binary payloads such as Avro are decoded with the writer's schema, which a
+//! codec looks up by the schema id it is given.
+
+use bumpalo::Bump;
+
+use crate::datavalue::DataValue;
+use crate::de::from_str;
+use crate::error::{Error, Result};
+use crate::ser::write_json;
+
+/// First byte of every message in the wire format
+pub const MAGIC_BYTE: u8 = 0;
+
+/// Length of the magic byte and schema id that precede the payload
+const HEADER_LEN: usize = 5;
+
+/// A message split into its schema id and payload
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::kafka::Envelope;
+///
+/// let message = Envelope { schema_id: 42, payload: br#"{"id":1}"# }.to_vec();
+/// assert_eq!(message[..5], [0, 0, 0, 0, 42]);
+///
+/// let envelope = Envelope::parse(&message).unwrap();
+/// assert_eq!(envelope.schema_id, 42);
+/// assert_eq!(envelope.payload, br#"{"id":1}"#);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Envelope<'b> {
+    /// Id of the schema the payload was written with
+    pub schema_id: u32,
+    /// The serialized record
+    pub payload: &'b [u8],
+}
+
+impl<'b> Envelope<'b> {
+    /// Splits a message into its schema id and payload
+    ///
+    /// # Errors
+    ///
+    /// Returns a syntax error if the message is shorter than the header or does
+    /// not start with [`MAGIC_BYTE`].
+    pub fn parse(message: &'b [u8]) -> Result<Self> {
+        if message.len() < HEADER_LEN {
+            return Err(Error::syntax(format!(
+                "Message of {} bytes is too short for the schema registry header",
+                message.len()
+            )));
+        }
+        if message[0] != MAGIC_BYTE {
+            return Err(Error::syntax(format!(
+                "Unknown schema registry magic byte {}",
+                message[0]
+            )));
+        }
+        let schema_id = u32::from_be_bytes(message[1..HEADER_LEN].try_into().expect("length is 4"));
+        Ok(Envelope {
+            schema_id,
+            payload: &message[HEADER_LEN..],
+        })
+    }
+
+    /// Returns the message bytes: the header followed by the payload
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut message = Vec::with_capacity(HEADER_LEN + self.payload.len());
+        message.push(MAGIC_BYTE);
+        message.extend_from_slice(&self.schema_id.to_be_bytes());
+        message.extend_from_slice(self.payload);
+        message
+    }
+}
+
+/// Converts the payloads of one serialization format
+///
+/// Implementations that need the writer's schema, such as Avro, look it up by
+/// `schema_id`, typically in a cache in front of the registry's REST API.
+pub trait PayloadCodec {
+    /// Decodes a payload written with schema `schema_id` into the arena
+    fn decode<'a>(&self, arena: &'a Bump, schema_id: u32, payload: &[u8]) -> Result<DataValue<'a>>;
+
+    /// Encodes a value with schema `schema_id`
+    fn encode(&self, schema_id: u32, value: &DataValue<'_>) -> Result<Vec<u8>>;
+}
+
+/// Codec for JSON Schema payloads, which are UTF-8 JSON text
+///
+/// The schema id is not used: records are read and written as JSON whatever
+/// schema they claim. Validate them against the schema separately if needed.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonSchemaCodec;
+
+impl PayloadCodec for JsonSchemaCodec {
+    fn decode<'a>(
+        &self,
+        arena: &'a Bump,
+        _schema_id: u32,
+        payload: &[u8],
+    ) -> Result<DataValue<'a>> {
+        let text = std::str::from_utf8(payload)
+            .map_err(|e| Error::syntax(format!("Payload is not UTF-8: {}", e)))?;
+        from_str(arena, text)
+    }
+
+    fn encode(&self, _schema_id: u32, value: &DataValue<'_>) -> Result<Vec<u8>> {
+        let mut payload = Vec::new();
+        write_json(value, &mut payload)?;
+        Ok(payload)
+    }
+}
+
+/// Decodes a message in the wire format, returning its schema id and record
+///
+/// # Errors
+///
+/// Returns an error if the header is invalid or the codec fails to decode the
+/// payload.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::kafka::{self, JsonSchemaCodec};
+/// use datavalue_rs::Bump;
+///
+/// let arena = Bump::new();
+/// let message = b"\x00\x00\x00\x01\x07{\"user\": \"ada\", \"clicks\": 3}";
+/// let (schema_id, record) = kafka::decode(&arena, message, &JsonSchemaCodec).unwrap();
+/// assert_eq!(schema_id, 263);
+/// assert_eq!(record["clicks"].as_i64(), Some(3));
+///
+/// let republished = kafka::encode(schema_id, &record, &JsonSchemaCodec).unwrap();
+/// assert_eq!(&republished[5..], br#"{"user":"ada","clicks":3}"#);
+/// ```
+pub fn decode<'a>(
+    arena: &'a Bump,
+    message: &[u8],
+    codec: &impl PayloadCodec,
+) -> Result<(u32, DataValue<'a>)> {
+    let envelope = Envelope::parse(message)?;
+    let value = codec.decode(arena, envelope.schema_id, envelope.payload)?;
+    Ok((envelope.schema_id, value))
+}
+
+/// Encodes a record as a message in the wire format
+///
+/// # Errors
+///
+/// Returns an error if the codec fails to encode the value.
+pub fn encode(schema_id: u32, value: &DataValue<'_>, codec: &impl PayloadCodec) -> Result<Vec<u8>> {
+    let payload = codec.encode(schema_id, value)?;
+    Ok(Envelope {
+        schema_id,
+        payload: &payload,
+    }
+    .to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Prefixes payloads with the schema id, standing in for a schema-aware codec
+    struct Tagged;
+
+    impl PayloadCodec for Tagged {
+        fn decode<'a>(
+            &self,
+            arena: &'a Bump,
+            schema_id: u32,
+            payload: &[u8],
+        ) -> Result<DataValue<'a>> {
+            let text = std::str::from_utf8(payload).unwrap();
+            let expected = format!("{}:", schema_id);
+            let text = text
+                .strip_prefix(&expected)
+                .ok_or_else(|| Error::custom("payload was written with another schema"))?;
+            from_str(arena, text)
+        }
+
+        fn encode(&self, schema_id: u32, value: &DataValue<'_>) -> Result<Vec<u8>> {
+            let mut payload = format!("{}:", schema_id).into_bytes();
+            payload.extend(JsonSchemaCodec.encode(schema_id, value)?);
+            Ok(payload)
+        }
+    }
+
+    #[test]
+    fn test_wire_format() {
+        let arena = Bump::new();
+        let record = from_str(&arena, r#"{"id": 7, "tags": ["a\"b"], "at": null}"#).unwrap();
+
+        let message = encode(u32::MAX, &record, &JsonSchemaCodec).unwrap();
+        assert_eq!(message[..5], [0, 255, 255, 255, 255]);
+        assert_eq!(
+            decode(&arena, &message, &JsonSchemaCodec).unwrap(),
+            (u32::MAX, record.clone())
+        );
+
+        let message = encode(9, &record, &Tagged).unwrap();
+        assert_eq!(&message[5..7], b"9:");
+        assert_eq!(decode(&arena, &message, &Tagged).unwrap().1, record);
+        let mut relabeled = message.clone();
+        relabeled[4] = 8;
+        assert!(decode(&arena, &relabeled, &Tagged).is_err());
+
+        assert!(Envelope::parse(b"\x00\x00\x00\x01").is_err());
+        assert!(Envelope::parse(b"\x01\x00\x00\x00\x01{}").is_err());
+        assert_eq!(
+            Envelope::parse(b"\x00\x00\x00\x00\x01").unwrap().payload,
+            b""
+        );
+        assert!(decode(&arena, b"\x00\x00\x00\x00\x01\xFF", &JsonSchemaCodec).is_err());
+        assert!(decode(&arena, b"\x00\x00\x00\x00\x01{", &JsonSchemaCodec).is_err());
+    }
+}
@@ -58,19 +58,70 @@
  */
 
 mod access;
+#[cfg(feature = "rkyv")]
+pub mod archive;
+mod base64;
+#[cfg(feature = "bench-corpus")]
+pub mod bench_corpus;
+#[cfg(feature = "bignum")]
+pub mod bignum;
+mod binary;
+pub mod builder;
+#[cfg(feature = "cbor")]
+pub mod cbor;
+#[cfg(feature = "client")]
+pub mod client;
+pub mod collection;
+pub mod config;
 mod conversion;
 mod datavalue;
-mod de;
+pub mod de;
+mod document;
 mod error;
+pub mod format;
+#[cfg(feature = "geo")]
+pub mod geo;
+pub mod graph;
 pub mod helpers;
+#[cfg(feature = "ion")]
+pub mod ion;
+pub mod kafka;
+pub mod lint;
+mod macros;
+mod metrics;
+pub mod money;
 pub mod operations;
-mod ser;
+#[cfg(feature = "otel")]
+pub mod otel;
+pub mod patch;
+pub mod pool;
+#[cfg(feature = "sqlx")]
+mod postgres;
+#[cfg(feature = "prost")]
+pub mod protobuf;
+#[cfg(feature = "redis")]
+pub mod redis;
+pub mod ser;
+pub mod signing;
+#[cfg(feature = "testing")]
+pub mod testing;
+#[cfg(feature = "ubjson")]
+pub mod ubjson;
+mod unicode;
+pub mod units;
 
 // Re-export key types and functions for easy access
+#[cfg(feature = "bignum")]
+pub use bignum::{BigDecimal, BigInt};
 pub use bumpalo::Bump;
+pub use config::{from_args, from_env};
+pub use conversion::{IntoDataValue, ToArenaValue};
 pub use datavalue::{DataValue, DataValueType, Number};
-pub use error::{Error, Result};
+pub use document::{Document, NodeHandle, NodeRef, SharedDocument};
+pub use error::{Error, Location, Result};
 pub use helpers::*;
+pub use metrics::metrics_snapshot;
+pub use unicode::NormalizationForm;
 
 /// Re-export of the bumpalo crate for convenient usage.
 ///
@@ -86,10 +137,53 @@ pub mod json {
     pub use super::datavalue::DataValue as Value;
     pub use super::datavalue::DataValueType;
     pub use super::error::{Error, Result};
+    #[cfg(feature = "serde_json-compat")]
+    pub use super::from_json;
     pub use super::helpers::*;
-    pub use super::{from_json, from_str, to_string, to_string_pretty};
+    pub use super::{from_str, to_string, to_string_pretty};
+}
+
+/// The commonly used types, functions and traits in one import
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::prelude::*;
+///
+/// let arena = Bump::new();
+/// let value = from_str(&arena, r#"{"id": 7}"#).unwrap();
+/// assert_eq!(value["id"].get_type(), DataValueType::Integer);
+/// assert_eq!(to_string(&helpers::array(&arena, vec![helpers::int(1)])), "[1]");
+/// ```
+pub mod prelude {
+    #[cfg(feature = "bignum")]
+    pub use crate::bignum::{BigDecimal, BigInt};
+    pub use crate::datavalue::{DataValue, DataValueType, Number};
+    pub use crate::document::Document;
+    pub use crate::error::{Error, Result};
+    pub use crate::helpers;
+    #[cfg(feature = "serde_json-compat")]
+    pub use crate::{from_json, from_json_with_options};
+    pub use crate::{
+        from_str, from_str_borrowed, from_str_with_options, to_string, to_string_pretty,
+        to_string_with_options, ParseOptions, SerializeOptions,
+    };
+    pub use crate::{Bump, IntoDataValue};
 }
 
 // Standalone functions (similar to serde_json)
-pub use de::{from_json, from_str};
-pub use ser::{to_string, to_string_pretty};
+#[cfg(feature = "json5")]
+pub use de::json5::{from_str_json5, from_str_json5_with_options};
+pub use de::lenient::from_str_lenient;
+#[cfg(feature = "serde_json-compat")]
+pub use de::{from_json, from_json_with_options, from_value, DataValueSeed};
+pub use de::{
+    from_str, from_str_borrowed, from_str_borrowed_with_options, from_str_jsonc,
+    from_str_with_options, DuplicateKeyPolicy, NumberMode, ParseOptions, SurrogatePolicy,
+};
+#[cfg(feature = "serde_json-compat")]
+pub use ser::to_value;
+pub use ser::{
+    to_string, to_string_canonical, to_string_pretty, to_string_pretty_with,
+    to_string_with_options, Indent, NonFiniteFloats, PrettyConfig, RepeatedKeys, SerializeOptions,
+};
@@ -0,0 +1,450 @@
+//! Lint rules for JSON documents
+//!
+//! This module checks DataValue trees against configurable structural rules and
+//! reports diagnostics with JSON pointer paths, plus source locations when the
+//! value comes from a [`Document`]. It is intended for quality gates that run
+//! before data is ingested.
+
+use std::fmt;
+use std::ops::Range;
+
+use crate::access::escape_pointer_token;
+use crate::datavalue::{DataValue, DataValueType};
+use crate::document::Document;
+
+/// Naming conventions that can be enforced on object keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyCase {
+    /// `snake_case`
+    Snake,
+    /// `SCREAMING_SNAKE_CASE`
+    ScreamingSnake,
+    /// `camelCase`
+    Camel,
+    /// `PascalCase`
+    Pascal,
+    /// `kebab-case`
+    Kebab,
+}
+
+impl KeyCase {
+    /// Returns true if the key follows this convention
+    ///
+    /// Digits are allowed anywhere except at the start of the key.
+    pub fn matches(&self, key: &str) -> bool {
+        let mut chars = key.chars();
+        let Some(first) = chars.next() else {
+            return false;
+        };
+        let rest = chars.as_str();
+        match self {
+            KeyCase::Snake => separated(key, '_', |c| c.is_ascii_lowercase()),
+            KeyCase::ScreamingSnake => separated(key, '_', |c| c.is_ascii_uppercase()),
+            KeyCase::Kebab => separated(key, '-', |c| c.is_ascii_lowercase()),
+            KeyCase::Camel => {
+                first.is_ascii_lowercase() && rest.chars().all(|c| c.is_ascii_alphanumeric())
+            }
+            KeyCase::Pascal => {
+                first.is_ascii_uppercase() && rest.chars().all(|c| c.is_ascii_alphanumeric())
+            }
+        }
+    }
+}
+
+/// Checks `word(sep word)*` where words are letters accepted by `letter` or digits
+fn separated(key: &str, sep: char, letter: impl Fn(char) -> bool) -> bool {
+    key.split(sep).all(|word| {
+        word.chars().next().is_some_and(&letter)
+            && word.chars().all(|c| letter(c) || c.is_ascii_digit())
+    })
+}
+
+/// A structural rule checked by [`lint`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Rule {
+    /// Containers may not be nested deeper than this (the root is depth 0)
+    MaxDepth(usize),
+    /// Every object key must follow the naming convention
+    KeyNaming(KeyCase),
+    /// These keys may not appear anywhere in the document
+    ForbiddenKeys(Vec<String>),
+    /// Strings may not be longer than this many characters
+    MaxStringLength(usize),
+    /// Array elements must all have the same type (integers and floats count as one)
+    NoMixedTypeArrays,
+}
+
+impl Rule {
+    /// Short identifier of the rule, used in diagnostics
+    pub fn name(&self) -> &'static str {
+        match self {
+            Rule::MaxDepth(_) => "max-depth",
+            Rule::KeyNaming(_) => "key-naming",
+            Rule::ForbiddenKeys(_) => "forbidden-key",
+            Rule::MaxStringLength(_) => "max-string-length",
+            Rule::NoMixedTypeArrays => "mixed-type-array",
+        }
+    }
+}
+
+/// How serious a diagnostic is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Reported but does not fail the gate
+    Warning,
+    /// Fails the gate
+    Error,
+}
+
+/// A set of rules with their severities
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::lint::{KeyCase, LintConfig, Rule, Severity};
+///
+/// let config = LintConfig::new()
+///     .rule(Rule::MaxDepth(8))
+///     .rule(Rule::KeyNaming(KeyCase::Snake))
+///     .rule_with_severity(Rule::MaxStringLength(256), Severity::Warning);
+/// assert_eq!(config.rules().len(), 3);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct LintConfig {
+    rules: Vec<(Rule, Severity)>,
+}
+
+impl LintConfig {
+    /// Creates an empty configuration
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a rule reported as an error
+    pub fn rule(self, rule: Rule) -> Self {
+        self.rule_with_severity(rule, Severity::Error)
+    }
+
+    /// Adds a rule with an explicit severity
+    pub fn rule_with_severity(mut self, rule: Rule, severity: Severity) -> Self {
+        self.rules.push((rule, severity));
+        self
+    }
+
+    /// Returns the configured rules
+    pub fn rules(&self) -> &[(Rule, Severity)] {
+        &self.rules
+    }
+}
+
+/// A rule violation found by [`lint`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// Name of the violated rule, see [`Rule::name`]
+    pub rule: &'static str,
+    /// Severity configured for the rule
+    pub severity: Severity,
+    /// JSON pointer to the offending value
+    pub path: String,
+    /// Human readable description
+    pub message: String,
+    /// Byte span of the offending value, when linting a [`Document`]
+    pub span: Option<Range<usize>>,
+    /// 1-based line and column of the offending value, when linting a [`Document`]
+    pub location: Option<(usize, usize)>,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let level = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        let path = if self.path.is_empty() {
+            "/"
+        } else {
+            &self.path
+        };
+        write!(f, "{}[{}]: {} at {}", level, self.rule, self.message, path)?;
+        if let Some((line, column)) = self.location {
+            write!(f, " (line {}, column {})", line, column)?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks a value against the configured rules
+///
+/// Diagnostics are returned in document order. Use [`lint_document`] to also get
+/// source locations.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::{lint::{self, KeyCase, LintConfig, Rule}, Bump, from_str};
+///
+/// let arena = Bump::new();
+/// let value = from_str(&arena, r#"{"userName": "ada", "tags": [1, "two"]}"#).unwrap();
+/// let config = LintConfig::new()
+///     .rule(Rule::KeyNaming(KeyCase::Snake))
+///     .rule(Rule::NoMixedTypeArrays);
+///
+/// let diagnostics = lint::lint(&value, &config);
+/// assert_eq!(diagnostics.len(), 2);
+/// assert_eq!(diagnostics[0].path, "/userName");
+/// assert_eq!(diagnostics[1].rule, "mixed-type-array");
+/// ```
+pub fn lint(value: &DataValue<'_>, config: &LintConfig) -> Vec<Diagnostic> {
+    let mut walker = Walker {
+        config,
+        document: None,
+        path: String::new(),
+        diagnostics: Vec::new(),
+    };
+    walker.walk(value);
+    walker.diagnostics
+}
+
+/// Checks a parsed document against the configured rules, with source locations
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::{lint::{self, LintConfig, Rule}, Bump, Document};
+///
+/// let arena = Bump::new();
+/// let doc = Document::parse(&arena, "{\n  \"password\": \"hunter2\"\n}").unwrap();
+/// let config = LintConfig::new().rule(Rule::ForbiddenKeys(vec!["password".into()]));
+///
+/// let diagnostics = lint::lint_document(&doc, &config);
+/// assert_eq!(diagnostics[0].location, Some((2, 15)));
+/// ```
+pub fn lint_document(document: &Document<'_>, config: &LintConfig) -> Vec<Diagnostic> {
+    let mut walker = Walker {
+        config,
+        document: Some(document),
+        path: String::new(),
+        diagnostics: Vec::new(),
+    };
+    walker.walk(document.root());
+    walker.diagnostics
+}
+
+struct Walker<'c, 'd> {
+    config: &'c LintConfig,
+    document: Option<&'d Document<'d>>,
+    path: String,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Walker<'_, '_> {
+    fn report(&mut self, rule: &Rule, severity: Severity, node: &DataValue<'_>, message: String) {
+        let span = self.document.and_then(|doc| doc.span_of(node));
+        let location = self.document.and_then(|doc| doc.location_of(node));
+        self.diagnostics.push(Diagnostic {
+            rule: rule.name(),
+            severity,
+            path: self.path.clone(),
+            message,
+            span,
+            location,
+        });
+    }
+
+    /// Checks `root` and everything under it, in document order
+    ///
+    /// Nodes wait on an explicit stack with their depth and the length of their
+    /// parent's pointer, so deep input does not consume thread stack. The walk is
+    /// depth first, so that prefix of `self.path` is still the parent's pointer
+    /// when a node is reached.
+    fn walk(&mut self, root: &DataValue<'_>) {
+        /// How a node is reached from its parent
+        enum Member<'k> {
+            Root,
+            Index(usize),
+            Key(&'k str),
+        }
+
+        self.path.clear();
+        let mut stack = vec![(root, 0, 0, Member::Root)];
+        while let Some((node, parent_len, depth, member)) = stack.pop() {
+            self.path.truncate(parent_len);
+            match member {
+                Member::Root => {}
+                Member::Index(i) => {
+                    self.path.push('/');
+                    self.path.push_str(&i.to_string());
+                }
+                Member::Key(key) => {
+                    self.path.push('/');
+                    self.path.push_str(&escape_pointer_token(key));
+                    self.check_key(key, node);
+                }
+            }
+            if !self.check(node, depth) {
+                continue;
+            }
+            let len = self.path.len();
+            match node {
+                DataValue::Array(items) => {
+                    for (i, item) in items.iter().enumerate().rev() {
+                        stack.push((item, len, depth + 1, Member::Index(i)));
+                    }
+                }
+                DataValue::Object(entries) => {
+                    for (key, value) in entries.iter().rev() {
+                        stack.push((value, len, depth + 1, Member::Key(key)));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Applies the value rules to `node`, returning false if its descendants
+    /// should not be checked
+    fn check(&mut self, node: &DataValue<'_>, depth: usize) -> bool {
+        let config = self.config;
+        for (rule, severity) in &config.rules {
+            match (rule, node) {
+                (Rule::MaxDepth(max), DataValue::Array(_) | DataValue::Object(_))
+                    if depth > *max =>
+                {
+                    let message = format!("nesting depth {} exceeds {}", depth, max);
+                    self.report(rule, *severity, node, message);
+                    // Do not flood the report with every descendant
+                    return false;
+                }
+                (Rule::MaxStringLength(max), DataValue::String(s)) => {
+                    let length = s.chars().count();
+                    if length > *max {
+                        let message = format!("string length {} exceeds {}", length, max);
+                        self.report(rule, *severity, node, message);
+                    }
+                }
+                (Rule::NoMixedTypeArrays, DataValue::Array(items)) => {
+                    let kind = |v: &DataValue<'_>| match v.get_type() {
+                        DataValueType::Float => DataValueType::Integer,
+                        other => other,
+                    };
+                    if let Some(first) = items.first() {
+                        if let Some(other) = items.iter().find(|v| kind(v) != kind(first)) {
+                            let message = format!(
+                                "array mixes {:?} and {:?} elements",
+                                first.get_type(),
+                                other.get_type()
+                            );
+                            self.report(rule, *severity, node, message);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        true
+    }
+
+    fn check_key(&mut self, key: &str, value: &DataValue<'_>) {
+        let config = self.config;
+        for (rule, severity) in &config.rules {
+            match rule {
+                Rule::KeyNaming(case) if !case.matches(key) => {
+                    let message = format!("key {:?} is not {:?} case", key, case);
+                    self.report(rule, *severity, value, message);
+                }
+                Rule::ForbiddenKeys(keys) if keys.iter().any(|k| k == key) => {
+                    let message = format!("key {:?} is forbidden", key);
+                    self.report(rule, *severity, value, message);
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_str;
+    use bumpalo::Bump;
+
+    #[test]
+    fn test_key_cases() {
+        assert!(KeyCase::Snake.matches("user_id2"));
+        assert!(!KeyCase::Snake.matches("user__id"));
+        assert!(!KeyCase::Snake.matches("userId"));
+        assert!(KeyCase::ScreamingSnake.matches("MAX_RETRIES"));
+        assert!(KeyCase::Camel.matches("userId"));
+        assert!(!KeyCase::Camel.matches("UserId"));
+        assert!(KeyCase::Pascal.matches("UserId"));
+        assert!(KeyCase::Kebab.matches("content-type"));
+        assert!(!KeyCase::Kebab.matches("-leading"));
+        assert!(!KeyCase::Snake.matches(""));
+    }
+
+    #[test]
+    fn test_rules_and_paths() {
+        let arena = Bump::new();
+        let value = from_str(
+            &arena,
+            r#"{"a": {"b": {"c": [1]}}, "x/y": "toolong", "nums": [1, 2.5], "secret": null}"#,
+        )
+        .unwrap();
+        let config = LintConfig::new()
+            .rule(Rule::MaxDepth(2))
+            .rule_with_severity(Rule::MaxStringLength(3), Severity::Warning)
+            .rule(Rule::ForbiddenKeys(vec!["secret".to_string()]))
+            .rule(Rule::NoMixedTypeArrays);
+
+        let diagnostics = lint(&value, &config);
+        let summary: Vec<(&str, &str)> = diagnostics
+            .iter()
+            .map(|d| (d.rule, d.path.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("max-depth", "/a/b/c"),
+                ("max-string-length", "/x~1y"),
+                ("forbidden-key", "/secret"),
+            ]
+        );
+        assert_eq!(diagnostics[1].severity, Severity::Warning);
+        assert!(diagnostics.iter().all(|d| d.span.is_none()));
+    }
+
+    #[test]
+    fn test_deep_input() {
+        let depth = 50_000;
+        let arena = Bump::new();
+        let input = format!(
+            "{}\"secret\"{}",
+            r#"{"a":["#.repeat(depth),
+            "]}".repeat(depth)
+        );
+        let value = from_str(&arena, &input).unwrap();
+        let config = LintConfig::new()
+            .rule(Rule::MaxStringLength(3))
+            .rule(Rule::ForbiddenKeys(vec!["b".to_string()]));
+        let diagnostics = lint(&value, &config);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].path, "/a/0".repeat(depth));
+
+        let config = LintConfig::new().rule(Rule::MaxDepth(10));
+        assert_eq!(lint(&value, &config).len(), 1);
+    }
+
+    #[test]
+    fn test_display_with_location() {
+        let arena = Bump::new();
+        let doc = Document::parse(&arena, "[\n  [1, true]\n]").unwrap();
+        let config = LintConfig::new().rule(Rule::NoMixedTypeArrays);
+        let diagnostics = lint_document(&doc, &config);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].span, Some(4..13));
+        assert_eq!(
+            diagnostics[0].to_string(),
+            "error[mixed-type-array]: array mixes Integer and Bool elements at /0 (line 2, column 3)"
+        );
+    }
+}
@@ -0,0 +1,267 @@
+//! The [`dv!`](crate::dv) macro for writing DataValue literals
+
+/// Builds a DataValue from a JSON literal, allocating it in an arena
+///
+/// The first argument is a `&Bump`; the rest uses the syntax of
+/// `serde_json::json!`. Any Rust expression can stand in for a value, and is
+/// converted with [`ToArenaValue`](crate::ToArenaValue), so strings, numbers,
+/// options, vectors and other DataValues from the same arena can be
+/// interpolated. Object keys are string literals or expressions that give a
+/// `&str` or `String`; wrap anything longer than a single token in parentheses.
+/// Arrays and objects are collected with [`ArrayBuilder`](crate::builder::ArrayBuilder)
+/// and [`ObjectBuilder`](crate::builder::ObjectBuilder), so entries are written
+/// to the arena once.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::{dv, to_string, Bump};
+///
+/// let arena = Bump::new();
+/// let x = 3;
+/// let nickname: Option<&str> = None;
+/// let field = "role";
+/// let value = dv!(&arena, {
+///     "name": "John",
+///     "tags": [1, 2, x],
+///     "nickname": nickname,
+///     (field): format!("admin-{}", x + 1),
+///     "meta": {"active": true, "score": -1.5, "ids": []},
+/// });
+/// assert_eq!(
+///     to_string(&value),
+///     r#"{"name":"John","tags":[1,2,3],"nickname":null,"role":"admin-4","meta":{"active":true,"score":-1.5,"ids":[]}}"#
+/// );
+/// ```
+#[macro_export]
+macro_rules! dv {
+    ($arena:expr, $($value:tt)+) => {{
+        let arena: &$crate::Bump = $arena;
+        $crate::__dv!(arena; $($value)+)
+    }};
+}
+
+/// Implementation of [`dv!`](crate::dv), which munches the literal token by token
+/// the way `serde_json::json!` does
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __dv {
+    // Array elements: the converted elements so far, then the remaining tokens
+
+    // Done, with or without a trailing comma
+    ($a:ident; @array [$($elems:expr,)*]) => {
+        $crate::builder::ArrayBuilder::with_capacity($a, $crate::__dv!(@count $($elems)*))
+            $(.push($elems))*
+            .finish()
+    };
+    ($a:ident; @array [$($elems:expr),*]) => {
+        $crate::__dv!($a; @array [$($elems,)*])
+    };
+
+    // Next element is a literal, array or object
+    ($a:ident; @array [$($elems:expr,)*] null $($rest:tt)*) => {
+        $crate::__dv!($a; @array [$($elems,)* $crate::__dv!($a; null)] $($rest)*)
+    };
+    ($a:ident; @array [$($elems:expr,)*] true $($rest:tt)*) => {
+        $crate::__dv!($a; @array [$($elems,)* $crate::__dv!($a; true)] $($rest)*)
+    };
+    ($a:ident; @array [$($elems:expr,)*] false $($rest:tt)*) => {
+        $crate::__dv!($a; @array [$($elems,)* $crate::__dv!($a; false)] $($rest)*)
+    };
+    ($a:ident; @array [$($elems:expr,)*] [$($array:tt)*] $($rest:tt)*) => {
+        $crate::__dv!($a; @array [$($elems,)* $crate::__dv!($a; [$($array)*])] $($rest)*)
+    };
+    ($a:ident; @array [$($elems:expr,)*] {$($map:tt)*} $($rest:tt)*) => {
+        $crate::__dv!($a; @array [$($elems,)* $crate::__dv!($a; {$($map)*})] $($rest)*)
+    };
+
+    // Next element is an expression followed by a comma, or the last element
+    ($a:ident; @array [$($elems:expr,)*] $next:expr, $($rest:tt)*) => {
+        $crate::__dv!($a; @array [$($elems,)* $crate::__dv!($a; $next),] $($rest)*)
+    };
+    ($a:ident; @array [$($elems:expr,)*] $last:expr) => {
+        $crate::__dv!($a; @array [$($elems,)* $crate::__dv!($a; $last)])
+    };
+
+    // Comma after the most recent element
+    ($a:ident; @array [$($elems:expr),*] , $($rest:tt)*) => {
+        $crate::__dv!($a; @array [$($elems,)*] $($rest)*)
+    };
+
+    // Anything else is a syntax error
+    ($a:ident; @array [$($elems:expr),*] $unexpected:tt $($rest:tt)*) => {
+        $crate::__dv_unexpected!($unexpected)
+    };
+
+    // Object entries: the builder, the key tokens so far, the remaining tokens
+    // and a copy of them for error reporting
+
+    // Done
+    ($a:ident; @object $object:ident () () ()) => {};
+
+    // Insert the current entry, then continue after the comma
+    ($a:ident; @object $object:ident [$($key:tt)+] ($value:expr) , $($rest:tt)*) => {
+        $object.push(::core::convert::AsRef::<str>::as_ref(&($($key)+)), $value);
+        $crate::__dv!($a; @object $object () ($($rest)*) ($($rest)*));
+    };
+
+    // The current entry is followed by something other than a comma
+    ($a:ident; @object $object:ident [$($key:tt)+] ($value:expr) $unexpected:tt $($rest:tt)*) => {
+        $crate::__dv_unexpected!($unexpected);
+    };
+
+    // Insert the last entry
+    ($a:ident; @object $object:ident [$($key:tt)+] ($value:expr)) => {
+        $object.push(::core::convert::AsRef::<str>::as_ref(&($($key)+)), $value);
+    };
+
+    // Next value is a literal, array or object
+    ($a:ident; @object $object:ident ($($key:tt)+) (: null $($rest:tt)*) $copy:tt) => {
+        $crate::__dv!($a; @object $object [$($key)+] ($crate::__dv!($a; null)) $($rest)*);
+    };
+    ($a:ident; @object $object:ident ($($key:tt)+) (: true $($rest:tt)*) $copy:tt) => {
+        $crate::__dv!($a; @object $object [$($key)+] ($crate::__dv!($a; true)) $($rest)*);
+    };
+    ($a:ident; @object $object:ident ($($key:tt)+) (: false $($rest:tt)*) $copy:tt) => {
+        $crate::__dv!($a; @object $object [$($key)+] ($crate::__dv!($a; false)) $($rest)*);
+    };
+    ($a:ident; @object $object:ident ($($key:tt)+) (: [$($array:tt)*] $($rest:tt)*) $copy:tt) => {
+        $crate::__dv!($a; @object $object [$($key)+] ($crate::__dv!($a; [$($array)*])) $($rest)*);
+    };
+    ($a:ident; @object $object:ident ($($key:tt)+) (: {$($map:tt)*} $($rest:tt)*) $copy:tt) => {
+        $crate::__dv!($a; @object $object [$($key)+] ($crate::__dv!($a; {$($map)*})) $($rest)*);
+    };
+
+    // Next value is an expression followed by a comma, or the last value
+    ($a:ident; @object $object:ident ($($key:tt)+) (: $value:expr , $($rest:tt)*) $copy:tt) => {
+        $crate::__dv!($a; @object $object [$($key)+] ($crate::__dv!($a; $value)) , $($rest)*);
+    };
+    ($a:ident; @object $object:ident ($($key:tt)+) (: $value:expr) $copy:tt) => {
+        $crate::__dv!($a; @object $object [$($key)+] ($crate::__dv!($a; $value)));
+    };
+
+    // A colon with no value, or a key with no colon
+    ($a:ident; @object $object:ident ($($key:tt)+) (:) $copy:tt) => {
+        $crate::__dv!();
+    };
+    ($a:ident; @object $object:ident ($($key:tt)+) () $copy:tt) => {
+        $crate::__dv!();
+    };
+
+    // A colon or comma where a key should be
+    ($a:ident; @object $object:ident () (: $($rest:tt)*) ($colon:tt $($copy:tt)*)) => {
+        $crate::__dv_unexpected!($colon);
+    };
+    ($a:ident; @object $object:ident ($($key:tt)*) (, $($rest:tt)*) ($comma:tt $($copy:tt)*)) => {
+        $crate::__dv_unexpected!($comma);
+    };
+
+    // A parenthesized key is taken whole
+    ($a:ident; @object $object:ident () (($key:expr) : $($rest:tt)*) $copy:tt) => {
+        $crate::__dv!($a; @object $object ($key) (: $($rest)*) (: $($rest)*));
+    };
+
+    // Munch one more token into the current key
+    ($a:ident; @object $object:ident ($($key:tt)*) ($tt:tt $($rest:tt)*) $copy:tt) => {
+        $crate::__dv!($a; @object $object ($($key)* $tt) ($($rest)*) ($($rest)*));
+    };
+
+    // Number of elements, for the array's capacity
+    (@count) => { 0usize };
+    (@count $head:tt $($tail:tt)*) => { 1usize + $crate::__dv!(@count $($tail)*) };
+
+    // Values
+
+    ($a:ident; null) => {
+        $crate::DataValue::Null
+    };
+    ($a:ident; true) => {
+        $crate::DataValue::Bool(true)
+    };
+    ($a:ident; false) => {
+        $crate::DataValue::Bool(false)
+    };
+    ($a:ident; []) => {
+        $crate::builder::ArrayBuilder::new($a).finish()
+    };
+    ($a:ident; [ $($tt:tt)+ ]) => {
+        $crate::__dv!($a; @array [] $($tt)+)
+    };
+    ($a:ident; {}) => {
+        $crate::builder::ObjectBuilder::new($a).finish()
+    };
+    ($a:ident; { $($tt:tt)+ }) => {{
+        let mut object = $crate::builder::ObjectBuilder::new($a);
+        $crate::__dv!($a; @object object () ($($tt)+) ($($tt)+));
+        object.finish()
+    }};
+
+    // Any other expression is converted, borrowing it as serde_json::json! does
+    ($a:ident; $other:expr) => {
+        $crate::ToArenaValue::to_arena_value(&$other, $a)
+    };
+}
+
+/// Reports an unexpected token by matching no rule
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __dv_unexpected {
+    () => {};
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{from_str, helpers, to_string, Bump, DataValue};
+
+    #[test]
+    fn test_dv_literals() {
+        let arena = Bump::new();
+        let value = dv!(&arena, {
+            "a": null,
+            "b": [true, false, [], {}, [[1]], {"c": "d"}],
+            "e": -1.5e3,
+            "f": "x\"y",
+        });
+        let expected = from_str(
+            &arena,
+            r#"{"a": null, "b": [true, false, [], {}, [[1]], {"c": "d"}], "e": -1.5e3, "f": "x\"y"}"#,
+        )
+        .unwrap();
+        assert_eq!(value, expected);
+        assert_eq!(to_string(&dv!(&arena, [])), "[]");
+        assert_eq!(to_string(&dv!(&arena, [1, 2,])), "[1,2]");
+        assert_eq!(to_string(&dv!(&arena, "s")), r#""s""#);
+        assert_eq!(to_string(&dv!(&arena, 7)), "7");
+    }
+
+    #[test]
+    fn test_dv_interpolation() {
+        let arena = Bump::new();
+        let arena_ref = &arena;
+        let name = String::from("John");
+        let ids: Vec<u32> = vec![1, 2];
+        let nested = helpers::string(&arena, "already here");
+        let key = String::from("dynamic");
+        let value = dv!(arena_ref, {
+            "name": name,
+            "len": name.len(),
+            "ids": ids,
+            "missing": None::<bool>,
+            "nested": nested,
+            "sum": 1 + 2,
+            "list": [name.as_str(), 'c', Some(1.5)],
+            (key): {"k": key.to_uppercase()},
+            key.as_str(): 0,
+        });
+        assert_eq!(
+            to_string(&value),
+            concat!(
+                r#"{"name":"John","len":4,"ids":[1,2],"missing":null,"nested":"already here","#,
+                r#""sum":3,"list":["John","c",1.5],"dynamic":{"k":"DYNAMIC"},"dynamic":0}"#
+            )
+        );
+        // Interpolated values are borrowed, not moved
+        assert_eq!(name, "John");
+        assert!(matches!(value["nested"], DataValue::String(_)));
+    }
+}
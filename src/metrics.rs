@@ -0,0 +1,181 @@
+//! Process-wide counters describing what the crate has done
+//!
+//! The counters are plain relaxed atomics, cheap enough to stay on in production,
+//! and only ever increase, like Prometheus counters; compute rates by taking two
+//! snapshots. [`metrics_snapshot`] returns them as a DataValue ready to be embedded
+//! in a health or status endpoint.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use bumpalo::Bump;
+
+use crate::datavalue::DataValue;
+use crate::error::{Error, Result};
+use crate::helpers;
+
+static DOCUMENTS_PARSED: AtomicU64 = AtomicU64::new(0);
+static BYTES_PARSED: AtomicU64 = AtomicU64::new(0);
+static BYTES_ALLOCATED: AtomicU64 = AtomicU64::new(0);
+static POOL_HITS: AtomicU64 = AtomicU64::new(0);
+static POOL_MISSES: AtomicU64 = AtomicU64::new(0);
+
+/// Error kinds, in the order of [`ERRORS`]
+const ERROR_KINDS: [&str; 7] = [
+    "syntax",
+    "expected_type",
+    "missing_field",
+    "out_of_bounds",
+    "custom",
+    "io",
+    "json",
+];
+static ERRORS: [AtomicU64; ERROR_KINDS.len()] = [const { AtomicU64::new(0) }; ERROR_KINDS.len()];
+
+fn add(counter: &AtomicU64, n: usize) {
+    counter.fetch_add(n as u64, Ordering::Relaxed);
+}
+
+/// Runs a parse into `arena` and records it: the input length, how much the
+/// arena grew, and the document or error it produced
+pub(crate) fn parse<T>(
+    arena: &Bump,
+    input_len: usize,
+    parse: impl FnOnce() -> Result<T>,
+) -> Result<T> {
+    let before = arena.allocated_bytes();
+    let result = parse();
+    match &result {
+        Ok(_) => add(&DOCUMENTS_PARSED, 1),
+        Err(error) => record_error(error),
+    }
+    add(&BYTES_PARSED, input_len);
+    add(
+        &BYTES_ALLOCATED,
+        arena.allocated_bytes().saturating_sub(before),
+    );
+    result
+}
+
+/// Counts an error by its kind
+pub(crate) fn record_error(error: &Error) {
+    let kind = match error {
+        Error::Syntax(_) | Error::SyntaxAt { .. } => 0,
+        Error::ExpectedType { .. } => 1,
+        Error::MissingField(_) => 2,
+        Error::OutOfBounds(_) => 3,
+        Error::Custom(_) => 4,
+        Error::Io(_) => 5,
+        Error::Json(_) => 6,
+    };
+    add(&ERRORS[kind], 1);
+}
+
+/// Counts an [`ArenaPool`](crate::pool::ArenaPool) request, which was a hit if an
+/// idle arena was reused
+pub(crate) fn record_pool_take(hit: bool) {
+    add(if hit { &POOL_HITS } else { &POOL_MISSES }, 1);
+}
+
+fn get(counter: &AtomicU64) -> i64 {
+    counter.load(Ordering::Relaxed) as i64
+}
+
+/// Returns the crate's counters as an object allocated in `arena`
+///
+/// - `documents_parsed` and `bytes_parsed`: documents parsed successfully by
+///   [`from_str`](crate::from_str) and its variants and by
+///   [`Document`](crate::Document), and the bytes of input they were given,
+///   including input that failed to parse
+/// - `bytes_allocated`: growth of the arenas those parses allocated into
+/// - `pool`: `hits` and `misses` of [`ArenaPool::take`](crate::pool::ArenaPool::take),
+///   and `hit_rate`, the fraction of hits, or null before the first request
+/// - `errors`: the errors those parses returned, by kind
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::{from_str, metrics_snapshot, Bump};
+///
+/// let arena = Bump::new();
+/// let _ = from_str(&arena, "[1, 2");
+/// let metrics = metrics_snapshot(&arena);
+/// assert!(metrics["errors"]["syntax"].as_i64().unwrap() >= 1);
+/// assert!(metrics["bytes_parsed"].as_i64().unwrap() >= 5);
+/// println!("{}", metrics);
+/// ```
+pub fn metrics_snapshot(arena: &Bump) -> DataValue<'_> {
+    let (hits, misses) = (get(&POOL_HITS), get(&POOL_MISSES));
+    let hit_rate = match hits + misses {
+        0 => helpers::null(),
+        total => helpers::float(hits as f64 / total as f64),
+    };
+    let pool = helpers::object(
+        arena,
+        vec![
+            ("hits", helpers::int(hits)),
+            ("misses", helpers::int(misses)),
+            ("hit_rate", hit_rate),
+        ],
+    );
+    let errors = ERROR_KINDS
+        .iter()
+        .zip(&ERRORS)
+        .map(|(&kind, count)| (kind, helpers::int(get(count))))
+        .collect();
+    helpers::object(
+        arena,
+        vec![
+            ("documents_parsed", helpers::int(get(&DOCUMENTS_PARSED))),
+            ("bytes_parsed", helpers::int(get(&BYTES_PARSED))),
+            ("bytes_allocated", helpers::int(get(&BYTES_ALLOCATED))),
+            ("pool", pool),
+            ("errors", helpers::object(arena, errors)),
+        ],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pool::ArenaPool;
+
+    #[test]
+    fn test_counters() {
+        // Other tests run concurrently, so only lower bounds can be checked
+        let arena = Bump::new();
+        let before = metrics_snapshot(&arena);
+        let count = |snapshot: &DataValue, pointer: &str| {
+            snapshot
+                .pointer(pointer)
+                .and_then(DataValue::as_i64)
+                .unwrap()
+        };
+
+        let input = format!("[{}]", "\"some text\",".repeat(100) + "1");
+        crate::from_str(&arena, &input).unwrap();
+        crate::Document::parse(&arena, "{}").unwrap();
+        assert!(crate::from_str(&arena, "nul").is_err());
+        let pool = ArenaPool::new();
+        pool.put(pool.take());
+        drop(pool.take());
+
+        let after = metrics_snapshot(&arena);
+        for (pointer, at_least) in [
+            ("/documents_parsed", 2),
+            ("/bytes_parsed", input.len() as i64 + 5),
+            ("/bytes_allocated", 1),
+            ("/errors/syntax", 1),
+            ("/pool/hits", 1),
+            ("/pool/misses", 1),
+        ] {
+            let delta = count(&after, pointer) - count(&before, pointer);
+            assert!(delta >= at_least, "{} grew by {}", pointer, delta);
+        }
+        let rate = after["pool"]["hit_rate"].as_f64().unwrap();
+        assert!((0.0..=1.0).contains(&rate));
+        assert_eq!(
+            after["errors"].as_object().unwrap().len(),
+            ERROR_KINDS.len()
+        );
+    }
+}
@@ -0,0 +1,468 @@
+//! Currency amount conventions for DataValue
+//!
+//! This module recognizes the common `{"amount": "12.34", "currency": "USD"}` shape
+//! and parses it into a decimal-backed [`Amount`], so monetary values can be added,
+//! compared and formatted without the rounding errors of binary floating point.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+use bumpalo::Bump;
+
+use crate::datavalue::{DataValue, Number};
+use crate::error::{Error, Result};
+
+/// A fixed-point decimal number
+///
+/// The value is `mantissa * 10^-scale`. Trailing zeros are preserved, so `"12.30"`
+/// keeps a scale of 2 and formats back as `"12.30"`.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::money::Decimal;
+///
+/// let a: Decimal = "0.1".parse().unwrap();
+/// let b: Decimal = "0.2".parse().unwrap();
+/// assert_eq!(a.checked_add(&b).unwrap().to_string(), "0.3");
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Decimal {
+    mantissa: i128,
+    scale: u32,
+}
+
+/// The largest scale a Decimal can carry
+const MAX_SCALE: u32 = 28;
+
+impl Decimal {
+    /// Creates a decimal from a mantissa and a scale
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use datavalue_rs::money::Decimal;
+    ///
+    /// assert_eq!(Decimal::new(1234, 2).to_string(), "12.34");
+    /// ```
+    pub fn new(mantissa: i128, scale: u32) -> Self {
+        Decimal { mantissa, scale }
+    }
+
+    /// Returns the unscaled integer value
+    pub fn mantissa(&self) -> i128 {
+        self.mantissa
+    }
+
+    /// Returns the number of digits after the decimal point
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+
+    /// Returns true if the value is negative
+    pub fn is_negative(&self) -> bool {
+        self.mantissa < 0
+    }
+
+    /// Adds two decimals, returning an error on overflow
+    pub fn checked_add(&self, other: &Decimal) -> Result<Decimal> {
+        let (a, b, scale) = self.aligned(other)?;
+        a.checked_add(b)
+            .map(|mantissa| Decimal { mantissa, scale })
+            .ok_or_else(|| Error::custom("Decimal overflow in addition"))
+    }
+
+    /// Subtracts two decimals, returning an error on overflow
+    pub fn checked_sub(&self, other: &Decimal) -> Result<Decimal> {
+        let (a, b, scale) = self.aligned(other)?;
+        a.checked_sub(b)
+            .map(|mantissa| Decimal { mantissa, scale })
+            .ok_or_else(|| Error::custom("Decimal overflow in subtraction"))
+    }
+
+    /// Multiplies two decimals, returning an error on overflow
+    pub fn checked_mul(&self, other: &Decimal) -> Result<Decimal> {
+        let scale = self.scale + other.scale;
+        if scale > MAX_SCALE {
+            return Err(Error::custom("Decimal scale overflow in multiplication"));
+        }
+        self.mantissa
+            .checked_mul(other.mantissa)
+            .map(|mantissa| Decimal { mantissa, scale })
+            .ok_or_else(|| Error::custom("Decimal overflow in multiplication"))
+    }
+
+    /// Converts the decimal to the nearest f64
+    pub fn to_f64(&self) -> f64 {
+        self.mantissa as f64 / 10f64.powi(self.scale as i32)
+    }
+
+    /// Brings two decimals to a common scale
+    fn aligned(&self, other: &Decimal) -> Result<(i128, i128, u32)> {
+        let scale = self.scale.max(other.scale);
+        let rescale = |d: &Decimal| {
+            10i128
+                .checked_pow(scale - d.scale)
+                .and_then(|factor| d.mantissa.checked_mul(factor))
+                .ok_or_else(|| Error::custom("Decimal overflow while rescaling"))
+        };
+        Ok((rescale(self)?, rescale(other)?, scale))
+    }
+}
+
+impl FromStr for Decimal {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || Error::syntax(format!("Invalid decimal: {:?}", s));
+        let (negative, digits) = match s.as_bytes().first() {
+            Some(b'-') => (true, &s[1..]),
+            Some(b'+') => (false, &s[1..]),
+            _ => (false, s),
+        };
+        let (int_part, frac_part) = match digits.split_once('.') {
+            Some((i, f)) => (i, f),
+            None => (digits, ""),
+        };
+        if int_part.is_empty() && frac_part.is_empty() {
+            return Err(invalid());
+        }
+        if !int_part
+            .bytes()
+            .chain(frac_part.bytes())
+            .all(|b| b.is_ascii_digit())
+        {
+            return Err(invalid());
+        }
+        let scale = frac_part.len() as u32;
+        if scale > MAX_SCALE {
+            return Err(invalid());
+        }
+
+        let mut mantissa: i128 = 0;
+        for b in int_part.bytes().chain(frac_part.bytes()) {
+            mantissa = mantissa
+                .checked_mul(10)
+                .and_then(|m| m.checked_add((b - b'0') as i128))
+                .ok_or_else(invalid)?;
+        }
+        if negative {
+            mantissa = -mantissa;
+        }
+        Ok(Decimal { mantissa, scale })
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digits = self.mantissa.unsigned_abs().to_string();
+        let sign = if self.mantissa < 0 { "-" } else { "" };
+        let scale = self.scale as usize;
+        if scale == 0 {
+            return write!(f, "{}{}", sign, digits);
+        }
+        let padded = format!("{:0>width$}", digits, width = scale + 1);
+        let (int_part, frac_part) = padded.split_at(padded.len() - scale);
+        write!(f, "{}{}.{}", sign, int_part, frac_part)
+    }
+}
+
+impl PartialEq for Decimal {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Decimal {}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Decimal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Rescale the operand with the smaller scale. If that overflows, its
+        // magnitude exceeds any i128 mantissa, so its sign decides the order
+        let (low, high, ordering) = if self.scale <= other.scale {
+            (self, other, Ordering::Less)
+        } else {
+            (other, self, Ordering::Greater)
+        };
+        let order = match 10i128
+            .checked_pow(high.scale - low.scale)
+            .and_then(|factor| low.mantissa.checked_mul(factor))
+        {
+            Some(mantissa) => mantissa.cmp(&high.mantissa),
+            None if low.mantissa == 0 => 0.cmp(&high.mantissa),
+            None => low.mantissa.cmp(&0),
+        };
+        if ordering == Ordering::Less {
+            order
+        } else {
+            order.reverse()
+        }
+    }
+}
+
+/// A monetary amount in a specific currency
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::{money::Amount, Bump, from_str};
+///
+/// let arena = Bump::new();
+/// let price = from_str(&arena, r#"{"amount": "12.34", "currency": "USD"}"#).unwrap();
+/// let tax = from_str(&arena, r#"{"amount": "0.99", "currency": "USD"}"#).unwrap();
+///
+/// let total = Amount::from_value(&price)
+///     .unwrap()
+///     .checked_add(&Amount::from_value(&tax).unwrap())
+///     .unwrap();
+/// assert_eq!(total.to_string(), "13.33 USD");
+///
+/// let value = total.to_value(&arena);
+/// assert_eq!(value["amount"].as_str(), Some("13.33"));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Amount<'a> {
+    /// The decimal quantity
+    pub amount: Decimal,
+    /// The ISO 4217 currency code, e.g. `"USD"`
+    pub currency: &'a str,
+}
+
+impl<'a> Amount<'a> {
+    /// Creates a new amount
+    pub fn new(amount: Decimal, currency: &'a str) -> Self {
+        Amount { amount, currency }
+    }
+
+    /// Parses an amount from a `{"amount", "currency"}` object
+    ///
+    /// The `amount` field may be a decimal string (preferred, exact) or a JSON number.
+    /// The `currency` field must be a three-letter uppercase code.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the value is not an object, a field is missing, the
+    /// amount cannot be parsed, or the currency code is malformed.
+    pub fn from_value(value: &DataValue<'a>) -> Result<Self> {
+        if !value.is_object() {
+            return Err(Error::expected_type(
+                "amount object",
+                format!("{:?}", value.get_type()).to_lowercase(),
+            ));
+        }
+        let amount = match value.get("amount") {
+            Some(DataValue::String(s)) => s.parse()?,
+            Some(DataValue::Number(Number::Integer(i))) => Decimal::new(*i as i128, 0),
+            // Go through the shortest round-trip representation of the float
+            Some(DataValue::Number(Number::Float(f))) => f.to_string().parse()?,
+            // Raw number text keeps every digit of the input
+            Some(DataValue::Number(Number::Raw(text))) => text.parse()?,
+            #[cfg(feature = "bignum")]
+            Some(DataValue::Number(n @ (Number::BigInt(_) | Number::BigDecimal(_)))) => {
+                DataValue::Number(*n).to_string().parse()?
+            }
+            Some(other) => {
+                return Err(Error::expected_type(
+                    "decimal string or number",
+                    format!("{:?}", other.get_type()).to_lowercase(),
+                ))
+            }
+            None => return Err(Error::missing_field("amount")),
+        };
+        let currency = match value.get("currency") {
+            Some(DataValue::String(s)) => *s,
+            Some(other) => {
+                return Err(Error::expected_type(
+                    "currency string",
+                    format!("{:?}", other.get_type()).to_lowercase(),
+                ))
+            }
+            None => return Err(Error::missing_field("currency")),
+        };
+        if currency.len() != 3 || !currency.bytes().all(|b| b.is_ascii_uppercase()) {
+            return Err(Error::custom(format!(
+                "Invalid currency code: {:?}",
+                currency
+            )));
+        }
+        Ok(Amount { amount, currency })
+    }
+
+    /// Converts the amount back into a `{"amount", "currency"}` object
+    ///
+    /// The amount is written as a string to keep it exact.
+    pub fn to_value(&self, arena: &'a Bump) -> DataValue<'a> {
+        let amount = arena.alloc_str(&self.amount.to_string());
+        DataValue::Object(arena.alloc_slice_clone(&[
+            ("amount", DataValue::String(amount)),
+            ("currency", DataValue::String(self.currency)),
+        ]))
+    }
+
+    /// Adds two amounts, failing if the currencies differ
+    pub fn checked_add(&self, other: &Amount<'_>) -> Result<Amount<'a>> {
+        self.ensure_same_currency(other, "add")?;
+        Ok(Amount::new(
+            self.amount.checked_add(&other.amount)?,
+            self.currency,
+        ))
+    }
+
+    /// Subtracts two amounts, failing if the currencies differ
+    pub fn checked_sub(&self, other: &Amount<'_>) -> Result<Amount<'a>> {
+        self.ensure_same_currency(other, "subtract")?;
+        Ok(Amount::new(
+            self.amount.checked_sub(&other.amount)?,
+            self.currency,
+        ))
+    }
+
+    /// Multiplies the amount by a decimal factor, such as a quantity or a rate
+    pub fn checked_mul(&self, factor: &Decimal) -> Result<Amount<'a>> {
+        Ok(Amount::new(self.amount.checked_mul(factor)?, self.currency))
+    }
+
+    /// Compares two amounts, failing if the currencies differ
+    pub fn compare(&self, other: &Amount<'_>) -> Result<Ordering> {
+        self.ensure_same_currency(other, "compare")?;
+        Ok(self.amount.cmp(&other.amount))
+    }
+
+    fn ensure_same_currency(&self, other: &Amount<'_>, op: &str) -> Result<()> {
+        if self.currency != other.currency {
+            return Err(Error::custom(format!(
+                "Cannot {} amounts in different currencies: {} and {}",
+                op, self.currency, other.currency
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Amount<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.amount, self.currency)
+    }
+}
+
+/// Returns true if the value looks like a `{"amount", "currency"}` object
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::{money, Bump, from_str};
+///
+/// let arena = Bump::new();
+/// let price = from_str(&arena, r#"{"amount": "12.34", "currency": "EUR"}"#).unwrap();
+/// assert!(money::is_amount(&price));
+/// assert!(!money::is_amount(&price["amount"]));
+/// ```
+pub fn is_amount(value: &DataValue<'_>) -> bool {
+    Amount::from_value(value).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_str;
+
+    #[test]
+    fn test_decimal_parse_and_format() {
+        for input in ["0", "12.34", "-0.05", "100.00", "7."] {
+            let d: Decimal = input.parse().unwrap();
+            let expected = input.trim_end_matches('.');
+            assert_eq!(d.to_string(), expected);
+        }
+        assert_eq!(".5".parse::<Decimal>().unwrap().to_string(), "0.5");
+        assert!("".parse::<Decimal>().is_err());
+        assert!("1.2.3".parse::<Decimal>().is_err());
+        assert!("abc".parse::<Decimal>().is_err());
+
+        let a: Decimal = "1.10".parse().unwrap();
+        let b: Decimal = "1.1".parse().unwrap();
+        assert_eq!(a, b);
+        assert_eq!(a.checked_mul(&b).unwrap().to_string(), "1.210");
+        assert_eq!(
+            b.checked_sub(&"2".parse().unwrap()).unwrap().to_string(),
+            "-0.9"
+        );
+    }
+
+    #[test]
+    fn test_decimal_ordering_without_overflowing() {
+        // Aligning these scales overflows i128, yet they still order exactly
+        let huge = Decimal::new(i128::MAX, 0);
+        let almost = Decimal::new(i128::MAX - 1, 0);
+        let tiny = Decimal::new(1, 28);
+        let negative_tiny = Decimal::new(-1, 28);
+        assert_eq!(huge.cmp(&tiny), Ordering::Greater);
+        assert_eq!(tiny.cmp(&huge), Ordering::Less);
+        assert_eq!(huge.cmp(&almost), Ordering::Greater);
+        assert_ne!(huge, almost);
+        assert_eq!(
+            Decimal::new(i128::MIN, 0).cmp(&negative_tiny),
+            Ordering::Less
+        );
+        assert_eq!(
+            Decimal::new(-5, 0).cmp(&Decimal::new(1, 60)),
+            Ordering::Less
+        );
+        assert_eq!(
+            Decimal::new(0, 0).cmp(&Decimal::new(-1, 60)),
+            Ordering::Greater
+        );
+        assert_eq!(Decimal::new(0, 60), Decimal::new(0, 0));
+        assert_eq!(
+            Decimal::new(i128::MAX / 10 * 10, 1),
+            Decimal::new(i128::MAX / 10, 0)
+        );
+
+        let mut values = vec![huge, tiny, almost, negative_tiny, Decimal::new(0, 0)];
+        values.sort();
+        assert_eq!(
+            values,
+            vec![negative_tiny, Decimal::new(0, 0), tiny, almost, huge]
+        );
+    }
+
+    #[test]
+    fn test_amount_arithmetic() {
+        let arena = Bump::new();
+        let usd = from_str(&arena, r#"{"amount": "10.50", "currency": "USD"}"#).unwrap();
+        let usd_num = from_str(&arena, r#"{"amount": 2, "currency": "USD"}"#).unwrap();
+        let eur = from_str(&arena, r#"{"amount": "1", "currency": "EUR"}"#).unwrap();
+
+        let a = Amount::from_value(&usd).unwrap();
+        let b = Amount::from_value(&usd_num).unwrap();
+        let c = Amount::from_value(&eur).unwrap();
+
+        assert_eq!(a.checked_add(&b).unwrap().to_string(), "12.50 USD");
+        assert_eq!(a.checked_sub(&b).unwrap().to_string(), "8.50 USD");
+        assert_eq!(a.compare(&b).unwrap(), Ordering::Greater);
+        assert!(a.checked_add(&c).is_err());
+        assert!(a.compare(&c).is_err());
+    }
+
+    #[test]
+    fn test_amount_validation() {
+        let arena = Bump::new();
+        for bad in [
+            r#"{"amount": "1"}"#,
+            r#"{"currency": "USD"}"#,
+            r#"{"amount": "x", "currency": "USD"}"#,
+            r#"{"amount": "1", "currency": "usd"}"#,
+            r#"{"amount": true, "currency": "USD"}"#,
+            r#""12.34 USD""#,
+        ] {
+            let value = from_str(&arena, bad).unwrap();
+            assert!(!is_amount(&value), "accepted {}", bad);
+        }
+    }
+}
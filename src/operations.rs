@@ -5,8 +5,18 @@
 use std::cmp::{Ordering, PartialEq, PartialOrd};
 use std::ops::{Add, Div, Mul, Not, Sub};
 
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+
+use bumpalo::Bump;
+use chrono::{DateTime, Duration, Utc};
+
 use crate::{
+    access::escape_pointer_token,
     datavalue::{DataValue, Number},
+    helpers,
+    units::{BuiltinUnits, UnitConverter},
     Error, Result,
 };
 
@@ -30,7 +40,7 @@ impl Add for DataValue<'_> {
     ///
     /// A Result containing the resulting DataValue, or an Error if the operation is invalid.
     fn add(self, other: Self) -> Self::Output {
-        match (self, other) {
+        match (self.with_parsed_number(), other.with_parsed_number()) {
             // Integer + Integer
             (DataValue::Number(Number::Integer(a)), DataValue::Number(Number::Integer(b))) => {
                 Ok(DataValue::Number(Number::Integer(a + b)))
@@ -75,7 +85,7 @@ impl Sub for DataValue<'_> {
     ///
     /// A Result containing the resulting DataValue, or an Error if the operation is invalid.
     fn sub(self, other: Self) -> Self::Output {
-        match (self, other) {
+        match (self.with_parsed_number(), other.with_parsed_number()) {
             // Integer - Integer
             (DataValue::Number(Number::Integer(a)), DataValue::Number(Number::Integer(b))) => {
                 Ok(DataValue::Number(Number::Integer(a - b)))
@@ -120,7 +130,7 @@ impl Mul for DataValue<'_> {
     ///
     /// A Result containing the resulting DataValue, or an Error if the operation is invalid.
     fn mul(self, other: Self) -> Self::Output {
-        match (self, other) {
+        match (self.with_parsed_number(), other.with_parsed_number()) {
             // Integer * Integer
             (DataValue::Number(Number::Integer(a)), DataValue::Number(Number::Integer(b))) => {
                 Ok(DataValue::Number(Number::Integer(a * b)))
@@ -166,7 +176,7 @@ impl Div for DataValue<'_> {
     ///
     /// A Result containing the resulting DataValue, or an Error if the operation is invalid.
     fn div(self, other: Self) -> Self::Output {
-        match (self, other) {
+        match (self.with_parsed_number(), other.with_parsed_number()) {
             // Division by zero check for integers
             (_, DataValue::Number(Number::Integer(0))) => Err(Error::custom("Division by zero")),
             // Division by zero check for floats
@@ -272,9 +282,1153 @@ impl PartialOrd for DataValue<'_> {
     }
 }
 
+/// Interprets a DataValue as a point in time
+///
+/// # Behavior
+///
+/// - DateTime values are returned as-is
+/// - Strings are parsed with the same formats accepted by [`helpers::datetime`]
+/// - Integers are treated as seconds since the Unix epoch
+/// - Floats are treated as fractional seconds since the Unix epoch
+/// - Other types (or unparseable strings) return None
+///
+/// # Example
+///
+/// ```
+/// # use datavalue_rs::{operations, helpers, Bump};
+/// # let arena = Bump::new();
+/// let from_str = operations::coerce_datetime(&helpers::string(&arena, "2023-01-01")).unwrap();
+/// let from_epoch = operations::coerce_datetime(&helpers::int(1672531200)).unwrap();
+/// assert_eq!(from_str, from_epoch);
+/// ```
+pub fn coerce_datetime(value: &DataValue) -> Option<DateTime<Utc>> {
+    match value {
+        DataValue::DateTime(dt) => Some(*dt),
+        DataValue::String(s) => helpers::parse_datetime(s).ok(),
+        DataValue::Number(n @ Number::Raw(_)) => coerce_datetime(&DataValue::Number(n.parsed())),
+        #[cfg(feature = "bignum")]
+        DataValue::Number(n @ (Number::BigInt(_) | Number::BigDecimal(_))) => {
+            coerce_datetime(&DataValue::Number(n.parsed()))
+        }
+        DataValue::Number(Number::Integer(secs)) => DateTime::from_timestamp(*secs, 0),
+        DataValue::Number(Number::Float(secs)) => {
+            if !secs.is_finite() {
+                return None;
+            }
+            let whole = secs.floor();
+            let nanos = ((secs - whole) * 1_000_000_000.0).round() as u32;
+            DateTime::from_timestamp(whole as i64, nanos.min(999_999_999))
+        }
+        _ => None,
+    }
+}
+
+/// Compares two values as points in time, coercing either side as needed
+///
+/// Both operands are interpreted with [`coerce_datetime`], so a DateTime can be
+/// compared against an RFC3339 string, a bare date, or an epoch number, and vice versa.
+///
+/// # Arguments
+///
+/// * `value` - The left-hand operand, typically a field taken from a document
+/// * `spec` - The right-hand operand, typically the filter bound
+///
+/// # Returns
+///
+/// A Result containing the Ordering of `value` relative to `spec`, or an Error if
+/// either side cannot be interpreted as a point in time.
+///
+/// # Example
+///
+/// ```
+/// # use datavalue_rs::{operations, helpers, Bump, from_str};
+/// # use std::cmp::Ordering;
+/// let arena = Bump::new();
+/// let doc = from_str(&arena, r#"{"created": "2023-06-15T12:00:00Z"}"#).unwrap();
+///
+/// let bound = helpers::string(&arena, "2023-01-01");
+/// let ordering = operations::compare_temporal(&doc["created"], &bound).unwrap();
+/// assert!(ordering.is_ge());
+///
+/// // Epoch seconds work on either side
+/// let epoch = helpers::int(1700000000);
+/// assert_eq!(operations::compare_temporal(&doc["created"], &epoch).unwrap(), Ordering::Less);
+/// ```
+pub fn compare_temporal(value: &DataValue, spec: &DataValue) -> Result<Ordering> {
+    let left = coerce_datetime(value).ok_or_else(|| {
+        Error::custom(format!(
+            "Cannot interpret value of type {:?} as a datetime",
+            value.get_type()
+        ))
+    })?;
+    let right = coerce_datetime(spec).ok_or_else(|| {
+        Error::custom(format!(
+            "Cannot interpret value of type {:?} as a datetime",
+            spec.get_type()
+        ))
+    })?;
+    Ok(left.cmp(&right))
+}
+
+/// Aggregation applied to the records that fall into a bucket
+///
+/// Every variant except `Count` carries a JSON pointer selecting the field to
+/// aggregate within each record. Records where the pointer does not resolve
+/// (or, for numeric aggregations, resolves to a non-number) are skipped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aggregation<'p> {
+    /// Number of records in the bucket
+    Count,
+    /// Sum of the numeric field, kept as an integer while all inputs are integers and the
+    /// total fits in an `i64`; falls back to a float otherwise
+    Sum(&'p str),
+    /// Arithmetic mean of the numeric field, as a float
+    Mean(&'p str),
+    /// Smallest value of the field
+    Min(&'p str),
+    /// Largest value of the field
+    Max(&'p str),
+    /// Field value of the earliest record in the bucket
+    First(&'p str),
+    /// Field value of the latest record in the bucket
+    Last(&'p str),
+}
+
+/// How buckets that received no records are filled by [`resample`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FillPolicy {
+    /// Empty buckets hold null
+    Null,
+    /// Empty buckets repeat the value of the preceding bucket (null if there is none)
+    Previous,
+    /// Empty buckets hold the integer zero
+    Zero,
+}
+
+/// Largest number of buckets [`resample`] produces, gaps included
+///
+/// Resampling a few records that lie far apart with a small interval would
+/// otherwise allocate a bucket for every step in between.
+pub const MAX_RESAMPLE_BUCKETS: usize = 1_000_000;
+
+/// Aggregates a group of records into a single value
+///
+/// This is the kernel used by [`resample`] for each bucket, exposed so that callers
+/// grouping records by other means can reuse the same semantics.
+///
+/// # Returns
+///
+/// The aggregated value, or Null when no record contributes to the aggregation.
+/// `Count` always returns an integer.
+///
+/// # Example
+///
+/// ```
+/// # use datavalue_rs::{operations::{self, Aggregation}, Bump, from_str};
+/// let arena = Bump::new();
+/// let records = from_str(&arena, r#"[{"v": 1}, {"v": 2.5}, {"v": "skip"}]"#).unwrap();
+/// let records: Vec<_> = records.as_array().unwrap().iter().collect();
+///
+/// assert_eq!(operations::aggregate(&records, Aggregation::Sum("/v")).as_f64(), Some(3.5));
+/// assert_eq!(operations::aggregate(&records, Aggregation::Count).as_i64(), Some(3));
+/// ```
+pub fn aggregate<'a>(records: &[&DataValue<'a>], aggregation: Aggregation) -> DataValue<'a> {
+    let field = |ptr: &str| -> Vec<DataValue<'a>> {
+        records
+            .iter()
+            .filter_map(|record| record.pointer(ptr))
+            .cloned()
+            .collect()
+    };
+    let numbers = |ptr: &str| -> Vec<DataValue<'a>> {
+        field(ptr)
+            .into_iter()
+            .filter(DataValue::is_number)
+            .collect()
+    };
+
+    match aggregation {
+        Aggregation::Count => DataValue::from(records.len()),
+        Aggregation::Sum(ptr) => sum(numbers(ptr)),
+        Aggregation::Mean(ptr) => {
+            let values = numbers(ptr);
+            if values.is_empty() {
+                return DataValue::Null;
+            }
+            let total: f64 = values.iter().filter_map(DataValue::as_f64).sum();
+            DataValue::Number(Number::Float(total / values.len() as f64))
+        }
+        Aggregation::Min(ptr) => extreme(field(ptr), Ordering::Less),
+        Aggregation::Max(ptr) => extreme(field(ptr), Ordering::Greater),
+        Aggregation::First(ptr) => field(ptr).into_iter().next().unwrap_or(DataValue::Null),
+        Aggregation::Last(ptr) => field(ptr).into_iter().last().unwrap_or(DataValue::Null),
+    }
+}
+
+/// Resamples an array of timestamped records into evenly spaced buckets
+///
+/// Each record's timestamp is read through `time_ptr` and interpreted with
+/// [`coerce_datetime`], so DateTime values, date strings and epoch numbers are all
+/// accepted. Buckets are aligned to multiples of `every` since the Unix epoch and
+/// span from the earliest to the latest record without gaps.
+///
+/// # Arguments
+///
+/// * `arena` - The arena allocator to store the resulting buckets
+/// * `records` - An array of objects to resample
+/// * `time_ptr` - JSON pointer to the timestamp inside each record
+/// * `every` - Width of each bucket (at least one millisecond)
+/// * `aggregation` - How the records inside a bucket are combined
+/// * `fill` - How buckets without records are filled
+///
+/// # Returns
+///
+/// A Result containing an array of `{"time": DateTime, "value": ...}` objects in
+/// chronological order, or an Error if `records` is not an array, a timestamp is
+/// missing or unparseable, `every` is too small, or the records span more than
+/// [`MAX_RESAMPLE_BUCKETS`] buckets.
+///
+/// # Example
+///
+/// ```
+/// # use datavalue_rs::{operations::{self, Aggregation, FillPolicy}, Bump, from_str};
+/// # use chrono::Duration;
+/// let arena = Bump::new();
+/// let events = from_str(&arena, r#"[
+///     {"at": "2023-01-01T00:00:10Z", "bytes": 10},
+///     {"at": "2023-01-01T00:00:50Z", "bytes": 5},
+///     {"at": "2023-01-01T00:02:30Z", "bytes": 7}
+/// ]"#).unwrap();
+///
+/// let buckets = operations::resample(
+///     &arena, &events, "/at", Duration::minutes(1), Aggregation::Sum("/bytes"), FillPolicy::Zero,
+/// ).unwrap();
+///
+/// let sums: Vec<_> = buckets.as_array().unwrap().iter().map(|b| b["value"].as_i64()).collect();
+/// assert_eq!(sums, vec![Some(15), Some(0), Some(7)]);
+/// ```
+pub fn resample<'a>(
+    arena: &'a Bump,
+    records: &DataValue<'a>,
+    time_ptr: &str,
+    every: Duration,
+    aggregation: Aggregation,
+    fill: FillPolicy,
+) -> Result<DataValue<'a>> {
+    let items = records.as_array().ok_or_else(|| {
+        Error::expected_type("array", format!("{:?}", records.get_type()).to_lowercase())
+    })?;
+
+    let step = every.num_milliseconds();
+    if step < 1 {
+        return Err(Error::custom(
+            "Resample interval must be at least one millisecond",
+        ));
+    }
+
+    // Group records by bucket index, keeping them in chronological order
+    let mut buckets: BTreeMap<i64, Vec<(i64, &DataValue<'a>)>> = BTreeMap::new();
+    for record in items {
+        let time = record
+            .pointer(time_ptr)
+            .ok_or_else(|| Error::missing_field(time_ptr))?;
+        let dt = coerce_datetime(time).ok_or_else(|| {
+            Error::custom(format!(
+                "Cannot interpret value at {} as a datetime",
+                time_ptr
+            ))
+        })?;
+        let millis = dt.timestamp_millis();
+        buckets
+            .entry(millis.div_euclid(step))
+            .or_default()
+            .push((millis, record));
+    }
+
+    let (first, last) = match (buckets.keys().next(), buckets.keys().next_back()) {
+        (Some(first), Some(last)) => (*first, *last),
+        _ => return Ok(DataValue::Array(&[])),
+    };
+
+    let count = last
+        .checked_sub(first)
+        .and_then(|span| span.checked_add(1))
+        .and_then(|count| usize::try_from(count).ok())
+        .filter(|count| *count <= MAX_RESAMPLE_BUCKETS)
+        .ok_or_else(|| {
+            Error::custom(format!(
+                "Resampling would produce more than {} buckets",
+                MAX_RESAMPLE_BUCKETS
+            ))
+        })?;
+
+    let mut output = Vec::with_capacity(count);
+    let mut previous = DataValue::Null;
+    for index in first..=last {
+        let value = match buckets.get_mut(&index) {
+            Some(group) => {
+                group.sort_by_key(|(millis, _)| *millis);
+                let members: Vec<&DataValue<'a>> = group.iter().map(|(_, r)| *r).collect();
+                aggregate(&members, aggregation)
+            }
+            None if aggregation == Aggregation::Count => DataValue::Number(Number::Integer(0)),
+            None => match fill {
+                FillPolicy::Null => DataValue::Null,
+                FillPolicy::Previous => previous.clone(),
+                FillPolicy::Zero => DataValue::Number(Number::Integer(0)),
+            },
+        };
+        previous = value.clone();
+
+        let start = DateTime::from_timestamp_millis(index * step)
+            .ok_or_else(|| Error::custom("Resample bucket is out of the representable range"))?;
+        let entries =
+            arena.alloc_slice_clone(&[("time", DataValue::DateTime(start)), ("value", value)]);
+        output.push(DataValue::Object(entries));
+    }
+
+    Ok(DataValue::Array(arena.alloc_slice_clone(&output)))
+}
+
+/// Returns true if two intervals share at least one point
+///
+/// Intervals are `{"start", "end"}` objects (see [`helpers::interval`]) treated as
+/// half-open, so intervals that merely touch (`a.end == b.start`) do not overlap.
+/// Numeric bounds are compared numerically; any other bounds are compared as points
+/// in time using [`compare_temporal`].
+///
+/// # Returns
+///
+/// A Result containing whether the intervals overlap, or an Error if either value
+/// is not a well-formed interval.
+///
+/// # Example
+///
+/// ```
+/// # use datavalue_rs::{operations, Bump, from_str};
+/// let arena = Bump::new();
+/// let a = from_str(&arena, r#"{"start": "2023-01-01T09:00:00Z", "end": "2023-01-01T10:00:00Z"}"#).unwrap();
+/// let b = from_str(&arena, r#"{"start": "2023-01-01T09:30:00Z", "end": "2023-01-01T11:00:00Z"}"#).unwrap();
+/// let c = from_str(&arena, r#"{"start": "2023-01-01T10:00:00Z", "end": "2023-01-01T11:00:00Z"}"#).unwrap();
+///
+/// assert!(operations::overlaps(&a, &b).unwrap());
+/// assert!(!operations::overlaps(&a, &c).unwrap());
+/// ```
+pub fn overlaps(a: &DataValue, b: &DataValue) -> Result<bool> {
+    let (a_start, a_end) = interval_bounds(a)?;
+    let (b_start, b_end) = interval_bounds(b)?;
+    Ok(compare_bound(a_start, b_end)?.is_lt() && compare_bound(b_start, a_end)?.is_lt())
+}
+
+/// Returns true if the point lies within the interval
+///
+/// The start bound is inclusive and the end bound exclusive.
+///
+/// # Example
+///
+/// ```
+/// # use datavalue_rs::{operations, helpers, Bump};
+/// let arena = Bump::new();
+/// let hours = helpers::interval(&arena, helpers::int(9), helpers::int(17));
+///
+/// assert!(operations::contains_point(&hours, &helpers::int(9)).unwrap());
+/// assert!(operations::contains_point(&hours, &helpers::float(16.5)).unwrap());
+/// assert!(!operations::contains_point(&hours, &helpers::int(17)).unwrap());
+/// ```
+pub fn contains_point(interval: &DataValue, point: &DataValue) -> Result<bool> {
+    let (start, end) = interval_bounds(interval)?;
+    Ok(compare_bound(start, point)?.is_le() && compare_bound(point, end)?.is_lt())
+}
+
+/// Merges an array of intervals into the minimal set of non-overlapping intervals
+///
+/// Overlapping and touching intervals are coalesced. The result is sorted by start
+/// bound and keeps the original representation of each bound (a string bound stays
+/// a string, an epoch stays a number).
+///
+/// # Arguments
+///
+/// * `arena` - The arena allocator to store the merged intervals
+/// * `intervals` - An array of `{"start", "end"}` objects
+///
+/// # Returns
+///
+/// A Result containing the merged array, or an Error if `intervals` is not an array
+/// of well-formed intervals.
+///
+/// # Example
+///
+/// ```
+/// # use datavalue_rs::{operations, Bump, from_str};
+/// let arena = Bump::new();
+/// let slots = from_str(&arena, r#"[
+///     {"start": 5, "end": 8},
+///     {"start": 1, "end": 3},
+///     {"start": 2, "end": 5},
+///     {"start": 10, "end": 12}
+/// ]"#).unwrap();
+///
+/// let merged = operations::merge_intervals(&arena, &slots).unwrap();
+/// assert_eq!(datavalue_rs::to_string(&merged), r#"[{"start":1,"end":8},{"start":10,"end":12}]"#);
+/// ```
+pub fn merge_intervals<'a>(arena: &'a Bump, intervals: &DataValue<'a>) -> Result<DataValue<'a>> {
+    let items = intervals.as_array().ok_or_else(|| {
+        Error::expected_type(
+            "array",
+            format!("{:?}", intervals.get_type()).to_lowercase(),
+        )
+    })?;
+
+    let mut bounds = Vec::with_capacity(items.len());
+    for item in items {
+        let (start, end) = interval_bounds(item)?;
+        bounds.push((start, end));
+    }
+
+    // Sort by start bound; every pair was already validated as comparable
+    let mut sort_error = None;
+    bounds.sort_by(|a, b| {
+        compare_bound(a.0, b.0).unwrap_or_else(|e| {
+            sort_error.get_or_insert(e);
+            Ordering::Equal
+        })
+    });
+    if let Some(e) = sort_error {
+        return Err(e);
+    }
+
+    let mut merged: Vec<(&DataValue<'a>, &DataValue<'a>)> = Vec::new();
+    for (start, end) in bounds {
+        match merged.last_mut() {
+            Some(current) if compare_bound(start, current.1)?.is_le() => {
+                if compare_bound(end, current.1)?.is_gt() {
+                    current.1 = end;
+                }
+            }
+            _ => merged.push((start, end)),
+        }
+    }
+
+    let output: Vec<DataValue<'a>> = merged
+        .into_iter()
+        .map(|(start, end)| helpers::interval(arena, start.clone(), end.clone()))
+        .collect();
+    Ok(DataValue::Array(arena.alloc_slice_clone(&output)))
+}
+
+/// Converts numeric fields between units using the built-in unit table
+///
+/// This is [`convert_units_with`] using [`BuiltinUnits`], which covers time, byte
+/// and distance units.
+///
+/// # Example
+///
+/// ```
+/// # use datavalue_rs::{operations, Bump, from_str};
+/// let arena = Bump::new();
+/// let payload = from_str(&arena, r#"{"latency": 1500, "disk": {"free": 2}}"#).unwrap();
+///
+/// let normalized = operations::convert_units(
+///     &arena,
+///     &payload,
+///     &[("/latency", "ms", "s"), ("/disk/free", "GiB", "MiB")],
+/// ).unwrap();
+///
+/// assert_eq!(normalized["latency"].as_f64(), Some(1.5));
+/// assert_eq!(normalized["disk"]["free"].as_i64(), Some(2048));
+/// ```
+pub fn convert_units<'a>(
+    arena: &'a Bump,
+    value: &DataValue<'a>,
+    conversions: &[(&str, &str, &str)],
+) -> Result<DataValue<'a>> {
+    convert_units_with(arena, value, conversions, &BuiltinUnits)
+}
+
+/// Converts numeric fields between units using a custom converter
+///
+/// Each conversion is a `(pointer, from, to)` triple. The field at `pointer` is
+/// converted from unit `from` to unit `to`; the rest of the document is shared with
+/// the input. Pointers that do not resolve are skipped, so the same conversion list
+/// can be applied to heterogeneous payloads.
+///
+/// Integer fields are converted with [`UnitConverter::convert_integer`] first and
+/// stay integers when the result is exact; otherwise the field is converted as a
+/// float and kept as an integer only if the converted value is integral.
+///
+/// # Arguments
+///
+/// * `arena` - The arena allocator to store the rebuilt containers
+/// * `value` - The document to convert
+/// * `conversions` - The `(pointer, from, to)` triples to apply, in order
+/// * `converter` - The unit table used to convert each field
+///
+/// # Returns
+///
+/// A Result containing the converted document, or an Error if a targeted field is
+/// not a number or the converter does not know the requested units.
+pub fn convert_units_with<'a, C: UnitConverter + ?Sized>(
+    arena: &'a Bump,
+    value: &DataValue<'a>,
+    conversions: &[(&str, &str, &str)],
+    converter: &C,
+) -> Result<DataValue<'a>> {
+    let mut current = value.clone();
+    for (ptr, from, to) in conversions {
+        let field = match current.pointer(ptr) {
+            Some(field) => field,
+            None => continue,
+        };
+        let number = field.as_f64().ok_or_else(|| {
+            Error::expected_type("number", format!("{:?}", field.get_type()).to_lowercase())
+        })?;
+        let cannot_convert =
+            || Error::custom(format!("Cannot convert {} from {} to {}", ptr, from, to));
+
+        let exact = field
+            .as_i64()
+            .and_then(|integer| converter.convert_integer(integer, from, to));
+        let replacement = match exact {
+            Some(integer) => DataValue::Number(Number::Integer(integer)),
+            None => {
+                let converted = converter
+                    .convert(number, from, to)
+                    .ok_or_else(cannot_convert)?;
+                if field.as_i64().is_some()
+                    && converted.fract() == 0.0
+                    && converted.abs() < i64::MAX as f64
+                {
+                    DataValue::Number(Number::Integer(converted as i64))
+                } else {
+                    DataValue::Number(Number::Float(converted))
+                }
+            }
+        };
+        current = replace_at_pointer(arena, &current, ptr, replacement)?;
+    }
+    Ok(current)
+}
+
+/// Compares two string values in constant time
+///
+/// Use this instead of `==` when a DataValue carries a secret such as an API token
+/// or a signature, so the comparison time does not reveal how many leading bytes
+/// matched. The running time depends only on the length of the inputs; the length
+/// itself is not hidden.
+///
+/// # Returns
+///
+/// True if both values are strings with identical bytes. Non-string values are
+/// never equal under this comparison.
+///
+/// # Example
+///
+/// ```
+/// # use datavalue_rs::{operations, helpers, Bump};
+/// let arena = Bump::new();
+/// let expected = helpers::string(&arena, "s3cr3t-token");
+/// let presented = helpers::string(&arena, "s3cr3t-token");
+/// let forged = helpers::string(&arena, "s3cr3t-tokem");
+///
+/// assert!(operations::secure_equals(&expected, &presented));
+/// assert!(!operations::secure_equals(&expected, &forged));
+/// assert!(!operations::secure_equals(&helpers::int(1), &helpers::int(1)));
+/// ```
+pub fn secure_equals(a: &DataValue, b: &DataValue) -> bool {
+    match (a, b) {
+        (DataValue::String(a), DataValue::String(b)) => {
+            constant_time_eq(a.as_bytes(), b.as_bytes())
+        }
+        _ => false,
+    }
+}
+
+/// A group of identical subtrees found by [`find_duplicates`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Duplicate {
+    /// Structural hash shared by every occurrence
+    pub hash: u64,
+    /// Number of nodes in one occurrence, including the subtree root
+    pub size: usize,
+    /// JSON pointers to every occurrence, in traversal order
+    pub paths: Vec<String>,
+}
+
+impl Duplicate {
+    /// Number of nodes that could be saved by storing the subtree once
+    pub fn redundant_nodes(&self) -> usize {
+        self.size * (self.paths.len() - 1)
+    }
+}
+
+/// Finds subtrees that occur more than once in a document
+///
+/// Subtrees are compared structurally with the same rules as `==`: object key
+/// order does not matter and integers equal floats of the same value. Only the
+/// outermost duplicates are reported; the children of a repeated subtree are not
+/// listed again unless they also repeat elsewhere.
+///
+/// # Arguments
+///
+/// * `value` - The document to scan
+/// * `min_size` - Minimum number of nodes a subtree must have to be reported
+///
+/// # Returns
+///
+/// The duplicate groups, largest redundancy first.
+///
+/// # Example
+///
+/// ```
+/// # use datavalue_rs::{operations, Bump, from_str};
+/// let arena = Bump::new();
+/// let payload = from_str(&arena, r#"{
+///     "orders": [
+///         {"id": 1, "customer": {"name": "Ada", "tier": "gold"}},
+///         {"id": 2, "customer": {"tier": "gold", "name": "Ada"}}
+///     ]
+/// }"#).unwrap();
+///
+/// let duplicates = operations::find_duplicates(&payload, 2);
+/// assert_eq!(duplicates.len(), 1);
+/// assert_eq!(duplicates[0].size, 3);
+/// assert_eq!(
+///     duplicates[0].paths,
+///     vec!["/orders/0/customer", "/orders/1/customer"]
+/// );
+/// ```
+pub fn find_duplicates(value: &DataValue, min_size: usize) -> Vec<Duplicate> {
+    let nodes = structural_hashes(value);
+
+    // Bucket by hash, then split buckets on real equality to rule out collisions
+    let mut buckets: HashMap<u64, Vec<Vec<usize>>> = HashMap::new();
+    for (index, node) in nodes.iter().enumerate() {
+        if node.size < min_size.max(1) {
+            continue;
+        }
+        let groups = buckets.entry(node.hash).or_default();
+        match groups
+            .iter_mut()
+            .find(|group| equals(nodes[group[0]].value, node.value))
+        {
+            Some(group) => group.push(index),
+            None => groups.push(vec![index]),
+        }
+    }
+    let groups: Vec<Vec<usize>> = buckets
+        .into_values()
+        .flatten()
+        .filter(|group| group.len() > 1)
+        .collect();
+
+    // Drop groups whose every occurrence sits inside another reported occurrence
+    let repeated: HashSet<usize> = groups.iter().flatten().copied().collect();
+    // Nodes are in pre-order, so every parent is settled before its children
+    let mut inside_repeated = vec![false; nodes.len()];
+    for (index, node) in nodes.iter().enumerate() {
+        if let Some(parent) = node.parent {
+            inside_repeated[index] = repeated.contains(&parent) || inside_repeated[parent];
+        }
+    }
+
+    let mut duplicates: Vec<Duplicate> = groups
+        .into_iter()
+        .filter(|group| !group.iter().all(|&i| inside_repeated[i]))
+        .map(|mut group| {
+            group.sort_unstable();
+            Duplicate {
+                hash: nodes[group[0]].hash,
+                size: nodes[group[0]].size,
+                paths: group.iter().map(|&i| pointer_of(&nodes, i)).collect(),
+            }
+        })
+        .collect();
+    duplicates.sort_by(|a, b| {
+        b.redundant_nodes()
+            .cmp(&a.redundant_nodes())
+            .then_with(|| a.paths.cmp(&b.paths))
+    });
+    duplicates
+}
+
+/// Options for [`diff_pretty_with_options`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiffOptions {
+    /// Wrap removed lines in red, added lines in green and path headers in cyan
+    /// using ANSI escape codes
+    pub color: bool,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        DiffOptions { color: true }
+    }
+}
+
+/// Renders the differences between two values as colored, unified-diff-like text
+///
+/// Equivalent to [`diff_pretty_with_options`] with the default options.
+pub fn diff_pretty(a: &DataValue, b: &DataValue) -> String {
+    diff_pretty_with_options(a, b, &DiffOptions::default())
+}
+
+/// Renders the differences between two values as unified-diff-like text
+///
+/// Every change is introduced by an `@@ <pointer> @@` header naming the JSON
+/// pointer of the changed value (`(root)` for the values themselves), followed by
+/// a `-` line with the old value and a `+` line with the new one; values only
+/// present on one side get one of the two.
+/// Changes are listed in document order: object entries in the order of `a`,
+/// followed by entries only `b` has in the order of `b`, and array items by index.
+/// Values are compared with the same rules as `==`, so `1` and `1.0` are equal.
+///
+/// # Returns
+///
+/// The rendered diff, or an empty string if the values are equal.
+///
+/// # Example
+///
+/// ```
+/// # use datavalue_rs::{operations::{self, DiffOptions}, Bump, from_str};
+/// let arena = Bump::new();
+/// let before = from_str(&arena, r#"{"name": "Ada", "tags": ["a"], "age": 36}"#).unwrap();
+/// let after = from_str(&arena, r#"{"name": "Ada", "tags": ["a", "b"], "age": 37}"#).unwrap();
+///
+/// let options = DiffOptions { color: false };
+/// assert_eq!(
+///     operations::diff_pretty_with_options(&before, &after, &options),
+///     "--- a\n+++ b\n@@ /tags/1 @@\n+ \"b\"\n@@ /age @@\n- 36\n+ 37\n"
+/// );
+/// assert!(operations::diff_pretty(&before, &before).is_empty());
+/// ```
+pub fn diff_pretty_with_options(a: &DataValue, b: &DataValue, options: &DiffOptions) -> String {
+    let mut body = String::new();
+    let mut path = String::new();
+    render_diff(Some(a), Some(b), &mut path, options, &mut body);
+    if body.is_empty() {
+        return body;
+    }
+    let mut out = String::new();
+    push_diff_line(&mut out, "--- a", RED, options);
+    push_diff_line(&mut out, "+++ b", GREEN, options);
+    out.push_str(&body);
+    out
+}
+
 // Private helper functions
 
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const CYAN: &str = "\x1b[36m";
+
+/// Appends the diff lines for the value at `path`, which is missing on the side
+/// passed as None
+fn render_diff(
+    left: Option<&DataValue>,
+    right: Option<&DataValue>,
+    path: &mut String,
+    options: &DiffOptions,
+    out: &mut String,
+) {
+    match (left, right) {
+        (Some(DataValue::Object(old)), Some(DataValue::Object(new))) => {
+            for (key, value) in old.iter() {
+                let other = new.iter().find(|(k, _)| k == key).map(|(_, v)| v);
+                let len = path.len();
+                path.push('/');
+                path.push_str(&escape_pointer_token(key));
+                render_diff(Some(value), other, path, options, out);
+                path.truncate(len);
+            }
+            for (key, value) in new.iter() {
+                if old.iter().all(|(k, _)| k != key) {
+                    let len = path.len();
+                    path.push('/');
+                    path.push_str(&escape_pointer_token(key));
+                    render_diff(None, Some(value), path, options, out);
+                    path.truncate(len);
+                }
+            }
+        }
+        (Some(DataValue::Array(old)), Some(DataValue::Array(new))) => {
+            for i in 0..old.len().max(new.len()) {
+                let len = path.len();
+                path.push('/');
+                path.push_str(&i.to_string());
+                render_diff(old.get(i), new.get(i), path, options, out);
+                path.truncate(len);
+            }
+        }
+        (Some(old), Some(new)) if equals(old, new) => {}
+        _ => {
+            let header = if path.is_empty() { "(root)" } else { path };
+            push_diff_line(out, &format!("@@ {} @@", header), CYAN, options);
+            if let Some(old) = left {
+                push_diff_line(out, &format!("- {}", crate::to_string(old)), RED, options);
+            }
+            if let Some(new) = right {
+                push_diff_line(out, &format!("+ {}", crate::to_string(new)), GREEN, options);
+            }
+        }
+    }
+}
+
+fn push_diff_line(out: &mut String, line: &str, color: &str, options: &DiffOptions) {
+    if options.color {
+        out.push_str(color);
+        out.push_str(line);
+        out.push_str("\x1b[0m\n");
+    } else {
+        out.push_str(line);
+        out.push('\n');
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let diff = a
+        .iter()
+        .zip(b.iter())
+        .fold(0u8, |acc, (x, y)| acc | (x ^ y));
+    // Keep the optimizer from short-circuiting the fold
+    std::hint::black_box(diff) == 0
+}
+
+/// Returns a copy of `root` with the value at `pointer` replaced, rebuilding only
+/// the containers along the path and sharing every other subtree.
+fn replace_at_pointer<'a>(
+    arena: &'a Bump,
+    root: &DataValue<'a>,
+    pointer: &str,
+    replacement: DataValue<'a>,
+) -> Result<DataValue<'a>> {
+    if pointer.is_empty() {
+        return Ok(replacement);
+    }
+    let rest = pointer
+        .strip_prefix('/')
+        .ok_or_else(|| Error::syntax(format!("Invalid JSON pointer: {}", pointer)))?;
+    let (token, tail) = match rest.find('/') {
+        Some(pos) => (&rest[..pos], &rest[pos..]),
+        None => (rest, ""),
+    };
+    let token = token.replace("~1", "/").replace("~0", "~");
+
+    match root {
+        DataValue::Object(entries) => {
+            let pos = entries
+                .iter()
+                .position(|(k, _)| *k == token)
+                .ok_or_else(|| Error::missing_field(token.as_str()))?;
+            let child = replace_at_pointer(arena, &entries[pos].1, tail, replacement)?;
+            let mut rebuilt = entries.to_vec();
+            rebuilt[pos].1 = child;
+            Ok(DataValue::Object(arena.alloc_slice_clone(&rebuilt)))
+        }
+        DataValue::Array(items) => {
+            let index = token
+                .parse::<usize>()
+                .map_err(|_| Error::syntax(format!("Invalid array index: {}", token)))?;
+            let item = items.get(index).ok_or(Error::out_of_bounds(index))?;
+            let child = replace_at_pointer(arena, item, tail, replacement)?;
+            let mut rebuilt = items.to_vec();
+            rebuilt[index] = child;
+            Ok(DataValue::Array(arena.alloc_slice_clone(&rebuilt)))
+        }
+        other => Err(Error::expected_type(
+            "object or array",
+            format!("{:?}", other.get_type()).to_lowercase(),
+        )),
+    }
+}
+
+fn interval_bounds<'v, 'a>(
+    value: &'v DataValue<'a>,
+) -> Result<(&'v DataValue<'a>, &'v DataValue<'a>)> {
+    if !value.is_object() {
+        return Err(Error::expected_type(
+            "interval object",
+            format!("{:?}", value.get_type()).to_lowercase(),
+        ));
+    }
+    let start = value
+        .get("start")
+        .ok_or_else(|| Error::missing_field("start"))?;
+    let end = value
+        .get("end")
+        .ok_or_else(|| Error::missing_field("end"))?;
+    if compare_bound(start, end)?.is_gt() {
+        return Err(Error::custom("Interval start is after its end"));
+    }
+    Ok((start, end))
+}
+
+fn compare_bound(left: &DataValue, right: &DataValue) -> Result<Ordering> {
+    match (left, right) {
+        (DataValue::Number(_), DataValue::Number(_)) => left
+            .partial_cmp(right)
+            .ok_or_else(|| Error::custom("Cannot compare NaN interval bounds")),
+        _ => compare_temporal(left, right),
+    }
+}
+
+/// Adds numbers with `checked_add`, switching to an f64 total once an input is a float or
+/// the integer total would overflow. Numbers with no f64 value are skipped.
+fn sum<'a>(values: Vec<DataValue<'a>>) -> DataValue<'a> {
+    let mut integer: Option<i64> = Some(0);
+    let mut float = 0.0;
+    for value in values {
+        let value = value.with_parsed_number();
+        if let (Some(total), DataValue::Number(Number::Integer(i))) = (integer, &value) {
+            match total.checked_add(*i) {
+                Some(total) => integer = Some(total),
+                None => {
+                    integer = None;
+                    float = total as f64 + *i as f64;
+                }
+            }
+            continue;
+        }
+        let Some(f) = value.as_f64() else {
+            continue;
+        };
+        if let Some(total) = integer.take() {
+            float = total as f64;
+        }
+        float += f;
+    }
+    match integer {
+        Some(total) => DataValue::Number(Number::Integer(total)),
+        None => DataValue::Number(Number::Float(float)),
+    }
+}
+
+fn extreme<'a>(values: Vec<DataValue<'a>>, wanted: Ordering) -> DataValue<'a> {
+    let mut best: Option<DataValue<'a>> = None;
+    for value in values {
+        best = match best {
+            None => Some(value),
+            Some(current) => match value.partial_cmp(&current) {
+                Some(ordering) if ordering == wanted => Some(value),
+                _ => Some(current),
+            },
+        };
+    }
+    best.unwrap_or(DataValue::Null)
+}
+
+struct HashedNode<'v, 'a> {
+    value: &'v DataValue<'a>,
+    /// Index of the parent node, and the pointer token that leads here from it
+    parent: Option<usize>,
+    token: String,
+    hash: u64,
+    size: usize,
+}
+
+/// Rebuilds the JSON Pointer of a node from its chain of parents
+fn pointer_of(nodes: &[HashedNode<'_, '_>], index: usize) -> String {
+    let mut tokens = Vec::new();
+    let mut current = Some(index);
+    while let Some(i) = current {
+        if nodes[i].parent.is_some() {
+            tokens.push(nodes[i].token.as_str());
+        }
+        current = nodes[i].parent;
+    }
+    tokens
+        .iter()
+        .rev()
+        .fold(String::new(), |mut pointer, token| {
+            pointer.push('/');
+            pointer.push_str(token);
+            pointer
+        })
+}
+
+/// Computes an order-insensitive structural hash and node count for every node
+/// of `value`. Nodes are recorded in pre-order, with `value` first.
+///
+/// Hashes are combined bottom-up: each array or object waits on an explicit
+/// stack until its last child is hashed, so deep input does not consume thread
+/// stack.
+fn structural_hashes<'v, 'a>(value: &'v DataValue<'a>) -> Vec<HashedNode<'v, 'a>> {
+    /// The children of an array or object still to be hashed
+    enum Members<'v, 'a> {
+        Array(std::iter::Enumerate<std::slice::Iter<'v, DataValue<'a>>>),
+        Object(std::slice::Iter<'v, (&'a str, DataValue<'a>)>),
+    }
+
+    /// An array or object whose children are being hashed
+    struct Frame<'v, 'a> {
+        slot: usize,
+        members: Members<'v, 'a>,
+        hasher: DefaultHasher,
+        /// Commutative sum of the object entry hashes, so key order does not matter
+        combined: u64,
+        size: usize,
+        /// Key of the object entry being hashed
+        key: &'a str,
+    }
+
+    let mut nodes: Vec<HashedNode<'v, 'a>> = Vec::new();
+    let mut stack: Vec<Frame<'v, 'a>> = Vec::new();
+    let mut next = Some((value, None, String::new()));
+    while let Some((value, parent, token)) = next.take() {
+        let slot = nodes.len();
+        nodes.push(HashedNode {
+            value,
+            parent,
+            token,
+            hash: 0,
+            size: 0,
+        });
+        let mut hasher = DefaultHasher::new();
+        let mut done = match value {
+            DataValue::Array(items) => {
+                (6u8, items.len()).hash(&mut hasher);
+                stack.push(Frame {
+                    slot,
+                    members: Members::Array(items.iter().enumerate()),
+                    hasher,
+                    combined: 0,
+                    size: 1,
+                    key: "",
+                });
+                None
+            }
+            DataValue::Object(entries) => {
+                (7u8, entries.len()).hash(&mut hasher);
+                stack.push(Frame {
+                    slot,
+                    members: Members::Object(entries.iter()),
+                    hasher,
+                    combined: 0,
+                    size: 1,
+                    key: "",
+                });
+                None
+            }
+            scalar => {
+                let (hash, size) = scalar_hash(scalar);
+                nodes[slot].hash = hash;
+                nodes[slot].size = size;
+                Some((hash, size))
+            }
+        };
+
+        // Hand finished hashes to their parents until a child is left to hash
+        while let Some(frame) = stack.last_mut() {
+            if let Some((child, child_size)) = done.take() {
+                match frame.members {
+                    Members::Array(_) => child.hash(&mut frame.hasher),
+                    Members::Object(_) => {
+                        let mut entry = DefaultHasher::new();
+                        (frame.key, child).hash(&mut entry);
+                        frame.combined = frame.combined.wrapping_add(entry.finish());
+                    }
+                }
+                frame.size += child_size;
+            }
+            let child = match &mut frame.members {
+                Members::Array(items) => items.next().map(|(i, item)| (item, i.to_string())),
+                Members::Object(entries) => entries.next().map(|(key, item)| {
+                    frame.key = key;
+                    (item, escape_pointer_token(key).into_owned())
+                }),
+            };
+            if let Some((item, token)) = child {
+                next = Some((item, Some(frame.slot), token));
+                break;
+            }
+
+            let Some(mut frame) = stack.pop() else {
+                unreachable!("a frame was open");
+            };
+            if let Members::Object(_) = frame.members {
+                frame.combined.hash(&mut frame.hasher);
+            }
+            let hash = frame.hasher.finish();
+            nodes[frame.slot].hash = hash;
+            nodes[frame.slot].size = frame.size;
+            done = Some((hash, frame.size));
+        }
+    }
+    nodes
+}
+
+/// Hashes a value that is not an array or object, returning its hash and node count
+fn scalar_hash(value: &DataValue<'_>) -> (u64, usize) {
+    let mut hasher = DefaultHasher::new();
+    match value {
+        DataValue::Null => 0u8.hash(&mut hasher),
+        DataValue::Bool(b) => (1u8, b).hash(&mut hasher),
+        DataValue::Number(n) => {
+            // Integers and integral floats must hash alike since they compare equal
+            let f = DataValue::Number(*n).as_f64().unwrap_or(f64::NAN);
+            let f = if f == 0.0 { 0.0 } else { f };
+            (2u8, f.to_bits()).hash(&mut hasher);
+        }
+        DataValue::String(s) => (3u8, s).hash(&mut hasher),
+        DataValue::DateTime(dt) => (4u8, dt).hash(&mut hasher),
+        DataValue::Duration(d) => (5u8, d).hash(&mut hasher),
+        DataValue::Raw(_) => {
+            // Hash what the text parses to, so that it matches an equal parsed value
+            let arena = Bump::new();
+            let parsed = value.parse_raw(&arena).unwrap_or(DataValue::Null);
+            let root = &structural_hashes(&parsed)[0];
+            return (root.hash, root.size);
+        }
+        DataValue::Array(_) | DataValue::Object(_) => {
+            unreachable!("containers are hashed by structural_hashes")
+        }
+    }
+    (hasher.finish(), 1)
+}
+
 fn equals(left: &DataValue, right: &DataValue) -> bool {
+    // Pairs still to compare; arrays and objects queue their children instead of
+    // recursing, so deeply nested values cannot overflow the stack
+    let mut pending = vec![(left, right)];
+    while let Some(pair) = pending.pop() {
+        match pair {
+            // Array == Array
+            (DataValue::Array(a), DataValue::Array(b)) => {
+                if a.len() != b.len() {
+                    return false;
+                }
+                pending.extend(a.iter().zip(b.iter()));
+            }
+
+            // Object == Object
+            (DataValue::Object(a), DataValue::Object(b)) => {
+                if a.len() != b.len() {
+                    return false;
+                }
+
+                // For each key in a, find matching key in b and compare values
+                for (a_key, a_val) in a.iter() {
+                    match b.iter().find(|(b_key, _)| a_key == b_key) {
+                        Some((_, b_val)) => pending.push((a_val, b_val)),
+                        None => return false,
+                    }
+                }
+            }
+
+            (left, right) => {
+                if !scalar_equals(left, right) {
+                    return false;
+                }
+            }
+        }
+    }
+    true
+}
+
+/// Compares two values that are not both arrays or both objects
+fn scalar_equals(left: &DataValue, right: &DataValue) -> bool {
     match (left, right) {
         // Null == Null
         (DataValue::Null, DataValue::Null) => true,
@@ -283,6 +1437,15 @@ fn equals(left: &DataValue, right: &DataValue) -> bool {
         (DataValue::Bool(a), DataValue::Bool(b)) => a == b,
 
         // Number == Number (allowing integer/float comparison)
+        #[cfg(feature = "bignum")]
+        (DataValue::Number(a), DataValue::Number(b)) if a.is_big() || b.is_big() => {
+            crate::bignum::compare(*a, *b) == Some(std::cmp::Ordering::Equal)
+        }
+        (DataValue::Number(a @ Number::Raw(_)), DataValue::Number(b))
+        | (DataValue::Number(a), DataValue::Number(b @ Number::Raw(_))) => scalar_equals(
+            &DataValue::Number(a.parsed()),
+            &DataValue::Number(b.parsed()),
+        ),
         (DataValue::Number(Number::Integer(a)), DataValue::Number(Number::Integer(b))) => a == b,
         (DataValue::Number(Number::Float(a)), DataValue::Number(Number::Float(b))) => a == b,
         (DataValue::Number(Number::Integer(a)), DataValue::Number(Number::Float(b))) => {
@@ -295,36 +1458,21 @@ fn equals(left: &DataValue, right: &DataValue) -> bool {
         // String == String
         (DataValue::String(a), DataValue::String(b)) => a == b,
 
-        // Array == Array
-        (DataValue::Array(a), DataValue::Array(b)) => {
-            if a.len() != b.len() {
-                return false;
-            }
-            a.iter()
-                .zip(b.iter())
-                .all(|(a_elem, b_elem)| equals(a_elem, b_elem))
-        }
-
-        // Object == Object
-        (DataValue::Object(a), DataValue::Object(b)) => {
-            if a.len() != b.len() {
-                return false;
-            }
-
-            // For each key in a, find matching key in b and compare values
-            a.iter().all(|(a_key, a_val)| {
-                b.iter()
-                    .find(|(b_key, _)| a_key == b_key)
-                    .is_some_and(|(_, b_val)| equals(a_val, b_val))
-            })
-        }
-
         // DateTime == DateTime
         (DataValue::DateTime(a), DataValue::DateTime(b)) => a == b,
 
         // Duration == Duration
         (DataValue::Duration(a), DataValue::Duration(b)) => a == b,
 
+        // Raw values compare by what they parse to
+        (DataValue::Raw(_), _) | (_, DataValue::Raw(_)) => {
+            let arena = Bump::new();
+            match (left.parse_raw(&arena), right.parse_raw(&arena)) {
+                (Ok(left), Ok(right)) => equals(&left, &right),
+                _ => false,
+            }
+        }
+
         // Different types are never equal
         _ => false,
     }
@@ -333,6 +1481,15 @@ fn equals(left: &DataValue, right: &DataValue) -> bool {
 fn less_than(left: &DataValue, right: &DataValue) -> Result<bool> {
     match (left, right) {
         // Number < Number
+        #[cfg(feature = "bignum")]
+        (DataValue::Number(a), DataValue::Number(b)) if a.is_big() || b.is_big() => {
+            Ok(crate::bignum::compare(*a, *b) == Some(std::cmp::Ordering::Less))
+        }
+        (DataValue::Number(a @ Number::Raw(_)), DataValue::Number(b))
+        | (DataValue::Number(a), DataValue::Number(b @ Number::Raw(_))) => less_than(
+            &DataValue::Number(a.parsed()),
+            &DataValue::Number(b.parsed()),
+        ),
         (DataValue::Number(Number::Integer(a)), DataValue::Number(Number::Integer(b))) => Ok(a < b),
         (DataValue::Number(Number::Float(a)), DataValue::Number(Number::Float(b))) => Ok(a < b),
         (DataValue::Number(Number::Integer(a)), DataValue::Number(Number::Float(b))) => {
@@ -356,7 +1513,9 @@ fn less_than(left: &DataValue, right: &DataValue) -> Result<bool> {
 
 #[cfg(test)]
 mod tests {
+    use super::*;
     use crate::helpers;
+    use bumpalo::Bump;
 
     #[test]
     fn test_operator_add() {
@@ -411,4 +1570,382 @@ mod tests {
         assert!(a <= c);
         assert!(a >= c);
     }
+
+    #[test]
+    fn test_compare_temporal() {
+        let arena = Bump::new();
+        let created = helpers::datetime("2023-06-15T12:00:00Z").unwrap();
+
+        // DateTime against strings
+        let before = helpers::string(&arena, "2023-01-01");
+        let after = helpers::string(&arena, "2024-01-01T00:00:00+02:00");
+        assert_eq!(
+            compare_temporal(&created, &before).unwrap(),
+            Ordering::Greater
+        );
+        assert_eq!(compare_temporal(&created, &after).unwrap(), Ordering::Less);
+
+        // String against epoch seconds, in both directions
+        let epoch = helpers::int(1686830400);
+        let text = helpers::string(&arena, "2023-06-15T12:00:00Z");
+        assert_eq!(compare_temporal(&text, &epoch).unwrap(), Ordering::Equal);
+        assert_eq!(compare_temporal(&epoch, &created).unwrap(), Ordering::Equal);
+
+        // Fractional epochs keep sub-second precision
+        let fractional = helpers::float(1686830400.5);
+        assert_eq!(
+            compare_temporal(&fractional, &epoch).unwrap(),
+            Ordering::Greater
+        );
+
+        // Non-temporal operands are rejected
+        let garbage = helpers::string(&arena, "not a date");
+        assert!(compare_temporal(&created, &garbage).is_err());
+        assert!(compare_temporal(&helpers::boolean(true), &created).is_err());
+    }
+
+    #[test]
+    fn test_aggregate_sum_overflow() {
+        let arena = Bump::new();
+        let sum = |json: &str| {
+            let records = crate::from_str(&arena, json).unwrap();
+            let records: Vec<_> = records.as_array().unwrap().iter().collect();
+            aggregate(&records, Aggregation::Sum("/v"))
+        };
+
+        assert_eq!(sum(r#"[{"v": 1}, {"v": 2}]"#).as_i64(), Some(3));
+        assert_eq!(sum("[]").as_i64(), Some(0));
+
+        let overflowed = sum(r#"[{"v": 9223372036854775807}, {"v": 1}]"#);
+        assert!(matches!(overflowed, DataValue::Number(Number::Float(_))));
+        assert_eq!(overflowed.as_f64(), Some(9223372036854775808.0));
+
+        let mixed = sum(r#"[{"v": -9223372036854775808}, {"v": -1}, {"v": 0.5}]"#);
+        assert_eq!(mixed.as_f64(), Some(-9223372036854775808.5));
+    }
+
+    #[test]
+    fn test_resample_fill_policies() {
+        let arena = Bump::new();
+        let events = crate::from_str(
+            &arena,
+            r#"[
+                {"at": 120, "v": 4},
+                {"at": 0, "v": 1},
+                {"at": 30, "v": 2},
+                {"at": 300, "v": 8}
+            ]"#,
+        )
+        .unwrap();
+
+        let values = |agg, fill| -> Vec<DataValue> {
+            resample(&arena, &events, "/at", Duration::minutes(1), agg, fill)
+                .unwrap()
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|b| b["value"].clone())
+                .collect()
+        };
+
+        let null_filled = values(Aggregation::Sum("/v"), FillPolicy::Null);
+        assert_eq!(null_filled.len(), 6);
+        assert_eq!(null_filled[0].as_i64(), Some(3));
+        assert!(null_filled[1].is_null());
+        assert_eq!(null_filled[2].as_i64(), Some(4));
+        assert_eq!(null_filled[5].as_i64(), Some(8));
+
+        let carried = values(Aggregation::Last("/v"), FillPolicy::Previous);
+        let carried: Vec<_> = carried.iter().map(DataValue::as_i64).collect();
+        assert_eq!(
+            carried,
+            vec![Some(2), Some(2), Some(4), Some(4), Some(4), Some(8)]
+        );
+
+        let counts = values(Aggregation::Count, FillPolicy::Null);
+        let counts: Vec<_> = counts.iter().map(DataValue::as_i64).collect();
+        assert_eq!(
+            counts,
+            vec![Some(2), Some(0), Some(1), Some(0), Some(0), Some(1)]
+        );
+
+        let zeroed = values(Aggregation::Max("/v"), FillPolicy::Zero);
+        assert_eq!(zeroed[3].as_i64(), Some(0));
+    }
+
+    #[test]
+    fn test_resample_errors() {
+        let arena = Bump::new();
+        let events = crate::from_str(&arena, r#"[{"v": 1}]"#).unwrap();
+        let every = Duration::minutes(1);
+
+        assert!(resample(
+            &arena,
+            &events,
+            "/at",
+            every,
+            Aggregation::Count,
+            FillPolicy::Null
+        )
+        .is_err());
+        assert!(resample(
+            &arena,
+            &helpers::int(1),
+            "/at",
+            every,
+            Aggregation::Count,
+            FillPolicy::Null
+        )
+        .is_err());
+        assert!(resample(
+            &arena,
+            &events,
+            "/v",
+            Duration::zero(),
+            Aggregation::Count,
+            FillPolicy::Null
+        )
+        .is_err());
+
+        let empty = crate::from_str(&arena, "[]").unwrap();
+        let result = resample(
+            &arena,
+            &empty,
+            "/at",
+            every,
+            Aggregation::Count,
+            FillPolicy::Null,
+        )
+        .unwrap();
+        assert_eq!(result.as_array().map(|a| a.len()), Some(0));
+
+        // Far-apart records at a fine interval are rejected instead of allocating
+        let sparse =
+            crate::from_str(&arena, r#"[{"at": 0}, {"at": "2023-01-01T00:00:00Z"}]"#).unwrap();
+        let error = resample(
+            &arena,
+            &sparse,
+            "/at",
+            Duration::milliseconds(1),
+            Aggregation::Count,
+            FillPolicy::Null,
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("buckets"));
+    }
+
+    #[test]
+    fn test_interval_operations() {
+        let arena = Bump::new();
+        let a = helpers::interval(&arena, helpers::int(1), helpers::int(5));
+        let b = helpers::interval(&arena, helpers::int(4), helpers::int(9));
+        let c = helpers::interval(&arena, helpers::int(5), helpers::int(6));
+
+        assert!(overlaps(&a, &b).unwrap());
+        assert!(overlaps(&b, &a).unwrap());
+        assert!(!overlaps(&a, &c).unwrap());
+        assert!(contains_point(&a, &helpers::int(1)).unwrap());
+        assert!(!contains_point(&a, &helpers::int(5)).unwrap());
+
+        // Mixed temporal representations
+        let day = helpers::interval(
+            &arena,
+            helpers::string(&arena, "2023-01-01"),
+            helpers::string(&arena, "2023-01-02"),
+        );
+        assert!(contains_point(&day, &helpers::int(1672574400)).unwrap());
+
+        // Malformed intervals
+        let inverted = helpers::interval(&arena, helpers::int(9), helpers::int(1));
+        assert!(overlaps(&a, &inverted).is_err());
+        assert!(contains_point(&helpers::int(3), &helpers::int(3)).is_err());
+    }
+
+    #[test]
+    fn test_merge_intervals() {
+        let arena = Bump::new();
+        let slots = crate::from_str(
+            &arena,
+            r#"[
+                {"start": "2023-01-01T12:00:00Z", "end": "2023-01-01T13:00:00Z"},
+                {"start": "2023-01-01T09:00:00Z", "end": "2023-01-01T10:00:00Z"},
+                {"start": "2023-01-01T10:00:00Z", "end": "2023-01-01T10:30:00Z"}
+            ]"#,
+        )
+        .unwrap();
+
+        let merged = merge_intervals(&arena, &slots).unwrap();
+        let merged = merged.as_array().unwrap();
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[0]["start"].as_str(), Some("2023-01-01T09:00:00Z"));
+        assert_eq!(merged[0]["end"].as_str(), Some("2023-01-01T10:30:00Z"));
+        assert_eq!(merged[1]["start"].as_str(), Some("2023-01-01T12:00:00Z"));
+
+        assert!(merge_intervals(&arena, &helpers::int(1)).is_err());
+    }
+
+    #[test]
+    fn test_convert_units() {
+        let arena = Bump::new();
+        let payload = crate::from_str(
+            &arena,
+            r#"{"samples": [{"d": 3}, {"d": 1.5}], "size": 1024, "label": "x"}"#,
+        )
+        .unwrap();
+
+        let converted = convert_units(
+            &arena,
+            &payload,
+            &[
+                ("/samples/0/d", "km", "m"),
+                ("/samples/1/d", "km", "m"),
+                ("/size", "B", "KiB"),
+                ("/missing", "s", "ms"),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(converted["samples"][0]["d"].as_i64(), Some(3000));
+        assert_eq!(converted["samples"][1]["d"].as_f64(), Some(1500.0));
+        assert_eq!(converted["size"].as_i64(), Some(1));
+        assert_eq!(converted["label"].as_str(), Some("x"));
+
+        // The original document is left untouched
+        assert_eq!(payload["size"].as_i64(), Some(1024));
+
+        let times = crate::from_str(&arena, r#"{"a": 1, "b": 2, "c": 1500}"#).unwrap();
+        let times = convert_units(
+            &arena,
+            &times,
+            &[("/a", "ms", "us"), ("/b", "s", "ms"), ("/c", "us", "ms")],
+        )
+        .unwrap();
+        assert_eq!(times["a"], DataValue::Number(Number::Integer(1000)));
+        assert_eq!(times["b"], DataValue::Number(Number::Integer(2000)));
+        assert_eq!(times["c"], DataValue::Number(Number::Float(1.5)));
+
+        assert!(convert_units(&arena, &payload, &[("/label", "s", "ms")]).is_err());
+        assert!(convert_units(&arena, &payload, &[("/size", "B", "m")]).is_err());
+    }
+
+    #[test]
+    fn test_convert_units_with_custom_converter() {
+        struct Percent;
+        impl UnitConverter for Percent {
+            fn convert(&self, value: f64, from: &str, to: &str) -> Option<f64> {
+                match (from, to) {
+                    ("ratio", "percent") => Some(value * 100.0),
+                    _ => None,
+                }
+            }
+        }
+
+        let arena = Bump::new();
+        let payload = crate::from_str(&arena, r#"{"cpu": 0.25}"#).unwrap();
+        let converted =
+            convert_units_with(&arena, &payload, &[("/cpu", "ratio", "percent")], &Percent)
+                .unwrap();
+        assert_eq!(converted["cpu"].as_f64(), Some(25.0));
+    }
+
+    #[test]
+    fn test_secure_equals() {
+        let arena = Bump::new();
+        let token = helpers::string(&arena, "abc123");
+        assert!(secure_equals(&token, &helpers::string(&arena, "abc123")));
+        assert!(!secure_equals(&token, &helpers::string(&arena, "abc12")));
+        assert!(!secure_equals(&token, &helpers::string(&arena, "xbc123")));
+        assert!(secure_equals(
+            &helpers::string(&arena, ""),
+            &helpers::string(&arena, "")
+        ));
+        assert!(!secure_equals(&token, &helpers::null()));
+    }
+
+    #[test]
+    fn test_find_duplicates() {
+        let arena = Bump::new();
+        let value = crate::from_str(
+            &arena,
+            r#"{
+                "a": {"x": [1, 2], "y": {"k": 1.0}},
+                "b": {"y": {"k": 1}, "x": [1, 2]},
+                "c": [[1, 2], {"k": 2}],
+                "d": "tiny"
+            }"#,
+        )
+        .unwrap();
+
+        let duplicates = find_duplicates(&value, 2);
+        let paths: Vec<&[String]> = duplicates.iter().map(|d| d.paths.as_slice()).collect();
+        // /a and /b repeat as a whole; [1, 2] also repeats outside of them
+        assert_eq!(
+            paths,
+            vec![
+                &["/a".to_string(), "/b".to_string()][..],
+                &["/a/x".to_string(), "/b/x".to_string(), "/c/0".to_string()][..],
+            ]
+        );
+        assert_eq!(duplicates[0].size, 6);
+        assert_eq!(duplicates[0].redundant_nodes(), 6);
+
+        // Scalars only show up with min_size 1
+        let scalars = find_duplicates(&value, 1);
+        assert!(scalars.iter().all(|d| d.size >= 1));
+        assert!(find_duplicates(&value, 7).is_empty());
+        assert!(find_duplicates(&helpers::int(1), 0).is_empty());
+    }
+
+    #[test]
+    fn test_find_duplicates_in_deep_input() {
+        let depth = 50_000;
+        let arena = Bump::new();
+        let input = format!(
+            r#"{}{{"x/y": [1, 2], "z": [1, 2]}}{}"#,
+            r#"{"a":["#.repeat(depth),
+            "]}".repeat(depth)
+        );
+        let value = crate::from_str(&arena, &input).unwrap();
+        let duplicates = find_duplicates(&value, 2);
+        assert_eq!(duplicates.len(), 1);
+        let prefix = "/a/0".repeat(depth);
+        assert_eq!(
+            duplicates[0].paths,
+            vec![format!("{}/x~1y", prefix), format!("{}/z", prefix)]
+        );
+        assert_eq!(duplicates[0].size, 3);
+    }
+
+    #[test]
+    fn test_diff_pretty() {
+        let arena = Bump::new();
+        let before = crate::from_str(
+            &arena,
+            r#"{"id": 1, "a/b": {"x": 1, "y": [1, 2, 3]}, "gone": null, "same": 2.0}"#,
+        )
+        .unwrap();
+        let after = crate::from_str(
+            &arena,
+            r#"{"new": true, "same": 2, "a/b": {"y": [1, "2"], "x": 1}, "id": 1}"#,
+        )
+        .unwrap();
+
+        let plain = DiffOptions { color: false };
+        assert_eq!(
+            diff_pretty_with_options(&before, &after, &plain),
+            "--- a\n+++ b\n\
+             @@ /a~1b/y/1 @@\n- 2\n+ \"2\"\n\
+             @@ /a~1b/y/2 @@\n- 3\n\
+             @@ /gone @@\n- null\n\
+             @@ /new @@\n+ true\n"
+        );
+
+        let colored = diff_pretty(&helpers::int(1), &helpers::null());
+        assert_eq!(
+            colored,
+            "\x1b[31m--- a\x1b[0m\n\x1b[32m+++ b\x1b[0m\n\x1b[36m@@ (root) @@\x1b[0m\n\
+             \x1b[31m- 1\x1b[0m\n\x1b[32m+ null\x1b[0m\n"
+        );
+        assert!(diff_pretty_with_options(&before, &before, &plain).is_empty());
+    }
 }
@@ -0,0 +1,469 @@
+//! OpenTelemetry values and attributes, enabled by the `otel` feature
+//!
+//! Log records carry an [`AnyValue`] body and attributes, and spans carry flat
+//! [`KeyValue`] attributes. This module converts DataValues to and from both, so
+//! a structured excerpt of a payload can be attached to telemetry without
+//! mapping each field by hand.
+//!
+//! [`to_any_value`] keeps the structure: arrays become lists, objects become
+//! maps, and `{"$bytes": base64}` objects become byte arrays. [`to_attributes`]
+//! flattens an object into dotted keys, following the OpenTelemetry naming
+//! convention (`{"http": {"method": "GET"}}` becomes `http.method`), since span
+//! attributes cannot nest.
+//!
+//! OpenTelemetry has no null: null array items and object entries are left out.
+//! Integers and floats outside the `i64` and `f64` ranges are converted to the
+//! nearest `f64`, and DateTimes and durations become strings, as in JSON.
+
+use std::collections::HashMap;
+
+use bumpalo::Bump;
+use opentelemetry::logs::AnyValue;
+use opentelemetry::{Array, Key, KeyValue, StringValue, Value};
+
+use crate::base64;
+use crate::datavalue::{DataValue, Number};
+use crate::error::{Error, Result};
+use crate::helpers::format_duration;
+use crate::ser::write_json;
+
+const BYTES_KEY: &str = "$bytes";
+
+/// Converts a value to an [`AnyValue`], or None for null
+///
+/// # Errors
+///
+/// Returns an error if a `$bytes` object does not hold valid base64, or a raw
+/// value is not valid JSON.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::{from_str, otel, Bump};
+/// use opentelemetry::logs::AnyValue;
+/// use opentelemetry::Key;
+///
+/// let arena = Bump::new();
+/// let value = from_str(&arena, r#"{"ids": [1, 2], "user": null}"#).unwrap();
+/// let Some(AnyValue::Map(map)) = otel::to_any_value(&value).unwrap() else {
+///     panic!("objects become maps");
+/// };
+/// assert_eq!(map.len(), 1);
+/// assert_eq!(
+///     map[&Key::new("ids")],
+///     AnyValue::ListAny(Box::new(vec![AnyValue::Int(1), AnyValue::Int(2)]))
+/// );
+/// ```
+pub fn to_any_value(value: &DataValue<'_>) -> Result<Option<AnyValue>> {
+    /// An array or object whose elements are still being converted
+    enum Frame<'v, 'a> {
+        List(std::slice::Iter<'v, DataValue<'a>>, Vec<AnyValue>),
+        /// The remaining entries, the converted ones and the key of the value
+        /// being converted
+        Map(
+            std::slice::Iter<'v, (&'a str, DataValue<'a>)>,
+            HashMap<Key, AnyValue>,
+            &'a str,
+        ),
+    }
+
+    let mut stack: Vec<Frame<'_, '_>> = Vec::new();
+    let mut next = Some(value);
+    loop {
+        let mut converted = match next.take() {
+            Some(DataValue::Object([(BYTES_KEY, DataValue::String(text))])) => {
+                Some(AnyValue::Bytes(Box::new(base64::decode(text)?)))
+            }
+            Some(DataValue::Array(items)) => {
+                stack.push(Frame::List(items.iter(), Vec::with_capacity(items.len())));
+                None
+            }
+            Some(DataValue::Object(entries)) => {
+                stack.push(Frame::Map(
+                    entries.iter(),
+                    HashMap::with_capacity(entries.len()),
+                    "",
+                ));
+                None
+            }
+            Some(DataValue::Raw(text)) => {
+                let arena = Bump::new();
+                to_any_value(&DataValue::Raw(text).parse_raw(&arena)?)?
+            }
+            Some(DataValue::Bool(b)) => Some(AnyValue::Boolean(*b)),
+            Some(number @ DataValue::Number(_)) => Some(match number.as_i64() {
+                Some(i) => AnyValue::Int(i),
+                None => AnyValue::Double(number.as_f64().unwrap_or(f64::NAN)),
+            }),
+            Some(scalar) => scalar_string(scalar).map(AnyValue::String),
+            None => None,
+        };
+
+        // Hand the converted value to its container, closing finished ones
+        loop {
+            match stack.last_mut() {
+                None => return Ok(converted),
+                Some(Frame::List(items, list)) => {
+                    list.extend(converted.take());
+                    if let Some(item) = items.next() {
+                        next = Some(item);
+                        break;
+                    }
+                }
+                Some(Frame::Map(entries, map, key)) => {
+                    if let Some(value) = converted.take() {
+                        map.insert(Key::new(key.to_string()), value);
+                    }
+                    if let Some((name, value)) = entries.next() {
+                        *key = name;
+                        next = Some(value);
+                        break;
+                    }
+                }
+            }
+            converted = Some(match stack.pop() {
+                Some(Frame::List(_, list)) => AnyValue::ListAny(Box::new(list)),
+                Some(Frame::Map(_, map, _)) => AnyValue::Map(Box::new(map)),
+                None => unreachable!("a frame was open"),
+            });
+        }
+    }
+}
+
+/// Converts an [`AnyValue`] to a DataValue in the arena
+///
+/// Map entries are sorted by key, since maps have no order. Byte arrays become
+/// `{"$bytes": base64}` objects.
+///
+/// # Example
+///
+/// ```
+/// use std::collections::HashMap;
+///
+/// use datavalue_rs::{otel, Bump};
+/// use opentelemetry::logs::AnyValue;
+/// use opentelemetry::Key;
+///
+/// let arena = Bump::new();
+/// let body = AnyValue::Map(Box::new(HashMap::from([
+///     (Key::new("status"), AnyValue::Int(200)),
+///     (Key::new("cached"), AnyValue::Boolean(true)),
+/// ])));
+/// let value = otel::from_any_value(&arena, &body);
+/// assert_eq!(datavalue_rs::to_string(&value), r#"{"cached":true,"status":200}"#);
+/// ```
+pub fn from_any_value<'a>(arena: &'a Bump, value: &AnyValue) -> DataValue<'a> {
+    /// A list or map whose elements are still being converted
+    enum Frame<'v, 'a> {
+        List(std::slice::Iter<'v, AnyValue>, Vec<DataValue<'a>>),
+        /// The remaining entries, the converted ones and the key of the value
+        /// being converted
+        Map(
+            std::vec::IntoIter<(&'v Key, &'v AnyValue)>,
+            Vec<(&'a str, DataValue<'a>)>,
+            &'a str,
+        ),
+    }
+
+    let mut stack: Vec<Frame<'_, 'a>> = Vec::new();
+    let mut next = value;
+    loop {
+        let mut converted = match next {
+            AnyValue::ListAny(items) => {
+                stack.push(Frame::List(items.iter(), Vec::with_capacity(items.len())));
+                None
+            }
+            AnyValue::Map(map) => {
+                let mut entries: Vec<_> = map.iter().collect();
+                entries.sort_by(|a, b| a.0.as_str().cmp(b.0.as_str()));
+                stack.push(Frame::Map(
+                    entries.into_iter(),
+                    Vec::with_capacity(map.len()),
+                    "",
+                ));
+                None
+            }
+            AnyValue::Bytes(bytes) => {
+                let text = DataValue::String(arena.alloc_str(&base64::encode(bytes)));
+                Some(DataValue::Object(
+                    arena.alloc_slice_fill_iter([(BYTES_KEY, text)]),
+                ))
+            }
+            AnyValue::Int(i) => Some(DataValue::Number(Number::Integer(*i))),
+            AnyValue::Double(f) => Some(DataValue::Number(Number::Float(*f))),
+            AnyValue::Boolean(b) => Some(DataValue::Bool(*b)),
+            AnyValue::String(s) => Some(DataValue::String(arena.alloc_str(s.as_str()))),
+            _ => Some(DataValue::Null),
+        };
+
+        loop {
+            match stack.last_mut() {
+                None => return converted.expect("a value was converted"),
+                Some(Frame::List(items, list)) => {
+                    list.extend(converted.take());
+                    if let Some(item) = items.next() {
+                        next = item;
+                        break;
+                    }
+                }
+                Some(Frame::Map(entries, converted_entries, key)) => {
+                    if let Some(value) = converted.take() {
+                        converted_entries.push((key, value));
+                    }
+                    if let Some((name, value)) = entries.next() {
+                        *key = arena.alloc_str(name.as_str());
+                        next = value;
+                        break;
+                    }
+                }
+            }
+            converted = Some(match stack.pop() {
+                Some(Frame::List(_, list)) => DataValue::Array(arena.alloc_slice_fill_iter(list)),
+                Some(Frame::Map(_, entries, _)) => {
+                    DataValue::Object(arena.alloc_slice_fill_iter(entries))
+                }
+                None => unreachable!("a frame was open"),
+            });
+        }
+    }
+}
+
+/// Flattens an object into span attributes with dotted keys
+///
+/// Nested objects contribute their entries under `parent.child` keys. Arrays
+/// whose items are all booleans, all integers, all floats or all strings become
+/// array attributes; other arrays are written as JSON text, since attribute
+/// arrays must be homogeneous. Null entries are left out.
+///
+/// # Errors
+///
+/// Returns an error if `value` is not an object, or a raw value is not valid JSON.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::{from_str, otel, Bump};
+/// use opentelemetry::{KeyValue, Value};
+///
+/// let arena = Bump::new();
+/// let value = from_str(&arena, r#"{"http": {"method": "GET", "status": 200}, "tags": ["a", 1]}"#).unwrap();
+/// let attributes = otel::to_attributes(&value).unwrap();
+/// assert_eq!(attributes, [
+///     KeyValue::new("http.method", "GET"),
+///     KeyValue::new("http.status", 200),
+///     KeyValue::new("tags", r#"["a",1]"#),
+/// ]);
+/// ```
+pub fn to_attributes(value: &DataValue<'_>) -> Result<Vec<KeyValue>> {
+    let arena = Bump::new();
+    let value = match value {
+        DataValue::Raw(text) => DataValue::Raw(text).parse_raw(&arena)?,
+        value => value.clone(),
+    };
+    let DataValue::Object(entries) = value else {
+        return Err(Error::expected_type(
+            "object",
+            format!("{:?}", value.get_type()).to_lowercase(),
+        ));
+    };
+
+    let mut attributes = Vec::new();
+    // Open objects with the key prefix of their entries
+    let mut stack = vec![(String::new(), entries.iter())];
+    while let Some((prefix, entries)) = stack.last_mut() {
+        let Some((name, value)) = entries.next() else {
+            stack.pop();
+            continue;
+        };
+        let key = format!("{}{}", prefix, name);
+        let value = match value {
+            DataValue::Raw(text) => DataValue::Raw(text).parse_raw(&arena)?,
+            value => value.clone(),
+        };
+        let attribute = match value {
+            DataValue::Null => continue,
+            DataValue::Object(entries) => {
+                stack.push((format!("{}.", key), entries.iter()));
+                continue;
+            }
+            DataValue::Array(items) => array_attribute(items)?,
+            DataValue::Bool(b) => Value::Bool(b),
+            DataValue::Number(_) => match value.as_i64() {
+                Some(i) => Value::I64(i),
+                None => Value::F64(value.as_f64().unwrap_or(f64::NAN)),
+            },
+            ref scalar => {
+                Value::String(scalar_string(scalar).expect("strings and times have text"))
+            }
+        };
+        attributes.push(KeyValue::new(key, attribute));
+    }
+    Ok(attributes)
+}
+
+/// Converts span attributes to an object, keeping their keys as they are
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::{otel, Bump};
+/// use opentelemetry::KeyValue;
+///
+/// let arena = Bump::new();
+/// let attributes = [KeyValue::new("http.status", 200), KeyValue::new("retry", false)];
+/// let value = otel::from_attributes(&arena, &attributes);
+/// assert_eq!(value["http.status"].as_i64(), Some(200));
+/// ```
+pub fn from_attributes<'a>(arena: &'a Bump, attributes: &[KeyValue]) -> DataValue<'a> {
+    let entries = attributes.iter().map(|attribute| {
+        let value = match &attribute.value {
+            Value::Bool(b) => DataValue::Bool(*b),
+            Value::I64(i) => DataValue::Number(Number::Integer(*i)),
+            Value::F64(f) => DataValue::Number(Number::Float(*f)),
+            Value::String(s) => DataValue::String(arena.alloc_str(s.as_str())),
+            Value::Array(array) => {
+                let items: Vec<DataValue<'a>> = match array {
+                    Array::Bool(items) => items.iter().map(|b| DataValue::Bool(*b)).collect(),
+                    Array::I64(items) => items
+                        .iter()
+                        .map(|i| DataValue::Number(Number::Integer(*i)))
+                        .collect(),
+                    Array::F64(items) => items
+                        .iter()
+                        .map(|f| DataValue::Number(Number::Float(*f)))
+                        .collect(),
+                    Array::String(items) => items
+                        .iter()
+                        .map(|s| DataValue::String(arena.alloc_str(s.as_str())))
+                        .collect(),
+                    _ => Vec::new(),
+                };
+                DataValue::Array(arena.alloc_slice_fill_iter(items))
+            }
+            _ => DataValue::Null,
+        };
+        (&*arena.alloc_str(attribute.key.as_str()), value)
+    });
+    DataValue::Object(arena.alloc_slice_fill_iter(entries))
+}
+
+/// Returns the text of strings, DateTimes and durations
+fn scalar_string(value: &DataValue<'_>) -> Option<StringValue> {
+    match value {
+        DataValue::String(s) => Some(s.to_string().into()),
+        DataValue::DateTime(dt) => Some(dt.to_rfc3339().into()),
+        DataValue::Duration(dur) => Some(format_duration(dur).into()),
+        _ => None,
+    }
+}
+
+/// Converts an array to a homogeneous array attribute, or to JSON text
+fn array_attribute(items: &[DataValue<'_>]) -> Result<Value> {
+    let array = if let Some(items) = items.iter().map(DataValue::as_bool).collect::<Option<_>>() {
+        Some(Array::Bool(items))
+    } else if let Some(items) = items.iter().map(DataValue::as_i64).collect::<Option<_>>() {
+        Some(Array::I64(items))
+    } else if let Some(items) = items
+        .iter()
+        .map(|item| match item {
+            DataValue::Number(_) if item.as_i64().is_none() => item.as_f64(),
+            _ => None,
+        })
+        .collect::<Option<_>>()
+    {
+        Some(Array::F64(items))
+    } else {
+        items
+            .iter()
+            .map(scalar_string)
+            .collect::<Option<_>>()
+            .map(Array::String)
+    };
+    match array {
+        Some(array) => Ok(Value::Array(array)),
+        None => {
+            let mut text = Vec::new();
+            write_json(&DataValue::Array(items), &mut text)?;
+            Ok(Value::String(
+                String::from_utf8(text)
+                    .expect("JSON output is UTF-8")
+                    .into(),
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_str;
+
+    #[test]
+    fn test_any_value() {
+        let arena = Bump::new();
+        let text =
+            r#"{"a": [1, -2.5, "x", true, [], {}], "b": {"c": {"$bytes": "AAE="}}, "n": null}"#;
+        let value = from_str(&arena, text).unwrap();
+        let any = to_any_value(&value).unwrap().unwrap();
+        let AnyValue::Map(map) = &any else {
+            panic!("expected a map, got {:?}", any);
+        };
+        assert!(!map.contains_key(&Key::new("n")));
+        let AnyValue::Map(b) = &map[&Key::new("b")] else {
+            panic!("expected a map");
+        };
+        assert_eq!(b[&Key::new("c")], AnyValue::Bytes(Box::new(vec![0, 1])));
+
+        let back = from_any_value(&arena, &any);
+        let expected = from_str(
+            &arena,
+            r#"{"a": [1, -2.5, "x", true, [], {}], "b": {"c": {"$bytes": "AAE="}}}"#,
+        )
+        .unwrap();
+        assert_eq!(back, expected);
+
+        assert_eq!(to_any_value(&DataValue::Null).unwrap(), None);
+        let list = from_str(&arena, "[null, 1, null]").unwrap();
+        assert_eq!(
+            to_any_value(&list).unwrap(),
+            Some(AnyValue::ListAny(Box::new(vec![AnyValue::Int(1)])))
+        );
+        assert_eq!(
+            to_any_value(&DataValue::Raw(r#"{"k": "v"}"#)).unwrap(),
+            to_any_value(&from_str(&arena, r#"{"k": "v"}"#).unwrap()).unwrap()
+        );
+        let bad = from_str(&arena, r#"{"$bytes": "!"}"#).unwrap();
+        assert!(to_any_value(&bad).is_err());
+    }
+
+    #[test]
+    fn test_attributes() {
+        let arena = Bump::new();
+        let text = r#"{
+            "service": {"name": "api", "instance": {"id": 7}},
+            "flags": [true, false], "ids": [1, 2], "ratios": [0.5, 1.5], "names": ["a"],
+            "mixed": [1, 2.5], "nested": [[1]], "empty": [], "skip": null, "dotted.key": 1
+        }"#;
+        let value = from_str(&arena, text).unwrap();
+        let attributes = to_attributes(&value).unwrap();
+        let expected = [
+            KeyValue::new("service.name", "api"),
+            KeyValue::new("service.instance.id", 7),
+            KeyValue::new("flags", Value::Array(vec![true, false].into())),
+            KeyValue::new("ids", Value::Array(vec![1, 2].into())),
+            KeyValue::new("ratios", Value::Array(vec![0.5, 1.5].into())),
+            KeyValue::new("names", Value::Array(vec![StringValue::from("a")].into())),
+            KeyValue::new("mixed", "[1,2.5]"),
+            KeyValue::new("nested", "[[1]]"),
+            KeyValue::new("empty", Value::Array(Vec::<bool>::new().into())),
+            KeyValue::new("dotted.key", 1),
+        ];
+        assert_eq!(attributes, expected);
+
+        let object = from_attributes(&arena, &attributes);
+        assert_eq!(object["service.instance.id"].as_i64(), Some(7));
+        assert_eq!(object["ratios"][1].as_f64(), Some(1.5));
+        assert_eq!(object["mixed"].as_str(), Some("[1,2.5]"));
+        assert!(to_attributes(&from_str(&arena, "[1]").unwrap()).is_err());
+    }
+}
@@ -0,0 +1,646 @@
+//! JSON Patch and JSON Merge Patch with copy-on-write sharing
+//!
+//! [`apply_patch`] applies an RFC 6902 JSON Patch and [`merge_patch`] an RFC 7396
+//! merge patch. Values in the arena are never mutated, so both build a new
+//! document: only the arrays and objects along the modified paths are copied, and
+//! every untouched subtree, key and string is shared with the source document. The
+//! `_with_stats` variants report how many bytes were copied and how many shared.
+
+use std::collections::HashSet;
+
+use bumpalo::Bump;
+
+use crate::access::unescape_pointer_token;
+use crate::datavalue::{DataValue, Number};
+use crate::error::{Error, Result};
+
+/// How much of a patched document was copied and how much shared
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PatchStats {
+    /// Bytes allocated in the arena for the arrays and objects rebuilt along
+    /// modified paths
+    pub bytes_copied: usize,
+    /// Bytes of arrays, objects, keys and strings the result references from the
+    /// source document and the patch without copying them
+    pub bytes_shared: usize,
+}
+
+/// Applies a JSON Patch (RFC 6902) to a document
+///
+/// The patch is an array of operations (`add`, `remove`, `replace`, `move`, `copy`
+/// and `test`), applied in order. If any operation fails, an error naming it is
+/// returned; the target itself is never modified.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::{from_str, patch, Bump};
+///
+/// let arena = Bump::new();
+/// let doc = from_str(&arena, r#"{"user": {"name": "ada"}, "log": [1, 2, 3]}"#).unwrap();
+/// let ops = from_str(&arena, r#"[
+///     {"op": "test", "path": "/user/name", "value": "ada"},
+///     {"op": "add", "path": "/user/admin", "value": true}
+/// ]"#).unwrap();
+///
+/// let patched = patch::apply_patch(&arena, &doc, &ops).unwrap();
+/// assert_eq!(patched["user"]["admin"].as_bool(), Some(true));
+/// // The untouched array is the same slice, not a copy
+/// assert!(std::ptr::eq(
+///     patched["log"].as_array().unwrap(),
+///     doc["log"].as_array().unwrap()
+/// ));
+/// ```
+pub fn apply_patch<'a>(
+    arena: &'a Bump,
+    target: &DataValue<'a>,
+    patch: &DataValue<'a>,
+) -> Result<DataValue<'a>> {
+    apply_patch_with_stats(arena, target, patch).map(|(value, _)| value)
+}
+
+/// Applies a JSON Patch and reports how much of the result was copied
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::{from_str, patch, Bump};
+///
+/// let arena = Bump::new();
+/// let doc = from_str(&arena, r#"{"a": {"b": 1}, "big": ["lots", "of", "data"]}"#).unwrap();
+/// let ops = from_str(&arena, r#"[{"op": "replace", "path": "/a/b", "value": 2}]"#).unwrap();
+///
+/// let (patched, stats) = patch::apply_patch_with_stats(&arena, &doc, &ops).unwrap();
+/// assert_eq!(patched["a"]["b"].as_i64(), Some(2));
+/// assert!(stats.bytes_shared > 0);
+/// ```
+pub fn apply_patch_with_stats<'a>(
+    arena: &'a Bump,
+    target: &DataValue<'a>,
+    patch: &DataValue<'a>,
+) -> Result<(DataValue<'a>, PatchStats)> {
+    let operations = patch.as_array().ok_or_else(|| {
+        Error::expected_type("array", format!("{:?}", patch.get_type()).to_lowercase())
+    })?;
+    let mut patcher = Patcher::new(arena);
+    let mut root = target.clone();
+    for (i, operation) in operations.iter().enumerate() {
+        root = patcher
+            .apply(&root, operation)
+            .map_err(|e| Error::custom(format!("patch operation {}: {}", i, e)))?;
+    }
+    let stats = patcher.stats(&root);
+    Ok((root, stats))
+}
+
+/// Applies a JSON Merge Patch (RFC 7396) to a document
+///
+/// Object members of the patch are merged into the target, `null` members remove
+/// the key, and any other patch value replaces the target outright.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::{from_str, patch, Bump};
+///
+/// let arena = Bump::new();
+/// let doc = from_str(&arena, r#"{"title": "Hi", "author": {"name": "ada", "email": "a@x"}}"#).unwrap();
+/// let changes = from_str(&arena, r#"{"title": "Hello", "author": {"email": null}}"#).unwrap();
+///
+/// let merged = patch::merge_patch(&arena, &doc, &changes);
+/// assert_eq!(merged["title"].as_str(), Some("Hello"));
+/// assert!(merged["author"].get("email").is_none());
+/// assert_eq!(merged["author"]["name"].as_str(), Some("ada"));
+/// ```
+pub fn merge_patch<'a>(
+    arena: &'a Bump,
+    target: &DataValue<'a>,
+    patch: &DataValue<'a>,
+) -> DataValue<'a> {
+    merge_patch_with_stats(arena, target, patch).0
+}
+
+/// Applies a JSON Merge Patch and reports how much of the result was copied
+pub fn merge_patch_with_stats<'a>(
+    arena: &'a Bump,
+    target: &DataValue<'a>,
+    patch: &DataValue<'a>,
+) -> (DataValue<'a>, PatchStats) {
+    let mut patcher = Patcher::new(arena);
+    let merged = patcher.merge(target, patch);
+    let stats = patcher.stats(&merged);
+    (merged, stats)
+}
+
+/// A change to one member of an array or object
+enum Edit<'a> {
+    Add(DataValue<'a>),
+    Remove,
+    Replace(DataValue<'a>),
+}
+
+/// Builds patched documents, copying containers only along modified paths
+struct Patcher<'a> {
+    arena: &'a Bump,
+    /// Addresses of the slices allocated by this patcher
+    fresh: HashSet<usize>,
+    copied: usize,
+}
+
+impl<'a> Patcher<'a> {
+    fn new(arena: &'a Bump) -> Self {
+        Patcher {
+            arena,
+            fresh: HashSet::new(),
+            copied: 0,
+        }
+    }
+
+    fn array(&mut self, items: Vec<DataValue<'a>>) -> DataValue<'a> {
+        let slice = self.arena.alloc_slice_clone(&items);
+        self.copied += size_of_val(slice);
+        if !slice.is_empty() {
+            self.fresh.insert(slice.as_ptr() as usize);
+        }
+        DataValue::Array(slice)
+    }
+
+    fn object(&mut self, entries: Vec<(&'a str, DataValue<'a>)>) -> DataValue<'a> {
+        let slice = self.arena.alloc_slice_clone(&entries);
+        self.copied += size_of_val(slice);
+        if !slice.is_empty() {
+            self.fresh.insert(slice.as_ptr() as usize);
+        }
+        DataValue::Object(slice)
+    }
+
+    /// Applies one JSON Patch operation to `root`
+    fn apply(&mut self, root: &DataValue<'a>, operation: &DataValue<'a>) -> Result<DataValue<'a>> {
+        let field = |name: &str| {
+            operation
+                .get(name)
+                .ok_or_else(|| Error::missing_field(name))
+        };
+        let pointer = |name: &str| -> Result<&'a str> {
+            let value = field(name)?;
+            value.as_str().ok_or_else(|| {
+                Error::expected_type("string", format!("{:?}", value.get_type()).to_lowercase())
+            })
+        };
+        let op = pointer("op")?;
+        let path = pointer("path")?;
+        match op {
+            "add" => self.edit(root, path, Edit::Add(field("value")?.clone())),
+            "remove" => self.edit(root, path, Edit::Remove),
+            "replace" => self.edit(root, path, Edit::Replace(field("value")?.clone())),
+            "move" => {
+                let from = pointer("from")?;
+                if path.len() > from.len()
+                    && path.starts_with(from)
+                    && path.as_bytes()[from.len()] == b'/'
+                {
+                    return Err(Error::custom(format!(
+                        "cannot move {} into its own child {}",
+                        from, path
+                    )));
+                }
+                let value = resolve(root, from)?.clone();
+                let removed = self.edit(root, from, Edit::Remove)?;
+                self.edit(&removed, path, Edit::Add(value))
+            }
+            "copy" => {
+                let value = resolve(root, pointer("from")?)?.clone();
+                self.edit(root, path, Edit::Add(value))
+            }
+            "test" => {
+                if *resolve(root, path)? == *field("value")? {
+                    Ok(root.clone())
+                } else {
+                    Err(Error::custom(format!("test failed at {}", path)))
+                }
+            }
+            other => Err(Error::custom(format!("unknown operation {}", other))),
+        }
+    }
+
+    /// Applies `edit` to the member `path` points to, rebuilding its ancestors
+    fn edit(&mut self, root: &DataValue<'a>, path: &str, edit: Edit<'a>) -> Result<DataValue<'a>> {
+        let tokens = parse_pointer(path)?;
+        let Some((last, parents)) = tokens.split_last() else {
+            return match edit {
+                Edit::Add(value) | Edit::Replace(value) => Ok(value),
+                Edit::Remove => Err(Error::custom("cannot remove the whole document")),
+            };
+        };
+        // The containers from the root down to the parent of the edited member
+        let mut chain = vec![root.clone()];
+        for token in parents {
+            let parent = &chain[chain.len() - 1];
+            let child = member(parent, token).ok_or_else(|| missing(path))?.clone();
+            chain.push(child);
+        }
+        let parent = chain.pop().unwrap_or(DataValue::Null);
+        let mut value = self.edit_member(&parent, last, edit, path)?;
+        for (container, token) in chain.iter().zip(parents).rev() {
+            value = match container {
+                DataValue::Array(items) => {
+                    let mut items = items.to_vec();
+                    if let Some(index) = parse_index(token) {
+                        items[index] = value;
+                    }
+                    self.array(items)
+                }
+                DataValue::Object(entries) => {
+                    let mut entries = entries.to_vec();
+                    if let Some(entry) = entries.iter_mut().find(|(key, _)| key == token) {
+                        entry.1 = value;
+                    }
+                    self.object(entries)
+                }
+                _ => unreachable!("the chain holds only arrays and objects"),
+            };
+        }
+        Ok(value)
+    }
+
+    /// Applies `edit` to the member `token` of `parent`, returning the new parent
+    fn edit_member(
+        &mut self,
+        parent: &DataValue<'a>,
+        token: &str,
+        edit: Edit<'a>,
+        path: &str,
+    ) -> Result<DataValue<'a>> {
+        match parent {
+            DataValue::Object(entries) => {
+                let position = entries.iter().position(|(key, _)| *key == token);
+                let mut entries = entries.to_vec();
+                match (edit, position) {
+                    (Edit::Add(value) | Edit::Replace(value), Some(i)) => entries[i].1 = value,
+                    (Edit::Add(value), None) => {
+                        let key = self.arena.alloc_str(token);
+                        self.copied += key.len();
+                        entries.push((key, value));
+                    }
+                    (Edit::Remove, Some(i)) => {
+                        entries.remove(i);
+                    }
+                    (Edit::Remove | Edit::Replace(_), None) => return Err(missing(path)),
+                }
+                Ok(self.object(entries))
+            }
+            DataValue::Array(items) => {
+                let len = items.len();
+                let index = match token {
+                    "-" if matches!(edit, Edit::Add(_)) => len,
+                    _ => parse_index(token).ok_or_else(|| missing(path))?,
+                };
+                let mut items = items.to_vec();
+                match edit {
+                    Edit::Add(value) if index <= len => items.insert(index, value),
+                    Edit::Replace(value) if index < len => items[index] = value,
+                    Edit::Remove if index < len => {
+                        items.remove(index);
+                    }
+                    _ => return Err(Error::out_of_bounds(index)),
+                }
+                Ok(self.array(items))
+            }
+            _ => Err(missing(path)),
+        }
+    }
+
+    /// Merges a merge patch into `target`, copying only the objects it changes
+    ///
+    /// Nested patch objects are merged on an explicit stack, so the depth of the
+    /// patch does not consume thread stack.
+    fn merge(&mut self, target: &DataValue<'a>, patch: &DataValue<'a>) -> DataValue<'a> {
+        /// An object being merged, with the entry waiting on the frame above it
+        struct Frame<'a> {
+            target: DataValue<'a>,
+            changes: std::slice::Iter<'a, (&'a str, DataValue<'a>)>,
+            /// Copied from the target's entries on the first change
+            merged: Option<Vec<(&'a str, DataValue<'a>)>>,
+            /// Key, position and current value of the entry being merged above
+            pending: Option<(&'a str, Option<usize>, Option<DataValue<'a>>)>,
+        }
+
+        impl<'a> Frame<'a> {
+            fn new(target: DataValue<'a>, changes: &'a [(&'a str, DataValue<'a>)]) -> Self {
+                Frame {
+                    target,
+                    changes: changes.iter(),
+                    merged: None,
+                    pending: None,
+                }
+            }
+
+            fn entries(&self) -> &[(&'a str, DataValue<'a>)] {
+                match (&self.merged, &self.target) {
+                    (Some(merged), _) => merged,
+                    (None, DataValue::Object(entries)) => entries,
+                    (None, _) => &[],
+                }
+            }
+
+            /// Sets or, for `None`, removes the entry for `key`, unless the value
+            /// is the container already there
+            fn apply(
+                &mut self,
+                key: &'a str,
+                position: Option<usize>,
+                existing: Option<DataValue<'a>>,
+                value: Option<DataValue<'a>>,
+            ) {
+                if let (Some(value), Some(existing)) = (&value, &existing) {
+                    if same_container(value, existing) {
+                        return;
+                    }
+                }
+                if value.is_none() && position.is_none() {
+                    return;
+                }
+                if self.merged.is_none() {
+                    self.merged = Some(self.entries().to_vec());
+                }
+                let merged = self.merged.get_or_insert_with(Vec::new);
+                match (value, position) {
+                    (Some(value), Some(i)) => merged[i].1 = value,
+                    (Some(value), None) => merged.push((key, value)),
+                    (None, Some(i)) => {
+                        merged.remove(i);
+                    }
+                    (None, None) => {}
+                }
+            }
+        }
+
+        let DataValue::Object(changes) = patch else {
+            return patch.clone();
+        };
+        let mut stack = vec![Frame::new(target.clone(), changes)];
+        loop {
+            let Some(frame) = stack.last_mut() else {
+                unreachable!("the outermost object is returned when it is closed");
+            };
+            if let Some((key, change)) = frame.changes.next() {
+                let position = frame.entries().iter().position(|(k, _)| k == key);
+                let existing = position.map(|i| frame.entries()[i].1.clone());
+                match change {
+                    DataValue::Null => frame.apply(key, position, existing, None),
+                    DataValue::Object(inner) => {
+                        let target = existing.clone().unwrap_or(DataValue::Null);
+                        frame.pending = Some((key, position, existing));
+                        stack.push(Frame::new(target, inner));
+                    }
+                    change => frame.apply(key, position, existing, Some(change.clone())),
+                }
+                continue;
+            }
+
+            let Some(frame) = stack.pop() else {
+                unreachable!("a frame was open");
+            };
+            let value = match frame.merged {
+                Some(merged) => self.object(merged),
+                None if matches!(frame.target, DataValue::Object(_)) => frame.target,
+                None => DataValue::Object(&[]),
+            };
+            let Some(parent) = stack.last_mut() else {
+                return value;
+            };
+            if let Some((key, position, existing)) = parent.pending.take() {
+                parent.apply(key, position, existing, Some(value));
+            }
+        }
+    }
+
+    /// Measures the result: containers this patcher allocated are walked, and
+    /// everything else they reference is shared
+    fn stats(&self, result: &DataValue<'a>) -> PatchStats {
+        let mut shared = 0;
+        let mut pending = vec![result];
+        while let Some(value) = pending.pop() {
+            match value {
+                DataValue::Array(items) if self.fresh.contains(&(items.as_ptr() as usize)) => {
+                    pending.extend(items.iter());
+                }
+                DataValue::Object(entries) if self.fresh.contains(&(entries.as_ptr() as usize)) => {
+                    for (key, value) in entries.iter() {
+                        shared += key.len();
+                        pending.push(value);
+                    }
+                }
+                other => shared += footprint(other),
+            }
+        }
+        PatchStats {
+            bytes_copied: self.copied,
+            bytes_shared: shared,
+        }
+    }
+}
+
+/// Returns true if both values are the same array or object slice
+fn same_container(a: &DataValue<'_>, b: &DataValue<'_>) -> bool {
+    match (a, b) {
+        (DataValue::Array(a), DataValue::Array(b)) => std::ptr::eq(*a, *b),
+        (DataValue::Object(a), DataValue::Object(b)) => std::ptr::eq(*a, *b),
+        _ => false,
+    }
+}
+
+/// Returns the bytes of arrays, objects, keys and strings a value references
+fn footprint(value: &DataValue<'_>) -> usize {
+    let mut total = 0;
+    let mut pending = vec![value];
+    while let Some(value) = pending.pop() {
+        match value {
+            DataValue::Array(items) => {
+                total += size_of_val(*items);
+                pending.extend(items.iter());
+            }
+            DataValue::Object(entries) => {
+                total += size_of_val(*entries);
+                for (key, value) in entries.iter() {
+                    total += key.len();
+                    pending.push(value);
+                }
+            }
+            DataValue::String(s) | DataValue::Number(Number::Raw(s)) | DataValue::Raw(s) => {
+                total += s.len()
+            }
+            _ => {}
+        }
+    }
+    total
+}
+
+/// Splits a JSON pointer into its unescaped reference tokens
+fn parse_pointer(pointer: &str) -> Result<Vec<String>> {
+    if pointer.is_empty() {
+        return Ok(Vec::new());
+    }
+    if !pointer.starts_with('/') {
+        return Err(Error::custom(format!(
+            "invalid JSON pointer {:?}: must start with '/'",
+            pointer
+        )));
+    }
+    Ok(pointer
+        .split('/')
+        .skip(1)
+        .map(|token| unescape_pointer_token(token).into_owned())
+        .collect())
+}
+
+/// Parses an array index, which RFC 6901 writes without leading zeros
+fn parse_index(token: &str) -> Option<usize> {
+    if token.is_empty()
+        || (token.len() > 1 && token.starts_with('0'))
+        || !token.bytes().all(|b| b.is_ascii_digit())
+    {
+        return None;
+    }
+    token.parse().ok()
+}
+
+/// Returns the member `token` of an array or object
+fn member<'v, 'a>(container: &'v DataValue<'a>, token: &str) -> Option<&'v DataValue<'a>> {
+    match container {
+        DataValue::Object(entries) => entries.iter().find(|(key, _)| *key == token).map(|e| &e.1),
+        DataValue::Array(items) => items.get(parse_index(token)?),
+        _ => None,
+    }
+}
+
+/// Returns the value `path` points to
+fn resolve<'v, 'a>(root: &'v DataValue<'a>, path: &str) -> Result<&'v DataValue<'a>> {
+    let mut current = root;
+    for token in parse_pointer(path)? {
+        current = member(current, &token).ok_or_else(|| missing(path))?;
+    }
+    Ok(current)
+}
+
+fn missing(path: &str) -> Error {
+    Error::custom(format!("path {} does not exist", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{from_str, to_string};
+
+    #[test]
+    fn test_apply_patch_operations() {
+        let arena = Bump::new();
+        let doc = from_str(&arena, r#"{"a": {"b": [1, 2]}, "c": "x", "a/b": 0}"#).unwrap();
+        let ops = from_str(
+            &arena,
+            r#"[
+                {"op": "add", "path": "/a/b/1", "value": 9},
+                {"op": "add", "path": "/a/b/-", "value": 3},
+                {"op": "remove", "path": "/c"},
+                {"op": "replace", "path": "/a~1b", "value": 1},
+                {"op": "copy", "from": "/a/b", "path": "/d"},
+                {"op": "move", "from": "/d/0", "path": "/e"},
+                {"op": "test", "path": "/e", "value": 1}
+            ]"#,
+        )
+        .unwrap();
+        let patched = apply_patch(&arena, &doc, &ops).unwrap();
+        assert_eq!(
+            to_string(&patched),
+            r#"{"a":{"b":[1,9,2,3]},"a/b":1,"d":[9,2,3],"e":1}"#
+        );
+        // The source document is unchanged
+        assert_eq!(to_string(&doc), r#"{"a":{"b":[1,2]},"c":"x","a/b":0}"#);
+
+        let root = from_str(&arena, r#"[{"op": "replace", "path": "", "value": 5}]"#).unwrap();
+        assert_eq!(apply_patch(&arena, &doc, &root).unwrap().as_i64(), Some(5));
+    }
+
+    #[test]
+    fn test_apply_patch_errors() {
+        let arena = Bump::new();
+        let doc = from_str(&arena, r#"{"a": [1], "b": {"c": 1}}"#).unwrap();
+        let fails = |ops: &str| {
+            let ops = from_str(&arena, ops).unwrap();
+            apply_patch(&arena, &doc, &ops).unwrap_err().to_string()
+        };
+        assert!(fails(r#"[{"op": "remove", "path": "/x"}]"#).contains("does not exist"));
+        assert!(fails(r#"[{"op": "replace", "path": "/a/1", "value": 0}]"#).contains("1"));
+        assert!(fails(r#"[{"op": "add", "path": "/a/01", "value": 0}]"#).contains("/a/01"));
+        assert!(fails(r#"[{"op": "test", "path": "/b/c", "value": 2}]"#).contains("test failed"));
+        assert!(fails(r#"[{"op": "move", "from": "/b", "path": "/b/d"}]"#).contains("own child"));
+        assert!(fails(r#"[{"op": "add", "path": "/b"}]"#).contains("value"));
+        assert!(fails(r#"[{"op": "frob", "path": "/b"}]"#).contains("operation 0"));
+        assert!(fails(r#"[{"op": "remove", "path": "b"}]"#).contains("'/'"));
+        assert!(apply_patch(&arena, &doc, &doc).is_err());
+    }
+
+    #[test]
+    fn test_merge_patch() {
+        let arena = Bump::new();
+        let doc = from_str(&arena, r#"{"a": "b", "c": {"d": "e", "f": "g"}, "h": [1]}"#).unwrap();
+        let changes = from_str(
+            &arena,
+            r#"{"a": "z", "c": {"f": null}, "h": null, "n": {"x": null, "y": 1}}"#,
+        )
+        .unwrap();
+        let merged = merge_patch(&arena, &doc, &changes);
+        assert_eq!(to_string(&merged), r#"{"a":"z","c":{"d":"e"},"n":{"y":1}}"#);
+
+        let scalar = from_str(&arena, "[1]").unwrap();
+        assert_eq!(to_string(&merge_patch(&arena, &doc, &scalar)), "[1]");
+    }
+
+    #[test]
+    fn test_deep_merge_patch() {
+        let depth = 50_000;
+        let arena = Bump::new();
+        let nested =
+            |inner: &str| format!("{}{}{}", r#"{"a":"#.repeat(depth), inner, "}".repeat(depth));
+        let doc = from_str(&arena, &nested(r#"{"x":1,"y":2}"#)).unwrap();
+        let changes = from_str(&arena, &nested(r#"{"x":null,"z":3}"#)).unwrap();
+        let (merged, stats) = merge_patch_with_stats(&arena, &doc, &changes);
+        assert_eq!(to_string(&merged), nested(r#"{"y":2,"z":3}"#));
+        assert!(stats.bytes_copied > 0);
+    }
+
+    #[test]
+    fn test_untouched_subtrees_are_shared() {
+        let arena = Bump::new();
+        let big: Vec<String> = (0..1000).map(|i| format!(r#""item {}""#, i)).collect();
+        let input = format!(r#"{{"meta": {{"v": 1}}, "big": [{}]}}"#, big.join(","));
+        let doc = from_str(&arena, &input).unwrap();
+
+        let ops = from_str(
+            &arena,
+            r#"[{"op": "replace", "path": "/meta/v", "value": 2}]"#,
+        )
+        .unwrap();
+        let (patched, stats) = apply_patch_with_stats(&arena, &doc, &ops).unwrap();
+        assert!(same_container(&patched["big"], &doc["big"]));
+        assert_eq!(
+            stats.bytes_shared,
+            footprint(&doc["big"]) + "meta".len() + "big".len() + "v".len()
+        );
+        // Only the root and the "meta" object were copied
+        assert_eq!(stats.bytes_copied, 3 * size_of::<(&str, DataValue<'_>)>());
+
+        let changes = from_str(&arena, r#"{"meta": {"v": 3}}"#).unwrap();
+        let (merged, stats) = merge_patch_with_stats(&arena, &doc, &changes);
+        assert!(same_container(&merged["big"], &doc["big"]));
+        assert_eq!(stats.bytes_copied, 3 * size_of::<(&str, DataValue<'_>)>());
+        assert!(stats.bytes_shared > 100 * stats.bytes_copied);
+
+        // A merge patch that changes nothing copies nothing
+        let noop = from_str(&arena, r#"{"meta": {"missing": null}}"#).unwrap();
+        let (merged, stats) = merge_patch_with_stats(&arena, &doc, &noop);
+        assert!(same_container(&merged, &doc));
+        assert_eq!(stats.bytes_copied, 0);
+        assert_eq!(stats.bytes_shared, footprint(&doc));
+    }
+}
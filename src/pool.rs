@@ -0,0 +1,245 @@
+//! Reusable arenas with generation IDs
+//!
+//! Services that parse many short-lived documents usually keep a pool of arenas
+//! and reset them between requests instead of allocating new ones. An [`Arena`]
+//! counts its resets, and a [`Generation`] records which reset a value was
+//! allocated after, so stale values can be detected.
+//!
+//! Safe code cannot reset an arena while values borrow it. The check is for pools
+//! that erase the arena lifetime with `unsafe` code, where a value that outlives a
+//! reset silently reads reused memory. With the `arena-debug` feature enabled,
+//! [`Tagged`] values carry their arena's generation and panic on access once the
+//! arena has been reset; without it they are a plain wrapper.
+
+use std::ops::Deref;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use bumpalo::Bump;
+
+use crate::datavalue::DataValue;
+use crate::metrics;
+
+/// A bump arena that counts how many times it has been reset
+///
+/// `Arena` dereferences to [`Bump`], so it can be passed anywhere an arena is
+/// expected.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::{from_str, pool::Arena};
+///
+/// let mut arena = Arena::new();
+/// let before = arena.generation();
+/// {
+///     let value = from_str(&arena, "[1, 2]").unwrap();
+///     assert_eq!(value[1].as_i64(), Some(2));
+/// }
+/// arena.reset();
+/// assert!(!before.is_current());
+/// assert!(arena.generation().is_current());
+/// ```
+#[derive(Debug, Default)]
+pub struct Arena {
+    bump: Bump,
+    generation: Arc<AtomicU64>,
+}
+
+impl Arena {
+    /// Creates an empty arena at generation 0
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the current generation of the arena
+    pub fn generation(&self) -> Generation {
+        Generation {
+            cell: Arc::clone(&self.generation),
+            value: self.generation.load(Ordering::Acquire),
+        }
+    }
+
+    /// Frees every value in the arena and starts a new generation
+    pub fn reset(&mut self) {
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        self.bump.reset();
+    }
+
+    /// Tags a value allocated in this arena with the current generation
+    pub fn tag<'a>(&'a self, value: DataValue<'a>) -> Tagged<'a> {
+        Tagged {
+            value,
+            #[cfg(feature = "arena-debug")]
+            generation: self.generation(),
+        }
+    }
+}
+
+impl Deref for Arena {
+    type Target = Bump;
+
+    fn deref(&self) -> &Bump {
+        &self.bump
+    }
+}
+
+/// The generation of an arena at the time the token was taken
+///
+/// The token does not borrow the arena, so it can be checked after the arena has
+/// been reset.
+#[derive(Debug, Clone)]
+pub struct Generation {
+    cell: Arc<AtomicU64>,
+    value: u64,
+}
+
+impl Generation {
+    /// Returns the generation number
+    pub fn get(&self) -> u64 {
+        self.value
+    }
+
+    /// Returns true if the arena has not been reset since the token was taken
+    pub fn is_current(&self) -> bool {
+        self.cell.load(Ordering::Acquire) == self.value
+    }
+
+    /// Panics if the arena has been reset since the token was taken
+    ///
+    /// # Panics
+    ///
+    /// Panics with the stale and current generation numbers.
+    pub fn assert_current(&self) {
+        let current = self.cell.load(Ordering::Acquire);
+        assert!(
+            current == self.value,
+            "value from arena generation {} used after the arena was reset (now generation {})",
+            self.value,
+            current
+        );
+    }
+}
+
+/// A value tagged with the generation of the arena it was allocated in
+///
+/// Access goes through [`Tagged::get`] or `Deref`. With the `arena-debug` feature,
+/// both assert that the arena has not been reset since the value was tagged.
+#[derive(Debug, Clone)]
+pub struct Tagged<'a> {
+    value: DataValue<'a>,
+    #[cfg(feature = "arena-debug")]
+    generation: Generation,
+}
+
+impl<'a> Tagged<'a> {
+    /// Returns the value
+    ///
+    /// # Panics
+    ///
+    /// With the `arena-debug` feature, panics if the arena has been reset.
+    pub fn get(&self) -> &DataValue<'a> {
+        #[cfg(feature = "arena-debug")]
+        self.generation.assert_current();
+        &self.value
+    }
+
+    /// Returns the generation the value was tagged with
+    #[cfg(feature = "arena-debug")]
+    pub fn generation(&self) -> &Generation {
+        &self.generation
+    }
+}
+
+impl<'a> Deref for Tagged<'a> {
+    type Target = DataValue<'a>;
+
+    fn deref(&self) -> &DataValue<'a> {
+        self.get()
+    }
+}
+
+/// A thread-safe pool of arenas that are reset when they are returned
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::{from_str, pool::ArenaPool};
+///
+/// let pool = ArenaPool::new();
+/// for request in [r#"{"id": 1}"#, r#"{"id": 2}"#] {
+///     let arena = pool.take();
+///     let id = from_str(&arena, request).unwrap()["id"].as_i64();
+///     assert!(id.is_some());
+///     pool.put(arena);
+/// }
+/// assert_eq!(pool.idle(), 1);
+/// ```
+#[derive(Debug, Default)]
+pub struct ArenaPool {
+    idle: Mutex<Vec<Arena>>,
+}
+
+impl ArenaPool {
+    /// Creates an empty pool
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Takes an idle arena from the pool, or creates one if none is idle
+    pub fn take(&self) -> Arena {
+        let idle = self.lock().pop();
+        metrics::record_pool_take(idle.is_some());
+        idle.unwrap_or_default()
+    }
+
+    /// Resets an arena, which starts its next generation, and returns it to the pool
+    pub fn put(&self, mut arena: Arena) {
+        arena.reset();
+        self.lock().push(arena);
+    }
+
+    /// Returns the number of idle arenas
+    pub fn idle(&self) -> usize {
+        self.lock().len()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Arena>> {
+        // The list stays consistent even if a thread panicked while holding it
+        self.idle.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_str;
+
+    #[test]
+    fn test_generations() {
+        let pool = ArenaPool::new();
+        let arena = pool.take();
+        let first = arena.generation();
+        assert_eq!(first.get(), 0);
+        {
+            let value = arena.tag(from_str(&arena, r#"{"a": [1]}"#).unwrap());
+            assert_eq!(value["a"][0].as_i64(), Some(1));
+            first.assert_current();
+        }
+        pool.put(arena);
+
+        let arena = pool.take();
+        assert_eq!(pool.idle(), 0);
+        assert_eq!(arena.generation().get(), 1);
+        assert!(!first.is_current());
+    }
+
+    #[test]
+    #[should_panic(expected = "used after the arena was reset")]
+    fn test_stale_generation_panics() {
+        let mut arena = Arena::new();
+        let generation = arena.generation();
+        arena.reset();
+        generation.assert_current();
+    }
+}
@@ -0,0 +1,140 @@
+//! sqlx support for Postgres `json` and `jsonb`, enabled by the `sqlx` feature
+//!
+//! DataValue implements sqlx's `Type`, `Encode` and `Decode` for Postgres, so it
+//! binds directly as a `jsonb` parameter and reads back from `json` and `jsonb`
+//! columns without a `serde_json::Value` in between. Decoding borrows the column
+//! text from the row as a [`DataValue::Raw`], which
+//! [`parse_raw`](DataValue::parse_raw) turns into a tree in an arena when the
+//! contents are needed. [`SharedDocument`] decodes as well, for rows that must
+//! own their data.
+
+use sqlx::decode::Decode;
+use sqlx::encode::{Encode, IsNull};
+use sqlx::error::BoxDynError;
+use sqlx::postgres::{PgArgumentBuffer, PgTypeInfo, PgValueFormat, PgValueRef, Postgres};
+use sqlx::types::{JsonValue, Type};
+use sqlx::ValueRef;
+
+use crate::datavalue::DataValue;
+use crate::document::SharedDocument;
+use crate::ser::write_json;
+
+/// Version byte that starts `jsonb` values in the binary protocol
+const JSONB_VERSION: u8 = 1;
+
+impl Type<Postgres> for DataValue<'_> {
+    fn type_info() -> PgTypeInfo {
+        <JsonValue as Type<Postgres>>::type_info()
+    }
+
+    fn compatible(ty: &PgTypeInfo) -> bool {
+        <JsonValue as Type<Postgres>>::compatible(ty)
+    }
+}
+
+/// Binds the value as `jsonb`, written as compact JSON
+///
+/// # Example
+///
+/// ```no_run
+/// # async fn run(pool: sqlx::PgPool) -> Result<(), sqlx::Error> {
+/// use datavalue_rs::{from_str, Bump, DataValue};
+/// use sqlx::Row;
+///
+/// let arena = Bump::new();
+/// let payload = from_str(&arena, r#"{"kind": "click", "ids": [1, 2]}"#).unwrap();
+/// sqlx::query("INSERT INTO events (payload) VALUES ($1)")
+///     .bind(payload)
+///     .execute(&pool)
+///     .await?;
+///
+/// let row = sqlx::query("SELECT payload FROM events LIMIT 1")
+///     .fetch_one(&pool)
+///     .await?;
+/// let payload: DataValue<'_> = row.try_get("payload")?;
+/// let payload = payload.parse_raw(&arena).unwrap();
+/// assert_eq!(payload["kind"].as_str(), Some("click"));
+/// # Ok(())
+/// # }
+/// ```
+impl Encode<'_, Postgres> for DataValue<'_> {
+    fn encode_by_ref(&self, buf: &mut PgArgumentBuffer) -> Result<IsNull, BoxDynError> {
+        buf.push(JSONB_VERSION);
+        write_json(self, &mut **buf)?;
+        Ok(IsNull::No)
+    }
+}
+
+/// Reads a `json` or `jsonb` column as a [`DataValue::Raw`] borrowing the row
+impl<'r> Decode<'r, Postgres> for DataValue<'r> {
+    fn decode(value: PgValueRef<'r>) -> Result<Self, BoxDynError> {
+        Ok(DataValue::Raw(json_text(value)?))
+    }
+}
+
+impl Type<Postgres> for SharedDocument {
+    fn type_info() -> PgTypeInfo {
+        <JsonValue as Type<Postgres>>::type_info()
+    }
+
+    fn compatible(ty: &PgTypeInfo) -> bool {
+        <JsonValue as Type<Postgres>>::compatible(ty)
+    }
+}
+
+/// Reads a `json` or `jsonb` column into an owned document
+impl Decode<'_, Postgres> for SharedDocument {
+    fn decode(value: PgValueRef<'_>) -> Result<Self, BoxDynError> {
+        Ok(SharedDocument::parse(json_text(value)?)?)
+    }
+}
+
+/// Returns the JSON text of a column value
+fn json_text(value: PgValueRef<'_>) -> Result<&str, BoxDynError> {
+    let binary_jsonb = value.format() == PgValueFormat::Binary
+        && *value.type_info() == <JsonValue as Type<Postgres>>::type_info();
+    Ok(strip_version(value.as_bytes()?, binary_jsonb)?)
+}
+
+/// Checks and removes the version byte of a binary `jsonb` value
+fn strip_version(bytes: &[u8], binary_jsonb: bool) -> crate::Result<&str> {
+    let text = match bytes.split_first() {
+        Some((&JSONB_VERSION, text)) if binary_jsonb => text,
+        Some((version, _)) if binary_jsonb => {
+            return Err(crate::Error::syntax(format!(
+                "Unsupported jsonb format version {}",
+                version
+            )))
+        }
+        _ => bytes,
+    };
+    std::str::from_utf8(text).map_err(|_| crate::Error::syntax("Invalid UTF-8 in json column"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_str;
+    use bumpalo::Bump;
+
+    #[test]
+    fn test_jsonb_encoding() {
+        let arena = Bump::new();
+        let value = from_str(&arena, r#"{"name": "a\"b", "at": [1.5, null]}"#).unwrap();
+        let mut buf = PgArgumentBuffer::default();
+        assert!(matches!(value.encode_by_ref(&mut buf).unwrap(), IsNull::No));
+        assert_eq!(buf[0], JSONB_VERSION);
+        let text = strip_version(&buf, true).unwrap();
+        assert_eq!(text, r#"{"name":"a\"b","at":[1.5,null]}"#);
+        assert_eq!(DataValue::Raw(text).parse_raw(&arena).unwrap(), value);
+
+        assert_eq!(strip_version(b"[1]", false).unwrap(), "[1]");
+        assert!(strip_version(b"\x02[1]", true).is_err());
+        assert!(<DataValue<'_> as Type<Postgres>>::compatible(
+            &<JsonValue as Type<Postgres>>::type_info()
+        ));
+        assert!(!<DataValue<'_> as Type<Postgres>>::compatible(
+            &<String as Type<Postgres>>::type_info()
+        ));
+    }
+}
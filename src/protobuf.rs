@@ -0,0 +1,302 @@
+//! `google.protobuf.Struct` and `Value`, enabled by the `prost` feature
+//!
+//! gRPC services carry schemaless JSON in the well-known [`Struct`], [`Value`]
+//! and [`ListValue`] messages. This module converts DataValues to and from the
+//! `prost-types` representation of those messages, so a dynamic payload can be
+//! forwarded to or read from a gRPC call without going through JSON text.
+//!
+//! Protobuf numbers are doubles: integers beyond 2^53 lose precision on the way
+//! out, and integral numbers within 2^53 come back as integers. Struct fields
+//! are a sorted map, so objects come back sorted by key and only the last of
+//! duplicate keys is kept. DateTimes and durations become strings, as in JSON.
+
+use std::collections::BTreeMap;
+
+use bumpalo::Bump;
+use prost_types::value::Kind;
+use prost_types::{ListValue, NullValue, Struct, Value};
+
+use crate::datavalue::{DataValue, Number};
+use crate::error::{Error, Result};
+use crate::helpers::format_duration;
+
+/// Largest magnitude up to which every integer is exactly representable as f64
+const MAX_SAFE_INTEGER: f64 = 9_007_199_254_740_992.0;
+
+/// Converts a value to a protobuf [`Value`]
+///
+/// # Errors
+///
+/// Returns an error if a raw value is not valid JSON.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::{from_str, protobuf, Bump};
+/// use prost_types::value::Kind;
+///
+/// let arena = Bump::new();
+/// let value = from_str(&arena, r#"{"ids": [1, 2], "user": null}"#).unwrap();
+/// let Some(Kind::StructValue(message)) = protobuf::to_value(&value).unwrap().kind else {
+///     panic!("objects become structs");
+/// };
+/// assert_eq!(message.fields["ids"], vec![1.into(), 2.into()].into());
+/// assert!(matches!(message.fields["user"].kind, Some(Kind::NullValue(_))));
+/// ```
+pub fn to_value(value: &DataValue<'_>) -> Result<Value> {
+    /// An array or object whose elements are still being converted
+    enum Frame<'v, 'a> {
+        List(std::slice::Iter<'v, DataValue<'a>>, Vec<Value>),
+        /// The remaining entries, the converted ones and the key of the value
+        /// being converted
+        Struct(
+            std::slice::Iter<'v, (&'a str, DataValue<'a>)>,
+            BTreeMap<String, Value>,
+            &'a str,
+        ),
+    }
+
+    let mut stack: Vec<Frame<'_, '_>> = Vec::new();
+    let mut next = Some(value);
+    loop {
+        let mut converted = match next.take() {
+            Some(DataValue::Array(items)) => {
+                stack.push(Frame::List(items.iter(), Vec::with_capacity(items.len())));
+                None
+            }
+            Some(DataValue::Object(entries)) => {
+                stack.push(Frame::Struct(entries.iter(), BTreeMap::new(), ""));
+                None
+            }
+            Some(DataValue::Raw(text)) => {
+                let arena = Bump::new();
+                Some(to_value(&DataValue::Raw(text).parse_raw(&arena)?)?)
+            }
+            Some(DataValue::Null) => Some(Kind::NullValue(NullValue::NullValue as i32).into()),
+            Some(DataValue::Bool(b)) => Some(Value::from(*b)),
+            Some(number @ DataValue::Number(_)) => {
+                Some(Value::from(number.as_f64().unwrap_or(f64::NAN)))
+            }
+            Some(DataValue::String(s)) => Some(Value::from(*s)),
+            Some(DataValue::DateTime(dt)) => Some(Value::from(dt.to_rfc3339())),
+            Some(DataValue::Duration(dur)) => Some(Value::from(format_duration(dur))),
+            None => None,
+        };
+
+        // Hand the converted value to its container, closing finished ones
+        loop {
+            match stack.last_mut() {
+                None => return Ok(converted.expect("a value was converted")),
+                Some(Frame::List(items, list)) => {
+                    list.extend(converted.take());
+                    if let Some(item) = items.next() {
+                        next = Some(item);
+                        break;
+                    }
+                }
+                Some(Frame::Struct(entries, fields, key)) => {
+                    if let Some(value) = converted.take() {
+                        fields.insert(key.to_string(), value);
+                    }
+                    if let Some((name, value)) = entries.next() {
+                        *key = name;
+                        next = Some(value);
+                        break;
+                    }
+                }
+            }
+            converted = Some(match stack.pop() {
+                Some(Frame::List(_, list)) => Value::from(list),
+                Some(Frame::Struct(_, fields, _)) => Value::from(fields),
+                None => unreachable!("a frame was open"),
+            });
+        }
+    }
+}
+
+/// Converts an object to a protobuf [`Struct`]
+///
+/// # Errors
+///
+/// Returns an error if `value` is not an object, or a raw value is not valid JSON.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::{from_str, protobuf, Bump};
+///
+/// let arena = Bump::new();
+/// let value = from_str(&arena, r#"{"name": "ada", "admin": true}"#).unwrap();
+/// let message = protobuf::to_struct(&value).unwrap();
+/// assert_eq!(message.fields["name"], "ada".into());
+///
+/// let scalar = from_str(&arena, "1").unwrap();
+/// assert!(protobuf::to_struct(&scalar).is_err());
+/// ```
+pub fn to_struct(value: &DataValue<'_>) -> Result<Struct> {
+    let converted = to_value(value)?;
+    match converted.kind {
+        Some(Kind::StructValue(message)) => Ok(message),
+        _ => Err(Error::expected_type(
+            "object",
+            format!("{:?}", value.get_type()).to_lowercase(),
+        )),
+    }
+}
+
+/// Converts a protobuf [`Value`] to a DataValue in the arena
+///
+/// A value with no kind set is converted to null.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::{protobuf, Bump};
+/// use prost_types::Value;
+///
+/// let arena = Bump::new();
+/// let message = Value::from(vec![Value::from(3.0), Value::from(0.5), Value::from("x")]);
+/// let value = protobuf::from_value(&arena, &message);
+/// assert_eq!(datavalue_rs::to_string(&value), r#"[3,0.5,"x"]"#);
+/// ```
+pub fn from_value<'a>(arena: &'a Bump, value: &Value) -> DataValue<'a> {
+    /// A list or struct whose elements are still being converted
+    enum Frame<'v, 'a> {
+        List(std::slice::Iter<'v, Value>, Vec<DataValue<'a>>),
+        /// The remaining fields, the converted ones and the key of the value
+        /// being converted
+        Struct(
+            std::collections::btree_map::Iter<'v, String, Value>,
+            Vec<(&'a str, DataValue<'a>)>,
+            &'a str,
+        ),
+    }
+
+    let mut stack: Vec<Frame<'_, 'a>> = Vec::new();
+    let mut next = value;
+    loop {
+        let mut converted = match &next.kind {
+            Some(Kind::ListValue(ListValue { values })) => {
+                stack.push(Frame::List(values.iter(), Vec::with_capacity(values.len())));
+                None
+            }
+            Some(Kind::StructValue(Struct { fields })) => {
+                stack.push(Frame::Struct(
+                    fields.iter(),
+                    Vec::with_capacity(fields.len()),
+                    "",
+                ));
+                None
+            }
+            Some(Kind::NumberValue(f)) => Some(DataValue::Number(number(*f))),
+            Some(Kind::StringValue(s)) => Some(DataValue::String(arena.alloc_str(s))),
+            Some(Kind::BoolValue(b)) => Some(DataValue::Bool(*b)),
+            Some(Kind::NullValue(_)) | None => Some(DataValue::Null),
+        };
+
+        loop {
+            match stack.last_mut() {
+                None => return converted.expect("a value was converted"),
+                Some(Frame::List(items, list)) => {
+                    list.extend(converted.take());
+                    if let Some(item) = items.next() {
+                        next = item;
+                        break;
+                    }
+                }
+                Some(Frame::Struct(fields, entries, key)) => {
+                    if let Some(value) = converted.take() {
+                        entries.push((key, value));
+                    }
+                    if let Some((name, value)) = fields.next() {
+                        *key = arena.alloc_str(name);
+                        next = value;
+                        break;
+                    }
+                }
+            }
+            converted = Some(match stack.pop() {
+                Some(Frame::List(_, list)) => DataValue::Array(arena.alloc_slice_fill_iter(list)),
+                Some(Frame::Struct(_, entries, _)) => {
+                    DataValue::Object(arena.alloc_slice_fill_iter(entries))
+                }
+                None => unreachable!("a frame was open"),
+            });
+        }
+    }
+}
+
+/// Converts a protobuf [`Struct`] to an object in the arena
+///
+/// # Example
+///
+/// ```
+/// use std::collections::BTreeMap;
+///
+/// use datavalue_rs::{protobuf, Bump};
+/// use prost_types::Struct;
+///
+/// let arena = Bump::new();
+/// let message = Struct {
+///     fields: BTreeMap::from([
+///         ("status".to_string(), 200.into()),
+///         ("cached".to_string(), true.into()),
+///     ]),
+/// };
+/// let value = protobuf::from_struct(&arena, &message);
+/// assert_eq!(datavalue_rs::to_string(&value), r#"{"cached":true,"status":200}"#);
+/// ```
+pub fn from_struct<'a>(arena: &'a Bump, message: &Struct) -> DataValue<'a> {
+    let entries = message
+        .fields
+        .iter()
+        .map(|(name, value)| (&*arena.alloc_str(name), from_value(arena, value)));
+    DataValue::Object(arena.alloc_slice_fill_iter(entries))
+}
+
+/// Converts a protobuf double, keeping exactly representable integers integral
+fn number(f: f64) -> Number<'static> {
+    if f.fract() == 0.0 && f.abs() <= MAX_SAFE_INTEGER {
+        Number::Integer(f as i64)
+    } else {
+        Number::Float(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_str;
+
+    #[test]
+    fn test_struct_round_trip() {
+        let arena = Bump::new();
+        let value = from_str(
+            &arena,
+            r#"{"b": [1, -2.5, "x", null, {"deep": [[]]}], "a": {"ok": false}, "big": 9007199254740993}"#,
+        )
+        .unwrap();
+
+        let message = to_struct(&value).unwrap();
+        assert_eq!(message.fields.len(), 3);
+        let back = from_struct(&arena, &message);
+        assert_eq!(
+            crate::to_string(&back),
+            r#"{"a":{"ok":false},"b":[1,-2.5,"x",null,{"deep":[[]]}],"big":9007199254740992}"#
+        );
+
+        let raw = DataValue::Raw(r#"{"n": 1}"#);
+        assert_eq!(
+            from_struct(&arena, &to_struct(&raw).unwrap())["n"].as_i64(),
+            Some(1)
+        );
+        assert!(to_struct(&DataValue::Raw("{")).is_err());
+        assert!(to_struct(&DataValue::Null).is_err());
+
+        assert_eq!(from_value(&arena, &Value { kind: None }), DataValue::Null);
+        assert_eq!(
+            from_value(&arena, &Value::from(1e300)),
+            DataValue::Number(Number::Float(1e300))
+        );
+    }
+}
@@ -0,0 +1,565 @@
+//! Redis RESP3 input and output, enabled by the `redis` feature
+//!
+//! [RESP3](https://github.com/redis/redis-specification/blob/master/protocol/RESP3.md)
+//! is the protocol Redis speaks on the wire. Converting DataValues to and from
+//! it lets a cache layer store a structured value as a RESP3 reply, or read one
+//! back, without going through JSON text. [`from_slice`] decodes one value and
+//! [`to_vec`] encodes one.
+//!
+//! RESP3 values map onto DataValue as follows:
+//!
+//! | RESP3 | DataValue |
+//! |---|---|
+//! | null, and the RESP2 null bulk string and array | [`DataValue::Null`] |
+//! | boolean | [`DataValue::Bool`] |
+//! | number | [`Number::Integer`] |
+//! | big number | [`Number::BigInt`], or [`Number::Integer`] when it fits |
+//! | double | [`Number::Float`], or [`Number::BigDecimal`] when that would lose digits |
+//! | simple string, bulk string, verbatim string | [`DataValue::String`] |
+//! | bulk string that is not UTF-8 | `{"$bytes": base64}` |
+//! | array, set, push | [`DataValue::Array`] |
+//! | map | [`DataValue::Object`], with integer keys as their decimal text |
+//!
+//! Attributes are skipped, and the format prefix of verbatim strings is
+//! dropped. Simple and bulk errors are returned as errors. Streamed strings and
+//! aggregates of unknown length are not supported.
+//!
+//! The encoder writes strings as bulk strings, integers outside the `i64` range
+//! as big numbers, and floats and exact decimals as doubles. `$bytes` objects
+//! become bulk strings of their bytes, and DateTimes and durations become
+//! strings, as in JSON.
+//!
+//! # Example
+//!
+//! ```
+//! use datavalue_rs::{from_str, redis, Bump};
+//!
+//! let arena = Bump::new();
+//! let value = from_str(&arena, r#"{"hits": 3, "ratio": 0.5}"#).unwrap();
+//! let bytes = redis::to_vec(&value).unwrap();
+//! assert_eq!(bytes, b"%2\r\n$4\r\nhits\r\n:3\r\n$5\r\nratio\r\n,0.5\r\n");
+//! assert_eq!(redis::from_slice(&arena, &bytes).unwrap(), value);
+//! ```
+
+use bumpalo::Bump;
+
+use crate::base64;
+use crate::bignum::parse_number;
+use crate::datavalue::{DataValue, Number};
+use crate::error::{Error, Result};
+use crate::helpers::format_duration;
+
+const BYTES_KEY: &str = "$bytes";
+
+/// An open aggregate
+struct Frame<'a> {
+    /// Elements still to read, counting keys and values separately for maps
+    remaining: u64,
+    kind: Aggregate,
+    items: Vec<DataValue<'a>>,
+    entries: Vec<(&'a str, DataValue<'a>)>,
+    /// Key of the map entry whose value is being read
+    key: Option<&'a str>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Aggregate {
+    Array,
+    Map,
+    /// An attribute map, read and then dropped
+    Attribute,
+}
+
+/// Decodes one RESP3 value
+///
+/// # Errors
+///
+/// Returns a syntax error with the byte offset of the problem if the input is
+/// not valid RESP3, is followed by more bytes, or has a map key that is not a
+/// string or integer. A Redis error reply is returned as an error holding its
+/// message.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::{redis, Bump};
+///
+/// let arena = Bump::new();
+/// let value = redis::from_slice(&arena, b"*3\r\n+OK\r\n(18446744073709551616\r\n=9\r\ntxt:hello\r\n").unwrap();
+/// assert_eq!(value[0].as_str(), Some("OK"));
+/// assert_eq!(value[2].as_str(), Some("hello"));
+///
+/// let error = redis::from_slice(&arena, b"-ERR unknown command\r\n").unwrap_err();
+/// assert!(error.to_string().contains("ERR unknown command"));
+/// ```
+pub fn from_slice<'a>(arena: &'a Bump, bytes: &[u8]) -> Result<DataValue<'a>> {
+    let mut reader = Reader {
+        arena,
+        bytes,
+        pos: 0,
+    };
+    let value = reader.read_value()?;
+    if reader.pos < bytes.len() {
+        return Err(reader.error("Trailing bytes after RESP3 value"));
+    }
+    Ok(value)
+}
+
+/// Encodes a value as RESP3
+///
+/// # Errors
+///
+/// Returns an error if a `$bytes` object does not hold valid base64, or a raw
+/// value is not valid JSON.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::{from_str, redis, Bump};
+///
+/// let arena = Bump::new();
+/// let value = from_str(&arena, r#"[null, true, "é"]"#).unwrap();
+/// assert_eq!(redis::to_vec(&value).unwrap(), "*3\r\n_\r\n#t\r\n$2\r\né\r\n".as_bytes());
+/// ```
+pub fn to_vec(value: &DataValue<'_>) -> Result<Vec<u8>> {
+    let mut output = Vec::new();
+    write_value(value, &mut output)?;
+    Ok(output)
+}
+
+struct Reader<'a, 'b> {
+    arena: &'a Bump,
+    bytes: &'b [u8],
+    pos: usize,
+}
+
+impl<'a, 'b> Reader<'a, 'b> {
+    fn error(&self, msg: &str) -> Error {
+        Error::syntax(format!("{} at byte {}", msg, self.pos))
+    }
+
+    /// Reads up to the next CRLF and consumes it
+    fn line(&mut self) -> Result<&'b [u8]> {
+        let rest = &self.bytes[self.pos..];
+        let end = rest
+            .windows(2)
+            .position(|w| w == b"\r\n")
+            .ok_or_else(|| self.error("Truncated RESP3 value"))?;
+        self.pos += end + 2;
+        Ok(&rest[..end])
+    }
+
+    fn text_line(&mut self) -> Result<&'b str> {
+        let start = self.pos;
+        let line = self.line()?;
+        std::str::from_utf8(line)
+            .map_err(|_| Error::syntax(format!("Invalid UTF-8 in RESP3 value at byte {}", start)))
+    }
+
+    /// Reads a length line, returning None for the RESP2 null length `-1`
+    fn length(&mut self) -> Result<Option<u64>> {
+        let start = self.pos;
+        match self.text_line()? {
+            "-1" => Ok(None),
+            "?" => Err(Error::syntax(format!(
+                "Streamed RESP3 values are not supported at byte {}",
+                start
+            ))),
+            text => match text.parse::<u64>() {
+                Ok(len) => Ok(Some(len)),
+                Err(_) => Err(Error::syntax(format!(
+                    "Invalid RESP3 length at byte {}",
+                    start
+                ))),
+            },
+        }
+    }
+
+    /// Reads the payload and CRLF of a bulk string, bulk error or verbatim string
+    fn bulk(&mut self, len: u64) -> Result<&'b [u8]> {
+        let available = self.bytes.len() - self.pos;
+        let len = usize::try_from(len)
+            .ok()
+            .filter(|&len| len.checked_add(2).is_some_and(|n| n <= available))
+            .ok_or_else(|| self.error("Truncated RESP3 value"))?;
+        let payload = &self.bytes[self.pos..self.pos + len];
+        self.pos += len;
+        if &self.bytes[self.pos..self.pos + 2] != b"\r\n" {
+            return Err(self.error("Expected CRLF after RESP3 bulk string"));
+        }
+        self.pos += 2;
+        Ok(payload)
+    }
+
+    fn number(&self, text: &str, is_float: bool, start: usize) -> Result<DataValue<'a>> {
+        let number = match text {
+            "inf" => Some(Number::Float(f64::INFINITY)),
+            "-inf" => Some(Number::Float(f64::NEG_INFINITY)),
+            "nan" => Some(Number::Float(f64::NAN)),
+            _ if is_number(text) => parse_number(self.arena, text, is_float),
+            _ => None,
+        };
+        number
+            .map(DataValue::Number)
+            .ok_or_else(|| Error::syntax(format!("Invalid RESP3 number at byte {}", start)))
+    }
+
+    /// Reads a value's type byte and payload, returning None and opening a
+    /// frame for aggregates
+    fn read_head(&mut self, stack: &mut Vec<Frame<'a>>) -> Result<Option<DataValue<'a>>> {
+        let start = self.pos;
+        let Some(&marker) = self.bytes.get(self.pos) else {
+            return Err(self.error("Truncated RESP3 value"));
+        };
+        self.pos += 1;
+        let value = match marker {
+            b'_' => {
+                self.line()?;
+                DataValue::Null
+            }
+            b'#' => match self.line()? {
+                b"t" => DataValue::Bool(true),
+                b"f" => DataValue::Bool(false),
+                _ => {
+                    return Err(Error::syntax(format!(
+                        "Invalid RESP3 boolean at byte {}",
+                        start
+                    )))
+                }
+            },
+            b':' | b'(' | b',' => {
+                let text = self.text_line()?;
+                let text = text.strip_prefix('+').unwrap_or(text);
+                self.number(text, marker == b',', start)?
+            }
+            b'+' => DataValue::String(self.arena.alloc_str(self.text_line()?)),
+            b'-' => {
+                return Err(Error::custom(format!(
+                    "Redis error reply: {}",
+                    String::from_utf8_lossy(self.line()?)
+                )))
+            }
+            b'$' | b'!' | b'=' => {
+                let Some(len) = self.length()? else {
+                    return Ok(Some(DataValue::Null));
+                };
+                let payload = self.bulk(len)?;
+                match marker {
+                    b'!' => {
+                        return Err(Error::custom(format!(
+                            "Redis error reply: {}",
+                            String::from_utf8_lossy(payload)
+                        )))
+                    }
+                    b'=' if payload.len() < 4 || payload[3] != b':' => {
+                        return Err(Error::syntax(format!(
+                            "Invalid RESP3 verbatim string at byte {}",
+                            start
+                        )))
+                    }
+                    b'=' => self.string(&payload[4..]),
+                    _ => self.string(payload),
+                }
+            }
+            b'*' | b'~' | b'>' | b'%' | b'|' => {
+                let Some(len) = self.length()? else {
+                    return Ok(Some(DataValue::Null));
+                };
+                let (kind, remaining) = match marker {
+                    b'%' => (Aggregate::Map, len.checked_mul(2)),
+                    b'|' => (Aggregate::Attribute, len.checked_mul(2)),
+                    _ => (Aggregate::Array, Some(len)),
+                };
+                // Every element takes at least three bytes
+                let remaining = remaining
+                    .filter(|&n| n <= (self.bytes.len() - self.pos) as u64 / 3)
+                    .ok_or_else(|| {
+                        Error::syntax(format!("Truncated RESP3 value at byte {}", start))
+                    })?;
+                let capacity = remaining as usize;
+                stack.push(Frame {
+                    remaining,
+                    kind,
+                    items: Vec::with_capacity(if kind == Aggregate::Array {
+                        capacity
+                    } else {
+                        0
+                    }),
+                    entries: Vec::with_capacity(if kind == Aggregate::Map {
+                        capacity / 2
+                    } else {
+                        0
+                    }),
+                    key: None,
+                });
+                return Ok(None);
+            }
+            _ => {
+                self.pos = start;
+                return Err(self.error("Unknown RESP3 type"));
+            }
+        };
+        Ok(Some(value))
+    }
+
+    fn string(&self, bytes: &[u8]) -> DataValue<'a> {
+        match std::str::from_utf8(bytes) {
+            Ok(text) => DataValue::String(self.arena.alloc_str(text)),
+            Err(_) => {
+                let text = DataValue::String(self.arena.alloc_str(&base64::encode(bytes)));
+                DataValue::Object(self.arena.alloc_slice_fill_iter([(BYTES_KEY, text)]))
+            }
+        }
+    }
+
+    fn key(&self, key: DataValue<'a>) -> Result<&'a str> {
+        match key {
+            DataValue::String(s) => Ok(s),
+            DataValue::Number(n @ (Number::Integer(_) | Number::BigInt(_))) => {
+                Ok(self.arena.alloc_str(&DataValue::Number(n).to_string()))
+            }
+            _ => Err(self.error("RESP3 map keys must be strings or integers")),
+        }
+    }
+
+    /// Reads one value; aggregates are kept on an explicit stack
+    fn read_value(&mut self) -> Result<DataValue<'a>> {
+        let mut stack: Vec<Frame<'a>> = Vec::new();
+        loop {
+            let value = match stack.last() {
+                Some(frame) if frame.remaining == 0 => {
+                    let frame = stack.pop().expect("frame is open");
+                    match frame.kind {
+                        Aggregate::Array => {
+                            DataValue::Array(self.arena.alloc_slice_fill_iter(frame.items))
+                        }
+                        Aggregate::Map => {
+                            DataValue::Object(self.arena.alloc_slice_fill_iter(frame.entries))
+                        }
+                        // The attribute belongs to the value that follows it
+                        Aggregate::Attribute => continue,
+                    }
+                }
+                _ => match self.read_head(&mut stack)? {
+                    Some(value) => value,
+                    None => continue,
+                },
+            };
+
+            let Some(frame) = stack.last_mut() else {
+                return Ok(value);
+            };
+            frame.remaining -= 1;
+            match frame.kind {
+                Aggregate::Array => frame.items.push(value),
+                Aggregate::Map => match frame.key.take() {
+                    None => frame.key = Some(self.key(value)?),
+                    Some(key) => frame.entries.push((key, value)),
+                },
+                Aggregate::Attribute => {}
+            }
+        }
+    }
+}
+
+/// Returns whether `text` is a JSON number, the syntax RESP3 numbers share
+fn is_number(text: &str) -> bool {
+    let digits = text.strip_prefix('-').unwrap_or(text);
+    let (mantissa, exponent) = match digits.find(['e', 'E']) {
+        Some(e) => (&digits[..e], Some(&digits[e + 1..])),
+        None => (digits, None),
+    };
+    let (whole, fraction) = match mantissa.split_once('.') {
+        Some((whole, fraction)) => (whole, Some(fraction)),
+        None => (mantissa, None),
+    };
+    let all_digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    all_digits(whole)
+        && fraction.is_none_or(all_digits)
+        && exponent.is_none_or(|e| all_digits(e.strip_prefix(['+', '-']).unwrap_or(e)))
+}
+
+fn write_bulk(output: &mut Vec<u8>, bytes: &[u8]) {
+    output.extend_from_slice(format!("${}\r\n", bytes.len()).as_bytes());
+    output.extend_from_slice(bytes);
+    output.extend_from_slice(b"\r\n");
+}
+
+/// An aggregate being written: its remaining elements, with keys for maps
+type Open<'v, 'a> = std::vec::IntoIter<(Option<&'a str>, &'v DataValue<'a>)>;
+
+/// Writes one value, keeping open aggregates on an explicit stack
+fn write_value(value: &DataValue<'_>, output: &mut Vec<u8>) -> Result<()> {
+    let mut stack: Vec<Open<'_, '_>> = Vec::new();
+    let mut next = Some(value);
+    loop {
+        match next.take() {
+            Some(DataValue::Object([(BYTES_KEY, DataValue::String(text))])) => {
+                write_bulk(output, &base64::decode(text)?);
+            }
+            Some(DataValue::Array(items)) => {
+                output.extend_from_slice(format!("*{}\r\n", items.len()).as_bytes());
+                let items: Vec<_> = items.iter().map(|item| (None, item)).collect();
+                stack.push(items.into_iter());
+            }
+            Some(DataValue::Object(entries)) => {
+                output.extend_from_slice(format!("%{}\r\n", entries.len()).as_bytes());
+                let entries: Vec<_> = entries
+                    .iter()
+                    .map(|(key, value)| (Some(*key), value))
+                    .collect();
+                stack.push(entries.into_iter());
+            }
+            Some(DataValue::Raw(text)) => {
+                let arena = Bump::new();
+                write_value(&DataValue::Raw(text).parse_raw(&arena)?, output)?;
+            }
+            Some(scalar) => write_scalar(scalar, output),
+            None => {}
+        }
+
+        let Some(open) = stack.last_mut() else {
+            return Ok(());
+        };
+        match open.next() {
+            Some((key, item)) => {
+                if let Some(key) = key {
+                    write_bulk(output, key.as_bytes());
+                }
+                next = Some(item);
+            }
+            None => {
+                stack.pop();
+            }
+        }
+    }
+}
+
+fn write_scalar(value: &DataValue<'_>, output: &mut Vec<u8>) {
+    let line = match value {
+        DataValue::Null => "_".to_string(),
+        DataValue::Bool(b) => format!("#{}", if *b { 't' } else { 'f' }),
+        DataValue::Number(number) => match number {
+            Number::Integer(i) => format!(":{}", i),
+            Number::Float(f) if f.is_nan() => ",nan".to_string(),
+            Number::Float(f) if f.is_infinite() => {
+                format!(",{}inf", if *f < 0.0 { "-" } else { "" })
+            }
+            Number::Float(_) | Number::BigDecimal(_) => format!(",{}", value),
+            Number::BigInt(_) => format!("({}", value),
+            Number::Raw(text) => {
+                let arena = Bump::new();
+                let is_float = text.contains(['.', 'e', 'E']);
+                match parse_number(&arena, text, is_float) {
+                    Some(number) => return write_scalar(&DataValue::Number(number), output),
+                    None => format!(",{}", text),
+                }
+            }
+        },
+        DataValue::String(s) => return write_bulk(output, s.as_bytes()),
+        DataValue::DateTime(dt) => return write_bulk(output, dt.to_rfc3339().as_bytes()),
+        DataValue::Duration(dur) => return write_bulk(output, format_duration(dur).as_bytes()),
+        DataValue::Array(_) | DataValue::Object(_) | DataValue::Raw(_) => {
+            unreachable!("aggregates and raw values are written by write_value")
+        }
+    };
+    output.extend_from_slice(line.as_bytes());
+    output.extend_from_slice(b"\r\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_str;
+
+    #[test]
+    fn test_decode() {
+        let arena = Bump::new();
+        let cases: [(&[u8], &str); 12] = [
+            (b"_\r\n", "null"),
+            (b"$-1\r\n", "null"),
+            (b"*-1\r\n", "null"),
+            (b"#f\r\n", "false"),
+            (b":-42\r\n", "-42"),
+            (b",1.5e3\r\n", "1500.0"),
+            (b"+OK\r\n", r#""OK""#),
+            (b"$0\r\n\r\n", r#""""#),
+            (b"$4\r\na\r\nb\r\n", "\"a\\r\\nb\""),
+            (b"~2\r\n:1\r\n*0\r\n", "[1,[]]"),
+            (
+                b"%2\r\n:1\r\n+one\r\n+two\r\n%0\r\n",
+                r#"{"1":"one","two":{}}"#,
+            ),
+            (
+                b"|1\r\n+ttl\r\n:10\r\n>2\r\n+msg\r\n|1\r\n+a\r\n_\r\n#t\r\n",
+                r#"["msg",true]"#,
+            ),
+        ];
+        for (bytes, expected) in cases {
+            let value = from_slice(&arena, bytes).unwrap();
+            assert_eq!(value, from_str(&arena, expected).unwrap(), "{}", expected);
+        }
+
+        let value = from_slice(&arena, b"*3\r\n,inf\r\n,-inf\r\n,nan\r\n").unwrap();
+        assert_eq!(value[1].as_f64(), Some(f64::NEG_INFINITY));
+        assert!(value[2].as_f64().unwrap().is_nan());
+        let big = from_slice(&arena, b"(-123456789012345678901234567890\r\n").unwrap();
+        assert!(matches!(big, DataValue::Number(Number::BigInt(_))));
+        let bytes = from_slice(&arena, b"$2\r\n\xFF\x00\r\n").unwrap();
+        assert_eq!(bytes["$bytes"].as_str(), Some("/wA="));
+
+        for bad in [
+            &b""[..],
+            b"+OK",
+            b"?\r\n",
+            b":1.5\r\n",
+            b":\r\n",
+            b"#x\r\n",
+            b"$5\r\nab\r\n",
+            b"$2\r\nabc\r\n",
+            b"=3\r\ntxt\r\n",
+            b"*2\r\n:1\r\n",
+            b"*9999999999\r\n",
+            b"*?\r\n",
+            b"%1\r\n_\r\n:1\r\n",
+            b"!3\r\nERR\r\n",
+            b":1\r\n:2\r\n",
+        ] {
+            assert!(from_slice(&arena, bad).is_err(), "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let arena = Bump::new();
+        let options = crate::ParseOptions {
+            number_mode: crate::NumberMode::Arbitrary,
+            ..Default::default()
+        };
+        let text = r#"{
+            "numbers": [0, -1, 9223372036854775807, 18446744073709551616, 1.5, -0.1, 1e300,
+                        1.23456789012345678901234567890],
+            "misc": [null, true, false, "", "line\r\nbreak ü", {}, [], [[{"": [1]}]]],
+            "bytes": {"$bytes": "/wA="}
+        }"#;
+        let value = crate::from_str_with_options(&arena, text, &options).unwrap();
+        let bytes = to_vec(&value).unwrap();
+        assert_eq!(from_slice(&arena, &bytes).unwrap(), value);
+
+        let raw = DataValue::Raw(r#"{"k": [1, 2]}"#);
+        assert_eq!(
+            to_vec(&raw).unwrap(),
+            b"%1\r\n$1\r\nk\r\n*2\r\n:1\r\n:2\r\n"
+        );
+        assert_eq!(
+            to_vec(&DataValue::Number(Number::Raw("18446744073709551616"))).unwrap(),
+            b"(18446744073709551616\r\n"
+        );
+        assert_eq!(
+            to_vec(&DataValue::Number(Number::Float(f64::NEG_INFINITY))).unwrap(),
+            b",-inf\r\n"
+        );
+        let bad_bytes = from_str(&arena, r#"{"$bytes": "!"}"#).unwrap();
+        assert!(to_vec(&bad_bytes).is_err());
+    }
+}
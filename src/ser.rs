@@ -1,15 +1,48 @@
 //! Serialization functionality for DataValue
 //!
 //! This module provides serialization capabilities for DataValue, allowing conversion
-//! to JSON strings and integration with serde's serialization system.
+//! to JSON strings and integration with serde's serialization system. The
+//! [`ndjson`] module writes streams of values as newline-delimited JSON,
+//! [`to_value`] builds a DataValue from any `Serialize` type, and
+//! [`JsonSerializer`] writes any `Serialize` type as JSON text without one.
+//! A [`Formatter`] controls the layout of the text written by
+//! [`DataValue::to_writer_with_formatter`], and [`to_gron`] writes one
+//! assignment per value for grepping.
+//! With the `async` feature, [`DataValue::to_writer_async`] streams JSON text to
+//! a tokio `AsyncWrite`.
 
 use crate::datavalue::{DataValue, Number};
 use crate::error::{Error, Result};
+use crate::helpers::format_duration;
+use bumpalo::Bump;
+#[cfg(feature = "serde_json-compat")]
 use serde::ser::{Serialize, SerializeMap, SerializeSeq, Serializer};
+use std::cmp::Ordering;
+use std::fmt;
+use std::io::{self, Write};
+
+#[cfg(feature = "async")]
+mod async_writer;
+mod formatter;
+mod gron;
+pub mod ndjson;
+#[cfg(feature = "serde_json-compat")]
+mod stream;
+#[cfg(feature = "serde_json-compat")]
+mod value;
+
+pub use formatter::{CompactFormatter, Formatter, PrettyFormatter};
+pub use gron::to_gron;
+#[cfg(feature = "serde_json-compat")]
+pub use stream::{Compound, JsonSerializer};
+#[cfg(feature = "serde_json-compat")]
+pub use value::to_value;
 
 /// Converts a DataValue to a JSON string
 ///
-/// This produces a compact representation without extra whitespace.
+/// This produces a compact representation without extra whitespace. NaN and
+/// infinite floats are written as `null`; use [`to_string_with_options`] to reject
+/// them or write them as literals instead.
 ///
 /// # Example
 ///
@@ -28,10 +61,42 @@ pub fn to_string(value: &DataValue<'_>) -> String {
     format!("{}", value)
 }
 
+/// Converts a DataValue to canonical JSON as defined by RFC 8785, the JSON
+/// Canonicalization Scheme (JCS)
+///
+/// The output is compact, object keys are sorted by their UTF-16 code units,
+/// strings use the minimal JSON escaping and numbers are written as ECMAScript
+/// writes doubles: integral values without a fraction and very large or small
+/// values with an exponent. Semantically equal documents therefore produce
+/// identical bytes, suitable for hashing or signing.
+///
+/// JCS treats every number as an f64, so integers beyond 2<sup>53</sup> and
+/// arbitrary-precision numbers are written as the nearest f64. DateTime and
+/// Duration values are written as strings, and non-finite floats, which JCS
+/// cannot represent, as `null`. Repeated object keys are all kept.
+///
+/// # Example
+///
+/// ```
+/// # use datavalue_rs::{Bump, from_str, to_string_canonical};
+/// let arena = Bump::new();
+/// let value = from_str(&arena, r#"{"b": [1.0, 1e21, 0.0000001], "a": "\u00e9\n"}"#).unwrap();
+/// assert_eq!(
+///     to_string_canonical(&value),
+///     r#"{"a":"é\n","b":[1,1e+21,1e-7]}"#
+/// );
+/// ```
+pub fn to_string_canonical(value: &DataValue<'_>) -> String {
+    let mut result = String::new();
+    write_canonical(value, &mut result);
+    result
+}
+
 /// Converts a DataValue to a pretty-printed JSON string
 ///
 /// This produces a formatted representation with indentation and line breaks
-/// for improved readability.
+/// for improved readability. NaN and infinite floats are written as `null`, as in
+/// [`to_string`].
 ///
 /// # Example
 ///
@@ -51,73 +116,820 @@ pub fn to_string(value: &DataValue<'_>) -> String {
 pub fn to_string_pretty(value: &DataValue<'_>) -> String {
     // A simple pretty-printing implementation
     let mut result = String::new();
-    to_string_pretty_internal(value, 0, &mut result);
+    write_pretty(value, "  ", &mut result);
+    result
+}
+
+/// Converts a DataValue to a pretty-printed JSON string laid out by `config`
+///
+/// # Example
+///
+/// ```
+/// # use datavalue_rs::{Bump, from_str, to_string_pretty_with, Indent, PrettyConfig};
+/// let arena = Bump::new();
+/// let value = from_str(&arena, r#"{"name": "point", "at": [1, 2], "tags": []}"#).unwrap();
+///
+/// let config = PrettyConfig {
+///     indent: Indent::Tabs,
+///     inline_arrays: 4,
+///     trailing_newline: true,
+///     ..PrettyConfig::default()
+/// };
+/// assert_eq!(
+///     to_string_pretty_with(&value, &config),
+///     "{\n\t\"name\": \"point\",\n\t\"at\": [1, 2],\n\t\"tags\": []\n}\n"
+/// );
+/// ```
+pub fn to_string_pretty_with(value: &DataValue<'_>, config: &PrettyConfig) -> String {
+    let mut result = String::new();
+    let unit = match config.indent {
+        Indent::Spaces(width) => " ".repeat(width),
+        Indent::Tabs => "\t".to_string(),
+    };
+    let layout = Layout {
+        indent: Some(&unit),
+        tight_colon: !config.space_after_colon,
+        inline_arrays: config.inline_arrays,
+        ..Layout::default()
+    };
+    write_pretty_layout(value, layout, &mut result);
+    if config.trailing_newline {
+        result.push('\n');
+    }
     result
 }
 
-/// Internal helper function for pretty-printing
+/// Layout of the output of [`to_string_pretty_with`] and
+/// [`DataValue::to_writer_pretty_with`]
 ///
-/// Recursively formats the DataValue with proper indentation.
-fn to_string_pretty_internal(value: &DataValue<'_>, indent: usize, output: &mut String) {
-    let indent_str = "  ".repeat(indent);
+/// The default matches [`to_string_pretty`]: two-space indentation, a space after
+/// each colon, no trailing newline and every array element on its own line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrettyConfig {
+    /// One level of indentation
+    pub indent: Indent,
 
-    match value {
-        DataValue::Null => output.push_str("null"),
-        DataValue::Bool(b) => output.push_str(if *b { "true" } else { "false" }),
-        DataValue::Number(Number::Integer(i)) => output.push_str(&i.to_string()),
-        DataValue::Number(Number::Float(f)) => output.push_str(&f.to_string()),
-        DataValue::String(s) => {
-            output.push('"');
-            output.push_str(&s.replace('\"', "\\\""));
-            output.push('"');
-        }
-        DataValue::Array(arr) => {
-            if arr.is_empty() {
-                output.push_str("[]");
-                return;
-            }
-
-            output.push_str("[\n");
-            for (i, item) in arr.iter().enumerate() {
-                output.push_str(&"  ".repeat(indent + 1));
-                to_string_pretty_internal(item, indent + 1, output);
-                if i < arr.len() - 1 {
+    /// Write `"key": value` rather than `"key":value`
+    pub space_after_colon: bool,
+
+    /// End the output with a line break, as most editors and golden files do
+    pub trailing_newline: bool,
+
+    /// Write arrays of at most this many elements on one line, as `[1, 2, 3]`,
+    /// when none of the elements is a non-empty array or object
+    ///
+    /// `0` (the default) puts every element on its own line.
+    pub inline_arrays: usize,
+}
+
+impl Default for PrettyConfig {
+    fn default() -> Self {
+        PrettyConfig {
+            indent: Indent::Spaces(2),
+            space_after_colon: true,
+            trailing_newline: false,
+            inline_arrays: 0,
+        }
+    }
+}
+
+/// One level of indentation in pretty-printed output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Indent {
+    /// The given number of spaces
+    Spaces(usize),
+    /// One tab character
+    Tabs,
+}
+
+/// How the serializer treats keys that appear more than once in an object
+///
+/// Objects are stored as slices of entries, so repeated keys are representable
+/// and [`DataValue::get_all`] can read them back. RFC 8259 allows them but says
+/// names SHOULD be unique, and many consumers keep only one value.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RepeatedKeys {
+    /// Write every entry as stored, repeating the key
+    #[default]
+    Allow,
+    /// Fail with an error at the first repeated key
+    Reject,
+    /// Write the key once, at its first position, with its first value
+    FirstWins,
+    /// Write the key once, at its first position, with its last value
+    LastWins,
+    /// Write the key once, at its first position, with an array of all its values
+    Group,
+}
+
+/// How NaN and the infinities are written and read
+///
+/// JSON has no representation for non-finite numbers. JavaScript and JSON5 spell
+/// them `NaN`, `Infinity` and `-Infinity`, and some JSON producers emit those
+/// tokens, while most consumers reject them.
+///
+/// # Example
+///
+/// ```
+/// # use datavalue_rs::{helpers, to_string_with_options, NonFiniteFloats, SerializeOptions};
+/// let value = helpers::float(f64::NEG_INFINITY);
+///
+/// let strict = SerializeOptions::default();
+/// assert!(to_string_with_options(&value, &strict).is_err());
+///
+/// let null = SerializeOptions { non_finite: NonFiniteFloats::Null, ..SerializeOptions::default() };
+/// assert_eq!(to_string_with_options(&value, &null).unwrap(), "null");
+///
+/// let literal = SerializeOptions { non_finite: NonFiniteFloats::Literal, ..SerializeOptions::default() };
+/// assert_eq!(to_string_with_options(&value, &literal).unwrap(), "-Infinity");
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NonFiniteFloats {
+    /// Fail with an error
+    #[default]
+    Reject,
+    /// Write `null` in their place, or parse the tokens as `null`
+    Null,
+    /// Write and parse the tokens `NaN`, `Infinity` and `-Infinity`
+    Literal,
+}
+
+/// Returns the JavaScript token for a non-finite float
+pub(crate) fn non_finite_literal(f: f64) -> &'static str {
+    if f.is_nan() {
+        "NaN"
+    } else if f > 0.0 {
+        "Infinity"
+    } else {
+        "-Infinity"
+    }
+}
+
+/// Options for [`to_string_with_options`]
+#[derive(Debug, Clone, Default)]
+pub struct SerializeOptions {
+    /// Treatment of keys that appear more than once in an object
+    pub repeated_keys: RepeatedKeys,
+
+    /// Treatment of NaN and infinite floats, which JSON cannot represent
+    ///
+    /// Rejected by default, so the output is always valid JSON.
+    pub non_finite: NonFiniteFloats,
+
+    /// Write every non-ASCII character in strings and keys as a `\u` escape, using
+    /// a surrogate pair for characters outside the Basic Multilingual Plane
+    ///
+    /// The output is then plain ASCII, for consumers that mishandle UTF-8.
+    ///
+    /// ```
+    /// # use datavalue_rs::{Bump, from_str, to_string_with_options, SerializeOptions};
+    /// let arena = Bump::new();
+    /// let value = from_str(&arena, r#"{"café": "🚀"}"#).unwrap();
+    /// let options = SerializeOptions { escape_non_ascii: true, ..SerializeOptions::default() };
+    /// assert_eq!(
+    ///     to_string_with_options(&value, &options).unwrap(),
+    ///     r#"{"caf\u00e9":"\ud83d\ude80"}"#
+    /// );
+    /// ```
+    pub escape_non_ascii: bool,
+}
+
+/// Converts a DataValue to a compact JSON string with the given options
+///
+/// # Errors
+///
+/// Returns an error if `options.repeated_keys` is [`RepeatedKeys::Reject`] and an
+/// object contains a repeated key, or if `options.non_finite` is
+/// [`NonFiniteFloats::Reject`] and the value contains NaN or an infinity.
+///
+/// # Example
+///
+/// ```
+/// # use datavalue_rs::{Bump, from_str, to_string_with_options, RepeatedKeys, SerializeOptions};
+/// let arena = Bump::new();
+/// let query = from_str(&arena, r#"{"tag": "a", "page": 2, "tag": "b"}"#).unwrap();
+///
+/// let allow = SerializeOptions::default();
+/// assert_eq!(
+///     to_string_with_options(&query, &allow).unwrap(),
+///     r#"{"tag":"a","page":2,"tag":"b"}"#
+/// );
+///
+/// let group = SerializeOptions { repeated_keys: RepeatedKeys::Group, ..allow };
+/// assert_eq!(
+///     to_string_with_options(&query, &group).unwrap(),
+///     r#"{"tag":["a","b"],"page":2}"#
+/// );
+///
+/// let reject = SerializeOptions { repeated_keys: RepeatedKeys::Reject, ..allow };
+/// assert!(to_string_with_options(&query, &reject).is_err());
+/// ```
+pub fn to_string_with_options(value: &DataValue<'_>, options: &SerializeOptions) -> Result<String> {
+    let mut output = String::new();
+    write_with_options(value, options, &mut output)?;
+    Ok(output)
+}
+
+fn write_with_options(
+    value: &DataValue<'_>,
+    options: &SerializeOptions,
+    output: &mut String,
+) -> Result<()> {
+    /// What to write for an array element or object entry
+    enum Item<'v, 'a> {
+        Value(&'v DataValue<'a>),
+        /// The values of a repeated key, written as one array
+        Group(Vec<&'v DataValue<'a>>),
+    }
+    type Items<'v, 'a> = std::vec::IntoIter<(Option<&'v str>, Item<'v, 'a>)>;
+
+    // Open arrays and objects: the items left to write, the closing bracket and
+    // whether no item has been written yet
+    let mut stack: Vec<(Items<'_, '_>, char, bool)> = Vec::new();
+    let mut next = Some(Item::Value(value));
+    loop {
+        match next.take() {
+            Some(Item::Value(DataValue::Array(arr))) => {
+                let items: Vec<_> = arr.iter().map(|item| (None, Item::Value(item))).collect();
+                output.push('[');
+                stack.push((items.into_iter(), ']', true));
+            }
+            Some(Item::Value(DataValue::Object(obj)))
+                if options.repeated_keys == RepeatedKeys::Allow =>
+            {
+                let items: Vec<_> = obj
+                    .iter()
+                    .map(|(key, value)| (Some(*key), Item::Value(value)))
+                    .collect();
+                output.push('{');
+                stack.push((items.into_iter(), '}', true));
+            }
+            Some(Item::Value(DataValue::Object(obj))) => {
+                // Group values by key, keeping keys in order of first appearance
+                let mut groups: Vec<(&str, Vec<&DataValue<'_>>)> = Vec::new();
+                for (key, value) in obj.iter() {
+                    match groups.iter_mut().find(|(k, _)| k == key) {
+                        Some(_) if options.repeated_keys == RepeatedKeys::Reject => {
+                            return Err(Error::custom(format!("Repeated key {}", key)))
+                        }
+                        Some((_, values)) => values.push(value),
+                        None => groups.push((key, vec![value])),
+                    }
+                }
+                let items: Vec<_> = groups
+                    .into_iter()
+                    .map(|(key, values)| {
+                        let item = match options.repeated_keys {
+                            RepeatedKeys::Group if values.len() > 1 => Item::Group(values),
+                            RepeatedKeys::LastWins => Item::Value(values[values.len() - 1]),
+                            _ => Item::Value(values[0]),
+                        };
+                        (Some(key), item)
+                    })
+                    .collect();
+                output.push('{');
+                stack.push((items.into_iter(), '}', true));
+            }
+            Some(Item::Group(values)) => {
+                let items: Vec<_> = values
+                    .into_iter()
+                    .map(|value| (None, Item::Value(value)))
+                    .collect();
+                output.push('[');
+                stack.push((items.into_iter(), ']', true));
+            }
+            Some(Item::Value(DataValue::Number(Number::Float(f)))) if !f.is_finite() => {
+                match options.non_finite {
+                    NonFiniteFloats::Reject => {
+                        return Err(Error::custom(format!("Cannot serialize {} as JSON", f)))
+                    }
+                    NonFiniteFloats::Null => output.push_str("null"),
+                    NonFiniteFloats::Literal => output.push_str(non_finite_literal(*f)),
+                }
+            }
+            Some(Item::Value(scalar)) => {
+                let start = output.len();
+                output.push_str(&scalar.to_string());
+                if options.escape_non_ascii {
+                    escape_non_ascii_from(output, start);
+                }
+            }
+            None => {}
+        }
+
+        let Some((items, close, first)) = stack.last_mut() else {
+            return Ok(());
+        };
+        match items.next() {
+            Some((key, item)) => {
+                if !std::mem::take(first) {
                     output.push(',');
                 }
-                output.push('\n');
+                if let Some(key) = key {
+                    let start = output.len();
+                    push_json_string(key, output);
+                    if options.escape_non_ascii {
+                        escape_non_ascii_from(output, start);
+                    }
+                    output.push(':');
+                }
+                next = Some(item);
+            }
+            None => {
+                output.push(*close);
+                stack.pop();
+            }
+        }
+    }
+}
+
+/// Replaces the non-ASCII characters in `output[start..]` with `\u` escapes
+///
+/// The text is a JSON token, so such characters can only be inside a string,
+/// where the escape means the same character.
+fn escape_non_ascii_from(output: &mut String, start: usize) {
+    if output[start..].is_ascii() {
+        return;
+    }
+    let tail = output.split_off(start);
+    for c in tail.chars() {
+        if c.is_ascii() {
+            output.push(c);
+        } else {
+            for unit in c.encode_utf16(&mut [0; 2]) {
+                output.push_str(&format!("\\u{:04x}", unit));
             }
-            output.push_str(&indent_str);
-            output.push(']');
         }
-        DataValue::Object(obj) => {
-            if obj.is_empty() {
-                output.push_str("{}");
-                return;
+    }
+}
+
+/// How [`write_tree`] lays out arrays and objects
+#[derive(Clone, Copy, Default)]
+pub(crate) struct Layout<'u> {
+    /// One level of indentation for one entry per line, or None for compact output
+    pub(crate) indent: Option<&'u str>,
+    /// Order to write object keys in, instead of the order they are stored in
+    pub(crate) key_order: Option<fn(&str, &str) -> Ordering>,
+    /// Write `:` without the space that otherwise follows it when indenting
+    pub(crate) tight_colon: bool,
+    /// Arrays of at most this many scalars are written on one line when indenting
+    pub(crate) inline_arrays: usize,
+}
+
+/// An array or object being written by [`write_tree`]
+enum Frame<'v, 'a> {
+    Array(std::slice::Iter<'v, DataValue<'a>>),
+    /// An array written on one line inside indented output
+    Inline(std::slice::Iter<'v, DataValue<'a>>),
+    Object(std::slice::Iter<'v, (&'a str, DataValue<'a>)>),
+    Sorted(std::vec::IntoIter<&'v (&'a str, DataValue<'a>)>),
+}
+
+/// Writes the JSON text of a value, keeping open arrays and objects on an
+/// explicit stack so that nesting depth does not consume thread stack
+///
+/// Brackets, commas and whitespace are written here; `scalar` writes everything
+/// else, including empty arrays and objects, and `key` writes the quoted key of an
+/// object entry.
+pub(crate) fn write_tree<'v, 'a, W: fmt::Write + ?Sized>(
+    value: &'v DataValue<'a>,
+    layout: Layout<'_>,
+    output: &mut W,
+    mut scalar: impl FnMut(&'v DataValue<'a>, &mut W) -> fmt::Result,
+    mut key: impl FnMut(&'v (&'a str, DataValue<'a>), &mut W) -> fmt::Result,
+) -> fmt::Result {
+    let mut writer = TreeWriter::new(value, layout);
+    while writer.step(output, &mut scalar, &mut key)? {}
+    Ok(())
+}
+
+/// The state of [`write_tree`], which writes a tree a step at a time so that
+/// callers can hand off the output between steps
+pub(crate) struct TreeWriter<'v, 'a, 'u> {
+    layout: Layout<'u>,
+    stack: Vec<(Frame<'v, 'a>, bool)>,
+    next: Option<&'v DataValue<'a>>,
+}
+
+impl<'v, 'a, 'u> TreeWriter<'v, 'a, 'u> {
+    pub(crate) fn new(value: &'v DataValue<'a>, layout: Layout<'u>) -> Self {
+        TreeWriter {
+            layout,
+            stack: Vec::new(),
+            next: Some(value),
+        }
+    }
+
+    /// Writes the next value, or opens it if it is a non-empty array or object,
+    /// followed by the separators or closing brackets after it
+    ///
+    /// Returns false once the whole tree has been written.
+    pub(crate) fn step<W: fmt::Write + ?Sized>(
+        &mut self,
+        output: &mut W,
+        scalar: &mut impl FnMut(&'v DataValue<'a>, &mut W) -> fmt::Result,
+        key: &mut impl FnMut(&'v (&'a str, DataValue<'a>), &mut W) -> fmt::Result,
+    ) -> std::result::Result<bool, fmt::Error> {
+        let layout = self.layout;
+        let newline = |output: &mut W, level: usize| -> fmt::Result {
+            if let Some(unit) = layout.indent {
+                output.write_char('\n')?;
+                for _ in 0..level {
+                    output.write_str(unit)?;
+                }
+            }
+            Ok(())
+        };
+        match self.next.take() {
+            Some(DataValue::Array(items)) if !items.is_empty() => {
+                output.write_char('[')?;
+                let inline = layout.indent.is_some()
+                    && items.len() <= layout.inline_arrays
+                    && items.iter().all(|item| match item {
+                        DataValue::Array(a) => a.is_empty(),
+                        DataValue::Object(o) => o.is_empty(),
+                        _ => true,
+                    });
+                let frame = if inline {
+                    Frame::Inline(items.iter())
+                } else {
+                    Frame::Array(items.iter())
+                };
+                self.stack.push((frame, true));
+            }
+            Some(DataValue::Object(entries)) if !entries.is_empty() => {
+                output.write_char('{')?;
+                let frame = match layout.key_order {
+                    Some(order) => {
+                        let mut sorted: Vec<_> = entries.iter().collect();
+                        sorted.sort_by(|(a, _), (b, _)| order(a, b));
+                        Frame::Sorted(sorted.into_iter())
+                    }
+                    None => Frame::Object(entries.iter()),
+                };
+                self.stack.push((frame, true));
             }
+            Some(leaf) => scalar(leaf, output)?,
+            None => {}
+        }
 
-            output.push_str("{\n");
-            for (i, (key, value)) in obj.iter().enumerate() {
-                output.push_str(&"  ".repeat(indent + 1));
-                output.push('"');
-                output.push_str(&key.replace('\"', "\\\""));
-                output.push_str("\": ");
-                to_string_pretty_internal(value, indent + 1, output);
-                if i < obj.len() - 1 {
-                    output.push(',');
+        let level = self.stack.len();
+        let Some((frame, first)) = self.stack.last_mut() else {
+            return Ok(false);
+        };
+        let inline = matches!(frame, Frame::Inline(_));
+        let (entry, item) = match frame {
+            Frame::Array(items) | Frame::Inline(items) => (None, items.next()),
+            Frame::Object(entries) => match entries.next() {
+                Some(entry) => (Some(entry), Some(&entry.1)),
+                None => (None, None),
+            },
+            Frame::Sorted(entries) => match entries.next() {
+                Some(entry) => (Some(entry), Some(&entry.1)),
+                None => (None, None),
+            },
+        };
+        match item {
+            Some(item) => {
+                let first = std::mem::take(first);
+                if !first {
+                    output.write_char(',')?;
+                }
+                if !inline {
+                    newline(output, level)?;
+                } else if !first {
+                    output.write_char(' ')?;
+                }
+                if let Some(entry) = entry {
+                    key(entry, output)?;
+                    let spaced = layout.indent.is_some() && !layout.tight_colon;
+                    output.write_str(if spaced { ": " } else { ":" })?;
                 }
-                output.push('\n');
+                self.next = Some(item);
+            }
+            None => {
+                let close = match frame {
+                    Frame::Array(_) | Frame::Inline(_) => ']',
+                    _ => '}',
+                };
+                self.stack.pop();
+                if !inline {
+                    newline(output, level - 1)?;
+                }
+                output.write_char(close)?;
+            }
+        }
+        Ok(true)
+    }
+}
+
+/// Pretty-prints a DataValue using `unit` as one level of indentation
+pub(crate) fn write_pretty(value: &DataValue<'_>, unit: &str, output: &mut String) {
+    let layout = Layout {
+        indent: Some(unit),
+        ..Layout::default()
+    };
+    write_pretty_layout(value, layout, output);
+}
+
+/// Pretty-prints a DataValue with the given layout
+fn write_pretty_layout(value: &DataValue<'_>, layout: Layout<'_>, output: &mut String) {
+    // Writing to a String cannot fail
+    let _ = write_tree(
+        value,
+        layout,
+        output,
+        |value, output| {
+            match value {
+                DataValue::String(s) => push_json_string(s, output),
+                DataValue::DateTime(dt) => push_json_string(&dt.to_rfc3339(), output),
+                DataValue::Duration(dur) => push_json_string(&format_duration(dur), output),
+                scalar => output.push_str(&scalar.to_string()),
+            }
+            Ok(())
+        },
+        |(key, _), output| {
+            push_json_string(key, output);
+            Ok(())
+        },
+    );
+}
+
+/// Writes the canonical form of a DataValue used for hashing and signing
+///
+/// See [`to_string_canonical`] for the format.
+pub(crate) fn write_canonical(value: &DataValue<'_>, output: &mut String) {
+    write_canonical_layout(value, None, output);
+}
+
+/// Writes the canonical form of a DataValue with `unit` as one level of indentation
+///
+/// Used where canonical output is also read by people, such as snapshot files.
+#[cfg_attr(not(feature = "testing"), allow(dead_code))]
+pub(crate) fn write_canonical_pretty(value: &DataValue<'_>, unit: &str, output: &mut String) {
+    write_canonical_layout(value, Some(unit), output);
+}
+
+fn write_canonical_layout(value: &DataValue<'_>, indent: Option<&str>, output: &mut String) {
+    let layout = Layout {
+        indent,
+        key_order: Some(|a, b| a.encode_utf16().cmp(b.encode_utf16())),
+        ..Layout::default()
+    };
+    // Writing to a String cannot fail
+    let _ = write_tree(
+        value,
+        layout,
+        output,
+        |value, output| {
+            write_canonical_scalar(value, output);
+            Ok(())
+        },
+        |(key, _), output| {
+            push_json_string(key, output);
+            Ok(())
+        },
+    );
+}
+
+fn write_canonical_scalar(value: &DataValue<'_>, output: &mut String) {
+    match value {
+        // Canonical output is numeric, so `1.0`, `1` and a raw `1e0` produce the same
+        // bytes, and numbers beyond the exact range of an f64 are written as the f64
+        // a JavaScript reader would see
+        DataValue::Number(n) => match n.parsed() {
+            Number::Integer(i) if i.unsigned_abs() <= MAX_SAFE_INTEGER => {
+                output.push_str(&i.to_string())
+            }
+            Number::Integer(i) => push_es_number(i as f64, output),
+            Number::Float(f) if !f.is_finite() => output.push_str("null"),
+            Number::Float(f) => push_es_number(f, output),
+            _ => unreachable!("parsed numbers are integers or floats"),
+        },
+        DataValue::String(s) => push_json_string(s, output),
+        DataValue::DateTime(dt) => push_json_string(&dt.to_rfc3339(), output),
+        DataValue::Duration(dur) => push_json_string(&format_duration(dur), output),
+        // Raw text is canonicalized like the value it parses to
+        DataValue::Raw(text) => {
+            let arena = Bump::new();
+            match value.parse_raw(&arena) {
+                Ok(parsed) => write_canonical(&parsed, output),
+                Err(_) => output.push_str(text),
             }
-            output.push_str(&indent_str);
-            output.push('}');
         }
-        DataValue::DateTime(dt) => output.push_str(&dt.to_rfc3339()),
-        DataValue::Duration(dur) => output.push_str(&dur.to_string()),
+        scalar => output.push_str(&scalar.to_string()),
+    }
+}
+
+/// Largest integer magnitude up to which every integer is exactly an f64
+const MAX_SAFE_INTEGER: u64 = (1 << 53) - 1;
+
+/// Appends a finite float formatted like ECMAScript's `Number.prototype.toString`,
+/// as RFC 8785 requires
+///
+/// The shortest digits that round-trip are written in plain notation for decimal
+/// exponents from -6 to 20, and in exponent notation such as `1e+21` or `1.5e-7`
+/// outside that range. Negative zero is written as `0`.
+fn push_es_number(f: f64, output: &mut String) {
+    if f == 0.0 {
+        output.push('0');
+        return;
+    }
+    if f < 0.0 {
+        output.push('-');
+    }
+    // `{:e}` gives the shortest round-trip digits as `d.ddde<exp>`
+    let scientific = format!("{:e}", f.abs());
+    let (mantissa, exponent) = scientific.split_once('e').unwrap_or((&scientific, "0"));
+    let mut digits: String = mantissa.chars().filter(|c| *c != '.').collect();
+    round_half_even(f.abs(), &mut digits, exponent);
+    let k = digits.len() as i32;
+    // The value is 0.<digits> * 10^n
+    let n = exponent.parse::<i32>().unwrap_or(0) + 1;
+
+    if k <= n && n <= 21 {
+        output.push_str(&digits);
+        output.extend(std::iter::repeat_n('0', (n - k) as usize));
+    } else if 0 < n && n <= 21 {
+        output.push_str(&digits[..n as usize]);
+        output.push('.');
+        output.push_str(&digits[n as usize..]);
+    } else if -6 < n && n <= 0 {
+        output.push_str("0.");
+        output.extend(std::iter::repeat_n('0', (-n) as usize));
+        output.push_str(&digits);
+    } else {
+        output.push_str(&digits[..1]);
+        if k > 1 {
+            output.push('.');
+            output.push_str(&digits[1..]);
+        }
+        output.push('e');
+        output.push(if n > 0 { '+' } else { '-' });
+        output.push_str(&(n - 1).abs().to_string());
+    }
+}
+
+/// Rust rounds a tie between two shortest candidates up, where ECMAScript picks the
+/// even digit. A tie means the exact value ends in a 5 right after the last digit, so
+/// an odd last digit is stepped down when that is the case.
+fn round_half_even(f: f64, digits: &mut String, exponent: &str) {
+    let Some(last) = digits.pop() else {
+        return;
+    };
+    let last = last as u8 - b'0';
+    if last % 2 == 1 {
+        // 767 fractional digits hold the exact expansion of every f64
+        let exact = format!("{:.767e}", f);
+        let (mantissa, exact_exponent) = exact.split_once('e').unwrap_or((&exact, "0"));
+        let exact_digits: String = mantissa.chars().filter(|c| *c != '.').collect();
+        let exact_digits = exact_digits.trim_end_matches('0');
+        let lower = char::from(b'0' + last - 1);
+        if exact_exponent == exponent && exact_digits == format!("{}{}5", digits, lower) {
+            digits.push(lower);
+            return;
+        }
+    }
+    digits.push(char::from(b'0' + last));
+}
+
+/// Adapts an `io::Write` to the `fmt::Write` that [`write_tree`] writes to,
+/// keeping the I/O error that `fmt::Error` cannot carry
+struct IoWriter<'w, W: ?Sized> {
+    writer: &'w mut W,
+    error: Option<io::Error>,
+}
+
+impl<W: Write + ?Sized> IoWriter<'_, W> {
+    fn check(&mut self, result: io::Result<()>) -> fmt::Result {
+        result.map_err(|e| {
+            self.error = Some(e);
+            fmt::Error
+        })
+    }
+}
+
+impl<W: Write + ?Sized> fmt::Write for IoWriter<'_, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let result = self.writer.write_all(s.as_bytes());
+        self.check(result)
+    }
+}
+
+/// Writes the compact JSON form of a DataValue straight to a writer
+///
+/// Strings and keys get full RFC 8259 escaping, DateTime and Duration values are
+/// written as quoted strings, and non-finite floats as `null`, so the output is
+/// always valid JSON.
+pub(crate) fn write_json<W: Write + ?Sized>(
+    value: &DataValue<'_>,
+    writer: &mut W,
+) -> io::Result<()> {
+    let mut output = IoWriter {
+        writer,
+        error: None,
+    };
+    let written = write_tree(
+        value,
+        Layout::default(),
+        &mut output,
+        |value, output| {
+            let result = write_json_scalar(value, output.writer);
+            output.check(result)
+        },
+        |(key, _), output| {
+            let result = write_json_string(key, output.writer);
+            output.check(result)
+        },
+    );
+    match (written, output.error) {
+        (Err(_), Some(e)) => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Writes a value that is not a non-empty array or object as compact JSON
+pub(crate) fn write_json_scalar<W: Write + ?Sized>(
+    value: &DataValue<'_>,
+    writer: &mut W,
+) -> io::Result<()> {
+    match value {
+        DataValue::Number(Number::Float(f)) if !f.is_finite() => writer.write_all(b"null"),
+        DataValue::String(s) => write_json_string(s, writer),
+        DataValue::DateTime(dt) => write_json_string(&dt.to_rfc3339(), writer),
+        DataValue::Duration(dur) => write_json_string(&format_duration(dur), writer),
+        scalar => write!(writer, "{}", scalar),
+    }
+}
+
+/// Writes a JSON string literal, escaping quotes, backslashes and control characters
+fn write_json_string<W: Write + ?Sized>(s: &str, writer: &mut W) -> io::Result<()> {
+    let mut output = IoWriter {
+        writer,
+        error: None,
+    };
+    match fmt_json_string(s, &mut output) {
+        Err(_) => Err(output
+            .error
+            .unwrap_or_else(|| io::Error::other("Failed to format JSON"))),
+        Ok(()) => Ok(()),
     }
 }
 
+/// Formats a JSON string literal with full RFC 8259 escaping: quotes,
+/// backslashes and control characters are escaped, using the short forms where
+/// JSON has them and `\u00XX` otherwise
+pub(crate) fn fmt_json_string<W: fmt::Write + ?Sized>(s: &str, output: &mut W) -> fmt::Result {
+    output.write_char('"')?;
+    let mut run_start = 0;
+    for (i, b) in s.bytes().enumerate() {
+        let escape = match b {
+            b'"' => "\\\"",
+            b'\\' => "\\\\",
+            b'\n' => "\\n",
+            b'\r' => "\\r",
+            b'\t' => "\\t",
+            0x08 => "\\b",
+            0x0c => "\\f",
+            0x00..=0x1f => {
+                output.write_str(&s[run_start..i])?;
+                write!(output, "\\u{:04x}", b)?;
+                run_start = i + 1;
+                continue;
+            }
+            _ => continue,
+        };
+        output.write_str(&s[run_start..i])?;
+        output.write_str(escape)?;
+        run_start = i + 1;
+    }
+    output.write_str(&s[run_start..])?;
+    output.write_char('"')
+}
+
+/// Writes a float in the shortest form that parses back to the same value, as
+/// serde_json does: `1.0`, `0.1`, `1e300`
+///
+/// NaN and the infinities, which JSON cannot represent, are written as `null`, as
+/// with [`NonFiniteFloats::Null`], so the output is always valid JSON.
+pub(crate) fn fmt_f64<W: fmt::Write + ?Sized>(f: f64, output: &mut W) -> fmt::Result {
+    if f.is_finite() {
+        output.write_str(ryu::Buffer::new().format_finite(f))
+    } else {
+        output.write_str("null")
+    }
+}
+
+/// Appends a JSON string literal with standard escaping
+pub(crate) fn push_json_string(s: &str, output: &mut String) {
+    // Writing to a String cannot fail
+    let _ = fmt_json_string(s, output);
+}
+
 /// Implementation of serde's Serialize trait for DataValue
 ///
 /// This allows DataValue to be used with serde's serialization framework.
+#[cfg(feature = "serde_json-compat")]
 impl Serialize for DataValue<'_> {
     fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
     where
@@ -128,6 +940,16 @@ impl Serialize for DataValue<'_> {
             DataValue::Bool(b) => serializer.serialize_bool(*b),
             DataValue::Number(Number::Integer(i)) => serializer.serialize_i64(*i),
             DataValue::Number(Number::Float(f)) => serializer.serialize_f64(*f),
+            DataValue::Number(n @ Number::Raw(_)) => {
+                DataValue::Number(n.parsed()).serialize(serializer)
+            }
+            #[cfg(feature = "bignum")]
+            DataValue::Number(Number::BigInt(n)) => match n.to_string().parse::<u64>() {
+                Ok(u) => serializer.serialize_u64(u),
+                Err(_) => serializer.serialize_f64(n.to_f64()),
+            },
+            #[cfg(feature = "bignum")]
+            DataValue::Number(Number::BigDecimal(n)) => serializer.serialize_f64(n.to_f64()),
             DataValue::String(s) => serializer.serialize_str(s),
             DataValue::Array(arr) => {
                 let mut seq = serializer.serialize_seq(Some(arr.len()))?;
@@ -144,7 +966,13 @@ impl Serialize for DataValue<'_> {
                 map.end()
             }
             DataValue::DateTime(dt) => serializer.serialize_str(&dt.to_rfc3339()),
-            DataValue::Duration(dur) => serializer.serialize_str(&dur.to_string()),
+            DataValue::Duration(dur) => serializer.serialize_str(&format_duration(dur)),
+            DataValue::Raw(_) => {
+                let arena = Bump::new();
+                self.parse_raw(&arena)
+                    .map_err(serde::ser::Error::custom)?
+                    .serialize(serializer)
+            }
         }
     }
 }
@@ -153,25 +981,398 @@ impl Serialize for DataValue<'_> {
 impl DataValue<'_> {
     /// Serialize to a writer
     ///
-    /// Writes the compact JSON representation of this value to the given writer.
+    /// Writes the compact JSON representation of this value to the given writer,
+    /// using [`CompactFormatter`].
     ///
     /// # Errors
     ///
     /// Returns an error if writing to the writer fails.
-    pub fn to_writer<W: std::io::Write>(&self, mut writer: W) -> Result<()> {
-        let s = format!("{}", self);
-        writer.write_all(s.as_bytes()).map_err(Error::from)
+    pub fn to_writer<W: std::io::Write>(&self, writer: W) -> Result<()> {
+        self.to_writer_with_formatter(writer, &mut CompactFormatter)
     }
 
     /// Serialize to a writer with pretty-printing
     ///
-    /// Writes the pretty-printed JSON representation of this value to the given writer.
+    /// Writes the pretty-printed JSON representation of this value to the given
+    /// writer, using [`PrettyFormatter`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing to the writer fails.
+    pub fn to_writer_pretty<W: std::io::Write>(&self, writer: W) -> Result<()> {
+        self.to_writer_with_formatter(writer, &mut PrettyFormatter::new())
+    }
+
+    /// Serialize to a writer, with `formatter` deciding how each token is written
+    ///
+    /// The value is written as it is walked, without building the text in memory
+    /// first. See [`Formatter`] for an example.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing to the writer fails.
+    pub fn to_writer_with_formatter<W: std::io::Write, F: Formatter + ?Sized>(
+        &self,
+        mut writer: W,
+        formatter: &mut F,
+    ) -> Result<()> {
+        formatter::write_formatted(self, &mut writer, formatter).map_err(Error::from)
+    }
+
+    /// Serialize to a writer with pretty-printing laid out by `config`
+    ///
+    /// Writes the output of [`to_string_pretty_with`] to the given writer.
     ///
     /// # Errors
     ///
     /// Returns an error if writing to the writer fails.
-    pub fn to_writer_pretty<W: std::io::Write>(&self, mut writer: W) -> Result<()> {
-        let s = to_string_pretty(self);
+    pub fn to_writer_pretty_with<W: std::io::Write>(
+        &self,
+        mut writer: W,
+        config: &PrettyConfig,
+    ) -> Result<()> {
+        let s = to_string_pretty_with(self, config);
         writer.write_all(s.as_bytes()).map_err(Error::from)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_str;
+    use bumpalo::Bump;
+
+    #[test]
+    fn test_repeated_keys() {
+        let arena = Bump::new();
+        let value = from_str(
+            &arena,
+            r#"[{"a": 1, "b": {"c": 1, "c": 2}, "a": 3, "a": 4}]"#,
+        )
+        .unwrap();
+        let cases = [
+            (
+                RepeatedKeys::Allow,
+                r#"[{"a":1,"b":{"c":1,"c":2},"a":3,"a":4}]"#,
+            ),
+            (RepeatedKeys::FirstWins, r#"[{"a":1,"b":{"c":1}}]"#),
+            (RepeatedKeys::LastWins, r#"[{"a":4,"b":{"c":2}}]"#),
+            (RepeatedKeys::Group, r#"[{"a":[1,3,4],"b":{"c":[1,2]}}]"#),
+        ];
+        for (repeated_keys, expected) in cases {
+            let options = SerializeOptions {
+                repeated_keys,
+                ..SerializeOptions::default()
+            };
+            assert_eq!(to_string_with_options(&value, &options).unwrap(), expected);
+        }
+
+        let options = SerializeOptions {
+            repeated_keys: RepeatedKeys::Reject,
+            ..SerializeOptions::default()
+        };
+        assert!(to_string_with_options(&value, &options).is_err());
+        let unique = from_str(&arena, r#"{"a": 1, "b": [1, 1]}"#).unwrap();
+        assert_eq!(
+            to_string_with_options(&unique, &options).unwrap(),
+            r#"{"a":1,"b":[1,1]}"#
+        );
+    }
+
+    #[test]
+    fn test_string_escaping() {
+        let arena = Bump::new();
+        let text = "quote\" backslash\\ slash/ \n\r\t\u{8}\u{c}\u{0}\u{1f}\u{7f} é 🚀";
+        let key = "k\"\\\n\u{1}";
+        let value = DataValue::Object(arena.alloc_slice_fill_iter([(
+            key,
+            DataValue::Array(arena.alloc_slice_fill_iter([DataValue::String(text)])),
+        )]));
+
+        let compact = to_string(&value);
+        assert_eq!(
+            compact,
+            "{\"k\\\"\\\\\\n\\u0001\":[\"quote\\\" backslash\\\\ slash/ \\n\\r\\t\\b\\f\\u0000\\u001f\u{7f} é 🚀\"]}"
+        );
+        for output in [
+            compact,
+            to_string_pretty(&value),
+            to_string_with_options(&value, &SerializeOptions::default()).unwrap(),
+        ] {
+            let parsed = from_str(&arena, &output).unwrap();
+            assert_eq!(parsed, value, "{}", output);
+            assert_eq!(parsed[key][0].as_str(), Some(text));
+        }
+    }
+
+    #[test]
+    fn test_temporal_values() {
+        use crate::{from_str_with_options, helpers, ParseOptions};
+        use chrono::Duration;
+
+        let arena = Bump::new();
+        let value = helpers::array(
+            &arena,
+            vec![
+                helpers::datetime("2023-05-01T12:30:00.5Z").unwrap(),
+                DataValue::Duration(Duration::seconds(-90)),
+            ],
+        );
+        let compact = value.to_string();
+        assert_eq!(compact, r#"["2023-05-01T12:30:00.500+00:00","-PT1M30S"]"#);
+        let mut written = Vec::new();
+        value.to_writer(&mut written).unwrap();
+        assert_eq!(written, compact.as_bytes());
+
+        let options = ParseOptions {
+            revive_datetimes: true,
+            ..ParseOptions::default()
+        };
+        for output in [
+            compact,
+            to_string_pretty(&value),
+            to_string_with_options(&value, &SerializeOptions::default()).unwrap(),
+        ] {
+            let plain = from_str(&arena, &output).unwrap();
+            assert_eq!(plain[0].as_str(), Some("2023-05-01T12:30:00.500+00:00"));
+            assert_eq!(plain[1].as_duration(), Some(Duration::seconds(-90)));
+
+            let revived = from_str_with_options(&arena, &output, &options).unwrap();
+            assert_eq!(revived, value, "{}", output);
+        }
+    }
+
+    #[test]
+    fn test_canonical() {
+        // The example from RFC 8785 section 3.2.2
+        let arena = Bump::new();
+        let input = r#"{
+            "numbers": [333333333.33333329, 1E30, 4.50, 2e-3, 0.000000000000000000000000001],
+            "string": "\u20ac$\u000F\u000aA'\u0042\u0022\u005c\\\"\/",
+            "literals": [null, true, false]
+        }"#;
+        let value = from_str(&arena, input).unwrap();
+        assert_eq!(
+            to_string_canonical(&value),
+            r#"{"literals":[null,true,false],"numbers":[333333333.3333333,1e+30,4.5,0.002,1e-27],"string":"€$\u000f\nA'B\"\\\\\"/"}"#
+        );
+
+        // Keys sort by UTF-16 code units, so the emoji comes before U+FB33
+        let keys = r#"{"\u20ac": 1, "\r": 2, "\ufb33": 3, "1": 4, "\ud83d\ude00": 5, "\u0080": 6, "\u00f6": 7}"#;
+        let value = from_str(&arena, keys).unwrap();
+        assert_eq!(
+            to_string_canonical(&value),
+            "{\"\\r\":2,\"1\":4,\"\u{80}\":6,\"ö\":7,\"€\":1,\"😀\":5,\"\u{fb33}\":3}"
+        );
+
+        let numbers = [
+            ("0", "0"),
+            ("-0.0", "0"),
+            ("1.0", "1"),
+            ("-1.5", "-1.5"),
+            ("1e20", "100000000000000000000"),
+            ("1e21", "1e+21"),
+            ("0.000001", "0.000001"),
+            ("0.0000001", "1e-7"),
+            ("123e-20", "1.23e-18"),
+            ("5e-324", "5e-324"),
+            ("1.7976931348623157e308", "1.7976931348623157e+308"),
+            ("9007199254740991", "9007199254740991"),
+            ("-9007199254740993", "-9007199254740992"),
+            ("295147905179352830000", "295147905179352830000"),
+        ];
+        for (input, expected) in numbers {
+            let value = from_str(&arena, input).unwrap();
+            assert_eq!(to_string_canonical(&value), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_canonical_rfc8785_numbers() {
+        // The IEEE 754 vectors from RFC 8785 appendix B. The RFC rejects NaN and
+        // Infinity, which canonical output writes as null like every other writer.
+        let vectors = [
+            (0x0000000000000000, "0"),
+            (0x8000000000000000, "0"),
+            (0x0000000000000001, "5e-324"),
+            (0x8000000000000001, "-5e-324"),
+            (0x7fefffffffffffff, "1.7976931348623157e+308"),
+            (0xffefffffffffffff, "-1.7976931348623157e+308"),
+            (0x4340000000000000, "9007199254740992"),
+            (0xc340000000000000, "-9007199254740992"),
+            (0x4430000000000000, "295147905179352830000"),
+            (0x7fffffffffffffff, "null"),
+            (0x7ff0000000000000, "null"),
+            (0x44b52d02c7e14af5, "9.999999999999997e+22"),
+            (0x44b52d02c7e14af6, "1e+23"),
+            (0x44b52d02c7e14af7, "1.0000000000000001e+23"),
+            (0x444b1ae4d6e2ef4e, "999999999999999700000"),
+            (0x444b1ae4d6e2ef4f, "999999999999999900000"),
+            (0x444b1ae4d6e2ef50, "1e+21"),
+            (0x3eb0c6f7a0b5ed8c, "9.999999999999997e-7"),
+            (0x3eb0c6f7a0b5ed8d, "0.000001"),
+            (0x41b3de4355555553, "333333333.3333332"),
+            (0x41b3de4355555554, "333333333.33333325"),
+            (0x41b3de4355555555, "333333333.3333333"),
+            (0x41b3de4355555556, "333333333.3333334"),
+            (0x41b3de4355555557, "333333333.33333343"),
+            (0xbecbf647612f3696, "-0.0000033333333333333333"),
+            // A tie between two shortest candidates takes the even digit
+            (0x43143ff3c1cb0959, "1424953923781206.2"),
+        ];
+        for (bits, expected) in vectors {
+            let value = DataValue::Number(Number::Float(f64::from_bits(bits)));
+            assert_eq!(to_string_canonical(&value), expected, "{:016x}", bits);
+        }
+    }
+
+    #[test]
+    fn test_pretty_config() {
+        let arena = Bump::new();
+        let value = from_str(
+            &arena,
+            r#"{"a": [1, "x", [], {}], "b": [[1], 2], "c": {"d": [true, null, 3]}, "e": []}"#,
+        )
+        .unwrap();
+
+        // The default config matches to_string_pretty
+        assert_eq!(
+            to_string_pretty_with(&value, &PrettyConfig::default()),
+            to_string_pretty(&value)
+        );
+
+        let config = PrettyConfig {
+            indent: Indent::Spaces(4),
+            space_after_colon: false,
+            trailing_newline: true,
+            inline_arrays: 3,
+        };
+        let expected = r#"{
+    "a":[
+        1,
+        "x",
+        [],
+        {}
+    ],
+    "b":[
+        [1],
+        2
+    ],
+    "c":{
+        "d":[true, null, 3]
+    },
+    "e":[]
+}
+"#;
+        assert_eq!(to_string_pretty_with(&value, &config), expected);
+
+        let mut written = Vec::new();
+        value.to_writer_pretty_with(&mut written, &config).unwrap();
+        assert_eq!(written, expected.as_bytes());
+
+        let tabs = PrettyConfig {
+            indent: Indent::Tabs,
+            ..PrettyConfig::default()
+        };
+        assert_eq!(
+            to_string_pretty_with(&from_str(&arena, "[[1]]").unwrap(), &tabs),
+            "[\n\t[\n\t\t1\n\t]\n]"
+        );
+        assert_eq!(to_string_pretty_with(&DataValue::Null, &config), "null\n");
+    }
+
+    #[test]
+    fn test_float_formatting() {
+        let arena = Bump::new();
+        let floats = [
+            0.1,
+            1.0,
+            -0.0,
+            0.30000000000000004,
+            123456.789,
+            1e16,
+            1e300,
+            1e-7,
+            5e-324,
+            f64::MAX,
+            f64::MIN_POSITIVE,
+        ];
+        for f in floats {
+            let text = to_string(&DataValue::Number(Number::Float(f)));
+            #[cfg(feature = "serde_json-compat")]
+            assert_eq!(text, serde_json::to_string(&f).unwrap());
+            let parsed = from_str(&arena, &text).unwrap();
+            assert_eq!(
+                parsed.as_f64().map(f64::to_bits),
+                Some(f.to_bits()),
+                "{}",
+                text
+            );
+        }
+        assert_eq!(
+            to_string(&DataValue::Number(Number::Integer(i64::MIN))),
+            "-9223372036854775808"
+        );
+    }
+
+    #[test]
+    fn test_non_finite_floats_round_trip() {
+        let arena = Bump::new();
+        for f in [f64::INFINITY, f64::NEG_INFINITY, f64::NAN] {
+            let value = crate::helpers::array(
+                &arena,
+                vec![DataValue::Number(Number::Float(f)), DataValue::Null],
+            );
+            let compact = to_string(&value);
+            assert_eq!(compact, "[null,null]");
+            assert_eq!(value.to_string(), compact);
+            let pretty = to_string_pretty(&value);
+            for text in [&compact, &pretty] {
+                let parsed = from_str(&arena, text).unwrap();
+                assert_eq!(parsed[0], DataValue::Null, "{}", text);
+            }
+
+            let strict = SerializeOptions::default();
+            assert!(to_string_with_options(&value, &strict).is_err());
+
+            let literal = SerializeOptions {
+                non_finite: NonFiniteFloats::Literal,
+                ..SerializeOptions::default()
+            };
+            let text = to_string_with_options(&value, &literal).unwrap();
+            let options = crate::ParseOptions {
+                non_finite: NonFiniteFloats::Literal,
+                ..crate::ParseOptions::default()
+            };
+            let parsed = crate::from_str_with_options(&arena, &text, &options).unwrap();
+            let back = parsed[0].as_f64().unwrap();
+            assert!(back == f || (f.is_nan() && back.is_nan()), "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_escape_non_ascii() {
+        let arena = Bump::new();
+        let input = r#"{"naïve": ["é\n", "𝄞 clef", {"日本": "ok"}], "raw": {"ü": 1}}"#;
+        let options = crate::ParseOptions {
+            raw_keys: vec!["raw".to_string()],
+            ..crate::ParseOptions::default()
+        };
+        let value = crate::from_str_with_options(&arena, input, &options).unwrap();
+        let ascii = SerializeOptions {
+            escape_non_ascii: true,
+            ..SerializeOptions::default()
+        };
+        let output = to_string_with_options(&value, &ascii).unwrap();
+        assert_eq!(
+            output,
+            r#"{"na\u00efve":["\u00e9\n","\ud834\udd1e clef",{"\u65e5\u672c":"ok"}],"raw":{"\u00fc": 1}}"#
+        );
+        assert!(output.is_ascii());
+        let parsed = from_str(&arena, &output).unwrap();
+        assert_eq!(
+            to_string(&parsed),
+            to_string(&from_str(&arena, input).unwrap())
+        );
+    }
+}
@@ -0,0 +1,140 @@
+//! Writing JSON to a tokio `AsyncWrite`, enabled by the `async` feature
+
+use std::fmt;
+
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+use super::{write_json_scalar, write_json_string, IoWriter, Layout, TreeWriter};
+use crate::datavalue::DataValue;
+use crate::error::{Error, Result};
+
+/// Size at which buffered output is handed to the writer
+const CHUNK_SIZE: usize = 8 * 1024;
+
+impl DataValue<'_> {
+    /// Serialize to an async writer
+    ///
+    /// Writes the compact JSON representation of this value, with full string
+    /// escaping, in chunks of about 8 KiB, so a large value is streamed without
+    /// blocking the runtime or being formatted into one string first. The writer
+    /// is flushed at the end.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing to the writer fails.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use datavalue_rs::DataValue;
+    /// # use tokio::io::AsyncWrite;
+    /// async fn respond(body: &DataValue<'_>, socket: impl AsyncWrite) -> datavalue_rs::Result<()> {
+    ///     body.to_writer_async(socket).await
+    /// }
+    /// ```
+    pub async fn to_writer_async<W: AsyncWrite>(&self, writer: W) -> Result<()> {
+        write_async(self, Layout::default(), writer).await
+    }
+
+    /// Serialize to an async writer with pretty-printing
+    ///
+    /// Writes the JSON representation of this value with two-space indentation,
+    /// streaming it as [`to_writer_async`](DataValue::to_writer_async) does.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing to the writer fails.
+    pub async fn to_writer_pretty_async<W: AsyncWrite>(&self, writer: W) -> Result<()> {
+        let layout = Layout {
+            indent: Some("  "),
+            ..Layout::default()
+        };
+        write_async(self, layout, writer).await
+    }
+}
+
+async fn write_async<W: AsyncWrite>(
+    value: &DataValue<'_>,
+    layout: Layout<'_>,
+    writer: W,
+) -> Result<()> {
+    let mut writer = std::pin::pin!(writer);
+    let mut buffer = Vec::with_capacity(CHUNK_SIZE);
+    let mut tree = TreeWriter::new(value, layout);
+    loop {
+        let mut output = IoWriter {
+            writer: &mut buffer,
+            error: None,
+        };
+        // Writing to a Vec cannot fail, so any error comes from formatting
+        let more = tree
+            .step(
+                &mut output,
+                &mut |value, output: &mut IoWriter<'_, Vec<u8>>| {
+                    let result = write_json_scalar(value, output.writer);
+                    output.check(result)
+                },
+                &mut |(key, _), output: &mut IoWriter<'_, Vec<u8>>| {
+                    let result = write_json_string(key, output.writer);
+                    output.check(result)
+                },
+            )
+            .map_err(|fmt::Error| Error::custom("Failed to format JSON"))?;
+        if buffer.len() >= CHUNK_SIZE || !more {
+            writer.write_all(&buffer).await?;
+            buffer.clear();
+        }
+        if !more {
+            break;
+        }
+    }
+    writer.flush().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::Future;
+    use std::task::{Context, Poll, Waker};
+
+    use crate::{from_str, Bump};
+
+    /// Runs a future that never waits, as writing to a Vec does not
+    fn run<F: Future>(future: F) -> F::Output {
+        let mut future = std::pin::pin!(future);
+        match future
+            .as_mut()
+            .poll(&mut Context::from_waker(Waker::noop()))
+        {
+            Poll::Ready(output) => output,
+            Poll::Pending => panic!("writing to a Vec does not wait"),
+        }
+    }
+
+    #[test]
+    fn test_to_writer_async() {
+        let arena = Bump::new();
+        let value = from_str(&arena, r#"{"a": [1, "x\ny", {}], "b": {"c": null}}"#).unwrap();
+
+        let mut compact = Vec::new();
+        run(value.to_writer_async(&mut compact)).unwrap();
+        assert_eq!(compact, br#"{"a":[1,"x\ny",{}],"b":{"c":null}}"#);
+
+        let mut pretty = Vec::new();
+        run(value.to_writer_pretty_async(&mut pretty)).unwrap();
+        assert_eq!(
+            String::from_utf8(pretty).unwrap(),
+            "{\n  \"a\": [\n    1,\n    \"x\\ny\",\n    {}\n  ],\n  \"b\": {\n    \"c\": null\n  }\n}"
+        );
+
+        // Output larger than a chunk arrives complete
+        let text = format!("[{}]", vec!["\"0123456789\""; 5000].join(","));
+        let large = from_str(&arena, &text).unwrap();
+        let mut output = Vec::new();
+        run(large.to_writer_async(&mut output)).unwrap();
+        assert_eq!(output, text.as_bytes());
+
+        fn assert_send<T: Send>(_: T) {}
+        assert_send(value.to_writer_async(Vec::new()));
+    }
+}
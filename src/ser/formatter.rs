@@ -0,0 +1,448 @@
+//! The [`Formatter`] trait, which decides how each token of the JSON text is
+//! written, and its compact and pretty implementations
+
+use std::io::{self, Write};
+
+use super::write_json_string;
+use crate::datavalue::{DataValue, Number};
+use crate::helpers::format_duration;
+
+/// Writes the tokens of JSON text on behalf of
+/// [`DataValue::to_writer_with_formatter`]
+///
+/// Every method has a default that writes compact JSON, so an implementation only
+/// overrides what it wants to change, such as the whitespace between tokens or the
+/// spelling of floats. Strings, including object keys, are passed whole to
+/// [`write_string`](Formatter::write_string); DateTime and Duration values arrive
+/// there in their RFC 3339 and ISO 8601 forms.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::ser::Formatter;
+/// use datavalue_rs::{from_str, Bump};
+/// use std::io::{self, Write};
+///
+/// /// Writes floats with two decimal places
+/// struct Fixed;
+///
+/// impl Formatter for Fixed {
+///     fn write_f64<W: Write + ?Sized>(&mut self, writer: &mut W, value: f64) -> io::Result<()> {
+///         write!(writer, "{:.2}", value)
+///     }
+/// }
+///
+/// let arena = Bump::new();
+/// let value = from_str(&arena, r#"{"price": 9.5, "qty": 3}"#).unwrap();
+/// let mut output = Vec::new();
+/// value.to_writer_with_formatter(&mut output, &mut Fixed).unwrap();
+/// assert_eq!(output, br#"{"price":9.50,"qty":3}"#);
+/// ```
+pub trait Formatter {
+    /// Writes `null`
+    fn write_null<W: Write + ?Sized>(&mut self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(b"null")
+    }
+
+    /// Writes `true` or `false`
+    fn write_bool<W: Write + ?Sized>(&mut self, writer: &mut W, value: bool) -> io::Result<()> {
+        writer.write_all(if value { b"true" } else { b"false" })
+    }
+
+    /// Writes an integer
+    fn write_i64<W: Write + ?Sized>(&mut self, writer: &mut W, value: i64) -> io::Result<()> {
+        writer.write_all(itoa::Buffer::new().format(value).as_bytes())
+    }
+
+    /// Writes a float in the shortest form that parses back to the same value, or
+    /// `null` if it is NaN or infinite
+    fn write_f64<W: Write + ?Sized>(&mut self, writer: &mut W, value: f64) -> io::Result<()> {
+        if value.is_finite() {
+            writer.write_all(ryu::Buffer::new().format_finite(value).as_bytes())
+        } else {
+            writer.write_all(b"null")
+        }
+    }
+
+    /// Writes a number given as text: a [`Number::Raw`] or an arbitrary-precision
+    /// number
+    fn write_number_str<W: Write + ?Sized>(
+        &mut self,
+        writer: &mut W,
+        value: &str,
+    ) -> io::Result<()> {
+        writer.write_all(value.as_bytes())
+    }
+
+    /// Writes a string value or object key as a quoted, escaped JSON string
+    fn write_string<W: Write + ?Sized>(&mut self, writer: &mut W, value: &str) -> io::Result<()> {
+        write_json_string(value, writer)
+    }
+
+    /// Writes the unparsed text of a [`DataValue::Raw`]
+    fn write_raw_value<W: Write + ?Sized>(&mut self, writer: &mut W, text: &str) -> io::Result<()> {
+        writer.write_all(text.as_bytes())
+    }
+
+    /// Called before the elements of an array
+    fn begin_array<W: Write + ?Sized>(&mut self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(b"[")
+    }
+
+    /// Called after the elements of an array
+    fn end_array<W: Write + ?Sized>(&mut self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(b"]")
+    }
+
+    /// Called before each array element, with `first` set for the first one
+    fn begin_array_value<W: Write + ?Sized>(
+        &mut self,
+        writer: &mut W,
+        first: bool,
+    ) -> io::Result<()> {
+        if first {
+            Ok(())
+        } else {
+            writer.write_all(b",")
+        }
+    }
+
+    /// Called after each array element
+    fn end_array_value<W: Write + ?Sized>(&mut self, _writer: &mut W) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Called before the entries of an object
+    fn begin_object<W: Write + ?Sized>(&mut self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(b"{")
+    }
+
+    /// Called after the entries of an object
+    fn end_object<W: Write + ?Sized>(&mut self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(b"}")
+    }
+
+    /// Called before each object key, with `first` set for the first one
+    fn begin_object_key<W: Write + ?Sized>(
+        &mut self,
+        writer: &mut W,
+        first: bool,
+    ) -> io::Result<()> {
+        if first {
+            Ok(())
+        } else {
+            writer.write_all(b",")
+        }
+    }
+
+    /// Called after each object key
+    fn end_object_key<W: Write + ?Sized>(&mut self, _writer: &mut W) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Called before each object value, after its key
+    fn begin_object_value<W: Write + ?Sized>(&mut self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(b":")
+    }
+
+    /// Called after each object value
+    fn end_object_value<W: Write + ?Sized>(&mut self, _writer: &mut W) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Writes compact JSON with no whitespace, as [`to_string`](super::to_string) does
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CompactFormatter;
+
+impl Formatter for CompactFormatter {}
+
+/// Writes one array element or object entry per line, as
+/// [`to_string_pretty`](super::to_string_pretty) does
+///
+/// Empty arrays and objects are written as `[]` and `{}`.
+#[derive(Debug, Clone)]
+pub struct PrettyFormatter<'i> {
+    indent: &'i [u8],
+    level: usize,
+    has_value: bool,
+}
+
+impl PrettyFormatter<'static> {
+    /// Creates a formatter indenting by two spaces
+    pub fn new() -> Self {
+        PrettyFormatter::with_indent(b"  ")
+    }
+}
+
+impl Default for PrettyFormatter<'static> {
+    fn default() -> Self {
+        PrettyFormatter::new()
+    }
+}
+
+impl<'i> PrettyFormatter<'i> {
+    /// Creates a formatter using `indent` as one level of indentation
+    pub fn with_indent(indent: &'i [u8]) -> Self {
+        PrettyFormatter {
+            indent,
+            level: 0,
+            has_value: false,
+        }
+    }
+
+    fn newline<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(b"\n")?;
+        for _ in 0..self.level {
+            writer.write_all(self.indent)?;
+        }
+        Ok(())
+    }
+
+    fn begin<W: Write + ?Sized>(&mut self, writer: &mut W, open: &[u8]) -> io::Result<()> {
+        self.level += 1;
+        self.has_value = false;
+        writer.write_all(open)
+    }
+
+    fn end<W: Write + ?Sized>(&mut self, writer: &mut W, close: &[u8]) -> io::Result<()> {
+        self.level -= 1;
+        if self.has_value {
+            self.newline(writer)?;
+        }
+        writer.write_all(close)
+    }
+
+    fn item<W: Write + ?Sized>(&mut self, writer: &mut W, first: bool) -> io::Result<()> {
+        if !first {
+            writer.write_all(b",")?;
+        }
+        self.newline(writer)
+    }
+}
+
+impl Formatter for PrettyFormatter<'_> {
+    fn begin_array<W: Write + ?Sized>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.begin(writer, b"[")
+    }
+
+    fn end_array<W: Write + ?Sized>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.end(writer, b"]")
+    }
+
+    fn begin_array_value<W: Write + ?Sized>(
+        &mut self,
+        writer: &mut W,
+        first: bool,
+    ) -> io::Result<()> {
+        self.item(writer, first)
+    }
+
+    fn end_array_value<W: Write + ?Sized>(&mut self, _writer: &mut W) -> io::Result<()> {
+        self.has_value = true;
+        Ok(())
+    }
+
+    fn begin_object<W: Write + ?Sized>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.begin(writer, b"{")
+    }
+
+    fn end_object<W: Write + ?Sized>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.end(writer, b"}")
+    }
+
+    fn begin_object_key<W: Write + ?Sized>(
+        &mut self,
+        writer: &mut W,
+        first: bool,
+    ) -> io::Result<()> {
+        self.item(writer, first)
+    }
+
+    fn begin_object_value<W: Write + ?Sized>(&mut self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(b": ")
+    }
+
+    fn end_object_value<W: Write + ?Sized>(&mut self, _writer: &mut W) -> io::Result<()> {
+        self.has_value = true;
+        Ok(())
+    }
+}
+
+/// An array or object being written by [`write_formatted`]
+enum Frame<'v, 'a> {
+    Array(std::slice::Iter<'v, DataValue<'a>>),
+    Object(std::slice::Iter<'v, (&'a str, DataValue<'a>)>),
+}
+
+/// Writes a value through `formatter`, keeping open arrays and objects on an
+/// explicit stack so that nesting depth does not consume thread stack
+pub(crate) fn write_formatted<W: Write + ?Sized, F: Formatter + ?Sized>(
+    value: &DataValue<'_>,
+    writer: &mut W,
+    formatter: &mut F,
+) -> io::Result<()> {
+    let mut stack: Vec<(Frame<'_, '_>, bool)> = Vec::new();
+    let mut next = Some(value);
+    // Set once a value has been written in full, so its container can close it
+    let mut done = false;
+    loop {
+        match next.take() {
+            Some(DataValue::Array(items)) => {
+                formatter.begin_array(writer)?;
+                stack.push((Frame::Array(items.iter()), true));
+            }
+            Some(DataValue::Object(entries)) => {
+                formatter.begin_object(writer)?;
+                stack.push((Frame::Object(entries.iter()), true));
+            }
+            Some(leaf) => {
+                write_scalar(leaf, writer, formatter)?;
+                done = true;
+            }
+            None => {}
+        }
+
+        let Some((frame, first)) = stack.last_mut() else {
+            return Ok(());
+        };
+        if std::mem::take(&mut done) {
+            match frame {
+                Frame::Array(_) => formatter.end_array_value(writer)?,
+                Frame::Object(_) => formatter.end_object_value(writer)?,
+            }
+        }
+        let first = std::mem::take(first);
+        match frame {
+            Frame::Array(items) => match items.next() {
+                Some(item) => {
+                    formatter.begin_array_value(writer, first)?;
+                    next = Some(item);
+                }
+                None => {
+                    stack.pop();
+                    formatter.end_array(writer)?;
+                    done = true;
+                }
+            },
+            Frame::Object(entries) => match entries.next() {
+                Some((key, item)) => {
+                    formatter.begin_object_key(writer, first)?;
+                    formatter.write_string(writer, key)?;
+                    formatter.end_object_key(writer)?;
+                    formatter.begin_object_value(writer)?;
+                    next = Some(item);
+                }
+                None => {
+                    stack.pop();
+                    formatter.end_object(writer)?;
+                    done = true;
+                }
+            },
+        }
+    }
+}
+
+/// Writes a value that is not an array or object through `formatter`
+fn write_scalar<W: Write + ?Sized, F: Formatter + ?Sized>(
+    value: &DataValue<'_>,
+    writer: &mut W,
+    formatter: &mut F,
+) -> io::Result<()> {
+    match value {
+        DataValue::Null => formatter.write_null(writer),
+        DataValue::Bool(b) => formatter.write_bool(writer, *b),
+        DataValue::Number(Number::Integer(i)) => formatter.write_i64(writer, *i),
+        DataValue::Number(Number::Float(f)) => formatter.write_f64(writer, *f),
+        DataValue::Number(Number::Raw(text)) => formatter.write_number_str(writer, text),
+        #[cfg(feature = "bignum")]
+        DataValue::Number(Number::BigInt(n)) => formatter.write_number_str(writer, &n.to_string()),
+        #[cfg(feature = "bignum")]
+        DataValue::Number(Number::BigDecimal(n)) => {
+            formatter.write_number_str(writer, &n.to_string())
+        }
+        DataValue::String(s) => formatter.write_string(writer, s),
+        DataValue::DateTime(dt) => formatter.write_string(writer, &dt.to_rfc3339()),
+        DataValue::Duration(dur) => formatter.write_string(writer, &format_duration(dur)),
+        DataValue::Raw(text) => formatter.write_raw_value(writer, text),
+        DataValue::Array(_) | DataValue::Object(_) => {
+            unreachable!("containers are written by write_formatted")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{from_str, to_string, to_string_pretty, Bump};
+
+    #[test]
+    fn test_formatters_match_string_output() {
+        let arena = Bump::new();
+        let value = from_str(
+            &arena,
+            r#"{"a": [1, 2.5, "x\ny", [], {}], "b": {"c": null, "d": [true, {"e": false}]}, "f": []}"#,
+        )
+        .unwrap();
+
+        let mut compact = Vec::new();
+        value
+            .to_writer_with_formatter(&mut compact, &mut CompactFormatter)
+            .unwrap();
+        assert_eq!(String::from_utf8(compact).unwrap(), to_string(&value));
+
+        let mut pretty = Vec::new();
+        value
+            .to_writer_with_formatter(&mut pretty, &mut PrettyFormatter::new())
+            .unwrap();
+        assert_eq!(String::from_utf8(pretty).unwrap(), to_string_pretty(&value));
+
+        let mut tabs = Vec::new();
+        let nested = from_str(&arena, r#"[{"a": []}]"#).unwrap();
+        nested
+            .to_writer_with_formatter(&mut tabs, &mut PrettyFormatter::with_indent(b"\t"))
+            .unwrap();
+        assert_eq!(tabs, b"[\n\t{\n\t\t\"a\": []\n\t}\n]");
+    }
+
+    #[test]
+    fn test_custom_formatter() {
+        /// Writes objects on one line with spaces, and keys unquoted
+        struct Spaced;
+
+        impl Formatter for Spaced {
+            fn begin_object_key<W: Write + ?Sized>(
+                &mut self,
+                writer: &mut W,
+                first: bool,
+            ) -> io::Result<()> {
+                writer.write_all(if first { b" " } else { b", " })
+            }
+
+            fn begin_object_value<W: Write + ?Sized>(&mut self, writer: &mut W) -> io::Result<()> {
+                writer.write_all(b": ")
+            }
+
+            fn end_object<W: Write + ?Sized>(&mut self, writer: &mut W) -> io::Result<()> {
+                writer.write_all(b" }")
+            }
+        }
+
+        let arena = Bump::new();
+        let value = from_str(&arena, r#"{"a": 1, "b": {"c": [1, 2]}}"#).unwrap();
+        let mut output = Vec::new();
+        value
+            .to_writer_with_formatter(&mut output, &mut Spaced)
+            .unwrap();
+        assert_eq!(output, br#"{ "a": 1, "b": { "c": [1,2] } }"#);
+
+        // Deep nesting does not overflow the stack
+        let depth = 100_000;
+        let text = format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+        let deep = from_str(&arena, &text).unwrap();
+        let mut output = Vec::new();
+        deep.to_writer_with_formatter(&mut output, &mut CompactFormatter)
+            .unwrap();
+        assert_eq!(output, text.as_bytes());
+    }
+}
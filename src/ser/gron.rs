@@ -0,0 +1,151 @@
+//! gron-style output: one JavaScript assignment per value, for grepping
+
+use super::push_json_string;
+use crate::datavalue::{DataValue, Number};
+
+/// Converts a DataValue to gron text, one assignment statement per line
+///
+/// Every array and object is assigned an empty `[]` or `{}`, followed by one
+/// statement for each of its elements or entries, so every line holds the full
+/// path of one value and `grep` output can be read on its own, or turned back into
+/// JSON with `gron --ungron`. The root is called `json`. Keys that are JavaScript
+/// identifiers use dot notation and others are written as quoted, escaped
+/// `["..."]` subscripts. Statements appear in document order.
+///
+/// Scalars are written as in compact JSON, with non-finite floats as `null`.
+///
+/// # Example
+///
+/// ```
+/// # use datavalue_rs::{Bump, from_str, ser::to_gron};
+/// let arena = Bump::new();
+/// let value = from_str(&arena, r#"{"data": {"items": ["a", {"x y": null}]}}"#).unwrap();
+/// assert_eq!(
+///     to_gron(&value),
+///     concat!(
+///         "json = {};\n",
+///         "json.data = {};\n",
+///         "json.data.items = [];\n",
+///         "json.data.items[0] = \"a\";\n",
+///         "json.data.items[1] = {};\n",
+///         "json.data.items[1][\"x y\"] = null;\n",
+///     )
+/// );
+/// ```
+pub fn to_gron(value: &DataValue<'_>) -> String {
+    /// An array or object whose children are being written, with the length of
+    /// its path
+    enum Frame<'v, 'a> {
+        Array(std::iter::Enumerate<std::slice::Iter<'v, DataValue<'a>>>),
+        Object(std::slice::Iter<'v, (&'a str, DataValue<'a>)>),
+    }
+
+    let mut output = String::new();
+    let mut path = String::from("json");
+    let mut stack: Vec<(Frame<'_, '_>, usize)> = Vec::new();
+    let mut next = Some(value);
+    loop {
+        if let Some(value) = next.take() {
+            output.push_str(&path);
+            output.push_str(" = ");
+            match value {
+                DataValue::Array(items) => {
+                    output.push_str("[]");
+                    stack.push((Frame::Array(items.iter().enumerate()), path.len()));
+                }
+                DataValue::Object(entries) => {
+                    output.push_str("{}");
+                    stack.push((Frame::Object(entries.iter()), path.len()));
+                }
+                DataValue::Number(Number::Float(f)) if !f.is_finite() => output.push_str("null"),
+                scalar => output.push_str(&scalar.to_string()),
+            }
+            output.push_str(";\n");
+        }
+
+        let Some((frame, len)) = stack.last_mut() else {
+            return output;
+        };
+        path.truncate(*len);
+        match frame {
+            Frame::Array(items) => match items.next() {
+                Some((index, item)) => {
+                    path.push('[');
+                    path.push_str(&index.to_string());
+                    path.push(']');
+                    next = Some(item);
+                }
+                None => {
+                    stack.pop();
+                }
+            },
+            Frame::Object(entries) => match entries.next() {
+                Some((key, item)) => {
+                    if is_identifier(key) {
+                        path.push('.');
+                        path.push_str(key);
+                    } else {
+                        path.push('[');
+                        push_json_string(key, &mut path);
+                        path.push(']');
+                    }
+                    next = Some(item);
+                }
+                None => {
+                    stack.pop();
+                }
+            },
+        }
+    }
+}
+
+/// Returns true if `key` can follow a `.` in a JavaScript property access
+fn is_identifier(key: &str) -> bool {
+    let mut chars = key.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '$')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{from_str, helpers, Bump};
+
+    #[test]
+    fn test_to_gron() {
+        let arena = Bump::new();
+        let value = from_str(
+            &arena,
+            r#"{"a": [1, [], {}, [true]], "$b_2": 2.5, "2c": "q\"", "": null, "é": {"d": -1}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            to_gron(&value),
+            concat!(
+                "json = {};\n",
+                "json.a = [];\n",
+                "json.a[0] = 1;\n",
+                "json.a[1] = [];\n",
+                "json.a[2] = {};\n",
+                "json.a[3] = [];\n",
+                "json.a[3][0] = true;\n",
+                "json.$b_2 = 2.5;\n",
+                "json[\"2c\"] = \"q\\\"\";\n",
+                "json[\"\"] = null;\n",
+                "json[\"é\"] = {};\n",
+                "json[\"é\"].d = -1;\n",
+            )
+        );
+
+        assert_eq!(to_gron(&helpers::string(&arena, "x")), "json = \"x\";\n");
+        assert_eq!(to_gron(&helpers::float(f64::NAN)), "json = null;\n");
+
+        // Nesting is walked without recursion
+        let depth = 1000;
+        let text = format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+        let deep = from_str(&arena, &text).unwrap();
+        assert_eq!(to_gron(&deep).lines().count(), depth);
+    }
+}
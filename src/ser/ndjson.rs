@@ -0,0 +1,95 @@
+//! Newline-delimited JSON (JSON Lines) output
+//!
+//! Each value is written as one line of compact JSON followed by `\n`. Strings
+//! are fully escaped, so a newline inside a value can never split a record.
+
+use std::io::{BufWriter, Write};
+
+use super::write_json;
+use crate::datavalue::DataValue;
+use crate::error::Result;
+
+/// Writes values as newline-delimited JSON
+///
+/// Values are serialized straight into a buffer wrapped around `writer`, without
+/// building a `String` per record, so arbitrarily long streams can be written with
+/// constant memory. The buffer is flushed before returning.
+///
+/// # Arguments
+///
+/// * `writer` - The destination
+/// * `values` - The values to write, one per line
+///
+/// # Returns
+///
+/// A Result that is an Error if writing to `writer` fails.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::{from_str, ser::ndjson, Bump};
+///
+/// let arena = Bump::new();
+/// let records = from_str(&arena, r#"[{"id": 1, "note": "two\nlines"}, {"id": 2}]"#).unwrap();
+///
+/// let mut output = Vec::new();
+/// ndjson::write_lines(&mut output, records.as_array().unwrap()).unwrap();
+///
+/// assert_eq!(
+///     String::from_utf8(output).unwrap(),
+///     "{\"id\":1,\"note\":\"two\\nlines\"}\n{\"id\":2}\n"
+/// );
+/// ```
+pub fn write_lines<'v, 'a: 'v, W, I>(writer: W, values: I) -> Result<()>
+where
+    W: Write,
+    I: IntoIterator<Item = &'v DataValue<'a>>,
+{
+    let mut writer = BufWriter::new(writer);
+    for value in values {
+        write_json(value, &mut writer)?;
+        writer.write_all(b"\n")?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_str;
+    use bumpalo::Bump;
+    use chrono::Duration;
+
+    #[test]
+    fn test_lines_round_trip() {
+        let arena = Bump::new();
+        let input = r#"[{"s": "q\"b\\c\u0001\t", "k\ney": [1, 2.5, null, true]}, "plain", 3]"#;
+        let values = from_str(&arena, input).unwrap();
+        let mut output = Vec::new();
+        write_lines(&mut output, values.as_array().unwrap()).unwrap();
+        let text = String::from_utf8(output).unwrap();
+
+        assert_eq!(text.lines().count(), 3);
+        assert!(text.contains(r#""q\"b\\c\u0001\t""#));
+        for (line, original) in text.lines().zip(values.as_array().unwrap()) {
+            assert_eq!(&from_str(&arena, line).unwrap(), original);
+        }
+    }
+
+    #[test]
+    fn test_temporal_values_are_quoted() {
+        let values = [
+            DataValue::Duration(Duration::minutes(90)),
+            DataValue::Number(crate::Number::Float(f64::NAN)),
+        ];
+        let mut output = Vec::new();
+        write_lines(&mut output, &values).unwrap();
+        let text = String::from_utf8(output).unwrap();
+        let arena = Bump::new();
+        for line in text.lines() {
+            assert!(from_str(&arena, line).is_ok(), "{}", line);
+        }
+        assert!(text.ends_with("null\n"));
+    }
+}
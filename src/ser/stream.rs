@@ -0,0 +1,498 @@
+//! A serde `Serializer` that writes JSON text as it goes
+
+use std::io::Write;
+
+use serde::ser::{self, Serialize};
+
+use super::{write_json_scalar, write_json_string};
+use crate::datavalue::{DataValue, Number};
+use crate::error::{Error, Result};
+
+/// Serializes any `Serialize` value straight to compact JSON text
+///
+/// Nothing is buffered beyond what the writer does, so this is the output half of
+/// a streaming conversion: pair it with any serde `Deserializer` through
+/// `serde_transcode`, or feed it from a
+/// [`JsonDeserializer`](crate::de::JsonDeserializer) to reformat JSON. Values are
+/// written the way [`to_value`](super::to_value) maps them and
+/// [`to_string`](super::to_string) prints them, with full string escaping;
+/// non-finite floats are written as `null` and bytes as an array of numbers.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::ser::JsonSerializer;
+/// use serde::Serialize;
+///
+/// #[derive(Serialize)]
+/// enum Shape {
+///     Circle { r: f64 },
+/// }
+///
+/// let mut serializer = JsonSerializer::new(Vec::new());
+/// vec![Shape::Circle { r: 1.5 }].serialize(&mut serializer).unwrap();
+/// let json = serializer.into_inner();
+/// assert_eq!(json, br#"[{"Circle":{"r":1.5}}]"#);
+/// ```
+pub struct JsonSerializer<W> {
+    writer: W,
+}
+
+impl<W: Write> JsonSerializer<W> {
+    /// Creates a serializer writing to `writer`
+    pub fn new(writer: W) -> Self {
+        JsonSerializer { writer }
+    }
+
+    /// Returns the writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<()> {
+        Ok(self.writer.write_all(bytes)?)
+    }
+
+    fn scalar(&mut self, value: DataValue<'_>) -> Result<()> {
+        Ok(write_json_scalar(&value, &mut self.writer)?)
+    }
+
+    fn string(&mut self, s: &str) -> Result<()> {
+        Ok(write_json_string(s, &mut self.writer)?)
+    }
+
+    /// Opens `{"variant":`, the start of an enum variant with data
+    fn open_variant(&mut self, variant: &str) -> Result<()> {
+        self.write(b"{")?;
+        self.string(variant)?;
+        self.write(b":")
+    }
+
+    fn compound(&mut self, open: &[u8], close: &'static [u8]) -> Result<Compound<'_, W>> {
+        self.write(open)?;
+        Ok(Compound {
+            serializer: self,
+            first: true,
+            close,
+        })
+    }
+}
+
+impl<'s, W: Write> ser::Serializer for &'s mut JsonSerializer<W> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Compound<'s, W>;
+    type SerializeTuple = Compound<'s, W>;
+    type SerializeTupleStruct = Compound<'s, W>;
+    type SerializeTupleVariant = Compound<'s, W>;
+    type SerializeMap = Compound<'s, W>;
+    type SerializeStruct = Compound<'s, W>;
+    type SerializeStructVariant = Compound<'s, W>;
+
+    fn serialize_bool(self, v: bool) -> Result<()> {
+        self.scalar(DataValue::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<()> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<()> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<()> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<()> {
+        self.scalar(DataValue::Number(Number::Integer(v)))
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<()> {
+        self.write(itoa::Buffer::new().format(v).as_bytes())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<()> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<()> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<()> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<()> {
+        self.write(itoa::Buffer::new().format(v).as_bytes())
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<()> {
+        self.write(itoa::Buffer::new().format(v).as_bytes())
+    }
+
+    // Formatted as an f32, so 0.1f32 is written as `0.1` rather than the digits of
+    // the nearest f64
+    fn serialize_f32(self, v: f32) -> Result<()> {
+        if v.is_finite() {
+            self.write(ryu::Buffer::new().format_finite(v).as_bytes())
+        } else {
+            self.write(b"null")
+        }
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<()> {
+        self.scalar(DataValue::Number(Number::Float(v)))
+    }
+
+    fn serialize_char(self, v: char) -> Result<()> {
+        self.string(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<()> {
+        self.string(v)
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<()> {
+        let mut seq = self.compound(b"[", b"]")?;
+        for b in v {
+            ser::SerializeSeq::serialize_element(&mut seq, b)?;
+        }
+        ser::SerializeSeq::end(seq)
+    }
+
+    fn serialize_none(self) -> Result<()> {
+        self.write(b"null")
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<()> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        self.write(b"null")
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<()> {
+        self.string(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.open_variant(variant)?;
+        value.serialize(&mut *self)?;
+        self.write(b"}")
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Compound<'s, W>> {
+        self.compound(b"[", b"]")
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Compound<'s, W>> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<Compound<'s, W>> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'s, W>> {
+        self.open_variant(variant)?;
+        self.compound(b"[", b"]}")
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Compound<'s, W>> {
+        self.compound(b"{", b"}")
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Compound<'s, W>> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'s, W>> {
+        self.open_variant(variant)?;
+        self.compound(b"{", b"}}")
+    }
+}
+
+/// An array or object being written by [`JsonSerializer`]
+pub struct Compound<'s, W> {
+    serializer: &'s mut JsonSerializer<W>,
+    first: bool,
+    /// Written by `end`, including the brace of an enum variant
+    close: &'static [u8],
+}
+
+impl<W: Write> Compound<'_, W> {
+    /// Writes the comma before every entry but the first
+    fn separate(&mut self) -> Result<()> {
+        if !std::mem::take(&mut self.first) {
+            self.serializer.write(b",")?;
+        }
+        Ok(())
+    }
+
+    fn element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.separate()?;
+        value.serialize(&mut *self.serializer)
+    }
+
+    fn field<T: Serialize + ?Sized>(&mut self, key: &str, value: &T) -> Result<()> {
+        self.separate()?;
+        self.serializer.string(key)?;
+        self.serializer.write(b":")?;
+        value.serialize(&mut *self.serializer)
+    }
+
+    fn finish(self) -> Result<()> {
+        self.serializer.write(self.close)
+    }
+}
+
+impl<W: Write> ser::SerializeSeq for Compound<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+impl<W: Write> ser::SerializeTuple for Compound<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+impl<W: Write> ser::SerializeTupleStruct for Compound<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+impl<W: Write> ser::SerializeTupleVariant for Compound<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+impl<W: Write> ser::SerializeMap for Compound<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<()> {
+        self.separate()?;
+        // Keys go through to_value, which checks that they are strings or
+        // numbers and spells them the same way
+        let arena = bumpalo::Bump::new();
+        if let DataValue::Object([(key, _)]) = super::to_value(&arena, &MapKey(key))? {
+            self.serializer.string(key)?;
+        }
+        self.serializer.write(b":")
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        value.serialize(&mut *self.serializer)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+/// Serializes as a map with the wrapped value as its only key
+struct MapKey<'k, T: ?Sized>(&'k T);
+
+impl<T: Serialize + ?Sized> Serialize for MapKey<'_, T> {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        use ser::SerializeMap;
+        let mut map = serializer.serialize_map(Some(1))?;
+        map.serialize_entry(self.0, &())?;
+        map.end()
+    }
+}
+
+impl<W: Write> ser::SerializeStruct for Compound<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+impl<W: Write> ser::SerializeStructVariant for Compound<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::de::JsonDeserializer;
+    use crate::{from_str, to_string};
+    use bumpalo::Bump;
+    use serde::{Deserialize, Serialize};
+    use std::collections::BTreeMap;
+
+    #[derive(Serialize)]
+    enum Shape {
+        Empty,
+        Circle(f64),
+        Point(i32, i32),
+        Rect { w: u8, h: u8 },
+    }
+
+    #[derive(Serialize)]
+    struct Drawing<'a> {
+        title: &'a str,
+        id: u64,
+        shapes: Vec<Shape>,
+        layers: BTreeMap<i32, char>,
+        offset: (i8, f32),
+        hidden: Option<bool>,
+        #[serde(with = "bytes")]
+        data: &'a [u8],
+    }
+
+    mod bytes {
+        pub fn serialize<S: serde::Serializer>(bytes: &[u8], s: S) -> Result<S::Ok, S::Error> {
+            s.serialize_bytes(bytes)
+        }
+    }
+
+    fn json<T: Serialize>(value: &T) -> Result<String> {
+        let mut serializer = JsonSerializer::new(Vec::new());
+        value.serialize(&mut serializer)?;
+        Ok(String::from_utf8(serializer.into_inner()).unwrap())
+    }
+
+    #[test]
+    fn test_json_serializer() {
+        let drawing = Drawing {
+            title: "a \"plan\"\n",
+            id: u64::MAX,
+            shapes: vec![
+                Shape::Empty,
+                Shape::Circle(1.5),
+                Shape::Point(-1, 2),
+                Shape::Rect { w: 3, h: 4 },
+            ],
+            layers: BTreeMap::from([(-1, 'a'), (2, 'b')]),
+            offset: (0, f32::NAN),
+            hidden: None,
+            data: b"\x00\xff",
+        };
+        let text = json(&drawing).unwrap();
+        assert_eq!(
+            text,
+            concat!(
+                r#"{"title":"a \"plan\"\n","id":18446744073709551615,"#,
+                r#""shapes":["Empty",{"Circle":1.5},{"Point":[-1,2]},{"Rect":{"w":3,"h":4}}],"#,
+                r#""layers":{"-1":"a","2":"b"},"offset":[0,null],"hidden":null,"data":[0,255]}"#
+            )
+        );
+
+        // The same text as going through a DataValue, for values it can hold
+        let arena = Bump::new();
+        let value = from_str(&arena, r#"{"a": [1, 2.5, -3e2, true, null, "s"], "b": {}}"#).unwrap();
+        assert_eq!(json(&value).unwrap(), to_string(&value));
+        assert_eq!(json(&(0.1f32, 1e20f32)).unwrap(), "[0.1,1e20]");
+
+        assert!(json(&BTreeMap::from([((1, 2), 3)])).is_err());
+
+        // JSON to JSON through serde
+        let input = r#"{"k": [{"x": "y\u0001"}, []], "n": -0.5}"#;
+        let mut deserializer = JsonDeserializer::new(input);
+        let mut serializer = JsonSerializer::new(Vec::new());
+        serde_json::Value::deserialize(&mut deserializer)
+            .unwrap()
+            .serialize(&mut serializer)
+            .unwrap();
+        assert_eq!(
+            serializer.into_inner(),
+            br#"{"k":[{"x":"y\u0001"},[]],"n":-0.5}"#
+        );
+    }
+}
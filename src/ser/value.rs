@@ -0,0 +1,506 @@
+//! Serializing any `Serialize` type straight into an arena
+
+use bumpalo::Bump;
+use serde::ser::{self, Serialize};
+
+use crate::datavalue::{DataValue, Number};
+use crate::error::{Error, Result};
+
+/// Converts a `Serialize` value into a DataValue allocated in `arena`
+///
+/// The value is built directly in the arena, without going through an
+/// intermediate `serde_json::Value`. Values map the way `serde_json` maps them:
+/// structs and maps become objects, sequences and tuples become arrays, `None`
+/// and unit become null, and enum variants with data become an object with the
+/// variant name as its only key. Map keys must serialize as strings, integers,
+/// bools or chars; the latter three are written as text.
+///
+/// Integers outside the `i64` range become a [`Number::BigInt`] with the
+/// `bignum` feature and the nearest [`Number::Float`] otherwise.
+///
+/// # Errors
+///
+/// Returns an error if a map key is not a string or number, or if the
+/// `Serialize` implementation itself fails.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::{ser::to_value, to_string, Bump};
+/// use serde::Serialize;
+///
+/// #[derive(Serialize)]
+/// struct User<'s> {
+///     name: &'s str,
+///     roles: Vec<&'s str>,
+///     manager: Option<u32>,
+/// }
+///
+/// let arena = Bump::new();
+/// let user = User { name: "John", roles: vec!["admin"], manager: None };
+/// let value = to_value(&arena, &user).unwrap();
+/// assert_eq!(value["roles"][0].as_str(), Some("admin"));
+/// assert_eq!(to_string(&value), r#"{"name":"John","roles":["admin"],"manager":null}"#);
+/// ```
+pub fn to_value<'a, T>(arena: &'a Bump, value: &T) -> Result<DataValue<'a>>
+where
+    T: Serialize + ?Sized,
+{
+    value.serialize(ValueSerializer { arena })
+}
+
+/// Serializer whose output is a DataValue in `arena`
+#[derive(Clone, Copy)]
+struct ValueSerializer<'a> {
+    arena: &'a Bump,
+}
+
+impl<'a> ValueSerializer<'a> {
+    /// Integer that may not fit in an `i64`
+    fn wide_integer(self, text: String) -> DataValue<'a> {
+        if let Ok(i) = text.parse::<i64>() {
+            return DataValue::Number(Number::Integer(i));
+        }
+        #[cfg(feature = "bignum")]
+        if let Some(number) = crate::bignum::parse_number(self.arena, &text, false) {
+            return DataValue::Number(number);
+        }
+        DataValue::Number(Number::Float(text.parse().unwrap_or(f64::NAN)))
+    }
+
+    /// `{variant: value}`, the representation of an enum variant with data
+    fn variant(self, variant: &str, value: DataValue<'a>) -> DataValue<'a> {
+        let key: &'a str = self.arena.alloc_str(variant);
+        DataValue::Object(self.arena.alloc_slice_fill_iter([(key, value)]))
+    }
+}
+
+impl<'a> ser::Serializer for ValueSerializer<'a> {
+    type Ok = DataValue<'a>;
+    type Error = Error;
+    type SerializeSeq = SerializeArray<'a>;
+    type SerializeTuple = SerializeArray<'a>;
+    type SerializeTupleStruct = SerializeArray<'a>;
+    type SerializeTupleVariant = SerializeArray<'a>;
+    type SerializeMap = SerializeObject<'a>;
+    type SerializeStruct = SerializeObject<'a>;
+    type SerializeStructVariant = SerializeObject<'a>;
+
+    fn serialize_bool(self, v: bool) -> Result<DataValue<'a>> {
+        Ok(DataValue::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<DataValue<'a>> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<DataValue<'a>> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<DataValue<'a>> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<DataValue<'a>> {
+        Ok(DataValue::Number(Number::Integer(v)))
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<DataValue<'a>> {
+        Ok(self.wide_integer(v.to_string()))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<DataValue<'a>> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<DataValue<'a>> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<DataValue<'a>> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<DataValue<'a>> {
+        match i64::try_from(v) {
+            Ok(i) => self.serialize_i64(i),
+            Err(_) => Ok(self.wide_integer(v.to_string())),
+        }
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<DataValue<'a>> {
+        Ok(self.wide_integer(v.to_string()))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<DataValue<'a>> {
+        self.serialize_f64(v.into())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<DataValue<'a>> {
+        Ok(DataValue::Number(Number::Float(v)))
+    }
+
+    fn serialize_char(self, v: char) -> Result<DataValue<'a>> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<DataValue<'a>> {
+        Ok(DataValue::String(self.arena.alloc_str(v)))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<DataValue<'a>> {
+        let bytes = v
+            .iter()
+            .map(|&b| DataValue::Number(Number::Integer(b.into())));
+        Ok(DataValue::Array(self.arena.alloc_slice_fill_iter(bytes)))
+    }
+
+    fn serialize_none(self) -> Result<DataValue<'a>> {
+        Ok(DataValue::Null)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<DataValue<'a>> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<DataValue<'a>> {
+        Ok(DataValue::Null)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<DataValue<'a>> {
+        Ok(DataValue::Null)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<DataValue<'a>> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<DataValue<'a>> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<DataValue<'a>> {
+        let value = value.serialize(self)?;
+        Ok(self.variant(variant, value))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SerializeArray<'a>> {
+        Ok(SerializeArray {
+            serializer: self,
+            variant: None,
+            items: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SerializeArray<'a>> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<SerializeArray<'a>> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeArray<'a>> {
+        let mut array = self.serialize_seq(Some(len))?;
+        array.variant = Some(variant);
+        Ok(array)
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<SerializeObject<'a>> {
+        Ok(SerializeObject {
+            serializer: self,
+            variant: None,
+            entries: Vec::with_capacity(len.unwrap_or(0)),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<SerializeObject<'a>> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeObject<'a>> {
+        let mut object = self.serialize_map(Some(len))?;
+        object.variant = Some(variant);
+        Ok(object)
+    }
+}
+
+/// Collects the elements of a sequence, tuple or tuple variant
+struct SerializeArray<'a> {
+    serializer: ValueSerializer<'a>,
+    /// Enum variant the array is wrapped in
+    variant: Option<&'static str>,
+    items: Vec<DataValue<'a>>,
+}
+
+impl<'a> SerializeArray<'a> {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.items.push(value.serialize(self.serializer)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<DataValue<'a>> {
+        let array = DataValue::Array(self.serializer.arena.alloc_slice_fill_iter(self.items));
+        Ok(match self.variant {
+            Some(variant) => self.serializer.variant(variant, array),
+            None => array,
+        })
+    }
+}
+
+impl<'a> ser::SerializeSeq for SerializeArray<'a> {
+    type Ok = DataValue<'a>;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<DataValue<'a>> {
+        self.finish()
+    }
+}
+
+impl<'a> ser::SerializeTuple for SerializeArray<'a> {
+    type Ok = DataValue<'a>;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<DataValue<'a>> {
+        self.finish()
+    }
+}
+
+impl<'a> ser::SerializeTupleStruct for SerializeArray<'a> {
+    type Ok = DataValue<'a>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<DataValue<'a>> {
+        self.finish()
+    }
+}
+
+impl<'a> ser::SerializeTupleVariant for SerializeArray<'a> {
+    type Ok = DataValue<'a>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<DataValue<'a>> {
+        self.finish()
+    }
+}
+
+/// Collects the entries of a map, struct or struct variant
+struct SerializeObject<'a> {
+    serializer: ValueSerializer<'a>,
+    /// Enum variant the object is wrapped in
+    variant: Option<&'static str>,
+    entries: Vec<(&'a str, DataValue<'a>)>,
+    /// Key passed to `serialize_key` and waiting for its value
+    key: Option<&'a str>,
+}
+
+impl<'a> SerializeObject<'a> {
+    fn entry<T: Serialize + ?Sized>(&mut self, key: &str, value: &T) -> Result<()> {
+        let key: &'a str = self.serializer.arena.alloc_str(key);
+        self.entries.push((key, value.serialize(self.serializer)?));
+        Ok(())
+    }
+
+    fn finish(self) -> Result<DataValue<'a>> {
+        let object = DataValue::Object(self.serializer.arena.alloc_slice_fill_iter(self.entries));
+        Ok(match self.variant {
+            Some(variant) => self.serializer.variant(variant, object),
+            None => object,
+        })
+    }
+}
+
+impl<'a> ser::SerializeMap for SerializeObject<'a> {
+    type Ok = DataValue<'a>;
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<()> {
+        let key = match key.serialize(self.serializer)? {
+            DataValue::String(s) => s,
+            DataValue::Number(Number::Integer(i)) => {
+                self.serializer.arena.alloc_str(&i.to_string())
+            }
+            DataValue::Number(Number::Float(f)) => self.serializer.arena.alloc_str(&f.to_string()),
+            DataValue::Bool(b) => {
+                if b {
+                    "true"
+                } else {
+                    "false"
+                }
+            }
+            other => {
+                return Err(Error::custom(format!(
+                    "map key must be a string, found {:?}",
+                    other.get_type()
+                )))
+            }
+        };
+        self.key = Some(key);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| Error::custom("serialize_value called before serialize_key"))?;
+        self.entries.push((key, value.serialize(self.serializer)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<DataValue<'a>> {
+        self.finish()
+    }
+}
+
+impl<'a> ser::SerializeStruct for SerializeObject<'a> {
+    type Ok = DataValue<'a>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.entry(key, value)
+    }
+
+    fn end(self) -> Result<DataValue<'a>> {
+        self.finish()
+    }
+}
+
+impl<'a> ser::SerializeStructVariant for SerializeObject<'a> {
+    type Ok = DataValue<'a>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.entry(key, value)
+    }
+
+    fn end(self) -> Result<DataValue<'a>> {
+        self.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::to_string;
+    use serde::Serialize;
+    use std::collections::BTreeMap;
+
+    #[derive(Serialize)]
+    enum Shape {
+        Empty,
+        Circle(f64),
+        Point(i32, i32),
+        Rect { w: u8, h: u8 },
+    }
+
+    #[derive(Serialize)]
+    struct Drawing {
+        id: u64,
+        shapes: Vec<Shape>,
+        layers: BTreeMap<u16, char>,
+        offset: (i8, i8),
+        hidden: Option<bool>,
+        #[serde(with = "serde_bytes_like")]
+        thumbnail: Vec<u8>,
+    }
+
+    mod serde_bytes_like {
+        pub fn serialize<S: serde::Serializer>(bytes: &[u8], s: S) -> Result<S::Ok, S::Error> {
+            s.serialize_bytes(bytes)
+        }
+    }
+
+    #[test]
+    fn test_to_value() {
+        let arena = Bump::new();
+        let drawing = Drawing {
+            id: 7,
+            shapes: vec![
+                Shape::Empty,
+                Shape::Circle(1.5),
+                Shape::Point(-1, 2),
+                Shape::Rect { w: 3, h: 4 },
+            ],
+            layers: BTreeMap::from([(1, 'a'), (2, '\u{e9}')]),
+            offset: (0, -1),
+            hidden: None,
+            thumbnail: vec![0, 255],
+        };
+        let value = to_value(&arena, &drawing).unwrap();
+        assert_eq!(
+            to_string(&value),
+            concat!(
+                r#"{"id":7,"shapes":["Empty",{"Circle":1.5},{"Point":[-1,2]},{"Rect":{"w":3,"h":4}}],"#,
+                r#""layers":{"1":"a","2":"é"},"offset":[0,-1],"hidden":null,"thumbnail":[0,255]}"#
+            )
+        );
+
+        // The same result as going through serde_json
+        let json = serde_json::to_value(&drawing).unwrap();
+        assert_eq!(value, crate::from_json(&arena, &json).unwrap());
+
+        let big = to_value(&arena, &u64::MAX).unwrap();
+        if cfg!(feature = "bignum") {
+            assert_eq!(to_string(&big), u64::MAX.to_string());
+        } else {
+            assert_eq!(big.as_f64(), Some(u64::MAX as f64));
+        }
+
+        let bad_key = BTreeMap::from([((1, 2), "tuple key")]);
+        assert!(to_value(&arena, &bad_key).is_err());
+    }
+}
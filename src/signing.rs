@@ -0,0 +1,239 @@
+//! Signed document envelopes
+//!
+//! This module wraps a DataValue in a `{"payload", "protected", "signature"}`
+//! envelope, where `protected` names the signing algorithm and key id. The signature
+//! covers the canonical serialization of both the protected header and the payload,
+//! so envelopes survive re-serialization with different key order or whitespace.
+//!
+//! Cryptography is pluggable: implement [`Signer`] and [`Verifier`] on top of the
+//! primitives your service already uses.
+
+use bumpalo::Bump;
+
+use crate::base64;
+use crate::datavalue::DataValue;
+use crate::error::{Error, Result};
+use crate::ser::write_canonical;
+
+/// Produces signatures for envelopes
+pub trait Signer {
+    /// Identifier of the key, embedded in the envelope as `protected.kid`
+    fn key_id(&self) -> &str;
+
+    /// Name of the signing algorithm, embedded in the envelope as `protected.alg`
+    fn algorithm(&self) -> &str;
+
+    /// Signs the message bytes
+    fn sign(&self, message: &[u8]) -> Result<Vec<u8>>;
+}
+
+/// Checks envelope signatures
+///
+/// Implementations typically look up the key by `key_id` in a key ring and reject
+/// unknown keys or algorithms that do not match the key.
+pub trait Verifier {
+    /// Returns Ok(true) if `signature` is valid for `message` under the given key
+    fn verify(
+        &self,
+        key_id: &str,
+        algorithm: &str,
+        message: &[u8],
+        signature: &[u8],
+    ) -> Result<bool>;
+}
+
+/// Wraps a payload in a signed envelope
+///
+/// # Arguments
+///
+/// * `arena` - The arena allocator to store the envelope
+/// * `payload` - The document to sign
+/// * `signer` - The signer providing the key id, algorithm and signature
+///
+/// # Returns
+///
+/// A Result containing the envelope object, or an Error if signing fails.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::{signing::{self, Signer, Verifier}, Bump, Result, from_str};
+///
+/// // A toy keyed checksum; use a real MAC or signature scheme in production
+/// struct Checksum(&'static str, u8);
+///
+/// impl Signer for Checksum {
+///     fn key_id(&self) -> &str { self.0 }
+///     fn algorithm(&self) -> &str { "XOR8" }
+///     fn sign(&self, message: &[u8]) -> Result<Vec<u8>> {
+///         Ok(vec![message.iter().fold(self.1, |acc, b| acc ^ b)])
+///     }
+/// }
+///
+/// impl Verifier for Checksum {
+///     fn verify(&self, kid: &str, alg: &str, message: &[u8], sig: &[u8]) -> Result<bool> {
+///         Ok(kid == self.0 && alg == "XOR8" && self.sign(message)? == sig)
+///     }
+/// }
+///
+/// let arena = Bump::new();
+/// let payload = from_str(&arena, r#"{"user": "ada", "role": "admin"}"#).unwrap();
+/// let key = Checksum("key-1", 0x5a);
+///
+/// let envelope = signing::sign(&arena, &payload, &key).unwrap();
+/// assert_eq!(signing::key_id(&envelope), Some("key-1"));
+///
+/// let verified = signing::verify(&envelope, &key).unwrap();
+/// assert_eq!(verified["user"].as_str(), Some("ada"));
+/// ```
+pub fn sign<'a>(
+    arena: &'a Bump,
+    payload: &DataValue<'a>,
+    signer: &dyn Signer,
+) -> Result<DataValue<'a>> {
+    let protected = DataValue::Object(arena.alloc_slice_clone(&[
+        (
+            "alg",
+            DataValue::String(arena.alloc_str(signer.algorithm())),
+        ),
+        ("kid", DataValue::String(arena.alloc_str(signer.key_id()))),
+    ]));
+    let message = signing_input(&protected, payload);
+    let signature = signer.sign(message.as_bytes())?;
+    let signature = arena.alloc_str(&base64::encode_url(&signature));
+
+    Ok(DataValue::Object(arena.alloc_slice_clone(&[
+        ("payload", payload.clone()),
+        ("protected", protected),
+        ("signature", DataValue::String(signature)),
+    ])))
+}
+
+/// Verifies an envelope and returns its payload
+///
+/// # Errors
+///
+/// Returns an error if the envelope is malformed, the verifier reports an error,
+/// or the signature does not match.
+pub fn verify<'v, 'a>(
+    envelope: &'v DataValue<'a>,
+    verifier: &dyn Verifier,
+) -> Result<&'v DataValue<'a>> {
+    let payload = envelope
+        .get("payload")
+        .ok_or_else(|| Error::missing_field("payload"))?;
+    let protected = envelope
+        .get("protected")
+        .ok_or_else(|| Error::missing_field("protected"))?;
+    let header = |name: &str| -> Result<&'a str> {
+        protected
+            .get(name)
+            .ok_or_else(|| Error::missing_field(format!("protected.{}", name)))?
+            .as_str()
+            .ok_or_else(|| Error::custom(format!("protected.{} must be a string", name)))
+    };
+    let algorithm = header("alg")?;
+    let key_id = header("kid")?;
+    let signature = envelope
+        .get("signature")
+        .and_then(DataValue::as_str)
+        .ok_or_else(|| Error::missing_field("signature"))?;
+    let signature = base64::decode_url(signature)
+        .map_err(|e| Error::syntax(format!("Invalid base64url signature: {}", e)))?;
+
+    let message = signing_input(protected, payload);
+    if verifier.verify(key_id, algorithm, message.as_bytes(), &signature)? {
+        Ok(payload)
+    } else {
+        Err(Error::custom(format!(
+            "Signature verification failed for key {}",
+            key_id
+        )))
+    }
+}
+
+/// Returns the key id embedded in an envelope without verifying it
+///
+/// Useful for selecting the verification key before calling [`verify`].
+pub fn key_id<'a>(envelope: &DataValue<'a>) -> Option<&'a str> {
+    envelope
+        .get("protected")
+        .and_then(|p| p.get("kid"))
+        .and_then(DataValue::as_str)
+}
+
+/// Bytes covered by the signature: canonical protected header, `.`, canonical payload
+fn signing_input(protected: &DataValue<'_>, payload: &DataValue<'_>) -> String {
+    let mut message = String::new();
+    write_canonical(protected, &mut message);
+    message.push('.');
+    write_canonical(payload, &mut message);
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_str;
+
+    struct Sum(&'static str);
+
+    impl Signer for Sum {
+        fn key_id(&self) -> &str {
+            self.0
+        }
+        fn algorithm(&self) -> &str {
+            "SUM"
+        }
+        fn sign(&self, message: &[u8]) -> Result<Vec<u8>> {
+            let sum: u32 = message.iter().map(|b| *b as u32).sum();
+            Ok(sum.to_be_bytes().to_vec())
+        }
+    }
+
+    impl Verifier for Sum {
+        fn verify(&self, kid: &str, alg: &str, message: &[u8], sig: &[u8]) -> Result<bool> {
+            if kid != self.0 {
+                return Err(Error::custom(format!("Unknown key {}", kid)));
+            }
+            Ok(alg == "SUM" && self.sign(message)? == sig)
+        }
+    }
+
+    #[test]
+    fn test_envelope_survives_reserialization() {
+        let arena = Bump::new();
+        let payload = from_str(&arena, r#"{"b": [1, 2.0], "a": "x"}"#).unwrap();
+        let envelope = sign(&arena, &payload, &Sum("k1")).unwrap();
+
+        // Reorder keys and whitespace through a serde_json round trip
+        let text = crate::to_string_pretty(&envelope);
+        let reparsed = from_str(&arena, &text).unwrap();
+        assert_eq!(
+            verify(&reparsed, &Sum("k1")).unwrap()["a"].as_str(),
+            Some("x")
+        );
+        assert!(verify(&reparsed, &Sum("k2")).is_err());
+    }
+
+    #[test]
+    fn test_tampered_envelope_is_rejected() {
+        let arena = Bump::new();
+        let payload = from_str(&arena, r#"{"amount": 10}"#).unwrap();
+        let envelope = sign(&arena, &payload, &Sum("k1")).unwrap();
+        let signature = envelope["signature"].as_str().unwrap();
+
+        let forged = from_str(
+            &arena,
+            &format!(
+                r#"{{"payload": {{"amount": 1000}}, "protected": {{"alg": "SUM", "kid": "k1"}}, "signature": "{}"}}"#,
+                signature
+            ),
+        )
+        .unwrap();
+        assert!(verify(&forged, &Sum("k1")).is_err());
+
+        let unsigned = from_str(&arena, r#"{"payload": {}}"#).unwrap();
+        assert!(verify(&unsigned, &Sum("k1")).is_err());
+    }
+}
@@ -0,0 +1,327 @@
+//! Test support for code that produces DataValues
+//!
+//! [`assert_datavalue_snapshot!`](crate::assert_datavalue_snapshot) compares a
+//! value against a snapshot file stored next to the crate's tests. Values are
+//! written in canonical form, with sorted keys and two-space indentation, so
+//! snapshots do not change when only key order does. Volatile fields such as
+//! timestamps and generated ids can be redacted before the comparison.
+//!
+//! A missing snapshot file is written and the assertion passes, so new snapshots
+//! are recorded by running the tests once. Set `DATAVALUE_UPDATE_SNAPSHOTS=1` to
+//! overwrite snapshots that no longer match instead of failing.
+//!
+//! [`fixture!`](crate::fixture) loads a file from the crate's `tests/fixtures`
+//! directory once per test binary and hands out the parsed document for the rest
+//! of the run.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use bumpalo::Bump;
+
+use crate::access::unescape_pointer_token;
+use crate::datavalue::DataValue;
+use crate::document::Document;
+use crate::operations::{diff_pretty_with_options, DiffOptions};
+use crate::ser::write_canonical_pretty;
+
+/// Environment variable that makes mismatching snapshots be overwritten
+pub const UPDATE_SNAPSHOTS_ENV: &str = "DATAVALUE_UPDATE_SNAPSHOTS";
+
+/// The string that redacted values are replaced with
+pub const REDACTED: &str = "[redacted]";
+
+/// Asserts that a DataValue matches a stored snapshot
+///
+/// The snapshot is stored as `tests/snapshots/<module>__<name>.json` under the
+/// crate being tested, where `<module>` is the module path of the caller with
+/// `::` replaced by `__`. Redactions are JSON pointers whose `*` tokens match any
+/// key or index; the values they select are replaced with `"[redacted]"`.
+///
+/// # Example
+///
+/// ```no_run
+/// use datavalue_rs::{assert_datavalue_snapshot, Bump, from_str};
+///
+/// let arena = Bump::new();
+/// let order = from_str(&arena, r#"{"id": "f3a1", "items": [{"sku": "A", "added": "2024-05-01"}]}"#).unwrap();
+/// assert_datavalue_snapshot!("order", order, redact = ["/id", "/items/*/added"]);
+/// ```
+#[macro_export]
+macro_rules! assert_datavalue_snapshot {
+    ($name:expr, $value:expr $(,)?) => {
+        $crate::assert_datavalue_snapshot!($name, $value, redact = [])
+    };
+    ($name:expr, $value:expr, redact = [$($pattern:expr),* $(,)?] $(,)?) => {
+        $crate::testing::assert_snapshot(
+            &$crate::testing::snapshot_path(env!("CARGO_MANIFEST_DIR"), module_path!(), $name),
+            &$value,
+            &[$($pattern),*],
+        )
+    };
+}
+
+/// Returns the path of the snapshot file for a named snapshot
+///
+/// Used by [`assert_datavalue_snapshot!`](crate::assert_datavalue_snapshot); see
+/// there for the layout.
+pub fn snapshot_path(manifest_dir: &str, module_path: &str, name: &str) -> PathBuf {
+    Path::new(manifest_dir)
+        .join("tests")
+        .join("snapshots")
+        .join(format!(
+            "{}__{}.json",
+            module_path.replace("::", "__"),
+            name
+        ))
+}
+
+/// Asserts that `value`, after redaction, matches the snapshot stored at `path`
+///
+/// # Panics
+///
+/// Panics with a diff of the stored and the actual value if they differ, unless
+/// `DATAVALUE_UPDATE_SNAPSHOTS` is set to `1`. Also panics if the snapshot file
+/// cannot be read, parsed or written.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::{testing, Bump, from_str};
+///
+/// let path = std::env::temp_dir().join(format!("datavalue-doc-{}.json", std::process::id()));
+/// let arena = Bump::new();
+/// let first = from_str(&arena, r#"{"user": "ada", "seen_at": "2024-05-01T10:00:00Z"}"#).unwrap();
+/// let later = from_str(&arena, r#"{"seen_at": "2024-06-12T08:30:00Z", "user": "ada"}"#).unwrap();
+///
+/// testing::assert_snapshot(&path, &first, &["/seen_at"]);
+/// testing::assert_snapshot(&path, &later, &["/seen_at"]);
+/// assert_eq!(
+///     std::fs::read_to_string(&path).unwrap(),
+///     "{\n  \"seen_at\": \"[redacted]\",\n  \"user\": \"ada\"\n}\n"
+/// );
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+pub fn assert_snapshot(path: &Path, value: &DataValue<'_>, redactions: &[&str]) {
+    let arena = Bump::new();
+    let actual = redact(&arena, value, redactions);
+    let mut text = String::new();
+    write_canonical_pretty(&actual, "  ", &mut text);
+    text.push('\n');
+
+    let update = std::env::var(UPDATE_SNAPSHOTS_ENV).is_ok_and(|v| v == "1");
+    match fs::read_to_string(path) {
+        Ok(stored) if stored == text => {}
+        Ok(stored) if !update => {
+            let expected = crate::from_str(&arena, &stored)
+                .unwrap_or_else(|e| panic!("snapshot {} is not valid JSON: {}", path.display(), e));
+            let diff = diff_pretty_with_options(&expected, &actual, &DiffOptions { color: false });
+            panic!(
+                "snapshot {} does not match (set {}=1 to update)\n{}",
+                path.display(),
+                UPDATE_SNAPSHOTS_ENV,
+                if diff.is_empty() {
+                    "only the formatting differs\n".to_string()
+                } else {
+                    diff
+                }
+            );
+        }
+        _ => {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir).unwrap_or_else(|e| {
+                    panic!("cannot create snapshot directory {}: {}", dir.display(), e)
+                });
+            }
+            fs::write(path, text)
+                .unwrap_or_else(|e| panic!("cannot write snapshot {}: {}", path.display(), e));
+        }
+    }
+}
+
+/// Loads a fixture file as a document that lives for the rest of the program
+///
+/// The path is relative to the `tests/fixtures` directory of the crate being
+/// tested, and the format is chosen from the extension as in [`Document::load`].
+/// The file is read and parsed the first time each `fixture!` call site runs;
+/// later runs, including from other threads, return the same document.
+///
+/// # Panics
+///
+/// Panics if the file cannot be read or parsed.
+///
+/// # Example
+///
+/// ```no_run
+/// use datavalue_rs::fixture;
+///
+/// let orders = fixture!("orders.json").root();
+/// assert_eq!(orders[0]["status"].as_str(), Some("shipped"));
+/// ```
+#[macro_export]
+macro_rules! fixture {
+    ($path:expr $(,)?) => {{
+        static FIXTURE: ::std::sync::OnceLock<&'static $crate::Document<'static>> =
+            ::std::sync::OnceLock::new();
+        *FIXTURE.get_or_init(|| {
+            $crate::testing::load_fixture(
+                ::std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+                    .join("tests")
+                    .join("fixtures")
+                    .join($path),
+            )
+        })
+    }};
+}
+
+/// Loads and parses a file into a document that is never freed
+///
+/// Used by [`fixture!`](crate::fixture), which calls it once per call site. The
+/// arena and document are leaked so the document can be borrowed for `'static`.
+///
+/// # Panics
+///
+/// Panics if the file cannot be read or parsed.
+pub fn load_fixture(path: impl AsRef<Path>) -> &'static Document<'static> {
+    let path = path.as_ref();
+    let arena: &'static Bump = Box::leak(Box::new(Bump::new()));
+    let document = Document::load(arena, path)
+        .unwrap_or_else(|e| panic!("cannot load fixture {}: {}", path.display(), e));
+    Box::leak(Box::new(document))
+}
+
+/// Returns a copy of `value` with every value selected by `patterns` replaced
+/// with `"[redacted]"`
+///
+/// Patterns are JSON pointers in which a `*` token matches any object key or
+/// array index. Patterns that select nothing are ignored. Subtrees without a
+/// redaction are shared with `value`.
+///
+/// # Example
+///
+/// ```
+/// # use datavalue_rs::{testing, Bump, from_str, to_string};
+/// let arena = Bump::new();
+/// let events = from_str(&arena, r#"[{"id": 7, "kind": "login"}, {"id": 9, "kind": "logout"}]"#).unwrap();
+/// let redacted = testing::redact(&arena, &events, &["/*/id"]);
+/// assert_eq!(
+///     to_string(&redacted),
+///     r#"[{"id":"[redacted]","kind":"login"},{"id":"[redacted]","kind":"logout"}]"#
+/// );
+/// ```
+pub fn redact<'a>(arena: &'a Bump, value: &DataValue<'a>, patterns: &[&str]) -> DataValue<'a> {
+    let patterns: Vec<Vec<String>> = patterns
+        .iter()
+        .map(|pattern| {
+            pattern
+                .split('/')
+                .skip(1)
+                .map(|token| unescape_pointer_token(token).into_owned())
+                .collect()
+        })
+        .collect();
+    let selected: Vec<&[String]> = patterns.iter().map(Vec::as_slice).collect();
+    redact_tokens(arena, value, &selected)
+}
+
+/// Redacts the values selected by the remaining pointer tokens of each pattern
+fn redact_tokens<'a>(
+    arena: &'a Bump,
+    value: &DataValue<'a>,
+    patterns: &[&[String]],
+) -> DataValue<'a> {
+    if patterns.iter().any(|tokens| tokens.is_empty()) {
+        return DataValue::String(REDACTED);
+    }
+    if patterns.is_empty() {
+        return value.clone();
+    }
+    let matching = |key: &str| -> Vec<&[String]> {
+        patterns
+            .iter()
+            .filter(|tokens| tokens[0] == "*" || tokens[0] == key)
+            .map(|tokens| &tokens[1..])
+            .collect()
+    };
+    match value {
+        DataValue::Array(items) => {
+            let items: Vec<DataValue<'a>> = items
+                .iter()
+                .enumerate()
+                .map(|(i, item)| redact_tokens(arena, item, &matching(&i.to_string())))
+                .collect();
+            DataValue::Array(arena.alloc_slice_clone(&items))
+        }
+        DataValue::Object(entries) => {
+            let entries: Vec<(&'a str, DataValue<'a>)> = entries
+                .iter()
+                .map(|(key, item)| (*key, redact_tokens(arena, item, &matching(key))))
+                .collect();
+            DataValue::Object(arena.alloc_slice_clone(&entries))
+        }
+        scalar => scalar.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_patterns() {
+        let arena = Bump::new();
+        let value = crate::from_str(
+            &arena,
+            r#"{"a/b": 1, "list": [{"id": 1, "keep": 2}, {"id": 3}], "id": 4, "n": null}"#,
+        )
+        .unwrap();
+        let redacted = redact(
+            &arena,
+            &value,
+            &["/a~1b", "/list/*/id", "/missing/x", "/n/x"],
+        );
+        assert_eq!(
+            crate::to_string(&redacted),
+            r#"{"a/b":"[redacted]","list":[{"id":"[redacted]","keep":2},{"id":"[redacted]"}],"id":4,"n":null}"#
+        );
+        assert_eq!(
+            crate::to_string(&redact(&arena, &value, &[""])),
+            "\"[redacted]\""
+        );
+        assert_eq!(redact(&arena, &value, &[]), value);
+    }
+
+    #[test]
+    fn test_fixture_loads_once() {
+        let load = || crate::fixture!("orders.json");
+        let orders = load();
+        assert!(std::ptr::eq(orders, load()));
+        assert_eq!(orders.root()[1]["items"][0]["sku"].as_str(), Some("B-2"));
+        let span = orders.span_of_pointer("/1/status").unwrap();
+        assert_eq!(&orders.source()[span], "\"pending\"");
+
+        let other = std::thread::spawn(move || load() as *const Document as usize);
+        assert_eq!(other.join().unwrap(), orders as *const Document as usize);
+    }
+
+    #[test]
+    fn test_snapshot_mismatch_reports_diff() {
+        let dir = std::env::temp_dir().join(format!("datavalue-snapshots-{}", std::process::id()));
+        let path = snapshot_path(dir.to_str().unwrap(), "crate::tests", "mismatch");
+        assert!(path.ends_with("tests/snapshots/crate__tests__mismatch.json"));
+
+        let arena = Bump::new();
+        let stored = crate::from_str(&arena, r#"{"b": [1, 2], "a": "x"}"#).unwrap();
+        assert_snapshot(&path, &stored, &[]);
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "{\n  \"a\": \"x\",\n  \"b\": [\n    1,\n    2\n  ]\n}\n"
+        );
+
+        let changed = crate::from_str(&arena, r#"{"a": "x", "b": [1, 3]}"#).unwrap();
+        let panic = std::panic::catch_unwind(|| assert_snapshot(&path, &changed, &[])).unwrap_err();
+        let message = panic.downcast_ref::<String>().unwrap();
+        assert!(message.contains("@@ /b/1 @@\n- 2\n+ 3\n"), "{}", message);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,578 @@
+//! UBJSON input and output, enabled by the `ubjson` feature
+//!
+//! [Universal Binary JSON](https://ubjson.org) has exactly the JSON data model,
+//! with typed numbers, length-prefixed strings and optional element counts in
+//! place of punctuation, so it suits message buses that want smaller payloads
+//! without a schema. [`from_slice`] decodes one value and [`to_vec`] encodes
+//! one.
+//!
+//! UBJSON values map onto DataValue as follows:
+//!
+//! | UBJSON | DataValue |
+//! |---|---|
+//! | `Z` | [`DataValue::Null`] |
+//! | `T`, `F` | [`DataValue::Bool`] |
+//! | `i`, `U`, `I`, `l`, `L` | [`Number::Integer`] |
+//! | `d`, `D` | [`Number::Float`] |
+//! | `H` | [`Number::Integer`], [`Number::Float`], or [`Number::BigInt`] and [`Number::BigDecimal`] when those would lose digits |
+//! | `C`, `S` | [`DataValue::String`] |
+//! | `[`, `{`, with or without a type and count | [`DataValue::Array`], [`DataValue::Object`] |
+//!
+//! The `N` no-op marker is skipped wherever a value may start. The encoder
+//! writes integers in the smallest type that holds them, floats as `d` when
+//! single precision is exact, numbers beyond `i64` and `f64` as `H`, and
+//! containers with their end markers. Non-finite floats become `Z`, and
+//! DateTimes and durations become strings, as in JSON.
+//!
+//! # Example
+//!
+//! ```
+//! use datavalue_rs::{from_str, ubjson, Bump};
+//!
+//! let arena = Bump::new();
+//! let value = from_str(&arena, r#"{"id": 300, "ok": true}"#).unwrap();
+//! let bytes = ubjson::to_vec(&value).unwrap();
+//! assert_eq!(bytes, b"{i\x02idI\x01\x2ci\x02okT}");
+//! assert_eq!(ubjson::from_slice(&arena, &bytes).unwrap(), value);
+//! ```
+
+use bumpalo::Bump;
+
+use crate::datavalue::{DataValue, Number};
+use crate::error::{Error, Result};
+use crate::helpers::format_duration;
+use crate::ser::write_json_scalar;
+use crate::{from_str_with_options, NumberMode, ParseOptions};
+
+/// Most elements that strongly typed `Z`, `T` and `F` arrays may hold in one
+/// input, counted across all such arrays
+///
+/// Those elements take no bytes, so without a limit a few bytes of input could
+/// ask for billions of values.
+pub const MAX_PAYLOADLESS_ELEMENTS: u64 = 1 << 20;
+
+/// An open array or object
+struct Frame<'a> {
+    /// Elements still to read, or None until the end marker
+    remaining: Option<u64>,
+    /// Marker shared by every element of a strongly typed container
+    element: Option<u8>,
+    /// Whether this is an object, whose elements are preceded by keys
+    object: bool,
+    items: Vec<DataValue<'a>>,
+    entries: Vec<(&'a str, DataValue<'a>)>,
+    /// Key of the object entry whose value is being read
+    key: Option<&'a str>,
+}
+
+/// Decodes one UBJSON value
+///
+/// # Errors
+///
+/// Returns a syntax error with the byte offset of the problem if the input is
+/// not valid UBJSON, is followed by anything but no-op markers, or contains a
+/// string that is not UTF-8, a negative length or an invalid high-precision
+/// number. Strongly typed `Z`, `T` and `F` arrays with more than
+/// [`MAX_PAYLOADLESS_ELEMENTS`] elements in total are rejected.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::{ubjson, Bump};
+///
+/// let arena = Bump::new();
+/// // A strongly typed array of three uint8 values
+/// let value = ubjson::from_slice(&arena, b"[$U#i\x03\x01\x02\x03").unwrap();
+/// assert_eq!(value[2].as_i64(), Some(3));
+/// ```
+pub fn from_slice<'a>(arena: &'a Bump, bytes: &[u8]) -> Result<DataValue<'a>> {
+    let mut reader = Reader {
+        arena,
+        bytes,
+        pos: 0,
+        payloadless: 0,
+    };
+    let value = reader.read_value()?;
+    reader.skip_noops();
+    if reader.pos < bytes.len() {
+        return Err(reader.error("Trailing bytes after UBJSON value"));
+    }
+    Ok(value)
+}
+
+/// Encodes a value as UBJSON
+///
+/// # Errors
+///
+/// Returns an error if a raw value is not valid JSON.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::{from_str, ubjson, Bump};
+///
+/// let arena = Bump::new();
+/// let value = from_str(&arena, r#"[-1, 1.5, "é"]"#).unwrap();
+/// assert_eq!(ubjson::to_vec(&value).unwrap(), b"[i\xFFd\x3F\xC0\x00\x00Si\x02\xC3\xA9]");
+/// ```
+pub fn to_vec(value: &DataValue<'_>) -> Result<Vec<u8>> {
+    let mut output = Vec::new();
+    write_value(value, &mut output)?;
+    Ok(output)
+}
+
+struct Reader<'a, 'b> {
+    arena: &'a Bump,
+    bytes: &'b [u8],
+    pos: usize,
+    /// Elements of strongly typed arrays without a payload read so far
+    payloadless: u64,
+}
+
+impl<'a, 'b> Reader<'a, 'b> {
+    fn error(&self, msg: &str) -> Error {
+        Error::syntax(format!("{} at byte {}", msg, self.pos))
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        let b = *self
+            .bytes
+            .get(self.pos)
+            .ok_or_else(|| self.error("Truncated UBJSON value"))?;
+        self.pos += 1;
+        Ok(b)
+    }
+
+    fn take(&mut self, len: u64) -> Result<&'b [u8]> {
+        let available = self.bytes.len() - self.pos;
+        let len = usize::try_from(len)
+            .ok()
+            .filter(|&len| len <= available)
+            .ok_or_else(|| self.error("Truncated UBJSON value"))?;
+        self.pos += len;
+        Ok(&self.bytes[self.pos - len..self.pos])
+    }
+
+    fn take_array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        Ok(self.take(N as u64)?.try_into().expect("length is N"))
+    }
+
+    fn skip_noops(&mut self) {
+        while self.bytes.get(self.pos) == Some(&b'N') {
+            self.pos += 1;
+        }
+    }
+
+    /// Reads the payload of an integer with the given marker
+    fn integer(&mut self, marker: u8) -> Result<Option<i64>> {
+        let i = match marker {
+            b'i' => i64::from(self.byte()? as i8),
+            b'U' => i64::from(self.byte()?),
+            b'I' => i64::from(i16::from_be_bytes(self.take_array()?)),
+            b'l' => i64::from(i32::from_be_bytes(self.take_array()?)),
+            b'L' => i64::from_be_bytes(self.take_array()?),
+            _ => return Ok(None),
+        };
+        Ok(Some(i))
+    }
+
+    /// Reads a length or count, which is an integer value with its marker
+    fn length(&mut self) -> Result<u64> {
+        let marker = self.byte()?;
+        match self.integer(marker)? {
+            Some(len) => u64::try_from(len).map_err(|_| self.error("Negative UBJSON length")),
+            None => {
+                self.pos -= 1;
+                Err(self.error("Expected a UBJSON length"))
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<&'a str> {
+        let len = self.length()?;
+        let bytes = self.take(len)?;
+        let text =
+            std::str::from_utf8(bytes).map_err(|_| self.error("Invalid UTF-8 in UBJSON string"))?;
+        Ok(self.arena.alloc_str(text))
+    }
+
+    /// Reads the payload of a value with the given marker, returning None and
+    /// opening a frame for containers
+    fn payload(&mut self, marker: u8, stack: &mut Vec<Frame<'a>>) -> Result<Option<DataValue<'a>>> {
+        let float = |f: f64| DataValue::Number(Number::Float(f));
+        let value = match marker {
+            b'Z' => DataValue::Null,
+            b'T' => DataValue::Bool(true),
+            b'F' => DataValue::Bool(false),
+            b'd' => float(f64::from(f32::from_be_bytes(self.take_array()?))),
+            b'D' => float(f64::from_be_bytes(self.take_array()?)),
+            b'H' => {
+                let start = self.pos;
+                let text = self.string()?;
+                let options = ParseOptions {
+                    number_mode: NumberMode::Arbitrary,
+                    ..Default::default()
+                };
+                match from_str_with_options(self.arena, text, &options) {
+                    Ok(number @ DataValue::Number(_)) => number,
+                    _ => {
+                        return Err(Error::syntax(format!(
+                            "Invalid UBJSON high-precision number at byte {}",
+                            start
+                        )))
+                    }
+                }
+            }
+            b'C' => {
+                let c = self.byte()?;
+                if !c.is_ascii() {
+                    self.pos -= 1;
+                    return Err(self.error("UBJSON char must be ASCII"));
+                }
+                DataValue::String(self.arena.alloc_str(char::from(c).encode_utf8(&mut [0; 4])))
+            }
+            b'S' => DataValue::String(self.string()?),
+            b'[' | b'{' => {
+                self.open(marker == b'{', stack)?;
+                return Ok(None);
+            }
+            _ => match self.integer(marker)? {
+                Some(i) => DataValue::Number(Number::Integer(i)),
+                None => {
+                    self.pos -= 1;
+                    return Err(self.error("Unexpected UBJSON marker"));
+                }
+            },
+        };
+        Ok(Some(value))
+    }
+
+    /// Reads the optional type and count after `[` or `{` and opens a frame
+    fn open(&mut self, object: bool, stack: &mut Vec<Frame<'a>>) -> Result<()> {
+        let mut element = None;
+        if self.bytes.get(self.pos) == Some(&b'$') {
+            self.pos += 1;
+            let marker = self.byte()?;
+            if matches!(marker, b'N' | b'[' | b'{' | b']' | b'}' | b'$' | b'#') {
+                self.pos -= 1;
+                return Err(self.error("Invalid UBJSON container type"));
+            }
+            element = Some(marker);
+            if self.bytes.get(self.pos) != Some(&b'#') {
+                return Err(self.error("UBJSON container type must be followed by a count"));
+            }
+        }
+        let mut remaining = None;
+        if self.bytes.get(self.pos) == Some(&b'#') {
+            self.pos += 1;
+            let count = self.length()?;
+            let available = (self.bytes.len() - self.pos) as u64;
+            // Every element takes at least a byte, except in arrays of a type
+            // without a payload
+            let payload = object || !matches!(element, Some(b'Z' | b'T' | b'F'));
+            if payload && count > available {
+                return Err(self.error("UBJSON count exceeds the input"));
+            }
+            if !payload {
+                self.payloadless = self.payloadless.saturating_add(count);
+                if self.payloadless > MAX_PAYLOADLESS_ELEMENTS {
+                    return Err(self.error("UBJSON typed array count exceeds the limit"));
+                }
+            }
+            remaining = Some(count);
+        }
+        let capacity = remaining.map_or(0, |n| n.min(self.bytes.len() as u64) as usize);
+        stack.push(Frame {
+            remaining,
+            element,
+            object,
+            items: Vec::with_capacity(if object { 0 } else { capacity }),
+            entries: Vec::with_capacity(if object { capacity } else { 0 }),
+            key: None,
+        });
+        Ok(())
+    }
+
+    /// Reads one value; containers are kept on an explicit stack
+    fn read_value(&mut self) -> Result<DataValue<'a>> {
+        let mut stack: Vec<Frame<'a>> = Vec::new();
+        loop {
+            let value = match stack.last_mut() {
+                None => {
+                    self.skip_noops();
+                    let marker = self.byte()?;
+                    self.payload(marker, &mut stack)?
+                }
+                Some(frame) => {
+                    if frame.element.is_none() {
+                        self.skip_noops();
+                    }
+                    let end = match frame.remaining {
+                        Some(remaining) => remaining == 0,
+                        None => {
+                            let close = if frame.object { b'}' } else { b']' };
+                            if self.bytes.get(self.pos) == Some(&close) {
+                                self.pos += 1;
+                                true
+                            } else {
+                                false
+                            }
+                        }
+                    };
+                    if end {
+                        let frame = stack.pop().expect("frame is open");
+                        Some(if frame.object {
+                            DataValue::Object(self.arena.alloc_slice_fill_iter(frame.entries))
+                        } else {
+                            DataValue::Array(self.arena.alloc_slice_fill_iter(frame.items))
+                        })
+                    } else {
+                        if let Some(remaining) = &mut frame.remaining {
+                            *remaining -= 1;
+                        }
+                        let element = frame.element;
+                        if frame.object {
+                            frame.key = Some(self.string()?);
+                        }
+                        let marker = match element {
+                            Some(marker) => marker,
+                            None => {
+                                self.skip_noops();
+                                self.byte()?
+                            }
+                        };
+                        self.payload(marker, &mut stack)?
+                    }
+                }
+            };
+            let Some(value) = value else {
+                continue;
+            };
+
+            match stack.last_mut() {
+                None => return Ok(value),
+                Some(frame) if frame.object => {
+                    let key = frame.key.take().expect("key is read before its value");
+                    frame.entries.push((key, value));
+                }
+                Some(frame) => frame.items.push(value),
+            }
+        }
+    }
+}
+
+/// Writes a length or integer in the smallest type that holds it
+fn write_integer(output: &mut Vec<u8>, i: i64) {
+    if let Ok(i) = i8::try_from(i) {
+        output.extend([b'i', i as u8]);
+    } else if let Ok(i) = u8::try_from(i) {
+        output.extend([b'U', i]);
+    } else if let Ok(i) = i16::try_from(i) {
+        output.push(b'I');
+        output.extend(i.to_be_bytes());
+    } else if let Ok(i) = i32::try_from(i) {
+        output.push(b'l');
+        output.extend(i.to_be_bytes());
+    } else {
+        output.push(b'L');
+        output.extend(i.to_be_bytes());
+    }
+}
+
+/// Writes a string's length and bytes, without the `S` marker used for values
+fn write_string(output: &mut Vec<u8>, s: &str) {
+    write_integer(output, s.len() as i64);
+    output.extend_from_slice(s.as_bytes());
+}
+
+/// An array or object being written: its remaining elements, with keys for objects
+type Open<'v, 'a> = std::vec::IntoIter<(Option<&'a str>, &'v DataValue<'a>)>;
+
+/// Writes one value, keeping open containers on an explicit stack
+fn write_value(value: &DataValue<'_>, output: &mut Vec<u8>) -> Result<()> {
+    let mut stack: Vec<(u8, Open<'_, '_>)> = Vec::new();
+    let mut next = Some(value);
+    loop {
+        match next.take() {
+            Some(DataValue::Array(items)) => {
+                output.push(b'[');
+                let items: Vec<_> = items.iter().map(|item| (None, item)).collect();
+                stack.push((b']', items.into_iter()));
+            }
+            Some(DataValue::Object(entries)) => {
+                output.push(b'{');
+                let entries: Vec<_> = entries
+                    .iter()
+                    .map(|(key, value)| (Some(*key), value))
+                    .collect();
+                stack.push((b'}', entries.into_iter()));
+            }
+            Some(DataValue::Raw(text)) => {
+                let arena = Bump::new();
+                write_value(&DataValue::Raw(text).parse_raw(&arena)?, output)?;
+            }
+            Some(scalar) => write_scalar(scalar, output),
+            None => {}
+        }
+
+        let Some((close, open)) = stack.last_mut() else {
+            return Ok(());
+        };
+        match open.next() {
+            Some((key, item)) => {
+                if let Some(key) = key {
+                    write_string(output, key);
+                }
+                next = Some(item);
+            }
+            None => {
+                output.push(*close);
+                stack.pop();
+            }
+        }
+    }
+}
+
+fn write_scalar(value: &DataValue<'_>, output: &mut Vec<u8>) {
+    match value {
+        DataValue::Null => output.push(b'Z'),
+        DataValue::Bool(b) => output.push(if *b { b'T' } else { b'F' }),
+        DataValue::Number(Number::Integer(i)) => write_integer(output, *i),
+        DataValue::Number(Number::Float(f)) if !f.is_finite() => output.push(b'Z'),
+        DataValue::Number(Number::Float(f)) if f64::from(*f as f32) == *f => {
+            output.push(b'd');
+            output.extend((*f as f32).to_be_bytes());
+        }
+        DataValue::Number(Number::Float(f)) => {
+            output.push(b'D');
+            output.extend(f.to_be_bytes());
+        }
+        DataValue::Number(number) => {
+            // Raw numbers and big numbers keep their digits as text
+            let mut text = Vec::new();
+            write_json_scalar(&DataValue::Number(*number), &mut text)
+                .expect("writing to a Vec cannot fail");
+            output.push(b'H');
+            write_integer(output, text.len() as i64);
+            output.extend(text);
+        }
+        DataValue::String(s) => {
+            output.push(b'S');
+            write_string(output, s);
+        }
+        DataValue::DateTime(dt) => {
+            output.push(b'S');
+            write_string(output, &dt.to_rfc3339());
+        }
+        DataValue::Duration(dur) => {
+            output.push(b'S');
+            write_string(output, &format_duration(dur));
+        }
+        DataValue::Array(_) | DataValue::Object(_) | DataValue::Raw(_) => {
+            unreachable!("containers and raw values are written by write_value")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_str;
+
+    #[test]
+    fn test_decode() {
+        let arena = Bump::new();
+        let cases: [(&[u8], &str); 12] = [
+            (b"Z", "null"),
+            (b"NNT", "true"),
+            (b"i\x80", "-128"),
+            (b"I\x80\x00", "-32768"),
+            (b"L\x7F\xFF\xFF\xFF\xFF\xFF\xFF\xFF", "9223372036854775807"),
+            (b"D\x3F\xF1\x99\x99\x99\x99\x99\x9A", "1.1"),
+            (b"Ca", r#""a""#),
+            (b"[i\x01N[]Si\x00]", r#"[1,[],""]"#),
+            (b"{U\x01a{}i\x01b[$Z#i\x02}", r#"{"a":{},"b":[null,null]}"#),
+            (b"{#U\x02i\x01aTi\x01bF", r#"{"a":true,"b":false}"#),
+            (b"{$i#i\x02i\x01a\x01i\x01b\x02", r#"{"a":1,"b":2}"#),
+            (b"[#i\x01[$C#i\x01x", r#"[["x"]]"#),
+        ];
+        for (bytes, expected) in cases {
+            let value = from_slice(&arena, bytes).unwrap();
+            assert_eq!(value, from_str(&arena, expected).unwrap(), "{}", expected);
+        }
+
+        let big = from_slice(&arena, b"HU\x1412345678901234567890").unwrap();
+        assert!(matches!(big, DataValue::Number(Number::BigInt(_))));
+        let decimal = from_slice(&arena, b"Hi\x040.25").unwrap();
+        assert_eq!(decimal.as_f64(), Some(0.25));
+
+        for bad in [
+            &b""[..],
+            b"X",
+            b"i",
+            b"Si\x05ab",
+            b"Si\xFFab",
+            b"Si\x01\xFF",
+            b"C\xC3",
+            b"[i\x01",
+            b"{i\x01aZ",
+            b"{Z}",
+            b"[$i]",
+            b"[#L\x7F\xFF\xFF\xFF\xFF\xFF\xFF\xFF",
+            b"Hi\x03abc",
+            b"TT",
+            b"]",
+        ] {
+            assert!(from_slice(&arena, bad).is_err(), "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn test_payloadless_count_limit() {
+        let arena = Bump::new();
+        // 2^36 nulls in 13 bytes
+        let error = from_slice(&arena, b"[$Z#L\x00\x00\x00\x0f\xff\xff\xff\xff").unwrap_err();
+        assert!(error.to_string().contains("limit"), "{}", error);
+
+        // The limit applies to the total, not to each array
+        let mut nested = b"[#I\x02\x00".to_vec();
+        for _ in 0..512 {
+            nested.extend_from_slice(b"[$T#I\x09\x00");
+        }
+        assert!(from_slice(&arena, &nested).is_err());
+
+        let value = from_slice(&arena, b"[$F#I\x10\x00").unwrap();
+        assert_eq!(value.as_array().map(|a| a.len()), Some(4096));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let arena = Bump::new();
+        let options = ParseOptions {
+            number_mode: NumberMode::Arbitrary,
+            ..Default::default()
+        };
+        let text = r#"{
+            "ints": [0, 127, 128, 255, 256, -129, 32768, -2147483649, 9223372036854775807],
+            "big": [18446744073709551616, -1.23456789012345678901234567890, 1e400],
+            "floats": [1.5, -0.1, 1e300],
+            "misc": [null, true, false, "", "text ü", {}, [], [[{"": [1]}]]]
+        }"#;
+        let value = from_str_with_options(&arena, text, &options).unwrap();
+        let bytes = to_vec(&value).unwrap();
+        assert_eq!(from_slice(&arena, &bytes).unwrap(), value);
+
+        let raw = DataValue::Raw(r#"{"k": [1, 2]}"#);
+        assert_eq!(to_vec(&raw).unwrap(), b"{i\x01k[i\x01i\x02]}");
+        assert_eq!(
+            to_vec(&DataValue::Number(Number::Raw("1e999"))).unwrap(),
+            b"Hi\x051e999"
+        );
+        assert_eq!(
+            to_vec(&DataValue::Number(Number::Float(f64::NAN))).unwrap(),
+            b"Z"
+        );
+        let duration = DataValue::Duration(crate::helpers::parse_duration("PT1.5S").unwrap());
+        assert_eq!(to_vec(&duration).unwrap(), b"Si\x06PT1.5S");
+    }
+}
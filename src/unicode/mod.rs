@@ -0,0 +1,188 @@
+//! Unicode normalization (NFC and NFKC)
+//!
+//! Implements the normalization algorithm of UAX #15 on top of tables generated
+//! from the Unicode Character Database by `scripts/gen_unicode_tables.py`. Hangul
+//! syllables are decomposed and composed algorithmically.
+
+use std::borrow::Cow;
+
+mod tables;
+
+/// Unicode normalization form applied to parsed strings and keys
+///
+/// Different producers may encode the same text differently, e.g. `é` as the
+/// single code point U+00E9 or as `e` followed by U+0301. Normalizing makes
+/// such strings compare and look up as equal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NormalizationForm {
+    /// Canonical composition: only canonically equivalent forms are unified
+    Nfc,
+    /// Compatibility composition: additionally folds compatibility variants such as
+    /// ligatures (`ﬁ` becomes `fi`) and superscripts (`²` becomes `2`)
+    Nfkc,
+}
+
+const S_BASE: u32 = 0xAC00;
+const L_BASE: u32 = 0x1100;
+const V_BASE: u32 = 0x1161;
+const T_BASE: u32 = 0x11A7;
+const L_COUNT: u32 = 19;
+const V_COUNT: u32 = 21;
+const T_COUNT: u32 = 28;
+const N_COUNT: u32 = V_COUNT * T_COUNT;
+const S_COUNT: u32 = L_COUNT * N_COUNT;
+
+/// Normalizes `s`, borrowing it when it is already normalized
+pub(crate) fn normalize(s: &str, form: NormalizationForm) -> Cow<'_, str> {
+    // Code points below U+0300 are stable under NFC and never combine with a
+    // preceding character; ASCII is additionally stable under NFKC
+    let stable = match form {
+        NormalizationForm::Nfc => s.chars().all(|c| (c as u32) < 0x300),
+        NormalizationForm::Nfkc => s.is_ascii(),
+    };
+    if stable {
+        return Cow::Borrowed(s);
+    }
+
+    let mut chars: Vec<u32> = Vec::with_capacity(s.len());
+    for c in s.chars() {
+        decompose(c as u32, form == NormalizationForm::Nfkc, &mut chars);
+    }
+    reorder(&mut chars);
+    compose(&mut chars);
+
+    let normalized: String = chars.into_iter().filter_map(char::from_u32).collect();
+    if normalized == s {
+        Cow::Borrowed(s)
+    } else {
+        Cow::Owned(normalized)
+    }
+}
+
+fn decompose(c: u32, compatibility: bool, output: &mut Vec<u32>) {
+    if (S_BASE..S_BASE + S_COUNT).contains(&c) {
+        let index = c - S_BASE;
+        output.push(L_BASE + index / N_COUNT);
+        output.push(V_BASE + (index % N_COUNT) / T_COUNT);
+        if !index.is_multiple_of(T_COUNT) {
+            output.push(T_BASE + index % T_COUNT);
+        }
+        return;
+    }
+    let lookup = |table: &[(u32, &'static [u32])]| {
+        table
+            .binary_search_by_key(&c, |(k, _)| *k)
+            .ok()
+            .map(|i| table[i].1)
+    };
+    let decomposition = if compatibility {
+        lookup(tables::COMPATIBILITY_DECOMPOSITION)
+            .or_else(|| lookup(tables::CANONICAL_DECOMPOSITION))
+    } else {
+        lookup(tables::CANONICAL_DECOMPOSITION)
+    };
+    match decomposition {
+        Some(chars) => output.extend_from_slice(chars),
+        None => output.push(c),
+    }
+}
+
+fn combining_class(c: u32) -> u8 {
+    tables::COMBINING_CLASS
+        .binary_search_by_key(&c, |(k, _)| *k)
+        .map_or(0, |i| tables::COMBINING_CLASS[i].1)
+}
+
+/// Sorts each run of non-starters by combining class, keeping equal classes in order
+fn reorder(chars: &mut [u32]) {
+    let mut start = 0;
+    while start < chars.len() {
+        if combining_class(chars[start]) == 0 {
+            start += 1;
+            continue;
+        }
+        let mut end = start;
+        while end < chars.len() && combining_class(chars[end]) != 0 {
+            end += 1;
+        }
+        chars[start..end].sort_by_key(|&c| combining_class(c));
+        start = end;
+    }
+}
+
+fn compose_pair(first: u32, second: u32) -> Option<u32> {
+    if (L_BASE..L_BASE + L_COUNT).contains(&first) && (V_BASE..V_BASE + V_COUNT).contains(&second) {
+        return Some(S_BASE + ((first - L_BASE) * V_COUNT + (second - V_BASE)) * T_COUNT);
+    }
+    if (S_BASE..S_BASE + S_COUNT).contains(&first)
+        && (first - S_BASE).is_multiple_of(T_COUNT)
+        && (T_BASE + 1..T_BASE + T_COUNT).contains(&second)
+    {
+        return Some(first + (second - T_BASE));
+    }
+    tables::COMPOSITION
+        .binary_search_by(|(a, b, _)| (*a, *b).cmp(&(first, second)))
+        .ok()
+        .map(|i| tables::COMPOSITION[i].2)
+}
+
+/// Canonical composition of a decomposed, canonically ordered sequence
+fn compose(chars: &mut Vec<u32>) {
+    let mut output: Vec<u32> = Vec::with_capacity(chars.len());
+    let mut starter: Option<usize> = None;
+    // Combining class of the last character kept after the starter
+    let mut last_class: Option<u8> = None;
+    for &c in chars.iter() {
+        let class = combining_class(c);
+        if let Some(index) = starter {
+            let blocked = last_class.is_some_and(|last| last >= class);
+            if !blocked {
+                if let Some(composite) = compose_pair(output[index], c) {
+                    output[index] = composite;
+                    continue;
+                }
+            }
+        }
+        if class == 0 {
+            starter = Some(output.len());
+            last_class = None;
+        } else {
+            last_class = Some(class);
+        }
+        output.push(c);
+    }
+    *chars = output;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalization_forms() {
+        let cases = [
+            ("cafe\u{301}", "caf\u{e9}", "caf\u{e9}"),
+            ("a\u{302}\u{323}", "\u{1ead}", "\u{1ead}"),
+            ("a\u{323}\u{302}", "\u{1ead}", "\u{1ead}"),
+            ("\u{1100}\u{1161}\u{11a8}", "\u{ac01}", "\u{ac01}"),
+            ("\u{fb01}le x\u{b2}", "\u{fb01}le x\u{b2}", "file x2"),
+            ("\u{212b}", "\u{c5}", "\u{c5}"),
+            ("e\u{301}\u{301}", "\u{e9}\u{301}", "\u{e9}\u{301}"),
+            ("\u{301}e", "\u{301}e", "\u{301}e"),
+            ("\u{ff21}\u{ff22}", "\u{ff21}\u{ff22}", "AB"),
+        ];
+        for (input, nfc, nfkc) in cases {
+            assert_eq!(normalize(input, NormalizationForm::Nfc), nfc, "{:?}", input);
+            assert_eq!(
+                normalize(input, NormalizationForm::Nfkc),
+                nfkc,
+                "{:?}",
+                input
+            );
+        }
+        assert!(matches!(
+            normalize("caf\u{e9}", NormalizationForm::Nfc),
+            Cow::Borrowed(_)
+        ));
+    }
+}
@@ -0,0 +1,185 @@
+//! Unit conversion support for numeric fields
+//!
+//! This module defines the [`UnitConverter`] trait used by
+//! [`crate::operations::convert_units_with`] and a small built-in table covering
+//! time, byte and distance units.
+
+/// Converts a numeric quantity between two named units
+///
+/// Implement this trait to plug custom units (currencies, temperatures, domain
+/// specific scales) into [`crate::operations::convert_units_with`].
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::units::UnitConverter;
+///
+/// struct Temperature;
+///
+/// impl UnitConverter for Temperature {
+///     fn convert(&self, value: f64, from: &str, to: &str) -> Option<f64> {
+///         match (from, to) {
+///             ("C", "F") => Some(value * 9.0 / 5.0 + 32.0),
+///             ("F", "C") => Some((value - 32.0) * 5.0 / 9.0),
+///             (a, b) if a == b => Some(value),
+///             _ => None,
+///         }
+///     }
+/// }
+///
+/// assert_eq!(Temperature.convert(100.0, "C", "F"), Some(212.0));
+/// ```
+pub trait UnitConverter {
+    /// Converts `value` from unit `from` to unit `to`
+    ///
+    /// Returns None if either unit is unknown or the units are incompatible.
+    fn convert(&self, value: f64, from: &str, to: &str) -> Option<f64>;
+
+    /// Converts an integer `value` from unit `from` to unit `to` exactly
+    ///
+    /// Returns None when the result is not an integer or cannot be computed
+    /// exactly, in which case callers fall back to [`convert`](Self::convert).
+    /// The default implementation always returns None.
+    fn convert_integer(&self, value: i64, from: &str, to: &str) -> Option<i64> {
+        let _ = (value, from, to);
+        None
+    }
+}
+
+/// Physical dimension of a built-in unit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Dimension {
+    Time,
+    Bytes,
+    Distance,
+}
+
+/// Built-in units as (name, dimension, size in the smallest unit of the dimension)
+///
+/// Sizes are counted in nanoseconds, bytes and micrometres, so every factor is an
+/// exact integer and conversions between units are exact ratios.
+const UNITS: &[(&str, Dimension, u64)] = &[
+    ("ns", Dimension::Time, 1),
+    ("us", Dimension::Time, 1_000),
+    ("ms", Dimension::Time, 1_000_000),
+    ("s", Dimension::Time, 1_000_000_000),
+    ("min", Dimension::Time, 60_000_000_000),
+    ("h", Dimension::Time, 3_600_000_000_000),
+    ("d", Dimension::Time, 86_400_000_000_000),
+    ("B", Dimension::Bytes, 1),
+    ("KB", Dimension::Bytes, 1_000),
+    ("MB", Dimension::Bytes, 1_000_000),
+    ("GB", Dimension::Bytes, 1_000_000_000),
+    ("TB", Dimension::Bytes, 1_000_000_000_000),
+    ("KiB", Dimension::Bytes, 1 << 10),
+    ("MiB", Dimension::Bytes, 1 << 20),
+    ("GiB", Dimension::Bytes, 1 << 30),
+    ("TiB", Dimension::Bytes, 1 << 40),
+    ("mm", Dimension::Distance, 1_000),
+    ("cm", Dimension::Distance, 10_000),
+    ("m", Dimension::Distance, 1_000_000),
+    ("km", Dimension::Distance, 1_000_000_000),
+    ("in", Dimension::Distance, 25_400),
+    ("ft", Dimension::Distance, 304_800),
+    ("yd", Dimension::Distance, 914_400),
+    ("mi", Dimension::Distance, 1_609_344_000),
+];
+
+/// The built-in unit table
+///
+/// | Dimension | Units |
+/// |-----------|-------|
+/// | Time      | `ns`, `us`, `ms`, `s`, `min`, `h`, `d` |
+/// | Bytes     | `B`, `KB`, `MB`, `GB`, `TB` (powers of 1000), `KiB`, `MiB`, `GiB`, `TiB` (powers of 1024) |
+/// | Distance  | `mm`, `cm`, `m`, `km`, `in`, `ft`, `yd`, `mi` |
+///
+/// Unit names are case-sensitive. Conversions across dimensions return None.
+///
+/// # Example
+///
+/// ```
+/// use datavalue_rs::units::{BuiltinUnits, UnitConverter};
+///
+/// assert_eq!(BuiltinUnits.convert(1500.0, "ms", "s"), Some(1.5));
+/// assert_eq!(BuiltinUnits.convert(2.0, "KiB", "B"), Some(2048.0));
+/// assert_eq!(BuiltinUnits.convert(1.0, "km", "s"), None);
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BuiltinUnits;
+
+impl UnitConverter for BuiltinUnits {
+    fn convert(&self, value: f64, from: &str, to: &str) -> Option<f64> {
+        let (numerator, denominator) = ratio(from, to)?;
+        if numerator == denominator {
+            return Some(value);
+        }
+        Some(value * numerator as f64 / denominator as f64)
+    }
+
+    fn convert_integer(&self, value: i64, from: &str, to: &str) -> Option<i64> {
+        let (numerator, denominator) = ratio(from, to)?;
+        let scaled = i128::from(value) * i128::from(numerator);
+        if scaled % i128::from(denominator) != 0 {
+            return None;
+        }
+        i64::try_from(scaled / i128::from(denominator)).ok()
+    }
+}
+
+/// Returns the factor between two units of the same dimension as a reduced fraction
+fn ratio(from: &str, to: &str) -> Option<(u64, u64)> {
+    let (from_dim, from_size) = lookup(from)?;
+    let (to_dim, to_size) = lookup(to)?;
+    if from_dim != to_dim {
+        return None;
+    }
+    let divisor = gcd(from_size, to_size);
+    Some((from_size / divisor, to_size / divisor))
+}
+
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+fn lookup(unit: &str) -> Option<(Dimension, u64)> {
+    UNITS
+        .iter()
+        .find(|(name, _, _)| *name == unit)
+        .map(|(_, dim, size)| (*dim, *size))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_units() {
+        assert_eq!(BuiltinUnits.convert(2.0, "h", "min"), Some(120.0));
+        assert_eq!(BuiltinUnits.convert(3.0, "GB", "MB"), Some(3000.0));
+        assert_eq!(BuiltinUnits.convert(1.0, "mi", "ft"), Some(5280.0));
+        assert_eq!(BuiltinUnits.convert(7.0, "s", "s"), Some(7.0));
+        assert_eq!(BuiltinUnits.convert(1.0, "furlong", "m"), None);
+        assert_eq!(BuiltinUnits.convert(1.0, "MB", "m"), None);
+
+        // Scale conversions carry no rounding noise
+        assert_eq!(BuiltinUnits.convert(1.0, "ms", "us"), Some(1000.0));
+        assert_eq!(BuiltinUnits.convert(0.1, "s", "ms"), Some(100.0));
+        assert_eq!(BuiltinUnits.convert(1500.0, "us", "ms"), Some(1.5));
+    }
+
+    #[test]
+    fn test_builtin_units_integer() {
+        assert_eq!(BuiltinUnits.convert_integer(1, "ms", "us"), Some(1000));
+        assert_eq!(BuiltinUnits.convert_integer(3, "s", "ms"), Some(3000));
+        assert_eq!(BuiltinUnits.convert_integer(2000, "us", "ms"), Some(2));
+        assert_eq!(BuiltinUnits.convert_integer(1, "mi", "ft"), Some(5280));
+        // Inexact or out-of-range results are left to the float conversion
+        assert_eq!(BuiltinUnits.convert_integer(1500, "us", "ms"), None);
+        assert_eq!(BuiltinUnits.convert_integer(1, "in", "ft"), None);
+        assert_eq!(BuiltinUnits.convert_integer(i64::MAX, "d", "ns"), None);
+        assert_eq!(BuiltinUnits.convert_integer(1, "s", "B"), None);
+    }
+}